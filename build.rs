@@ -0,0 +1,18 @@
+// Writes `include/video_clip.h` for the C bindings when the `ffi` feature is on
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("set by cargo");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).expect("valid cbindgen.toml");
+        match cbindgen::generate_with_config(&crate_dir, config) {
+            Ok(bindings) => {
+                bindings.write_to_file(format!("{}/include/video_clip.h", crate_dir));
+            }
+            // Leave the existing header alone rather than failing the build
+            Err(e) => println!("cargo:warning=could not generate the C header: {}", e),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+#ifndef VIDEO_CLIP_H
+#define VIDEO_CLIP_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// C bindings
+// A small, stable C ABI over the library for Python (ctypes/cffi), Swift and
+// other non-Rust callers. Structured values cross the boundary as JSON
+// strings in the same shape as the CLI's `--json` output, so the ABI doesn't
+// change when a field is added. Every function returns a `VcStatus`; on
+// failure `vc_last_error` describes what went wrong on the calling thread.
+// Strings returned by the library must be released with `vc_string_free`.
+// `build.rs` writes the matching header to `include/video_clip.h`.
+typedef enum VcStatus {
+  VC_STATUS_OK = 0,
+  // A required pointer argument was null
+  VC_STATUS_NULL_ARGUMENT = 1,
+  // A string argument wasn't valid UTF-8
+  VC_STATUS_INVALID_UTF8 = 2,
+  // The request JSON didn't parse
+  VC_STATUS_INVALID_JSON = 3,
+  // The operation itself failed; see `vc_last_error`
+  VC_STATUS_FAILED = 4,
+  // An internal error (a panic) was caught at the boundary
+  VC_STATUS_PANIC = 5,
+} VcStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Clips the range described by `request_json` (a `ClipRequest` as JSON).
+// `output_dir` may be null for the default `downloads`. On success `*out_json`
+// receives the `ClipResult` as JSON.
+//
+// # Safety
+// `request_json` must be a valid NUL-terminated string, `output_dir` null or
+// one, and `out_json` a valid pointer.
+enum VcStatus vc_clip_video(const char *request_json, const char *output_dir, char **out_json);
+
+// Parses a time such as `"1:30"`, `"01:02:03.5"` or `"90"` into seconds
+//
+// # Safety
+// `time` must be a valid NUL-terminated string and `out_seconds` a valid pointer.
+enum VcStatus vc_parse_time(const char *time, double *out_seconds);
+
+// Probes a media file with ffprobe; `*out_json` receives the `MediaInfo` as JSON
+//
+// # Safety
+// `path` must be a valid NUL-terminated string and `out_json` a valid pointer.
+enum VcStatus vc_probe(const char *path, char **out_json);
+
+// The last error on this thread as a new string (free it with
+// `vc_string_free`), or null if the last call succeeded
+char *vc_last_error(void);
+
+// Releases a string returned by this library; null is ignored
+//
+// # Safety
+// `s` must be null or a string from this library that hasn't been freed yet.
+void vc_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* VIDEO_CLIP_H */
@@ -0,0 +1,84 @@
+//! Directories requests may name files in
+//! The MCP and REST servers take requests from callers that shouldn't reach
+//! every file the process can. With allowed directories set, the files a
+//! request names (sources, intros, overlays, output directories, ...) must
+//! be inside one of them. Requests from JSON never carry raw FFmpeg
+//! arguments, which could name files of their own.
+
+use crate::error::{VideoClipError, Result};
+use crate::video_clipper::ClipRequest;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct AllowedDirs {
+    /// Canonical directories requests may name files in; anywhere when empty
+    roots: Vec<PathBuf>,
+}
+
+impl AllowedDirs {
+    /// Allows files under `dirs`, which must exist; anywhere when `dirs` is
+    /// empty
+    pub fn new(dirs: &[PathBuf]) -> Result<Self> {
+        let roots = dirs.iter()
+            .map(|root| root.canonicalize().map_err(|_| VideoClipError::FileNotFound(root.display().to_string())))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { roots })
+    }
+
+    /// Whether every file is allowed
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Refuses `request` if it names a file outside the directories
+    pub fn check(&self, request: &ClipRequest) -> Result<()> {
+        if self.roots.is_empty() {
+            return Ok(());
+        }
+        for (field, path) in named_paths(request) {
+            if !resolve(Path::new(path)).is_some_and(|path| self.roots.iter().any(|root| path.starts_with(root))) {
+                return Err(VideoClipError::InvalidPath(format!("{} '{}' is outside the allowed directories", field, path)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every file or directory a request names, by field
+fn named_paths(request: &ClipRequest) -> Vec<(&'static str, &str)> {
+    let mut paths = vec![("input_file", request.input_file.as_str())];
+    let optional = [
+        ("output_dir", request.output_dir.as_deref()),
+        ("intro", request.intro.as_deref()),
+        ("outro", request.outro.as_deref()),
+        ("subtitles", request.subtitles.as_deref()),
+        ("mirror_root", request.mirror_root.as_deref()),
+        ("overlay_audio.path", request.overlay_audio.as_ref().map(|overlay| overlay.path.as_str())),
+        ("end_card.image", request.end_card.as_ref().map(|card| card.image.as_str())),
+        ("end_card.music", request.end_card.as_ref().and_then(|card| card.music.as_deref())),
+    ];
+    paths.extend(optional.into_iter().filter_map(|(field, path)| Some((field, path?))));
+    paths
+}
+
+/// `path` made absolute with links resolved, including one that doesn't
+/// exist yet (an output directory); `None` when it climbs with `..` past
+/// where it exists
+fn resolve(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    let name = path.file_name()?;
+    if path.components().any(|component| component == Component::ParentDir) {
+        return None;
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(resolve(parent)?.join(name))
+}
@@ -0,0 +1,379 @@
+use crate::error::{VideoClipError, Result};
+use crate::highlights::LoudnessSample;
+use crate::media_index::{IndexScanCommand, SourceStamp};
+use crate::plan::RangeAnalysis;
+use crate::probe::{Chapter, MediaInfo};
+use crate::ranges::TimeRange;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Staged source analysis
+/// Probing, keyframe scans, scene and silence detection and loudness
+/// measurement each read the whole source, and the longest take minutes on
+/// a multi-hour recording. The pipeline runs them as stages whose results
+/// are saved one by one in a per-source folder of the user cache directory,
+/// keyed by the source's size and modification time. A stage that has run
+/// for the current file is read back instead of run again, so a later clip,
+/// index or highlight pass on the same source only pays for the stages it
+/// hasn't had yet, and an interrupted run picks up at the first stage that
+/// didn't finish. Scenes and silences share one decode when both are due.
+/// What the stages find about one range goes into clip plans (see
+/// [`crate::plan`]).
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Probe,
+    Keyframes,
+    Chapters,
+    Scenes,
+    Silences,
+    Loudness,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [Stage::Probe, Stage::Keyframes, Stage::Chapters, Stage::Scenes, Stage::Silences, Stage::Loudness];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Probe => "probe",
+            Stage::Keyframes => "keyframes",
+            Stage::Chapters => "chapters",
+            Stage::Scenes => "scenes",
+            Stage::Silences => "silences",
+            Stage::Loudness => "loudness",
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Stage::ALL
+            .into_iter()
+            .find(|stage| stage.name() == s)
+            .ok_or_else(|| VideoClipError::InvalidOptions(format!("unknown analysis stage '{}'", s)))
+    }
+}
+
+/// A stage's saved result, with the source it was computed from
+#[derive(Serialize, Deserialize)]
+struct Artifact<T> {
+    source: SourceStamp,
+    data: T,
+}
+
+/// The saved stage results of one source
+#[derive(Debug, Clone)]
+pub struct StageStore {
+    dir: PathBuf,
+    stamp: SourceStamp,
+}
+
+impl StageStore {
+    /// Results kept in `dir`; `None` when the source can't be read
+    pub fn new(dir: impl AsRef<Path>, source: &Path) -> Option<Self> {
+        Some(Self { dir: dir.as_ref().to_path_buf(), stamp: SourceStamp::of(source)? })
+    }
+
+    /// `<user cache dir>/video-clip-rs/analysis/<source hash>`
+    pub fn for_source(source: &Path) -> Option<Self> {
+        let path = std::fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf());
+        let key = crate::probe_cache::cache_key(path.as_os_str().as_encoded_bytes());
+        let dir = dirs::cache_dir()?.join("video-clip-rs").join("analysis").join(key);
+        Self::new(dir, source)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, stage: Stage) -> PathBuf {
+        self.dir.join(format!("{}.json", stage.name()))
+    }
+
+    /// The saved result of `stage`, if it was computed from the source as it is now
+    pub fn load<T: DeserializeOwned>(&self, stage: Stage) -> Option<T> {
+        let artifact: Artifact<T> = serde_json::from_str(&std::fs::read_to_string(self.path(stage)).ok()?).ok()?;
+        (artifact.source == self.stamp).then_some(artifact.data)
+    }
+
+    pub fn save<T: Serialize>(&self, stage: Stage, data: &T) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let artifact = Artifact { source: self.stamp, data };
+        let json = serde_json::to_string(&artifact)
+            .map_err(|e| VideoClipError::InvalidOptions(format!("couldn't serialize the {} stage: {}", stage.name(), e)))?;
+        std::fs::write(self.path(stage), json)?;
+        Ok(())
+    }
+
+    /// Stages with a current saved result
+    pub fn completed(&self) -> Vec<Stage> {
+        Stage::ALL.into_iter().filter(|&stage| self.load::<serde_json::Value>(stage).is_some()).collect()
+    }
+
+    /// Removes every saved result, returning how many there were
+    pub fn clear(&self) -> Result<usize> {
+        let mut removed = 0;
+        for stage in Stage::ALL {
+            if std::fs::remove_file(self.path(stage)).is_ok() {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Everything the pipeline finds out about a source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Analysis {
+    pub info: MediaInfo,
+    pub keyframes: Vec<f64>,
+    pub chapters: Vec<Chapter>,
+    pub scenes: Vec<f64>,
+    pub silences: Vec<TimeRange>,
+    pub loudness: Vec<LoudnessSample>,
+}
+
+/// Analysis of one source; each stage runs at most once per source
+#[derive(Debug, Clone)]
+pub struct AnalysisPipeline {
+    source: PathBuf,
+    store: Option<StageStore>,
+    rerun: bool,
+    ran: Vec<Stage>,
+    /// Results run or read back so far, kept even when there's nowhere to
+    /// save them
+    results: HashMap<Stage, serde_json::Value>,
+}
+
+impl AnalysisPipeline {
+    /// A pipeline saving to the user cache directory, when there is one
+    pub fn new(source: impl AsRef<Path>) -> Self {
+        let source = source.as_ref().to_path_buf();
+        let store = StageStore::for_source(&source);
+        Self::with_store(source, store)
+    }
+
+    /// A pipeline saving to `store`, or nowhere
+    pub fn with_store(source: impl AsRef<Path>, store: Option<StageStore>) -> Self {
+        Self { source: source.as_ref().to_path_buf(), store, rerun: false, ran: Vec::new(), results: HashMap::new() }
+    }
+
+    /// Run every stage asked for again, replacing the saved results
+    pub fn set_rerun(&mut self, rerun: bool) {
+        self.rerun = rerun;
+    }
+
+    pub fn store(&self) -> Option<&StageStore> {
+        self.store.as_ref()
+    }
+
+    /// Stages that ran, rather than being read back, in order
+    pub fn ran(&self) -> &[Stage] {
+        &self.ran
+    }
+
+    /// The result of `stage` if it has one, without running anything
+    pub fn cached<T: DeserializeOwned>(&mut self, stage: Stage) -> Option<T> {
+        if !self.results.contains_key(&stage) {
+            if self.rerun {
+                return None;
+            }
+            let saved: serde_json::Value = self.store.as_ref()?.load(stage)?;
+            self.results.insert(stage, saved);
+        }
+        serde_json::from_value(self.results[&stage].clone()).ok()
+    }
+
+    fn record<T: Serialize>(&mut self, stage: Stage, data: &T) {
+        self.ran.push(stage);
+        if let Ok(value) = serde_json::to_value(data) {
+            self.results.insert(stage, value);
+        }
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save(stage, data) {
+                log::warn!("Could not save the {} stage of {}: {}", stage.name(), self.source.display(), e);
+            }
+        }
+    }
+
+    /// Runs `compute` unless `stage` has a saved result, saving what it returns
+    fn stage<T: Serialize + DeserializeOwned>(&mut self, stage: Stage, compute: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if let Some(data) = self.cached(stage) {
+            return Ok(data);
+        }
+        let data = compute(self)?;
+        self.record(stage, &data);
+        Ok(data)
+    }
+
+    pub fn probe(&mut self) -> Result<MediaInfo> {
+        self.stage(Stage::Probe, |p| crate::probe::probe(&p.source))
+    }
+
+    pub fn keyframes(&mut self) -> Result<Vec<f64>> {
+        self.stage(Stage::Keyframes, |p| match p.probe()?.video_stream() {
+            Some(_) => crate::probe::probe_all_keyframes(&p.source),
+            None => Ok(Vec::new()),
+        })
+    }
+
+    pub fn chapters(&mut self) -> Result<Vec<Chapter>> {
+        self.stage(Stage::Chapters, |p| crate::probe::probe_chapters(&p.source))
+    }
+
+    pub fn scenes(&mut self) -> Result<Vec<f64>> {
+        if let Some(scenes) = self.cached(Stage::Scenes) {
+            return Ok(scenes);
+        }
+        self.scan()?;
+        self.cached(Stage::Scenes).ok_or_else(|| self.unsaved(Stage::Scenes))
+    }
+
+    pub fn silences(&mut self) -> Result<Vec<TimeRange>> {
+        if let Some(silences) = self.cached(Stage::Silences) {
+            return Ok(silences);
+        }
+        self.scan()?;
+        self.cached(Stage::Silences).ok_or_else(|| self.unsaved(Stage::Silences))
+    }
+
+    pub fn loudness(&mut self) -> Result<Vec<LoudnessSample>> {
+        self.stage(Stage::Loudness, |p| match p.probe()?.audio_stream() {
+            Some(_) => crate::highlights::LoudnessCommand::new(&p.source, 0.0, None).execute(),
+            None => Ok(Vec::new()),
+        })
+    }
+
+    /// Runs `stages`, reading back the ones already done
+    pub fn run(&mut self, stages: &[Stage]) -> Result<()> {
+        for stage in stages {
+            match stage {
+                Stage::Probe => self.probe().map(drop)?,
+                Stage::Keyframes => self.keyframes().map(drop)?,
+                Stage::Chapters => self.chapters().map(drop)?,
+                Stage::Scenes => self.scenes().map(drop)?,
+                Stage::Silences => self.silences().map(drop)?,
+                Stage::Loudness => self.loudness().map(drop)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// One decode for whichever of scenes and silences aren't saved yet
+    fn scan(&mut self) -> Result<()> {
+        let info = self.probe()?;
+        let want_scenes = self.cached::<Vec<f64>>(Stage::Scenes).is_none();
+        let want_silences = self.cached::<Vec<TimeRange>>(Stage::Silences).is_none();
+        let mut command = IndexScanCommand::new(&self.source);
+        command.set_detect(want_scenes && info.video_stream().is_some(), want_silences && info.audio_stream().is_some());
+        let (silences, scenes) = match command.detects_anything() {
+            true => command.execute(info.duration)?,
+            false => (Vec::new(), Vec::new()),
+        };
+        if want_scenes {
+            self.record(Stage::Scenes, &scenes);
+        }
+        if want_silences {
+            self.record(Stage::Silences, &silences);
+        }
+        Ok(())
+    }
+
+    fn unsaved(&self, stage: Stage) -> VideoClipError {
+        VideoClipError::InvalidOptions(format!("the {} stage of {} ran but wasn't kept", stage.name(), self.source.display()))
+    }
+
+    /// Every stage, running the ones that aren't saved yet
+    pub fn analyze(&mut self) -> Result<Analysis> {
+        Ok(Analysis {
+            info: self.probe()?,
+            keyframes: self.keyframes()?,
+            chapters: self.chapters()?,
+            scenes: self.scenes()?,
+            silences: self.silences()?,
+            loudness: self.loudness()?,
+        })
+    }
+
+    /// What the analysis says about clipping `[start, end]`, running the
+    /// stages that aren't saved yet
+    pub fn range(&mut self, start: f64, end: f64) -> Result<RangeAnalysis> {
+        crate::time_parser::TimeParser::validate_time_range(start, end)?;
+        Ok(RangeAnalysis::from_analysis(start, end, &self.analyze()?))
+    }
+
+    /// The same, only when every stage is saved already, so nothing runs
+    pub fn saved_range(&mut self, start: f64, end: f64) -> Option<RangeAnalysis> {
+        let analysis = Analysis {
+            info: self.cached(Stage::Probe)?,
+            keyframes: self.cached(Stage::Keyframes)?,
+            chapters: self.cached(Stage::Chapters)?,
+            scenes: self.cached(Stage::Scenes)?,
+            silences: self.cached(Stage::Silences)?,
+            loudness: self.cached(Stage::Loudness)?,
+        };
+        Some(RangeAnalysis::from_analysis(start, end, &analysis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PathBuf, StageStore) {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("talk.mp4");
+        std::fs::write(&source, b"video").unwrap();
+        let store = StageStore::new(dir.path().join("stages"), &source).unwrap();
+        (dir, source, store)
+    }
+
+    #[test]
+    fn test_stage_names() {
+        for stage in Stage::ALL {
+            assert_eq!(stage.name().parse::<Stage>().unwrap(), stage);
+        }
+        assert!("motion".parse::<Stage>().is_err());
+    }
+
+    #[test]
+    fn test_saved_stages_follow_the_source() {
+        let (_dir, source, store) = setup();
+        assert!(store.completed().is_empty());
+        store.save(Stage::Keyframes, &vec![0.0, 2.0]).unwrap();
+        assert_eq!(store.load::<Vec<f64>>(Stage::Keyframes), Some(vec![0.0, 2.0]));
+        assert_eq!(store.completed(), vec![Stage::Keyframes]);
+
+        std::fs::write(&source, b"a re-exported video").unwrap();
+        let store = StageStore::new(store.dir(), &source).unwrap();
+        assert_eq!(store.load::<Vec<f64>>(Stage::Keyframes), None);
+        assert_eq!(store.clear().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_saved_stages_are_not_run_again() {
+        let (_dir, source, store) = setup();
+        let info = MediaInfo { format_name: "mov,mp4".to_string(), duration: Some(120.0), ..Default::default() };
+        store.save(Stage::Probe, &info).unwrap();
+        store.save(Stage::Keyframes, &vec![0.0, 2.0, 4.0]).unwrap();
+        store.save(Stage::Scenes, &vec![30.0]).unwrap();
+        store.save(Stage::Silences, &Vec::<TimeRange>::new()).unwrap();
+
+        let mut pipeline = AnalysisPipeline::with_store(&source, Some(store.clone()));
+        assert_eq!(pipeline.probe().unwrap(), info);
+        assert_eq!(pipeline.keyframes().unwrap(), vec![0.0, 2.0, 4.0]);
+        pipeline.run(&[Stage::Scenes, Stage::Silences]).unwrap();
+        assert!(pipeline.ran().is_empty());
+
+        let mut rerun = AnalysisPipeline::with_store(&source, Some(store));
+        rerun.set_rerun(true);
+        assert_eq!(rerun.cached::<Vec<f64>>(Stage::Keyframes), None);
+    }
+}
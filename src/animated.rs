@@ -0,0 +1,374 @@
+use crate::capabilities::FfmpegCapabilities;
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Animated image export
+/// Renders a range as a looping GIF, animated WebP or AVIF for web embeds;
+/// WebP and AVIF are typically a fraction of the GIF's size but depend on
+/// optional encoders, so they're checked against the FFmpeg build first
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimatedFormat {
+    Gif,
+    #[default]
+    Webp,
+    Avif,
+}
+
+impl AnimatedFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimatedFormat::Gif => "gif",
+            AnimatedFormat::Webp => "webp",
+            AnimatedFormat::Avif => "avif",
+        }
+    }
+
+    /// Encoders to try, best first
+    pub fn encoder_candidates(&self) -> &'static [&'static str] {
+        match self {
+            AnimatedFormat::Gif => &["gif"],
+            AnimatedFormat::Webp => &["libwebp_anim", "libwebp"],
+            AnimatedFormat::Avif => &["libaom-av1", "libsvtav1"],
+        }
+    }
+
+    /// Best encoder in `capabilities` for this format
+    pub fn select_encoder(&self, capabilities: &FfmpegCapabilities) -> Result<&'static str> {
+        if *self == AnimatedFormat::Avif {
+            // The AVIF muxer landed in FFmpeg 5.1
+            capabilities.require_version(5, 1, "animated AVIF export")?;
+        }
+
+        let candidates = self.encoder_candidates();
+        candidates
+            .iter()
+            .copied()
+            .find(|name| capabilities.has_encoder(name))
+            .ok_or_else(|| VideoClipError::UnsupportedByFfmpegBuild {
+                feature: format!("animated {} export", self.extension().to_uppercase()),
+                reason: format!("none of {} are available", candidates.join(", ")),
+            })
+    }
+
+    /// Detects the local FFmpeg build (once per process) and picks an encoder
+    #[cfg(not(feature = "wasm"))]
+    pub fn resolve_encoder(&self) -> Result<&'static str> {
+        self.select_encoder(FfmpegCapabilities::cached()?)
+    }
+
+    /// ffmpeg.wasm builds can't be queried; assume the first candidate
+    #[cfg(feature = "wasm")]
+    pub fn resolve_encoder(&self) -> Result<&'static str> {
+        Ok(self.encoder_candidates()[0])
+    }
+}
+
+impl FromStr for AnimatedFormat {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "gif" => Ok(AnimatedFormat::Gif),
+            "webp" => Ok(AnimatedFormat::Webp),
+            "avif" => Ok(AnimatedFormat::Avif),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown animated format '{}'", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnimatedOptions {
+    #[serde(default)]
+    pub format: AnimatedFormat,
+    #[serde(default = "default_fps")]
+    pub fps: f64,
+    /// Output width in pixels; height follows the aspect ratio
+    #[serde(default = "default_width")]
+    pub width: u32,
+    /// 0 (smallest) to 100 (best); ignored for GIF
+    #[serde(default = "default_quality")]
+    pub quality: u32,
+    /// Times to play after the first; 0 loops forever
+    #[serde(default)]
+    pub loop_count: u32,
+}
+
+fn default_fps() -> f64 {
+    15.0
+}
+
+fn default_width() -> u32 {
+    480
+}
+
+fn default_quality() -> u32 {
+    75
+}
+
+impl Default for AnimatedOptions {
+    fn default() -> Self {
+        Self {
+            format: AnimatedFormat::default(),
+            fps: default_fps(),
+            width: default_width(),
+            quality: default_quality(),
+            loop_count: 0,
+        }
+    }
+}
+
+impl AnimatedOptions {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.fps.is_finite() && self.fps > 0.0) {
+            return Err(VideoClipError::InvalidOptions(format!("animation fps must be positive, got {}", self.fps)));
+        }
+        if self.width == 0 {
+            return Err(VideoClipError::InvalidOptions("animation width must be non-zero".to_string()));
+        }
+        if self.quality > 100 {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "animation quality must be between 0 and 100, got {}",
+                self.quality
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnimatedResult {
+    pub input_file: String,
+    pub output_file: String,
+    pub format: AnimatedFormat,
+    pub encoder: String,
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    pub file_size_mb: Option<f64>,
+    pub command: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimatedCommand {
+    input: PathBuf,
+    output: PathBuf,
+    start_time: f64,
+    duration: f64,
+    options: AnimatedOptions,
+    encoder: &'static str,
+}
+
+impl AnimatedCommand {
+    pub fn new(input: impl AsRef<Path>, output: impl AsRef<Path>, start_time: f64, duration: f64, options: AnimatedOptions) -> Self {
+        Self {
+            input: input.as_ref().to_path_buf(),
+            output: output.as_ref().to_path_buf(),
+            start_time,
+            duration,
+            encoder: options.format.encoder_candidates()[0],
+            options,
+        }
+    }
+
+    pub fn set_encoder(&mut self, encoder: &'static str) {
+        self.encoder = encoder;
+    }
+
+    pub fn encoder(&self) -> &'static str {
+        self.encoder
+    }
+
+    fn filter_graph(&self) -> FilterGraph {
+        let mut graph: FilterGraph = [
+            Filter::new("fps").arg(self.options.fps),
+            Filter::new("scale").arg(self.options.width).arg(-2).option("flags", "lanczos"),
+        ].into_iter().collect();
+
+        match self.options.format {
+            AnimatedFormat::Gif => {
+                // A palette generated from the clip itself beats GIF's default 256 colors
+                let mut palette = FilterGraph::new();
+                palette.add_chain(&[], vec![Filter::new("split").arg(2)], &["frames", "source"]);
+                palette.add_chain(&["source"], vec![Filter::new("palettegen").option("stats_mode", "diff")], &["palette"]);
+                palette.add_chain(
+                    &["frames", "palette"],
+                    vec![Filter::new("paletteuse").option("dither", "bayer").option("bayer_scale", 5)],
+                    &[],
+                );
+                graph.append(palette);
+            }
+            AnimatedFormat::Webp => graph.push(Filter::new("format").arg("yuva420p")),
+            AnimatedFormat::Avif => graph.push(Filter::new("format").arg("yuv420p")),
+        }
+        graph
+    }
+
+    /// Quality settings for the selected encoder
+    fn encoder_args(&self) -> Vec<String> {
+        let quality = self.options.quality.min(100);
+        // AV1 CRF runs 63 (worst) to 0 (best)
+        let crf = ((100 - quality) as f64 * 63.0 / 100.0).round() as u32;
+
+        let args: Vec<String> = match self.encoder {
+            "libwebp_anim" | "libwebp" => vec![
+                "-quality".into(), quality.to_string(),
+                "-compression_level".into(), "4".into(),
+                "-lossless".into(), "0".into(),
+            ],
+            "libaom-av1" => vec![
+                "-crf".into(), crf.to_string(),
+                "-b:v".into(), "0".into(),
+                "-cpu-used".into(), "6".into(),
+            ],
+            "libsvtav1" => vec![
+                "-crf".into(), crf.to_string(),
+                "-preset".into(), "8".into(),
+            ],
+            _ => Vec::new(),
+        };
+
+        let mut out: Vec<String> = vec!["-c:v".into(), self.encoder.into()];
+        out.extend(args);
+        out
+    }
+
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-ss".into(), self.start_time.to_string(),
+            "-i".into(), self.input.display().to_string(),
+            "-t".into(), self.duration.to_string(),
+            "-map".into(), "0:v:0".into(),
+        ];
+
+        args.extend(self.filter_graph().to_args());
+        args.extend(self.encoder_args());
+        args.extend(["-loop".into(), self.options.loop_count.to_string()]);
+
+        if self.options.format == AnimatedFormat::Avif {
+            args.extend(["-f".into(), "avif".into()]);
+        }
+
+        args.extend(["-an".into(), "-y".into(), self.output.display().to_string()]);
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<()> {
+        self.options.validate()?;
+        crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Animated export failed")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn capabilities(version: &str, encoders: &[&str]) -> FfmpegCapabilities {
+        FfmpegCapabilities {
+            version: crate::capabilities::FfmpegVersion::parse(&format!("ffmpeg version {} Copyright", version)).unwrap(),
+            enabled_libs: BTreeSet::new(),
+            encoders: encoders.iter().map(|e| e.to_string()).collect(),
+            filters: BTreeSet::new(),
+        }
+    }
+
+    mod encoder_selection_tests {
+        use super::*;
+
+        #[test]
+        fn test_prefers_animated_webp_encoder() {
+            let caps = capabilities("6.1", &["libwebp", "libwebp_anim", "gif"]);
+            assert_eq!(AnimatedFormat::Webp.select_encoder(&caps).unwrap(), "libwebp_anim");
+            assert_eq!(AnimatedFormat::Gif.select_encoder(&caps).unwrap(), "gif");
+        }
+
+        #[test]
+        fn test_missing_encoder_is_reported() {
+            let caps = capabilities("6.1", &["gif", "libx264"]);
+            let err = AnimatedFormat::Webp.select_encoder(&caps).unwrap_err();
+            assert!(matches!(err, VideoClipError::UnsupportedByFfmpegBuild { .. }));
+            assert!(err.to_string().contains("animated WEBP export"));
+        }
+
+        #[test]
+        fn test_avif_needs_recent_ffmpeg() {
+            let old = capabilities("4.4", &["libaom-av1"]);
+            assert!(AnimatedFormat::Avif.select_encoder(&old).unwrap_err().to_string().contains("5.1"));
+
+            let recent = capabilities("6.0", &["libsvtav1"]);
+            assert_eq!(AnimatedFormat::Avif.select_encoder(&recent).unwrap(), "libsvtav1");
+        }
+    }
+
+    mod command_building_tests {
+        use super::*;
+
+        #[test]
+        fn test_webp_command() {
+            let cmd = AnimatedCommand::new("talk.mp4", "talk.webp", 30.0, 5.0, AnimatedOptions::default());
+            assert_eq!(
+                cmd.get_command_string(),
+                "ffmpeg -ss 30 -i talk.mp4 -t 5 -map 0:v:0 -vf fps=15,scale=480:-2:flags=lanczos,format=yuva420p \
+                 -c:v libwebp_anim -quality 75 -compression_level 4 -lossless 0 -loop 0 -an -y talk.webp"
+            );
+        }
+
+        #[test]
+        fn test_gif_uses_generated_palette() {
+            let options = AnimatedOptions { format: AnimatedFormat::Gif, loop_count: 2, ..Default::default() };
+            let cmd_string = AnimatedCommand::new("talk.mp4", "talk.gif", 0.0, 3.0, options).get_command_string();
+            assert!(cmd_string.contains(
+                "-vf fps=15,scale=480:-2:flags=lanczos,split=2[frames][source];[source]palettegen=stats_mode=diff[palette];\
+                 [frames][palette]paletteuse=dither=bayer:bayer_scale=5"
+            ));
+            assert!(cmd_string.contains("-c:v gif -loop 2"));
+        }
+
+        #[test]
+        fn test_avif_quality_maps_to_crf() {
+            let options = AnimatedOptions { format: AnimatedFormat::Avif, quality: 100, ..Default::default() };
+            let mut cmd = AnimatedCommand::new("talk.mp4", "talk.avif", 0.0, 3.0, options);
+            assert!(cmd.get_command_string().contains("-c:v libaom-av1 -crf 0 -b:v 0 -cpu-used 6 -loop 0 -f avif"));
+
+            cmd.set_encoder("libsvtav1");
+            cmd.options.quality = 0;
+            assert!(cmd.get_command_string().contains("-c:v libsvtav1 -crf 63 -preset 8"));
+        }
+    }
+
+    mod option_tests {
+        use super::*;
+
+        #[test]
+        fn test_options_deserialize_with_defaults() {
+            let options: AnimatedOptions = serde_json::from_str(r#"{"format": "avif"}"#).unwrap();
+            assert_eq!(options.format, AnimatedFormat::Avif);
+            assert_eq!(options.fps, 15.0);
+            assert_eq!(options.width, 480);
+            assert_eq!(options.quality, 75);
+        }
+
+        #[test]
+        fn test_invalid_options() {
+            assert!(AnimatedOptions { quality: 101, ..Default::default() }.validate().is_err());
+            assert!(AnimatedOptions { fps: 0.0, ..Default::default() }.validate().is_err());
+            assert!(AnimatedOptions { width: 0, ..Default::default() }.validate().is_err());
+            assert!("apng".parse::<AnimatedFormat>().is_err());
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use crate::error::{VideoClipError, Result};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+#[cfg(feature = "server")]
+use base64::Engine;
+#[cfg(feature = "server")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "server")]
+use schemars::JsonSchema;
+#[cfg(feature = "server")]
+use sha2::Sha256;
+#[cfg(feature = "server")]
+use std::collections::HashMap;
+#[cfg(feature = "server")]
+use std::sync::Mutex;
+#[cfg(feature = "server")]
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Server authentication
+/// With API keys or a JWT secret set, the server only answers requests that
+/// send `Authorization: Bearer <token>`, the token being one of the keys or
+/// an HS256 JWT signed with the secret and carrying an `exp`, so no token
+/// is good forever; `GET /health` stays open for load balancers. Each key,
+/// and each JWT subject, may make `rate_limit` requests a minute (a key's
+/// own limit first) and is refused with 429 after that. Only keys marked
+/// `high_priority` may queue `"priority": "high"` jobs.
+///
+/// With a `url_secret`, finished clips can also be shared as signed links
+/// that work without a token until they expire, for `<video>` elements and
+/// other clients that can't send headers.
+///
+/// ```toml
+/// [auth]
+/// jwt_secret = "..."
+/// rate_limit = 60
+/// url_secret = "..."
+///
+/// [[auth.keys]]
+/// name = "ci"
+/// key = "..."
+/// rate_limit = 600
+/// high_priority = true
+/// ```
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub keys: Vec<ApiKey>,
+    /// Secret HS256 tokens are signed with
+    #[serde(default)]
+    pub jwt_secret: Option<String>,
+    /// Requests a minute for each key or JWT subject; unlimited when unset
+    #[serde(default)]
+    pub rate_limit: Option<u32>,
+    /// Secret download links are signed with; links are off when unset
+    #[serde(default)]
+    pub url_secret: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// Stands for the key in logs and rate limits
+    pub name: String,
+    pub key: String,
+    /// Overrides the shared rate limit
+    #[serde(default)]
+    pub rate_limit: Option<u32>,
+    /// May queue high-priority jobs
+    #[serde(default)]
+    pub high_priority: bool,
+}
+
+impl AuthConfig {
+    /// Whether requests need a token
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty() || self.jwt_secret.is_some()
+    }
+
+    /// These settings, with `under`'s wherever these have none
+    pub fn or(self, under: AuthConfig) -> AuthConfig {
+        Self {
+            keys: match self.keys.is_empty() {
+                true => under.keys,
+                false => self.keys,
+            },
+            jwt_secret: self.jwt_secret.or(under.jwt_secret),
+            rate_limit: self.rate_limit.or(under.rate_limit),
+            url_secret: self.url_secret.or(under.url_secret),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(VideoClipError::InvalidOptions(message));
+        for (i, key) in self.keys.iter().enumerate() {
+            if key.key.trim().is_empty() {
+                return invalid(format!("API key '{}' is empty", key.name));
+            }
+            if self.keys[..i].iter().any(|earlier| earlier.name == key.name) {
+                return invalid(format!("API key name '{}' is used twice", key.name));
+            }
+        }
+        if self.jwt_secret.as_deref().is_some_and(|secret| secret.is_empty()) {
+            return invalid("the JWT secret is empty".to_string());
+        }
+        if self.url_secret.as_deref().is_some_and(|secret| secret.is_empty()) {
+            return invalid("the URL signing secret is empty".to_string());
+        }
+        if self.rate_limit == Some(0) || self.keys.iter().any(|key| key.rate_limit == Some(0)) {
+            return invalid("rate limits must be at least one request a minute".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Why a request was turned away
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    /// No bearer token, or one that isn't a key or a valid JWT
+    Unauthorized(String),
+    /// The caller has used up this minute's requests
+    RateLimited { retry_after: Duration },
+}
+
+/// Who a request came from; the auth middleware adds it to each request it
+/// lets through
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    /// `key:<name>` or `jwt:<subject>`
+    pub name: String,
+    /// Whether the caller may queue high-priority jobs
+    pub high_priority: bool,
+}
+
+/// Checks tokens and counts each caller's requests
+#[cfg(feature = "server")]
+#[derive(Debug)]
+pub struct Authenticator {
+    config: AuthConfig,
+    /// Start of each caller's current minute, and its requests in it
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+#[cfg(feature = "server")]
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+#[cfg(feature = "server")]
+impl Authenticator {
+    pub fn new(config: AuthConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self { config, windows: Mutex::new(HashMap::new()) })
+    }
+
+    /// The caller an `Authorization` header identifies, counting the request
+    /// against its rate limit
+    pub fn authenticate(&self, authorization: Option<&str>) -> std::result::Result<Caller, AuthError> {
+        self.authenticate_at(authorization, unix_now(), Instant::now())
+    }
+
+    fn authenticate_at(&self, authorization: Option<&str>, unix_now: u64, now: Instant) -> std::result::Result<Caller, AuthError> {
+        let token = authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| AuthError::Unauthorized("a bearer token is required".to_string()))?;
+        let key = self.config.keys.iter().find(|key| constant_time_eq(key.key.as_bytes(), token.as_bytes()));
+        let (caller, limit) = match (key, &self.config.jwt_secret) {
+            (Some(key), _) => (
+                Caller { name: format!("key:{}", key.name), high_priority: key.high_priority },
+                key.rate_limit.or(self.config.rate_limit),
+            ),
+            (None, Some(secret)) => (
+                Caller { name: format!("jwt:{}", verify_jwt(token, secret, unix_now)?), high_priority: false },
+                self.config.rate_limit,
+            ),
+            (None, None) => return Err(AuthError::Unauthorized("unknown API key".to_string())),
+        };
+        if let Some(limit) = limit {
+            self.admit(&caller.name, limit, now)?;
+        }
+        Ok(caller)
+    }
+
+    fn admit(&self, caller: &str, limit: u32, now: Instant) -> std::result::Result<(), AuthError> {
+        let mut windows = self.windows.lock().unwrap();
+        let (started, count) = windows.entry(caller.to_string()).or_insert((now, 0));
+        if now.duration_since(*started) >= RATE_WINDOW {
+            *started = now;
+            *count = 0;
+        }
+        if *count >= limit {
+            return Err(AuthError::RateLimited { retry_after: RATE_WINDOW - now.duration_since(*started) });
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// A link that works without a token until `expires_at`
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SignedUrl {
+    /// Path and query, relative to the server
+    pub url: String,
+    /// Unix seconds
+    pub expires_at: u64,
+}
+
+/// Signs paths with an expiry, and checks the signatures links come back with
+#[cfg(feature = "server")]
+#[derive(Debug, Clone)]
+pub struct UrlSigner {
+    secret: String,
+}
+
+#[cfg(feature = "server")]
+impl UrlSigner {
+    pub fn new(secret: &str) -> Result<Self> {
+        match secret.is_empty() {
+            true => Err(VideoClipError::InvalidOptions("the URL signing secret is empty".to_string())),
+            false => Ok(Self { secret: secret.to_string() }),
+        }
+    }
+
+    /// `path` with `expires` and `signature` query parameters, good for `ttl`
+    pub fn sign(&self, path: &str, ttl: Duration) -> SignedUrl {
+        let expires_at = unix_now() + ttl.as_secs();
+        let signature = hex::encode(self.mac(path, expires_at).finalize().into_bytes());
+        SignedUrl { url: format!("{}?expires={}&signature={}", path, expires_at, signature), expires_at }
+    }
+
+    /// Whether `signature` is this signer's for `path` and `expires`, and
+    /// that hasn't passed
+    pub fn verify(&self, path: &str, expires: u64, signature: &str) -> bool {
+        self.verify_at(path, expires, signature, unix_now())
+    }
+
+    fn verify_at(&self, path: &str, expires: u64, signature: &str, now: u64) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        now < expires && self.mac(path, expires).verify_slice(&signature).is_ok()
+    }
+
+    fn mac(&self, path: &str, expires: u64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}\n{}", path, expires).as_bytes());
+        mac
+    }
+}
+
+#[cfg(feature = "server")]
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+/// The subject of an HS256 JWT signed with `secret` that's valid at `now`;
+/// tokens without an expiry are refused
+#[cfg(feature = "server")]
+fn verify_jwt(token: &str, secret: &str, now: u64) -> std::result::Result<String, AuthError> {
+    let invalid = |reason: &str| AuthError::Unauthorized(format!("invalid token: {}", reason));
+    let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| invalid("not base64url"));
+    let json = |part: &str| serde_json::from_slice::<serde_json::Value>(&decode(part)?).map_err(|_| invalid("not JSON"));
+
+    let [header, claims, signature] = token.split('.').collect::<Vec<_>>()[..] else {
+        return Err(invalid("not a JWT"));
+    };
+    if json(header)?["alg"] != "HS256" {
+        return Err(invalid("only HS256 is accepted"));
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", header, claims).as_bytes());
+    mac.verify_slice(&decode(signature)?).map_err(|_| invalid("bad signature"))?;
+
+    let claims = json(claims)?;
+    match claims["exp"].as_u64() {
+        None => return Err(invalid("no expiry (exp)")),
+        Some(exp) if now >= exp => return Err(invalid("expired")),
+        Some(_) => {}
+    }
+    if claims["nbf"].as_u64().is_some_and(|nbf| now < nbf) {
+        return Err(invalid("not valid yet"));
+    }
+    Ok(claims["sub"].as_str().unwrap_or_default().to_string())
+}
+
+/// Compares without returning early, so timing doesn't reveal how much of a key matched
+#[cfg(feature = "server")]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, key: &str, rate_limit: Option<u32>) -> ApiKey {
+        ApiKey { name: name.to_string(), key: key.to_string(), rate_limit, high_priority: false }
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(AuthConfig::default().validate().is_ok());
+        assert!(!AuthConfig::default().is_enabled());
+        let twice = AuthConfig { keys: vec![key("ci", "a", None), key("ci", "b", None)], ..Default::default() };
+        assert!(twice.validate().is_err());
+        assert!(AuthConfig { keys: vec![key("ci", " ", None)], ..Default::default() }.validate().is_err());
+        assert!(AuthConfig { rate_limit: Some(0), ..Default::default() }.validate().is_err());
+    }
+
+    #[cfg(feature = "server")]
+    fn jwt(secret: &str, claims: serde_json::Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.{}", header, claims).as_bytes());
+        format!("{}.{}.{}", header, claims, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_keys_and_tokens() {
+        let auth = Authenticator::new(AuthConfig {
+            keys: vec![key("ci", "k-123", None), ApiKey { high_priority: true, ..key("ops", "k-456", None) }],
+            jwt_secret: Some("s3cret".to_string()),
+            ..Default::default()
+        }).unwrap();
+        let now = Instant::now();
+        let caller = |name: &str, high_priority| Ok(Caller { name: name.to_string(), high_priority });
+        assert_eq!(auth.authenticate_at(Some("Bearer k-123"), 1_000, now), caller("key:ci", false));
+        assert_eq!(auth.authenticate_at(Some("Bearer k-456"), 1_000, now), caller("key:ops", true));
+        assert!(matches!(auth.authenticate_at(None, 1_000, now), Err(AuthError::Unauthorized(_))));
+        assert!(matches!(auth.authenticate_at(Some("Bearer k-124"), 1_000, now), Err(AuthError::Unauthorized(_))));
+
+        let token = jwt("s3cret", serde_json::json!({ "sub": "render-farm", "exp": 2_000 }));
+        assert_eq!(auth.authenticate_at(Some(&format!("Bearer {}", token)), 1_000, now), caller("jwt:render-farm", false));
+        assert!(auth.authenticate_at(Some(&format!("Bearer {}", token)), 2_000, now).is_err());
+        let forged = jwt("guess", serde_json::json!({ "sub": "render-farm", "exp": 2_000 }));
+        assert!(auth.authenticate_at(Some(&format!("Bearer {}", forged)), 1_000, now).is_err());
+        let forever = jwt("s3cret", serde_json::json!({ "sub": "render-farm" }));
+        assert_eq!(
+            auth.authenticate_at(Some(&format!("Bearer {}", forever)), 1_000, now),
+            Err(AuthError::Unauthorized("invalid token: no expiry (exp)".to_string()))
+        );
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_rate_limit_per_key() {
+        let auth = Authenticator::new(AuthConfig {
+            keys: vec![key("ci", "a", Some(2)), key("dashboard", "b", None)],
+            rate_limit: Some(1),
+            ..Default::default()
+        }).unwrap();
+        let start = Instant::now();
+        assert!(auth.authenticate_at(Some("Bearer a"), 0, start).is_ok());
+        assert!(auth.authenticate_at(Some("Bearer a"), 0, start).is_ok());
+        let limited = auth.authenticate_at(Some("Bearer a"), 0, start + Duration::from_secs(20));
+        assert_eq!(limited, Err(AuthError::RateLimited { retry_after: Duration::from_secs(40) }));
+        // Keys are counted separately, and the count starts over each minute
+        assert!(auth.authenticate_at(Some("Bearer b"), 0, start).is_ok());
+        assert!(auth.authenticate_at(Some("Bearer b"), 0, start).is_err());
+        assert!(auth.authenticate_at(Some("Bearer a"), 0, start + RATE_WINDOW).is_ok());
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_signed_urls() {
+        let signer = UrlSigner::new("links").unwrap();
+        let link = signer.sign("/files/7", Duration::from_secs(60));
+        let signature = link.url.rsplit_once("signature=").unwrap().1;
+        assert!(link.url.starts_with(&format!("/files/7?expires={}&", link.expires_at)));
+        assert!(signer.verify("/files/7", link.expires_at, signature));
+        assert!(!signer.verify("/files/8", link.expires_at, signature));
+        assert!(!signer.verify("/files/7", link.expires_at + 1, signature));
+        assert!(!signer.verify_at("/files/7", link.expires_at, signature, link.expires_at));
+        assert!(!UrlSigner::new("other").unwrap().verify("/files/7", link.expires_at, signature));
+        assert!(!signer.verify("/files/7", link.expires_at, "not hex"));
+    }
+}
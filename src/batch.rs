@@ -0,0 +1,951 @@
+use crate::error::{VideoClipError, Result};
+use crate::events::{Progress, ProgressStage};
+use crate::metadata::ClipMetadata;
+use crate::ranges::TimeRange;
+use crate::sniff::FileKind;
+use crate::time_parser::TimeParser;
+use crate::video_clipper::{ClipRequest, ClipResult, Priority, VideoClipper};
+use crate::wall_clock::ClockTime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Batch clipping
+/// Expands glob patterns (`recordings/2024-*/*.mp4`) into one clip request
+/// per matching file, either with a shared range or per-entry ranges from a
+/// JSON or CSV manifest, and runs them all, collecting failures instead of stopping
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchEntry {
+    /// File path or glob pattern
+    pub input: String,
+    /// Overrides the manifest's shared range
+    #[serde(default)]
+    pub start_time: Option<String>,
+    #[serde(default)]
+    pub end_time: Option<String>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    /// Written into the clip's metadata and sidecar
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl BatchEntry {
+    /// The entry's title, description and tags, if it sets any
+    pub fn metadata(&self) -> Option<ClipMetadata> {
+        Some(ClipMetadata {
+            title: self.title.clone(),
+            description: self.description.clone(),
+            tags: self.tags.clone(),
+        }).filter(|m| !m.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchManifest {
+    /// Range used by entries that don't set their own
+    #[serde(default)]
+    pub start_time: Option<String>,
+    #[serde(default)]
+    pub end_time: Option<String>,
+    /// Where clips go unless the caller names a directory (e.g. with `-o`),
+    /// which wins
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    /// Mirror each entry's directory structure under the output directory
+    #[serde(default)]
+    pub mirror_dirs: bool,
+    /// Priority for entries that don't set their own
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    pub entries: Vec<BatchEntry>,
+}
+
+/// CSV header names for each manifest field; only input, start and end are
+/// required, and headers are matched ignoring case
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CsvColumns {
+    pub input: String,
+    pub start: String,
+    pub end: String,
+    pub title: String,
+    pub description: String,
+    /// Tags separated by `;`
+    pub tags: String,
+    pub priority: String,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            input: "input".to_string(),
+            start: "start".to_string(),
+            end: "end".to_string(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            tags: "tags".to_string(),
+            priority: "priority".to_string(),
+        }
+    }
+}
+
+impl FromStr for CsvColumns {
+    type Err = VideoClipError;
+
+    /// `input=File,start=In,end=Out,title=Name`; fields not named keep their default header
+    fn from_str(s: &str) -> Result<Self> {
+        let mut columns = Self::default();
+        for pair in s.split(',').filter(|p| !p.trim().is_empty()) {
+            let (field, header) = pair.split_once('=')
+                .ok_or_else(|| VideoClipError::InvalidOptions(format!("column mapping '{}' should be FIELD=HEADER", pair)))?;
+            let slot = match field.trim().to_lowercase().as_str() {
+                "input" => &mut columns.input,
+                "start" => &mut columns.start,
+                "end" => &mut columns.end,
+                "title" => &mut columns.title,
+                "description" => &mut columns.description,
+                "tags" => &mut columns.tags,
+                "priority" => &mut columns.priority,
+                other => return Err(VideoClipError::InvalidOptions(format!("unknown manifest column '{}'", other))),
+            };
+            *slot = header.trim().to_string();
+        }
+        Ok(columns)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchItemResult {
+    pub input_file: String,
+    #[serde(default)]
+    pub result: Option<ClipResult>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Not clipped again: a duplicate of an earlier request, or already on disk
+    #[serde(default)]
+    pub skipped: bool,
+    /// The worker that made the clip, when a batch is dispatched to several
+    #[serde(default)]
+    pub worker: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchReport {
+    pub items: Vec<BatchItemResult>,
+}
+
+impl BatchReport {
+    pub fn succeeded(&self) -> usize {
+        self.items.iter().filter(|i| i.error.is_none()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.items.len() - self.succeeded()
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.items.iter().filter(|i| i.skipped).count()
+    }
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Videos matching `pattern`, sorted; plain paths are returned as-is so the
+/// clipper reports missing files the usual way. Matches are kept when they
+/// have a video extension or look like a video (as in
+/// [`files_in_directory`]), so `recordings/*` skips notes and thumbnails.
+pub fn expand_pattern(pattern: &str) -> Result<Vec<PathBuf>> {
+    if !is_glob(pattern) {
+        return Ok(vec![PathBuf::from(pattern)]);
+    }
+
+    let entries = glob::glob(pattern)
+        .map_err(|e| VideoClipError::InvalidOptions(format!("invalid glob pattern '{}': {}", pattern, e)))?;
+
+    let videos = DirectoryOptions::default();
+    let mut matches: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file() && videos.matches(path))
+        .collect();
+    matches.sort();
+
+    if matches.is_empty() {
+        return Err(VideoClipError::FileNotFound(format!("no videos match '{}'", pattern)));
+    }
+    Ok(matches)
+}
+
+/// The fixed directory a pattern searches under: everything before the first
+/// wildcard component (`recordings/2024-*/*.mp4` → `recordings`), or a plain
+/// path's parent
+pub fn pattern_base(pattern: &str) -> PathBuf {
+    let path = Path::new(pattern);
+    if !is_glob(pattern) {
+        return path.parent().map(Path::to_path_buf).unwrap_or_default();
+    }
+    path.components()
+        .take_while(|c| !is_glob(&c.as_os_str().to_string_lossy()))
+        .collect()
+}
+
+/// One request per file matched by `patterns`, each cloned from `template`
+/// (which supplies the range and options). A file matched twice is clipped once.
+/// With `mirror`, each clip keeps its path below the pattern's base directory
+/// (unless `template` already names a `mirror_root`).
+pub fn requests_from_patterns(patterns: &[String], template: &ClipRequest, mirror: bool) -> Result<Vec<ClipRequest>> {
+    let mut requests: Vec<ClipRequest> = Vec::new();
+    for pattern in patterns {
+        let mirror_root = match &template.mirror_root {
+            None if mirror => Some(pattern_base(pattern).display().to_string()),
+            root => root.clone(),
+        };
+        for path in expand_pattern(pattern)? {
+            let input_file = path.display().to_string();
+            if requests.iter().any(|r| r.input_file == input_file) {
+                continue;
+            }
+            requests.push(ClipRequest {
+                input_file,
+                mirror_root: mirror_root.clone(),
+                ..template.clone()
+            });
+        }
+    }
+    validate_requests(&requests)?;
+    Ok(requests)
+}
+
+/// Extensions picked up from a directory by default
+pub const VIDEO_EXTENSIONS: [&str; 14] = ["mp4", "mov", "mkv", "webm", "avi", "m4v", "ts", "m2ts", "mts", "mpg", "mpeg", "flv", "wmv", "3gp"];
+
+/// Which files of a directory to clip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryOptions {
+    /// Compared case-insensitively, without the dot
+    pub extensions: Vec<String>,
+    /// Also clip files in subdirectories
+    pub recursive: bool,
+    /// Also pick up files without one of the extensions whose first bytes
+    /// are a video container's
+    pub sniff: bool,
+}
+
+impl Default for DirectoryOptions {
+    fn default() -> Self {
+        Self {
+            extensions: VIDEO_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+            recursive: false,
+            sniff: true,
+        }
+    }
+}
+
+impl DirectoryOptions {
+    fn matches(&self, path: &Path) -> bool {
+        let listed = path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.extensions.iter().any(|wanted| wanted.trim_start_matches('.').eq_ignore_ascii_case(ext)));
+        listed || (self.sniff && FileKind::of_path(path).is_ok_and(FileKind::is_video))
+    }
+}
+
+/// Files of `dir` with one of `options`' extensions, sorted
+pub fn files_in_directory(dir: impl AsRef<Path>, options: &DirectoryOptions) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        return Err(VideoClipError::FileNotFound(format!("no directory '{}'", dir.display())));
+    }
+
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if options.recursive {
+                    pending.push(path);
+                }
+            } else if options.matches(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+
+    if files.is_empty() {
+        return Err(VideoClipError::FileNotFound(format!(
+            "no {} files in '{}'",
+            options.extensions.join("/"),
+            dir.display()
+        )));
+    }
+    Ok(files)
+}
+
+/// One request per video in `dir`, each cloned from `template` with the range
+/// `range_for` picks for the file; files it returns `None` for are left out.
+/// With `mirror`, clips of a recursive scan keep their subdirectories.
+pub fn requests_from_directory(
+    dir: impl AsRef<Path>,
+    template: &ClipRequest,
+    options: &DirectoryOptions,
+    mirror: bool,
+    range_for: impl Fn(&Path) -> Option<TimeRange>,
+) -> Result<Vec<ClipRequest>> {
+    let dir = dir.as_ref();
+    let mirror_root = match &template.mirror_root {
+        None if mirror => Some(dir.display().to_string()),
+        root => root.clone(),
+    };
+    let requests: Vec<ClipRequest> = files_in_directory(dir, options)?
+        .into_iter()
+        .filter_map(|path| {
+            let range = range_for(&path)?;
+            Some(ClipRequest {
+                input_file: path.display().to_string(),
+                start_time: range.start.to_string(),
+                end_time: range.end.to_string(),
+                mirror_root: mirror_root.clone(),
+                ..template.clone()
+            })
+        })
+        .collect();
+    validate_requests(&requests)?;
+    Ok(requests)
+}
+
+/// Checks every range up front so a typo doesn't surface halfway through a nightly run
+pub fn validate_requests(requests: &[ClipRequest]) -> Result<()> {
+    for request in requests {
+        if request.wall_clock {
+            // Resolved against each recording's start when it's clipped
+            request.start_time.parse::<ClockTime>()?;
+            request.end_time.parse::<ClockTime>()?;
+        } else {
+            let start = TimeParser::parse_to_seconds(&request.start_time)?;
+            let end = TimeParser::parse_to_seconds(&request.end_time)?;
+            TimeParser::validate_time_range(start, end)?;
+        }
+        request.validate_options()?;
+    }
+    Ok(())
+}
+
+impl BatchManifest {
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| VideoClipError::InvalidOptions(format!("invalid batch manifest: {}", e)))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// One entry per row of a CSV with a header row, read through `columns`.
+    /// Empty cells are treated as missing, so rows can fall back to shared values.
+    pub fn from_csv(csv: &str, columns: &CsvColumns) -> Result<Self> {
+        let invalid = |e: csv::Error| VideoClipError::InvalidOptions(format!("invalid CSV manifest: {}", e));
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(csv.as_bytes());
+        let headers = reader.headers().map_err(invalid)?.clone();
+        let position = |header: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(header));
+        let required = |header: &str| position(header).ok_or_else(|| {
+            VideoClipError::InvalidOptions(format!(
+                "CSV manifest has no '{}' column (columns: {})",
+                header,
+                headers.iter().collect::<Vec<_>>().join(", ")
+            ))
+        });
+
+        let (input, start, end) = (required(&columns.input)?, required(&columns.start)?, required(&columns.end)?);
+        let (title, description) = (position(&columns.title), position(&columns.description));
+        let (tags, priority) = (position(&columns.tags), position(&columns.priority));
+
+        let mut entries = Vec::new();
+        for row in reader.records() {
+            let row = row.map_err(invalid)?;
+            let cell = |index: Option<usize>| index.and_then(|i| row.get(i)).filter(|v| !v.is_empty()).map(str::to_string);
+            let Some(input) = cell(Some(input)) else {
+                continue;
+            };
+            entries.push(BatchEntry {
+                input,
+                start_time: cell(Some(start)),
+                end_time: cell(Some(end)),
+                priority: cell(priority).map(|p| p.parse()).transpose().map_err(|e| match e {
+                    VideoClipError::InvalidOptions(message) => {
+                        let line = row.position().map_or(0, |position| position.line());
+                        VideoClipError::InvalidOptions(format!("CSV manifest line {}: {}", line, message))
+                    }
+                    other => other,
+                })?,
+                title: cell(title),
+                description: cell(description),
+                tags: cell(tags)
+                    .map(|t| t.split(';').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect())
+                    .unwrap_or_default(),
+            });
+        }
+        Ok(Self { entries, ..Default::default() })
+    }
+
+    /// Reads a `.csv` manifest through `columns` (the default headers when
+    /// `None`), anything else as JSON, which takes no column mapping
+    pub fn from_path(path: impl AsRef<Path>, columns: Option<&CsvColumns>) -> Result<Self> {
+        let path = path.as_ref();
+        let is_csv = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        match (is_csv, columns) {
+            (true, columns) => Self::from_csv(&std::fs::read_to_string(path)?, columns.unwrap_or(&CsvColumns::default())),
+            (false, None) => Self::from_file(path),
+            (false, Some(_)) => Err(VideoClipError::InvalidOptions(format!(
+                "column mappings only apply to CSV manifests, and {} isn't one",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Expands every entry, filling in the shared range and `template`'s options
+    pub fn requests(&self, template: &ClipRequest) -> Result<Vec<ClipRequest>> {
+        let mut requests = Vec::new();
+        for entry in &self.entries {
+            let range = |own: &Option<String>, shared: &Option<String>, name: &str| {
+                own.clone().or_else(|| shared.clone()).ok_or_else(|| {
+                    VideoClipError::InvalidOptions(format!("manifest entry '{}' has no {} time", entry.input, name))
+                })
+            };
+            let entry_template = ClipRequest {
+                start_time: range(&entry.start_time, &self.start_time, "start")?,
+                end_time: range(&entry.end_time, &self.end_time, "end")?,
+                output_dir: template.output_dir.clone().or_else(|| self.output_dir.clone()),
+                priority: entry.priority.or(self.priority).unwrap_or(template.priority),
+                metadata: entry.metadata().or_else(|| template.metadata.clone()),
+                ..template.clone()
+            };
+            requests.extend(requests_from_patterns(std::slice::from_ref(&entry.input), &entry_template, self.mirror_dirs)?);
+        }
+        Ok(requests)
+    }
+}
+
+/// Identity of the clip a request produces: everything except scheduling,
+/// with the input resolved so `./a.mp4` and `a.mp4` match, and the source's
+/// size and modification time so a replaced source is clipped again
+pub fn request_fingerprint(request: &ClipRequest) -> String {
+    let input_file = std::fs::canonicalize(&request.input_file)
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| request.input_file.clone());
+    let source = std::fs::metadata(&input_file).ok().map(|meta| {
+        let modified = meta.modified().ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|since| since.as_nanos());
+        (meta.len(), modified)
+    });
+    // Raw options aren't serialized with the request
+    let raw_args = (&request.extra_input_args, &request.extra_output_args);
+    let request = ClipRequest {
+        input_file,
+        priority: Priority::default(),
+        ..request.clone()
+    };
+    serde_json::to_string(&(request, source, raw_args)).unwrap_or_default()
+}
+
+/// Whether `output` was made from `request`, as its sidecar records
+#[cfg(not(feature = "wasm"))]
+fn made_from(output: &Path, request: &ClipRequest) -> bool {
+    crate::metadata::read_sidecar(output)
+        .and_then(|sidecar| sidecar.request)
+        .is_some_and(|recorded| recorded == request_fingerprint(request))
+}
+
+/// How far an existing output's duration may stray from the range and still
+/// count as the finished clip (stream copy snaps to keyframes)
+#[cfg(not(feature = "wasm"))]
+const EXISTING_DURATION_TOLERANCE: f64 = 1.0;
+
+impl VideoClipper {
+    /// Clips `template`'s range out of every video in `dir`, as a batch
+    pub fn clip_directory(&self, dir: impl AsRef<Path>, template: &ClipRequest, options: &DirectoryOptions) -> Result<BatchReport> {
+        let range = template.time_range()?;
+        self.clip_directory_with(dir, template, options, |_| Some(range))
+    }
+
+    /// Clips every video in `dir` with the range `range_for` picks for it
+    /// (say, from its duration or name), skipping files it returns `None` for
+    pub fn clip_directory_with(
+        &self,
+        dir: impl AsRef<Path>,
+        template: &ClipRequest,
+        options: &DirectoryOptions,
+        range_for: impl Fn(&Path) -> Option<TimeRange>,
+    ) -> Result<BatchReport> {
+        let requests = requests_from_directory(dir, template, options, false, range_for)?;
+        Ok(self.clip_batch(&requests))
+    }
+
+    /// Clips each request in turn, highest priority first, recording failures
+    /// and carrying on. Requests identical to an earlier one, or whose output
+    /// an earlier batch made from the same request and probes as complete,
+    /// are reported as `skipped` (unless the clipper overwrites existing
+    /// outputs). The report keeps the requests' order.
+    pub fn clip_batch(&self, requests: &[ClipRequest]) -> BatchReport {
+        let mut order: Vec<usize> = (0..requests.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(requests[i].priority));
+
+        let mut items: Vec<Option<BatchItemResult>> = vec![None; requests.len()];
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for (done, i) in order.into_iter().enumerate() {
+            let request = &requests[i];
+            let fingerprint = request_fingerprint(request);
+            let item = match seen.get(&fingerprint) {
+                Some(&first) => BatchItemResult {
+                    input_file: request.input_file.clone(),
+                    skipped: true,
+                    ..items[first].clone().expect("earlier requests have run")
+                },
+                None => match self.existing_clip(request) {
+                    Some(result) => BatchItemResult {
+                        input_file: request.input_file.clone(),
+                        result: Some(result),
+                        error: None,
+                        skipped: true,
+                        worker: None,
+                    },
+                    None => self.clip_batch_item(request),
+                },
+            };
+            seen.insert(fingerprint, i);
+            items[i] = Some(item);
+            self.events().progress(request, &Progress { stage: ProgressStage::Batch, completed: done + 1, total: requests.len() });
+        }
+        BatchReport { items: items.into_iter().flatten().collect() }
+    }
+
+    /// Plans a batch without running it, for hosts that execute FFmpeg
+    /// themselves (the WASM build): each request's preset is applied and
+    /// splits are expanded, so the report has one item per clip, holding the
+    /// command to run or why the clip can't be made
+    pub fn prepare_batch(&self, requests: &[ClipRequest]) -> BatchReport {
+        let mut items = Vec::new();
+        for request in requests {
+            let parts = self.config().apply_preset(request).and_then(|resolved| resolved.split_parts());
+            let parts = match parts {
+                Ok(parts) => parts,
+                Err(e) => {
+                    items.push(BatchItemResult {
+                        input_file: request.input_file.clone(),
+                        result: None,
+                        error: Some(e.to_string()),
+                        skipped: false,
+                        worker: None,
+                    });
+                    continue;
+                }
+            };
+            items.extend(parts.iter().map(|part| {
+                let planned = self.prepare_clip_command(part);
+                BatchItemResult {
+                    input_file: part.input_file.clone(),
+                    error: planned.as_ref().err().map(ToString::to_string),
+                    result: planned.ok(),
+                    skipped: false,
+                    worker: None,
+                }
+            }));
+        }
+        BatchReport { items }
+    }
+
+    fn clip_batch_item(&self, request: &ClipRequest) -> BatchItemResult {
+        match self.clip_video(request) {
+            Ok(result) => {
+                #[cfg(not(feature = "wasm"))]
+                if let Err(e) = crate::metadata::record_request(&result, &request_fingerprint(request)) {
+                    log::warn!("Couldn't record the options {} was made with ({}); the next batch will clip it again", result.output_file, e);
+                }
+                BatchItemResult {
+                    input_file: request.input_file.clone(),
+                    result: Some(result),
+                    error: None,
+                    skipped: false,
+                    worker: None,
+                }
+            }
+            Err(e) => {
+                log::warn!("Batch item {} failed: {}", request.input_file, e);
+                BatchItemResult {
+                    input_file: request.input_file.clone(),
+                    result: None,
+                    error: Some(e.to_string()),
+                    skipped: false,
+                    worker: None,
+                }
+            }
+        }
+    }
+
+    /// The clip `request` would produce, if a complete one is already on disk.
+    /// Black trimming renames the output after probing, so those always run.
+    #[cfg(not(feature = "wasm"))]
+    fn existing_clip(&self, request: &ClipRequest) -> Option<ClipResult> {
+        if request.auto_trim_black || self.overwrites_existing() {
+            return None;
+        }
+        let mut planned = self.prepare_clip_command(request).ok()?;
+        let input_path = Path::new(&request.input_file);
+        let output_path = self.output_dir_for(request, input_path).ok()?
+            .join(Path::new(&planned.output_file).file_name()?);
+        if !output_path.is_file() {
+            return None;
+        }
+        // Codecs, filters and the like aren't in the name, so it could be another request's clip
+        if !made_from(&output_path, request) {
+            log::info!("Re-clipping {}: it wasn't made with these options", output_path.display());
+            return None;
+        }
+
+        let info = crate::probe::probe(&output_path).ok()?;
+        let duration = info.duration?;
+        let expected = request.output_duration(planned.duration);
+        if (duration - expected).abs() > EXISTING_DURATION_TOLERANCE {
+            log::info!("Re-clipping {}: existing output is {:.1}s, expected {:.1}s", output_path.display(), duration, expected);
+            return None;
+        }
+
+        planned.output_file = output_path.display().to_string();
+        planned.file_size_mb = info.size_bytes.map(|bytes| bytes as f64 / (1024.0 * 1024.0));
+        planned.warnings.push("Output already exists; not re-clipped".to_string());
+        Some(planned)
+    }
+
+    #[cfg(feature = "wasm")]
+    fn existing_clip(&self, _request: &ClipRequest) -> Option<ClipResult> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn library() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for name in ["2024-01/a.mp4", "2024-01/b.mp4", "2024-02/c.mp4", "2024-02/notes.txt", "2023-12/old.mp4"] {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+        dir
+    }
+
+    fn template(start: &str, end: &str) -> ClipRequest {
+        ClipRequest {
+            start_time: start.to_string(),
+            end_time: end.to_string(),
+            ..Default::default()
+        }
+    }
+
+    mod pattern_tests {
+        use super::*;
+
+        #[test]
+        fn test_glob_expansion_is_sorted_and_videos_only() {
+            let dir = library();
+            let pattern = format!("{}/2024-*/*", dir.path().display());
+            let names: Vec<String> = expand_pattern(&pattern).unwrap()
+                .iter()
+                .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            assert_eq!(names, vec!["a.mp4", "b.mp4", "c.mp4"]);
+            let notes = format!("{}/2024-02/*.txt", dir.path().display());
+            assert!(matches!(expand_pattern(&notes), Err(VideoClipError::FileNotFound(_))));
+        }
+
+        #[test]
+        fn test_plain_paths_and_empty_matches() {
+            assert_eq!(expand_pattern("missing.mp4").unwrap(), vec![PathBuf::from("missing.mp4")]);
+            assert!(matches!(expand_pattern("/nonexistent-dir/*.mp4"), Err(VideoClipError::FileNotFound(_))));
+            assert!(matches!(expand_pattern("[.mp4"), Err(VideoClipError::InvalidOptions(_))));
+        }
+
+        #[test]
+        fn test_requests_share_the_template_range() {
+            let dir = library();
+            let patterns = vec![
+                format!("{}/2024-*/*.mp4", dir.path().display()),
+                format!("{}/2024-01/a.mp4", dir.path().display()),
+            ];
+            let requests = requests_from_patterns(&patterns, &template("0:00", "0:30"), false).unwrap();
+            assert_eq!(requests.len(), 3);
+            assert!(requests.iter().all(|r| r.start_time == "0:00" && r.end_time == "0:30"));
+        }
+
+        #[test]
+        fn test_pattern_base() {
+            assert_eq!(pattern_base("recordings/2024-*/*.mp4"), PathBuf::from("recordings"));
+            assert_eq!(pattern_base("/data/rec/**/*.mov"), PathBuf::from("/data/rec"));
+            assert_eq!(pattern_base("*.mp4"), PathBuf::new());
+            assert_eq!(pattern_base("talks/intro.mp4"), PathBuf::from("talks"));
+        }
+
+        #[test]
+        fn test_mirrored_requests_keep_relative_directories() {
+            let dir = library();
+            let root = dir.path().join("out");
+            let patterns = vec![format!("{}/2024-*/*.mp4", dir.path().display())];
+            let base = ClipRequest { output_dir: Some(root.display().to_string()), ..template("0", "5") };
+            let requests = requests_from_patterns(&patterns, &base, true).unwrap();
+
+            let clipper = VideoClipper::new();
+            let dirs: Vec<PathBuf> = requests.iter()
+                .map(|r| clipper.output_dir_for(r, Path::new(&r.input_file)).unwrap())
+                .collect();
+            assert_eq!(dirs, vec![root.join("2024-01"), root.join("2024-01"), root.join("2024-02")]);
+        }
+
+        #[test]
+        fn test_invalid_range_fails_before_anything_runs() {
+            let dir = library();
+            let patterns = vec![format!("{}/2024-01/*.mp4", dir.path().display())];
+            assert!(matches!(
+                requests_from_patterns(&patterns, &template("0:30", "0:10"), false),
+                Err(VideoClipError::InvalidTimeRange { .. })
+            ));
+        }
+    }
+
+    mod directory_tests {
+        use super::*;
+
+        #[test]
+        fn test_directory_files_by_extension() {
+            let dir = library();
+            std::fs::write(dir.path().join("2024-02/D.MOV"), b"").unwrap();
+            let names = |options: &DirectoryOptions| -> Vec<String> {
+                files_in_directory(dir.path(), options).unwrap()
+                    .iter()
+                    .map(|p| p.strip_prefix(dir.path()).unwrap().display().to_string())
+                    .collect()
+            };
+
+            let recursive = DirectoryOptions { recursive: true, ..Default::default() };
+            assert_eq!(names(&recursive), vec!["2023-12/old.mp4", "2024-01/a.mp4", "2024-01/b.mp4", "2024-02/D.MOV", "2024-02/c.mp4"]);
+            let only_mov = DirectoryOptions { extensions: vec![".mov".to_string()], recursive: true, sniff: false };
+            assert_eq!(names(&only_mov), vec!["2024-02/D.MOV"]);
+
+            // A recording saved without an extension is found by its content
+            std::fs::write(dir.path().join("2024-02/recording"), b"\0\0\0\x20ftypisom\0\0\x02\0").unwrap();
+            assert_eq!(names(&DirectoryOptions { sniff: true, ..only_mov.clone() }), vec!["2024-02/D.MOV", "2024-02/recording"]);
+
+            // Only subdirectories at the top level
+            assert!(matches!(files_in_directory(dir.path(), &DirectoryOptions::default()), Err(VideoClipError::FileNotFound(_))));
+            assert!(matches!(files_in_directory(dir.path().join("missing"), &DirectoryOptions::default()), Err(VideoClipError::FileNotFound(_))));
+        }
+
+        #[test]
+        fn test_directory_requests_take_a_range_per_file() {
+            let dir = library();
+            let requests = requests_from_directory(dir.path().join("2024-01"), &template("0", "10"), &DirectoryOptions::default(), false, |path| {
+                (path.file_name()? != "b.mp4").then_some(TimeRange { start: 0.0, end: 10.0 })
+            }).unwrap();
+            assert_eq!(requests.len(), 1);
+            assert!(requests[0].input_file.ends_with("a.mp4"));
+            assert_eq!((requests[0].start_time.as_str(), requests[0].end_time.as_str()), ("0", "10"));
+        }
+
+        #[test]
+        fn test_clip_directory_reports_every_file() {
+            let dir = library();
+            let report = VideoClipper::new()
+                .clip_directory(dir.path().join("2024-01"), &template("0", "10"), &DirectoryOptions::default())
+                .unwrap();
+            // Empty files aren't videos, so each fails on its own
+            assert_eq!(report.items.len(), 2);
+            assert_eq!(report.failed(), 2);
+        }
+    }
+
+    mod manifest_tests {
+        use super::*;
+
+        #[test]
+        fn test_manifest_with_shared_and_per_entry_ranges() {
+            let dir = library();
+            let json = format!(
+                r#"{{
+                    "start_time": "0:00",
+                    "end_time": "0:45",
+                    "output_dir": "intros",
+                    "entries": [
+                        {{ "input": "{root}/2024-*/*.mp4" }},
+                        {{ "input": "{root}/2023-12/old.mp4", "start_time": "1:00", "end_time": "1:20", "priority": "high",
+                           "title": "Old intro", "tags": ["intro", "2023"] }}
+                    ]
+                }}"#,
+                root = dir.path().display()
+            );
+
+            let requests = BatchManifest::from_json(&json).unwrap().requests(&ClipRequest::default()).unwrap();
+            assert_eq!(requests.len(), 4);
+            assert_eq!(requests[0].end_time, "0:45");
+            assert_eq!(requests[3].start_time, "1:00");
+            assert_eq!(requests[3].priority, Priority::High);
+            assert_eq!(requests[0].priority, Priority::Normal);
+            assert!(requests.iter().all(|r| r.output_dir.as_deref() == Some("intros")));
+            let elsewhere = ClipRequest { output_dir: Some("elsewhere".to_string()), ..Default::default() };
+            let requests = BatchManifest::from_json(&json).unwrap().requests(&elsewhere).unwrap();
+            assert!(requests.iter().all(|r| r.output_dir.as_deref() == Some("elsewhere")));
+            assert!(requests[0].metadata.is_none());
+            let metadata = requests[3].metadata.as_ref().unwrap();
+            assert_eq!(metadata.title.as_deref(), Some("Old intro"));
+            assert_eq!(metadata.tags, vec!["intro", "2023"]);
+        }
+
+        #[test]
+        fn test_csv_manifest_with_column_mapping() {
+            let csv = "\
+File,In,Out,Name,Keywords,Notes
+talk.mp4,0:10,0:40,Opening,keynote; 2024,
+\"panel, day 2.mp4\",1:00,1:30,,,
+,,,,,blank row
+";
+            let columns: CsvColumns = "input=File,start=In,end=Out,title=Name,tags=Keywords".parse().unwrap();
+            let manifest = BatchManifest::from_csv(csv, &columns).unwrap();
+            assert_eq!(manifest.entries.len(), 2);
+            assert_eq!(manifest.entries[0].title.as_deref(), Some("Opening"));
+            assert_eq!(manifest.entries[0].tags, vec!["keynote", "2024"]);
+            assert_eq!(manifest.entries[1].input, "panel, day 2.mp4");
+            assert!(manifest.entries[1].metadata().is_none());
+
+            let requests = manifest.requests(&ClipRequest::default()).unwrap();
+            assert_eq!((requests[0].start_time.as_str(), requests[0].end_time.as_str()), ("0:10", "0:40"));
+        }
+
+        #[test]
+        fn test_csv_manifest_missing_column() {
+            let err = BatchManifest::from_csv("input,from,to\na.mp4,0,5\n", &CsvColumns::default()).unwrap_err();
+            assert!(err.to_string().contains("no 'start' column (columns: input, from, to)"));
+            assert!("input=File,length=Len".parse::<CsvColumns>().is_err());
+        }
+
+        #[test]
+        fn test_csv_manifest_bad_priority_names_the_line() {
+            let err = BatchManifest::from_csv("input,start,end,priority
+a.mp4,0,5,high
+b.mp4,0,5,urgent
+", &CsvColumns::default()).unwrap_err();
+            assert_eq!(err.to_string(), "Invalid options: CSV manifest line 3: unknown priority 'urgent'");
+        }
+
+        #[test]
+        fn test_column_mapping_needs_a_csv_manifest() {
+            let dir = TempDir::new().unwrap();
+            let path = dir.path().join("batch.json");
+            std::fs::write(&path, r#"{"entries": []}"#).unwrap();
+            assert!(BatchManifest::from_path(&path, None).is_ok());
+            let columns: CsvColumns = "input=File".parse().unwrap();
+            assert!(matches!(BatchManifest::from_path(&path, Some(&columns)), Err(VideoClipError::InvalidOptions(_))));
+        }
+
+        #[test]
+        fn test_entry_without_range() {
+            let manifest = BatchManifest::from_json(r#"{"entries": [{"input": "a.mp4"}]}"#).unwrap();
+            let err = manifest.requests(&ClipRequest::default()).unwrap_err();
+            assert!(err.to_string().contains("no start time"));
+        }
+
+        #[test]
+        fn test_batch_collects_failures() {
+            let requests = vec![ClipRequest { input_file: "missing.mp4".to_string(), ..template("0", "5") }];
+            let report = VideoClipper::new().clip_batch(&requests);
+            assert_eq!((report.succeeded(), report.failed()), (0, 1));
+            assert!(report.items[0].error.as_ref().unwrap().contains("missing.mp4"));
+        }
+        
+        #[test]
+        fn test_identical_requests_run_once() {
+            let request = ClipRequest { input_file: "missing.mp4".to_string(), ..template("0", "5") };
+            let requests = vec![
+                request.clone(),
+                ClipRequest { priority: Priority::High, ..request.clone() },
+                ClipRequest { end_time: "6".to_string(), ..request },
+            ];
+            let report = VideoClipper::new().clip_batch(&requests);
+            let skipped: Vec<bool> = report.items.iter().map(|i| i.skipped).collect();
+            // The high-priority copy runs first, so the original is its duplicate
+            assert_eq!(skipped, vec![true, false, false]);
+            assert_eq!(report.items[0].error, report.items[1].error);
+            assert_eq!(report.skipped(), 1);
+        }
+
+        #[cfg(not(feature = "wasm"))]
+        #[test]
+        fn test_existing_outputs_need_the_same_options() {
+            let dir = TempDir::new().unwrap();
+            let output = dir.path().join("talk_00-00_00-05.mp4");
+            std::fs::write(&output, b"old clip").unwrap();
+            let request = ClipRequest { input_file: "talk.mp4".to_string(), ..template("0", "5") };
+            // Made outside a batch, or before outputs were recorded
+            assert!(!made_from(&output, &request));
+
+            let sidecar = crate::metadata::Sidecar {
+                input_file: request.input_file.clone(),
+                start_seconds: 0.0,
+                end_seconds: 5.0,
+                metadata: Default::default(),
+                request: Some(request_fingerprint(&request)),
+            };
+            std::fs::write(crate::metadata::sidecar_path(&output), serde_json::to_string(&sidecar).unwrap()).unwrap();
+            assert!(made_from(&output, &request));
+            assert!(made_from(&output, &ClipRequest { priority: Priority::High, ..request.clone() }));
+            let reencoded = ClipRequest { video_codec: crate::encoder::VideoCodec::H264, ..request.clone() };
+            assert!(!made_from(&output, &reencoded));
+            // So the batch clips it again rather than reporting the old clip
+            let report = VideoClipper::with_output_dir(dir.path()).clip_batch(&[reencoded]);
+            assert!(!report.items[0].skipped);
+
+            let mut clipper = VideoClipper::with_output_dir(dir.path());
+            clipper.set_overwrite_existing(true);
+            assert!(clipper.existing_clip(&request).is_none());
+        }
+
+        #[cfg(not(feature = "wasm"))]
+        #[test]
+        fn test_replaced_sources_are_clipped_again() {
+            let dir = TempDir::new().unwrap();
+            let source = dir.path().join("talk.mp4");
+            std::fs::write(&source, b"first take").unwrap();
+            let request = ClipRequest { input_file: source.display().to_string(), ..template("0", "5") };
+            let before = request_fingerprint(&request);
+            assert_eq!(request_fingerprint(&request), before);
+
+            std::fs::write(&source, b"second, longer take").unwrap();
+            assert_ne!(request_fingerprint(&request), before);
+        }
+
+        #[test]
+        fn test_prepare_batch_expands_splits_without_running() {
+            let requests = vec![
+                ClipRequest {
+                    input_file: "talk.mp4".to_string(),
+                    split: Some(crate::ranges::SplitOptions::new(60.0)),
+                    ..template("0:00", "2:30")
+                },
+                ClipRequest { input_file: "talk.mp4".to_string(), ..template("1:00", "0:30") },
+            ];
+            let report = VideoClipper::new().prepare_batch(&requests);
+            assert_eq!((report.succeeded(), report.failed()), (3, 1));
+            let commands: Vec<_> = report.items.iter().filter_map(|i| i.result.as_ref()).map(|r| r.duration).collect();
+            assert_eq!(commands, vec![60.0, 60.0, 30.0]);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Black frame detection
+/// Wraps FFmpeg's `blackdetect` filter to find black segments, and trims
+/// leading/trailing black (fade-ins, slates, dead air) off a clip range
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BlackSegment {
+    pub start: f64,
+    pub end: f64,
+    pub duration: f64,
+}
+
+/// Shortest run of black frames worth reporting, in seconds
+pub const DEFAULT_MIN_BLACK_SECONDS: f64 = 0.1;
+
+/// Luma threshold below which a pixel counts as black (0.0 - 1.0)
+pub const DEFAULT_PIXEL_THRESHOLD: f64 = 0.10;
+
+/// Gap between a segment and a range boundary still treated as touching,
+/// roughly one frame at 24fps
+const BOUNDARY_TOLERANCE: f64 = 0.05;
+
+/// Parses `black_start:0 black_end:2.002 black_duration:2.002` lines from
+/// FFmpeg's stderr
+pub fn parse_blackdetect(stderr: &str) -> Vec<BlackSegment> {
+    stderr
+        .lines()
+        .filter(|line| line.contains("black_start:"))
+        .filter_map(|line| {
+            let value = |key: &str| -> Option<f64> {
+                let rest = &line[line.find(key)? + key.len()..];
+                rest.split_whitespace().next()?.parse().ok()
+            };
+            let start = value("black_start:")?;
+            let end = value("black_end:")?;
+            Some(BlackSegment {
+                start,
+                end,
+                duration: value("black_duration:").unwrap_or(end - start),
+            })
+        })
+        .collect()
+}
+
+/// Boundaries of a range before and after black trimming
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BlackTrim {
+    pub requested_start: f64,
+    pub requested_end: f64,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl BlackTrim {
+    /// Seconds of black removed from the front and back
+    pub fn removed(&self) -> (f64, f64) {
+        (self.start - self.requested_start, self.requested_end - self.end)
+    }
+
+    pub fn is_trimmed(&self) -> bool {
+        self.start > self.requested_start || self.end < self.requested_end
+    }
+}
+
+/// Shrinks `[start, end]` past black segments touching either boundary.
+/// Returns `None` when the whole range is black.
+pub fn trim_black(start: f64, end: f64, segments: &[BlackSegment]) -> Option<BlackTrim> {
+    let mut sorted = segments.to_vec();
+    sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut trimmed_start = start;
+    for segment in &sorted {
+        if segment.start <= trimmed_start + BOUNDARY_TOLERANCE && segment.end > trimmed_start {
+            trimmed_start = segment.end;
+        }
+    }
+
+    let mut trimmed_end = end;
+    for segment in sorted.iter().rev() {
+        if segment.end >= trimmed_end - BOUNDARY_TOLERANCE && segment.start < trimmed_end {
+            trimmed_end = segment.start;
+        }
+    }
+
+    if trimmed_end - trimmed_start <= BOUNDARY_TOLERANCE {
+        return None;
+    }
+
+    Some(BlackTrim {
+        requested_start: start,
+        requested_end: end,
+        start: trimmed_start.max(start),
+        end: trimmed_end.min(end),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct BlackDetectCommand {
+    input: PathBuf,
+    start_time: f64,
+    duration: f64,
+    min_duration: f64,
+    pixel_threshold: f64,
+}
+
+impl BlackDetectCommand {
+    pub fn new(input: impl AsRef<Path>, start_time: f64, duration: f64) -> Self {
+        Self {
+            input: input.as_ref().to_path_buf(),
+            start_time,
+            duration,
+            min_duration: DEFAULT_MIN_BLACK_SECONDS,
+            pixel_threshold: DEFAULT_PIXEL_THRESHOLD,
+        }
+    }
+
+    pub fn set_min_duration(&mut self, seconds: f64) {
+        self.min_duration = seconds;
+    }
+
+    pub fn set_pixel_threshold(&mut self, threshold: f64) {
+        self.pixel_threshold = threshold;
+    }
+
+    pub fn filter_graph(&self) -> FilterGraph {
+        FilterGraph::from(
+            Filter::new("blackdetect")
+                .option("d", self.min_duration)
+                .option("pix_th", format!("{:.2}", self.pixel_threshold)),
+        )
+    }
+
+    /// Decodes the first video stream through `blackdetect`, discarding output
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-hide_banner".into(),
+            "-ss".into(), self.start_time.to_string(),
+            "-i".into(), self.input.display().to_string(),
+            "-t".into(), self.duration.to_string(),
+            "-map".into(), "0:v:0".into(),
+        ];
+        args.extend(self.filter_graph().to_args());
+        args.extend(["-an", "-f", "null", "-"].iter().map(|s| s.to_string()));
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    /// Black segments in source time (input seeking resets timestamps to zero,
+    /// so they're shifted back by the start time)
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> crate::error::Result<Vec<BlackSegment>> {
+        let output = crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Black frame detection failed")?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        Ok(parse_blackdetect(&stderr)
+            .into_iter()
+            .map(|s| BlackSegment {
+                start: s.start + self.start_time,
+                end: s.end + self.start_time,
+                duration: s.duration,
+            })
+            .collect())
+    }
+}
+
+/// How much of each end of a long range is scanned; black beyond this is
+/// content, not a fade or slate
+pub const TRIM_SCAN_WINDOW: f64 = 30.0;
+
+/// Finds black segments in `[start, end]` of `input`. Long ranges only have
+/// their head and tail scanned, since only boundary black gets trimmed.
+#[cfg(not(feature = "wasm"))]
+pub fn detect_black(input: impl AsRef<Path>, start: f64, end: f64) -> crate::error::Result<Vec<BlackSegment>> {
+    let input = input.as_ref();
+    let duration = end - start;
+    if duration <= 2.0 * TRIM_SCAN_WINDOW {
+        return BlackDetectCommand::new(input, start, duration).execute();
+    }
+
+    let mut segments = BlackDetectCommand::new(input, start, TRIM_SCAN_WINDOW).execute()?;
+    segments.extend(BlackDetectCommand::new(input, end - TRIM_SCAN_WINDOW, TRIM_SCAN_WINDOW).execute()?);
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: f64, end: f64) -> BlackSegment {
+        BlackSegment { start, end, duration: end - start }
+    }
+
+    mod parsing_tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_blackdetect_output() {
+            let stderr = "Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'talk.mp4':\n\
+                [blackdetect @ 0x55d0c8a0] black_start:0 black_end:2.002 black_duration:2.002\n\
+                frame=  600 fps=0.0 q=-0.0 size=N/A time=00:00:20.00\n\
+                [blackdetect @ 0x55d0c8a0] black_start:18.5 black_end:20 black_duration:1.5\n";
+            assert_eq!(parse_blackdetect(stderr), vec![segment(0.0, 2.002), segment(18.5, 20.0)]);
+        }
+
+        #[test]
+        fn test_parse_ignores_other_lines() {
+            assert!(parse_blackdetect("frame=1 fps=0\nblack_start:oops\n").is_empty());
+        }
+    }
+
+    mod trim_tests {
+        use super::*;
+
+        #[test]
+        fn test_trims_leading_and_trailing_black() {
+            let trim = trim_black(10.0, 40.0, &[segment(10.0, 12.5), segment(20.0, 21.0), segment(38.0, 40.0)]).unwrap();
+            assert_eq!((trim.start, trim.end), (12.5, 38.0));
+            assert_eq!(trim.removed(), (2.5, 2.0));
+            assert!(trim.is_trimmed());
+        }
+
+        #[test]
+        fn test_adjacent_segments_are_merged() {
+            let trim = trim_black(0.0, 30.0, &[segment(1.02, 3.0), segment(0.0, 1.0)]).unwrap();
+            assert_eq!(trim.start, 3.0);
+            assert_eq!(trim.end, 30.0);
+        }
+
+        #[test]
+        fn test_segments_spilling_over_boundaries() {
+            let trim = trim_black(10.0, 40.0, &[segment(8.0, 11.0), segment(39.0, 45.0)]).unwrap();
+            assert_eq!((trim.start, trim.end), (11.0, 39.0));
+        }
+
+        #[test]
+        fn test_interior_black_is_kept() {
+            let trim = trim_black(0.0, 30.0, &[segment(5.0, 6.0)]).unwrap();
+            assert!(!trim.is_trimmed());
+        }
+
+        #[test]
+        fn test_all_black_range() {
+            assert_eq!(trim_black(0.0, 10.0, &[segment(0.0, 10.0)]), None);
+        }
+    }
+
+    mod command_tests {
+        use super::*;
+
+        #[test]
+        fn test_blackdetect_command() {
+            let mut command = BlackDetectCommand::new("talk.mp4", 60.0, 30.0);
+            assert_eq!(
+                command.get_command_string(),
+                "ffmpeg -hide_banner -ss 60 -i talk.mp4 -t 30 -map 0:v:0 -vf blackdetect=d=0.1:pix_th=0.10 -an -f null -"
+            );
+
+            command.set_min_duration(0.5);
+            command.set_pixel_threshold(0.05);
+            assert_eq!(command.filter_graph().to_string(), "blackdetect=d=0.5:pix_th=0.05");
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::error::{VideoClipError, Result};
+use std::cell::RefCell;
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Cancelling clips in progress
+/// Work run inside [`CancelHandle::scope`] can be stopped from another
+/// thread: [`CancelHandle::cancel`] kills the FFmpeg process it's waiting on
+/// and the clip fails with `VideoClipError::Cancelled` instead of starting
+/// another. Its staged output is removed like any failed clip's. When FFmpeg
+/// runs in a container or over SSH, the container or remote process is
+/// killed too, not just the client that started it. The job queue runs
+/// every job under a handle of its own (see
+/// [`crate::jobs::JobQueue::cancel`]).
+
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+thread_local! {
+    /// The handle work on this thread runs under
+    static CURRENT: RefCell<Option<CancelHandle>> = const { RefCell::new(None) };
+}
+
+/// How often a process is checked on while its clip can be cancelled
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops the work running under this handle, and any it would start
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Runs `f` with the FFmpeg processes it starts on this thread watching
+    /// the handle
+    pub fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        let previous = CURRENT.with(|cell| cell.replace(Some(self.clone())));
+        let outcome = f();
+        CURRENT.with(|cell| *cell.borrow_mut() = previous);
+        outcome
+    }
+
+    /// The handle this thread's work runs under, if any
+    pub fn current() -> Option<Self> {
+        CURRENT.with(|cell| cell.borrow().clone())
+    }
+}
+
+/// Runs `command` like `Command::output`, killing it when the thread's
+/// handle is cancelled
+pub(crate) fn output(command: &mut Command) -> Result<Output> {
+    let spawn_error = |e: std::io::Error| VideoClipError::FFmpegError(e.to_string());
+    let Some(handle) = CancelHandle::current() else {
+        return command.output().map_err(spawn_error);
+    };
+    if handle.is_cancelled() {
+        return Err(VideoClipError::Cancelled);
+    }
+
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn()
+        .map_err(spawn_error)?;
+    let stdout = read_to_end(child.stdout.take());
+    let stderr = read_to_end(child.stderr.take());
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if handle.is_cancelled() {
+            if let Some(mut stop) = crate::tools::stop_command(command) {
+                let _ = stop.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status();
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(VideoClipError::Cancelled);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+    Ok(Output { status, stdout: collect(stdout), stderr: collect(stderr) })
+}
+
+/// Drains a pipe on its own thread, so a chatty process can't fill it and stall
+fn read_to_end(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+fn collect(reader: JoinHandle<Vec<u8>>) -> Vec<u8> {
+    reader.join().unwrap_or_default()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_cancel_kills_the_process() {
+        let handle = CancelHandle::new();
+        let canceller = handle.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            canceller.cancel();
+        });
+        let started = Instant::now();
+        let outcome = handle.scope(|| output(Command::new("sleep").arg("10")));
+        assert!(matches!(outcome, Err(VideoClipError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Nothing new starts once cancelled
+        assert!(matches!(handle.scope(|| output(&mut Command::new("true"))), Err(VideoClipError::Cancelled)));
+    }
+
+    #[test]
+    fn test_output_is_captured_under_a_handle() {
+        let output = CancelHandle::new().scope(|| output(Command::new("echo").arg("done"))).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"done\n");
+        assert!(CancelHandle::current().is_none());
+    }
+}
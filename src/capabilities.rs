@@ -0,0 +1,300 @@
+use crate::error::{VideoClipError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// FFmpeg build detection
+/// Parses `ffmpeg -version`, `-encoders` and `-filters` output so features that
+/// depend on optional libraries fail up front with a clear error instead of an
+/// opaque FFmpeg failure halfway through a job
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FfmpegVersion {
+    /// Version string as printed, e.g. `6.1.1-3ubuntu5` or `N-112345-g1234abcd`
+    pub raw: String,
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// Git snapshot builds carry no release number and are treated as newest
+    pub development: bool,
+}
+
+impl FfmpegVersion {
+    /// Parses the first line of `ffmpeg -version` (or `ffprobe -version`)
+    pub fn parse(output: &str) -> Option<Self> {
+        let raw = output.lines().next()?.split_whitespace().nth(2)?.to_string();
+
+        let release = raw.strip_prefix('n').unwrap_or(&raw);
+        let numeric: String = release
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == '.')
+            .collect();
+        if numeric.is_empty() {
+            let development = raw.starts_with("N-") || raw.starts_with("git-");
+            return Some(Self { raw, major: 0, minor: 0, patch: 0, development });
+        }
+
+        let mut parts = numeric.split('.').map(|p| p.parse::<u32>().unwrap_or(0));
+        Some(Self {
+            major: parts.next().unwrap_or(0),
+            minor: parts.next().unwrap_or(0),
+            patch: parts.next().unwrap_or(0),
+            development: false,
+            raw,
+        })
+    }
+
+    pub fn at_least(&self, major: u32, minor: u32) -> bool {
+        self.development || (self.major, self.minor) >= (major, minor)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FfmpegCapabilities {
+    pub version: FfmpegVersion,
+    /// Libraries from the build configuration, e.g. `libx264` for `--enable-libx264`
+    pub enabled_libs: BTreeSet<String>,
+    pub encoders: BTreeSet<String>,
+    pub filters: BTreeSet<String>,
+}
+
+/// Library names from the `configuration:` line of `ffmpeg -version`
+pub fn parse_enabled_libs(output: &str) -> BTreeSet<String> {
+    output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("configuration:"))
+        .map(|config| {
+            config
+                .split_whitespace()
+                .filter_map(|flag| flag.strip_prefix("--enable-"))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Encoder names from `ffmpeg -encoders` (lines like ` V....D libx264  description`)
+pub fn parse_encoder_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("------"))
+        .skip(1)
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let flags = parts.next()?;
+            if flags.len() != 6 {
+                return None;
+            }
+            parts.next().map(str::to_string)
+        })
+        .collect()
+}
+
+/// Filter names from `ffmpeg -filters` (lines like ` TSC loudnorm  A->A  description`)
+pub fn parse_filter_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let flags = parts.next()?;
+            let name = parts.next()?;
+            let io = parts.next()?;
+            if flags.len() == 3 && io.contains("->") {
+                Some(name.to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+impl FfmpegCapabilities {
+    /// Builds capabilities from captured `-version`, `-encoders` and `-filters` output
+    pub fn from_outputs(version: &str, encoders: &str, filters: &str) -> Result<Self> {
+        let version_info = FfmpegVersion::parse(version)
+            .ok_or_else(|| VideoClipError::FFmpegError("unrecognized `ffmpeg -version` output".to_string()))?;
+
+        Ok(Self {
+            version: version_info,
+            enabled_libs: parse_enabled_libs(version),
+            encoders: parse_encoder_list(encoders).into_iter().collect(),
+            filters: parse_filter_list(filters).into_iter().collect(),
+        })
+    }
+
+    /// Queries the `ffmpeg` binary that commands run (see [`crate::tools`])
+    #[cfg(not(feature = "wasm"))]
+    pub fn detect() -> Result<Self> {
+        use crate::ffmpeg::FFmpegCommand;
+
+        let capture = |args: &[&str]| -> Result<String> {
+            let cmd = crate::tools::ffmpeg_command(args.iter().map(|arg| arg.to_string()).collect());
+            let output = FFmpegCommand::run(cmd, "Capability detection failed")?;
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        };
+
+        Self::from_outputs(
+            &capture(&["-version"])?,
+            &capture(&["-hide_banner", "-encoders"])?,
+            &capture(&["-hide_banner", "-filters"])?,
+        )
+    }
+
+    /// Detects once per process and reuses the result. A failed detection
+    /// isn't kept, so FFmpeg installed (or configured) later is still found.
+    #[cfg(not(feature = "wasm"))]
+    pub fn cached() -> Result<&'static Self> {
+        static CAPABILITIES: std::sync::OnceLock<FfmpegCapabilities> = std::sync::OnceLock::new();
+        if let Some(capabilities) = CAPABILITIES.get() {
+            return Ok(capabilities);
+        }
+        let detected = Self::detect().map_err(|_| VideoClipError::FFmpegNotFound)?;
+        Ok(CAPABILITIES.get_or_init(|| detected))
+    }
+
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.contains(name)
+    }
+
+    pub fn has_filter(&self, name: &str) -> bool {
+        self.filters.contains(name)
+    }
+
+    pub fn has_lib(&self, name: &str) -> bool {
+        self.enabled_libs.contains(name)
+    }
+
+    /// Errors unless the build has `filter`; `feature` names what needed it
+    pub fn require_filter(&self, filter: &str, feature: &str) -> Result<()> {
+        if self.has_filter(filter) {
+            return Ok(());
+        }
+        Err(VideoClipError::UnsupportedByFfmpegBuild {
+            feature: feature.to_string(),
+            reason: format!("the `{}` filter is not available", filter),
+        })
+    }
+
+    /// Errors unless the build has `encoder`; `feature` names what needed it
+    pub fn require_encoder(&self, encoder: &str, feature: &str) -> Result<()> {
+        if self.has_encoder(encoder) {
+            return Ok(());
+        }
+        Err(VideoClipError::UnsupportedByFfmpegBuild {
+            feature: feature.to_string(),
+            reason: format!("the `{}` encoder is not available", encoder),
+        })
+    }
+
+    /// Errors unless FFmpeg is at least `major.minor`
+    pub fn require_version(&self, major: u32, minor: u32, feature: &str) -> Result<()> {
+        if self.version.at_least(major, minor) {
+            return Ok(());
+        }
+        Err(VideoClipError::UnsupportedByFfmpegBuild {
+            feature: feature.to_string(),
+            reason: format!("requires FFmpeg {}.{} or newer, found {}", major, minor, self.version.raw),
+        })
+    }
+
+    /// HDR to SDR conversion, which needs zscale (libzimg) alongside tonemap
+    pub fn require_tonemap(&self) -> Result<()> {
+        self.require_filter("zscale", "HDR tonemapping")?;
+        self.require_filter("tonemap", "HDR tonemapping")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERSION_OUTPUT: &str = "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers\n\
+        built with gcc 13 (Ubuntu 13.2.0-23ubuntu3)\n\
+        configuration: --prefix=/usr --enable-gpl --enable-libx264 --enable-libaom --disable-stripping\n\
+        libavutil      58. 29.100 / 58. 29.100\n";
+
+    const ENCODERS_OUTPUT: &str = "Encoders:\n V..... = Video\n A..... = Audio\n ------\n \
+        V....D libx264              libx264 H.264\n \
+        V....D libaom-av1           libaom AV1\n \
+        A....D aac                  AAC (Advanced Audio Coding)\n";
+
+    const FILTERS_OUTPUT: &str = "Filters:\n  T.. = Timeline support\n  A = Audio input/output\n \
+        ... abench            A->A       Benchmark part of a filter graph.\n \
+        TSC scale             V->V       Scale the input video size.\n \
+        T.C yadif             V->V       Deinterlace the input image.\n";
+
+    fn sample() -> FfmpegCapabilities {
+        FfmpegCapabilities::from_outputs(VERSION_OUTPUT, ENCODERS_OUTPUT, FILTERS_OUTPUT).unwrap()
+    }
+
+    mod parsing_tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_release_version() {
+            let version = FfmpegVersion::parse(VERSION_OUTPUT).unwrap();
+            assert_eq!(version.raw, "6.1.1-3ubuntu5");
+            assert_eq!((version.major, version.minor, version.patch), (6, 1, 1));
+            assert!(!version.development);
+            assert!(version.at_least(5, 0));
+            assert!(version.at_least(6, 1));
+            assert!(!version.at_least(7, 0));
+        }
+
+        #[test]
+        fn test_parse_tagged_and_development_versions() {
+            let tagged = FfmpegVersion::parse("ffmpeg version n7.0 Copyright").unwrap();
+            assert_eq!((tagged.major, tagged.minor), (7, 0));
+
+            let snapshot = FfmpegVersion::parse("ffmpeg version N-112345-g1234abcd Copyright").unwrap();
+            assert!(snapshot.development);
+            assert!(snapshot.at_least(99, 0));
+
+            assert!(FfmpegVersion::parse("").is_none());
+        }
+
+        #[test]
+        fn test_parse_capability_lists() {
+            let caps = sample();
+            assert!(caps.has_lib("libx264"));
+            assert!(caps.has_lib("gpl"));
+            assert!(!caps.has_lib("stripping"));
+            assert_eq!(parse_encoder_list(ENCODERS_OUTPUT), vec!["libx264", "libaom-av1", "aac"]);
+            assert_eq!(parse_filter_list(FILTERS_OUTPUT), vec!["abench", "scale", "yadif"]);
+        }
+
+        #[test]
+        fn test_unrecognized_version_output() {
+            assert!(FfmpegCapabilities::from_outputs("", "", "").is_err());
+        }
+    }
+
+    mod gating_tests {
+        use super::*;
+
+        #[test]
+        fn test_available_features_pass() {
+            let caps = sample();
+            assert!(caps.require_encoder("libaom-av1", "AV1 encoding").is_ok());
+            assert!(caps.require_filter("yadif", "deinterlacing").is_ok());
+            assert!(caps.require_version(4, 4, "fps_mode").is_ok());
+        }
+
+        #[test]
+        fn test_missing_features_are_reported() {
+            let caps = sample();
+
+            match caps.require_filter("loudnorm", "loudness normalization") {
+                Err(VideoClipError::UnsupportedByFfmpegBuild { feature, reason }) => {
+                    assert_eq!(feature, "loudness normalization");
+                    assert!(reason.contains("loudnorm"));
+                }
+                other => panic!("expected UnsupportedByFfmpegBuild, got {:?}", other),
+            }
+            assert!(matches!(caps.require_encoder("h264_nvenc", "NVENC hardware encoding"), Err(VideoClipError::UnsupportedByFfmpegBuild { .. })));
+            assert!(matches!(caps.require_tonemap(), Err(VideoClipError::UnsupportedByFfmpegBuild { .. })));
+
+            let err = caps.require_version(7, 0, "new thing").unwrap_err();
+            assert!(err.to_string().contains("requires FFmpeg 7.0 or newer, found 6.1.1-3ubuntu5"));
+        }
+    }
+}
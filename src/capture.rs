@@ -0,0 +1,399 @@
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Screen capture command builder and executor
+/// Records the desktop (or a rectangular region of it) for a fixed duration
+/// using the platform's FFmpeg grab device, producing a file that can be clipped
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaptureBackend {
+    /// X11 display grabbing (Linux/BSD)
+    X11Grab,
+    /// GDI desktop grabbing (Windows)
+    GdiGrab,
+    /// AVFoundation screen devices (macOS)
+    AvFoundation,
+}
+
+impl CaptureBackend {
+    pub fn for_current_platform() -> Result<Self> {
+        if cfg!(target_os = "windows") {
+            Ok(CaptureBackend::GdiGrab)
+        } else if cfg!(target_os = "macos") {
+            Ok(CaptureBackend::AvFoundation)
+        } else if cfg!(unix) && !cfg!(target_arch = "wasm32") {
+            Ok(CaptureBackend::X11Grab)
+        } else {
+            Err(VideoClipError::UnsupportedPlatform(
+                "no FFmpeg screen grab device available".to_string()
+            ))
+        }
+    }
+
+    pub fn input_format(&self) -> &'static str {
+        match self {
+            CaptureBackend::X11Grab => "x11grab",
+            CaptureBackend::GdiGrab => "gdigrab",
+            CaptureBackend::AvFoundation => "avfoundation",
+        }
+    }
+
+    fn default_display(&self) -> String {
+        match self {
+            CaptureBackend::X11Grab => std::env::var("DISPLAY").unwrap_or_else(|_| ":0.0".to_string()),
+            CaptureBackend::GdiGrab => "desktop".to_string(),
+            // First screen device, no audio device
+            CaptureBackend::AvFoundation => "1:none".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CaptureRegion {
+    /// Parses an X11-style geometry string: `WIDTHxHEIGHT` or `WIDTHxHEIGHT+X+Y`
+    pub fn parse(geometry: &str) -> Result<Self> {
+        let invalid = || VideoClipError::InvalidCaptureRegion(geometry.to_string());
+        let geometry = geometry.trim();
+
+        let mut parts = geometry.split('+');
+        let size = parts.next().ok_or_else(invalid)?;
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        let width = width.parse::<u32>().map_err(|_| invalid())?;
+        let height = height.parse::<u32>().map_err(|_| invalid())?;
+
+        let (x, y) = match (parts.next(), parts.next(), parts.next()) {
+            (None, None, None) => (0, 0),
+            (Some(x), Some(y), None) => (
+                x.parse::<u32>().map_err(|_| invalid())?,
+                y.parse::<u32>().map_err(|_| invalid())?,
+            ),
+            _ => return Err(invalid()),
+        };
+
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self { x, y, width, height })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureResult {
+    pub output_file: String,
+    pub duration: f64,
+    pub backend: CaptureBackend,
+    pub file_size_mb: Option<f64>,
+    pub command: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScreenCapture {
+    backend: CaptureBackend,
+    output: PathBuf,
+    duration: f64,
+    framerate: u32,
+    region: Option<CaptureRegion>,
+    display: Option<String>,
+    capture_cursor: bool,
+}
+
+impl ScreenCapture {
+    pub fn new(backend: CaptureBackend, output: impl AsRef<Path>, duration: f64) -> Self {
+        Self {
+            backend,
+            output: output.as_ref().to_path_buf(),
+            duration,
+            framerate: 30,
+            region: None,
+            display: None,
+            capture_cursor: true,
+        }
+    }
+
+    pub fn for_current_platform(output: impl AsRef<Path>, duration: f64) -> Result<Self> {
+        Ok(Self::new(CaptureBackend::for_current_platform()?, output, duration))
+    }
+
+    /// Default output path for a capture started now, e.g. `capture_20240131_142501.mp4`
+    pub fn default_output_path(output_dir: impl AsRef<Path>) -> PathBuf {
+        let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        output_dir.as_ref().join(format!("capture_{}.mp4", stamp))
+    }
+
+    pub fn set_framerate(&mut self, framerate: u32) {
+        self.framerate = framerate;
+    }
+
+    pub fn set_region(&mut self, region: Option<CaptureRegion>) {
+        self.region = region;
+    }
+
+    /// Overrides the grab device: X11 display (`:1.0`), gdigrab target
+    /// (`desktop` or `title=Window Name`) or AVFoundation device (`2:none`)
+    pub fn set_display(&mut self, display: Option<String>) {
+        self.display = display;
+    }
+
+    pub fn set_capture_cursor(&mut self, capture_cursor: bool) {
+        self.capture_cursor = capture_cursor;
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// Checks the duration is a positive number of seconds and the region,
+    /// if any, has an even size, which libx264's yuv420p output needs
+    pub fn validate(&self) -> Result<()> {
+        if !self.duration.is_finite() || self.duration <= 0.0 {
+            return Err(VideoClipError::InvalidTimeRange { start: 0.0, end: self.duration });
+        }
+        if let Some(region) = self.region {
+            if region.width % 2 != 0 || region.height % 2 != 0 {
+                return Err(VideoClipError::InvalidOptions(format!(
+                    "capture region {}x{} must have an even width and height (try {}x{})",
+                    region.width, region.height, region.width.max(2) & !1, region.height.max(2) & !1
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-f".into(), self.backend.input_format().into(),
+            "-framerate".into(), self.framerate.to_string(),
+        ];
+
+        let display = self.display.clone().unwrap_or_else(|| self.backend.default_display());
+        let cursor = if self.capture_cursor { "1" } else { "0" };
+
+        // Each grab device expresses regions differently; AVFoundation has no
+        // native region support so the frame is cropped after capture instead
+        let mut filters = FilterGraph::new();
+        match self.backend {
+            CaptureBackend::X11Grab => {
+                args.extend(["-draw_mouse".into(), cursor.into()]);
+                let input = match self.region {
+                    Some(region) => {
+                        args.extend(["-video_size".into(), format!("{}x{}", region.width, region.height)]);
+                        format!("{}+{},{}", display, region.x, region.y)
+                    }
+                    None => display,
+                };
+                args.extend(["-i".into(), input]);
+            }
+            CaptureBackend::GdiGrab => {
+                args.extend(["-draw_mouse".into(), cursor.into()]);
+                if let Some(region) = self.region {
+                    args.extend([
+                        "-offset_x".into(), region.x.to_string(),
+                        "-offset_y".into(), region.y.to_string(),
+                        "-video_size".into(), format!("{}x{}", region.width, region.height),
+                    ]);
+                }
+                args.extend(["-i".into(), display]);
+            }
+            CaptureBackend::AvFoundation => {
+                args.extend(["-capture_cursor".into(), cursor.into()]);
+                args.extend(["-i".into(), display]);
+                if let Some(r) = self.region {
+                    filters.push(Filter::new("crop").arg(r.width).arg(r.height).arg(r.x).arg(r.y));
+                }
+            }
+        }
+
+        args.extend(["-t".into(), self.duration.to_string()]);
+
+        args.extend(filters.to_args());
+
+        // Fast H.264 settings keep up with real time; the result is meant to be clipped afterwards
+        args.extend([
+            "-c:v".into(), "libx264".into(),
+            "-preset".into(), "ultrafast".into(),
+            "-pix_fmt".into(), "yuv420p".into(),
+            "-y".into(),
+            self.output.display().to_string(),
+        ]);
+
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<CaptureResult> {
+        self.validate()?;
+
+        if let Some(parent) = self.output.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Screen capture failed")?;
+
+        let file_size_mb = self.output.metadata()
+            .ok()
+            .map(|m| m.len() as f64 / (1024.0 * 1024.0));
+
+        Ok(CaptureResult {
+            output_file: self.output.display().to_string(),
+            duration: self.duration,
+            backend: self.backend,
+            file_size_mb,
+            command: self.get_command_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod region_tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_size_only() {
+            let region = CaptureRegion::parse("1280x720").unwrap();
+            assert_eq!(region, CaptureRegion { x: 0, y: 0, width: 1280, height: 720 });
+        }
+
+        #[test]
+        fn test_parse_with_offset() {
+            let region = CaptureRegion::parse("800x600+100+50").unwrap();
+            assert_eq!(region, CaptureRegion { x: 100, y: 50, width: 800, height: 600 });
+        }
+
+        #[test]
+        fn test_parse_invalid_regions() {
+            assert!(CaptureRegion::parse("").is_err());
+            assert!(CaptureRegion::parse("1280").is_err());
+            assert!(CaptureRegion::parse("0x720").is_err());
+            assert!(CaptureRegion::parse("1280x720+10").is_err());
+            assert!(CaptureRegion::parse("1280x720+10+20+30").is_err());
+            assert!(CaptureRegion::parse("widexhigh").is_err());
+        }
+    }
+
+    mod command_building_tests {
+        use super::*;
+
+        #[test]
+        fn test_x11grab_full_screen() {
+            let mut capture = ScreenCapture::new(CaptureBackend::X11Grab, "demo.mp4", 30.0);
+            capture.set_display(Some(":1.0".to_string()));
+
+            let cmd_string = capture.get_command_string();
+            assert!(cmd_string.contains("-f x11grab"));
+            assert!(cmd_string.contains("-framerate 30"));
+            assert!(cmd_string.contains("-draw_mouse 1"));
+            assert!(cmd_string.contains("-i :1.0"));
+            assert!(cmd_string.contains("-t 30"));
+            assert!(cmd_string.ends_with("-y demo.mp4"));
+            assert!(!cmd_string.contains("-video_size"));
+        }
+
+        #[test]
+        fn test_x11grab_region() {
+            let mut capture = ScreenCapture::new(CaptureBackend::X11Grab, "demo.mp4", 10.0);
+            capture.set_display(Some(":0.0".to_string()));
+            capture.set_region(Some(CaptureRegion::parse("640x480+10+20").unwrap()));
+
+            let cmd_string = capture.get_command_string();
+            assert!(cmd_string.contains("-video_size 640x480"));
+            assert!(cmd_string.contains("-i :0.0+10,20"));
+        }
+
+        #[test]
+        fn test_gdigrab_region() {
+            let mut capture = ScreenCapture::new(CaptureBackend::GdiGrab, "demo.mp4", 5.0);
+            capture.set_region(Some(CaptureRegion::parse("640x480+10+20").unwrap()));
+            capture.set_capture_cursor(false);
+
+            let cmd_string = capture.get_command_string();
+            assert!(cmd_string.contains("-f gdigrab"));
+            assert!(cmd_string.contains("-draw_mouse 0"));
+            assert!(cmd_string.contains("-offset_x 10 -offset_y 20 -video_size 640x480"));
+            assert!(cmd_string.contains("-i desktop"));
+        }
+
+        #[test]
+        fn test_avfoundation_region_uses_crop() {
+            let mut capture = ScreenCapture::new(CaptureBackend::AvFoundation, "demo.mp4", 5.0);
+            capture.set_region(Some(CaptureRegion::parse("640x480+10+20").unwrap()));
+            capture.set_framerate(60);
+
+            let cmd_string = capture.get_command_string();
+            assert!(cmd_string.contains("-f avfoundation"));
+            assert!(cmd_string.contains("-framerate 60"));
+            assert!(cmd_string.contains("-capture_cursor 1"));
+            assert!(cmd_string.contains("-i 1:none"));
+            assert!(cmd_string.contains("-vf crop=640:480:10:20"));
+        }
+
+        #[test]
+        fn test_build_command_args_match_string() {
+            let capture = ScreenCapture::new(CaptureBackend::GdiGrab, "demo.mp4", 5.0);
+            let command = capture.build_command();
+            let args: Vec<String> = command.get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect();
+
+            assert_eq!(command.get_program(), "ffmpeg");
+            assert_eq!(args, capture.build_args());
+        }
+    }
+
+    mod validation_tests {
+        use super::*;
+
+        #[test]
+        fn test_rejects_non_positive_duration() {
+            let capture = ScreenCapture::new(CaptureBackend::X11Grab, "demo.mp4", 0.0);
+            assert!(matches!(capture.validate(), Err(VideoClipError::InvalidTimeRange { .. })));
+            let capture = ScreenCapture::new(CaptureBackend::X11Grab, "demo.mp4", f64::NAN);
+            assert!(matches!(capture.validate(), Err(VideoClipError::InvalidTimeRange { .. })));
+        }
+
+        #[test]
+        fn test_rejects_odd_region_sizes() {
+            let mut capture = ScreenCapture::new(CaptureBackend::X11Grab, "demo.mp4", 10.0);
+            capture.set_region(Some(CaptureRegion::parse("1281x720").unwrap()));
+            let err = capture.validate().unwrap_err().to_string();
+            assert!(err.contains("1280x720"), "{}", err);
+            capture.set_region(Some(CaptureRegion::parse("1x1").unwrap()));
+            let err = capture.validate().unwrap_err().to_string();
+            assert!(err.contains("(try 2x2)"), "{}", err);
+            capture.set_region(Some(CaptureRegion::parse("1280x720+1+1").unwrap()));
+            assert!(capture.validate().is_ok());
+        }
+
+        #[test]
+        fn test_default_output_path() {
+            let path = ScreenCapture::default_output_path("downloads");
+            let filename = path.file_name().unwrap().to_string_lossy();
+            assert!(filename.starts_with("capture_"));
+            assert!(filename.ends_with(".mp4"));
+            assert_eq!(path.parent().unwrap(), Path::new("downloads"));
+        }
+    }
+}
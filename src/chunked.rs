@@ -0,0 +1,317 @@
+//! Chunked encoding
+//! Splits a long re-encode into fixed-length pieces that are encoded
+//! independently (optionally in parallel) into `<output>.chunks/` and joined
+//! with the concat demuxer. Finished chunks survive a failed run, so rerunning
+//! the same request only encodes what's missing.
+
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::FFmpegCommand;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ChunkOptions {
+    /// Length of each chunk in minutes
+    pub minutes: f64,
+    /// Chunks encoded at the same time
+    #[serde(default = "default_parallel")]
+    pub parallel: usize,
+}
+
+fn default_parallel() -> usize {
+    1
+}
+
+impl ChunkOptions {
+    pub fn new(minutes: f64) -> Self {
+        Self {
+            minutes,
+            parallel: default_parallel(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.minutes.is_finite() && self.minutes > 0.0) {
+            return Err(VideoClipError::InvalidOptions(format!("chunk length must be positive, got {} minutes", self.minutes)));
+        }
+        if self.parallel == 0 {
+            return Err(VideoClipError::InvalidOptions("chunk parallelism must be at least 1".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// `[start, end)` ranges of at most `chunk_seconds`; a sliver shorter than a
+/// second at the end is folded into the previous chunk
+pub fn plan_chunks(start: f64, end: f64, chunk_seconds: f64) -> Vec<(f64, f64)> {
+    let mut chunks = Vec::new();
+    let mut chunk_start = start;
+    while chunk_start < end {
+        let mut chunk_end = (chunk_start + chunk_seconds).min(end);
+        if end - chunk_end < 1.0 {
+            chunk_end = end;
+        }
+        chunks.push((chunk_start, chunk_end));
+        chunk_start = chunk_end;
+    }
+    chunks
+}
+
+/// Containers chunks can be written in; chunk audio is always AAC, which
+/// WebM can't hold
+pub const CONTAINERS: &[&str] = &["mp4", "mov", "m4v", "mkv"];
+
+fn chunk_name(index: usize, extension: &str) -> String {
+    format!("chunk_{:03}.{}", index, extension)
+}
+
+#[derive(Debug, Clone)]
+pub struct ChunkedCommand {
+    output: PathBuf,
+    /// Chunks are written in the joined clip's container
+    extension: String,
+    chunks: Vec<FFmpegCommand>,
+    parallel: usize,
+}
+
+/// How a chunked run went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkProgress {
+    pub total: usize,
+    /// Chunks left over from an earlier, interrupted run
+    pub reused: usize,
+}
+
+impl ChunkedCommand {
+    /// One chunk per planned range, each a copy of `template` (encoder,
+    /// filters, audio) cut to its own range
+    pub fn new(template: &FFmpegCommand, output: impl AsRef<Path>, start: f64, end: f64, options: &ChunkOptions) -> Self {
+        let output = output.as_ref().to_path_buf();
+        let work_dir = Self::work_dir_for(&output);
+        let extension = output.extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_else(|| "mp4".to_string());
+        let chunks = plan_chunks(start, end, options.minutes * 60.0)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (chunk_start, chunk_end))| {
+                template.with_range(work_dir.join(chunk_name(i, &extension)), chunk_start, chunk_end - chunk_start)
+            })
+            .collect();
+
+        Self {
+            output,
+            extension,
+            chunks,
+            parallel: options.parallel,
+        }
+    }
+
+    fn work_dir_for(output: &Path) -> PathBuf {
+        let mut dir = output.as_os_str().to_owned();
+        dir.push(".chunks");
+        PathBuf::from(dir)
+    }
+
+    /// Where chunks (and the resume state) are kept until the clip is joined
+    pub fn work_dir(&self) -> PathBuf {
+        Self::work_dir_for(&self.output)
+    }
+
+    pub fn chunks(&self) -> &[FFmpegCommand] {
+        &self.chunks
+    }
+
+    pub fn chunk_path(&self, index: usize) -> PathBuf {
+        self.work_dir().join(chunk_name(index, &self.extension))
+    }
+
+    /// Chunks encoded at the same time
+    pub fn parallel(&self) -> usize {
+        self.parallel
+    }
+
+    /// Written once a chunk has encoded completely; FFmpeg leaves partial
+    /// files behind on failure, so the chunk itself existing proves nothing
+    #[cfg(not(feature = "wasm"))]
+    fn done_marker(&self, index: usize) -> PathBuf {
+        self.work_dir().join(format!("chunk_{:03}.done", index))
+    }
+
+    /// Every chunk's command line; a work dir from a different plan is discarded
+    pub fn plan(&self) -> String {
+        self.chunks.iter().map(|c| c.get_command_string() + "\n").collect()
+    }
+
+    pub fn concat_list_path(&self) -> PathBuf {
+        self.work_dir().join("chunks.txt")
+    }
+
+    /// Concat demuxer list; entries are relative to the list file
+    pub fn concat_list(&self) -> String {
+        (0..self.chunks.len())
+            .map(|i| format!("file '{}'\n", chunk_name(i, &self.extension)))
+            .collect()
+    }
+
+    /// Joins the chunks; the template's metadata goes on the joined clip
+    pub fn concat_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-f".into(), "concat".into(),
+            "-safe".into(), "0".into(),
+            "-i".into(), self.concat_list_path().display().to_string(),
+            "-c".into(), "copy".into(),
+        ];
+        if matches!(self.extension.as_str(), "mp4" | "mov" | "m4v") {
+            args.extend(["-movflags".into(), "+faststart".into()]);
+        }
+        if let Some(metadata) = self.chunks.first().and_then(|c| c.metadata()) {
+            args.extend(metadata.ffmpeg_args());
+        }
+        args.extend(["-y".into(), self.output.display().to_string()]);
+        args
+    }
+
+    /// Every step as it would be typed, chunks first
+    pub fn get_command_string(&self) -> String {
+        self.chunks.iter()
+            .map(|c| c.get_command_string())
+            .chain(std::iter::once(format!("{} {}", crate::tools::ffmpeg(), self.concat_args().join(" "))))
+            .collect::<Vec<_>>()
+            .join(" && ")
+    }
+
+    /// Encodes missing chunks, joins them and removes the work dir. On
+    /// failure the finished chunks are kept for the next attempt.
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<ChunkProgress> {
+        self.execute_with_progress(|_| {})
+    }
+
+    /// Like `execute`, calling `on_chunk` with the number of chunks done
+    /// (reused ones included) each time one finishes
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute_with_progress(&self, on_chunk: impl Fn(usize) + Sync) -> Result<ChunkProgress> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        let work_dir = self.work_dir();
+        let plan_path = work_dir.join("plan.txt");
+        let plan = self.plan();
+        if work_dir.exists() && std::fs::read_to_string(&plan_path).ok().as_deref() != Some(plan.as_str()) {
+            log::info!("Discarding chunks from a different request in {}", work_dir.display());
+            std::fs::remove_dir_all(&work_dir)?;
+        }
+        std::fs::create_dir_all(&work_dir)?;
+        std::fs::write(&plan_path, &plan)?;
+
+        let pending: Vec<usize> = (0..self.chunks.len())
+            .filter(|&i| !self.done_marker(i).exists())
+            .collect();
+        let progress = ChunkProgress {
+            total: self.chunks.len(),
+            reused: self.chunks.len() - pending.len(),
+        };
+
+        let next = AtomicUsize::new(0);
+        let done = AtomicUsize::new(progress.reused);
+        let first_error = Mutex::new(None);
+        // Chunks run wherever this thread runs FFmpeg, a batch worker's host
+        // included, and are cancelled with it
+        let executor = crate::tools::executor();
+        let cancel = crate::cancel::CancelHandle::current().unwrap_or_default();
+        std::thread::scope(|scope| {
+            for _ in 0..self.parallel.min(pending.len()) {
+                scope.spawn(|| cancel.scope(|| crate::tools::with_executor(executor.clone(), || {
+                    while let Some(&index) = pending.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        if first_error.lock().unwrap().is_some() {
+                            return;
+                        }
+                        let outcome = self.chunks[index].execute()
+                            .and_then(|_| std::fs::write(self.done_marker(index), b"").map_err(VideoClipError::from));
+                        if let Err(e) = outcome {
+                            let e = match e {
+                                VideoClipError::Cancelled => e,
+                                e => VideoClipError::FFmpegError(format!("Chunk {} of {} failed: {}", index + 1, self.chunks.len(), e)),
+                            };
+                            first_error.lock().unwrap().get_or_insert(e);
+                            return;
+                        }
+                        on_chunk(done.fetch_add(1, Ordering::Relaxed) + 1);
+                    }
+                })));
+            }
+        });
+        if let Some(e) = first_error.into_inner().unwrap() {
+            return Err(e);
+        }
+
+        std::fs::write(self.concat_list_path(), self.concat_list())?;
+        let cmd = crate::tools::ffmpeg_command(self.concat_args());
+        FFmpegCommand::run(cmd, "Joining chunks failed")?;
+
+        if let Err(e) = std::fs::remove_dir_all(&work_dir) {
+            log::warn!("Could not remove {}: {}", work_dir.display(), e);
+        }
+        Ok(progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::EncoderChoice;
+
+    fn command(minutes: f64, start: f64, end: f64) -> ChunkedCommand {
+        let mut template = FFmpegCommand::new("talk.mp4", "out/talk.mp4", start, end - start);
+        template.set_video_encoder(Some(EncoderChoice::new("libx264")));
+        ChunkedCommand::new(&template, "out/talk.mp4", start, end, &ChunkOptions::new(minutes))
+    }
+
+    #[test]
+    fn test_plan_chunks() {
+        assert_eq!(plan_chunks(0.0, 250.0, 100.0), vec![(0.0, 100.0), (100.0, 200.0), (200.0, 250.0)]);
+        assert_eq!(plan_chunks(10.0, 200.5, 100.0), vec![(10.0, 110.0), (110.0, 200.5)]);
+        assert_eq!(plan_chunks(0.0, 60.0, 600.0), vec![(0.0, 60.0)]);
+    }
+
+    #[test]
+    fn test_options_validation() {
+        assert!(ChunkOptions::new(10.0).validate().is_ok());
+        assert!(ChunkOptions::new(0.0).validate().is_err());
+        assert!(ChunkOptions { minutes: 5.0, parallel: 0 }.validate().is_err());
+    }
+
+    #[test]
+    fn test_chunks_keep_encoder_and_write_to_work_dir() {
+        let chunked = command(10.0, 600.0, 2400.0);
+        assert_eq!(chunked.chunks().len(), 3);
+        assert_eq!(chunked.work_dir(), PathBuf::from("out/talk.mp4.chunks"));
+
+        let second = chunked.chunks()[1].get_command_string();
+        assert!(second.contains("-i talk.mp4 -ss 1200 -t 600"));
+        assert!(second.contains("-c:v libx264"));
+        assert!(second.ends_with("-y out/talk.mp4.chunks/chunk_001.mp4"));
+        assert_eq!(chunked.chunk_path(1), PathBuf::from("out/talk.mp4.chunks/chunk_001.mp4"));
+    }
+
+    #[test]
+    fn test_concat_step() {
+        let chunked = command(1.0, 0.0, 90.0);
+        assert_eq!(chunked.concat_list(), "file 'chunk_000.mp4'\nfile 'chunk_001.mp4'\n");
+        let commands = chunked.get_command_string();
+        assert_eq!(commands.matches(" && ").count(), 2);
+        assert!(commands.ends_with("-f concat -safe 0 -i out/talk.mp4.chunks/chunks.txt -c copy -movflags +faststart -y out/talk.mp4"));
+    }
+
+    #[test]
+    fn test_chunks_use_the_clips_container() {
+        let template = FFmpegCommand::new("talk.mp4", "out/talk.mkv", 0.0, 90.0);
+        let chunked = ChunkedCommand::new(&template, "out/talk.mkv", 0.0, 90.0, &ChunkOptions::new(1.0));
+        assert_eq!(chunked.chunk_path(0), PathBuf::from("out/talk.mkv.chunks/chunk_000.mkv"));
+        assert_eq!(chunked.concat_list(), "file 'chunk_000.mkv'\nfile 'chunk_001.mkv'\n");
+        assert!(chunked.get_command_string().ends_with("-c copy -y out/talk.mkv"));
+    }
+}
@@ -0,0 +1,449 @@
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use crate::probe::{MediaInfo, StreamInfo};
+use crate::remux::DURATION_TOLERANCE;
+#[cfg(not(feature = "wasm"))]
+use crate::video_clipper::VideoClipper;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Clip comparison
+/// Reports how two renditions of the same clip differ, e.g. the output of
+/// an encoding preset before and after a change: their durations, their
+/// streams and codecs, and optionally how close the pictures are by PSNR,
+/// SSIM or VMAF. The first file is the reference the second is scored
+/// against, and is what a differently sized second file is scaled to. The
+/// same scoring backs the VMAF gate re-encoded clips can be held to.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityMetric {
+    Psnr,
+    Ssim,
+    Vmaf,
+}
+
+impl FromStr for QualityMetric {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "psnr" => Ok(QualityMetric::Psnr),
+            "ssim" => Ok(QualityMetric::Ssim),
+            "vmaf" => Ok(QualityMetric::Vmaf),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown quality metric '{}'", other))),
+        }
+    }
+}
+
+impl QualityMetric {
+    fn filter(&self) -> &'static str {
+        match self {
+            QualityMetric::Psnr => "psnr",
+            QualityMetric::Ssim => "ssim",
+            QualityMetric::Vmaf => "libvmaf",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QualityScores {
+    /// Average PSNR in dB; `None` for identical pictures (infinite PSNR)
+    /// as well as when it wasn't measured
+    pub psnr: Option<f64>,
+    /// Average SSIM over all planes, 0-1
+    pub ssim: Option<f64>,
+    /// Pooled VMAF score, 0-100
+    pub vmaf: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompareReport {
+    pub file_a: String,
+    pub file_b: String,
+    pub duration_a: Option<f64>,
+    pub duration_b: Option<f64>,
+    /// How much longer `b` is than `a`, in seconds
+    pub duration_delta: Option<f64>,
+    /// Every way the streams of `b` differ from those of `a`; empty when
+    /// they have the same streams, codecs and formats
+    pub differences: Vec<String>,
+    /// Picture quality of `b` against `a`, when metrics were asked for
+    pub quality: Option<QualityScores>,
+}
+
+/// What a clip that scores below its [`QualityGate`] does once retries run out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GateAction {
+    /// The clip is deleted and the request fails
+    #[default]
+    Fail,
+    /// The clip is kept with a warning
+    Warn,
+}
+
+fn default_min_vmaf() -> f64 {
+    90.0
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+/// Minimum VMAF a re-encoded clip must score against its range of the
+/// source. A clip that falls short is encoded again at a higher quality
+/// (see [`crate::encoder::EncoderChoice::boosted_args`]) up to `retries`
+/// times.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct QualityGate {
+    #[serde(default = "default_min_vmaf")]
+    pub min_vmaf: f64,
+    #[serde(default)]
+    pub action: GateAction,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+impl Default for QualityGate {
+    fn default() -> Self {
+        Self { min_vmaf: default_min_vmaf(), action: GateAction::default(), retries: default_retries() }
+    }
+}
+
+/// Each retry lowers a CRF by 4, so a few are enough to reach near-lossless
+const MAX_RETRIES: u32 = 5;
+
+impl QualityGate {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.min_vmaf > 0.0 && self.min_vmaf <= 100.0) {
+            return Err(VideoClipError::InvalidOptions(format!("minimum VMAF must be between 0 and 100, got {}", self.min_vmaf)));
+        }
+        if self.retries > MAX_RETRIES {
+            return Err(VideoClipError::InvalidOptions(format!("at most {} quality retries, got {}", MAX_RETRIES, self.retries)));
+        }
+        Ok(())
+    }
+
+    pub fn passes(&self, vmaf: f64) -> bool {
+        vmaf >= self.min_vmaf
+    }
+}
+
+/// How a clip fared against its [`QualityGate`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct QualityCheck {
+    /// Score of the clip that was kept
+    pub vmaf: f64,
+    pub min_vmaf: f64,
+    /// Encodes made, including the first
+    pub attempts: u32,
+    pub passed: bool,
+}
+
+/// Properties worth comparing, as displayed
+fn properties(stream: &StreamInfo) -> Vec<(&'static str, Option<String>)> {
+    let mut properties = vec![("codec", stream.codec_name.clone())];
+    match stream.codec_type.as_str() {
+        "video" => properties.extend([
+            ("size", stream.width.zip(stream.height).map(|(w, h)| format!("{}x{}", w, h))),
+            ("pixel format", stream.pix_fmt.clone()),
+            ("frame rate", stream.avg_frame_rate.or(stream.frame_rate).map(|fps| format!("{:.3}", fps))),
+            ("bit rate", stream.bit_rate.map(kbps)),
+        ]),
+        "audio" => properties.extend([
+            ("sample rate", stream.sample_rate.map(|rate| rate.to_string())),
+            ("channels", stream.channels.map(|channels| channels.to_string())),
+            ("bit rate", stream.bit_rate.map(kbps)),
+        ]),
+        _ => {}
+    }
+    properties
+}
+
+fn kbps(bit_rate: u64) -> String {
+    format!("{}k", bit_rate / 1000)
+}
+
+impl CompareReport {
+    /// Compares the probes of two files stream by stream, in order
+    pub fn compare(file_a: impl Into<String>, file_b: impl Into<String>, a: &MediaInfo, b: &MediaInfo) -> Self {
+        let mut differences = Vec::new();
+        if a.streams.len() != b.streams.len() {
+            differences.push(format!("a has {} streams and b has {}", a.streams.len(), b.streams.len()));
+        }
+        for (i, (stream_a, stream_b)) in a.streams.iter().zip(&b.streams).enumerate() {
+            if stream_a.codec_type != stream_b.codec_type {
+                differences.push(format!("stream {} is {} in a and {} in b", i, stream_a.codec_type, stream_b.codec_type));
+                continue;
+            }
+            for ((property, before), (_, after)) in properties(stream_a).into_iter().zip(properties(stream_b)) {
+                if before != after {
+                    differences.push(format!(
+                        "stream {} ({}) {}: {} -> {}",
+                        i,
+                        stream_a.codec_type,
+                        property,
+                        before.as_deref().unwrap_or("unknown"),
+                        after.as_deref().unwrap_or("unknown")
+                    ));
+                }
+            }
+        }
+
+        Self {
+            file_a: file_a.into(),
+            file_b: file_b.into(),
+            duration_a: a.duration,
+            duration_b: b.duration,
+            duration_delta: a.duration.zip(b.duration).map(|(a, b)| b - a),
+            differences,
+            quality: None,
+        }
+    }
+
+    /// No stream differences and durations within a frame or so of each other
+    pub fn is_equivalent(&self) -> bool {
+        self.differences.is_empty() && self.duration_delta.is_none_or(|delta| delta.abs() <= DURATION_TOLERANCE)
+    }
+}
+
+/// Parses the summary lines the metric filters write to stderr:
+/// `PSNR y:.. u:.. v:.. average:36.12 ..`, `SSIM Y:.. All:0.985 (18.3)` and
+/// `VMAF score: 94.23`
+pub fn parse_quality(stderr: &str) -> QualityScores {
+    let value_after = |line: &str, key: &str| -> Option<f64> {
+        let rest = &line[line.find(key)? + key.len()..];
+        rest.split_whitespace().next()?.parse().ok()
+    };
+    let mut scores = QualityScores::default();
+    for line in stderr.lines() {
+        if line.contains("] PSNR ") {
+            scores.psnr = value_after(line, "average:").filter(|psnr: &f64| psnr.is_finite());
+        } else if line.contains("] SSIM ") {
+            scores.ssim = value_after(line, "All:");
+        } else if line.contains("VMAF score") {
+            scores.vmaf = value_after(line, "VMAF score:");
+        }
+    }
+    scores
+}
+
+/// Scores one file's video against another's with FFmpeg's metric filters
+#[derive(Debug, Clone)]
+pub struct QualityCommand {
+    reference: PathBuf,
+    distorted: PathBuf,
+    metrics: Vec<QualityMetric>,
+    /// Reference picture size the distorted video is scaled to, if it differs
+    scale_to: Option<(u32, u32)>,
+    /// Start and length of the part of the reference that was clipped
+    reference_range: Option<(f64, f64)>,
+}
+
+impl QualityCommand {
+    pub fn new(reference: impl AsRef<Path>, distorted: impl AsRef<Path>, metrics: &[QualityMetric]) -> Self {
+        let mut unique = Vec::new();
+        for metric in metrics {
+            if !unique.contains(metric) {
+                unique.push(*metric);
+            }
+        }
+        Self {
+            reference: reference.as_ref().to_path_buf(),
+            distorted: distorted.as_ref().to_path_buf(),
+            metrics: unique,
+            scale_to: None,
+            reference_range: None,
+        }
+    }
+
+    pub fn set_scale_to(&mut self, size: Option<(u32, u32)>) {
+        self.scale_to = size;
+    }
+
+    /// Scores against `duration` seconds of the reference from `start`, for
+    /// a clip scored against its whole source
+    pub fn set_reference_range(&mut self, start: f64, duration: f64) {
+        self.reference_range = Some((start, duration));
+    }
+
+    /// Both videos restarted at zero and split once per metric; each metric
+    /// takes the distorted picture first and the reference second
+    pub fn filter_graph(&self) -> FilterGraph {
+        let count = self.metrics.len();
+        let labels = |prefix: &str| -> Vec<String> { (0..count).map(|i| format!("{}{}", prefix, i)).collect() };
+        let (references, distorted) = (labels("ref"), labels("dist"));
+        fn as_strs(labels: &[String]) -> Vec<&str> {
+            labels.iter().map(String::as_str).collect()
+        }
+
+        let mut graph = FilterGraph::new();
+        graph.add_chain(
+            &["0:v"],
+            vec![Filter::new("setpts").arg("PTS-STARTPTS"), Filter::new("split").arg(count)],
+            &as_strs(&references),
+        );
+        let mut filters = Vec::new();
+        if let Some((width, height)) = self.scale_to {
+            filters.push(Filter::new("scale").arg(width).arg(height).option("flags", "bicubic"));
+        }
+        filters.extend([Filter::new("setpts").arg("PTS-STARTPTS"), Filter::new("split").arg(count)]);
+        graph.add_chain(&["1:v"], filters, &as_strs(&distorted));
+        for (i, metric) in self.metrics.iter().enumerate() {
+            graph.add_chain(&[distorted[i].as_str(), references[i].as_str()], vec![Filter::new(metric.filter())], &[]);
+        }
+        graph
+    }
+
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec!["-hide_banner".into()];
+        if let Some((start, duration)) = self.reference_range {
+            args.extend(["-ss".into(), start.to_string(), "-t".into(), duration.to_string()]);
+        }
+        args.extend([
+            "-i".into(), self.reference.display().to_string(),
+            "-i".into(), self.distorted.display().to_string(),
+            "-lavfi".into(), self.filter_graph().to_string(),
+        ]);
+        args.extend(["-an", "-f", "null", "-"].iter().map(|s| s.to_string()));
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<QualityScores> {
+        if self.metrics.contains(&QualityMetric::Vmaf) {
+            crate::capabilities::FfmpegCapabilities::cached()?.require_filter("libvmaf", "VMAF scoring")?;
+        }
+        let output = crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Quality measurement failed")?;
+        Ok(parse_quality(&String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+impl VideoClipper {
+    /// Compares `b` with `a`, scoring its picture against `a`'s with
+    /// `metrics` when there are any
+    pub fn compare(&self, a: &Path, b: &Path, metrics: &[QualityMetric]) -> Result<CompareReport> {
+        self.validate_video_input(a)?;
+        self.validate_video_input(b)?;
+        let (info_a, info_b) = (crate::probe::probe(a)?, crate::probe::probe(b)?);
+        let mut report = CompareReport::compare(a.display().to_string(), b.display().to_string(), &info_a, &info_b);
+        if !metrics.is_empty() {
+            let size = |info: &MediaInfo| info.video_stream().and_then(|v| v.width.zip(v.height));
+            let (size_a, size_b) = (size(&info_a), size(&info_b));
+            if size_a.is_none() || size_b.is_none() {
+                return Err(VideoClipError::InvalidOptions("quality metrics need a video stream in both files".to_string()));
+            }
+            let mut command = QualityCommand::new(a, b, metrics);
+            command.set_scale_to(size_a.filter(|_| size_a != size_b));
+            report.quality = Some(command.execute()?);
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(codec: &str, width: u32, height: u32, bit_rate: u64) -> StreamInfo {
+        StreamInfo {
+            codec_type: "video".to_string(),
+            codec_name: Some(codec.to_string()),
+            width: Some(width),
+            height: Some(height),
+            pix_fmt: Some("yuv420p".to_string()),
+            avg_frame_rate: Some(30.0),
+            bit_rate: Some(bit_rate),
+            ..Default::default()
+        }
+    }
+
+    fn audio(codec: &str, channels: u32) -> StreamInfo {
+        StreamInfo {
+            codec_type: "audio".to_string(),
+            codec_name: Some(codec.to_string()),
+            sample_rate: Some(48000),
+            channels: Some(channels),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compare_streams() {
+        let a = MediaInfo { duration: Some(30.0), streams: vec![video("h264", 1920, 1080, 6_000_000), audio("aac", 2)], ..Default::default() };
+        let same = MediaInfo { duration: Some(30.02), ..a.clone() };
+        assert!(CompareReport::compare("a.mp4", "b.mp4", &a, &same).is_equivalent());
+
+        let b = MediaInfo { duration: Some(29.5), streams: vec![video("hevc", 1280, 720, 2_500_000)], ..Default::default() };
+        let report = CompareReport::compare("old.mp4", "new.mp4", &a, &b);
+        assert_eq!(report.duration_delta, Some(-0.5));
+        assert_eq!(
+            report.differences,
+            vec![
+                "a has 2 streams and b has 1",
+                "stream 0 (video) codec: h264 -> hevc",
+                "stream 0 (video) size: 1920x1080 -> 1280x720",
+                "stream 0 (video) bit rate: 6000k -> 2500k",
+            ]
+        );
+        assert!(!report.is_equivalent());
+    }
+
+    #[test]
+    fn test_parse_quality() {
+        let stderr = "frame=  900 fps=120 q=-0.0 Lsize=N/A time=00:00:30.00 bitrate=N/A speed=4x
+            [Parsed_psnr_4 @ 0x55d0] PSNR y:38.21 u:43.90 v:44.12 average:39.45 min:31.02 max:48.77
+            [Parsed_ssim_5 @ 0x55d1] SSIM Y:0.972 (15.51) U:0.985 (18.24) V:0.986 (18.53) All:0.976 (16.20)
+            [Parsed_libvmaf_6 @ 0x55d2] VMAF score: 93.417
+";
+        assert_eq!(parse_quality(stderr), QualityScores { psnr: Some(39.45), ssim: Some(0.976), vmaf: Some(93.417) });
+
+        let identical = "[Parsed_psnr_2 @ 0x55d0] PSNR y:inf u:inf v:inf average:inf min:inf max:inf
+";
+        assert_eq!(parse_quality(identical), QualityScores::default());
+    }
+
+    #[test]
+    fn test_quality_command() {
+        let mut command = QualityCommand::new("a.mp4", "b.mp4", &[QualityMetric::Psnr, QualityMetric::Vmaf, QualityMetric::Psnr]);
+        assert_eq!(
+            command.filter_graph().to_string(),
+            "[0:v]setpts=PTS-STARTPTS,split=2[ref0][ref1];[1:v]setpts=PTS-STARTPTS,split=2[dist0][dist1];\
+             [dist0][ref0]psnr;[dist1][ref1]libvmaf"
+        );
+        command.set_scale_to(Some((1920, 1080)));
+        let args = command.get_command_string();
+        assert!(args.starts_with("ffmpeg -hide_banner -i a.mp4 -i b.mp4 -lavfi "));
+        assert!(args.contains("[1:v]scale=1920:1080:flags=bicubic,setpts"));
+        assert!(args.ends_with(" -an -f null -"));
+        assert_eq!("SSIM".parse::<QualityMetric>().unwrap(), QualityMetric::Ssim);
+        assert!("mos".parse::<QualityMetric>().is_err());
+    }
+
+    #[test]
+    fn test_quality_gate() {
+        let gate = QualityGate::default();
+        assert!(gate.validate().is_ok());
+        assert!(gate.passes(93.4) && !gate.passes(89.9));
+        assert!(QualityGate { min_vmaf: 120.0, ..Default::default() }.validate().is_err());
+        assert!(QualityGate { retries: 10, ..Default::default() }.validate().is_err());
+
+        let mut command = QualityCommand::new("talk.mp4", "out/clip.mp4", &[QualityMetric::Vmaf]);
+        command.set_reference_range(90.0, 30.0);
+        assert!(command.get_command_string().starts_with("ffmpeg -hide_banner -ss 90 -t 30 -i talk.mp4 -i out/clip.mp4 -lavfi "));
+    }
+}
@@ -0,0 +1,330 @@
+use crate::auth::AuthConfig;
+use crate::container::ContainerExecutor;
+use crate::dispatch::Worker;
+use crate::error::{VideoClipError, Result};
+use crate::speech::TranscriptProvider;
+use crate::ssh::SshExecutor;
+use crate::tools::Executor;
+use crate::video_clipper::ClipRequest;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// User configuration
+/// Read from `<config dir>/video-clip-rs/config.toml`. Named presets bundle
+/// clip options so they can be applied by name:
+///
+/// ```toml
+/// [preset.vertical]
+/// video_codec = "h264"
+/// fit = { width = 1080, height = 1920, mode = "crop" }
+/// ```
+///
+/// `whisper_model` points speech recognition (captions, searching what's
+/// said) at a whisper.cpp model, in builds with the `whisper` feature.
+///
+/// `output_dir`, `ffmpeg_path` and `default_preset` can also come from the
+/// `VIDEO_CLIP_OUTPUT_DIR`, `VIDEO_CLIP_FFMPEG_PATH` and `VIDEO_CLIP_PRESET`
+/// environment variables, which the file overrides (see
+/// [`crate::VideoClipper::from_env`]). A `[container]` table runs FFmpeg in
+/// a Docker or Podman image instead (see [`crate::container`]), and an
+/// `[ssh]` table on another host (see [`crate::ssh`]). Batches are shared
+/// out among any `[[workers]]` (see [`crate::dispatch`]), and `[auth]` locks
+/// down the server (see [`crate::auth`]).
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// `ClipRequest` fields by preset name
+    #[serde(default)]
+    pub preset: BTreeMap<String, Map<String, Value>>,
+    /// ggml model file, e.g. `ggml-base.en.bin`
+    #[serde(default)]
+    pub whisper_model: Option<String>,
+    /// Spoken language for the model (`en`, `de`, ...); detected when unset
+    #[serde(default)]
+    pub whisper_language: Option<String>,
+    /// Where clips go when the request doesn't say
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+    /// The ffmpeg binary to run instead of the one on the PATH; ffprobe is
+    /// taken from the same directory. It applies to the whole process, set
+    /// by [`crate::VideoClipper::from_env`] or [`crate::tools::set_ffmpeg_path`].
+    #[serde(default)]
+    pub ffmpeg_path: Option<PathBuf>,
+    /// Preset applied to requests that don't name one
+    #[serde(default)]
+    pub default_preset: Option<String>,
+    /// Image to run ffmpeg and ffprobe in, for hosts without them
+    #[serde(default)]
+    pub container: Option<ContainerExecutor>,
+    /// Host to run ffmpeg and ffprobe on, where the files are
+    #[serde(default)]
+    pub ssh: Option<SshExecutor>,
+    /// Where batches are clipped, when across several places
+    #[serde(default)]
+    pub workers: Vec<Worker>,
+    /// API keys and JWT secret the server checks
+    #[serde(default)]
+    pub auth: AuthConfig,
+}
+
+/// Environment variables read by [`Config::from_env`]
+pub const OUTPUT_DIR_VAR: &str = "VIDEO_CLIP_OUTPUT_DIR";
+pub const FFMPEG_PATH_VAR: &str = "VIDEO_CLIP_FFMPEG_PATH";
+pub const PRESET_VAR: &str = "VIDEO_CLIP_PRESET";
+
+/// Request fields a preset can't set: they identify the clip rather than shape it
+const PER_REQUEST_FIELDS: [&str; 6] = ["input_file", "start_time", "end_time", "preset_name", "set_fields", "schema_version"];
+
+impl Config {
+    /// `<config dir>/video-clip-rs/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("video-clip-rs").join("config.toml"))
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let config: Self = toml::from_str(toml)
+            .map_err(|e| VideoClipError::InvalidOptions(format!("invalid config: {}", e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// The default config file, or an empty config when there isn't one
+    pub fn load() -> Result<Self> {
+        match Self::default_path() {
+            Some(path) if path.is_file() => Self::from_file(path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Settings from the `VIDEO_CLIP_*` environment variables; empty ones
+    /// count as unset
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name| var(name).filter(|value| !value.trim().is_empty());
+        Self {
+            output_dir: var(OUTPUT_DIR_VAR).map(PathBuf::from),
+            ffmpeg_path: var(FFMPEG_PATH_VAR).map(PathBuf::from),
+            default_preset: var(PRESET_VAR),
+            ..Self::default()
+        }
+    }
+
+    /// This config, with `under`'s settings wherever it has none of its own
+    pub fn or(self, under: Config) -> Config {
+        let mut preset = under.preset;
+        preset.extend(self.preset);
+        Self {
+            preset,
+            whisper_model: self.whisper_model.or(under.whisper_model),
+            whisper_language: self.whisper_language.or(under.whisper_language),
+            output_dir: self.output_dir.or(under.output_dir),
+            ffmpeg_path: self.ffmpeg_path.or(under.ffmpeg_path),
+            default_preset: self.default_preset.or(under.default_preset),
+            container: self.container.or(under.container),
+            ssh: self.ssh.or(under.ssh),
+            workers: match self.workers.is_empty() {
+                true => under.workers,
+                false => self.workers,
+            },
+            auth: self.auth.or(under.auth),
+        }
+    }
+
+    /// Where FFmpeg runs, when it isn't on this host
+    pub fn executor(&self) -> Option<Executor> {
+        match (&self.ssh, &self.container) {
+            (Some(ssh), _) => Some(Executor::Ssh(ssh.clone())),
+            (None, Some(container)) => Some(Executor::Container(container.clone())),
+            (None, None) => None,
+        }
+    }
+
+    /// Every preset must only name known, shareable request fields
+    pub fn validate(&self) -> Result<()> {
+        let known = request_fields(&ClipRequest::default())?;
+        for (name, fields) in &self.preset {
+            for key in fields.keys() {
+                if !known.contains_key(key) || PER_REQUEST_FIELDS.contains(&key.as_str()) {
+                    return Err(VideoClipError::InvalidOptions(format!("preset '{}' can't set '{}'", name, key)));
+                }
+            }
+        }
+        if self.ssh.is_some() && self.container.is_some() {
+            return Err(VideoClipError::InvalidOptions("FFmpeg runs either in a container or over SSH, not both".to_string()));
+        }
+        for worker in &self.workers {
+            worker.validate()?;
+        }
+        self.auth.validate()?;
+        match &self.default_preset {
+            Some(name) if !self.preset.contains_key(name) => Err(self.unknown_preset(name)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Speech recognition with the configured whisper model, if there is one
+    pub fn transcript_provider(&self) -> Result<Option<Arc<dyn TranscriptProvider>>> {
+        self.whisper_model.as_deref()
+            .map(|model| crate::speech::whisper_provider(model, self.whisper_language.clone()))
+            .transpose()
+    }
+
+    pub fn preset_names(&self) -> Vec<&str> {
+        self.preset.keys().map(String::as_str).collect()
+    }
+
+    /// `request` with its preset's options filled in, or the default
+    /// preset's when it names none. Options the request sets itself win
+    /// over the preset: anything not at its default, and anything in its
+    /// `set_fields`.
+    pub fn apply_preset(&self, request: &ClipRequest) -> Result<ClipRequest> {
+        let Some(name) = request.preset_name.as_ref().or(self.default_preset.as_ref()) else {
+            return Ok(request.clone());
+        };
+        let preset = self.preset.get(name).ok_or_else(|| self.unknown_preset(name))?;
+
+        let defaults = request_fields(&ClipRequest::default())?;
+        if let Some(unknown) = request.set_fields.iter().find(|field| !defaults.contains_key(field.as_str())) {
+            return Err(VideoClipError::InvalidOptions(format!("set_fields names unknown option '{}'", unknown)));
+        }
+        let mut merged = preset.clone();
+        for (key, value) in request_fields(request)? {
+            let set = request.set_fields.contains(&key) || PER_REQUEST_FIELDS.contains(&key.as_str());
+            if set || defaults.get(&key) != Some(&value) {
+                merged.insert(key, value);
+            }
+        }
+        serde_json::from_value(Value::Object(merged))
+            .map_err(|e| VideoClipError::InvalidOptions(format!("preset '{}': {}", name, e)))
+    }
+
+    fn unknown_preset(&self, name: &str) -> VideoClipError {
+        VideoClipError::InvalidOptions(format!(
+            "unknown preset '{}' (available: {})",
+            name,
+            self.preset_names().join(", ")
+        ))
+    }
+}
+
+fn request_fields(request: &ClipRequest) -> Result<Map<String, Value>> {
+    match serde_json::to_value(request) {
+        Ok(Value::Object(fields)) => Ok(fields),
+        _ => Err(VideoClipError::InvalidOptions("clip request doesn't serialize to an object".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::VideoCodec;
+    use crate::ffmpeg::fit::FitMode;
+
+    const CONFIG: &str = r#"
+        [preset.vertical]
+        video_codec = "h264"
+        fit = { width = 1080, height = 1920, mode = "crop" }
+
+        [preset.quiet]
+        volume_db = -6.0
+    "#;
+
+    fn request(preset: &str) -> ClipRequest {
+        ClipRequest {
+            input_file: "talk.mp4".to_string(),
+            start_time: "1:00".to_string(),
+            end_time: "1:30".to_string(),
+            preset_name: Some(preset.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_preset_fills_in_options() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        assert_eq!(config.preset_names(), vec!["quiet", "vertical"]);
+
+        let resolved = config.apply_preset(&request("vertical")).unwrap();
+        assert_eq!(resolved.video_codec, VideoCodec::H264);
+        assert_eq!(resolved.fit.as_ref().unwrap().mode, FitMode::Crop);
+        assert_eq!(resolved.input_file, "talk.mp4");
+        assert_eq!(resolved.end_time, "1:30");
+    }
+
+    #[test]
+    fn test_request_options_override_preset() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        let request = ClipRequest { video_codec: VideoCodec::Hevc, ..request("vertical") };
+        let resolved = config.apply_preset(&request).unwrap();
+        assert_eq!(resolved.video_codec, VideoCodec::Hevc);
+        assert!(resolved.fit.is_some());
+
+        // Back to the default, on purpose
+        let copy = ClipRequest { video_codec: VideoCodec::Copy, set_fields: vec!["video_codec".to_string()], ..request.clone() };
+        let resolved = config.apply_preset(&copy).unwrap();
+        assert_eq!(resolved.video_codec, VideoCodec::Copy);
+        assert!(resolved.fit.is_some());
+        let typo = ClipRequest { set_fields: vec!["codec".to_string()], ..request };
+        assert!(config.apply_preset(&typo).is_err());
+    }
+
+    #[test]
+    fn test_unknown_preset_and_fields() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        let err = config.apply_preset(&request("podcast")).unwrap_err();
+        assert!(err.to_string().contains("available: quiet, vertical"));
+
+        assert!(Config::from_toml("[preset.bad]\ninput_file = \"a.mp4\"").is_err());
+        assert!(Config::from_toml("[preset.bad]\nloudness = 1").is_err());
+        assert!(Config::from_toml("").unwrap().preset.is_empty());
+    }
+
+    #[test]
+    fn test_environment_under_config_file() {
+        let vars = [(OUTPUT_DIR_VAR, "/clips"), (FFMPEG_PATH_VAR, "/opt/ffmpeg/bin/ffmpeg"), (PRESET_VAR, "quiet")];
+        let env = Config::from_vars(|name| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string()));
+        assert_eq!(env.output_dir.as_deref(), Some(Path::new("/clips")));
+
+        let file = Config::from_toml(&format!("output_dir = \"/srv/out\"\n{}", CONFIG)).unwrap();
+        let config = file.or(env);
+        config.validate().unwrap();
+        assert_eq!(config.output_dir.as_deref(), Some(Path::new("/srv/out")));
+        assert_eq!(config.ffmpeg_path.as_deref(), Some(Path::new("/opt/ffmpeg/bin/ffmpeg")));
+
+        // The default preset fills in requests that don't name one
+        let resolved = config.apply_preset(&ClipRequest { preset_name: None, ..request("") }).unwrap();
+        assert_eq!(resolved.volume_db, Some(-6.0));
+        let resolved = config.apply_preset(&request("vertical")).unwrap();
+        assert_eq!(resolved.volume_db, None);
+
+        assert!(Config::from_vars(|_| Some(" ".to_string())).output_dir.is_none());
+        assert!(Config::from_toml("default_preset = \"missing\"").is_err());
+    }
+
+    #[test]
+    fn test_ssh_executor_from_config_file() {
+        let config = Config::from_toml("[ssh]\nhost = \"clipper@encode-01\"\nport = 2222\nfetch = true").unwrap();
+        match config.executor() {
+            Some(Executor::Ssh(ssh)) => assert!(ssh.fetch && ssh.port == Some(2222)),
+            other => panic!("expected an SSH executor, got {:?}", other),
+        }
+        assert!(Config::from_toml("[ssh]\nhost = \"encode-01\"\n[container]\nimage = \"ffmpeg:7\"").is_err());
+    }
+
+    #[test]
+    fn test_no_preset_is_a_no_op() {
+        let request = ClipRequest { preset_name: None, ..request("") };
+        let resolved = Config::default().apply_preset(&request).unwrap();
+        assert_eq!(resolved.input_file, request.input_file);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::{VideoClipError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Running FFmpeg in a container
+/// On hosts where FFmpeg can't be installed, every ffmpeg and ffprobe run can
+/// go through `docker run` or `podman run` with an image that has them (see
+/// [`crate::tools::set_executor`]). The program is the container's
+/// entrypoint, so images that already use ffmpeg as theirs work too.
+///
+/// The working directory and the directory of every file a command names
+/// are mounted into the container. POSIX paths keep their place, so paths
+/// inside filters and concat lists still resolve; drive-letter paths from a
+/// Windows host move under `/mnt/<drive>`. Files are written as the
+/// container's user unless `user` is set (e.g. `1000:1000`); rootless
+/// Podman maps its root to the caller already.
+///
+/// Each run is named and has an init process passing signals on to FFmpeg,
+/// so a cancelled clip kills its container (`docker kill NAME`) rather than
+/// just the client that started it.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerRuntime {
+    #[default]
+    Docker,
+    Podman,
+}
+
+impl ContainerRuntime {
+    pub fn program(&self) -> &'static str {
+        match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+        }
+    }
+}
+
+impl FromStr for ContainerRuntime {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "docker" => Ok(Self::Docker),
+            "podman" => Ok(Self::Podman),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown container runtime '{}'", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerExecutor {
+    /// Image with ffmpeg and ffprobe on its PATH
+    pub image: String,
+    #[serde(default)]
+    pub runtime: ContainerRuntime,
+    /// `UID[:GID]` to run as, so outputs aren't owned by the container's root
+    #[serde(default)]
+    pub user: Option<String>,
+    /// More `run` options, e.g. `--gpus all` for NVENC
+    #[serde(default)]
+    pub run_args: Vec<String>,
+}
+
+impl ContainerExecutor {
+    pub fn new(runtime: ContainerRuntime, image: impl Into<String>) -> Self {
+        Self { image: image.into(), runtime, user: None, run_args: Vec::new() }
+    }
+
+    /// `run` arguments that execute `program args...` in a container called
+    /// `name` from the current directory
+    pub fn run_args(&self, name: &str, program: &str, args: Vec<String>) -> Vec<String> {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+        self.run_args_in(&cwd, name, program, args)
+    }
+
+    /// Arguments that stop the container `name`
+    pub fn stop_args(&self, name: &str) -> Vec<String> {
+        vec!["kill".to_string(), name.to_string()]
+    }
+
+    fn run_args_in(&self, cwd: &Path, name: &str, program: &str, args: Vec<String>) -> Vec<String> {
+        let files = file_args(&args);
+        let mut dirs: Vec<PathBuf> = files.iter()
+            .filter_map(|&i| {
+                let path = cwd.join(&args[i]);
+                match path.is_dir() {
+                    true => Some(path),
+                    false => path.parent().map(Path::to_path_buf),
+                }
+            })
+            .chain([cwd.to_path_buf()])
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        // A directory inside one already mounted comes along with it
+        let mounts: Vec<&PathBuf> = dirs.iter()
+            .filter(|dir| !dirs.iter().any(|other| other != *dir && dir.starts_with(other)))
+            .collect();
+
+        let mut run = vec!["run".to_string(), "--rm".to_string(), "--init".to_string(), "--name".to_string(), name.to_string()];
+        run.extend(["--entrypoint".to_string(), program.to_string()]);
+        run.extend(["--workdir".to_string(), container_path(&cwd.display().to_string())]);
+        if let Some(user) = &self.user {
+            run.extend(["--user".to_string(), user.clone()]);
+        }
+        for dir in &mounts {
+            let host = dir.display().to_string();
+            run.extend(["--volume".to_string(), format!("{}:{}", host, container_path(&host))]);
+        }
+        run.extend(self.run_args.iter().cloned());
+        run.push(self.image.clone());
+
+        // Files are named by their place in the container; paths inside other
+        // arguments (filters) are moved along with their directory
+        let moved: Vec<(String, String)> = mounts.iter()
+            .map(|dir| dir.display().to_string())
+            .map(|host| (container_path(&host), host))
+            .filter(|(container, host)| container != host)
+            .map(|(container, host)| (host, container))
+            .collect();
+        run.extend(args.into_iter().enumerate().map(|(i, arg)| match files.contains(&i) {
+            true => container_path(&cwd.join(&arg).display().to_string()),
+            false => moved.iter().fold(arg, |arg, (host, container)| arg.replace(host.as_str(), container)),
+        }));
+        run
+    }
+}
+
+/// Indexes of the arguments naming files: ones that exist, and the output at
+/// the end. Streams (`-`, `pipe:1`), URLs and lavfi sources aren't files.
+fn file_args(args: &[String]) -> Vec<usize> {
+    let is_file = |arg: &str| !arg.starts_with('-') && !arg.contains("://") && !arg.starts_with("pipe:");
+    (0..args.len())
+        .filter(|&i| {
+            let arg = args[i].as_str();
+            is_file(arg) && (i + 1 == args.len() || Path::new(arg).exists())
+        })
+        .collect()
+}
+
+/// Where a host path is in the container: POSIX paths stay put, and
+/// `C:\clips` becomes `/mnt/c/clips`
+pub fn container_path(host: &str) -> String {
+    let mut chars = host.chars();
+    match (chars.next(), chars.next()) {
+        (Some(drive), Some(':')) if drive.is_ascii_alphabetic() => {
+            let rest = chars.as_str().replace('\\', "/");
+            format!("/mnt/{}/{}", drive.to_ascii_lowercase(), rest.trim_matches('/'))
+                .trim_end_matches('/')
+                .to_string()
+        }
+        _ => host.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_run_args_mount_files() {
+        let work = tempdir().unwrap();
+        let media = tempdir().unwrap();
+        let input = media.path().join("talk.mp4");
+        std::fs::write(&input, b"").unwrap();
+        let args: Vec<String> = ["-ss", "10", "-i", &input.display().to_string(), "-c", "copy", "-y", "out/talk_clip.mp4"]
+            .map(String::from)
+            .to_vec();
+
+        let container = ContainerExecutor { user: Some("1000:1000".to_string()), ..ContainerExecutor::new(ContainerRuntime::Podman, "ffmpeg:7") };
+        let run = container.run_args_in(work.path(), "clip-1", "ffmpeg", args);
+        let cwd = work.path().display().to_string();
+        assert_eq!(run[..11], ["run", "--rm", "--init", "--name", "clip-1", "--entrypoint", "ffmpeg", "--workdir", &cwd, "--user", "1000:1000"]);
+        assert!(run.contains(&format!("{}:{}", cwd, cwd)));
+        let media_dir = media.path().display().to_string();
+        assert!(run.contains(&format!("{}:{}", media_dir, media_dir)));
+        assert!(run.ends_with(&["-y".to_string(), work.path().join("out/talk_clip.mp4").display().to_string()]));
+        assert_eq!(container.runtime.program(), "podman");
+    }
+
+    #[test]
+    fn test_windows_paths_move_under_mnt() {
+        assert_eq!(container_path(r"C:\Users\ana\clips\talk.mp4"), "/mnt/c/Users/ana/clips/talk.mp4");
+        assert_eq!(container_path("D:/"), "/mnt/d");
+        assert_eq!(container_path("/srv/media/talk.mp4"), "/srv/media/talk.mp4");
+    }
+
+    #[test]
+    fn test_streams_and_urls_are_not_files() {
+        let args: Vec<String> = ["-i", "https://example.com/live.m3u8", "-f", "lavfi", "-i", "sine=duration=3", "-f", "wav", "-"].map(String::from).to_vec();
+        assert!(file_args(&args).is_empty());
+        assert!("lxc".parse::<ContainerRuntime>().is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Lossless cut validation
+//! Stream-copied clips can only start on a keyframe, so the frames that end up
+//! in the file rarely match the requested range exactly. Probing the output
+//! recovers where the video really starts and ends in source time.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CutReport {
+    pub requested_start: f64,
+    pub requested_end: f64,
+    /// Source time of the first video frame in the clip
+    pub actual_start: f64,
+    /// Source time at which the last video frame stops being displayed
+    pub actual_end: f64,
+    /// `actual_start - requested_start`; positive means the clip starts late
+    pub start_delta: f64,
+    /// `actual_end - requested_end`; negative means the clip ends early
+    pub end_delta: f64,
+}
+
+/// Drift beyond this (about a frame at 24fps) is worth mentioning
+pub const DRIFT_TOLERANCE: f64 = 0.042;
+
+impl CutReport {
+    /// Builds a report from the clip's video frame timestamps. `output_start`
+    /// is the container start time, which lines up with `requested_start`
+    /// because audio (cut sample-accurately) begins there; `frame_duration`
+    /// extends the last frame to its display end.
+    pub fn from_frame_times(
+        requested_start: f64,
+        requested_end: f64,
+        output_start: f64,
+        frame_times: &[f64],
+        frame_duration: f64,
+    ) -> Option<Self> {
+        let first = frame_times.iter().copied().reduce(f64::min)?;
+        let last = frame_times.iter().copied().reduce(f64::max)?;
+
+        let actual_start = requested_start + (first - output_start);
+        let actual_end = requested_start + (last - output_start) + frame_duration;
+
+        Some(Self {
+            requested_start,
+            requested_end,
+            actual_start,
+            actual_end,
+            start_delta: actual_start - requested_start,
+            end_delta: actual_end - requested_end,
+        })
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.start_delta.abs() <= DRIFT_TOLERANCE && self.end_delta.abs() <= DRIFT_TOLERANCE
+    }
+
+    /// Human-readable drift summary, e.g. for a warning
+    pub fn summary(&self) -> String {
+        format!(
+            "video starts {:+.3}s and ends {:+.3}s from the requested range",
+            self.start_delta, self.end_delta
+        )
+    }
+}
+
+/// Probes a finished clip and compares its video frames against the range
+#[cfg(not(feature = "wasm"))]
+pub fn analyze_cut(output: impl AsRef<std::path::Path>, requested_start: f64, requested_end: f64) -> crate::error::Result<CutReport> {
+    use crate::error::VideoClipError;
+
+    let output = output.as_ref();
+    let info = crate::probe::probe(output)?;
+    let frame_duration = info.video_stream()
+        .and_then(|v| v.avg_frame_rate.or(v.frame_rate))
+        .map_or(0.0, |fps| 1.0 / fps);
+    let frame_times = crate::probe::probe_frame_times(output)?;
+
+    CutReport::from_frame_times(
+        requested_start,
+        requested_end,
+        info.start_time.unwrap_or(0.0),
+        &frame_times,
+        frame_duration,
+    )
+    .ok_or_else(|| VideoClipError::ProbeError(format!("{}: no video frames in clip", output.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyframe_drift_is_measured() {
+        // Requested 60-70s at 25fps; the first keyframe after 60s is at 61.2s
+        let frames: Vec<f64> = (0..220).map(|i| 1.2 + i as f64 * 0.04).collect();
+        let report = CutReport::from_frame_times(60.0, 70.0, 0.0, &frames, 0.04).unwrap();
+
+        assert!((report.actual_start - 61.2).abs() < 1e-9);
+        assert!((report.start_delta - 1.2).abs() < 1e-9);
+        assert!((report.actual_end - 70.0).abs() < 1e-9);
+        assert!(report.end_delta.abs() < 1e-9);
+        assert!(!report.is_exact());
+    }
+
+    #[test]
+    fn test_output_start_offset_and_unsorted_frames() {
+        let report = CutReport::from_frame_times(10.0, 12.0, 1.4, &[3.4, 1.4, 2.4], 1.0).unwrap();
+        assert_eq!(report.actual_start, 10.0);
+        assert_eq!(report.actual_end, 13.0);
+        assert_eq!(report.end_delta, 1.0);
+        assert_eq!(
+            report.summary(),
+            "video starts +0.000s and ends +1.000s from the requested range"
+        );
+    }
+
+    #[test]
+    fn test_exact_cut_and_empty_clip() {
+        let report = CutReport::from_frame_times(5.0, 6.0, 0.0, &[0.0, 0.5], 0.5).unwrap();
+        assert!(report.is_exact());
+        assert!(CutReport::from_frame_times(5.0, 6.0, 0.0, &[], 0.04).is_none());
+    }
+}
@@ -0,0 +1,186 @@
+#[cfg(not(feature = "wasm"))]
+use crate::batch::{request_fingerprint, BatchItemResult, BatchReport};
+use crate::container::{ContainerExecutor, ContainerRuntime};
+use crate::error::{VideoClipError, Result};
+use crate::ssh::SshExecutor;
+use crate::tools::Executor;
+#[cfg(not(feature = "wasm"))]
+use crate::video_clipper::{ClipRequest, VideoClipper};
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "wasm"))]
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Dispatching a batch to several workers
+/// A batch can be sharded across workers that each run FFmpeg somewhere
+/// else: this host, a container, or another machine over SSH. Every worker
+/// clips its shard in turn on its own thread, like a batch of its own, and
+/// the reports are merged back into the requests' order with each item
+/// naming the worker that made it. Shards are balanced by each clip's
+/// estimated processing time, so they finish at about the same time.
+///
+/// Workers are configured as `[[workers]]` tables or given as `--worker`
+/// specs. Over SSH the requests' paths must be valid on that host too,
+/// usually through shared storage.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Worker {
+    /// Shown next to the clips it made
+    pub name: String,
+    #[serde(default)]
+    pub ssh: Option<SshExecutor>,
+    #[serde(default)]
+    pub container: Option<ContainerExecutor>,
+}
+
+impl Worker {
+    /// A worker that runs FFmpeg on this host
+    pub fn local() -> Self {
+        Self { name: "local".to_string(), ssh: None, container: None }
+    }
+
+    /// Where the worker runs FFmpeg; `None` is this host
+    pub fn executor(&self) -> Option<Executor> {
+        match (&self.ssh, &self.container) {
+            (Some(ssh), _) => Some(Executor::Ssh(ssh.clone())),
+            (None, Some(container)) => Some(Executor::Container(container.clone())),
+            (None, None) => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self.ssh.is_some() && self.container.is_some() {
+            true => Err(VideoClipError::InvalidOptions(format!(
+                "worker '{}' runs FFmpeg either in a container or over SSH, not both",
+                self.name
+            ))),
+            false => Ok(()),
+        }
+    }
+}
+
+/// `local`, `ssh:[USER@]HOST[:PORT]`, `docker:IMAGE` or `podman:IMAGE`
+impl FromStr for Worker {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s == "local" {
+            return Ok(Self::local());
+        }
+        match s.split_once(':') {
+            Some(("ssh", host)) => Ok(Self { name: host.to_string(), ssh: Some(SshExecutor::parse(host)?), container: None }),
+            Some((runtime @ ("docker" | "podman"), image)) if !image.is_empty() => Ok(Self {
+                name: s.to_string(),
+                ssh: None,
+                container: Some(ContainerExecutor::new(runtime.parse::<ContainerRuntime>()?, image)),
+            }),
+            _ => Err(VideoClipError::InvalidOptions(format!("unknown worker '{}'", s))),
+        }
+    }
+}
+
+/// Splits jobs costing `costs` into `workers` shards of job indexes: the
+/// longest job goes to the least loaded shard, then the next longest, and
+/// so on. Each shard keeps its jobs in their original order.
+pub fn shard(costs: &[f64], workers: usize) -> Vec<Vec<usize>> {
+    let mut shards: Vec<(f64, Vec<usize>)> = vec![(0.0, Vec::new()); workers.max(1)];
+    let mut order: Vec<usize> = (0..costs.len()).collect();
+    order.sort_by(|&a, &b| costs[b].total_cmp(&costs[a]));
+    for job in order {
+        let (load, jobs) = shards.iter_mut()
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .expect("there is at least one shard");
+        *load += costs[job];
+        jobs.push(job);
+    }
+    shards.into_iter()
+        .map(|(_, mut jobs)| {
+            jobs.sort_unstable();
+            jobs
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "wasm"))]
+impl VideoClipper {
+    /// Clips a batch across `workers`, as [`VideoClipper::clip_batch`] does
+    /// on one. Identical requests go to the same worker so the copies are
+    /// still skipped. Without workers the batch runs here.
+    pub fn dispatch_batch(&self, requests: &[ClipRequest], workers: &[Worker]) -> BatchReport {
+        if workers.is_empty() {
+            return self.clip_batch(requests);
+        }
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of: HashMap<String, usize> = HashMap::new();
+        for (i, request) in requests.iter().enumerate() {
+            let group = *group_of.entry(request_fingerprint(request)).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(i);
+        }
+        let costs: Vec<f64> = groups.iter().map(|group| self.estimated_cost(&requests[group[0]])).collect();
+        let shards: Vec<Vec<usize>> = shard(&costs, workers.len()).into_iter()
+            .map(|shard| {
+                let mut jobs: Vec<usize> = shard.into_iter().flat_map(|group| groups[group].clone()).collect();
+                jobs.sort_unstable();
+                jobs
+            })
+            .collect();
+
+        let mut items: Vec<Option<BatchItemResult>> = vec![None; requests.len()];
+        std::thread::scope(|scope| {
+            let running: Vec<_> = shards.iter().zip(workers)
+                .filter(|(jobs, _)| !jobs.is_empty())
+                .map(|(jobs, worker)| {
+                    let shard: Vec<ClipRequest> = jobs.iter().map(|&i| requests[i].clone()).collect();
+                    let report = scope.spawn(move || crate::tools::with_executor(worker.executor(), || self.clip_batch(&shard)));
+                    (jobs, worker, report)
+                })
+                .collect();
+            for (jobs, worker, report) in running {
+                let report = report.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+                for (&i, item) in jobs.iter().zip(report.items) {
+                    items[i] = Some(BatchItemResult { worker: Some(worker.name.clone()), ..item });
+                }
+            }
+        });
+        BatchReport { items: items.into_iter().flatten().collect() }
+    }
+
+    /// Seconds the clip should take to make: the estimate's, or the range's
+    /// length when the source can't be probed from here
+    fn estimated_cost(&self, request: &ClipRequest) -> f64 {
+        match self.estimate_clip(request) {
+            Ok(estimate) => estimate.estimated_processing_secs,
+            Err(_) => request.time_range().map_or(0.0, |range| range.duration()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shards_balance_by_cost() {
+        let shards = shard(&[60.0, 10.0, 30.0, 30.0, 20.0], 2);
+        assert_eq!(shards, vec![vec![0, 4], vec![1, 2, 3]]);
+        assert_eq!(shard(&[5.0], 3), vec![vec![0], vec![], vec![]]);
+        assert_eq!(shard(&[1.0, 2.0], 0), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_worker_specs() {
+        assert_eq!("local".parse::<Worker>().unwrap().executor(), None);
+        let worker: Worker = "ssh:clipper@studio-mac:2222".parse().unwrap();
+        assert_eq!(worker.name, "clipper@studio-mac:2222");
+        assert_eq!(worker.ssh.unwrap().port, Some(2222));
+        let worker: Worker = "podman:ffmpeg:7".parse().unwrap();
+        assert_eq!(worker.container.map(|c| (c.runtime, c.image)), Some((ContainerRuntime::Podman, "ffmpeg:7".to_string())));
+        assert!("docker:".parse::<Worker>().is_err());
+        assert!("gpu-box".parse::<Worker>().is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::capabilities::{parse_encoder_list, FfmpegVersion};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Instant;
+
+/// Environment self-test
+/// Checks the FFmpeg toolchain, encoders, hardware acceleration and output
+/// directory permissions, then clips a tiny synthetic video end-to-end
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// Wall-clock time the check took, for the benchmark portion of the report
+    pub elapsed_ms: u128,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DoctorReport {
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    /// True when no check failed (warnings are allowed)
+    pub fn is_healthy(&self) -> bool {
+        self.checks.iter().all(|c| c.status != CheckStatus::Fail)
+    }
+
+    fn record(&mut self, name: &str, started: Instant, outcome: (CheckStatus, String)) {
+        self.checks.push(DoctorCheck {
+            name: name.to_string(),
+            status: outcome.0,
+            detail: outcome.1,
+            elapsed_ms: started.elapsed().as_millis(),
+        });
+    }
+}
+
+/// Hardware encoder name fragments worth reporting
+const HW_ENCODER_MARKERS: &[&str] = &["nvenc", "qsv", "vaapi", "videotoolbox", "amf", "v4l2m2m"];
+
+/// Method names from `ffmpeg -hwaccels`
+pub fn parse_hwaccels(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("Hardware acceleration methods"))
+        .skip(1)
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn capture(program: &str, args: &[&str]) -> Option<String> {
+    let output = crate::tools::command(program, args.iter().map(|arg| arg.to_string()).collect()).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn check_tool(program: &str) -> (CheckStatus, String) {
+    match capture(program, &["-version"]).as_deref().and_then(FfmpegVersion::parse) {
+        Some(version) => (CheckStatus::Pass, format!("{} {}", program, version.raw)),
+        None => (CheckStatus::Fail, format!("{} not found in PATH", program)),
+    }
+}
+
+fn check_encoders() -> (CheckStatus, String) {
+    let encoders = match capture(&crate::tools::ffmpeg(), &["-hide_banner", "-encoders"]) {
+        Some(output) => parse_encoder_list(&output),
+        None => return (CheckStatus::Fail, "could not list encoders".to_string()),
+    };
+
+    let hardware: Vec<&str> = encoders.iter()
+        .map(String::as_str)
+        .filter(|name| HW_ENCODER_MARKERS.iter().any(|marker| name.contains(marker)))
+        .collect();
+    let missing: Vec<&str> = ["libx264", "aac"].into_iter()
+        .filter(|name| !encoders.iter().any(|e| e == name))
+        .collect();
+
+    let mut detail = format!("{} encoders", encoders.len());
+    if !hardware.is_empty() {
+        detail.push_str(&format!(", hardware: {}", hardware.join(", ")));
+    }
+    if missing.is_empty() {
+        (CheckStatus::Pass, detail)
+    } else {
+        detail.push_str(&format!(", missing: {}", missing.join(", ")));
+        (CheckStatus::Warn, detail)
+    }
+}
+
+fn check_hwaccels() -> (CheckStatus, String) {
+    match capture(&crate::tools::ffmpeg(), &["-hide_banner", "-hwaccels"]) {
+        Some(output) => {
+            let methods = parse_hwaccels(&output);
+            if methods.is_empty() {
+                (CheckStatus::Warn, "no hardware acceleration methods".to_string())
+            } else {
+                (CheckStatus::Pass, methods.join(", "))
+            }
+        }
+        None => (CheckStatus::Fail, "could not list hardware acceleration methods".to_string()),
+    }
+}
+
+/// Verifies `output_dir` can be created and written to
+pub fn check_output_dir(output_dir: &Path) -> (CheckStatus, String) {
+    if let Err(e) = std::fs::create_dir_all(output_dir) {
+        return (CheckStatus::Fail, format!("cannot create {}: {}", output_dir.display(), e));
+    }
+
+    let probe_file = output_dir.join(format!(".video-clip-doctor-{}", std::process::id()));
+    match std::fs::write(&probe_file, b"ok") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe_file);
+            (CheckStatus::Pass, format!("{} is writable", output_dir.display()))
+        }
+        Err(e) => (CheckStatus::Fail, format!("cannot write to {}: {}", output_dir.display(), e)),
+    }
+}
+
+fn check_pipeline() -> (CheckStatus, String) {
+    use crate::ffmpeg::FFmpegCommand;
+
+    let work_dir = std::env::temp_dir().join(format!("video-clip-doctor-{}", std::process::id()));
+    if let Err(e) = std::fs::create_dir_all(&work_dir) {
+        return (CheckStatus::Fail, format!("cannot create scratch dir: {}", e));
+    }
+    let source = work_dir.join("source.mp4");
+    let clip = work_dir.join("clip.mp4");
+
+    let result = (|| {
+        let mut args: Vec<String> = [
+            "-f", "lavfi", "-i", "testsrc=duration=3:size=320x240:rate=25",
+            "-f", "lavfi", "-i", "sine=frequency=440:duration=3",
+            "-shortest", "-pix_fmt", "yuv420p", "-y",
+        ].map(String::from).to_vec();
+        args.push(source.display().to_string());
+        let generate = crate::tools::ffmpeg_command(args);
+        FFmpegCommand::run(generate, "Synthetic source generation failed")?;
+
+        let started = Instant::now();
+        FFmpegCommand::new(&source, &clip, 1.0, 1.0).execute()?;
+        let clip_ms = started.elapsed().as_millis();
+
+        let info = crate::probe::probe(&clip)?;
+        Ok::<_, crate::VideoClipError>((info, clip_ms))
+    })();
+
+    let _ = std::fs::remove_dir_all(&work_dir);
+
+    match result {
+        Ok((info, clip_ms)) => match info.duration {
+            Some(duration) if duration > 0.0 => (
+                CheckStatus::Pass,
+                format!("clipped {:.2}s synthetic sample in {} ms", duration, clip_ms),
+            ),
+            _ => (CheckStatus::Fail, "synthetic clip has no duration".to_string()),
+        },
+        Err(e) => (CheckStatus::Fail, e.to_string()),
+    }
+}
+
+/// Runs every check; the pipeline test is skipped when FFmpeg itself is missing
+pub fn run_doctor(output_dir: &Path) -> DoctorReport {
+    let mut report = DoctorReport::default();
+
+    let started = Instant::now();
+    report.record("ffmpeg", started, check_tool(&crate::tools::ffmpeg()));
+    let has_ffmpeg = report.checks[0].status == CheckStatus::Pass;
+
+    let started = Instant::now();
+    report.record("ffprobe", started, check_tool(&crate::tools::ffprobe()));
+
+    if has_ffmpeg {
+        let started = Instant::now();
+        report.record("encoders", started, check_encoders());
+
+        let started = Instant::now();
+        report.record("hwaccels", started, check_hwaccels());
+    }
+
+    let started = Instant::now();
+    report.record("output_dir", started, check_output_dir(output_dir));
+
+    if has_ffmpeg {
+        let started = Instant::now();
+        report.record("pipeline", started, check_pipeline());
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    mod parsing_tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_hwaccels() {
+            let output = "Hardware acceleration methods:\nvdpau\ncuda\nvaapi\n\n";
+            assert_eq!(parse_hwaccels(output), vec!["vdpau", "cuda", "vaapi"]);
+            assert!(parse_hwaccels("Hardware acceleration methods:\n").is_empty());
+        }
+    }
+
+    mod check_tests {
+        use super::*;
+
+        #[test]
+        fn test_output_dir_writable() {
+            let temp_dir = tempdir().unwrap();
+            let nested = temp_dir.path().join("a").join("b");
+            let (status, _) = check_output_dir(&nested);
+            assert_eq!(status, CheckStatus::Pass);
+            assert!(nested.exists());
+            assert_eq!(std::fs::read_dir(&nested).unwrap().count(), 0);
+        }
+
+        #[test]
+        fn test_report_health() {
+            let mut report = DoctorReport::default();
+            report.record("a", Instant::now(), (CheckStatus::Pass, String::new()));
+            report.record("b", Instant::now(), (CheckStatus::Warn, String::new()));
+            assert!(report.is_healthy());
+
+            report.record("c", Instant::now(), (CheckStatus::Fail, String::new()));
+            assert!(!report.is_healthy());
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! Video encoder selection
+//! Picks the best encoder the FFmpeg build offers for a codec, preferring
+//! hardware (NVENC > QSV > VAAPI > VideoToolbox) over software, with a manual override.
+//! ProRes 4444 and VP9 are the codecs that keep an alpha channel, and always
+//! encode one.
+
+use crate::capabilities::FfmpegCapabilities;
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    /// Stream copy, no re-encode
+    #[default]
+    Copy,
+    H264,
+    Hevc,
+    Av1,
+    /// ProRes 4444 with alpha, in a `.mov`
+    ProRes,
+    /// VP9 with alpha, in a `.mkv`
+    Vp9,
+}
+
+impl VideoCodec {
+    /// Encoders to try, best first
+    pub fn candidates(&self) -> &'static [&'static str] {
+        match self {
+            VideoCodec::Copy => &[],
+            VideoCodec::H264 => &["h264_nvenc", "h264_qsv", "h264_vaapi", "h264_videotoolbox", "libx264"],
+            VideoCodec::Hevc => &["hevc_nvenc", "hevc_qsv", "hevc_vaapi", "hevc_videotoolbox", "libx265"],
+            VideoCodec::Av1 => &["av1_nvenc", "av1_qsv", "av1_vaapi", "libsvtav1", "libaom-av1"],
+            // Hardware encoders drop alpha
+            VideoCodec::ProRes => &["prores_ks"],
+            VideoCodec::Vp9 => &["libvpx-vp9"],
+        }
+    }
+
+    /// The most widely available software encoder, used when detection isn't
+    /// possible (ffmpeg.wasm) or a hardware encoder fails at runtime
+    pub fn software_encoder(&self) -> Option<&'static str> {
+        match self {
+            VideoCodec::Copy => None,
+            VideoCodec::H264 => Some("libx264"),
+            VideoCodec::Hevc => Some("libx265"),
+            VideoCodec::Av1 => Some("libaom-av1"),
+            VideoCodec::ProRes => Some("prores_ks"),
+            VideoCodec::Vp9 => Some("libvpx-vp9"),
+        }
+    }
+
+    /// Container the codec's clips are written to, by file extension
+    pub fn container(&self) -> &'static str {
+        match self {
+            VideoCodec::ProRes => "mov",
+            VideoCodec::Vp9 => "mkv",
+            VideoCodec::Copy | VideoCodec::H264 | VideoCodec::Hevc | VideoCodec::Av1 => "mp4",
+        }
+    }
+}
+
+/// Container that holds a stream-copied `codec_name` stream's alpha: MP4 has
+/// no place for ProRes, PNG or QuickTime Animation, and drops VP8/VP9 alpha
+pub fn alpha_container(codec_name: &str) -> &'static str {
+    match codec_name {
+        "vp8" | "vp9" => "mkv",
+        _ => "mov",
+    }
+}
+
+impl FromStr for VideoCodec {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "copy" => Ok(VideoCodec::Copy),
+            "h264" | "avc" => Ok(VideoCodec::H264),
+            "hevc" | "h265" => Ok(VideoCodec::Hevc),
+            "av1" => Ok(VideoCodec::Av1),
+            "prores" => Ok(VideoCodec::ProRes),
+            "vp9" => Ok(VideoCodec::Vp9),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown video codec '{}'", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EncoderBackend {
+    Nvenc,
+    Qsv,
+    Vaapi,
+    VideoToolbox,
+    Software,
+}
+
+impl EncoderBackend {
+    pub fn from_encoder_name(name: &str) -> Self {
+        if name.contains("nvenc") {
+            EncoderBackend::Nvenc
+        } else if name.ends_with("_qsv") {
+            EncoderBackend::Qsv
+        } else if name.ends_with("_vaapi") {
+            EncoderBackend::Vaapi
+        } else if name.ends_with("_videotoolbox") {
+            EncoderBackend::VideoToolbox
+        } else {
+            EncoderBackend::Software
+        }
+    }
+}
+
+/// VAAPI render node used when a VAAPI encoder is selected
+const VAAPI_DEVICE: &str = "/dev/dri/renderD128";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncoderChoice {
+    pub name: String,
+    pub backend: EncoderBackend,
+}
+
+impl EncoderChoice {
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            backend: EncoderBackend::from_encoder_name(&name),
+            name,
+        }
+    }
+
+    pub fn is_hardware(&self) -> bool {
+        self.backend != EncoderBackend::Software
+    }
+
+    /// Arguments that must precede `-i`
+    pub fn input_args(&self) -> Vec<String> {
+        match self.backend {
+            EncoderBackend::Vaapi => vec!["-vaapi_device".into(), VAAPI_DEVICE.into()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Frames must be uploaded to the GPU before a VAAPI encoder sees them
+    pub fn upload_filters(&self) -> FilterGraph {
+        match self.backend {
+            EncoderBackend::Vaapi => [Filter::new("format").arg("nv12"), Filter::new("hwupload")].into_iter().collect(),
+            _ => FilterGraph::new(),
+        }
+    }
+
+    /// `-c:v` plus quality settings tuned per backend for roughly visually
+    /// lossless clips
+    pub fn output_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec!["-c:v".into(), self.name.clone()];
+        let quality: &[&str] = match self.backend {
+            EncoderBackend::Nvenc => &["-preset", "p4", "-cq", "23"],
+            EncoderBackend::Qsv => &["-global_quality", "23"],
+            EncoderBackend::Vaapi => &["-qp", "23"],
+            EncoderBackend::VideoToolbox => &["-q:v", "65"],
+            EncoderBackend::Software => match self.name.as_str() {
+                "libaom-av1" => &["-crf", "30", "-b:v", "0", "-cpu-used", "6", "-pix_fmt", "yuv420p"],
+                "libsvtav1" => &["-crf", "35", "-preset", "8", "-pix_fmt", "yuv420p"],
+                "prores_ks" => &["-profile:v", "4444", "-pix_fmt", "yuva444p10le"],
+                "libvpx-vp9" => &["-crf", "30", "-b:v", "0", "-row-mt", "1", "-pix_fmt", "yuva420p"],
+                _ => &["-preset", "medium", "-crf", "23", "-pix_fmt", "yuv420p"],
+            },
+        };
+        args.extend(quality.iter().map(|s| s.to_string()));
+        args
+    }
+
+    /// `output_args` with the quality raised `steps` notches, for retrying an
+    /// encode that scored too low: each notch takes 4 off a CRF/CQ/QP-style
+    /// setting or adds 10 to VideoToolbox's quality. Encoders without a
+    /// quality setting (ProRes) are unchanged.
+    pub fn boosted_args(&self, steps: u32) -> Vec<String> {
+        let mut args = self.output_args();
+        for i in 1..args.len() {
+            let Ok(value) = args[i].parse::<u32>() else {
+                continue;
+            };
+            args[i] = match args[i - 1].as_str() {
+                "-crf" | "-cq" | "-qp" | "-global_quality" => value.saturating_sub(4 * steps).to_string(),
+                "-q:v" => (value + 10 * steps).min(100).to_string(),
+                _ => continue,
+            };
+        }
+        args
+    }
+
+    /// `output_args` with the quality setting swapped for an average bit
+    /// rate of `kbps`, capped at that rate over a two-second buffer, for
+    /// streaming ladders where each rung must fit its bandwidth
+    pub fn capped_args(&self, kbps: u32) -> Vec<String> {
+        const QUALITY: [&str; 6] = ["-crf", "-cq", "-qp", "-global_quality", "-q:v", "-b:v"];
+        let mut args = Vec::new();
+        let mut output = self.output_args().into_iter();
+        while let Some(arg) = output.next() {
+            if QUALITY.contains(&arg.as_str()) {
+                output.next();
+            } else {
+                args.push(arg);
+            }
+        }
+        let rate = format!("{}k", kbps);
+        args.extend(["-b:v".into(), rate.clone(), "-maxrate".into(), rate, "-bufsize".into(), format!("{}k", 2 * kbps)]);
+        args
+    }
+
+    /// Container that holds the encoder's alpha, or `None` if its output
+    /// has none
+    pub fn alpha_container(&self) -> Option<&'static str> {
+        match self.name.as_str() {
+            "prores_ks" => Some(VideoCodec::ProRes.container()),
+            "libvpx-vp9" => Some(VideoCodec::Vp9.container()),
+            _ => None,
+        }
+    }
+
+    /// Fast, small encode for review copies where fidelity matters less than
+    /// size; only x264/x265 are tuned, others fall back to `output_args`
+    pub fn draft_args(&self) -> Vec<String> {
+        match self.name.as_str() {
+            "libx264" | "libx265" => ["-c:v", self.name.as_str(), "-preset", "veryfast", "-crf", "28", "-pix_fmt", "yuv420p"]
+                .iter().map(|s| s.to_string()).collect(),
+            _ => self.output_args(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EncoderSelector {
+    codec: VideoCodec,
+    override_encoder: Option<String>,
+    allow_hardware: bool,
+}
+
+impl EncoderSelector {
+    pub fn new(codec: VideoCodec) -> Self {
+        Self {
+            codec,
+            override_encoder: None,
+            allow_hardware: true,
+        }
+    }
+
+    /// Forces a specific FFmpeg encoder (e.g. `h264_nvenc`), bypassing the ranking
+    pub fn set_override(&mut self, encoder: Option<String>) {
+        self.override_encoder = encoder;
+    }
+
+    pub fn set_allow_hardware(&mut self, allow: bool) {
+        self.allow_hardware = allow;
+    }
+
+    /// Whether the output needs an encoder at all (i.e. isn't a stream copy)
+    pub fn needs_encoder(&self) -> bool {
+        self.override_encoder.is_some() || self.codec != VideoCodec::Copy
+    }
+
+    /// Best encoder available in `capabilities`; `None` means stream copy
+    pub fn select(&self, capabilities: &FfmpegCapabilities) -> Result<Option<EncoderChoice>> {
+        if let Some(name) = &self.override_encoder {
+            capabilities.require_encoder(name, "encoder override")?;
+            return Ok(Some(EncoderChoice::new(name.clone())));
+        }
+
+        let candidates = self.codec.candidates();
+        if candidates.is_empty() {
+            return Ok(None);
+        }
+
+        candidates
+            .iter()
+            .map(|name| EncoderChoice::new(*name))
+            .filter(|choice| self.allow_hardware || !choice.is_hardware())
+            .find(|choice| capabilities.has_encoder(&choice.name))
+            .map(Some)
+            .ok_or_else(|| VideoClipError::UnsupportedByFfmpegBuild {
+                feature: format!("{:?} encoding", self.codec),
+                reason: format!("none of {} are available", candidates.join(", ")),
+            })
+    }
+
+    /// Choice made without querying FFmpeg: the override, else the software encoder
+    pub fn select_offline(&self) -> Option<EncoderChoice> {
+        self.override_encoder.clone()
+            .or_else(|| self.codec.software_encoder().map(str::to_string))
+            .map(EncoderChoice::new)
+    }
+
+    /// Detects the local FFmpeg build (once per process) only when an encoder is needed
+    #[cfg(not(feature = "wasm"))]
+    pub fn resolve(&self) -> Result<Option<EncoderChoice>> {
+        if !self.needs_encoder() {
+            return Ok(None);
+        }
+        self.select(FfmpegCapabilities::cached()?)
+    }
+
+    #[cfg(feature = "wasm")]
+    pub fn resolve(&self) -> Result<Option<EncoderChoice>> {
+        Ok(self.select_offline())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn capabilities(encoders: &[&str]) -> FfmpegCapabilities {
+        FfmpegCapabilities {
+            version: crate::capabilities::FfmpegVersion::parse("ffmpeg version 6.1 Copyright").unwrap(),
+            enabled_libs: BTreeSet::new(),
+            encoders: encoders.iter().map(|e| e.to_string()).collect(),
+            filters: BTreeSet::new(),
+        }
+    }
+
+    mod selection_tests {
+        use super::*;
+
+        #[test]
+        fn test_prefers_hardware_in_priority_order() {
+            let caps = capabilities(&["libx264", "h264_vaapi", "h264_qsv", "h264_nvenc"]);
+            let choice = EncoderSelector::new(VideoCodec::H264).select(&caps).unwrap().unwrap();
+            assert_eq!(choice.name, "h264_nvenc");
+            assert_eq!(choice.backend, EncoderBackend::Nvenc);
+
+            let caps = capabilities(&["libx264", "h264_vaapi", "h264_qsv"]);
+            let choice = EncoderSelector::new(VideoCodec::H264).select(&caps).unwrap().unwrap();
+            assert_eq!(choice.backend, EncoderBackend::Qsv);
+        }
+
+        #[test]
+        fn test_falls_back_to_software() {
+            let caps = capabilities(&["libx264", "libx265"]);
+            let choice = EncoderSelector::new(VideoCodec::Hevc).select(&caps).unwrap().unwrap();
+            assert_eq!(choice.name, "libx265");
+            assert!(!choice.is_hardware());
+        }
+
+        #[test]
+        fn test_hardware_can_be_disabled() {
+            let caps = capabilities(&["libx264", "h264_nvenc"]);
+            let mut selector = EncoderSelector::new(VideoCodec::H264);
+            selector.set_allow_hardware(false);
+            assert_eq!(selector.select(&caps).unwrap().unwrap().name, "libx264");
+        }
+
+        #[test]
+        fn test_copy_needs_no_encoder() {
+            let selector = EncoderSelector::new(VideoCodec::Copy);
+            assert!(!selector.needs_encoder());
+            assert_eq!(selector.select(&capabilities(&[])).unwrap(), None);
+            assert_eq!(selector.select_offline(), None);
+        }
+
+        #[test]
+        fn test_manual_override() {
+            let caps = capabilities(&["libx264", "h264_nvenc"]);
+            let mut selector = EncoderSelector::new(VideoCodec::Copy);
+            selector.set_override(Some("h264_nvenc".to_string()));
+            assert!(selector.needs_encoder());
+            assert_eq!(selector.select(&caps).unwrap().unwrap().name, "h264_nvenc");
+
+            selector.set_override(Some("hevc_qsv".to_string()));
+            assert!(matches!(selector.select(&caps), Err(VideoClipError::UnsupportedByFfmpegBuild { .. })));
+        }
+
+        #[test]
+        fn test_no_candidate_available() {
+            let caps = capabilities(&["libx264"]);
+            let err = EncoderSelector::new(VideoCodec::Av1).select(&caps).unwrap_err();
+            assert!(err.to_string().contains("libaom-av1"));
+        }
+
+        #[test]
+        fn test_offline_selection_uses_software() {
+            assert_eq!(EncoderSelector::new(VideoCodec::Av1).select_offline().unwrap().name, "libaom-av1");
+        }
+    }
+
+    mod argument_tests {
+        use super::*;
+
+        #[test]
+        fn test_backend_specific_args() {
+            assert_eq!(
+                EncoderChoice::new("libx264").output_args().join(" "),
+                "-c:v libx264 -preset medium -crf 23 -pix_fmt yuv420p"
+            );
+            assert_eq!(EncoderChoice::new("h264_nvenc").output_args().join(" "), "-c:v h264_nvenc -preset p4 -cq 23");
+
+            let vaapi = EncoderChoice::new("hevc_vaapi");
+            assert_eq!(vaapi.input_args(), vec!["-vaapi_device", VAAPI_DEVICE]);
+            assert_eq!(vaapi.upload_filters().to_string(), "format=nv12,hwupload");
+            assert!(EncoderChoice::new("libx264").upload_filters().is_empty());
+            assert!(EncoderChoice::new("libx264").input_args().is_empty());
+        }
+
+        #[test]
+        fn test_boosted_args() {
+            assert_eq!(EncoderChoice::new("libx264").boosted_args(2).join(" "), "-c:v libx264 -preset medium -crf 15 -pix_fmt yuv420p");
+            assert_eq!(EncoderChoice::new("libaom-av1").boosted_args(1)[..6].join(" "), "-c:v libaom-av1 -crf 26 -b:v 0");
+            assert_eq!(EncoderChoice::new("h264_videotoolbox").boosted_args(4).join(" "), "-c:v h264_videotoolbox -q:v 100");
+            assert_eq!(EncoderChoice::new("prores_ks").boosted_args(3), EncoderChoice::new("prores_ks").output_args());
+        }
+
+        #[test]
+        fn test_capped_args() {
+            assert_eq!(
+                EncoderChoice::new("libx264").capped_args(3000).join(" "),
+                "-c:v libx264 -preset medium -pix_fmt yuv420p -b:v 3000k -maxrate 3000k -bufsize 6000k"
+            );
+            assert_eq!(EncoderChoice::new("h264_nvenc").capped_args(800).join(" "), "-c:v h264_nvenc -preset p4 -b:v 800k -maxrate 800k -bufsize 1600k");
+        }
+
+        #[test]
+        fn test_codec_from_str() {
+            assert_eq!("H264".parse::<VideoCodec>().unwrap(), VideoCodec::H264);
+            assert_eq!("h265".parse::<VideoCodec>().unwrap(), VideoCodec::Hevc);
+            assert_eq!("ProRes".parse::<VideoCodec>().unwrap(), VideoCodec::ProRes);
+            assert!("vp8".parse::<VideoCodec>().is_err());
+        }
+
+        #[test]
+        fn test_alpha_codecs() {
+            assert_eq!(
+                EncoderChoice::new("prores_ks").output_args().join(" "),
+                "-c:v prores_ks -profile:v 4444 -pix_fmt yuva444p10le"
+            );
+            assert!(EncoderChoice::new("libvpx-vp9").output_args().ends_with(&["-pix_fmt".to_string(), "yuva420p".to_string()]));
+            assert_eq!(EncoderSelector::new(VideoCodec::Vp9).select_offline().unwrap().name, "libvpx-vp9");
+            assert_eq!(EncoderChoice::new("prores_ks").alpha_container(), Some("mov"));
+            assert_eq!(EncoderChoice::new("libx264").alpha_container(), None);
+            assert_eq!((alpha_container("vp9"), alpha_container("qtrle")), ("mkv", "mov"));
+            assert_eq!((VideoCodec::ProRes.container(), VideoCodec::Vp9.container(), VideoCodec::Copy.container()), ("mov", "mkv", "mp4"));
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,15 +19,190 @@ pub enum VideoClipError {
     #[error("FFmpeg execution failed: {0}")]
     FFmpegError(String),
     
+    #[error("FFprobe failed: {0}")]
+    ProbeError(String),
+    
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     
+    #[error("Not a video file: {path} ({detected})")]
+    NotAVideoFile { path: String, detected: String },
+    
+    #[error("Source looks corrupt: {path} ({reason}); recovery mode can salvage what's clippable")]
+    CorruptSource { path: String, reason: String },
+    
+    #[error("Transcription failed: {0}")]
+    TranscriptionFailed(String),
+    
     #[error("Invalid file path: {0}")]
     InvalidPath(String),
     
+    #[error("Invalid options: {0}")]
+    InvalidOptions(String),
+    
+    #[error("Unsupported platform: {0}")]
+    UnsupportedPlatform(String),
+    
+    #[error("Unsupported by this FFmpeg build: {feature} ({reason})")]
+    UnsupportedByFfmpegBuild { feature: String, reason: String },
+    
+    #[error("Invalid capture region: {0} (expected WIDTHxHEIGHT or WIDTHxHEIGHT+X+Y)")]
+    InvalidCaptureRegion(String),
+    
+    #[error("Cancelled")]
+    Cancelled,
+    
     #[error("WASM error: {0}")]
     #[cfg(feature = "wasm")]
     WasmError(String),
 }
 
-pub type Result<T> = std::result::Result<T, VideoClipError>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, VideoClipError>;
+
+/// `strerror` texts FFmpeg and FFprobe print when a read from a network
+/// mount fails in a way worth retrying
+const TRANSIENT_IO_MESSAGES: &[&str] = &[
+    "Input/output error",
+    "Stale file handle",
+    "Resource temporarily unavailable",
+    "Connection reset by peer",
+    "Connection timed out",
+    "Host is down",
+    "Network is unreachable",
+    "Software caused connection abort",
+];
+
+impl VideoClipError {
+    /// Stable, machine-readable name of the error kind; messages may be
+    /// reworded, codes won't be
+    pub fn code(&self) -> &'static str {
+        match self {
+            VideoClipError::InvalidTimeFormat(_) => "invalid_time_format",
+            VideoClipError::InvalidTimeRange { .. } => "invalid_time_range",
+            VideoClipError::FileNotFound(_) => "file_not_found",
+            VideoClipError::FFmpegNotFound => "ffmpeg_not_found",
+            VideoClipError::FFmpegError(_) => "ffmpeg_failed",
+            VideoClipError::ProbeError(_) => "probe_failed",
+            VideoClipError::IoError(_) => "io_error",
+            VideoClipError::NotAVideoFile { .. } => "not_a_video_file",
+            VideoClipError::CorruptSource { .. } => "corrupt_source",
+            VideoClipError::TranscriptionFailed(_) => "transcription_failed",
+            VideoClipError::InvalidPath(_) => "invalid_path",
+            VideoClipError::InvalidOptions(_) => "invalid_options",
+            VideoClipError::UnsupportedPlatform(_) => "unsupported_platform",
+            VideoClipError::UnsupportedByFfmpegBuild { .. } => "unsupported_by_ffmpeg_build",
+            VideoClipError::InvalidCaptureRegion(_) => "invalid_capture_region",
+            VideoClipError::Cancelled => "cancelled",
+            #[cfg(feature = "wasm")]
+            VideoClipError::WasmError(_) => "wasm_error",
+        }
+    }
+
+    /// Whether the error looks like a read that failed on a flaky network
+    /// share (a dropped connection, a stale NFS handle, an SMB timeout) and
+    /// may well succeed if tried again
+    pub fn is_transient_io(&self) -> bool {
+        use std::io::ErrorKind;
+
+        match self {
+            VideoClipError::IoError(e) => matches!(
+                e.kind(),
+                ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::HostUnreachable
+                    | ErrorKind::NetworkUnreachable
+                    | ErrorKind::NetworkDown
+                    | ErrorKind::StaleNetworkFileHandle
+            ),
+            VideoClipError::FFmpegError(details) | VideoClipError::ProbeError(details) => {
+                TRANSIENT_IO_MESSAGES.iter().any(|message| details.contains(message))
+            }
+            _ => false,
+        }
+    }
+
+    /// The values the message was built from, for clients that localize
+    /// messages themselves
+    pub fn context(&self) -> Map<String, Value> {
+        let context = match self {
+            VideoClipError::InvalidTimeFormat(value) => json!({ "value": value }),
+            VideoClipError::InvalidTimeRange { start, end } => json!({ "start": start, "end": end }),
+            VideoClipError::FileNotFound(path) | VideoClipError::InvalidPath(path) => json!({ "path": path }),
+            VideoClipError::NotAVideoFile { path, detected } => json!({ "path": path, "detected": detected }),
+            VideoClipError::CorruptSource { path, reason } => json!({ "path": path, "reason": reason }),
+            VideoClipError::FFmpegNotFound | VideoClipError::Cancelled => json!({}),
+            VideoClipError::FFmpegError(details)
+            | VideoClipError::ProbeError(details)
+            | VideoClipError::TranscriptionFailed(details)
+            | VideoClipError::InvalidOptions(details) => json!({ "details": details }),
+            VideoClipError::IoError(e) => json!({ "kind": format!("{:?}", e.kind()) }),
+            VideoClipError::UnsupportedPlatform(platform) => json!({ "platform": platform }),
+            VideoClipError::UnsupportedByFfmpegBuild { feature, reason } => json!({ "feature": feature, "reason": reason }),
+            VideoClipError::InvalidCaptureRegion(region) => json!({ "region": region }),
+            #[cfg(feature = "wasm")]
+            VideoClipError::WasmError(details) => json!({ "details": details }),
+        };
+        match context {
+            Value::Object(context) => context,
+            _ => Map::new(),
+        }
+    }
+}
+
+/// An error as it crosses the WASM and HTTP boundaries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorInfo {
+    /// See [`VideoClipError::code`]
+    pub code: String,
+    pub message: String,
+    #[serde(default)]
+    pub context: Map<String, Value>,
+}
+
+impl From<&VideoClipError> for ErrorInfo {
+    fn from(e: &VideoClipError) -> Self {
+        Self {
+            code: e.code().to_string(),
+            message: e.to_string(),
+            context: e.context(),
+        }
+    }
+}
+
+impl Serialize for VideoClipError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        ErrorInfo::from(self).serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_error() {
+        let e = VideoClipError::InvalidTimeRange { start: 30.0, end: 10.0 };
+        assert_eq!(serde_json::to_value(&e).unwrap(), json!({
+            "code": "invalid_time_range",
+            "message": "End time (10) must be after start time (30)",
+            "context": { "start": 30.0, "end": 10.0 },
+        }));
+
+        let io = VideoClipError::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        let info: ErrorInfo = serde_json::from_value(serde_json::to_value(&io).unwrap()).unwrap();
+        assert_eq!(info.code, "io_error");
+        assert_eq!(info.context["kind"], "PermissionDenied");
+    }
+
+    #[test]
+    fn test_transient_io() {
+        assert!(VideoClipError::from(std::io::Error::from(std::io::ErrorKind::TimedOut)).is_transient_io());
+        assert!(!VideoClipError::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied)).is_transient_io());
+        assert!(VideoClipError::FFmpegError("Clip failed: /mnt/nas/talk.mp4: Input/output error".to_string()).is_transient_io());
+        assert!(!VideoClipError::FFmpegError("Clip failed: Invalid data found when processing input".to_string()).is_transient_io());
+        assert!(!VideoClipError::FileNotFound("/mnt/nas/talk.mp4".to_string()).is_transient_io());
+    }
+}
@@ -0,0 +1,161 @@
+//! Pre-flight clip estimation
+//! Predicts output size, stream-copy feasibility, keyframe-snapped start and
+//! processing time from probe data, so UIs can warn before expensive jobs
+
+use crate::probe::{MediaInfo, StreamInfo};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ClipEstimate {
+    pub duration: f64,
+    pub estimated_size_mb: Option<f64>,
+    pub stream_copy_possible: bool,
+    /// Why stream copy isn't possible, one entry per offending stream
+    pub copy_blockers: Vec<String>,
+    /// Keyframe a stream-copied clip will actually start from
+    pub keyframe_start: Option<f64>,
+    pub estimated_processing_secs: f64,
+}
+
+/// Codecs MP4 can carry without re-encoding
+const MP4_VIDEO_CODECS: &[&str] = &["h264", "hevc", "av1", "mpeg4", "vp9"];
+const MP4_AUDIO_CODECS: &[&str] = &["aac", "mp3", "ac3", "eac3", "opus", "flac", "alac"];
+
+/// Rough throughputs used for processing time estimates
+const COPY_MB_PER_SEC: f64 = 150.0;
+const ENCODE_1080P_REALTIME_FACTOR: f64 = 0.5;
+
+/// Whether a stream can be copied into an MP4 container as-is
+pub fn mp4_copy_compatible(stream: &StreamInfo) -> bool {
+    mp4_carries(&stream.codec_type, stream.codec_name.as_deref().unwrap_or(""))
+}
+
+/// Whether MP4 can carry a `codec_type` (`video`, `audio`) stream of `codec`
+pub fn mp4_carries(codec_type: &str, codec: &str) -> bool {
+    match codec_type {
+        "video" => MP4_VIDEO_CODECS.contains(&codec),
+        "audio" => MP4_AUDIO_CODECS.contains(&codec),
+        // Other stream types aren't mapped into clips
+        _ => true,
+    }
+}
+
+impl ClipEstimate {
+    /// Builds an estimate for `[start, end]` from probe data and the keyframes
+    /// found around `start`
+    pub fn from_media_info(info: &MediaInfo, start: f64, end: f64, keyframes: &[f64]) -> Self {
+        let duration = (end - start).max(0.0);
+
+        let estimated_size_mb = info.effective_bit_rate()
+            .map(|bps| bps as f64 * duration / 8.0 / (1024.0 * 1024.0));
+
+        let copy_blockers: Vec<String> = info.streams.iter()
+            .filter(|s| !mp4_copy_compatible(s))
+            .map(|s| format!(
+                "{} stream #{} ({}) can't be stream-copied into MP4",
+                s.codec_type,
+                s.index,
+                s.codec_name.as_deref().unwrap_or("unknown codec")
+            ))
+            .collect();
+        let stream_copy_possible = copy_blockers.is_empty();
+
+        // Stream copy starts at the last keyframe at or before the requested start
+        let keyframe_start = keyframes.iter()
+            .copied()
+            .filter(|&k| k <= start + 1e-6)
+            .fold(None, |latest: Option<f64>, k| Some(latest.map_or(k, |l| l.max(k))));
+
+        let estimated_processing_secs = if stream_copy_possible {
+            0.5 + estimated_size_mb.unwrap_or(0.0) / COPY_MB_PER_SEC
+        } else {
+            let pixels = info.video_stream()
+                .and_then(|v| Some(v.width? as f64 * v.height? as f64))
+                .unwrap_or(1920.0 * 1080.0);
+            duration * ENCODE_1080P_REALTIME_FACTOR * pixels / (1920.0 * 1080.0)
+        };
+
+        Self {
+            duration,
+            estimated_size_mb,
+            stream_copy_possible,
+            copy_blockers,
+            keyframe_start,
+            estimated_processing_secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(index: u32, codec_type: &str, codec_name: &str) -> StreamInfo {
+        StreamInfo {
+            index,
+            codec_type: codec_type.to_string(),
+            codec_name: Some(codec_name.to_string()),
+            width: if codec_type == "video" { Some(1920) } else { None },
+            height: if codec_type == "video" { Some(1080) } else { None },
+            ..Default::default()
+        }
+    }
+
+    fn media(streams: Vec<StreamInfo>) -> MediaInfo {
+        MediaInfo {
+            format_name: "mov,mp4,m4a,3gp,3g2,mj2".to_string(),
+            duration: Some(600.0),
+            bit_rate: Some(8 * 1024 * 1024),
+            streams,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_copyable_estimate() {
+        let info = media(vec![stream(0, "video", "h264"), stream(1, "audio", "aac")]);
+        let estimate = ClipEstimate::from_media_info(&info, 62.0, 122.0, &[58.0, 60.0, 64.0]);
+
+        assert_eq!(estimate.duration, 60.0);
+        assert_eq!(estimate.estimated_size_mb, Some(60.0));
+        assert!(estimate.stream_copy_possible);
+        assert!(estimate.copy_blockers.is_empty());
+        assert_eq!(estimate.keyframe_start, Some(60.0));
+        assert!((estimate.estimated_processing_secs - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pcm_audio_blocks_copy() {
+        let info = media(vec![stream(0, "video", "h264"), stream(1, "audio", "pcm_s16le")]);
+        let estimate = ClipEstimate::from_media_info(&info, 0.0, 10.0, &[]);
+
+        assert!(!estimate.stream_copy_possible);
+        assert_eq!(estimate.copy_blockers.len(), 1);
+        assert!(estimate.copy_blockers[0].contains("pcm_s16le"));
+        assert_eq!(estimate.keyframe_start, None);
+        assert_eq!(estimate.estimated_processing_secs, 5.0);
+    }
+
+    #[test]
+    fn test_keyframe_exactly_at_start() {
+        let info = media(vec![stream(0, "video", "h264")]);
+        let estimate = ClipEstimate::from_media_info(&info, 60.0, 70.0, &[50.0, 60.0, 70.0]);
+        assert_eq!(estimate.keyframe_start, Some(60.0));
+    }
+
+    #[test]
+    fn test_unknown_bitrate() {
+        let mut info = media(vec![stream(0, "video", "h264")]);
+        info.bit_rate = None;
+        info.duration = None;
+        let estimate = ClipEstimate::from_media_info(&info, 0.0, 10.0, &[]);
+        assert_eq!(estimate.estimated_size_mb, None);
+    }
+
+    #[test]
+    fn test_data_streams_do_not_block_copy() {
+        assert!(mp4_copy_compatible(&stream(2, "data", "bin_data")));
+        assert!(!mp4_copy_compatible(&stream(0, "video", "prores")));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::error::VideoClipError;
+use crate::video_clipper::{ClipRequest, ClipResult};
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+
+/// Clip events
+/// `EventSink`s receive live activity from `VideoClipper` as it happens: a
+/// clip starting, chunk and batch progress, fallbacks to slower paths, and
+/// the final result or error. GUIs and servers implement it instead of
+/// scraping logs; the CLI's colored output is one implementation.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Progress {
+    pub stage: ProgressStage,
+    pub completed: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    /// Completed share, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.completed as f64 / self.total as f64
+        }
+    }
+}
+
+/// What a progress event counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressStage {
+    /// Chunks of a chunked encode
+    Chunks,
+    /// Requests of a batch
+    Batch,
+}
+
+/// A slower path taken after the preferred one failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Fallback {
+    /// The hardware encoder failed; the clip was re-encoded in software
+    Encoder { from: String, to: String },
+    /// Audio couldn't be stream copied and was re-encoded to AAC
+    AudioCopy,
+}
+
+impl fmt::Display for Fallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fallback::Encoder { from, to } => write!(f, "encoder {} failed, retried with {}", from, to),
+            Fallback::AudioCopy => f.write_str("audio copy failed, re-encoded to AAC"),
+        }
+    }
+}
+
+/// Receives clip activity; every method does nothing by default. Methods are
+/// called on the clipping thread (or a chunk's encode thread), so keep them quick.
+pub trait EventSink: Send + Sync {
+    fn on_start(&self, _request: &ClipRequest) {}
+
+    fn on_progress(&self, _request: &ClipRequest, _progress: &Progress) {}
+
+    fn on_fallback(&self, _request: &ClipRequest, _fallback: &Fallback) {}
+
+    /// After hooks have run, so their errors are among the result's warnings
+    fn on_complete(&self, _result: &ClipResult) {}
+
+    fn on_error(&self, _request: &ClipRequest, _error: &VideoClipError) {}
+}
+
+/// The sinks a clipper reports to, in the order they were added
+#[derive(Clone, Default)]
+pub struct EventSinks {
+    sinks: Vec<Arc<dyn EventSink>>,
+}
+
+impl fmt::Debug for EventSinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EventSinks({})", self.sinks.len())
+    }
+}
+
+impl EventSinks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, sink: Arc<dyn EventSink>) {
+        self.sinks.push(sink);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    pub fn start(&self, request: &ClipRequest) {
+        self.sinks.iter().for_each(|s| s.on_start(request));
+    }
+
+    pub fn progress(&self, request: &ClipRequest, progress: &Progress) {
+        self.sinks.iter().for_each(|s| s.on_progress(request, progress));
+    }
+
+    pub fn fallback(&self, request: &ClipRequest, fallback: &Fallback) {
+        self.sinks.iter().for_each(|s| s.on_fallback(request, fallback));
+    }
+
+    pub fn complete(&self, result: &ClipResult) {
+        self.sinks.iter().for_each(|s| s.on_complete(result));
+    }
+
+    pub fn error(&self, request: &ClipRequest, error: &VideoClipError) {
+        self.sinks.iter().for_each(|s| s.on_error(request, error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video_clipper::VideoClipper;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl EventSink for Recorder {
+        fn on_start(&self, request: &ClipRequest) {
+            self.events.lock().unwrap().push(format!("start {}", request.input_file));
+        }
+
+        fn on_progress(&self, _request: &ClipRequest, progress: &Progress) {
+            self.events.lock().unwrap().push(format!("progress {}/{}", progress.completed, progress.total));
+        }
+
+        fn on_error(&self, request: &ClipRequest, _error: &VideoClipError) {
+            self.events.lock().unwrap().push(format!("error {}", request.input_file));
+        }
+    }
+
+    fn request(input: &str) -> ClipRequest {
+        ClipRequest {
+            input_file: input.to_string(),
+            start_time: "0".to_string(),
+            end_time: "5".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_batch_reports_each_clip_and_its_progress() {
+        let recorder = Arc::new(Recorder::default());
+        let mut clipper = VideoClipper::new();
+        clipper.add_event_sink(recorder.clone());
+
+        clipper.clip_batch(&[request("missing-a.mp4"), request("missing-b.mp4")]);
+        assert_eq!(*recorder.events.lock().unwrap(), vec![
+            "start missing-a.mp4", "error missing-a.mp4", "progress 1/2",
+            "start missing-b.mp4", "error missing-b.mp4", "progress 2/2",
+        ]);
+    }
+
+    #[test]
+    fn test_progress_and_fallback_text() {
+        let progress = Progress { stage: ProgressStage::Chunks, completed: 1, total: 4 };
+        assert_eq!(progress.fraction(), 0.25);
+        let fallback = Fallback::Encoder { from: "h264_nvenc".to_string(), to: "libx264".to_string() };
+        assert_eq!(fallback.to_string(), "encoder h264_nvenc failed, retried with libx264");
+    }
+}
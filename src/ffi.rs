@@ -0,0 +1,193 @@
+use crate::error::{VideoClipError, Result};
+use crate::time_parser::TimeParser;
+use crate::video_clipper::{ClipRequest, VideoClipper};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// C bindings
+/// A small, stable C ABI over the library for Python (ctypes/cffi), Swift and
+/// other non-Rust callers. Structured values cross the boundary as JSON
+/// strings in the same shape as the CLI's `--json` output, so the ABI doesn't
+/// change when a field is added. Every function returns a `VcStatus`; on
+/// failure `vc_last_error` describes what went wrong on the calling thread.
+/// Strings returned by the library must be released with `vc_string_free`.
+/// `build.rs` writes the matching header to `include/video_clip.h`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum VcStatus {
+    Ok = 0,
+    /// A required pointer argument was null
+    NullArgument = 1,
+    /// A string argument wasn't valid UTF-8
+    InvalidUtf8 = 2,
+    /// The request JSON didn't parse
+    InvalidJson = 3,
+    /// The operation itself failed; see `vc_last_error`
+    Failed = 4,
+    /// An internal error (a panic) was caught at the boundary
+    Panic = 5,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Runs `f`, recording its error (or panic) for `vc_last_error`
+fn guard(f: impl FnOnce() -> std::result::Result<(), (VcStatus, String)>) -> VcStatus {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => VcStatus::Ok,
+        Ok(Err((status, message))) => {
+            set_last_error(message);
+            status
+        }
+        Err(_) => {
+            set_last_error("video-clip-rs panicked");
+            VcStatus::Panic
+        }
+    }
+}
+
+fn failed(e: VideoClipError) -> (VcStatus, String) {
+    (VcStatus::Failed, e.to_string())
+}
+
+/// # Safety
+/// `s` must be null or a valid NUL-terminated string
+unsafe fn read_str<'a>(s: *const c_char, name: &str) -> std::result::Result<&'a str, (VcStatus, String)> {
+    if s.is_null() {
+        return Err((VcStatus::NullArgument, format!("{} is null", name)));
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| (VcStatus::InvalidUtf8, format!("{} is not valid UTF-8", name)))
+}
+
+/// Hands `value` to the caller as JSON through `out`
+///
+/// # Safety
+/// `out` must be a valid pointer to write to
+unsafe fn write_json(value: &impl serde::Serialize, out: *mut *mut c_char) -> std::result::Result<(), (VcStatus, String)> {
+    let json = serde_json::to_string(value).map_err(|e| (VcStatus::Failed, e.to_string()))?;
+    let json = CString::new(json).map_err(|e| (VcStatus::Failed, e.to_string()))?;
+    *out = json.into_raw();
+    Ok(())
+}
+
+fn clip(request: &ClipRequest, output_dir: Option<&str>) -> Result<crate::video_clipper::ClipResult> {
+    let clipper = output_dir.map_or_else(VideoClipper::new, VideoClipper::with_output_dir);
+    clipper.clip_video(request)
+}
+
+/// Clips the range described by `request_json` (a `ClipRequest` as JSON).
+/// `output_dir` may be null for the default `downloads`. On success `*out_json`
+/// receives the `ClipResult` as JSON.
+///
+/// # Safety
+/// `request_json` must be a valid NUL-terminated string, `output_dir` null or
+/// one, and `out_json` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn vc_clip_video(request_json: *const c_char, output_dir: *const c_char, out_json: *mut *mut c_char) -> VcStatus {
+    guard(|| {
+        if out_json.is_null() {
+            return Err((VcStatus::NullArgument, "out_json is null".to_string()));
+        }
+        let request: ClipRequest = serde_json::from_str(read_str(request_json, "request_json")?)
+            .map_err(|e| (VcStatus::InvalidJson, format!("invalid clip request: {}", e)))?;
+        let output_dir = if output_dir.is_null() { None } else { Some(read_str(output_dir, "output_dir")?) };
+        let result = clip(&request, output_dir).map_err(failed)?;
+        write_json(&result, out_json)
+    })
+}
+
+/// Parses a time such as `"1:30"`, `"01:02:03.5"` or `"90"` into seconds
+///
+/// # Safety
+/// `time` must be a valid NUL-terminated string and `out_seconds` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn vc_parse_time(time: *const c_char, out_seconds: *mut f64) -> VcStatus {
+    guard(|| {
+        if out_seconds.is_null() {
+            return Err((VcStatus::NullArgument, "out_seconds is null".to_string()));
+        }
+        *out_seconds = TimeParser::parse_to_seconds(read_str(time, "time")?).map_err(failed)?;
+        Ok(())
+    })
+}
+
+/// Probes a media file with ffprobe; `*out_json` receives the `MediaInfo` as JSON
+///
+/// # Safety
+/// `path` must be a valid NUL-terminated string and `out_json` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn vc_probe(path: *const c_char, out_json: *mut *mut c_char) -> VcStatus {
+    guard(|| {
+        if out_json.is_null() {
+            return Err((VcStatus::NullArgument, "out_json is null".to_string()));
+        }
+        let info = crate::probe::probe(read_str(path, "path")?).map_err(failed)?;
+        write_json(&info, out_json)
+    })
+}
+
+/// The last error on this thread as a new string (free it with
+/// `vc_string_free`), or null if the last call succeeded
+#[no_mangle]
+pub extern "C" fn vc_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| last.borrow().clone().map_or(ptr::null_mut(), CString::into_raw))
+}
+
+/// Releases a string returned by this library; null is ignored
+///
+/// # Safety
+/// `s` must be null or a string from this library that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn vc_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take_string(s: *mut c_char) -> String {
+        assert!(!s.is_null());
+        let owned = unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned();
+        unsafe { vc_string_free(s) };
+        owned
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let mut seconds = 0.0;
+        let time = CString::new("1:30").unwrap();
+        assert_eq!(unsafe { vc_parse_time(time.as_ptr(), &mut seconds) }, VcStatus::Ok);
+        assert_eq!(seconds, 90.0);
+        assert!(vc_last_error().is_null());
+
+        let time = CString::new("soon").unwrap();
+        assert_eq!(unsafe { vc_parse_time(time.as_ptr(), &mut seconds) }, VcStatus::Failed);
+        assert!(take_string(vc_last_error()).contains("Invalid time format"));
+        assert_eq!(unsafe { vc_parse_time(ptr::null(), &mut seconds) }, VcStatus::NullArgument);
+    }
+
+    #[test]
+    fn test_clip_video_errors() {
+        let mut out = ptr::null_mut();
+        let bad = CString::new("{\"input_file\": 3}").unwrap();
+        assert_eq!(unsafe { vc_clip_video(bad.as_ptr(), ptr::null(), &mut out) }, VcStatus::InvalidJson);
+        assert!(out.is_null());
+
+        let missing = CString::new(r#"{"input_file": "missing.mp4", "start_time": "0", "end_time": "5"}"#).unwrap();
+        assert_eq!(unsafe { vc_clip_video(missing.as_ptr(), ptr::null(), &mut out) }, VcStatus::Failed);
+        assert!(take_string(vc_last_error()).contains("missing.mp4"));
+    }
+}
@@ -0,0 +1,194 @@
+//! Attribution overlay
+//! Stamps a QR code of a URL or other text (usually where the footage came
+//! from) into a corner of the clip, for the whole clip or a window of it. The
+//! code is rendered here and written as a small grayscale image that the
+//! filter graph reads with `movie` and composites with `overlay`.
+
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{escape_value, time_window, Filter, FilterGraph};
+use qrcodegen::{QrCode, QrCodeEcc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl FromStr for Corner {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "top-left" => Ok(Corner::TopLeft),
+            "top-right" => Ok(Corner::TopRight),
+            "bottom-left" => Ok(Corner::BottomLeft),
+            "bottom-right" => Ok(Corner::BottomRight),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown corner '{}'", other))),
+        }
+    }
+}
+
+fn default_size() -> u32 {
+    120
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Attribution {
+    /// URL or text encoded in the code
+    pub text: String,
+    #[serde(default)]
+    pub corner: Corner,
+    /// Width of the code in pixels, rounded down to whole pixels per module
+    #[serde(default = "default_size")]
+    pub size: u32,
+    /// When the code appears and disappears, in seconds from the start of
+    /// the clip; shown throughout when unset
+    #[serde(default)]
+    pub start: Option<f64>,
+    #[serde(default)]
+    pub end: Option<f64>,
+}
+
+/// Blank modules around the code that scanners need to find it
+const QUIET_ZONE: i32 = 4;
+
+/// Gap between the code and the edges of the frame
+const MARGIN: u32 = 16;
+
+const SIZE_RANGE: std::ops::RangeInclusive<u32> = 32..=1024;
+
+impl Attribution {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            corner: Corner::default(),
+            size: default_size(),
+            start: None,
+            end: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.text.is_empty() {
+            return Err(VideoClipError::InvalidOptions("attribution text is empty".to_string()));
+        }
+        if !SIZE_RANGE.contains(&self.size) {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "attribution size must be between {} and {} pixels, got {}",
+                SIZE_RANGE.start(),
+                SIZE_RANGE.end(),
+                self.size
+            )));
+        }
+        for seconds in [self.start, self.end].into_iter().flatten() {
+            if !(seconds.is_finite() && seconds >= 0.0) {
+                return Err(VideoClipError::InvalidOptions(format!("attribution times must be at least 0s, got {}", seconds)));
+            }
+        }
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            if end <= start {
+                return Err(VideoClipError::InvalidOptions(format!("attribution ends ({}s) before it starts ({}s)", end, start)));
+            }
+        }
+        self.code().map(|_| ())
+    }
+
+    fn code(&self) -> Result<QrCode> {
+        QrCode::encode_text(&self.text, QrCodeEcc::Medium).map_err(|_| {
+            VideoClipError::InvalidOptions(format!("attribution text is too long for a QR code ({} bytes)", self.text.len()))
+        })
+    }
+
+    /// The code as a binary PGM: black modules on white, with its quiet zone,
+    /// scaled to as many whole pixels per module as fit in `size`
+    pub fn qr_image(&self) -> Result<Vec<u8>> {
+        let code = self.code()?;
+        let modules = code.size() + 2 * QUIET_ZONE;
+        let scale = (self.size as i32 / modules).max(1);
+        let side = modules * scale;
+
+        let mut image = format!("P5\n{} {}\n255\n", side, side).into_bytes();
+        for y in 0..side {
+            for x in 0..side {
+                let dark = code.get_module(x / scale - QUIET_ZONE, y / scale - QUIET_ZONE);
+                image.push(if dark { 0 } else { 255 });
+            }
+        }
+        Ok(image)
+    }
+
+    /// Composites the code at `image` over the video. `offset` is the
+    /// timestamp of the clip's first frame in the graph, since clips seek on
+    /// the output side and the graph still sees source times.
+    pub fn filter_graph(&self, image: &Path, offset: f64) -> FilterGraph {
+        let (x, y) = match self.corner {
+            Corner::TopLeft => (MARGIN.to_string(), MARGIN.to_string()),
+            Corner::TopRight => (format!("W-w-{}", MARGIN), MARGIN.to_string()),
+            Corner::BottomLeft => (MARGIN.to_string(), format!("H-h-{}", MARGIN)),
+            Corner::BottomRight => (format!("W-w-{}", MARGIN), format!("H-h-{}", MARGIN)),
+        };
+        let mut overlay = Filter::new("overlay").option("x", x).option("y", y);
+        if let Some(window) = time_window(self.start, self.end, offset) {
+            overlay = overlay.option("enable", window);
+        }
+
+        let mut graph = FilterGraph::new();
+        graph.add_chain(&[], vec![Filter::new("null")], &["attribution_main"]);
+        graph.add_chain(&[], vec![Filter::new("movie").arg(escape_value(&image.display().to_string()))], &["attribution_code"]);
+        graph.add_chain(&["attribution_main", "attribution_code"], vec![overlay], &[]);
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qr_image() {
+        let attribution = Attribution::new("https://example.com/footage/1234");
+        let image = attribution.qr_image().unwrap();
+        // Version 3 (29 modules) plus the quiet zone, 3 pixels a module
+        let header = "P5\n111 111\n255\n";
+        assert!(image.starts_with(header.as_bytes()));
+        assert_eq!(image.len(), header.len() + 111 * 111);
+        // Quiet zone, then the top-left finder pattern
+        let row = &image[header.len() + 12 * 111..];
+        assert_eq!(row[11], 255);
+        assert_eq!(row[12], 0);
+    }
+
+    #[test]
+    fn test_overlay_window() {
+        let mut attribution = Attribution::new("source");
+        let graph = attribution.filter_graph(Path::new("out/clip.attribution.pgm"), 30.0).to_string();
+        assert_eq!(graph, "null[attribution_main];movie=out/clip.attribution.pgm[attribution_code];[attribution_main][attribution_code]overlay=x=W-w-16:y=H-h-16");
+
+        attribution.corner = Corner::TopLeft;
+        attribution.start = Some(2.0);
+        attribution.end = Some(7.5);
+        let graph = attribution.filter_graph(Path::new("qr.pgm"), 30.0).to_string();
+        assert!(graph.ends_with("overlay=x=16:y=16:enable=between(t\\,32\\,37.5)"));
+        attribution.end = None;
+        assert!(attribution.filter_graph(Path::new("qr.pgm"), 0.0).to_string().ends_with("enable=gte(t\\,2)"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Attribution::new("https://example.com").validate().is_ok());
+        assert!(Attribution::new("").validate().is_err());
+        assert!(Attribution { size: 8, ..Attribution::new("x") }.validate().is_err());
+        assert!(Attribution { start: Some(5.0), end: Some(2.0), ..Attribution::new("x") }.validate().is_err());
+        assert!(Attribution::new("x".repeat(5000)).validate().is_err());
+        assert_eq!("Top-Right".parse::<Corner>().unwrap(), Corner::TopRight);
+    }
+}
@@ -0,0 +1,191 @@
+//! Overlay audio mixing
+//! Mixes a second audio file (music bed, voice-over) under the clip's own
+//! audio, optionally ducking it with a sidechain compressor whenever the
+//! clip's audio is active. A music bed shorter than the clip can be looped
+//! to fill it, and faded out over the clip's last seconds; the mix always
+//! ends with the clip.
+
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct OverlayAudio {
+    pub path: String,
+    /// Gain applied to the overlay before mixing
+    #[serde(default)]
+    pub volume_db: f64,
+    /// Compress the overlay whenever the clip's own audio is louder than the threshold
+    #[serde(default)]
+    pub duck: bool,
+    /// Repeat the overlay when it's shorter than the clip
+    #[serde(default)]
+    pub loop_to_fit: bool,
+    /// Fade the overlay out over the clip's last this many seconds
+    #[serde(default)]
+    pub fade_out: Option<f64>,
+}
+
+/// Sidechain compressor settings: fast attack, slow release so speech pulls the
+/// bed down cleanly without pumping between words
+const DUCK_THRESHOLD: f64 = 0.05;
+const DUCK_RATIO: u32 = 8;
+const DUCK_ATTACK_MS: u32 = 20;
+const DUCK_RELEASE_MS: u32 = 400;
+
+/// Pad label the mixed audio is written to
+pub const MIX_OUTPUT_LABEL: &str = "aout";
+
+/// Gain filter for a dB adjustment
+pub fn volume_filter(volume_db: f64) -> Filter {
+    Filter::new("volume").arg(format!("{}dB", volume_db))
+}
+
+pub fn validate_volume(volume_db: f64) -> Result<()> {
+    if !volume_db.is_finite() || !(-60.0..=30.0).contains(&volume_db) {
+        return Err(VideoClipError::InvalidOptions(format!(
+            "volume must be between -60 and +30 dB, got {}",
+            volume_db
+        )));
+    }
+    Ok(())
+}
+
+impl OverlayAudio {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            volume_db: 0.0,
+            duck: false,
+            loop_to_fit: false,
+            fade_out: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        validate_volume(self.volume_db)?;
+        if let Some(fade) = self.fade_out {
+            if !(fade.is_finite() && fade > 0.0) {
+                return Err(VideoClipError::InvalidOptions(format!("overlay fade-out must be positive, got {}s", fade)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Arguments for the overlay's `-i`
+    pub fn input_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.loop_to_fit {
+            args.extend(["-stream_loop".into(), "-1".into()]);
+        }
+        args.extend(["-i".into(), self.path.clone()]);
+        args
+    }
+
+    /// `-filter_complex` graph mixing input 0's audio (after `main_filters`)
+    /// with input 1 for a clip of `duration` seconds. The overlay is delayed
+    /// by `start_time` because clips seek on the output side, which discards
+    /// the first `start_time` seconds of the mix.
+    pub fn mix_graph(&self, main_filters: &FilterGraph, start_time: f64, duration: f64) -> FilterGraph {
+        let mut graph = FilterGraph::new();
+
+        let mut main: Vec<Filter> = main_filters.chains().iter()
+            .flat_map(|chain| chain.filters.iter().cloned())
+            .collect();
+        if main.is_empty() {
+            main.push(Filter::new("anull"));
+        }
+
+        let mut overlay = Vec::new();
+        if start_time > 0.0 {
+            overlay.push(Filter::new("adelay").arg((start_time * 1000.0).round() as u64).option("all", 1));
+        }
+        overlay.push(volume_filter(self.volume_db));
+        if let Some(fade) = self.fade_out {
+            let fade = fade.min(duration);
+            overlay.push(Filter::new("afade").option("t", "out").option("st", start_time + duration - fade).option("d", fade));
+        }
+        graph.add_chain(&["1:a"], overlay, &["bed"]);
+
+        let mix = Filter::new("amix")
+            .option("inputs", 2)
+            .option("duration", "first")
+            .option("dropout_transition", 0)
+            .option("normalize", 0);
+
+        if self.duck {
+            main.push(Filter::new("asplit").arg(2));
+            graph.add_chain(&["0:a"], main, &["main", "sidechain"]);
+            graph.add_chain(
+                &["bed", "sidechain"],
+                vec![Filter::new("sidechaincompress")
+                    .option("threshold", DUCK_THRESHOLD)
+                    .option("ratio", DUCK_RATIO)
+                    .option("attack", DUCK_ATTACK_MS)
+                    .option("release", DUCK_RELEASE_MS)],
+                &["ducked"],
+            );
+            graph.add_chain(&["main", "ducked"], vec![mix], &[MIX_OUTPUT_LABEL]);
+        } else {
+            graph.add_chain(&["0:a"], main, &["main"]);
+            graph.add_chain(&["main", "bed"], vec![mix], &[MIX_OUTPUT_LABEL]);
+        }
+
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_mix() {
+        let mut overlay = OverlayAudio::new("music.mp3");
+        overlay.volume_db = -12.0;
+
+        let graph = overlay.mix_graph(&FilterGraph::new(), 0.0, 10.0).to_string();
+        assert_eq!(
+            graph,
+            "[1:a]volume=-12dB[bed];[0:a]anull[main];[main][bed]amix=inputs=2:duration=first:dropout_transition=0:normalize=0[aout]"
+        );
+    }
+
+    #[test]
+    fn test_ducked_mix_with_main_gain_and_offset() {
+        let mut overlay = OverlayAudio::new("music.mp3");
+        overlay.duck = true;
+
+        let graph = overlay.mix_graph(&FilterGraph::from(volume_filter(3.0)), 12.5, 10.0).to_string();
+        assert!(graph.starts_with("[1:a]adelay=12500:all=1,volume=0dB[bed];"));
+        assert!(graph.contains("[0:a]volume=3dB,asplit=2[main][sidechain];"));
+        assert!(graph.contains("[bed][sidechain]sidechaincompress=threshold=0.05:ratio=8:attack=20:release=400[ducked];"));
+        assert!(graph.ends_with("[main][ducked]amix=inputs=2:duration=first:dropout_transition=0:normalize=0[aout]"));
+    }
+
+    #[test]
+    fn test_looped_bed_fades_out_with_the_clip() {
+        let mut overlay = OverlayAudio::new("music.mp3");
+        overlay.loop_to_fit = true;
+        overlay.fade_out = Some(3.0);
+        assert_eq!(overlay.input_args(), vec!["-stream_loop", "-1", "-i", "music.mp3"]);
+
+        let graph = overlay.mix_graph(&FilterGraph::new(), 60.0, 20.0).to_string();
+        assert!(graph.starts_with("[1:a]adelay=60000:all=1,volume=0dB,afade=t=out:st=77:d=3[bed];"));
+        // A fade longer than the clip covers all of it
+        let graph = overlay.mix_graph(&FilterGraph::new(), 0.0, 2.0).to_string();
+        assert!(graph.contains("afade=t=out:st=0:d=2[bed]"));
+
+        overlay.fade_out = Some(-1.0);
+        assert!(overlay.validate().is_err());
+    }
+
+    #[test]
+    fn test_volume_bounds() {
+        assert!(validate_volume(-6.0).is_ok());
+        assert!(validate_volume(45.0).is_err());
+        assert!(validate_volume(f64::NAN).is_err());
+    }
+}
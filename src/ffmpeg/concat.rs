@@ -0,0 +1,290 @@
+use crate::encoder::EncoderChoice;
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use crate::ffmpeg::fit::{FitMode, FitOptions};
+use crate::ffmpeg::limits::ProcessLimits;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Conforming concat
+/// Joins videos that may differ in size, frame rate and audio layout (an
+/// intro, the clip, an outro) by re-encoding them through the `concat`
+/// filter. Each part is first conformed to the format of the clip: scaled
+/// and padded into its frame, resampled to its rate, and given stereo audio,
+/// with silence standing in for parts that have none. A part can also be a
+/// still image (an end card) held for a number of seconds, optionally with a
+/// music file under it.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conform {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f64,
+    pub sample_rate: u32,
+}
+
+/// Sample rate parts are conformed to when the clip has no audio of its own
+pub const DEFAULT_SAMPLE_RATE: u32 = 48000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConcatPart {
+    pub path: PathBuf,
+    pub has_audio: bool,
+    /// Seconds, for the silence generated in place of missing audio
+    pub duration: f64,
+    /// The path is an image shown for `duration` rather than a video
+    pub still: bool,
+    /// Audio played under the part in place of its own, looped or cut to
+    /// `duration`
+    pub music: Option<PathBuf>,
+}
+
+impl ConcatPart {
+    pub fn video(path: impl AsRef<Path>, has_audio: bool, duration: f64) -> Self {
+        Self { path: path.as_ref().to_path_buf(), has_audio, duration, still: false, music: None }
+    }
+
+    pub fn still(path: impl AsRef<Path>, duration: f64, music: Option<PathBuf>) -> Self {
+        Self { path: path.as_ref().to_path_buf(), has_audio: false, duration, still: true, music }
+    }
+}
+
+/// Longest an end card can be held
+const MAX_END_CARD_SECONDS: f64 = 60.0;
+
+fn default_end_card_seconds() -> f64 {
+    5.0
+}
+
+/// A still image appended to a clip
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct EndCard {
+    /// PNG, JPEG or any other image FFmpeg reads
+    pub image: String,
+    /// How long the card is shown
+    #[serde(default = "default_end_card_seconds")]
+    pub seconds: f64,
+    /// Audio played under the card; silent when unset
+    #[serde(default)]
+    pub music: Option<String>,
+}
+
+impl EndCard {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.seconds.is_finite() && self.seconds > 0.0 && self.seconds <= MAX_END_CARD_SECONDS) {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "end card must last between 0 and {}s, got {}s",
+                MAX_END_CARD_SECONDS, self.seconds
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn part(&self) -> ConcatPart {
+        ConcatPart::still(&self.image, self.seconds, self.music.as_ref().map(PathBuf::from))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConcatCommand {
+    parts: Vec<ConcatPart>,
+    output: PathBuf,
+    conform: Conform,
+    encoder: EncoderChoice,
+    process_limits: ProcessLimits,
+    strip_metadata: bool,
+}
+
+const VIDEO_OUTPUT_LABEL: &str = "vout";
+const AUDIO_OUTPUT_LABEL: &str = "aout";
+
+impl ConcatCommand {
+    pub fn new(parts: Vec<ConcatPart>, output: impl AsRef<Path>, conform: Conform, encoder: EncoderChoice) -> Self {
+        Self {
+            parts,
+            output: output.as_ref().to_path_buf(),
+            conform,
+            encoder,
+            process_limits: ProcessLimits::default(),
+            strip_metadata: false,
+        }
+    }
+
+    pub fn set_process_limits(&mut self, limits: ProcessLimits) {
+        self.process_limits = limits;
+    }
+
+    /// The `-i` arguments for every part, and the input indices of each
+    /// part's picture and, when it has a music file, its audio
+    fn inputs(&self) -> (Vec<String>, Vec<(usize, Option<usize>)>) {
+        let mut args: Vec<String> = Vec::new();
+        let mut indices = Vec::new();
+        // Inputs are numbered in the order of their -i
+        let next = |args: &Vec<String>| args.iter().filter(|arg| *arg == "-i").count();
+        for part in &self.parts {
+            if part.still {
+                args.extend([
+                    "-loop".into(), "1".into(),
+                    "-framerate".into(), self.conform.fps.to_string(),
+                    "-t".into(), part.duration.to_string(),
+                ]);
+            } else {
+                args.extend(self.process_limits.input_args());
+            }
+            let video = next(&args);
+            args.extend(["-i".into(), part.path.display().to_string()]);
+            let music = part.music.as_ref().map(|music| {
+                let index = next(&args);
+                args.extend([
+                    "-stream_loop".into(), "-1".into(),
+                    "-t".into(), part.duration.to_string(),
+                    "-i".into(), music.display().to_string(),
+                ]);
+                index
+            });
+            indices.push((video, music));
+        }
+        (args, indices)
+    }
+
+    /// Drop the parts' metadata and chapters (see [`crate::scrub`])
+    pub fn set_strip_metadata(&mut self, strip: bool) {
+        self.strip_metadata = strip;
+    }
+
+    pub fn filter_graph(&self) -> FilterGraph {
+        let Conform { width, height, fps, sample_rate } = self.conform;
+        let mut graph = FilterGraph::new();
+        let mut concat_inputs = Vec::new();
+        let (_, indices) = self.inputs();
+        for (i, (part, (input, music))) in self.parts.iter().zip(indices).enumerate() {
+            let (video, audio) = (format!("v{}", i), format!("a{}", i));
+
+            let mut video_filters: Vec<Filter> = FitOptions::new(width, height, FitMode::Pad)
+                .filter_graph()
+                .chains()
+                .iter()
+                .flat_map(|chain| chain.filters.clone())
+                .collect();
+            video_filters.push(Filter::new("fps").arg(fps));
+            video_filters.push(Filter::new("format").arg("yuv420p"));
+            graph.add_chain(&[&format!("{}:v:0", input)], video_filters, &[&video]);
+
+            let layout = Filter::new("aformat").option("sample_rates", sample_rate).option("channel_layouts", "stereo");
+            if let Some(music) = music {
+                graph.add_chain(&[&format!("{}:a:0", music)], vec![layout], &[&audio]);
+            } else if part.has_audio {
+                graph.add_chain(&[&format!("{}:a:0", input)], vec![layout], &[&audio]);
+            } else {
+                let silence = Filter::new("anullsrc").option("r", sample_rate).option("cl", "stereo");
+                graph.add_chain(&[], vec![silence, Filter::new("atrim").option("duration", part.duration)], &[&audio]);
+            }
+            concat_inputs.extend([video, audio]);
+        }
+        let inputs: Vec<&str> = concat_inputs.iter().map(String::as_str).collect();
+        graph.add_chain(
+            &inputs,
+            vec![Filter::new("concat").option("n", self.parts.len()).option("v", 1).option("a", 1)],
+            &[VIDEO_OUTPUT_LABEL, AUDIO_OUTPUT_LABEL],
+        );
+        graph
+    }
+
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec!["-hide_banner".into()];
+        args.extend(self.inputs().0);
+        args.extend([
+            "-filter_complex".into(), self.filter_graph().to_string(),
+            "-map".into(), format!("[{}]", VIDEO_OUTPUT_LABEL),
+            "-map".into(), format!("[{}]", AUDIO_OUTPUT_LABEL),
+        ]);
+        args.extend(self.encoder.output_args());
+        args.extend(["-c:a".into(), "aac".into(), "-b:a".into(), "128k".into()]);
+        if self.strip_metadata {
+            args.extend(crate::scrub::strip_args());
+        }
+        if self.output.extension().is_some_and(|e| e != "mkv") {
+            args.extend(["-movflags".into(), "+faststart".into()]);
+        }
+        args.extend(self.process_limits.thread_args());
+        args.extend(["-y".into(), self.output.display().to_string()]);
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        self.process_limits.command(&crate::tools::ffmpeg(), self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        let mut argv = self.process_limits.priority_prefix();
+        argv.push(crate::tools::ffmpeg());
+        argv.extend(self.build_args());
+        argv.join(" ")
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> crate::error::Result<()> {
+        super::FFmpegCommand::run(self.build_command(), "Joining failed")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(path: &str, has_audio: bool, duration: f64) -> ConcatPart {
+        ConcatPart::video(path, has_audio, duration)
+    }
+
+    #[test]
+    fn test_parts_are_conformed_and_joined() {
+        let conform = Conform { width: 1920, height: 1080, fps: 30.0, sample_rate: 48000 };
+        let cmd = ConcatCommand::new(
+            vec![part("intro.mov", false, 3.5), part("out/clip.body.mp4", true, 60.0)],
+            "out/clip.mp4",
+            conform,
+            EncoderChoice::new("libx264"),
+        );
+        let graph = cmd.filter_graph().to_string();
+        assert!(graph.starts_with(
+            "[0:v:0]scale=1920:1080:force_original_aspect_ratio=decrease,pad=1920:1080:(ow-iw)/2:(oh-ih)/2:color=black,setsar=1,fps=30,format=yuv420p[v0];"
+        ));
+        assert!(graph.contains("anullsrc=r=48000:cl=stereo,atrim=duration=3.5[a0];"));
+        assert!(graph.contains("[1:a:0]aformat=sample_rates=48000:channel_layouts=stereo[a1];"));
+        assert!(graph.ends_with("[v0][a0][v1][a1]concat=n=2:v=1:a=1[vout][aout]"));
+
+        let args = cmd.build_args().join(" ");
+        assert!(args.starts_with("-hide_banner -i intro.mov -i out/clip.body.mp4 -filter_complex "));
+        assert!(args.ends_with("-map [vout] -map [aout] -c:v libx264 -preset medium -crf 23 -pix_fmt yuv420p -c:a aac -b:a 128k -movflags +faststart -y out/clip.mp4"));
+    }
+
+    #[test]
+    fn test_end_card_is_a_held_image() {
+        let conform = Conform { width: 1280, height: 720, fps: 25.0, sample_rate: 44100 };
+        let card = EndCard { image: "card.png".to_string(), seconds: 4.0, music: Some("sting.mp3".to_string()) };
+        let cmd = ConcatCommand::new(
+            vec![part("out/clip.body.mp4", true, 30.0), card.part(), EndCard { music: None, ..card.clone() }.part()],
+            "out/clip.mp4",
+            conform,
+            EncoderChoice::new("libx264"),
+        );
+        let args = cmd.build_args().join(" ");
+        assert!(args.starts_with(
+            "-hide_banner -i out/clip.body.mp4 -loop 1 -framerate 25 -t 4 -i card.png -stream_loop -1 -t 4 -i sting.mp3 -loop 1 -framerate 25 -t 4 -i card.png -filter_complex "
+        ));
+        let graph = cmd.filter_graph().to_string();
+        assert!(graph.contains("[1:v:0]scale=1280:720:"));
+        assert!(graph.contains("[2:a:0]aformat=sample_rates=44100:channel_layouts=stereo[a1];"));
+        assert!(graph.contains("[3:v:0]scale=1280:720:"));
+        assert!(graph.contains("anullsrc=r=44100:cl=stereo,atrim=duration=4[a2];"));
+
+        assert!(card.validate().is_ok());
+        assert!(EndCard { seconds: 0.0, ..card }.validate().is_err());
+        let card: EndCard = serde_json::from_str(r#"{"image": "card.png"}"#).unwrap();
+        assert_eq!(card.seconds, 5.0);
+    }
+}
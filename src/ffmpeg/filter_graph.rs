@@ -0,0 +1,328 @@
+use std::fmt;
+
+/// Filter graph builder
+/// Models FFmpeg filter graphs as chains of filters joined by labeled pads, so
+/// independent features (deinterlace, tonemap, fit, overlays, ...) can each
+/// contribute a fragment and be composed without string concatenation
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    name: String,
+    args: Vec<String>,
+}
+
+impl Filter {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Positional argument, e.g. `Filter::new("split").arg(2)` → `split=2`
+    pub fn arg(mut self, value: impl ToString) -> Self {
+        self.args.push(value.to_string());
+        self
+    }
+
+    /// Named argument, e.g. `.option("color", "black")` → `color=black`
+    pub fn option(mut self, key: &str, value: impl ToString) -> Self {
+        self.args.push(format!("{}={}", key, value.to_string()));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.args.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}={}", self.name, self.args.join(":"))
+        }
+    }
+}
+
+/// Escapes arbitrary text (paths, captions) for use as a filter argument
+/// inside a graph: first the argument level (`\ ' :`), then the graph level
+/// (`\ ' [ ] , ;`)
+pub fn escape_value(value: &str) -> String {
+    let escape = |input: &str, special: &str| {
+        let mut out = String::with_capacity(input.len());
+        for c in input.chars() {
+            if special.contains(c) {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out
+    };
+    escape(&escape(value, "\\':"), "\\'[],;")
+}
+
+/// `enable` expression for a filter that only runs from `start` to `end`
+/// (either may be open), shifted by `offset`; `None` when it always runs.
+/// The commas are escaped for use in a graph.
+pub fn time_window(start: Option<f64>, end: Option<f64>, offset: f64) -> Option<String> {
+    let from = offset + start.unwrap_or(0.0);
+    match (start, end) {
+        (_, Some(end)) => Some(format!("between(t\\,{}\\,{})", from, offset + end)),
+        (Some(_), None) => Some(format!("gte(t\\,{})", from)),
+        (None, None) => None,
+    }
+}
+
+/// Filters applied in sequence, reading from `inputs` and writing to `outputs`
+/// (unlabeled ends connect to the graph's input/output stream)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterChain {
+    pub inputs: Vec<String>,
+    pub filters: Vec<Filter>,
+    pub outputs: Vec<String>,
+}
+
+impl fmt::Display for FilterChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for label in &self.inputs {
+            write!(f, "[{}]", label)?;
+        }
+        let filters: Vec<String> = self.filters.iter().map(Filter::to_string).collect();
+        write!(f, "{}", filters.join(","))?;
+        for label in &self.outputs {
+            write!(f, "[{}]", label)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterGraph {
+    chains: Vec<FilterChain>,
+}
+
+impl FilterGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chains.iter().all(|c| c.filters.is_empty())
+    }
+
+    pub fn chains(&self) -> &[FilterChain] {
+        &self.chains
+    }
+
+    /// Appends `filter` to the end of the main stream
+    pub fn push(&mut self, filter: Filter) {
+        match self.chains.last_mut() {
+            Some(last) if last.outputs.is_empty() => last.filters.push(filter),
+            Some(last) => {
+                // The tail ends in labels, so start a chain that consumes them
+                let inputs = last.outputs.clone();
+                self.chains.push(FilterChain { inputs, filters: vec![filter], outputs: Vec::new() });
+            }
+            None => self.chains.push(FilterChain { filters: vec![filter], ..Default::default() }),
+        }
+    }
+
+    /// Adds a chain with explicit pad labels, for branching graphs
+    pub fn add_chain(&mut self, inputs: &[&str], filters: Vec<Filter>, outputs: &[&str]) {
+        self.chains.push(FilterChain {
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            filters,
+            outputs: outputs.iter().map(|s| s.to_string()).collect(),
+        });
+    }
+
+    fn labels(&self) -> Vec<&str> {
+        self.chains
+            .iter()
+            .flat_map(|c| c.inputs.iter().chain(c.outputs.iter()))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Feeds this graph's output into `next`, renaming any of `next`'s pad
+    /// labels that would collide with ours
+    pub fn append(&mut self, mut next: FilterGraph) {
+        if next.is_empty() {
+            return;
+        }
+
+        let taken: Vec<String> = self.labels().into_iter().map(str::to_string).collect();
+        if !taken.is_empty() {
+            let suffix = self.chains.len();
+            for chain in &mut next.chains {
+                for label in chain.inputs.iter_mut().chain(chain.outputs.iter_mut()) {
+                    if taken.contains(label) {
+                        *label = format!("{}_{}", label, suffix);
+                    }
+                }
+            }
+        }
+
+        let mut chains = next.chains.into_iter();
+        match (self.chains.last_mut(), chains.next()) {
+            // Unlabeled tail meets unlabeled head: one continuous chain
+            (Some(last), Some(first)) if last.outputs.is_empty() && first.inputs.is_empty() => {
+                last.filters.extend(first.filters);
+                last.outputs = first.outputs;
+            }
+            (_, Some(first)) => {
+                if first.inputs.is_empty() {
+                    for filter in first.filters.iter().cloned() {
+                        self.push(filter);
+                    }
+                    if let Some(last) = self.chains.last_mut() {
+                        last.outputs = first.outputs;
+                    }
+                } else {
+                    self.chains.push(first);
+                }
+            }
+            (_, None) => {}
+        }
+        self.chains.extend(chains);
+    }
+
+    pub fn then(mut self, next: FilterGraph) -> Self {
+        self.append(next);
+        self
+    }
+
+    /// `-vf` arguments, or nothing for an empty graph
+    pub fn to_args(&self) -> Vec<String> {
+        self.to_args_for("-vf")
+    }
+
+    /// `-af` arguments, or nothing for an empty graph
+    pub fn to_audio_args(&self) -> Vec<String> {
+        self.to_args_for("-af")
+    }
+
+    fn to_args_for(&self, option: &str) -> Vec<String> {
+        if self.is_empty() {
+            Vec::new()
+        } else {
+            vec![option.into(), self.to_string()]
+        }
+    }
+}
+
+impl From<Filter> for FilterGraph {
+    fn from(filter: Filter) -> Self {
+        let mut graph = FilterGraph::new();
+        graph.push(filter);
+        graph
+    }
+}
+
+impl FromIterator<Filter> for FilterGraph {
+    fn from_iter<I: IntoIterator<Item = Filter>>(iter: I) -> Self {
+        let mut graph = FilterGraph::new();
+        for filter in iter {
+            graph.push(filter);
+        }
+        graph
+    }
+}
+
+impl fmt::Display for FilterGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chains: Vec<String> = self.chains.iter().map(FilterChain::to_string).collect();
+        write!(f, "{}", chains.join(";"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn branching() -> FilterGraph {
+        let mut graph = FilterGraph::new();
+        graph.add_chain(&[], vec![Filter::new("split").arg(2)], &["a", "b"]);
+        graph.add_chain(&["a"], vec![Filter::new("boxblur").arg(10)], &["blur"]);
+        graph.add_chain(&["blur", "b"], vec![Filter::new("overlay")], &[]);
+        graph
+    }
+
+    mod building_tests {
+        use super::*;
+
+        #[test]
+        fn test_filter_display() {
+            assert_eq!(Filter::new("hflip").to_string(), "hflip");
+            assert_eq!(
+                Filter::new("scale").arg(1280).arg(-2).option("flags", "lanczos").to_string(),
+                "scale=1280:-2:flags=lanczos"
+            );
+        }
+
+        #[test]
+        fn test_linear_chain() {
+            let graph: FilterGraph = [Filter::new("bwdif"), Filter::new("fps").arg(25)].into_iter().collect();
+            assert_eq!(graph.to_string(), "bwdif,fps=25");
+            assert_eq!(graph.to_args(), vec!["-vf", "bwdif,fps=25"]);
+        }
+
+        #[test]
+        fn test_empty_graph_has_no_args() {
+            assert!(FilterGraph::new().is_empty());
+            assert!(FilterGraph::new().to_args().is_empty());
+        }
+
+        #[test]
+        fn test_branching_graph() {
+            assert_eq!(branching().to_string(), "split=2[a][b];[a]boxblur=10[blur];[blur][b]overlay");
+        }
+
+        #[test]
+        fn test_push_after_labeled_tail() {
+            let mut graph = FilterGraph::new();
+            graph.add_chain(&[], vec![Filter::new("split")], &["x"]);
+            graph.push(Filter::new("null"));
+            assert_eq!(graph.to_string(), "split[x];[x]null");
+        }
+    }
+
+    mod composition_tests {
+        use super::*;
+
+        #[test]
+        fn test_append_merges_linear_segments() {
+            let graph = FilterGraph::from(Filter::new("bwdif"))
+                .then(branching())
+                .then(FilterGraph::from(Filter::new("fps").arg(30)));
+            assert_eq!(graph.to_string(), "bwdif,split=2[a][b];[a]boxblur=10[blur];[blur][b]overlay,fps=30");
+        }
+
+        #[test]
+        fn test_append_renames_colliding_labels() {
+            let graph = branching().then(branching());
+            let rendered = graph.to_string();
+            assert!(rendered.starts_with("split=2[a][b];[a]boxblur=10[blur];[blur][b]overlay,split=2[a_3][b_3]"));
+            assert!(rendered.ends_with("[blur_3][b_3]overlay"));
+        }
+
+        #[test]
+        fn test_append_empty_is_noop() {
+            let graph = FilterGraph::from(Filter::new("hflip")).then(FilterGraph::new());
+            assert_eq!(graph.to_string(), "hflip");
+        }
+    }
+
+    mod escaping_tests {
+        use super::*;
+
+        #[test]
+        fn test_escape_value() {
+            assert_eq!(escape_value("plain.srt"), "plain.srt");
+            assert_eq!(escape_value("C:\\subs,en.srt"), "C\\\\:\\\\\\\\subs\\,en.srt");
+            assert_eq!(escape_value("it's"), "it\\\\\\'s");
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Aspect-ratio conversion
+//! Scales a source into a target frame (e.g. 16:9 landscape into a 9:16
+//! vertical), padding with a color or a blurred copy of the video, cropping,
+//! or stretching
+
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FitMode {
+    /// Letterbox/pillarbox: the whole picture is kept and the rest is filled
+    #[default]
+    Pad,
+    /// Fill the frame and cut off what overflows
+    Crop,
+    /// Distort to the target size
+    Stretch,
+}
+
+impl FromStr for FitMode {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "pad" => Ok(FitMode::Pad),
+            "crop" => Ok(FitMode::Crop),
+            "stretch" => Ok(FitMode::Stretch),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown fit mode '{}'", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FitBackground {
+    /// Any FFmpeg color: a name (`black`), `#RRGGBB` or `0xRRGGBB[@alpha]`
+    Color(String),
+    /// A blurred, cropped copy of the video itself
+    Blur,
+}
+
+impl Default for FitBackground {
+    fn default() -> Self {
+        FitBackground::Color("black".to_string())
+    }
+}
+
+impl FromStr for FitBackground {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("blur") {
+            return Ok(FitBackground::Blur);
+        }
+        // Keep the value from breaking out of the filter argument
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric() || "#@.".contains(c)) {
+            return Err(VideoClipError::InvalidOptions(format!("invalid background color '{}'", s)));
+        }
+        Ok(FitBackground::Color(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct FitOptions {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub mode: FitMode,
+    /// Fill for the padded area; only used by `FitMode::Pad`
+    #[serde(default)]
+    pub background: FitBackground,
+}
+
+/// Strength of the blurred background (boxblur luma radius and power)
+const BLUR_RADIUS: u32 = 20;
+const BLUR_POWER: u32 = 2;
+
+impl FitOptions {
+    pub fn new(width: u32, height: u32, mode: FitMode) -> Self {
+        Self {
+            width,
+            height,
+            mode,
+            background: FitBackground::default(),
+        }
+    }
+
+    /// 1080x1920 vertical with a blurred background, the usual short-form layout
+    pub fn vertical_blurred() -> Self {
+        Self {
+            background: FitBackground::Blur,
+            ..Self::new(1080, 1920, FitMode::Pad)
+        }
+    }
+
+    /// Parses `WIDTHxHEIGHT`, e.g. `1080x1920`
+    pub fn parse_size(size: &str) -> Result<(u32, u32)> {
+        let invalid = || VideoClipError::InvalidOptions(format!("invalid size '{}' (expected WIDTHxHEIGHT)", size));
+        let (width, height) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+        Ok((
+            width.trim().parse().map_err(|_| invalid())?,
+            height.trim().parse().map_err(|_| invalid())?,
+        ))
+    }
+
+    /// 4:2:0 output needs even, non-zero dimensions
+    pub fn validate(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 || !self.width.is_multiple_of(2) || !self.height.is_multiple_of(2) {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "fit size must be even and non-zero, got {}x{}",
+                self.width, self.height
+            )));
+        }
+        if let FitBackground::Color(color) = &self.background {
+            color.parse::<FitBackground>()?;
+        }
+        Ok(())
+    }
+
+    /// Filter graph with a single unlabeled input and output, so it composes
+    /// with the rest of the clip's filters
+    pub fn filter_graph(&self) -> FilterGraph {
+        let (w, h) = (self.width, self.height);
+        let cover = || vec![
+            Filter::new("scale").arg(w).arg(h).option("force_original_aspect_ratio", "increase"),
+            Filter::new("crop").arg(w).arg(h),
+        ];
+        let contain = || Filter::new("scale").arg(w).arg(h).option("force_original_aspect_ratio", "decrease");
+        let setsar = || Filter::new("setsar").arg(1);
+
+        match (self.mode, &self.background) {
+            (FitMode::Stretch, _) => [Filter::new("scale").arg(w).arg(h), setsar()].into_iter().collect(),
+            (FitMode::Crop, _) => cover().into_iter().chain([setsar()]).collect(),
+            (FitMode::Pad, FitBackground::Color(color)) => [
+                contain(),
+                Filter::new("pad").arg(w).arg(h).arg("(ow-iw)/2").arg("(oh-ih)/2").option("color", color),
+                setsar(),
+            ].into_iter().collect(),
+            (FitMode::Pad, FitBackground::Blur) => {
+                let mut graph = FilterGraph::new();
+                graph.add_chain(&[], vec![Filter::new("split").arg(2)], &["fit_bg", "fit_fg"]);
+                let mut background = cover();
+                background.push(Filter::new("boxblur").arg(BLUR_RADIUS).arg(BLUR_POWER));
+                graph.add_chain(&["fit_bg"], background, &["fit_blur"]);
+                graph.add_chain(&["fit_fg"], vec![contain()], &["fit_main"]);
+                graph.add_chain(
+                    &["fit_blur", "fit_main"],
+                    vec![Filter::new("overlay").arg("(W-w)/2").arg("(H-h)/2"), setsar()],
+                    &[],
+                );
+                graph
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod filter_tests {
+        use super::*;
+
+        #[test]
+        fn test_pad_with_color() {
+            let mut fit = FitOptions::new(1080, 1920, FitMode::Pad);
+            fit.background = FitBackground::Color("white".to_string());
+            assert_eq!(
+                fit.filter_graph().to_string(),
+                "scale=1080:1920:force_original_aspect_ratio=decrease,pad=1080:1920:(ow-iw)/2:(oh-ih)/2:color=white,setsar=1"
+            );
+        }
+
+        #[test]
+        fn test_crop_and_stretch() {
+            assert_eq!(
+                FitOptions::new(1080, 1080, FitMode::Crop).filter_graph().to_string(),
+                "scale=1080:1080:force_original_aspect_ratio=increase,crop=1080:1080,setsar=1"
+            );
+            assert_eq!(FitOptions::new(640, 480, FitMode::Stretch).filter_graph().to_string(), "scale=640:480,setsar=1");
+        }
+
+        #[test]
+        fn test_blurred_background() {
+            let graph = FitOptions::vertical_blurred().filter_graph().to_string();
+            assert!(graph.starts_with("split=2[fit_bg][fit_fg];"));
+            assert!(graph.contains("[fit_bg]scale=1080:1920:force_original_aspect_ratio=increase,crop=1080:1920,boxblur=20:2[fit_blur]"));
+            assert!(graph.contains("[fit_fg]scale=1080:1920:force_original_aspect_ratio=decrease[fit_main]"));
+            assert!(graph.ends_with("[fit_blur][fit_main]overlay=(W-w)/2:(H-h)/2,setsar=1"));
+        }
+    }
+
+    mod parsing_tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_size() {
+            assert_eq!(FitOptions::parse_size("1080x1920").unwrap(), (1080, 1920));
+            assert!(FitOptions::parse_size("1080").is_err());
+            assert!(FitOptions::parse_size("wide x tall").is_err());
+        }
+
+        #[test]
+        fn test_parse_background() {
+            assert_eq!("blur".parse::<FitBackground>().unwrap(), FitBackground::Blur);
+            assert_eq!("#1a1a1a".parse::<FitBackground>().unwrap(), FitBackground::Color("#1a1a1a".to_string()));
+            assert!("black:x=1".parse::<FitBackground>().is_err());
+        }
+
+        #[test]
+        fn test_validate_dimensions() {
+            assert!(FitOptions::new(1080, 1920, FitMode::Pad).validate().is_ok());
+            assert!(FitOptions::new(1081, 1920, FitMode::Pad).validate().is_err());
+            assert!(FitOptions::new(0, 1920, FitMode::Crop).validate().is_err());
+        }
+
+        #[test]
+        fn test_deserialize_with_defaults() {
+            let fit: FitOptions = serde_json::from_str(r#"{"width": 1080, "height": 1920}"#).unwrap();
+            assert_eq!(fit.mode, FitMode::Pad);
+            assert_eq!(fit.background, FitBackground::Color("black".to_string()));
+
+            let fit: FitOptions = serde_json::from_str(r#"{"width": 1080, "height": 1920, "background": "blur"}"#).unwrap();
+            assert_eq!(fit.background, FitBackground::Blur);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::capabilities::FfmpegVersion;
+use super::filter_graph::Filter;
+
+/// Output frame timing
+/// `-fps_mode` (`-vsync` before FFmpeg 5.1) decides whether frames are
+/// duplicated or dropped on the way out. Stream copies pass timestamps through
+/// untouched; encodes keep each frame's own timing unless a constant rate was
+/// asked for. Screen recordings and phone footage have a variable frame rate
+/// (VFR), and forcing a blanket sync mode onto them is what made long clips
+/// drift out of sync. Encoded audio is lined up with `aresample`, which
+/// replaced the deprecated `-async`.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FrameSync {
+    /// Timestamps as they are; all a stream copy can do
+    #[default]
+    Passthrough,
+    /// Keep each frame's timing, dropping only frames that share a timestamp
+    Vfr,
+    /// Duplicate and drop frames to hold the output rate constant
+    Cfr,
+}
+
+/// First release with `-fps_mode`; `-vsync` and `-async` are deprecated from here
+const FPS_MODE_SINCE: (u32, u32) = (5, 1);
+
+/// Whether `version` needs `-vsync`/`-async`; unknown builds are assumed current
+pub fn needs_legacy_options(version: Option<&FfmpegVersion>) -> bool {
+    version.is_some_and(|v| !v.at_least(FPS_MODE_SINCE.0, FPS_MODE_SINCE.1))
+}
+
+/// What `-async 1` did: pad or trim the start of the audio to meet the
+/// video, without stretching it afterwards
+pub fn audio_sync_filter() -> Filter {
+    Filter::new("aresample").option("async", 1).option("first_pts", 0)
+}
+
+impl FrameSync {
+    /// Passthrough for stream copies; for encodes, constant when the output
+    /// rate is fixed (e.g. by an `fps` filter), variable otherwise
+    pub fn choose(encoding: bool, constant_rate: bool) -> Self {
+        match (encoding, constant_rate) {
+            (false, _) => FrameSync::Passthrough,
+            (true, true) => FrameSync::Cfr,
+            (true, false) => FrameSync::Vfr,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FrameSync::Passthrough => "passthrough",
+            FrameSync::Vfr => "vfr",
+            FrameSync::Cfr => "cfr",
+        }
+    }
+
+    /// `-fps_mode <mode>`, or `-vsync <mode>` for FFmpeg builds older than 5.1
+    pub fn args(self, legacy: bool) -> Vec<String> {
+        let option = if legacy { "-vsync" } else { "-fps_mode" };
+        vec![option.to_string(), self.name().to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_per_mode() {
+        assert_eq!(FrameSync::choose(false, true), FrameSync::Passthrough);
+        assert_eq!(FrameSync::choose(true, false).args(false), vec!["-fps_mode", "vfr"]);
+        assert_eq!(FrameSync::choose(true, true).args(true), vec!["-vsync", "cfr"]);
+    }
+
+    #[test]
+    fn test_legacy_options_by_version() {
+        let version = |line: &str| FfmpegVersion::parse(line).unwrap();
+        assert!(needs_legacy_options(Some(&version("ffmpeg version 4.4.2-0ubuntu0.22.04.1 Copyright"))));
+        assert!(needs_legacy_options(Some(&version("ffmpeg version 5.0.1 Copyright"))));
+        assert!(!needs_legacy_options(Some(&version("ffmpeg version 5.1.4 Copyright"))));
+        assert!(!needs_legacy_options(Some(&version("ffmpeg version N-112345-g1234abcd Copyright"))));
+        assert!(!needs_legacy_options(None));
+        assert_eq!(audio_sync_filter().to_string(), "aresample=async=1:first_pts=0");
+    }
+}
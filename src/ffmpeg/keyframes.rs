@@ -0,0 +1,88 @@
+//! Forced keyframes
+//! An encoder places keyframes where it likes, so a tool that stream-copies
+//! from our re-encoded output can only cut near where it wants to. Forcing
+//! them (`-force_key_frames`) at a fixed interval, or where the clip's
+//! content starts and ends inside its handles, lets those cuts land exactly.
+
+use crate::error::{VideoClipError, Result};
+use crate::ranges::TimeRange;
+use crate::time_parser::TimeParser;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ForcedKeyframes {
+    /// At the first frame, and where the content starts and ends inside any
+    /// pre/post-roll handles
+    Start,
+    /// Every this many seconds of output
+    Every(f64),
+}
+
+impl FromStr for ForcedKeyframes {
+    type Err = VideoClipError;
+
+    /// `start`, or an interval such as `2` or `0:02`
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "start" => Ok(ForcedKeyframes::Start),
+            other => match TimeParser::parse_to_seconds(other) {
+                Ok(seconds) => Ok(ForcedKeyframes::Every(seconds)),
+                Err(_) => Err(VideoClipError::InvalidOptions(format!("unknown keyframe placement '{}'", other))),
+            },
+        }
+    }
+}
+
+impl ForcedKeyframes {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            ForcedKeyframes::Every(seconds) if !(seconds.is_finite() && *seconds > 0.0) => Err(VideoClipError::InvalidOptions(
+                format!("keyframe interval must be positive, got {}s", seconds),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// `-force_key_frames` for a clip whose content covers `content`, in
+    /// output seconds (it starts later than 0 when there's a pre-roll)
+    pub fn output_args(&self, content: &TimeRange) -> Vec<String> {
+        let value = match self {
+            ForcedKeyframes::Start => {
+                let mut times = vec![0.0];
+                for edge in [content.start, content.end] {
+                    if edge > *times.last().unwrap_or(&0.0) {
+                        times.push(edge);
+                    }
+                }
+                times.iter().map(|t| format!("{:.3}", t)).collect::<Vec<_>>().join(",")
+            }
+            ForcedKeyframes::Every(seconds) => format!("expr:gte(t,n_forced*{})", seconds),
+        };
+        vec!["-force_key_frames".into(), value]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forced_keyframe_args() {
+        let content = TimeRange { start: 5.0, end: 35.0 };
+        assert_eq!(ForcedKeyframes::Every(2.0).output_args(&content), vec!["-force_key_frames", "expr:gte(t,n_forced*2)"]);
+        assert_eq!(ForcedKeyframes::Start.output_args(&content), vec!["-force_key_frames", "0.000,5.000,35.000"]);
+        assert_eq!(ForcedKeyframes::Start.output_args(&TimeRange { start: 0.0, end: 30.0 })[1], "0.000,30.000");
+    }
+
+    #[test]
+    fn test_parse_and_validate() {
+        assert_eq!("start".parse::<ForcedKeyframes>().unwrap(), ForcedKeyframes::Start);
+        assert_eq!("0:02".parse::<ForcedKeyframes>().unwrap(), ForcedKeyframes::Every(2.0));
+        assert!("often".parse::<ForcedKeyframes>().is_err());
+        assert!(ForcedKeyframes::Every(0.0).validate().is_err());
+        assert!(serde_json::from_str::<ForcedKeyframes>(r#"{"every": 1.5}"#).unwrap().validate().is_ok());
+    }
+}
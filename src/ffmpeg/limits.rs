@@ -0,0 +1,200 @@
+use crate::capabilities::FfmpegVersion;
+use crate::error::{VideoClipError, Result};
+use crate::source_access::SourceAccess;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+/// Process resource limits
+/// Caps encoder threads (`-threads`) and lowers CPU/IO priority with
+/// `nice`/`ionice` so a long batch doesn't starve the rest of the host.
+/// Priority wrappers are Unix-only and skipped elsewhere. `ionice` only
+/// reorders requests, and the idle class does nothing on schedulers without
+/// priorities (most SSDs and network shares), so sources can also be read no
+/// faster than a multiple of real time with `-readrate`, which holds a
+/// background batch to a steady trickle while editors work off the same
+/// disk. Also carries how sources are read ([`SourceAccess`]), which matters
+/// most on the same long batches.
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessLimits {
+    /// Threads per FFmpeg process; FFmpeg picks when unset
+    #[serde(default)]
+    pub threads: Option<u32>,
+    /// `nice` adjustment, -20 (highest priority) to 19 (lowest)
+    #[serde(default)]
+    pub nice: Option<i32>,
+    /// `ionice` scheduling class: 1 realtime, 2 best-effort, 3 idle
+    #[serde(default)]
+    pub ionice_class: Option<u8>,
+    /// Read each input at most this many times faster than real time
+    #[serde(default)]
+    pub read_rate: Option<f64>,
+    #[serde(default)]
+    pub source: SourceAccess,
+}
+
+/// First release with `-readrate`
+const READRATE_SINCE: (u32, u32) = (5, 0);
+
+impl ProcessLimits {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.threads == Some(0) {
+            return Err(VideoClipError::InvalidOptions("threads must be at least 1".to_string()));
+        }
+        if let Some(nice) = self.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(VideoClipError::InvalidOptions(format!("nice must be between -20 and 19, got {}", nice)));
+            }
+        }
+        if let Some(class) = self.ionice_class {
+            if !(1..=3).contains(&class) {
+                return Err(VideoClipError::InvalidOptions(format!("ionice class must be 1, 2 or 3, got {}", class)));
+            }
+        }
+        if let Some(rate) = self.read_rate {
+            if !rate.is_finite() || rate <= 0.0 {
+                return Err(VideoClipError::InvalidOptions(format!("the read rate must be a positive multiple of real time, got {}", rate)));
+            }
+        }
+        self.check_privileges(!cfg!(unix) || is_root())?;
+        self.source.validate()
+    }
+
+    /// Raising priority (a negative nice) and the realtime IO class need
+    /// root; refused up front rather than when the first job starts
+    fn check_privileges(&self, root: bool) -> Result<()> {
+        if root {
+            return Ok(());
+        }
+        if self.nice.is_some_and(|nice| nice < 0) {
+            return Err(VideoClipError::InvalidOptions("a negative nice needs root; use 0 to 19".to_string()));
+        }
+        if self.ionice_class == Some(1) {
+            return Err(VideoClipError::InvalidOptions("the realtime ionice class needs root; use 2 or 3".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Whether `version` can throttle reads; unknown builds are assumed current
+    pub fn check_version(&self, version: Option<&FfmpegVersion>) -> Result<()> {
+        match version {
+            Some(v) if self.read_rate.is_some() && !v.at_least(READRATE_SINCE.0, READRATE_SINCE.1) => {
+                Err(VideoClipError::InvalidOptions(format!(
+                    "throttling reads needs FFmpeg {}.{} or newer for -readrate",
+                    READRATE_SINCE.0, READRATE_SINCE.1
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Input options to put before each `-i` of a file being read
+    pub fn input_args(&self) -> Vec<String> {
+        self.read_rate
+            .map(|rate| vec!["-readrate".to_string(), rate.to_string()])
+            .unwrap_or_default()
+    }
+
+    /// Output options to put before the output file
+    pub fn thread_args(&self) -> Vec<String> {
+        self.threads
+            .map(|threads| vec!["-threads".to_string(), threads.to_string()])
+            .unwrap_or_default()
+    }
+
+    /// `nice`/`ionice` invocation to run the program under
+    pub fn priority_prefix(&self) -> Vec<String> {
+        if !cfg!(unix) {
+            return Vec::new();
+        }
+        let mut prefix = Vec::new();
+        if let Some(nice) = self.nice {
+            prefix.extend(["nice".to_string(), "-n".to_string(), nice.to_string()]);
+        }
+        if let Some(class) = self.ionice_class {
+            prefix.extend(["ionice".to_string(), "-c".to_string(), class.to_string()]);
+        }
+        prefix
+    }
+
+    /// `program args...`, wrapped in the priority prefix when there is one
+    /// (and run in the container when there is one, see [`crate::tools`])
+    pub fn command(&self, program: &str, args: Vec<String>) -> Command {
+        crate::tools::command_with_prefix(self.priority_prefix(), program, args)
+    }
+}
+
+/// Whether the process runs as root, asked of `id` once per process; taken
+/// as root when `id` can't say, leaving the call to `nice`/`ionice`
+fn is_root() -> bool {
+    static ROOT: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *ROOT.get_or_init(|| {
+        Command::new("id")
+            .arg("-u")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .is_none_or(|output| String::from_utf8_lossy(&output.stdout).trim() == "0")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        assert!(ProcessLimits::default().validate().is_ok());
+        assert!(ProcessLimits { threads: Some(0), ..Default::default() }.validate().is_err());
+        assert!(ProcessLimits { nice: Some(20), ..Default::default() }.validate().is_err());
+        assert!(ProcessLimits { ionice_class: Some(4), ..Default::default() }.validate().is_err());
+        assert!(ProcessLimits { read_rate: Some(0.0), ..Default::default() }.validate().is_err());
+        assert!(ProcessLimits { read_rate: Some(f64::NAN), ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_raised_priority_needs_root() {
+        let negative = ProcessLimits { nice: Some(-5), ..Default::default() };
+        let realtime = ProcessLimits { ionice_class: Some(1), ..Default::default() };
+        assert!(negative.check_privileges(false).is_err());
+        assert!(realtime.check_privileges(false).is_err());
+        assert!(negative.check_privileges(true).is_ok());
+        assert!(realtime.check_privileges(true).is_ok());
+        assert!(ProcessLimits { nice: Some(10), ionice_class: Some(3), ..Default::default() }.check_privileges(false).is_ok());
+    }
+
+    #[test]
+    fn test_read_rate() {
+        assert!(ProcessLimits::default().input_args().is_empty());
+        let limits = ProcessLimits { read_rate: Some(4.0), ..Default::default() };
+        assert_eq!(limits.input_args(), vec!["-readrate", "4"]);
+
+        let old = FfmpegVersion::parse("ffmpeg version 4.4.2 Copyright");
+        assert!(limits.check_version(old.as_ref()).is_err());
+        assert!(limits.check_version(None).is_ok());
+        assert!(ProcessLimits::default().check_version(old.as_ref()).is_ok());
+    }
+
+    #[test]
+    fn test_thread_args() {
+        assert!(ProcessLimits::default().thread_args().is_empty());
+        let limits = ProcessLimits { threads: Some(2), ..Default::default() };
+        assert_eq!(limits.thread_args(), vec!["-threads", "2"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_priority_wrapping() {
+        let limits = ProcessLimits { nice: Some(10), ionice_class: Some(3), ..Default::default() };
+        let cmd = limits.command("ffmpeg", vec!["-version".to_string()]);
+        assert_eq!(cmd.get_program(), "nice");
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+        assert_eq!(args, vec!["-n", "10", "ionice", "-c", "3", "ffmpeg", "-version"]);
+
+        assert_eq!(ProcessLimits::default().command("ffmpeg", Vec::new()).get_program(), "ffmpeg");
+    }
+}
@@ -0,0 +1,159 @@
+//! Playback direction and looping
+//! Reversed clips and boomerangs (the clip, then the clip backwards) come from
+//! FFmpeg's `reverse`/`areverse`, and repeats from `loop`/`aloop`; all of them
+//! hold every decoded frame of their input in memory. The command seeks on
+//! the input so only the range is decoded, and ranges are capped at
+//! `MAX_BUFFERED_SECONDS` so a long range can't exhaust memory.
+
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Playback {
+    #[default]
+    Forward,
+    /// The range played backwards
+    Reverse,
+    /// The range played forwards, then backwards, for a seamless loop
+    Boomerang,
+}
+
+/// Longest range that may be reversed or looped; a minute of decoded
+/// 1080p30 is around 10 GB
+pub const MAX_BUFFERED_SECONDS: f64 = 15.0;
+
+/// Most times a clip may be repeated
+pub const MAX_LOOP_COUNT: u32 = 100;
+
+/// The `loop` filter keeps at most this many frames
+const MAX_LOOP_FRAMES: i32 = i16::MAX as i32;
+
+impl FromStr for Playback {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "forward" => Ok(Playback::Forward),
+            "reverse" => Ok(Playback::Reverse),
+            "boomerang" => Ok(Playback::Boomerang),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown playback '{}'", other))),
+        }
+    }
+}
+
+impl Playback {
+    pub fn is_forward(&self) -> bool {
+        *self == Playback::Forward
+    }
+
+    /// Checks a range of `seconds` is short enough to reverse
+    pub fn validate(&self, seconds: f64) -> Result<()> {
+        if !self.is_forward() {
+            check_buffered("reversing", seconds)?;
+        }
+        Ok(())
+    }
+
+    /// Seconds of output for a range of `seconds`
+    pub fn output_duration(&self, seconds: f64) -> f64 {
+        match self {
+            Playback::Boomerang => seconds * 2.0,
+            Playback::Forward | Playback::Reverse => seconds,
+        }
+    }
+
+    /// Video filters applied after the request's own
+    pub fn video_filter_graph(&self) -> FilterGraph {
+        self.filter_graph("split", "reverse", "v=1:a=0")
+    }
+
+    /// Audio filters applied after the request's own
+    pub fn audio_filter_graph(&self) -> FilterGraph {
+        self.filter_graph("asplit", "areverse", "v=0:a=1")
+    }
+
+    fn filter_graph(&self, split: &str, reverse: &str, streams: &str) -> FilterGraph {
+        match self {
+            Playback::Forward => FilterGraph::new(),
+            Playback::Reverse => FilterGraph::from(Filter::new(reverse)),
+            Playback::Boomerang => {
+                let mut graph = FilterGraph::new();
+                graph.add_chain(&[], vec![Filter::new(split).arg(2)], &["there", "back"]);
+                graph.add_chain(&["back"], vec![Filter::new(reverse)], &["reversed"]);
+                graph.add_chain(&["there", "reversed"], vec![Filter::new("concat").option("n", 2).arg(streams)], &[]);
+                graph
+            }
+        }
+    }
+}
+
+fn check_buffered(operation: &str, seconds: f64) -> Result<()> {
+    if seconds > MAX_BUFFERED_SECONDS {
+        return Err(VideoClipError::InvalidOptions(format!(
+            "{} buffers every frame in memory, so it's limited to {}s ranges; this one is {:.1}s",
+            operation, MAX_BUFFERED_SECONDS, seconds
+        )));
+    }
+    Ok(())
+}
+
+/// Checks the clip can be played `count` times in a row from a range of
+/// `seconds`
+pub fn validate_loop_count(count: u32, seconds: f64) -> Result<()> {
+    if !(1..=MAX_LOOP_COUNT).contains(&count) {
+        return Err(VideoClipError::InvalidOptions(format!("loop count must be 1 to {}, got {}", MAX_LOOP_COUNT, count)));
+    }
+    if count > 1 {
+        check_buffered("looping", seconds)?;
+    }
+    Ok(())
+}
+
+/// Video filter playing its input `count` times in a row
+pub fn loop_filter(count: u32) -> Filter {
+    Filter::new("loop").option("loop", count - 1).option("size", MAX_LOOP_FRAMES).option("start", 0)
+}
+
+/// Audio filter playing its input `count` times in a row
+pub fn aloop_filter(count: u32) -> Filter {
+    Filter::new("aloop").option("loop", count - 1).option("size", i32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playback_filters() {
+        assert!(Playback::Forward.video_filter_graph().is_empty());
+        assert_eq!(Playback::Reverse.audio_filter_graph().to_string(), "areverse");
+        assert_eq!(
+            Playback::Boomerang.video_filter_graph().to_string(),
+            "split=2[there][back];[back]reverse[reversed];[there][reversed]concat=n=2:v=1:a=0"
+        );
+        assert_eq!("Boomerang".parse::<Playback>().unwrap(), Playback::Boomerang);
+        assert!("sideways".parse::<Playback>().is_err());
+    }
+
+    #[test]
+    fn test_reverse_length_guard() {
+        assert!(Playback::Reverse.validate(MAX_BUFFERED_SECONDS).is_ok());
+        assert!(Playback::Boomerang.validate(MAX_BUFFERED_SECONDS + 1.0).is_err());
+        assert!(Playback::Forward.validate(3600.0).is_ok());
+        assert_eq!(Playback::Boomerang.output_duration(3.0), 6.0);
+    }
+
+    #[test]
+    fn test_loops() {
+        assert_eq!(loop_filter(3).to_string(), "loop=loop=2:size=32767:start=0");
+        assert_eq!(aloop_filter(2).to_string(), "aloop=loop=1:size=2147483647");
+        assert!(validate_loop_count(1, 3600.0).is_ok());
+        assert!(validate_loop_count(4, 5.0).is_ok());
+        assert!(validate_loop_count(4, 60.0).is_err());
+        assert!(validate_loop_count(0, 5.0).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::error::{VideoClipError, Result};
+
+/// Raw FFmpeg options
+/// Niche FFmpeg options the crate has no setting for can be passed through
+/// as given: input options go just before the source's `-i`, output options
+/// just before the output file. FFmpeg lets a repeated option quietly
+/// override the earlier one (a second `-ss` moves the cut, a second `-c:v`
+/// replaces the encoder), so options the command already generates are
+/// refused rather than left to fight it. A token that isn't an option or
+/// the value of the one before it would be taken for another output file,
+/// so those are refused too, as are values that look like files or URLs.
+/// These checks catch mistakes, not attacks: FFmpeg has too many ways to
+/// name a file, so raw options are never taken from deserialized requests
+/// (see `ClipRequest::extra_input_args`).
+
+#[derive(Debug, PartialEq, Eq)]
+struct OptionKey<'a> {
+    /// `-c` of `-c:v`
+    name: &'a str,
+    /// `v` of `-c:v`
+    stream: Option<&'a str>,
+}
+
+/// Options that add something each time they're given instead of overriding
+const REPEATABLE: &[&str] = &["-map", "-metadata", "-disposition"];
+
+/// The crate names the input and output files and always overwrites
+const RESERVED: &[&str] = &["-i", "-y", "-n"];
+
+/// Other spellings of the same option
+const ALIASES: &[(&str, &str)] = &[
+    ("-vcodec", "-c:v"),
+    ("-acodec", "-c:a"),
+    ("-scodec", "-c:s"),
+    ("-codec", "-c"),
+    ("-vf", "-filter:v"),
+    ("-af", "-filter:a"),
+    ("-vsync", "-fps_mode"),
+    // Both set where the output ends
+    ("-to", "-t"),
+];
+
+impl<'a> OptionKey<'a> {
+    /// `None` for values, including negative numbers
+    fn parse(arg: &'a str) -> Option<Self> {
+        if !arg.starts_with('-') || arg.len() < 2 || arg.parse::<f64>().is_ok() {
+            return None;
+        }
+        let arg = ALIASES.iter().find(|(alias, _)| *alias == arg).map_or(arg, |(_, canonical)| *canonical);
+        Some(match arg.split_once(':') {
+            Some((name, stream)) => Self { name, stream: Some(stream) },
+            None => Self { name: arg, stream: None },
+        })
+    }
+
+    /// Whether both set the option for some stream: `-c` covers `-c:v`,
+    /// and `-c:v` covers `-c:v:0`
+    fn overlaps(&self, other: &Self) -> bool {
+        self.name == other.name && match (self.stream, other.stream) {
+            (Some(a), Some(b)) => a.starts_with(b) || b.starts_with(a),
+            _ => true,
+        }
+    }
+}
+
+/// Refuses options that would replace the command's input or output, or
+/// name other files
+pub fn validate(args: &[String]) -> Result<()> {
+    if let Some(arg) = args.iter().find(|arg| RESERVED.contains(&arg.as_str())) {
+        return Err(VideoClipError::InvalidOptions(format!(
+            "raw FFmpeg options can't include {} (the clipper sets the input and output files)",
+            arg
+        )));
+    }
+    let mut after_option = false;
+    for arg in args {
+        if OptionKey::parse(arg).is_some() {
+            after_option = true;
+            continue;
+        }
+        if !after_option {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "raw FFmpeg options can't include '{}' on its own (FFmpeg would take it for another output file)",
+                arg
+            )));
+        }
+        if looks_like_file(arg) {
+            return Err(VideoClipError::InvalidOptions(format!("raw FFmpeg options can't name files or URLs, got '{}'", arg)));
+        }
+        after_option = false;
+    }
+    Ok(())
+}
+
+/// A path (`/tmp/x.mp4`, `../x`, `C:\x`), a URL, or a bare `name.ext`;
+/// option values are names, numbers and `key=value` lists instead
+fn looks_like_file(arg: &str) -> bool {
+    if arg.parse::<f64>().is_ok() {
+        return false;
+    }
+    let extension = arg.rsplit_once('.').map(|(stem, ext)| {
+        !stem.is_empty() && (2..=5).contains(&ext.len()) && ext.chars().all(|c| c.is_ascii_alphabetic())
+    });
+    arg.starts_with(['/', '.', '~', '\\'])
+        || arg.contains("://")
+        || arg.contains(":\\")
+        || (extension == Some(true) && !arg.contains(['=', ':']))
+}
+
+/// The options of `extra` that `generated` already sets
+pub fn conflicts<'a>(extra: &'a [String], generated: &[String]) -> Vec<&'a str> {
+    let generated: Vec<OptionKey> = generated.iter().filter_map(|arg| OptionKey::parse(arg)).collect();
+    extra.iter()
+        .filter(|arg| OptionKey::parse(arg).is_some_and(|key| {
+            !REPEATABLE.contains(&key.name) && generated.iter().any(|g| g.overlaps(&key))
+        }))
+        .map(String::as_str)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_conflicts_with_generated_options() {
+        let generated = args("-i in.mp4 -ss 10 -t 5 -map 0:v? -c:v libx264 -crf 23 -c:a copy -y out.mp4");
+        assert!(conflicts(&args("-probesize 50M -analyzeduration 10M"), &generated).is_empty());
+        assert_eq!(conflicts(&args("-ss 12"), &generated), vec!["-ss"]);
+        assert_eq!(conflicts(&args("-to 20 -vcodec libx265"), &generated), vec!["-to", "-vcodec"]);
+        assert_eq!(conflicts(&args("-c copy -c:v:0 h264"), &generated), vec!["-c", "-c:v:0"]);
+        assert!(conflicts(&args("-c:s mov_text -map 0:s? -metadata:s:a:0 language=eng"), &generated).is_empty());
+        // Negative values aren't options
+        assert!(conflicts(&args("-itsoffset -1.5"), &generated).is_empty());
+    }
+
+    #[test]
+    fn test_reserved_options() {
+        assert!(validate(&args("-movflags +faststart")).is_ok());
+        assert!(matches!(validate(&args("-i other.mp4")), Err(VideoClipError::InvalidOptions(_))));
+        assert!(validate(&args("-n")).is_err());
+    }
+
+    #[test]
+    fn test_stray_files_are_refused() {
+        assert!(validate(&args("-metadata title=talk.mp4 -movflags +faststart -ss -1.5 -vf scale=iw/2:-2 -shortest")).is_ok());
+        // An extra output file after a complete option
+        let err = validate(&args("-metadata x=y /tmp/evil.mp4")).unwrap_err();
+        assert!(err.to_string().contains("'/tmp/evil.mp4' on its own"));
+        assert!(validate(&args("evil.mp4")).is_err());
+        // In value position after an option that takes none
+        assert!(validate(&args("-an /tmp/evil.mp4")).is_err());
+        assert!(validate(&args("-shortest evil.mkv")).is_err());
+        assert!(validate(&args("-an rtmp://example.com/live")).is_err());
+        assert!(validate(&args("-passlogfile ../logs/pass")).is_err());
+        assert!(validate(&args("-f mp4 -preset veryfast -pix_fmt yuv420p")).is_ok());
+    }
+}
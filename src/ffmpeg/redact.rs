@@ -0,0 +1,321 @@
+//! Privacy redaction
+//! Hides rectangles of the picture (faces, license plates, screens) while
+//! clipping, for the whole clip or a window of it. Each region is cropped
+//! out, blurred or pixelated, and laid back over the frame with `overlay`;
+//! a solid box is drawn with `drawbox`. Intervals of the audio (profanity,
+//! names) are muted with `volume`, and bleeped by mixing in a tone that only
+//! sounds while they're muted.
+
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{time_window, Filter, FilterGraph};
+use crate::time_parser::TimeParser;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactStyle {
+    #[default]
+    Blur,
+    /// Large square blocks
+    Pixelate,
+    /// A solid black rectangle
+    Box,
+}
+
+impl FromStr for RedactStyle {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "blur" => Ok(RedactStyle::Blur),
+            "pixelate" => Ok(RedactStyle::Pixelate),
+            "box" => Ok(RedactStyle::Box),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown redaction style '{}'", other))),
+        }
+    }
+}
+
+/// A rectangle of the source frame, in pixels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct RedactRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub style: RedactStyle,
+    /// When the region is hidden, in seconds from the start of the clip;
+    /// throughout when unset
+    #[serde(default)]
+    pub start: Option<f64>,
+    #[serde(default)]
+    pub end: Option<f64>,
+}
+
+/// Smallest region side; blurring needs a few pixels of chroma to work with
+const MIN_SIDE: u32 = 8;
+
+/// Side of a pixelation block
+const PIXEL_BLOCK: u32 = 16;
+
+impl FromStr for RedactRegion {
+    type Err = VideoClipError;
+
+    /// `WxH+X+Y`, optionally followed by a window such as `@5-12`, `@0:05-`
+    /// or `@-12`; the style is blur
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || VideoClipError::InvalidOptions(format!("invalid region '{}' (expected WxH+X+Y[@START-END])", s));
+        let (geometry, window) = match s.split_once('@') {
+            Some((geometry, window)) => (geometry, Some(window)),
+            None => (s, None),
+        };
+        let (width, rest) = geometry.split_once('x').ok_or_else(invalid)?;
+        let mut numbers = rest.split('+').map(|n| n.trim().parse::<u32>().map_err(|_| invalid()));
+        let (height, x, y) = match (numbers.next(), numbers.next(), numbers.next(), numbers.next()) {
+            (Some(height), Some(x), Some(y), None) => (height?, x?, y?),
+            _ => return Err(invalid()),
+        };
+        let (start, end) = match window {
+            Some(window) => {
+                let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+                let time = |t: &str| match t.trim() {
+                    "" => Ok(None),
+                    t => TimeParser::parse_to_seconds(t).map(Some),
+                };
+                (time(start)?, time(end)?)
+            }
+            None => (None, None),
+        };
+        Ok(RedactRegion {
+            x,
+            y,
+            width: width.trim().parse().map_err(|_| invalid())?,
+            height,
+            style: RedactStyle::default(),
+            start,
+            end,
+        })
+    }
+}
+
+impl RedactRegion {
+    pub fn validate(&self) -> Result<()> {
+        if self.width < MIN_SIDE || self.height < MIN_SIDE {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "redacted regions must be at least {}x{} pixels, got {}x{}",
+                MIN_SIDE, MIN_SIDE, self.width, self.height
+            )));
+        }
+        for seconds in [self.start, self.end].into_iter().flatten() {
+            if !(seconds.is_finite() && seconds >= 0.0) {
+                return Err(VideoClipError::InvalidOptions(format!("redaction times must be at least 0s, got {}", seconds)));
+            }
+        }
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            if end <= start {
+                return Err(VideoClipError::InvalidOptions(format!("redaction ends ({}s) before it starts ({}s)", end, start)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Hides the region; `offset` is the timestamp of the clip's first frame
+    /// in the graph (see [`crate::ffmpeg::attribution::Attribution::filter_graph`])
+    pub fn filter_graph(&self, offset: f64) -> FilterGraph {
+        let (x, y, w, h) = (self.x, self.y, self.width, self.height);
+        let window = time_window(self.start, self.end, offset);
+        let enable = |filter: Filter| match &window {
+            Some(window) => filter.option("enable", window),
+            None => filter,
+        };
+
+        let patch = match self.style {
+            RedactStyle::Box => {
+                return enable(Filter::new("drawbox").option("x", x).option("y", y).option("w", w).option("h", h)
+                    .option("color", "black").option("t", "fill")).into();
+            }
+            // Chroma planes are half the size in 4:2:0, so their radius is too
+            RedactStyle::Blur => vec![Filter::new("boxblur")
+                .option("luma_radius", w.min(h) / 4)
+                .option("luma_power", 2)
+                .option("chroma_radius", w.min(h) / 8)
+                .option("chroma_power", 2)],
+            RedactStyle::Pixelate => vec![
+                Filter::new("scale").arg((w / PIXEL_BLOCK).max(1)).arg((h / PIXEL_BLOCK).max(1)).option("flags", "area"),
+                Filter::new("scale").arg(w).arg(h).option("flags", "neighbor"),
+            ],
+        };
+        let mut graph = FilterGraph::new();
+        graph.add_chain(&[], vec![Filter::new("split").arg(2)], &["redact_base", "redact_source"]);
+        let crop = Filter::new("crop").arg(w).arg(h).arg(x).arg(y);
+        graph.add_chain(&["redact_source"], std::iter::once(crop).chain(patch).collect(), &["redact_patch"]);
+        graph.add_chain(&["redact_base", "redact_patch"], vec![enable(Filter::new("overlay").arg(x).arg(y))], &[]);
+        graph
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioRedactStyle {
+    #[default]
+    Mute,
+    /// Mute and play a tone instead
+    Bleep,
+}
+
+/// An interval of the clip's audio to silence, in seconds from the start of
+/// the clip
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct AudioRedaction {
+    pub start: f64,
+    pub end: f64,
+    #[serde(default)]
+    pub style: AudioRedactStyle,
+}
+
+/// Pitch of the bleep tone in Hz
+const BLEEP_FREQUENCY: u32 = 1000;
+
+impl FromStr for AudioRedaction {
+    type Err = VideoClipError;
+
+    /// `START-END`, e.g. `12.5-13.2` or `0:12-0:14`; the style is mute
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s.split_once('-')
+            .ok_or_else(|| VideoClipError::InvalidOptions(format!("invalid interval '{}' (expected START-END)", s)))?;
+        Ok(AudioRedaction {
+            start: TimeParser::parse_to_seconds(start.trim())?,
+            end: TimeParser::parse_to_seconds(end.trim())?,
+            style: AudioRedactStyle::default(),
+        })
+    }
+}
+
+impl AudioRedaction {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.start.is_finite() && self.start >= 0.0 && self.end.is_finite() && self.end > self.start) {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "audio redaction needs 0 <= start < end, got {}s to {}s",
+                self.start, self.end
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `enable` expression covering every interval
+fn any_of(intervals: &[&AudioRedaction], offset: f64) -> String {
+    intervals.iter()
+        .filter_map(|i| time_window(Some(i.start), Some(i.end), offset))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// Silences every interval and, if any are bleeps, mixes in the tone while
+/// they play; `offset` is the timestamp of the clip's first sample
+pub fn audio_redaction_graph(intervals: &[AudioRedaction], offset: f64) -> FilterGraph {
+    if intervals.is_empty() {
+        return FilterGraph::new();
+    }
+    let all: Vec<&AudioRedaction> = intervals.iter().collect();
+    let mute = Filter::new("volume").option("volume", 0).option("enable", any_of(&all, offset));
+    let bleeps: Vec<&AudioRedaction> = intervals.iter().filter(|i| i.style == AudioRedactStyle::Bleep).collect();
+    if bleeps.is_empty() {
+        return mute.into();
+    }
+
+    let mut graph = FilterGraph::new();
+    graph.add_chain(&[], vec![mute], &["redact_voice"]);
+    graph.add_chain(
+        &[],
+        vec![
+            Filter::new("sine").option("frequency", BLEEP_FREQUENCY).option("sample_rate", 48000),
+            Filter::new("volume").option("volume", 0).option("enable", format!("not({})", any_of(&bleeps, offset))),
+        ],
+        &["redact_tone"],
+    );
+    graph.add_chain(
+        &["redact_voice", "redact_tone"],
+        vec![Filter::new("amix").option("inputs", 2).option("duration", "first").option("dropout_transition", 0).option("normalize", 0)],
+        &[],
+    );
+    graph
+}
+
+/// Every region's filters, one after another
+pub fn redaction_graph(regions: &[RedactRegion], offset: f64) -> FilterGraph {
+    regions.iter().fold(FilterGraph::new(), |graph, region| graph.then(region.filter_graph(offset)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_region() {
+        let region: RedactRegion = "200x100+640+360@0:05-12".parse().unwrap();
+        assert_eq!((region.width, region.height, region.x, region.y), (200, 100, 640, 360));
+        assert_eq!((region.start, region.end), (Some(5.0), Some(12.0)));
+        let region: RedactRegion = "64x64+0+0@-3".parse().unwrap();
+        assert_eq!((region.start, region.end), (None, Some(3.0)));
+        assert!("64x64+0".parse::<RedactRegion>().is_err());
+        assert!("64x64+0+0@5".parse::<RedactRegion>().is_err());
+        assert!("4x4+0+0".parse::<RedactRegion>().unwrap().validate().is_err());
+        assert!("64x64+0+0@9-3".parse::<RedactRegion>().unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_region_filters() {
+        let mut region: RedactRegion = "200x100+640+360".parse().unwrap();
+        assert_eq!(
+            region.filter_graph(0.0).to_string(),
+            "split=2[redact_base][redact_source];\
+             [redact_source]crop=200:100:640:360,boxblur=luma_radius=25:luma_power=2:chroma_radius=12:chroma_power=2[redact_patch];\
+             [redact_base][redact_patch]overlay=640:360"
+        );
+
+        region.style = RedactStyle::Pixelate;
+        region.end = Some(4.0);
+        let graph = region.filter_graph(60.0).to_string();
+        assert!(graph.contains("crop=200:100:640:360,scale=12:6:flags=area,scale=200:100:flags=neighbor[redact_patch]"));
+        assert!(graph.ends_with("overlay=640:360:enable=between(t\\,60\\,64)"));
+
+        region.style = RedactStyle::Box;
+        assert_eq!(
+            region.filter_graph(60.0).to_string(),
+            "drawbox=x=640:y=360:w=200:h=100:color=black:t=fill:enable=between(t\\,60\\,64)"
+        );
+    }
+
+    #[test]
+    fn test_mute_and_bleep() {
+        let mut intervals: Vec<AudioRedaction> = ["1-2", "0:05-0:06.5"].iter().map(|i| i.parse().unwrap()).collect();
+        assert_eq!(
+            audio_redaction_graph(&intervals, 10.0).to_string(),
+            "volume=volume=0:enable=between(t\\,11\\,12)+between(t\\,15\\,16.5)"
+        );
+
+        intervals[1].style = AudioRedactStyle::Bleep;
+        assert_eq!(
+            audio_redaction_graph(&intervals, 0.0).to_string(),
+            "volume=volume=0:enable=between(t\\,1\\,2)+between(t\\,5\\,6.5)[redact_voice];\
+             sine=frequency=1000:sample_rate=48000,volume=volume=0:enable=not(between(t\\,5\\,6.5))[redact_tone];\
+             [redact_voice][redact_tone]amix=inputs=2:duration=first:dropout_transition=0:normalize=0"
+        );
+        assert!(audio_redaction_graph(&[], 0.0).is_empty());
+        assert!("3-1".parse::<AudioRedaction>().unwrap().validate().is_err());
+        assert!("3".parse::<AudioRedaction>().is_err());
+    }
+
+    #[test]
+    fn test_regions_are_chained() {
+        let regions: Vec<RedactRegion> = ["64x64+0+0", "64x64+100+0"].iter().map(|r| r.parse().unwrap()).collect();
+        let graph = redaction_graph(&regions, 0.0).to_string();
+        assert!(graph.contains("[redact_base][redact_patch]overlay=0:0,split=2[redact_base_3][redact_source_3];"));
+        assert!(graph.ends_with("[redact_base_3][redact_patch_3]overlay=100:0"));
+    }
+}
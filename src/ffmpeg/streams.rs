@@ -0,0 +1,260 @@
+//! Stream selection
+//! Which of the source's streams go into the clip, as `-map` options. The
+//! default keeps every video and audio stream, as clips always have; a
+//! selection can instead name streams by index, type or language, e.g. the
+//! second camera angle and the English commentary. Subtitles are converted
+//! to MP4's text format and data streams (timecodes, GPS tracks) are copied;
+//! attachments such as Matroska fonts have no place in an MP4 and are dropped.
+
+use crate::error::{VideoClipError, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamType {
+    Video,
+    Audio,
+    Subtitle,
+    Data,
+    Attachment,
+}
+
+impl StreamType {
+    /// FFmpeg's stream specifier letter
+    pub fn specifier(self) -> char {
+        match self {
+            StreamType::Video => 'v',
+            StreamType::Audio => 'a',
+            StreamType::Subtitle => 's',
+            StreamType::Data => 'd',
+            StreamType::Attachment => 't',
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StreamType::Video => "video",
+            StreamType::Audio => "audio",
+            StreamType::Subtitle => "subtitle",
+            StreamType::Data => "data",
+            StreamType::Attachment => "attachment",
+        }
+    }
+}
+
+impl FromStr for StreamType {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "v" | "video" => Ok(StreamType::Video),
+            "a" | "audio" => Ok(StreamType::Audio),
+            "s" | "subtitle" | "subtitles" => Ok(StreamType::Subtitle),
+            "d" | "data" => Ok(StreamType::Data),
+            "t" | "attachment" | "attachments" => Ok(StreamType::Attachment),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown stream type '{}'", other))),
+        }
+    }
+}
+
+/// One rule of a selection, written `all`, an index (`2`), a type (`video`,
+/// `audio`, `subtitle`, `data`) or a type and language (`audio:eng`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum StreamSelector {
+    /// Every stream MP4 can carry
+    All,
+    /// The stream at this index, as ffprobe numbers them
+    Index(u32),
+    /// Every stream of a type
+    Type(StreamType),
+    /// Streams of a type tagged with a language, e.g. `eng`
+    Language { kind: StreamType, language: String },
+}
+
+impl StreamSelector {
+    /// `-map` arguments; type and language rules match nothing rather than
+    /// fail when the source has no such stream
+    fn map_args(&self) -> Vec<String> {
+        match self {
+            // Attachments can't go into MP4
+            StreamSelector::All => vec!["-map".into(), "0".into(), "-map".into(), "-0:t?".into()],
+            StreamSelector::Index(index) => vec!["-map".into(), format!("0:{}", index)],
+            StreamSelector::Type(kind) => vec!["-map".into(), format!("0:{}?", kind.specifier())],
+            StreamSelector::Language { kind, language } => {
+                vec!["-map".into(), format!("0:{}:m:language:{}?", kind.specifier(), language)]
+            }
+        }
+    }
+
+    /// Whether the rule can pick streams of `kind`
+    fn may_select(&self, kind: StreamType) -> bool {
+        match self {
+            StreamSelector::All | StreamSelector::Index(_) => true,
+            StreamSelector::Type(k) | StreamSelector::Language { kind: k, .. } => *k == kind,
+        }
+    }
+}
+
+impl FromStr for StreamSelector {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(StreamSelector::All);
+        }
+        if let Ok(index) = s.parse() {
+            return Ok(StreamSelector::Index(index));
+        }
+        let selector = match s.split_once(':') {
+            Some((kind, language)) => {
+                if language.is_empty() || !language.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    return Err(VideoClipError::InvalidOptions(format!("invalid language '{}' in stream selector '{}'", language, s)));
+                }
+                StreamSelector::Language { kind: kind.parse()?, language: language.to_lowercase() }
+            }
+            None => StreamSelector::Type(s.parse()?),
+        };
+        Ok(selector)
+    }
+}
+
+impl fmt::Display for StreamSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamSelector::All => write!(f, "all"),
+            StreamSelector::Index(index) => write!(f, "{}", index),
+            StreamSelector::Type(kind) => write!(f, "{}", kind.name()),
+            StreamSelector::Language { kind, language } => write!(f, "{}:{}", kind.name(), language),
+        }
+    }
+}
+
+impl TryFrom<String> for StreamSelector {
+    type Error = VideoClipError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<StreamSelector> for String {
+    fn from(selector: StreamSelector) -> Self {
+        selector.to_string()
+    }
+}
+
+/// The streams a clip keeps; every video and audio stream when empty
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct StreamSelection {
+    /// Selectors such as `all`, `2`, `video` or `audio:eng`
+    #[schemars(with = "Vec<String>")]
+    pub selectors: Vec<StreamSelector>,
+}
+
+impl StreamSelection {
+    pub fn new(selectors: Vec<StreamSelector>) -> Self {
+        Self { selectors }
+    }
+
+    /// Every video and audio stream, optional so audio-less sources work
+    pub fn is_default(&self) -> bool {
+        self.selectors.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.selectors.iter().any(|s| matches!(s, StreamSelector::Type(StreamType::Attachment) | StreamSelector::Language { kind: StreamType::Attachment, .. })) {
+            return Err(VideoClipError::InvalidOptions("attachment streams can't be written to MP4 clips".to_string()));
+        }
+        Ok(())
+    }
+
+    /// `-map` arguments for the video and other non-audio streams
+    pub fn video_map_args(&self) -> Vec<String> {
+        if self.is_default() {
+            return vec!["-map".into(), "0:v?".into()];
+        }
+        self.map_args(|s| !matches!(s, StreamSelector::Type(StreamType::Audio) | StreamSelector::Language { kind: StreamType::Audio, .. }))
+    }
+
+    /// `-map` arguments for the audio streams; overlay mixes replace these
+    pub fn audio_map_args(&self) -> Vec<String> {
+        if self.is_default() {
+            return vec!["-map".into(), "0:a?".into()];
+        }
+        self.map_args(|s| matches!(s, StreamSelector::Type(StreamType::Audio) | StreamSelector::Language { kind: StreamType::Audio, .. }))
+    }
+
+    fn map_args(&self, keep: impl Fn(&StreamSelector) -> bool) -> Vec<String> {
+        self.selectors.iter().filter(|s| keep(s)).flat_map(StreamSelector::map_args).collect()
+    }
+
+    /// Codecs for the subtitle and data streams the selection can pick up,
+    /// written to a `container` such as `mp4`
+    pub fn codec_args(&self, container: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.selectors.iter().any(|s| s.may_select(StreamType::Subtitle)) {
+            let subtitle_codec = if container == "mkv" { "srt" } else { "mov_text" };
+            args.extend(["-c:s".into(), subtitle_codec.into()]);
+        }
+        if self.selectors.iter().any(|s| s.may_select(StreamType::Data)) {
+            args.extend(["-c:d".into(), "copy".into()]);
+        }
+        args
+    }
+}
+
+impl FromStr for StreamSelection {
+    type Err = VideoClipError;
+
+    /// Comma-separated selectors, e.g. `1,audio:eng`
+    fn from_str(s: &str) -> Result<Self> {
+        let selection = Self::new(s.split(',').filter(|s| !s.trim().is_empty()).map(str::parse).collect::<Result<_>>()?);
+        selection.validate()?;
+        Ok(selection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_selectors() {
+        let selection: StreamSelection = "1, audio:ENG,subtitles".parse().unwrap();
+        assert_eq!(selection.selectors, vec![
+            StreamSelector::Index(1),
+            StreamSelector::Language { kind: StreamType::Audio, language: "eng".to_string() },
+            StreamSelector::Type(StreamType::Subtitle),
+        ]);
+        assert_eq!(serde_json::to_string(&selection).unwrap(), r#"["1","audio:eng","subtitle"]"#);
+        assert_eq!(serde_json::from_str::<StreamSelection>(r#"["all"]"#).unwrap().selectors, vec![StreamSelector::All]);
+
+        assert!("angle".parse::<StreamSelection>().is_err());
+        assert!("audio:".parse::<StreamSelection>().is_err());
+        assert!("attachment".parse::<StreamSelection>().is_err());
+    }
+
+    #[test]
+    fn test_map_args() {
+        let default = StreamSelection::default();
+        assert_eq!(default.video_map_args(), vec!["-map", "0:v?"]);
+        assert_eq!(default.audio_map_args(), vec!["-map", "0:a?"]);
+        assert!(default.codec_args("mp4").is_empty());
+
+        let angle: StreamSelection = "2,audio:eng".parse().unwrap();
+        assert_eq!(angle.video_map_args().join(" "), "-map 0:2");
+        assert_eq!(angle.audio_map_args().join(" "), "-map 0:a:m:language:eng?");
+        assert_eq!(angle.codec_args("mp4").join(" "), "-c:s mov_text -c:d copy");
+        assert_eq!(angle.codec_args("mkv").join(" "), "-c:s srt -c:d copy");
+
+        let all: StreamSelection = "all".parse().unwrap();
+        assert_eq!(all.video_map_args().join(" "), "-map 0 -map -0:t?");
+        assert!(all.audio_map_args().is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::probe::MediaInfo;
+use serde::{Deserialize, Serialize};
+
+/// Container timestamp handling
+/// Some containers carry timestamps that don't survive a plain cut into MP4.
+/// DVR `.ts` captures start hours into their clock, miss PTS on some packets
+/// and announce their programs (PAT/PMT) late; program streams and FLV/AVI
+/// often lack PTS altogether. The fixes are picked from the source's probe
+/// data, so well-behaved MP4/MKV sources get the same command as before.
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimestampFixes {
+    /// `-fflags +genpts`: fill in missing presentation timestamps
+    #[serde(default)]
+    pub generate_pts: bool,
+    /// `-copyts -start_at_zero`: keep the source's timing, shifted so it
+    /// starts at zero, instead of letting FFmpeg re-base each stream
+    #[serde(default)]
+    pub copy_timestamps: bool,
+    /// Read further into the file to find streams announced late
+    #[serde(default)]
+    pub deep_probe: bool,
+    /// `-bsf:a aac_adtstoasc`: ADTS AAC from a transport stream needs
+    /// repackaging before MP4 will take it as a stream copy
+    #[serde(default)]
+    pub adts_audio: bool,
+}
+
+/// `-analyzeduration` and `-probesize` for a deep probe
+const DEEP_PROBE: &str = "20M";
+
+impl TimestampFixes {
+    /// The fixes `info`'s container needs; none for containers that aren't
+    /// known to need any
+    pub fn for_source(info: &MediaInfo) -> Self {
+        let formats: Vec<&str> = info.format_name.split(',').collect();
+        let aac_audio = info.audio_stream().and_then(|a| a.codec_name.as_deref()) == Some("aac");
+        if formats.contains(&"mpegts") {
+            Self { generate_pts: true, copy_timestamps: true, deep_probe: true, adts_audio: aac_audio }
+        } else if formats.iter().any(|f| matches!(*f, "mpeg" | "vob" | "flv" | "avi")) {
+            Self { generate_pts: true, ..Default::default() }
+        } else {
+            Self::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// `-fflags` flags for the input, without their `+`
+    pub fn input_flags(&self) -> Vec<&'static str> {
+        if self.generate_pts { vec!["genpts"] } else { Vec::new() }
+    }
+
+    /// Demuxer options other than `-fflags`, to put before `-i`
+    pub fn input_args(&self) -> Vec<String> {
+        if !self.deep_probe {
+            return Vec::new();
+        }
+        ["-analyzeduration", DEEP_PROBE, "-probesize", DEEP_PROBE].map(String::from).to_vec()
+    }
+
+    /// Output options; `audio_copied` says whether the audio is stream copied
+    pub fn output_args(&self, audio_copied: bool) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.copy_timestamps {
+            args.extend(["-copyts", "-start_at_zero"].map(String::from));
+        }
+        if self.adts_audio && audio_copied {
+            args.extend(["-bsf:a", "aac_adtstoasc"].map(String::from));
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::StreamInfo;
+
+    fn source(format_name: &str, audio_codec: &str) -> MediaInfo {
+        MediaInfo {
+            format_name: format_name.to_string(),
+            streams: vec![StreamInfo {
+                codec_type: "audio".to_string(),
+                codec_name: Some(audio_codec.to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fixes_follow_the_container() {
+        let ts = TimestampFixes::for_source(&source("mpegts", "aac"));
+        assert_eq!(ts.input_flags(), vec!["genpts"]);
+        assert_eq!(ts.input_args().join(" "), "-analyzeduration 20M -probesize 20M");
+        assert_eq!(ts.output_args(true).join(" "), "-copyts -start_at_zero -bsf:a aac_adtstoasc");
+        assert_eq!(ts.output_args(false).join(" "), "-copyts -start_at_zero");
+
+        let ts_ac3 = TimestampFixes::for_source(&source("mpegts", "ac3"));
+        assert!(!ts_ac3.adts_audio);
+
+        let vob = TimestampFixes::for_source(&source("mpeg", "mp2"));
+        assert_eq!(vob, TimestampFixes { generate_pts: true, ..Default::default() });
+
+        assert!(TimestampFixes::for_source(&source("mov,mp4,m4a,3gp,3g2,mj2", "aac")).is_empty());
+        assert!(TimestampFixes::for_source(&source("matroska,webm", "opus")).is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+//! Track dispositions and labels
+//! Players pick the audio and subtitle tracks flagged `default` (and show
+//! `forced` subtitles regardless of the viewer's settings), and label tracks
+//! by their title and language. Clips inherit the source's flags, which is
+//! often the wrong track once streams have been selected; these settings
+//! override them per output track. Making a track the default clears the
+//! flag on the other tracks of its type.
+
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::streams::StreamType;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct TrackSettings {
+    /// `video`, `audio` or `subtitle`
+    pub kind: StreamType,
+    /// Which track of that kind in the clip, from 0
+    pub index: u32,
+    /// Set or clear the default flag; left as the source had it when unset
+    #[serde(default)]
+    pub default: Option<bool>,
+    /// Subtitles shown even when the viewer turned subtitles off
+    #[serde(default)]
+    pub forced: bool,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// ISO 639-2 code, e.g. `eng`
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+impl FromStr for TrackSettings {
+    type Err = VideoClipError;
+
+    /// `KIND:INDEX` followed by comma-separated settings, e.g.
+    /// `audio:1,default,language=eng,title=Commentary` or `s:0,forced`
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || VideoClipError::InvalidOptions(format!("invalid track '{}' (expected KIND:INDEX[,default][,forced][,title=T][,language=L])", s));
+        let mut parts = s.split(',');
+        let (kind, index) = parts.next().and_then(|track| track.split_once(':')).ok_or_else(invalid)?;
+        let mut track = TrackSettings {
+            kind: kind.trim().parse()?,
+            index: index.trim().parse().map_err(|_| invalid())?,
+            default: None,
+            forced: false,
+            title: None,
+            language: None,
+        };
+        for part in parts {
+            match part.trim().split_once('=') {
+                None if part.trim() == "default" => track.default = Some(true),
+                None if part.trim() == "not-default" => track.default = Some(false),
+                None if part.trim() == "forced" => track.forced = true,
+                Some(("title", title)) => track.title = Some(title.to_string()),
+                Some(("language" | "lang", language)) => track.language = Some(language.trim().to_string()),
+                _ => return Err(VideoClipError::InvalidOptions(format!("unknown track setting '{}'", part.trim()))),
+            }
+        }
+        Ok(track)
+    }
+}
+
+impl TrackSettings {
+    pub fn validate(&self) -> Result<()> {
+        if !matches!(self.kind, StreamType::Video | StreamType::Audio | StreamType::Subtitle) {
+            return Err(VideoClipError::InvalidOptions(format!("{} tracks can't be labeled", self.kind.name())));
+        }
+        if self.forced && self.kind != StreamType::Subtitle {
+            return Err(VideoClipError::InvalidOptions("only subtitle tracks can be forced".to_string()));
+        }
+        if let Some(language) = &self.language {
+            if language.len() != 3 || !language.chars().all(|c| c.is_ascii_lowercase()) {
+                return Err(VideoClipError::InvalidOptions(format!("language '{}' isn't a three-letter ISO 639-2 code", language)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Output stream specifier, e.g. `a:1`
+    fn specifier(&self) -> String {
+        format!("{}:{}", self.kind.specifier(), self.index)
+    }
+
+    fn disposition(&self) -> Option<String> {
+        let flags: Vec<&str> = [(self.default == Some(true), "default"), (self.forced, "forced")]
+            .into_iter()
+            .filter_map(|(set, flag)| set.then_some(flag))
+            .collect();
+        match (flags.is_empty(), self.default) {
+            (false, _) => Some(flags.join("+")),
+            (true, Some(false)) => Some("0".to_string()),
+            (true, _) => None,
+        }
+    }
+}
+
+/// `-disposition` and `-metadata:s` output options for every track. FFmpeg
+/// applies the last option that matches a stream, so the flags of a kind with
+/// a new default are cleared before the tracks are set.
+pub fn track_args(tracks: &[TrackSettings]) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+    let mut cleared: Vec<StreamType> = Vec::new();
+    for track in tracks.iter().filter(|t| t.default == Some(true)) {
+        if !cleared.contains(&track.kind) {
+            cleared.push(track.kind);
+            args.extend([format!("-disposition:{}", track.kind.specifier()), "0".into()]);
+        }
+    }
+    for track in tracks {
+        let specifier = track.specifier();
+        if let Some(disposition) = track.disposition() {
+            args.extend([format!("-disposition:{}", specifier), disposition]);
+        }
+        if let Some(title) = &track.title {
+            args.extend([format!("-metadata:s:{}", specifier), format!("title={}", title)]);
+        }
+        if let Some(language) = &track.language {
+            args.extend([format!("-metadata:s:{}", specifier), format!("language={}", language)]);
+        }
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_track() {
+        let track: TrackSettings = "audio:1,default,language=eng,title=Director's commentary".parse().unwrap();
+        assert_eq!((track.kind, track.index, track.default), (StreamType::Audio, 1, Some(true)));
+        assert_eq!(track.title.as_deref(), Some("Director's commentary"));
+        assert_eq!(track.language.as_deref(), Some("eng"));
+        assert!("s:0,forced".parse::<TrackSettings>().unwrap().validate().is_ok());
+        assert!("a:0,forced".parse::<TrackSettings>().unwrap().validate().is_err());
+        assert!("a:0,lang=english".parse::<TrackSettings>().unwrap().validate().is_err());
+        assert!("a:0,loud".parse::<TrackSettings>().is_err());
+        assert!("audio".parse::<TrackSettings>().is_err());
+    }
+
+    #[test]
+    fn test_track_args() {
+        let tracks: Vec<TrackSettings> = ["a:1,default,language=eng", "a:0,title=Original", "s:0,forced"]
+            .iter()
+            .map(|t| t.parse().unwrap())
+            .collect();
+        assert_eq!(
+            track_args(&tracks).join(" "),
+            "-disposition:a 0 -disposition:a:1 default -metadata:s:a:1 language=eng \
+             -metadata:s:a:0 title=Original -disposition:s:0 forced"
+        );
+        assert_eq!(track_args(&["v:0,not-default".parse().unwrap()]), vec!["-disposition:v:0", "0"]);
+    }
+}
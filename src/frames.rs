@@ -0,0 +1,278 @@
+use crate::error::Result;
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Image sequence export
+/// Dumps a time range of a video as numbered still images (e.g. for ML datasets)
+/// with optional frame-rate sampling and scaling
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// Filename prefix shared by every exported frame (`frame_000001.png`, ...)
+pub const FRAME_PREFIX: &str = "frame_";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameExportOptions {
+    #[serde(default)]
+    pub format: ImageFormat,
+    /// Frames per second to sample; `None` keeps every source frame
+    #[serde(default)]
+    pub fps: Option<f64>,
+    /// Output width in pixels; height follows the aspect ratio unless also set
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// JPEG quality from 2 (best) to 31 (worst)
+    #[serde(default)]
+    pub jpeg_quality: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameExportResult {
+    pub input_file: String,
+    pub output_dir: String,
+    /// printf-style pattern the frames were written with, e.g. `frame_%06d.png`
+    pub pattern: String,
+    pub frame_count: usize,
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    pub command: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameSequenceCommand {
+    input: PathBuf,
+    output_dir: PathBuf,
+    start_time: f64,
+    duration: f64,
+    options: FrameExportOptions,
+}
+
+impl FrameSequenceCommand {
+    pub fn new(input: impl AsRef<Path>, output_dir: impl AsRef<Path>, start_time: f64, duration: f64, options: FrameExportOptions) -> Self {
+        Self {
+            input: input.as_ref().to_path_buf(),
+            output_dir: output_dir.as_ref().to_path_buf(),
+            start_time,
+            duration,
+            options,
+        }
+    }
+
+    pub fn pattern(&self) -> String {
+        format!("{}%06d.{}", FRAME_PREFIX, self.options.format.extension())
+    }
+
+    fn filter_graph(&self) -> FilterGraph {
+        let mut graph = FilterGraph::new();
+
+        if let Some(fps) = self.options.fps {
+            graph.push(Filter::new("fps").arg(fps));
+        }
+
+        let scale = match (self.options.width, self.options.height) {
+            (Some(w), Some(h)) => Some((w as i64, h as i64)),
+            (Some(w), None) => Some((w as i64, -2)),
+            (None, Some(h)) => Some((-2, h as i64)),
+            (None, None) => None,
+        };
+        if let Some((w, h)) = scale {
+            graph.push(Filter::new("scale").arg(w).arg(h));
+        }
+
+        graph
+    }
+
+    pub fn build_args(&self) -> Vec<String> {
+        // Input seeking is frame-accurate here since every frame is decoded anyway
+        let mut args: Vec<String> = vec![
+            "-ss".into(), self.start_time.to_string(),
+            "-i".into(), self.input.display().to_string(),
+            "-t".into(), self.duration.to_string(),
+            "-map".into(), "0:v:0".into(),
+        ];
+
+        args.extend(self.filter_graph().to_args());
+
+        if self.options.format == ImageFormat::Jpeg {
+            let quality = self.options.jpeg_quality.unwrap_or(2).clamp(2, 31);
+            args.extend(["-q:v".into(), quality.to_string()]);
+        }
+
+        args.extend([
+            "-start_number".into(), "1".into(),
+            "-y".into(),
+            self.output_dir.join(self.pattern()).display().to_string(),
+        ]);
+
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    /// The frames in the output directory this command's pattern names
+    fn frames(&self) -> Result<Vec<PathBuf>> {
+        let extension = self.options.format.extension();
+        let mut frames = Vec::new();
+
+        for entry in std::fs::read_dir(&self.output_dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with(FRAME_PREFIX) && name.ends_with(extension) {
+                frames.push(entry.path());
+            }
+        }
+
+        Ok(frames)
+    }
+
+    /// Counts the frames this command has written to its output directory
+    pub fn count_frames(&self) -> Result<usize> {
+        Ok(self.frames()?.len())
+    }
+
+    /// Removes frames left by an earlier export to the same directory, so
+    /// the count covers this export's frames only
+    pub fn clear_frames(&self) -> Result<()> {
+        for frame in self.frames()? {
+            std::fs::remove_file(frame)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<usize> {
+        std::fs::create_dir_all(&self.output_dir)?;
+        self.clear_frames()?;
+        crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Frame export failed")?;
+        self.count_frames()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    mod command_building_tests {
+        use super::*;
+
+        #[test]
+        fn test_default_png_export() {
+            let cmd = FrameSequenceCommand::new("input.mp4", "frames", 10.0, 5.0, FrameExportOptions::default());
+
+            let cmd_string = cmd.get_command_string();
+            assert!(cmd_string.starts_with("ffmpeg -ss 10 -i input.mp4 -t 5"));
+            assert!(cmd_string.contains("-map 0:v:0"));
+            assert!(cmd_string.contains("-start_number 1"));
+            assert!(cmd_string.ends_with("frame_%06d.png"));
+            assert!(!cmd_string.contains("-vf"));
+            assert!(!cmd_string.contains("-q:v"));
+        }
+
+        #[test]
+        fn test_jpeg_with_fps_and_scaling() {
+            let options = FrameExportOptions {
+                format: ImageFormat::Jpeg,
+                fps: Some(2.0),
+                width: Some(640),
+                jpeg_quality: Some(5),
+                ..Default::default()
+            };
+            let cmd = FrameSequenceCommand::new("input.mp4", "frames", 0.0, 5.0, options);
+
+            let cmd_string = cmd.get_command_string();
+            assert!(cmd_string.contains("-vf fps=2,scale=640:-2"));
+            assert!(cmd_string.contains("-q:v 5"));
+            assert!(cmd_string.ends_with("frame_%06d.jpg"));
+        }
+
+        #[test]
+        fn test_jpeg_quality_is_clamped() {
+            let options = FrameExportOptions {
+                format: ImageFormat::Jpeg,
+                jpeg_quality: Some(100),
+                ..Default::default()
+            };
+            let cmd = FrameSequenceCommand::new("input.mp4", "frames", 0.0, 5.0, options);
+            assert!(cmd.get_command_string().contains("-q:v 31"));
+        }
+
+        #[test]
+        fn test_explicit_width_and_height() {
+            let options = FrameExportOptions {
+                width: Some(320),
+                height: Some(240),
+                ..Default::default()
+            };
+            let cmd = FrameSequenceCommand::new("input.mp4", "frames", 0.0, 5.0, options);
+            assert!(cmd.get_command_string().contains("-vf scale=320:240"));
+        }
+    }
+
+    mod frame_count_tests {
+        use super::*;
+        use std::fs::File;
+
+        #[test]
+        fn test_count_frames_ignores_other_files() {
+            let temp_dir = tempdir().unwrap();
+            for name in ["frame_000001.png", "frame_000002.png", "frame_000003.jpg", "notes.txt"] {
+                File::create(temp_dir.path().join(name)).unwrap();
+            }
+
+            let cmd = FrameSequenceCommand::new("input.mp4", temp_dir.path(), 0.0, 1.0, FrameExportOptions::default());
+            assert_eq!(cmd.count_frames().unwrap(), 2);
+        }
+
+        #[test]
+        fn test_clear_frames_keeps_other_files() {
+            let temp_dir = tempdir().unwrap();
+            for name in ["frame_000001.png", "frame_000002.png", "frame_000001.jpg", "notes.txt"] {
+                File::create(temp_dir.path().join(name)).unwrap();
+            }
+
+            let cmd = FrameSequenceCommand::new("input.mp4", temp_dir.path(), 0.0, 1.0, FrameExportOptions::default());
+            cmd.clear_frames().unwrap();
+            assert_eq!(cmd.count_frames().unwrap(), 0);
+            assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+        }
+    }
+
+    mod serialization_tests {
+        use super::*;
+
+        #[test]
+        fn test_options_deserialize_with_defaults() {
+            let options: FrameExportOptions = serde_json::from_str(r#"{"format": "jpeg", "fps": 1}"#).unwrap();
+            assert_eq!(options.format, ImageFormat::Jpeg);
+            assert_eq!(options.fps, Some(1.0));
+            assert_eq!(options.width, None);
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use crate::ranges::{self, TimeRange};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Highlight suggestions
+/// Scores a recording by how much louder each stretch is than its typical
+/// level, using FFmpeg's `ebur128` momentary loudness, and suggests the
+/// stretches around loudness spikes (crowd cheering, laughter, commentators
+/// shouting) as candidate clips, best first, for the user to confirm.
+/// Each suggestion starts a little before the spike, since the play that
+/// caused the cheer comes before it. With `min_motion` set, static lulls
+/// found by the motion pass (replays paused on a menu, a still scoreboard)
+/// are cut out of the suggestions.
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessSample {
+    /// Seconds into the input
+    pub time: f64,
+    /// Momentary (400ms) loudness in LUFS
+    pub loudness: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighlightOptions {
+    /// Seconds of audio scored together
+    #[serde(default = "default_window")]
+    pub window: f64,
+    /// How far above the recording's typical loudness (in LU) a window must
+    /// be to count as a spike
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+    /// Seconds kept before a spike, for the play that caused it
+    #[serde(default = "default_pre_roll")]
+    pub pre_roll: f64,
+    /// Seconds kept after a spike
+    #[serde(default = "default_post_roll")]
+    pub post_roll: f64,
+    /// Most suggestions returned
+    #[serde(default = "default_max_suggestions")]
+    pub max_suggestions: usize,
+    /// Mean frame difference (YDIF, 0-255) under which a stretch of video is
+    /// a static lull and left out of suggestions; `None` skips the motion pass
+    #[serde(default)]
+    pub min_motion: Option<f64>,
+}
+
+fn default_window() -> f64 {
+    1.0
+}
+
+fn default_threshold() -> f64 {
+    6.0
+}
+
+fn default_pre_roll() -> f64 {
+    8.0
+}
+
+fn default_post_roll() -> f64 {
+    4.0
+}
+
+fn default_max_suggestions() -> usize {
+    10
+}
+
+impl Default for HighlightOptions {
+    fn default() -> Self {
+        Self {
+            window: default_window(),
+            threshold: default_threshold(),
+            pre_roll: default_pre_roll(),
+            post_roll: default_post_roll(),
+            max_suggestions: default_max_suggestions(),
+            min_motion: None,
+        }
+    }
+}
+
+impl HighlightOptions {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.window.is_finite() && self.window > 0.0) {
+            return Err(VideoClipError::InvalidOptions("highlight window must be a positive number of seconds".to_string()));
+        }
+        if !(self.threshold.is_finite() && self.threshold > 0.0) {
+            return Err(VideoClipError::InvalidOptions("highlight threshold must be a positive loudness difference".to_string()));
+        }
+        if !(self.pre_roll >= 0.0 && self.post_roll >= 0.0) {
+            return Err(VideoClipError::InvalidOptions("highlight pre/post roll can't be negative".to_string()));
+        }
+        if self.min_motion.is_some_and(|m| !(m.is_finite() && m > 0.0)) {
+            return Err(VideoClipError::InvalidOptions("minimum motion must be a positive frame difference".to_string()));
+        }
+        if self.max_suggestions == 0 {
+            return Err(VideoClipError::InvalidOptions("at least one highlight must be asked for".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// A suggested clip and why
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Highlight {
+    pub range: TimeRange,
+    /// When the spike was loudest
+    pub peak: f64,
+    /// LU above the typical loudness at the peak
+    pub score: f64,
+}
+
+/// Loudness below this is silence (ebur128 reports -120.7 for digital silence)
+/// and doesn't count toward the typical level
+const SILENCE_LUFS: f64 = -70.0;
+
+/// Parses `t: 1.2  TARGET:-23 LUFS  M: -24.1 S: ...` lines from FFmpeg's stderr
+pub fn parse_ebur128(stderr: &str) -> Vec<LoudnessSample> {
+    stderr
+        .lines()
+        .filter(|line| line.contains("t:") && line.contains("M:"))
+        .filter_map(|line| {
+            let value = |key: &str| -> Option<f64> {
+                let rest = &line[line.find(key)? + key.len()..];
+                rest.split_whitespace().next()?.parse().ok()
+            };
+            Some(LoudnessSample { time: value("t:")?, loudness: value("M:")? })
+        })
+        .collect()
+}
+
+/// Mean loudness of each `window` seconds, as `(window start, loudness)`
+pub fn window_levels(samples: &[LoudnessSample], window: f64) -> Vec<(f64, f64)> {
+    let mut levels: Vec<(f64, f64, usize)> = Vec::new();
+    for sample in samples {
+        let start = (sample.time / window).floor() * window;
+        match levels.last_mut() {
+            Some((last, sum, count)) if *last == start => {
+                *sum += sample.loudness;
+                *count += 1;
+            }
+            _ => levels.push((start, sample.loudness, 1)),
+        }
+    }
+    levels.into_iter().map(|(start, sum, count)| (start, sum / count as f64)).collect()
+}
+
+/// The recording's typical loudness: the median of its non-silent windows
+fn typical_level(levels: &[(f64, f64)]) -> Option<f64> {
+    let mut audible: Vec<f64> = levels.iter().map(|&(_, level)| level).filter(|&level| level > SILENCE_LUFS).collect();
+    if audible.is_empty() {
+        return None;
+    }
+    audible.sort_by(f64::total_cmp);
+    Some(audible[audible.len() / 2])
+}
+
+/// Highlights around the loudness spikes in `samples`, best first. Spikes
+/// a window apart are one event; suggestions that overlap once padded are
+/// merged, keeping the higher score. `media_end` clamps the padding.
+pub fn score_highlights(samples: &[LoudnessSample], media_end: Option<f64>, options: &HighlightOptions) -> Vec<Highlight> {
+    let levels = window_levels(samples, options.window);
+    let Some(typical) = typical_level(&levels) else {
+        return Vec::new();
+    };
+
+    let mut events: Vec<Highlight> = Vec::new();
+    for &(start, level) in &levels {
+        let score = level - typical;
+        if score < options.threshold {
+            continue;
+        }
+        let end = start + options.window;
+        match events.last_mut() {
+            Some(event) if start <= event.range.end + options.window => {
+                event.range.end = end;
+                if score > event.score {
+                    event.score = score;
+                    event.peak = start + options.window / 2.0;
+                }
+            }
+            _ => events.push(Highlight { range: TimeRange { start, end }, peak: start + options.window / 2.0, score }),
+        }
+    }
+
+    let mut highlights: Vec<Highlight> = Vec::new();
+    for event in events {
+        let range = event.range.pad(options.pre_roll, options.post_roll, media_end);
+        match highlights.last_mut() {
+            Some(last) if last.range.end > range.start => {
+                last.range.end = last.range.end.max(range.end);
+                if event.score > last.score {
+                    last.score = event.score;
+                    last.peak = event.peak;
+                }
+            }
+            _ => highlights.push(Highlight { range, ..event }),
+        }
+    }
+
+    highlights.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.range.start.total_cmp(&b.range.start)));
+    highlights.truncate(options.max_suggestions);
+    highlights
+}
+
+/// Ranges worth clipping from `samples`, best first
+pub fn suggest_highlights(samples: &[LoudnessSample], media_end: Option<f64>, options: &HighlightOptions) -> Vec<TimeRange> {
+    score_highlights(samples, media_end, options).into_iter().map(|h| h.range).collect()
+}
+
+/// `highlights` with `lulls` cut out. Each keeps the part holding its peak,
+/// or its longest part if the peak was in a lull; ones entirely inside a
+/// lull are dropped.
+pub fn exclude_lulls(highlights: Vec<Highlight>, lulls: &[TimeRange]) -> Vec<Highlight> {
+    highlights
+        .into_iter()
+        .filter_map(|highlight| {
+            let pieces = ranges::subtract(&[highlight.range], lulls);
+            let range = pieces
+                .iter()
+                .find(|piece| piece.contains(highlight.peak))
+                .or_else(|| pieces.iter().max_by(|a, b| a.duration().total_cmp(&b.duration())))?;
+            Some(Highlight { range: *range, ..highlight })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct LoudnessCommand {
+    input: PathBuf,
+    start_time: f64,
+    duration: Option<f64>,
+}
+
+impl LoudnessCommand {
+    /// Measures `input` from `start_time`, for `duration` seconds or to the end
+    pub fn new(input: impl AsRef<Path>, start_time: f64, duration: Option<f64>) -> Self {
+        Self { input: input.as_ref().to_path_buf(), start_time, duration }
+    }
+
+    pub fn filter_graph(&self) -> FilterGraph {
+        FilterGraph::from(Filter::new("ebur128").option("framelog", "info"))
+    }
+
+    /// Decodes the first audio stream through `ebur128`, discarding output
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-hide_banner".into(),
+            "-nostats".into(),
+            "-ss".into(), self.start_time.to_string(),
+            "-i".into(), self.input.display().to_string(),
+        ];
+        if let Some(duration) = self.duration {
+            args.extend(["-t".into(), duration.to_string()]);
+        }
+        args.extend(["-map".into(), "0:a:0".into()]);
+        args.extend(self.filter_graph().to_audio_args());
+        args.extend(["-vn", "-f", "null", "-"].iter().map(|s| s.to_string()));
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    /// Loudness samples in source time
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<Vec<LoudnessSample>> {
+        let output = crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Loudness analysis failed")?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        Ok(parse_ebur128(&stderr)
+            .into_iter()
+            .map(|s| LoudnessSample { time: s.time + self.start_time, ..s })
+            .collect())
+    }
+}
+
+/// Highlights of `range` of `input` (all of it when `None`), best first
+#[cfg(not(feature = "wasm"))]
+pub fn detect_highlights(input: impl AsRef<Path>, range: Option<TimeRange>, options: &HighlightOptions) -> Result<Vec<Highlight>> {
+    options.validate()?;
+    let input = input.as_ref();
+    // The whole source's loudness is an analysis stage, measured once and
+    // reused; a range is cut from it when it's there, or measured alone
+    let mut pipeline = crate::analysis::AnalysisPipeline::new(input);
+    let (start, end, samples) = match range {
+        Some(range) => {
+            let samples = match pipeline.cached::<Vec<LoudnessSample>>(crate::analysis::Stage::Loudness) {
+                Some(all) => all.into_iter().filter(|s| s.time >= range.start && s.time <= range.end).collect(),
+                None => LoudnessCommand::new(input, range.start, Some(range.duration())).execute()?,
+            };
+            (range.start, Some(range.end), samples)
+        }
+        None => (0.0, pipeline.probe()?.duration, pipeline.loudness()?),
+    };
+    let duration = range.map(|r| r.duration());
+    let highlights = score_highlights(&samples, end, options);
+    let Some(min_motion) = options.min_motion else {
+        return Ok(highlights);
+    };
+    let activity = crate::motion::MotionCommand::new(input, start, duration).execute()?;
+    let lulls = crate::motion::static_lulls(&activity, options.window, min_motion, crate::motion::DEFAULT_MIN_LULL_SECONDS);
+    Ok(exclude_lulls(highlights, &lulls))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ten samples a second at `quiet` LUFS, with `loud` over each of `spikes`
+    fn samples(seconds: usize, quiet: f64, loud: f64, spikes: &[(f64, f64)]) -> Vec<LoudnessSample> {
+        (0..seconds * 10)
+            .map(|i| {
+                let time = i as f64 / 10.0;
+                let spiking = spikes.iter().any(|&(start, end)| start <= time && time < end);
+                LoudnessSample { time, loudness: if spiking { loud } else { quiet } }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_ebur128_output() {
+        let stderr = "[Parsed_ebur128_0 @ 0x55d0] t: 0.4       TARGET:-23 LUFS    M: -24.1 S:-120.7     I: -24.1 LUFS       LRA:   0.0 LU\n\
+            [Parsed_ebur128_0 @ 0x55d0] t: 0.5       TARGET:-23 LUFS    M:-120.7 S:-120.7     I: -70.0 LUFS       LRA:   0.0 LU\n\
+            [Parsed_ebur128_0 @ 0x55d0] Summary:\n\
+            \x20 Integrated loudness:\n    I:         -24.1 LUFS\n";
+        assert_eq!(
+            parse_ebur128(stderr),
+            vec![LoudnessSample { time: 0.4, loudness: -24.1 }, LoudnessSample { time: 0.5, loudness: -120.7 }]
+        );
+    }
+
+    #[test]
+    fn test_spikes_are_ranked_and_padded() {
+        let mut recording = samples(120, -30.0, -18.0, &[(30.0, 33.0), (90.0, 91.0)]);
+        // The later cheer is louder
+        for sample in recording.iter_mut().filter(|s| (90.0..91.0).contains(&s.time)) {
+            sample.loudness = -12.0;
+        }
+        let highlights = score_highlights(&recording, Some(95.0), &HighlightOptions::default());
+        assert_eq!(highlights.len(), 2);
+        assert_eq!(highlights[0].range, TimeRange { start: 82.0, end: 95.0 });
+        assert_eq!((highlights[0].peak, highlights[0].score), (90.5, 18.0));
+        assert_eq!(highlights[1].range, TimeRange { start: 22.0, end: 37.0 });
+
+        let options = HighlightOptions { max_suggestions: 1, ..Default::default() };
+        assert_eq!(suggest_highlights(&recording, None, &options), vec![TimeRange { start: 82.0, end: 95.0 }]);
+    }
+
+    #[test]
+    fn test_close_spikes_merge_and_quiet_recordings_have_none() {
+        let recording = samples(60, -30.0, -20.0, &[(20.0, 21.0), (26.0, 27.0)]);
+        let highlights = score_highlights(&recording, None, &HighlightOptions::default());
+        assert_eq!(highlights.iter().map(|h| h.range).collect::<Vec<_>>(), vec![TimeRange { start: 12.0, end: 31.0 }]);
+
+        assert!(score_highlights(&samples(60, -30.0, -30.0, &[]), None, &HighlightOptions::default()).is_empty());
+        assert!(score_highlights(&samples(60, -120.7, -120.7, &[]), None, &HighlightOptions::default()).is_empty());
+        assert!(HighlightOptions { window: 0.0, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_static_lulls_are_cut_out() {
+        let highlight = |start, end, peak| Highlight { range: TimeRange { start, end }, peak, score: 10.0 };
+        let lulls = [TimeRange { start: 30.0, end: 40.0 }, TimeRange { start: 100.0, end: 200.0 }];
+        let kept = exclude_lulls(vec![highlight(22.0, 37.0, 25.0), highlight(35.0, 60.0, 38.0), highlight(120.0, 130.0, 125.0)], &lulls);
+        assert_eq!(kept, vec![highlight(22.0, 30.0, 25.0), highlight(40.0, 60.0, 38.0)]);
+        assert!(HighlightOptions { min_motion: Some(-1.0), ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_loudness_command() {
+        assert_eq!(
+            LoudnessCommand::new("match.mp4", 60.0, Some(30.0)).get_command_string(),
+            "ffmpeg -hide_banner -nostats -ss 60 -i match.mp4 -t 30 -map 0:a:0 -af ebur128=framelog=info -vn -f null -"
+        );
+    }
+}
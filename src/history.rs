@@ -0,0 +1,154 @@
+use crate::error::{VideoClipError, Result};
+use crate::video_clipper::ClipRequest;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Clip history
+/// The CLI remembers the last clips it made in
+/// `<data dir>/video-clip-rs/history.json`, newest first. Interactive mode
+/// offers the recent files, output directories and ranges as quick picks, and
+/// `redo` runs a remembered request again.
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    /// Full requests, so a redo keeps every option
+    #[serde(default)]
+    pub entries: Vec<ClipRequest>,
+}
+
+impl History {
+    /// Entries kept; older ones are dropped as new clips are recorded
+    pub const MAX_ENTRIES: usize = 20;
+
+    /// `<data dir>/video-clip-rs/history.json`
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("video-clip-rs").join("history.json"))
+    }
+
+    /// The history at `path`, or an empty one when there isn't a file yet
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| VideoClipError::InvalidOptions(format!("invalid history file {}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| VideoClipError::InvalidOptions(format!("couldn't serialize history: {}", e)))?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Puts `request` first; running the same request again moves it up
+    /// instead of repeating it
+    pub fn record(&mut self, request: &ClipRequest) {
+        self.entries.retain(|entry| entry != request);
+        self.entries.insert(0, request.clone());
+        self.entries.truncate(Self::MAX_ENTRIES);
+    }
+
+    pub fn last(&self) -> Option<&ClipRequest> {
+        self.entries.first()
+    }
+
+    /// The `n`th most recent request, from 1
+    pub fn nth(&self, n: usize) -> Option<&ClipRequest> {
+        self.entries.get(n.checked_sub(1)?)
+    }
+
+    /// Distinct input files, most recent first
+    pub fn recent_inputs(&self, limit: usize) -> Vec<&str> {
+        distinct(self.entries.iter().map(|entry| entry.input_file.as_str()), limit)
+    }
+
+    /// Distinct output directories that were chosen explicitly, most recent first
+    pub fn recent_output_dirs(&self, limit: usize) -> Vec<&str> {
+        distinct(self.entries.iter().filter_map(|entry| entry.output_dir.as_deref()), limit)
+    }
+
+    /// Distinct `(start, end)` times clipped from `input_file`, most recent first
+    pub fn recent_ranges(&self, input_file: &str, limit: usize) -> Vec<(&str, &str)> {
+        let ranges = self.entries.iter()
+            .filter(|entry| entry.input_file == input_file)
+            .map(|entry| (entry.start_time.as_str(), entry.end_time.as_str()));
+        distinct(ranges, limit)
+    }
+}
+
+fn distinct<T: PartialEq>(values: impl Iterator<Item = T>, limit: usize) -> Vec<T> {
+    let mut seen = Vec::new();
+    for value in values {
+        if seen.len() == limit {
+            break;
+        }
+        if !seen.contains(&value) {
+            seen.push(value);
+        }
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(input: &str, start: &str, end: &str, output_dir: Option<&str>) -> ClipRequest {
+        ClipRequest {
+            input_file: input.to_string(),
+            start_time: start.to_string(),
+            end_time: end.to_string(),
+            output_dir: output_dir.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_and_quick_picks() {
+        let mut history = History::default();
+        history.record(&request("talk.mp4", "0:10", "0:20", Some("shorts")));
+        history.record(&request("game.mkv", "1:00", "1:30", None));
+        history.record(&request("talk.mp4", "2:00", "2:30", Some("shorts")));
+        history.record(&request("game.mkv", "1:00", "1:30", None));
+
+        assert_eq!(history.entries.len(), 3);
+        assert_eq!(history.last().unwrap().input_file, "game.mkv");
+        assert_eq!(history.nth(3).unwrap().start_time, "0:10");
+        assert!(history.nth(0).is_none());
+        assert_eq!(history.recent_inputs(5), vec!["game.mkv", "talk.mp4"]);
+        assert_eq!(history.recent_output_dirs(5), vec!["shorts"]);
+        assert_eq!(history.recent_ranges("talk.mp4", 5), vec![("2:00", "2:30"), ("0:10", "0:20")]);
+        assert_eq!(history.recent_ranges("talk.mp4", 1), vec![("2:00", "2:30")]);
+    }
+
+    #[test]
+    fn test_oldest_entries_are_dropped() {
+        let mut history = History::default();
+        for i in 0..History::MAX_ENTRIES + 5 {
+            history.record(&request("talk.mp4", &i.to_string(), "999", None));
+        }
+        assert_eq!(history.entries.len(), History::MAX_ENTRIES);
+        assert_eq!(history.last().unwrap().start_time, (History::MAX_ENTRIES + 4).to_string());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("nested").join("history.json");
+        assert_eq!(History::load(&path).unwrap(), History::default());
+
+        let mut history = History::default();
+        history.record(&request("talk.mp4", "0:10", "0:20", Some("shorts")));
+        history.save(&path).unwrap();
+        assert_eq!(History::load(&path).unwrap(), history);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(History::load(&path).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::video_clipper::{ClipRequest, ClipResult};
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+
+/// Post-processing hooks
+/// Runs shell commands, Rust closures or webhooks after each clip succeeds or
+/// fails, e.g. to upload the output or send a notification. Command hooks receive
+/// the event as JSON on stdin plus a few `VIDEO_CLIP_*` environment variables.
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+#[allow(clippy::large_enum_variant)] // One per finished clip; not worth boxing the request
+pub enum HookEvent {
+    Success { result: ClipResult },
+    Failure { request: ClipRequest, error: String },
+}
+
+impl HookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::Success { .. } => "success",
+            HookEvent::Failure { .. } => "failure",
+        }
+    }
+
+    pub fn input_file(&self) -> &str {
+        match self {
+            HookEvent::Success { result } => &result.input_file,
+            HookEvent::Failure { request, .. } => &request.input_file,
+        }
+    }
+
+    /// Environment passed to command hooks
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("VIDEO_CLIP_EVENT", self.name().to_string()),
+            ("VIDEO_CLIP_INPUT", self.input_file().to_string()),
+        ];
+        match self {
+            HookEvent::Success { result } => vars.push(("VIDEO_CLIP_OUTPUT", result.output_file.clone())),
+            HookEvent::Failure { error, .. } => vars.push(("VIDEO_CLIP_ERROR", error.clone())),
+        }
+        vars
+    }
+}
+
+pub type HookFn = dyn Fn(&HookEvent) -> std::result::Result<(), String> + Send + Sync;
+
+#[derive(Clone)]
+pub enum Hook {
+    /// Shell command line (`sh -c` on Unix, `cmd /C` on Windows)
+    Command(String),
+    Callback(Arc<HookFn>),
+    #[cfg(feature = "webhooks")]
+    Webhook(crate::webhook::Webhook),
+}
+
+impl fmt::Debug for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hook::Command(command) => f.debug_tuple("Command").field(command).finish(),
+            Hook::Callback(_) => f.write_str("Callback(..)"),
+            #[cfg(feature = "webhooks")]
+            Hook::Webhook(webhook) => f.debug_tuple("Webhook").field(&webhook.url).finish(),
+        }
+    }
+}
+
+impl Hook {
+    pub fn callback(f: impl Fn(&HookEvent) -> std::result::Result<(), String> + Send + Sync + 'static) -> Self {
+        Hook::Callback(Arc::new(f))
+    }
+
+    fn run(&self, event: &HookEvent) -> std::result::Result<(), String> {
+        match self {
+            Hook::Callback(f) => f(event),
+            Hook::Command(command) => run_command(command, event),
+            #[cfg(feature = "webhooks")]
+            Hook::Webhook(webhook) => webhook.send(event),
+        }
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+fn run_command(command: &str, event: &HookEvent) -> std::result::Result<(), String> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    cmd.envs(event.env_vars()).stdin(Stdio::piped()).stdout(Stdio::null());
+
+    let mut child = cmd.spawn().map_err(|e| format!("could not start `{}`: {}", command, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let json = serde_json::to_string(event).map_err(|e| e.to_string())?;
+        // Hooks that ignore stdin may exit before reading it
+        let _ = stdin.write_all(json.as_bytes());
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("`{}` exited with {}", command, status))
+    }
+}
+
+#[cfg(feature = "wasm")]
+fn run_command(command: &str, _event: &HookEvent) -> std::result::Result<(), String> {
+    Err(format!("command hooks can't run in WASM: `{}`", command))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    on_success: Vec<Hook>,
+    on_failure: Vec<Hook>,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.on_success.is_empty() && self.on_failure.is_empty()
+    }
+
+    pub fn add_on_success(&mut self, hook: Hook) {
+        self.on_success.push(hook);
+    }
+
+    pub fn add_on_failure(&mut self, hook: Hook) {
+        self.on_failure.push(hook);
+    }
+
+    /// Runs every hook registered for `event`, in order. Hooks can't fail the
+    /// clip; their errors are returned for reporting.
+    pub fn fire(&self, event: &HookEvent) -> Vec<String> {
+        let hooks = match event {
+            HookEvent::Success { .. } => &self.on_success,
+            HookEvent::Failure { .. } => &self.on_failure,
+        };
+        hooks
+            .iter()
+            .filter_map(|hook| hook.run(event).err())
+            .map(|error| {
+                log::warn!("{} hook failed for {}: {}", event.name(), event.input_file(), error);
+                format!("{} hook failed: {}", event.name(), error)
+            })
+            .collect()
+    }
+
+    /// Fires the success or failure hooks for the clip of `request` that
+    /// ended in `outcome`, adding the success hooks' errors to its warnings
+    pub fn fire_outcome(&self, request: &ClipRequest, outcome: &mut crate::error::Result<ClipResult>) {
+        match outcome {
+            Ok(result) => {
+                let errors = self.fire(&HookEvent::Success { result: result.clone() });
+                result.warnings.extend(errors);
+            }
+            Err(e) => {
+                self.fire(&HookEvent::Failure { request: request.clone(), error: e.to_string() });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn success() -> HookEvent {
+        HookEvent::Success {
+            result: ClipResult {
+                input_file: "talk.mp4".to_string(),
+                output_file: "downloads/talk_clip_00-00_to_00-05.mp4".to_string(),
+                start_seconds: 0.0,
+                end_seconds: 5.0,
+                duration: 5.0,
+                file_size_mb: None,
+                command: String::new(),
+                encoder: "copy".to_string(),
+                warnings: Vec::new(),
+                black_trim: None,
+                cut_report: None,
+                renditions: Vec::new(),
+                recovery: None,
+                scrub: None,
+                quality: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_callbacks_run_for_matching_event_only() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = Hooks::new();
+        let log = seen.clone();
+        hooks.add_on_success(Hook::callback(move |event| {
+            log.lock().unwrap().push(event.input_file().to_string());
+            Ok(())
+        }));
+        hooks.add_on_failure(Hook::callback(|_| panic!("failure hook must not run")));
+
+        assert!(hooks.fire(&success()).is_empty());
+        assert_eq!(*seen.lock().unwrap(), vec!["talk.mp4"]);
+    }
+
+    #[test]
+    fn test_hook_errors_are_collected() {
+        let mut hooks = Hooks::new();
+        hooks.add_on_failure(Hook::callback(|_| Err("upload refused".to_string())));
+        let event = HookEvent::Failure { request: ClipRequest::default(), error: "boom".to_string() };
+        assert_eq!(hooks.fire(&event), vec!["failure hook failed: upload refused"]);
+    }
+
+    #[test]
+    fn test_event_serialization_and_env() {
+        let json = serde_json::to_value(success()).unwrap();
+        assert_eq!(json["event"], "success");
+        assert_eq!(json["result"]["input_file"], "talk.mp4");
+
+        let vars = success().env_vars();
+        assert!(vars.contains(&("VIDEO_CLIP_OUTPUT", "downloads/talk_clip_00-00_to_00-05.mp4".to_string())));
+    }
+
+    #[cfg(all(unix, not(feature = "wasm")))]
+    #[test]
+    fn test_command_hooks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let marker = dir.path().join("hook.json");
+        let mut hooks = Hooks::new();
+        hooks.add_on_success(Hook::Command(format!("cat > '{}' && test \"$VIDEO_CLIP_EVENT\" = success", marker.display())));
+        hooks.add_on_success(Hook::Command("exit 3".to_string()));
+
+        let errors = hooks.fire(&success());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("exit 3"));
+        assert!(std::fs::read_to_string(marker).unwrap().contains("\"event\":\"success\""));
+    }
+}
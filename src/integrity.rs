@@ -0,0 +1,194 @@
+//! Source integrity checks
+//! Interrupted downloads and recordings that stopped mid-write leave files
+//! FFmpeg either refuses or half-reads. `check_source` looks for the usual
+//! damage before anything is run; a request with `recover` set is clipped
+//! anyway with FFmpeg's error tolerance on, and its result says what was wrong
+//! and how much of the range came out.
+
+use crate::error::{VideoClipError, Result};
+use crate::sniff::{Container, FileKind};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SourceIssue {
+    /// An MP4/MOV without its `moov` index, as a recording that never
+    /// finished leaves it
+    MissingMoov,
+    /// A top-level box runs past the end of the file
+    Truncated { expected_bytes: u64, actual_bytes: u64 },
+    /// ffprobe couldn't make sense of the file
+    Unreadable { details: String },
+}
+
+impl SourceIssue {
+    pub fn describe(&self) -> String {
+        match self {
+            SourceIssue::MissingMoov => "the MP4 index (moov atom) is missing".to_string(),
+            SourceIssue::Truncated { expected_bytes, actual_bytes } => format!(
+                "the file is truncated ({} of {} bytes)",
+                actual_bytes, expected_bytes
+            ),
+            SourceIssue::Unreadable { details } => format!("ffprobe can't read it: {}", details),
+        }
+    }
+}
+
+/// What a recovery-mode clip salvaged
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RecoveryReport {
+    /// What the pre-check found wrong with the source
+    pub issues: Vec<SourceIssue>,
+    pub requested_seconds: f64,
+    /// Length of the clip that was written, when it could be probed
+    pub recovered_seconds: Option<f64>,
+}
+
+/// Recovered clips this much shorter than requested are reported as such
+const SHORTFALL_TOLERANCE: f64 = 0.5;
+
+impl RecoveryReport {
+    /// Seconds of the range that didn't make it into the clip, when known and
+    /// more than rounding
+    pub fn shortfall(&self) -> Option<f64> {
+        let missing = self.requested_seconds - self.recovered_seconds?;
+        (missing > SHORTFALL_TOLERANCE).then_some(missing)
+    }
+
+    /// One line for the result's warnings
+    pub fn summary(&self) -> String {
+        let issues: Vec<String> = self.issues.iter().map(SourceIssue::describe).collect();
+        let outcome = match (self.recovered_seconds, self.shortfall()) {
+            (Some(recovered), Some(missing)) => format!(
+                "recovered {:.1}s of {:.1}s ({:.1}s lost)",
+                recovered, self.requested_seconds, missing
+            ),
+            (Some(recovered), None) => format!("recovered {:.1}s, the whole range", recovered),
+            (None, _) => "couldn't measure how much was recovered".to_string(),
+        };
+        format!("Damaged source ({}); {}", issues.join("; "), outcome)
+    }
+}
+
+/// Problems found in `path`; an empty list means nothing looked wrong (or
+/// couldn't be checked, e.g. without ffprobe)
+pub fn check_source(path: impl AsRef<Path>) -> Result<Vec<SourceIssue>> {
+    let path = path.as_ref();
+    let mut issues = Vec::new();
+    if FileKind::of_path(path)? == FileKind::Video(Container::Mp4) {
+        issues.extend(scan_boxes(&mut File::open(path)?)?);
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    match crate::probe::probe(path) {
+        Ok(_) | Err(VideoClipError::FFmpegNotFound) => {}
+        Err(VideoClipError::ProbeError(details)) => issues.push(SourceIssue::Unreadable { details }),
+        Err(e) => return Err(e),
+    }
+
+    Ok(issues)
+}
+
+/// `CorruptSource` listing `issues`, if there are any
+pub fn require_intact(path: &Path, issues: &[SourceIssue]) -> Result<()> {
+    if issues.is_empty() {
+        return Ok(());
+    }
+    let reason: Vec<String> = issues.iter().map(SourceIssue::describe).collect();
+    Err(VideoClipError::CorruptSource {
+        path: path.display().to_string(),
+        reason: reason.join("; "),
+    })
+}
+
+/// Walks an ISO media file's top-level boxes by their headers alone
+fn scan_boxes(file: &mut (impl Read + Seek)) -> Result<Vec<SourceIssue>> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut offset = 0;
+    let mut has_moov = false;
+    while offset + 8 <= len {
+        let mut header = [0u8; 16];
+        file.seek(SeekFrom::Start(offset))?;
+        let read = file.read(&mut header)?;
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        has_moov |= &header[4..8] == b"moov";
+        let size = match size {
+            // Runs to the end of the file
+            0 => break,
+            // 64-bit size after the type
+            1 if read >= 16 => u64::from_be_bytes(header[8..16].try_into().unwrap_or_default()),
+            size if size < 8 => break,
+            size => size,
+        };
+        if offset + size > len {
+            return Ok(with_moov(has_moov, vec![SourceIssue::Truncated { expected_bytes: offset + size, actual_bytes: len }]));
+        }
+        offset += size;
+    }
+    Ok(with_moov(has_moov, Vec::new()))
+}
+
+fn with_moov(has_moov: bool, mut issues: Vec<SourceIssue>) -> Vec<SourceIssue> {
+    if !has_moov {
+        issues.insert(0, SourceIssue::MissingMoov);
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn iso_box(kind: &[u8; 4], payload_len: usize) -> Vec<u8> {
+        let mut bytes = ((payload_len + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(kind);
+        bytes.resize(payload_len + 8, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_scan_boxes() {
+        let intact = [iso_box(b"ftyp", 8), iso_box(b"moov", 100), iso_box(b"mdat", 1000)].concat();
+        assert!(scan_boxes(&mut Cursor::new(&intact)).unwrap().is_empty());
+
+        // Cut off halfway through the media data
+        let truncated = &intact[..600];
+        assert_eq!(scan_boxes(&mut Cursor::new(truncated)).unwrap(), vec![
+            SourceIssue::Truncated { expected_bytes: 1132, actual_bytes: 600 },
+        ]);
+
+        // Recording stopped before the index was written at the end
+        let unfinished = [iso_box(b"ftyp", 8), iso_box(b"mdat", 1000)].concat();
+        assert_eq!(scan_boxes(&mut Cursor::new(&unfinished[..900])).unwrap(), vec![
+            SourceIssue::MissingMoov,
+            SourceIssue::Truncated { expected_bytes: 1024, actual_bytes: 900 },
+        ]);
+    }
+
+    #[test]
+    fn test_recovery_summary() {
+        let report = RecoveryReport {
+            issues: vec![SourceIssue::MissingMoov],
+            requested_seconds: 30.0,
+            recovered_seconds: Some(12.5),
+        };
+        assert_eq!(report.shortfall(), Some(17.5));
+        assert_eq!(
+            report.summary(),
+            "Damaged source (the MP4 index (moov atom) is missing); recovered 12.5s of 30.0s (17.5s lost)"
+        );
+        assert_eq!(RecoveryReport { recovered_seconds: Some(29.8), ..report }.shortfall(), None);
+    }
+
+    #[test]
+    fn test_require_intact() {
+        assert!(require_intact(Path::new("talk.mp4"), &[]).is_ok());
+        let err = require_intact(Path::new("talk.mp4"), &[SourceIssue::MissingMoov]).unwrap_err();
+        assert_eq!(err.code(), "corrupt_source");
+    }
+}
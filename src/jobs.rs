@@ -0,0 +1,483 @@
+//! Clip job queue
+//! Accepts clip requests and runs them on a fixed pool of worker threads, so
+//! the server can answer immediately and let clients poll for the result.
+//! The pool size caps concurrency globally; an optional per-source cap keeps
+//! one long recording from occupying every worker. Jobs start in priority
+//! order, FIFO within a level, and can be cancelled while queued or running.
+//! Finished jobs stay around to be polled until `keep_finished` newer ones
+//! have finished.
+
+use crate::batch::validate_requests;
+use crate::cancel::CancelHandle;
+use crate::error::{VideoClipError, Result};
+use crate::hooks::Hooks;
+use crate::metrics::Metrics;
+use crate::video_clipper::{ClipRequest, ClipResult, VideoClipper};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Job {
+    pub id: u64,
+    pub status: JobStatus,
+    pub request: ClipRequest,
+    #[serde(default)]
+    pub result: Option<ClipResult>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// The caller that submitted the job, when the server knows it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueLimits {
+    /// Worker threads, i.e. jobs running at once across all sources
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    /// Jobs running at once on the same input file; unlimited when unset
+    #[serde(default)]
+    pub max_per_source: Option<usize>,
+    /// Finished jobs remembered for polling; older ones are forgotten
+    #[serde(default = "default_keep_finished")]
+    pub keep_finished: usize,
+}
+
+fn default_max_concurrent() -> usize {
+    2
+}
+
+fn default_keep_finished() -> usize {
+    1000
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent: default_max_concurrent(),
+            max_per_source: None,
+            keep_finished: default_keep_finished(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    next_id: u64,
+    jobs: BTreeMap<u64, Job>,
+    pending: VecDeque<u64>,
+    running: usize,
+    running_by_source: HashMap<String, usize>,
+    /// Handles of the running jobs
+    cancels: HashMap<u64, CancelHandle>,
+    /// Finished jobs, oldest first
+    finished: VecDeque<u64>,
+    shutdown: bool,
+}
+
+impl QueueState {
+    /// Notes that job `id` has finished, forgetting the oldest finished
+    /// jobs beyond `keep`
+    fn retire(&mut self, id: u64, keep: usize) {
+        self.finished.push_back(id);
+        while self.finished.len() > keep {
+            if let Some(oldest) = self.finished.pop_front() {
+                self.jobs.remove(&oldest);
+            }
+        }
+    }
+
+    /// First pending job of the highest waiting priority whose source is below
+    /// the per-source cap. Lower priorities never jump ahead, even when every
+    /// top-priority job is held back by the cap.
+    fn next_eligible(&self, max_per_source: Option<usize>) -> Option<usize> {
+        let top = self.pending.iter().map(|id| self.jobs[id].request.priority).max()?;
+        self.pending.iter().position(|id| {
+            let request = &self.jobs[id].request;
+            request.priority == top
+                && max_per_source.is_none_or(|cap| {
+                    self.running_by_source.get(&request.input_file).copied().unwrap_or(0) < cap
+                })
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct JobQueue {
+    clipper: VideoClipper,
+    /// The clipper's hooks, fired by the workers so the FFmpeg timings
+    /// leave them out
+    hooks: Hooks,
+    limits: QueueLimits,
+    metrics: Metrics,
+    state: Mutex<QueueState>,
+    /// Signalled when a job is queued or finishes (freeing its source), or the queue shuts down
+    work_ready: Condvar,
+    /// Signalled when a job finishes
+    job_done: Condvar,
+}
+
+impl JobQueue {
+    /// Creates the queue and spawns `workers` threads (at least one) to drain it
+    pub fn start(clipper: VideoClipper, workers: usize) -> Arc<Self> {
+        Self::with_limits(clipper, QueueLimits { max_concurrent: workers, ..Default::default() })
+    }
+
+    pub fn with_limits(clipper: VideoClipper, limits: QueueLimits) -> Arc<Self> {
+        let workers = limits.max_concurrent.max(1);
+        let queue = Arc::new(Self::idle(clipper, limits));
+        for _ in 0..workers {
+            let queue = Arc::clone(&queue);
+            std::thread::spawn(move || queue.work());
+        }
+        queue
+    }
+
+    /// The queue without any workers
+    fn idle(mut clipper: VideoClipper, limits: QueueLimits) -> Self {
+        let hooks = std::mem::take(clipper.hooks_mut());
+        Self {
+            clipper,
+            hooks,
+            limits,
+            metrics: Metrics::new(),
+            state: Mutex::new(QueueState { next_id: 1, ..Default::default() }),
+            work_ready: Condvar::new(),
+            job_done: Condvar::new(),
+        }
+    }
+
+    /// Validates `request` and queues it, returning the job id
+    pub fn submit(&self, request: ClipRequest) -> Result<u64> {
+        self.submit_for(None, request)
+    }
+
+    /// [`submit`](Self::submit), recording `owner` on the job
+    pub fn submit_for(&self, owner: Option<String>, request: ClipRequest) -> Result<u64> {
+        validate_requests(std::slice::from_ref(&request))?;
+
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.jobs.insert(id, Job { id, status: JobStatus::Queued, request, result: None, error: None, owner });
+        state.pending.push_back(id);
+        self.metrics.job_submitted();
+        self.work_ready.notify_one();
+        Ok(id)
+    }
+
+    pub fn job(&self, id: u64) -> Option<Job> {
+        self.state.lock().unwrap().jobs.get(&id).cloned()
+    }
+
+    pub fn jobs(&self) -> Vec<Job> {
+        self.state.lock().unwrap().jobs.values().cloned().collect()
+    }
+
+    /// Blocks until job `id` has finished
+    pub fn wait(&self, id: u64) -> Option<Job> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            match state.jobs.get(&id) {
+                None => return None,
+                Some(job) if job.status.is_finished() => return Some(job.clone()),
+                Some(_) => state = self.job_done.wait(state).unwrap(),
+            }
+        }
+    }
+
+    /// Cancels job `id`. A queued job leaves the queue at once; a running
+    /// one has its FFmpeg process killed and its partial output removed,
+    /// and is `cancelled` when its worker has cleaned up. Finished jobs are
+    /// left as they are. Returns the job as it stands, `None` if there's no
+    /// such job.
+    pub fn cancel(&self, id: u64) -> Option<Job> {
+        let mut state = self.state.lock().unwrap();
+        let status = state.jobs.get(&id)?.status;
+        match status {
+            JobStatus::Queued => {
+                state.pending.retain(|&pending| pending != id);
+                let job = state.jobs.get_mut(&id).expect("the job was just found");
+                job.status = JobStatus::Cancelled;
+                let job = job.clone();
+                state.retire(id, self.limits.keep_finished);
+                self.job_done.notify_all();
+                Some(job)
+            }
+            JobStatus::Running => {
+                if let Some(handle) = state.cancels.get(&id) {
+                    handle.cancel();
+                }
+                state.jobs.get(&id).cloned()
+            }
+            _ => state.jobs.get(&id).cloned(),
+        }
+    }
+
+    /// Jobs waiting for a worker
+    pub fn depth(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    pub fn running(&self) -> usize {
+        self.state.lock().unwrap().running
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Prometheus text for the queue's metrics and current gauges
+    pub fn render_metrics(&self) -> String {
+        let (depth, running) = {
+            let state = self.state.lock().unwrap();
+            (state.pending.len(), state.running)
+        };
+        self.metrics.render(depth, running)
+    }
+
+    /// Stops workers once their current job is done; queued jobs stay queued
+    pub fn shutdown(&self) {
+        self.state.lock().unwrap().shutdown = true;
+        self.work_ready.notify_all();
+    }
+
+    fn next_job(&self) -> Option<(u64, ClipRequest, CancelHandle)> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.shutdown {
+                return None;
+            }
+            if let Some(index) = state.next_eligible(self.limits.max_per_source) {
+                let id = state.pending.remove(index).expect("index is in range");
+                state.running += 1;
+                let job = state.jobs.get_mut(&id).expect("pending jobs are tracked");
+                job.status = JobStatus::Running;
+                let request = job.request.clone();
+                *state.running_by_source.entry(request.input_file.clone()).or_default() += 1;
+                let cancel = CancelHandle::new();
+                state.cancels.insert(id, cancel.clone());
+                return Some((id, request, cancel));
+            }
+            state = self.work_ready.wait(state).unwrap();
+        }
+    }
+
+    fn work(&self) {
+        while let Some((id, request, cancel)) = self.next_job() {
+            self.metrics.job_started();
+            let started = Instant::now();
+            // A panicking clip fails its job rather than leaving it running
+            // and taking the worker down with it
+            let mut outcome = cancel.scope(|| panic::catch_unwind(AssertUnwindSafe(|| self.clipper.clip_video(&request))));
+            let elapsed = started.elapsed().as_secs_f64();
+
+            let bytes_written = match &outcome {
+                Ok(Ok(result)) => Some(std::fs::metadata(&result.output_file).map_or(0, |m| m.len())),
+                _ => None,
+            };
+            self.metrics.job_finished(elapsed, bytes_written);
+            if let Ok(outcome) = &mut outcome {
+                self.hooks.fire_outcome(&request, outcome);
+            }
+            self.finish(id, &request, outcome);
+        }
+    }
+
+    /// Records how running job `id` ended and frees its worker and source
+    fn finish(&self, id: u64, request: &ClipRequest, outcome: std::thread::Result<Result<ClipResult>>) {
+        let mut state = self.state.lock().unwrap();
+        state.running -= 1;
+        state.cancels.remove(&id);
+        if let Some(count) = state.running_by_source.get_mut(&request.input_file) {
+            *count -= 1;
+            if *count == 0 {
+                state.running_by_source.remove(&request.input_file);
+            }
+        }
+        let job = state.jobs.get_mut(&id).expect("running jobs are tracked");
+        match outcome {
+            Ok(Ok(result)) => {
+                job.status = JobStatus::Succeeded;
+                job.result = Some(result);
+            }
+            Ok(Err(VideoClipError::Cancelled)) => {
+                log::info!("Job {} ({}) cancelled", id, request.input_file);
+                job.status = JobStatus::Cancelled;
+            }
+            Ok(Err(e)) => {
+                log::warn!("Job {} ({}) failed: {}", id, request.input_file, e);
+                job.status = JobStatus::Failed;
+                job.error = Some(e.to_string());
+            }
+            Err(payload) => {
+                let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown cause".to_string());
+                log::error!("Job {} ({}) panicked: {}", id, request.input_file, message);
+                job.status = JobStatus::Failed;
+                job.error = Some(format!("Internal error: the clip panicked ({})", message));
+            }
+        }
+        state.retire(id, self.limits.keep_finished);
+        self.job_done.notify_all();
+        // A job held back by the per-source cap may be runnable now
+        self.work_ready.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video_clipper::Priority;
+
+    fn request(input: &str, start: &str, end: &str) -> ClipRequest {
+        ClipRequest {
+            input_file: input.to_string(),
+            start_time: start.to_string(),
+            end_time: end.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_invalid_requests_are_rejected_up_front() {
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        assert!(matches!(
+            queue.submit(request("a.mp4", "0:30", "0:10")),
+            Err(VideoClipError::InvalidTimeRange { .. })
+        ));
+        assert!(queue.jobs().is_empty());
+        queue.shutdown();
+    }
+
+    #[test]
+    fn test_failed_job_is_recorded() {
+        let queue = JobQueue::start(VideoClipper::new(), 2);
+        let id = queue.submit(request("missing.mp4", "0", "5")).unwrap();
+
+        let job = queue.wait(id).unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(job.error.unwrap().contains("missing.mp4"));
+        assert_eq!(queue.metrics().jobs_failed(), 1);
+        assert_eq!((queue.depth(), queue.running()), (0, 0));
+        assert!(queue.wait(id + 1).is_none());
+        queue.shutdown();
+    }
+
+    #[test]
+    fn test_old_finished_jobs_are_forgotten() {
+        let queue = JobQueue::with_limits(VideoClipper::new(), QueueLimits { max_concurrent: 1, keep_finished: 1, ..Default::default() });
+        let first = queue.submit(request("missing.mp4", "0", "5")).unwrap();
+        assert!(queue.wait(first).is_some());
+        let second = queue.submit(request("missing.mp4", "0", "5")).unwrap();
+        assert!(queue.wait(second).is_some());
+        assert!(queue.job(first).is_none());
+        assert_eq!(queue.jobs().len(), 1);
+        queue.shutdown();
+    }
+
+    #[test]
+    fn test_workers_fire_the_clippers_hooks() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let mut clipper = VideoClipper::new();
+        let log = Arc::clone(&fired);
+        clipper.hooks_mut().add_on_failure(crate::hooks::Hook::callback(move |event| {
+            log.lock().unwrap().push(event.input_file().to_string());
+            Ok(())
+        }));
+        let queue = JobQueue::start(clipper, 1);
+        let id = queue.submit(request("missing.mp4", "0", "5")).unwrap();
+        queue.wait(id);
+        assert_eq!(*fired.lock().unwrap(), vec!["missing.mp4"]);
+        queue.shutdown();
+    }
+
+    #[test]
+    fn test_panicked_job_fails_and_frees_its_worker() {
+        let queue = JobQueue::idle(VideoClipper::new(), QueueLimits::default());
+        let id = queue.submit(request("a.mp4", "0", "5")).unwrap();
+        let (running_id, request, _) = queue.next_job().unwrap();
+        assert_eq!((running_id, queue.running()), (id, 1));
+
+        queue.finish(id, &request, Err(Box::new("index out of bounds")));
+        let job = queue.wait(id).unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(job.error.unwrap().contains("index out of bounds"));
+        assert_eq!(queue.running(), 0);
+        assert!(queue.state.lock().unwrap().running_by_source.is_empty());
+    }
+
+    #[test]
+    fn test_cancel_queued_job() {
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        queue.shutdown();
+        let id = queue.submit(request("a.mp4", "0", "5")).unwrap();
+
+        let job = queue.cancel(id).unwrap();
+        assert_eq!(job.status, JobStatus::Cancelled);
+        assert_eq!(queue.depth(), 0);
+        assert_eq!(queue.wait(id).unwrap().status, JobStatus::Cancelled);
+        assert!(queue.cancel(id + 1).is_none());
+    }
+
+    #[test]
+    fn test_per_source_cap_skips_busy_sources() {
+        let mut state = QueueState::default();
+        for (id, input) in [(1, "a.mp4"), (2, "a.mp4"), (3, "b.mp4")] {
+            state.jobs.insert(id, Job { id, status: JobStatus::Queued, request: request(input, "0", "5"), result: None, error: None, owner: None });
+            state.pending.push_back(id);
+        }
+        state.running_by_source.insert("a.mp4".to_string(), 1);
+
+        assert_eq!(state.next_eligible(None), Some(0));
+        assert_eq!(state.next_eligible(Some(2)), Some(0));
+        assert_eq!(state.next_eligible(Some(1)), Some(2));
+        state.running_by_source.insert("b.mp4".to_string(), 1);
+        assert_eq!(state.next_eligible(Some(1)), None);
+    }
+
+    #[test]
+    fn test_high_priority_jobs_start_first() {
+        let mut state = QueueState::default();
+        for (id, input, priority) in [(1, "a.mp4", Priority::Low), (2, "b.mp4", Priority::High), (3, "c.mp4", Priority::High)] {
+            let request = ClipRequest { priority, ..request(input, "0", "5") };
+            state.jobs.insert(id, Job { id, status: JobStatus::Queued, request, result: None, error: None, owner: None });
+            state.pending.push_back(id);
+        }
+        assert_eq!(state.next_eligible(None), Some(1));
+
+        // A blocked high-priority job still holds back the low-priority one
+        state.pending.remove(1);
+        state.running_by_source.insert("c.mp4".to_string(), 1);
+        assert_eq!(state.next_eligible(Some(1)), None);
+    }
+
+    #[test]
+    fn test_job_serialization() {
+        let job = Job { id: 7, status: JobStatus::Queued, request: request("a.mp4", "0", "5"), result: None, error: None, owner: None };
+        let json = serde_json::to_value(&job).unwrap();
+        assert_eq!(json["status"], "queued");
+        assert_eq!(json["request"]["input_file"], "a.mp4");
+    }
+}
@@ -0,0 +1,249 @@
+//! Bitrate ladders
+//! Renders one clip at several heights and bit rates for adaptive streaming,
+//! as renditions of a single FFmpeg run, and writes a manifest of the rungs
+//! (size, bit rate, file) for a packager or player to pick from. Every rung
+//! is an H.264 MP4 named after its height, e.g. `<clip>_720p.mp4`. Rungs
+//! taller than the source are dropped rather than upscaled.
+
+use crate::encoder::VideoCodec;
+use crate::error::{VideoClipError, Result};
+use crate::renditions::{Rendition, RenditionResult};
+#[cfg(not(feature = "wasm"))]
+use crate::video_clipper::{ClipRequest, VideoClipper};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct LadderRung {
+    pub height: u32,
+    /// Video bit rate in kb/s
+    pub bitrate_kbps: u32,
+}
+
+impl LadderRung {
+    pub fn new(height: u32, bitrate_kbps: u32) -> Self {
+        Self { height, bitrate_kbps }
+    }
+
+    /// `720p`, naming the rung's rendition and file
+    pub fn name(&self) -> String {
+        format!("{}p", self.height)
+    }
+
+    pub fn rendition(&self) -> Rendition {
+        Rendition {
+            video_codec: VideoCodec::H264,
+            height: Some(self.height),
+            bitrate_kbps: Some(self.bitrate_kbps),
+            ..Rendition::new(self.name())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Ladder {
+    pub rungs: Vec<LadderRung>,
+}
+
+/// A common 16:9 H.264 ladder
+impl Default for Ladder {
+    fn default() -> Self {
+        Self {
+            rungs: vec![
+                LadderRung::new(1080, 5000),
+                LadderRung::new(720, 2800),
+                LadderRung::new(480, 1400),
+                LadderRung::new(360, 800),
+            ],
+        }
+    }
+}
+
+/// Comma-separated `HEIGHTp:RATEk` rungs, e.g. `1080p:5000k,720p:2800k`
+impl FromStr for Ladder {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let rungs = s
+            .split(',')
+            .map(str::trim)
+            .map(|rung| {
+                let invalid = || VideoClipError::InvalidOptions(format!("invalid ladder rung '{}' (expected HEIGHTp:RATEk, e.g. 720p:2800k)", rung));
+                let (height, rate) = rung.split_once(':').ok_or_else(invalid)?;
+                Ok(LadderRung {
+                    height: height.strip_suffix('p').and_then(|h| h.parse().ok()).ok_or_else(invalid)?,
+                    bitrate_kbps: rate.strip_suffix('k').and_then(|k| k.parse().ok()).ok_or_else(invalid)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let ladder = Ladder { rungs };
+        ladder.validate()?;
+        Ok(ladder)
+    }
+}
+
+impl Ladder {
+    pub fn validate(&self) -> Result<()> {
+        if self.rungs.is_empty() {
+            return Err(VideoClipError::InvalidOptions("a ladder needs at least one rung".to_string()));
+        }
+        for (i, rung) in self.rungs.iter().enumerate() {
+            // 4:2:0 video can't have an odd height
+            if rung.height == 0 || rung.height % 2 != 0 {
+                return Err(VideoClipError::InvalidOptions(format!("ladder height {} must be even and positive", rung.height)));
+            }
+            if rung.bitrate_kbps == 0 {
+                return Err(VideoClipError::InvalidOptions(format!("the {} rung's bit rate must be positive", rung.name())));
+            }
+            if self.rungs[..i].iter().any(|r| r.height == rung.height) {
+                return Err(VideoClipError::InvalidOptions(format!("duplicate ladder rung {}", rung.name())));
+            }
+        }
+        Ok(())
+    }
+
+    /// The rungs no taller than `source_height`, tallest first; a source
+    /// shorter than every rung still gets the smallest
+    pub fn rungs_for(&self, source_height: Option<u32>) -> Vec<LadderRung> {
+        let mut rungs = self.rungs.clone();
+        rungs.sort_by_key(|rung| std::cmp::Reverse(rung.height));
+        let Some(source_height) = source_height else {
+            return rungs;
+        };
+        let fitting: Vec<LadderRung> = rungs.iter().copied().filter(|r| r.height <= source_height).collect();
+        match fitting.is_empty() {
+            true => rungs.last().copied().into_iter().collect(),
+            false => fitting,
+        }
+    }
+}
+
+/// One rendered rung
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LadderOutput {
+    pub name: String,
+    pub height: u32,
+    pub bitrate_kbps: u32,
+    pub output_file: String,
+    pub file_size_mb: Option<f64>,
+    pub encoder: String,
+}
+
+/// What a ladder run produced, as written next to its renditions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LadderManifest {
+    pub input_file: String,
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    /// Tallest first
+    pub rungs: Vec<LadderOutput>,
+}
+
+impl LadderManifest {
+    pub fn new(input_file: impl Into<String>, start_seconds: f64, end_seconds: f64, rungs: &[LadderRung], results: &[RenditionResult]) -> Self {
+        Self {
+            input_file: input_file.into(),
+            start_seconds,
+            end_seconds,
+            rungs: rungs
+                .iter()
+                .zip(results)
+                .map(|(rung, result)| LadderOutput {
+                    name: result.name.clone(),
+                    height: rung.height,
+                    bitrate_kbps: rung.bitrate_kbps,
+                    output_file: result.output_file.clone(),
+                    file_size_mb: result.file_size_mb,
+                    encoder: result.encoder.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// `<clip>_ladder.json`, next to the rung files
+    pub fn path(&self) -> Option<PathBuf> {
+        let first = self.rungs.first()?;
+        let file = Path::new(&first.output_file);
+        let stem = file.file_stem()?.to_str()?;
+        let clip = stem.strip_suffix(&format!("_{}", first.name)).unwrap_or(stem);
+        Some(file.with_file_name(format!("{}_ladder.json", clip)))
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+impl VideoClipper {
+    /// Clips `request` once per rung of `ladder` in a single FFmpeg run and
+    /// writes the manifest, returning it and where it was written
+    pub fn clip_ladder(&self, request: &ClipRequest, ladder: &Ladder) -> Result<(LadderManifest, PathBuf)> {
+        ladder.validate()?;
+        if request.has_renditions() {
+            return Err(VideoClipError::InvalidOptions("a ladder makes its own renditions; drop the request's renditions and proxy".to_string()));
+        }
+        let input = crate::paths::InputPath::normalize(&request.input_file);
+        let source_height = crate::probe::probe(&input).ok()
+            .and_then(|info| info.video_stream().and_then(|video| video.height));
+        let rungs = ladder.rungs_for(source_height);
+
+        let ladder_request = ClipRequest {
+            renditions: rungs.iter().map(LadderRung::rendition).collect(),
+            ..request.clone()
+        };
+        let result = self.clip_video(&ladder_request)?;
+        let manifest = LadderManifest::new(&result.input_file, result.start_seconds, result.end_seconds, &rungs, &result.renditions);
+        let path = manifest.path()
+            .ok_or_else(|| VideoClipError::FFmpegError("the ladder rendered no renditions".to_string()))?;
+        let json = serde_json::to_string_pretty(&manifest)
+            .map_err(|e| VideoClipError::InvalidOptions(format!("couldn't serialize the ladder manifest: {}", e)))?;
+        std::fs::write(&path, json)?;
+        Ok((manifest, path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ladder() {
+        let ladder: Ladder = "720p:2800k, 1080p:5000k".parse().unwrap();
+        assert_eq!(ladder.rungs, vec![LadderRung::new(720, 2800), LadderRung::new(1080, 5000)]);
+        assert!("720p".parse::<Ladder>().is_err());
+        assert!("721p:2800k".parse::<Ladder>().is_err());
+        assert!("720p:2800k,720p:2000k".parse::<Ladder>().is_err());
+        assert!(Ladder::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_rungs_skip_upscaling() {
+        let ladder = Ladder::default();
+        let heights = |source| ladder.rungs_for(source).iter().map(|r| r.height).collect::<Vec<_>>();
+        assert_eq!(heights(Some(720)), vec![720, 480, 360]);
+        assert_eq!(heights(Some(240)), vec![360]);
+        assert_eq!(heights(None), vec![1080, 720, 480, 360]);
+
+        let rendition = LadderRung::new(720, 2800).rendition();
+        assert_eq!(rendition, "720p:720p,2800k,h264".parse().unwrap());
+    }
+
+    #[test]
+    fn test_manifest() {
+        let rungs = [LadderRung::new(720, 2800), LadderRung::new(480, 1400)];
+        let results: Vec<RenditionResult> = ["720p", "480p"]
+            .iter()
+            .map(|name| RenditionResult {
+                name: name.to_string(),
+                output_file: format!("out/talk_clip_01-00_to_01-30_{}.mp4", name),
+                file_size_mb: Some(2.5),
+                encoder: "libx264".to_string(),
+            })
+            .collect();
+        let manifest = LadderManifest::new("talk.mp4", 60.0, 90.0, &rungs, &results);
+        assert_eq!(manifest.rungs[1].bitrate_kbps, 1400);
+        assert_eq!(manifest.rungs[1].output_file, "out/talk_clip_01-00_to_01-30_480p.mp4");
+        assert_eq!(manifest.path(), Some(PathBuf::from("out/talk_clip_01-00_to_01-30_ladder.json")));
+    }
+}
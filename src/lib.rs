@@ -1,15 +1,168 @@
 pub mod error;
 pub mod time_parser;
+pub mod paths;
+pub mod sniff;
+pub mod integrity;
+pub mod ranges;
+pub mod wall_clock;
+pub mod phrase;
+pub mod transcript;
+pub mod speech;
 pub mod video_clipper;
+pub mod config;
 pub mod ffmpeg;
+pub mod capture;
+pub mod frames;
+pub mod storyboard;
+pub mod animated;
+pub mod preview;
+pub mod probe;
+pub mod probe_cache;
+pub mod tools;
+pub mod container;
+pub mod ssh;
+pub mod media_index;
+#[cfg(not(feature = "wasm"))]
+pub mod analysis;
+pub mod plan;
+pub mod estimate;
+pub mod capabilities;
+pub mod encoder;
+pub mod blackdetect;
+pub mod highlights;
+pub mod motion;
+pub mod cut_report;
+pub mod smart_cut;
+pub mod chunked;
+pub mod streaming;
+pub mod remux;
+pub mod compare;
+pub mod segments;
+pub mod scrub;
+pub mod source_access;
+pub mod renditions;
+pub mod ladder;
+pub mod batch;
+pub mod dispatch;
+pub mod multicam;
+pub mod sampling;
+pub mod preflight;
+pub mod report;
+pub mod metadata;
+pub mod events;
+pub mod hooks;
+#[cfg(feature = "webhooks")]
+pub mod webhook;
+pub mod metrics;
+pub mod auth;
+#[cfg(feature = "cli")]
+pub mod presenter;
+#[cfg(not(feature = "wasm"))]
+pub mod cancel;
+#[cfg(not(feature = "wasm"))]
+pub mod jobs;
+#[cfg(not(feature = "wasm"))]
+pub mod allowed_dirs;
+#[cfg(not(feature = "wasm"))]
+pub mod mcp;
+#[cfg(not(feature = "wasm"))]
+pub mod staging;
+
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "server")]
+pub mod openapi;
+#[cfg(feature = "server")]
+pub mod uploads;
+#[cfg(not(feature = "wasm"))]
+pub mod doctor;
+#[cfg(not(feature = "wasm"))]
+pub mod history;
+#[cfg(all(feature = "ffi", not(feature = "wasm")))]
+pub mod ffi;
 
 #[cfg(feature = "wasm")]
 pub mod wasm;
 
-pub use error::{VideoClipError, Result};
+pub use error::{ErrorInfo, VideoClipError, Result};
 pub use time_parser::TimeParser;
-pub use video_clipper::{VideoClipper, ClipRequest, ClipResult};
+pub use paths::InputPath;
+pub use sniff::{Container, FileKind};
+pub use integrity::{RecoveryReport, SourceIssue};
+pub use ranges::{PartNaming, SplitOptions, TimeRange};
+pub use video_clipper::{VideoClipper, ClipRequest, ClipResult, Priority, RequestProblem};
+pub use config::Config;
+pub use container::{ContainerExecutor, ContainerRuntime};
+pub use ssh::SshExecutor;
+pub use tools::Executor;
 pub use ffmpeg::{FFmpegCommand, AudioCodec};
+pub use ffmpeg::audio_mix::OverlayAudio;
+pub use ffmpeg::fit::{FitBackground, FitMode, FitOptions};
+pub use ffmpeg::keyframes::ForcedKeyframes;
+pub use ffmpeg::redact::{AudioRedactStyle, AudioRedaction, RedactRegion, RedactStyle};
+pub use ffmpeg::attribution::{Attribution, Corner};
+pub use ffmpeg::concat::{ConcatCommand, ConcatPart, Conform, EndCard};
+pub use ffmpeg::limits::ProcessLimits;
+pub use ffmpeg::playback::Playback;
+pub use ffmpeg::streams::{StreamSelection, StreamSelector, StreamType};
+pub use ffmpeg::tracks::TrackSettings;
+pub use ffmpeg::timestamps::TimestampFixes;
+pub use capture::{ScreenCapture, CaptureBackend, CaptureRegion, CaptureResult};
+pub use frames::{FrameExportOptions, FrameExportResult, ImageFormat};
+pub use storyboard::{StoryboardOptions, StoryboardResult};
+pub use animated::{AnimatedFormat, AnimatedOptions, AnimatedResult};
+pub use preview::{PreviewData, PreviewOptions, Thumbnail, WaveformData};
+pub use probe::{Chapter, MediaInfo, StreamInfo};
+pub use probe_cache::ProbeCache;
+pub use media_index::MediaIndex;
+#[cfg(not(feature = "wasm"))]
+pub use analysis::{Analysis, AnalysisPipeline, Stage};
+pub use plan::{ClipPlan, PlannedCommand, RangeAnalysis};
+pub use estimate::ClipEstimate;
+pub use capabilities::{FfmpegCapabilities, FfmpegVersion};
+pub use encoder::{EncoderBackend, EncoderChoice, EncoderSelector, VideoCodec};
+pub use blackdetect::{BlackSegment, BlackTrim};
+pub use highlights::{Highlight, HighlightOptions};
+pub use motion::MotionSample;
+pub use cut_report::CutReport;
+pub use smart_cut::{CutSegment, SegmentMode, SmartCutCommand};
+pub use chunked::{ChunkOptions, ChunkProgress, ChunkedCommand};
+pub use streaming::{StreamPlan, StreamSource, StreamStep, StreamingOptions};
+pub use remux::{RemuxCommand, RemuxReport};
+pub use compare::{CompareReport, GateAction, QualityCheck, QualityCommand, QualityGate, QualityMetric, QualityScores};
+pub use segments::{SegmentCommand, SegmentFile};
+pub use scrub::ScrubReport;
+pub use source_access::SourceAccess;
+pub use renditions::{MultiRenderCommand, ProxyOptions, Rendition, RenditionResult};
+pub use ladder::{Ladder, LadderManifest, LadderOutput, LadderRung};
+pub use batch::{BatchEntry, BatchItemResult, BatchManifest, BatchReport, CsvColumns, DirectoryOptions};
+pub use dispatch::Worker;
+pub use multicam::AlignedInput;
+pub use sampling::SampleOptions;
+pub use transcript::{Transcript, TranscriptQuery};
+pub use speech::{SubtitleFile, TranscriptProvider};
+pub use preflight::{IssueKind, PreflightAction, PreflightIssue, PreflightReport};
+pub use report::{ReportFormat, ReportSummary};
+pub use metadata::ClipMetadata;
+pub use events::{EventSink, EventSinks, Fallback, Progress, ProgressStage};
+pub use hooks::{Hook, HookEvent, Hooks};
+#[cfg(feature = "webhooks")]
+pub use webhook::Webhook;
+pub use metrics::Metrics;
+pub use auth::{ApiKey, AuthConfig};
+#[cfg(feature = "cli")]
+pub use presenter::{Presenter, Status};
+#[cfg(not(feature = "wasm"))]
+pub use cancel::CancelHandle;
+#[cfg(not(feature = "wasm"))]
+pub use jobs::{Job, JobQueue, JobStatus, QueueLimits};
+#[cfg(not(feature = "wasm"))]
+pub use allowed_dirs::AllowedDirs;
+#[cfg(not(feature = "wasm"))]
+pub use doctor::{CheckStatus, DoctorCheck, DoctorReport};
+#[cfg(not(feature = "wasm"))]
+pub use history::History;
+
 
 #[cfg(feature = "wasm")]
 pub use wasm::*;
\ No newline at end of file
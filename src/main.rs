@@ -1,16 +1,80 @@
 #[cfg(feature = "cli")]
-use clap::Parser;
+use clap::{Parser, Subcommand};
 #[cfg(feature = "cli")]
-use colored::*;
+use colored::Color;
 #[cfg(feature = "cli")]
-use video_clip_rs::{VideoClipper, ClipRequest, Result};
+use video_clip_rs::{InputPath, VideoClipper, ClipRequest, Priority, Result, TimeParser, TimeRange};
 #[cfg(feature = "cli")]
-use std::io::{self, Write};
+use video_clip_rs::{ScreenCapture, CaptureRegion, FrameExportOptions, ImageFormat, StoryboardOptions};
+#[cfg(feature = "cli")]
+use video_clip_rs::{AlignedInput, AnimatedOptions, SubtitleFile, TranscriptProvider, TranscriptQuery, BatchManifest, CsvColumns, DirectoryOptions, Hook, Hooks, Webhook};
+#[cfg(feature = "cli")]
+use video_clip_rs::{CheckStatus, DoctorReport, AnalysisPipeline, ClipPlan, History, MediaIndex, Presenter, Stage, ProbeCache, Status};
+#[cfg(feature = "cli")]
+use video_clip_rs::{ClipResult, EventSink, Fallback, Progress, ProgressStage, VideoClipError};
+#[cfg(feature = "cli")]
+use video_clip_rs::{ChunkOptions, Attribution, GateAction, Ladder, SourceAccess, QualityGate, QualityMetric, AudioRedactStyle, AudioRedaction, ClipMetadata, Config, ContainerExecutor, SshExecutor, Worker, EndCard, FitOptions, RedactRegion, HighlightOptions, SampleOptions, OverlayAudio, PreflightAction, ProcessLimits, ProxyOptions, ReportFormat, SplitOptions};
+#[cfg(feature = "server")]
+use video_clip_rs::{ApiKey, AuthConfig, QueueLimits};
+#[cfg(feature = "server")]
+use video_clip_rs::uploads::UploadStore;
+#[cfg(feature = "cli")]
+use std::sync::Arc;
+#[cfg(feature = "cli")]
+use std::path::{Path, PathBuf};
 
 #[cfg(feature = "cli")]
 #[derive(Parser, Debug)]
 #[command(author, version, about = "High-performance video clipping tool", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+    
+    /// Always run ffprobe instead of reusing cached probe results
+    #[arg(long, global = true)]
+    no_cache: bool,
+    
+    /// Config file with named presets (default: <config dir>/video-clip-rs/config.toml)
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<String>,
+    
+    /// Only print warnings, errors and JSON output
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    
+    /// Don't color the output (also set by a non-empty NO_COLOR)
+    #[arg(long, global = true)]
+    no_color: bool,
+    
+    /// Plain text for logs: no banner, emoji or colors
+    #[arg(long, global = true)]
+    plain: bool,
+    
+    /// Neither offer recent clips nor remember this one
+    #[arg(long, global = true)]
+    no_history: bool,
+    
+    /// Run ffmpeg and ffprobe in this Docker/Podman image instead of the host's (overrides the config's)
+    #[arg(long, global = true, value_name = "IMAGE")]
+    container_image: Option<String>,
+    
+    /// Container runtime for --container-image
+    #[arg(long, global = true, default_value = "docker", value_parser = ["docker", "podman"])]
+    container_runtime: String,
+    
+    /// Run ffmpeg and ffprobe on this host over SSH ([USER@]HOST[:PORT]); paths are the host's (overrides the config's)
+    #[arg(long, global = true, value_name = "HOST", conflicts_with = "container_image")]
+    ssh: Option<String>,
+    
+    /// Copy each clip made over --ssh back into the output directory
+    #[arg(long, global = true, requires = "ssh")]
+    ssh_fetch: bool,
+    
+    /// whisper.cpp model for speech recognition (needs a build with the whisper feature); overrides the config's
+    #[arg(long, global = true, value_name = "MODEL")]
+    whisper_model: Option<String>,
+    
     /// Input video file path
     #[arg(value_name = "FILE")]
     input: Option<String>,
@@ -23,124 +87,2486 @@ struct Args {
     #[arg(short, long)]
     end: Option<String>,
     
+    /// The range in words instead of --start and --end (e.g., "the last 90 seconds"
+    /// or "from 36 minutes 7 seconds to 37:19")
+    #[arg(long, value_name = "PHRASE", conflicts_with_all = ["start", "end", "wall_clock"])]
+    range: Option<String>,
+    
+    /// --start and --end are times of day (e.g., 14:03:00) on the recording's clock,
+    /// placed using the file's creation time
+    #[arg(long, requires_all = ["start", "end"])]
+    wall_clock: bool,
+    
+    /// When the recording began, for --wall-clock (e.g., "2024-03-01 13:58:20"); overrides the file's creation time
+    #[arg(long, value_name = "DATETIME", requires = "wall_clock")]
+    recording_start: Option<String>,
+    
     /// Output directory (default: downloads)
     #[arg(short, long)]
     output_dir: Option<String>,
+    
+    /// Video codec; anything but copy re-encodes with the best available encoder
+    /// (prores and vp9 keep transparency); copy when not given
+    #[arg(long, value_parser = ["copy", "h264", "hevc", "av1", "prores", "vp9"])]
+    codec: Option<String>,
+    
+    /// Force a specific FFmpeg encoder (e.g., h264_nvenc or libx264)
+    #[arg(long)]
+    encoder: Option<String>,
+    
+    /// Output container (default: follows the codec, mp4 for most)
+    #[arg(long, value_parser = ["mp4", "mov", "mkv"])]
+    container: Option<String>,
+    
+    /// Force keyframes at the clip start and handle edges ("start") or every N seconds (needs --codec)
+    #[arg(long, value_name = "start|SECONDS")]
+    force_keyframes: Option<String>,
+    
+    /// Deinterlace the clip (re-encodes)
+    #[arg(long)]
+    deinterlace: bool,
+    
+    /// Convert to this framerate (re-encodes), e.g. 25 or 29.97
+    #[arg(long)]
+    fps: Option<f64>,
+    
+    /// Convert variable-frame-rate sources (screen recordings) to a constant rate (re-encodes them)
+    #[arg(long, conflicts_with = "fps")]
+    cfr: bool,
+    
+    /// Tonemap HDR sources to SDR (re-encodes)
+    #[arg(long)]
+    tonemap: bool,
+    
+    /// Resize to WIDTHxHEIGHT, e.g. 1080x1920 for a vertical (re-encodes)
+    #[arg(long)]
+    size: Option<String>,
+    
+    /// How to fit the source into --size
+    #[arg(long, default_value = "pad", value_parser = ["pad", "crop", "stretch"], requires = "size")]
+    fit: String,
+    
+    /// Padding fill for --fit pad: a color (black, #202020) or "blur"
+    #[arg(long, default_value = "black", requires = "size")]
+    background: String,
+    
+    /// Adjust the clip's audio by this many dB (e.g., 6 or -3)
+    #[arg(long, allow_hyphen_values = true)]
+    volume: Option<f64>,
+    
+    /// Audio file to mix under the clip (music bed, voice-over)
+    #[arg(long)]
+    overlay_audio: Option<String>,
+    
+    /// Gain for --overlay-audio in dB
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true, requires = "overlay_audio")]
+    overlay_volume: f64,
+    
+    /// Duck --overlay-audio whenever the clip's own audio is active
+    #[arg(long, requires = "overlay_audio")]
+    duck: bool,
+    
+    /// Loop --overlay-audio when it's shorter than the clip
+    #[arg(long, requires = "overlay_audio")]
+    loop_overlay: bool,
+    
+    /// Fade --overlay-audio out over the clip's last SECONDS
+    #[arg(long, value_name = "SECONDS", requires = "overlay_audio")]
+    overlay_fade_out: Option<f64>,
+    
+    /// Video to play before the clip, scaled to fit its frame
+    #[arg(long)]
+    intro: Option<String>,
+    
+    /// Video to play after the clip, scaled to fit its frame
+    #[arg(long)]
+    outro: Option<String>,
+    
+    /// Image to hold after the clip as an end card
+    #[arg(long, value_name = "IMAGE")]
+    end_card: Option<String>,
+    
+    /// How long to show --end-card
+    #[arg(long, value_name = "SECONDS", default_value_t = 5.0, requires = "end_card")]
+    end_card_seconds: f64,
+    
+    /// Audio to play under --end-card (silent by default)
+    #[arg(long, value_name = "FILE", requires = "end_card")]
+    end_card_music: Option<String>,
+    
+    /// Stamp a QR code of this URL or text (e.g. where the footage came from) into the clip
+    #[arg(long, value_name = "TEXT")]
+    attribution: Option<String>,
+    
+    /// Corner for --attribution
+    #[arg(long, default_value = "bottom-right", value_parser = ["top-left", "top-right", "bottom-left", "bottom-right"], requires = "attribution")]
+    attribution_corner: String,
+    
+    /// Width of the --attribution code in pixels
+    #[arg(long, value_name = "PIXELS", default_value_t = 120, requires = "attribution")]
+    attribution_size: u32,
+    
+    /// Show --attribution from this far into the clip (e.g. 5 or 0:05)
+    #[arg(long, value_name = "TIME", requires = "attribution")]
+    attribution_from: Option<String>,
+    
+    /// Hide --attribution this far into the clip
+    #[arg(long, value_name = "TIME", requires = "attribution")]
+    attribution_until: Option<String>,
+    
+    /// Hide a rectangle of the source frame, e.g. 200x100+640+360, or 200x100+640+360@0:05-0:12 for part of the clip (repeatable)
+    #[arg(long, value_name = "WxH+X+Y[@START-END]")]
+    redact: Vec<String>,
+    
+    /// How --redact regions are hidden
+    #[arg(long, default_value = "blur", value_parser = ["blur", "pixelate", "box"])]
+    redact_style: String,
+    
+    /// Silence the audio from START to END, in seconds into the clip (repeatable)
+    #[arg(long, value_name = "START-END")]
+    mute: Vec<String>,
+    
+    /// Replace the audio from START to END with a bleep (repeatable)
+    #[arg(long, value_name = "START-END")]
+    bleep: Vec<String>,
+    
+    /// Streams to keep, e.g. 1,audio:eng for the second video stream and English audio (default: video,audio)
+    #[arg(long, value_name = "SELECTORS")]
+    streams: Option<String>,
+    
+    /// Flag or label an output track, e.g. audio:1,default,language=eng,title=Commentary or s:0,forced (repeatable)
+    #[arg(long = "track", value_name = "KIND:INDEX,SETTINGS")]
+    tracks: Vec<String>,
+    
+    /// Play the clip backwards (reverse) or forwards then backwards (boomerang); ranges up to 15s
+    #[arg(long, value_parser = ["forward", "reverse", "boomerang"])]
+    playback: Option<String>,
+    
+    /// Play the clip N times in a row, e.g. a looping background from a 5s range (ranges up to 15s)
+    #[arg(long = "loop", value_name = "N")]
+    loop_count: Option<u32>,
+    
+    /// Trim leading/trailing black frames off the range
+    #[arg(long)]
+    trim_black: bool,
+    
+    /// Probe a stream-copied clip and report how far the cut drifted from the range
+    #[arg(long)]
+    verify_cut: bool,
+    
+    /// Score the re-encoded clip with VMAF against the source and re-encode at a higher quality while it scores below this
+    #[arg(long)]
+    min_vmaf: Option<f64>,
+    
+    /// Keep a clip that still scores below --min-vmaf, with a warning, instead of failing
+    #[arg(long, requires = "min_vmaf")]
+    vmaf_warn_only: bool,
+    
+    /// Higher-quality re-encodes to try before giving up on --min-vmaf
+    #[arg(long, default_value = "2", requires = "min_vmaf")]
+    vmaf_retries: u32,
+    
+    /// Frame-accurate cut: re-encode only the edges and stream copy the rest
+    #[arg(long)]
+    smart_cut: bool,
+    
+    /// Salvage what's clippable from a truncated or corrupt source instead of stopping
+    #[arg(long)]
+    recover: bool,
+    
+    /// Re-encode in resumable chunks of N minutes, joined at the end
+    #[arg(long, value_name = "MINUTES")]
+    chunk_minutes: Option<f64>,
+    
+    /// Chunks encoded in parallel
+    #[arg(long, default_value_t = 1, requires = "chunk_minutes")]
+    chunk_parallel: usize,
+    
+    /// Named preset from the config file; options given here override it
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+    
+    /// Also render NAME[:SPEC,...] from the same decode, e.g. `full`, `web:720p`, `audio:mp3` (repeatable)
+    #[arg(long = "rendition", value_name = "SPEC")]
+    renditions: Vec<String>,
+    
+    /// Also write a low-res `_proxy` copy for review tools next to the stream copy
+    #[arg(long)]
+    proxy: bool,
+    
+    /// Render an adaptive-streaming ladder of HEIGHTp:RATEk rungs plus a manifest (default 1080p:5000k,720p:2800k,480p:1400k,360p:800k)
+    #[arg(long, value_name = "RUNGS", num_args = 0..=1, default_missing_value = "", conflicts_with_all = ["renditions", "proxy"])]
+    ladder: Option<String>,
+    
+    /// Proxy frame height
+    #[arg(long, default_value_t = 540, requires = "proxy")]
+    proxy_height: u32,
+    
+    /// Seconds of handle before the range, for trimming in an editor (default 0)
+    #[arg(long, value_name = "SECONDS")]
+    pre_roll: Option<f64>,
+    
+    /// Seconds of handle after the range (default 0)
+    #[arg(long, value_name = "SECONDS")]
+    post_roll: Option<f64>,
+    
+    /// Subtitles of the input (SRT or WebVTT); the clip's cues are re-timed and written next to it
+    #[arg(long, value_name = "FILE")]
+    subtitles: Option<String>,
+    
+    /// Transcribe the clip with the whisper model and write the captions next to it as SRT
+    #[arg(long, conflicts_with = "subtitles")]
+    captions: bool,
+    
+    /// Title written into the clip's metadata and a `.json` sidecar
+    #[arg(long)]
+    title: Option<String>,
+    
+    /// Description written into the clip's metadata and sidecar
+    #[arg(long)]
+    description: Option<String>,
+    
+    /// Tag written into the clip's metadata and sidecar (repeatable)
+    #[arg(long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+    
+    /// Drop the source's location, device and creation metadata, and check none is left
+    #[arg(long)]
+    strip_metadata: bool,
+    
+    /// Raw FFmpeg options for the source, passed before its -i (e.g., "-probesize 50M")
+    #[arg(long, value_name = "ARGS", allow_hyphen_values = true)]
+    ffmpeg_input_args: Option<String>,
+    
+    /// Raw FFmpeg options for the output, passed before the output file (e.g., "-movflags +faststart")
+    #[arg(long, value_name = "ARGS", allow_hyphen_values = true)]
+    ffmpeg_output_args: Option<String>,
+    
+    #[command(flatten)]
+    split: SplitArgs,
+    
+    /// Clip a sample this often across the range, or the whole file without one (e.g., 10:00)
+    #[arg(long, value_name = "INTERVAL", requires = "sample_length", conflicts_with = "max_duration")]
+    sample_every: Option<String>,
+    
+    /// Length of each --sample-every sample (e.g., 10)
+    #[arg(long, value_name = "DURATION", requires = "sample_every")]
+    sample_length: Option<String>,
+    
+    /// Print the clip's plan (times, codecs, filters, FFmpeg commands) as JSON instead of clipping; edit it and run it with run-plan
+    #[arg(long, conflicts_with_all = ["dry_run", "sample_every", "ladder"])]
+    plan: bool,
+    
+    /// Say what clipping would do (stream copy or re-encode, and why; the clip's length and size) instead of clipping
+    #[arg(long, conflicts_with_all = ["plan", "dry_run", "sample_every", "ladder"])]
+    explain: bool,
+    
+    #[command(flatten)]
+    dry_run: DryRunArgs,
+    
+    #[command(flatten)]
+    hooks: HookArgs,
+    
+    #[command(flatten)]
+    limits: LimitArgs,
 }
 
 #[cfg(feature = "cli")]
-fn print_banner() {
-    println!("{}", "=".repeat(60).bright_blue());
-    println!("{} {}", "🎬".bright_yellow(), "VIDEO CLIPPER - Rust Edition".bright_cyan().bold());
-    println!("   {}", "High-performance video clipping with WebAssembly support".bright_white());
-    println!("{}", "=".repeat(60).bright_blue());
-    println!();
+#[derive(clap::Args, Debug)]
+struct DryRunArgs {
+    /// Print the FFmpeg commands instead of running them
+    #[arg(long)]
+    dry_run: bool,
+    
+    /// Also put the commands on the clipboard
+    #[arg(long, requires = "dry_run")]
+    copy_command: bool,
 }
 
 #[cfg(feature = "cli")]
-fn get_input(prompt: &str) -> String {
-    print!("{}: ", prompt.bright_yellow());
-    io::stdout().flush().unwrap();
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    input.trim().trim_matches('"').trim_matches('\'').to_string()
+#[derive(clap::Args, Debug)]
+struct LimitArgs {
+    /// Threads per FFmpeg process
+    #[arg(long)]
+    threads: Option<u32>,
+    
+    /// Run FFmpeg under `nice -n N` (Unix)
+    #[arg(long, allow_hyphen_values = true)]
+    nice: Option<i32>,
+    
+    /// Run FFmpeg under `ionice -c CLASS` (Unix; 3 = idle)
+    #[arg(long, value_name = "CLASS")]
+    ionice: Option<u8>,
+    
+    /// Read sources at most N times faster than real time, so a background batch doesn't saturate a shared disk (FFmpeg 5.0+)
+    #[arg(long, value_name = "N")]
+    read_rate: Option<f64>,
+    
+    /// Hold a shared lock on each source while it's clipped (for sources on network shares)
+    #[arg(long)]
+    lock_sources: bool,
+    
+    /// Retry a clip up to N times when reading its source fails with a transient network error
+    #[arg(long, value_name = "N", default_value = "0")]
+    read_retries: u32,
+    
+    /// Pause before the first read retry, doubled for each one after
+    #[arg(long, value_name = "MS", default_value = "1000")]
+    retry_backoff_ms: u64,
 }
 
 #[cfg(feature = "cli")]
-fn main() -> Result<()> {
-    env_logger::init();
+impl LimitArgs {
+    fn into_limits(self) -> ProcessLimits {
+        ProcessLimits {
+            threads: self.threads,
+            nice: self.nice,
+            ionice_class: self.ionice,
+            read_rate: self.read_rate,
+            source: SourceAccess {
+                lock: self.lock_sources,
+                retries: self.read_retries,
+                backoff_ms: self.retry_backoff_ms,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+#[derive(clap::Args, Debug)]
+struct SplitArgs {
+    /// Split the range into clips of at most N seconds (e.g. 60 for Shorts)
+    #[arg(long, value_name = "SECONDS")]
+    max_duration: Option<f64>,
     
-    let args = Args::parse();
+    /// Seconds each part repeats from the end of the previous one (at most half the part length)
+    #[arg(long, default_value_t = 0.0, requires = "max_duration")]
+    split_overlap: f64,
+    
+    /// Name parts after their own range or as <clip>_part01of03
+    #[arg(long, default_value = "range", value_parser = ["range", "numbered"], requires = "max_duration")]
+    part_naming: String,
+}
+
+#[cfg(feature = "cli")]
+impl SplitArgs {
+    fn into_split(self) -> Result<Option<SplitOptions>> {
+        let Some(max_seconds) = self.max_duration else {
+            return Ok(None);
+        };
+        Ok(Some(SplitOptions {
+            max_seconds,
+            overlap_seconds: self.split_overlap,
+            naming: self.part_naming.parse()?,
+        }))
+    }
+}
+
+#[cfg(feature = "cli")]
+#[derive(clap::Args, Debug)]
+struct RedoTweaks {
+    /// Start time instead of the remembered one
+    #[arg(short, long)]
+    start: Option<String>,
     
-    print_banner();
+    /// End time instead of the remembered one
+    #[arg(short, long)]
+    end: Option<String>,
     
-    // Get input file
-    let input_file = match args.input {
-        Some(f) => f,
-        None => {
-            println!("{}", "Enter path to your video file:".bright_cyan());
-            println!("{}", "Note: The file must be accessible from this environment".bright_white());
-            println!();
-            get_input("Video file path")
-        }
-    };
+    /// Video codec instead of the remembered one
+    #[arg(long, value_parser = ["copy", "h264", "hevc", "av1", "prores", "vp9"])]
+    codec: Option<String>,
     
-    if input_file.is_empty() {
-        eprintln!("{} {}", "❌".bright_red(), "No file path provided!".red());
-        std::process::exit(1);
+    /// Named preset from the config file instead of the remembered one
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+    
+    /// Output directory instead of the remembered one
+    #[arg(short, long)]
+    output_dir: Option<String>,
+}
+
+#[cfg(feature = "cli")]
+impl RedoTweaks {
+    fn apply(self, mut request: ClipRequest) -> Result<ClipRequest> {
+        if let Some(start) = self.start {
+            request.start_time = start;
+        }
+        if let Some(end) = self.end {
+            request.end_time = end;
+        }
+        if let Some(codec) = self.codec {
+            request.video_codec = codec.parse()?;
+        }
+        if let Some(preset) = self.preset {
+            request.preset_name = Some(preset);
+        }
+        if let Some(output_dir) = self.output_dir {
+            request.output_dir = Some(output_dir);
+        }
+        Ok(request)
+    }
+}
+
+/// Request fields whose flags were given, which win over a preset even at
+/// their default values
+#[cfg(feature = "cli")]
+fn given_fields(flags: &[(&str, bool)]) -> Vec<String> {
+    flags.iter().filter(|(_, given)| *given).map(|(field, _)| field.to_string()).collect()
+}
+
+/// Each request, or its parts when it's split
+#[cfg(feature = "cli")]
+fn expand_splits(requests: Vec<ClipRequest>) -> Result<Vec<ClipRequest>> {
+    let mut expanded = Vec::new();
+    for request in requests {
+        expanded.extend(request.split_parts()?);
     }
+    Ok(expanded)
+}
+
+#[cfg(feature = "cli")]
+#[derive(clap::Args, Debug)]
+struct HookArgs {
+    /// Shell command to run after each successful clip (gets the result as JSON on stdin)
+    #[arg(long, value_name = "CMD")]
+    on_success: Vec<String>,
     
-    // Get start time
-    let start_time = match args.start {
-        Some(s) => s,
-        None => {
-            println!();
-            println!("{}", "Start time (e.g., 36:07 or 2167):".bright_cyan());
-            let input = get_input("Start");
-            if input.is_empty() { "0".to_string() } else { input }
+    /// Shell command to run after each failed clip
+    #[arg(long, value_name = "CMD")]
+    on_failure: Vec<String>,
+    
+    /// URL to POST success/failure events to
+    #[arg(long, value_name = "URL")]
+    webhook: Vec<String>,
+    
+    /// Secret used to sign webhook timestamps and bodies (X-Video-Clip-Signature)
+    #[arg(long, env = "VIDEO_CLIP_WEBHOOK_SECRET", hide_env_values = true)]
+    webhook_secret: Option<String>,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)] // Parsed once per run; boxing Batch's options buys nothing
+enum Commands {
+    /// Record the screen (or a region of it) into a file that can then be clipped
+    Capture {
+        /// How long to record (e.g., 90, 1:30 or 2m)
+        #[arg(short, long)]
+        duration: String,
+        
+        /// Region to record as WIDTHxHEIGHT+X+Y (default: whole screen)
+        #[arg(short, long)]
+        region: Option<String>,
+        
+        /// Capture framerate
+        #[arg(short, long, default_value_t = 30)]
+        framerate: u32,
+        
+        /// Grab device override (X11 display, gdigrab target or AVFoundation device)
+        #[arg(long)]
+        display: Option<String>,
+        
+        /// Hide the mouse cursor in the recording
+        #[arg(long)]
+        no_cursor: bool,
+        
+        /// Output directory (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Export a time range as a numbered PNG/JPEG image sequence
+    Frames {
+        /// Input video file path
+        #[arg(value_name = "FILE")]
+        input: String,
+        
+        /// Start time (e.g., 36:07 or 2167)
+        #[arg(short, long)]
+        start: String,
+        
+        /// End time (e.g., 37:19 or 2239)
+        #[arg(short, long)]
+        end: String,
+        
+        /// Image format
+        #[arg(long, default_value = "png", value_parser = ["png", "jpeg", "jpg"])]
+        format: String,
+        
+        /// Frames per second to sample (default: every frame)
+        #[arg(long)]
+        fps: Option<f64>,
+        
+        /// Output width in pixels (height keeps the aspect ratio unless given)
+        #[arg(long)]
+        width: Option<u32>,
+        
+        /// Output height in pixels
+        #[arg(long)]
+        height: Option<u32>,
+        
+        /// JPEG quality from 2 (best) to 31 (worst)
+        #[arg(long)]
+        quality: Option<u32>,
+        
+        /// Output directory (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Render a tiled contact sheet of evenly spaced frames across a range
+    Storyboard {
+        /// Input video file path
+        #[arg(value_name = "FILE")]
+        input: String,
+        
+        /// Start time (default: 0)
+        #[arg(short, long, default_value = "0")]
+        start: String,
+        
+        /// End time (e.g., 37:19 or 2239)
+        #[arg(short, long)]
+        end: String,
+        
+        /// Number of tile columns
+        #[arg(long, default_value_t = 4)]
+        columns: u32,
+        
+        /// Number of tile rows
+        #[arg(long, default_value_t = 4)]
+        rows: u32,
+        
+        /// Width of each tile in pixels
+        #[arg(long, default_value_t = 320)]
+        tile_width: u32,
+        
+        /// Leave timestamps off the tiles
+        #[arg(long)]
+        no_timestamps: bool,
+        
+        /// Output directory (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Clip the same range from every file matching glob patterns, or per-file ranges from a manifest
+    Batch {
+        /// Input files or glob patterns (quote them so the shell doesn't expand them)
+        #[arg(value_name = "PATTERN", required_unless_present_any = ["manifest", "input_dir", "align"])]
+        patterns: Vec<String>,
+        
+        /// Start time shared by every file
+        #[arg(short, long, required_unless_present = "manifest")]
+        start: Option<String>,
+        
+        /// End time shared by every file
+        #[arg(short, long, required_unless_present = "manifest")]
+        end: Option<String>,
+        
+        /// JSON manifest with shared and/or per-entry ranges, or a CSV with one range per row
+        #[arg(long, conflicts_with_all = ["patterns", "input_dir", "align"])]
+        manifest: Option<String>,
+        
+        /// Clip every video in a directory instead of matching patterns
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["patterns", "align"])]
+        input_dir: Option<String>,
+        
+        /// Clip the same moment from synchronized recordings (multi-camera): each FILE@OFFSET
+        /// gives the time its recording started on the shared timeline of --start/--end,
+        /// e.g. --align cam1.mp4 --align cam2.mp4@2.5
+        #[arg(long, value_name = "FILE@OFFSET", conflicts_with = "patterns")]
+        align: Vec<String>,
+        
+        /// Comma-separated extensions picked up from --input-dir (default: common video formats, plus
+        /// files whose content is video)
+        #[arg(long, value_name = "EXTS", value_delimiter = ',', requires = "input_dir")]
+        extensions: Vec<String>,
+        
+        /// Also clip videos in --input-dir's subdirectories
+        #[arg(long, requires = "input_dir")]
+        recursive: bool,
+        
+        /// CSV headers to read each field from, e.g. `input=File,start=In,end=Out,title=Name` (CSV manifests only)
+        #[arg(long, value_name = "MAPPING", requires = "manifest")]
+        columns: Option<String>,
+        
+        /// Recreate each file's directory (below the pattern's fixed prefix, or --input-dir) in the output dir
+        #[arg(long)]
+        mirror: bool,
+        
+        /// Priority for files that don't set one in the manifest
+        #[arg(long, value_enum, default_value_t = Priority::Normal)]
+        priority: Priority,
+        
+        /// Named preset from the config file applied to every file
+        #[arg(long, value_name = "NAME")]
+        preset: Option<String>,
+        
+        /// Print the batch report as JSON
+        #[arg(long)]
+        json: bool,
+        
+        /// Share the batch out among workers, balanced by estimated time: local, ssh:[USER@]HOST[:PORT],
+        /// docker:IMAGE or podman:IMAGE (repeatable; overrides the config's [[workers]])
+        #[arg(long = "worker", value_name = "SPEC")]
+        workers: Vec<String>,
+        
+        /// Also write a readable report of the run; the format follows the extension (.md or .html)
+        #[arg(long, value_name = "PATH")]
+        report: Option<String>,
+        
+        /// Clip every file again, even those an earlier run of the same batch already made
+        #[arg(long)]
+        overwrite: bool,
+        
+        /// Check every range against its source's duration first: stop on
+        /// problems (check), clamp ends that run past the source (fix) or drop bad ranges (skip)
+        #[arg(long, value_name = "ACTION")]
+        preflight: Option<PreflightAction>,
+        
+        /// Seconds of handle before every range (default 0)
+        #[arg(long, value_name = "SECONDS")]
+        pre_roll: Option<f64>,
+        
+        /// Seconds of handle after every range (default 0)
+        #[arg(long, value_name = "SECONDS")]
+        post_roll: Option<f64>,
+        
+        #[command(flatten)]
+        split: SplitArgs,
+        
+        #[command(flatten)]
+        dry_run: DryRunArgs,
+        
+        #[command(flatten)]
+        hooks: HookArgs,
+        
+        #[command(flatten)]
+        limits: LimitArgs,
+        
+        /// Output directory (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Clip where a transcript (SRT or WebVTT) says something, or a range of its cues
+    Transcript {
+        /// Input video file path
+        input: String,
+        
+        /// Subtitle or transcript file for the video (default: transcribe it with the whisper model)
+        #[arg(long, value_name = "FILE")]
+        transcript: Option<String>,
+        
+        /// Clip every place these words are said
+        #[arg(long, value_name = "TEXT", required_unless_present = "cues", conflicts_with = "cues")]
+        say: Option<String>,
+        
+        /// Clip cues FIRST-LAST, numbered from 1 (e.g., 12-18)
+        #[arg(long, value_name = "RANGE")]
+        cues: Option<String>,
+        
+        /// Write each clip's cues, re-timed to it, next to the clip
+        #[arg(long)]
+        retime: bool,
+        
+        /// Seconds of handle before each match
+        #[arg(long, default_value_t = 0.0, value_name = "SECONDS")]
+        pre_roll: f64,
+        
+        /// Seconds of handle after each match
+        #[arg(long, default_value_t = 0.0, value_name = "SECONDS")]
+        post_roll: f64,
+        
+        /// Print the batch report as JSON
+        #[arg(long)]
+        json: bool,
+        
+        #[command(flatten)]
+        hooks: HookArgs,
+        
+        #[command(flatten)]
+        limits: LimitArgs,
+        
+        /// Output directory (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Suggest highlight ranges from loudness spikes (cheering, laughter), best first
+    Highlights {
+        /// Input video file path
+        input: String,
+        
+        /// Start of the part to analyze (default: the beginning)
+        #[arg(short, long, requires = "end")]
+        start: Option<String>,
+        
+        /// End of the part to analyze (default: the end)
+        #[arg(short, long, requires = "start")]
+        end: Option<String>,
+        
+        /// LU above the typical loudness that counts as a spike
+        #[arg(long, default_value_t = 6.0, value_name = "LU")]
+        threshold: f64,
+        
+        /// Most suggestions to list
+        #[arg(long, default_value_t = 10)]
+        max: usize,
+        
+        /// Seconds kept before each spike
+        #[arg(long, default_value_t = 8.0, value_name = "SECONDS")]
+        pre_roll: f64,
+        
+        /// Seconds kept after each spike
+        #[arg(long, default_value_t = 4.0, value_name = "SECONDS")]
+        post_roll: f64,
+        
+        /// Leave static stretches (menus, pauses, still shots) out of the suggestions
+        #[arg(long)]
+        exclude_static: bool,
+        
+        /// Frame difference (0-255) under which video counts as static
+        #[arg(long, value_name = "YDIF", requires = "exclude_static")]
+        min_motion: Option<f64>,
+        
+        /// Print the suggestions as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    
+    /// Run the REST API: queue clips with POST /clips, scrape GET /metrics
+    #[cfg(feature = "server")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+        
+        /// Clips processed in parallel
+        #[arg(long, default_value_t = 2)]
+        workers: usize,
+        
+        /// Clips processed in parallel from the same source file
+        #[arg(long)]
+        max_per_source: Option<usize>,
+        
+        /// Require this bearer token (repeatable; also the config's [[auth.keys]])
+        #[arg(long = "api-key", value_name = "KEY", env = "VIDEO_CLIP_API_KEYS", value_delimiter = ',', hide_env_values = true)]
+        api_keys: Vec<String>,
+        
+        /// Also accept HS256 JWTs signed with this secret
+        #[arg(long, env = "VIDEO_CLIP_JWT_SECRET", hide_env_values = true)]
+        jwt_secret: Option<String>,
+        
+        /// Requests a minute allowed per API key or JWT subject
+        #[arg(long, value_name = "N")]
+        rate_limit: Option<u32>,
+        
+        /// Sign expiring download links to finished clips with this secret
+        #[arg(long, env = "VIDEO_CLIP_URL_SECRET", hide_env_values = true)]
+        url_secret: Option<String>,
+        
+        /// Accept source uploads at POST /sources, kept in this directory
+        #[arg(long, value_name = "DIR")]
+        upload_dir: Option<String>,
+        
+        /// Don't serve the web UI at /
+        #[arg(long)]
+        no_ui: bool,
+        
+        /// Largest source upload accepted, in MB
+        #[arg(long, value_name = "MB", default_value_t = 10240, requires = "upload_dir")]
+        max_upload_mb: u64,
+        
+        /// Remove uploads this many hours after they last changed
+        #[arg(long, value_name = "HOURS", default_value_t = 24, requires = "upload_dir")]
+        upload_ttl_hours: u64,
+        
+        /// Only let requests name files in this directory (repeatable; default: the output and upload directories)
+        #[arg(long = "allow-dir", value_name = "DIR")]
+        allowed_dirs: Vec<PathBuf>,
+        
+        #[command(flatten)]
+        hooks: HookArgs,
+        
+        #[command(flatten)]
+        limits: LimitArgs,
+        
+        /// Output directory (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Export a range as a looping animated GIF, WebP or AVIF
+    Animate {
+        /// Input video file path
+        #[arg(value_name = "FILE")]
+        input: String,
+        
+        /// Start time (e.g., 36:07 or 2167)
+        #[arg(short, long)]
+        start: String,
+        
+        /// End time (e.g., 36:12 or 2172)
+        #[arg(short, long)]
+        end: String,
+        
+        /// Output format
+        #[arg(long, default_value = "webp", value_parser = ["gif", "webp", "avif"])]
+        format: String,
+        
+        /// Animation framerate
+        #[arg(long, default_value_t = 15.0)]
+        fps: f64,
+        
+        /// Output width in pixels (height keeps the aspect ratio)
+        #[arg(long, default_value_t = 480)]
+        width: u32,
+        
+        /// Quality from 0 (smallest) to 100 (best); ignored for GIF
+        #[arg(long, default_value_t = 75)]
+        quality: u32,
+        
+        /// Times to replay after the first play (0 loops forever)
+        #[arg(long = "loop", default_value_t = 0)]
+        loop_count: u32,
+        
+        /// Output directory (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Rewrap a whole file into another container without re-encoding, verifying nothing was lost
+    Remux {
+        /// Input video file path
+        input: String,
+        
+        /// Container to rewrap into
+        #[arg(long, default_value = "mp4", value_parser = ["mp4", "mov", "mkv"])]
+        container: String,
+        
+        /// Print the verification report as JSON
+        #[arg(long)]
+        json: bool,
+        
+        /// Output directory (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Compare two renditions of a clip: durations, streams and picture quality
+    Compare {
+        /// Reference file
+        a: String,
+        
+        /// File compared against the reference
+        b: String,
+        
+        /// Quality metric to measure b against a (repeatable: psnr, ssim, vmaf)
+        #[arg(long = "metric", value_parser = ["psnr", "ssim", "vmaf"])]
+        metrics: Vec<String>,
+        
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    
+    /// Chop a whole file into equal-length parts in one pass (parts start on keyframes)
+    Segment {
+        /// Input video file path
+        input: String,
+        
+        /// Length of each part (e.g., 10:00 or 600)
+        #[arg(long)]
+        every: String,
+        
+        /// Print the parts as JSON
+        #[arg(long)]
+        json: bool,
+        
+        /// Output directory (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Check the FFmpeg toolchain and run a tiny end-to-end test clip
+    Doctor {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+        
+        /// Output directory to check for write access (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Show or clear the ffprobe result cache
+    Cache {
+        /// Remove every cached probe result
+        #[arg(long)]
+        clear: bool,
+        
+        /// Remove cached results for these files
+        #[arg(long, value_name = "FILE", conflicts_with = "clear")]
+        invalidate: Vec<String>,
+    },
+    
+    /// Run a source's analysis stages once, reusing saved ones, and describe a range of it
+    Analyze {
+        /// Input video file path
+        input: String,
+        
+        /// Stage to run (repeatable; default: all)
+        #[arg(long = "stage", value_parser = ["probe", "keyframes", "chapters", "scenes", "silences", "loudness"])]
+        stages: Vec<String>,
+        
+        /// Start of a range to describe (e.g., 1:30)
+        #[arg(short, long, requires = "end")]
+        start: Option<String>,
+        
+        /// End of the range
+        #[arg(short, long, requires = "start")]
+        end: Option<String>,
+        
+        /// Run the stages again even if they have saved results
+        #[arg(long)]
+        rerun: bool,
+        
+        /// Print the range (or the stages run) as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    
+    /// Build or show a source's index (keyframes, chapters, silences, scenes) that later clips reuse
+    Index {
+        /// Input video file path
+        input: String,
+        
+        /// Analyze the source again even if its index is current
+        #[arg(long)]
+        rebuild: bool,
+        
+        /// Print the index as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    
+    /// Run a clip plan printed by --plan, with any edits made to it
+    RunPlan {
+        /// Plan JSON file, or - to read it from stdin
+        plan: String,
+        
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+        
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    
+    /// Print the JSON Schema of clip requests (REST API, FFI and WASM bodies)
+    Schema,
+    
+    /// Serve the clipper to AI agents as MCP tools over stdin and stdout
+    Mcp {
+        /// Only let requests name files in this directory (repeatable)
+        #[arg(long = "allow-dir", value_name = "DIR")]
+        allowed_dirs: Vec<PathBuf>,
+        
+        /// Print the tool definitions (name, parameters and result schemas) and exit
+        #[arg(long)]
+        list_tools: bool,
+        
+        #[command(flatten)]
+        limits: LimitArgs,
+        
+        /// Output directory (default: downloads)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    
+    /// Clip a remembered request again, optionally with tweaks
+    Redo {
+        /// Re-run the most recent clip (the default)
+        #[arg(long, conflicts_with = "entry")]
+        last: bool,
+        
+        /// Re-run the Nth most recent clip instead (see --list)
+        #[arg(long, value_name = "N")]
+        entry: Option<usize>,
+        
+        /// List the remembered clips
+        #[arg(long, conflicts_with_all = ["last", "entry"])]
+        list: bool,
+        
+        #[command(flatten)]
+        tweaks: RedoTweaks,
+        
+        #[command(flatten)]
+        hooks: HookArgs,
+        
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+}
+
+/// Recent files, output directories and ranges offered in interactive mode
+#[cfg(feature = "cli")]
+const QUICK_PICKS: usize = 5;
+
+/// The clip history, or an empty one with `--no-history` or when it can't be read
+#[cfg(feature = "cli")]
+fn load_history(disabled: bool) -> History {
+    let Some(path) = History::default_path().filter(|_| !disabled) else {
+        return History::default();
+    };
+    History::load(&path).unwrap_or_else(|e| {
+        log::warn!("Ignoring the clip history: {}", e);
+        History::default()
+    })
+}
+
+#[cfg(feature = "cli")]
+fn record_history(request: &ClipRequest) {
+    let Some(path) = History::default_path() else {
+        return;
+    };
+    let mut history = History::load(&path).unwrap_or_default();
+    history.record(request);
+    if let Err(e) = history.save(&path) {
+        log::warn!("Could not save the clip history: {}", e);
+    }
+}
+
+#[cfg(feature = "cli")]
+fn print_history(out: Presenter, history: &History) {
+    if history.entries.is_empty() {
+        out.note("ℹ️", "No clips remembered yet");
+        return;
+    }
+    out.heading("📜", "Recent clips:");
+    for (i, request) in history.entries.iter().enumerate() {
+        let output_dir = request.output_dir.as_deref().map(|dir| format!(" -> {}", dir)).unwrap_or_default();
+        out.field(&format!("{:>2})", i + 1), format!("{} [{} - {}]{}", request.input_file, request.start_time, request.end_time, output_dir));
+    }
+}
+
+#[cfg(feature = "cli")]
+fn owned(values: Vec<&str>) -> Vec<String> {
+    values.into_iter().map(str::to_string).collect()
+}
+
+/// `answer`, or the quick pick it numbers (from 1)
+#[cfg(feature = "cli")]
+fn picked(answer: String, choices: &[String]) -> String {
+    match answer.parse::<usize>() {
+        Ok(n) if (1..=choices.len()).contains(&n) => choices[n - 1].clone(),
+        _ => answer,
+    }
+}
+
+/// Asks where the clip goes when there are recent directories to pick from;
+/// `None` for the default
+#[cfg(feature = "cli")]
+fn ask_output_dir(out: Presenter, recent: &[String]) -> Option<String> {
+    if recent.is_empty() {
+        return None;
+    }
+    out.blank();
+    out.prompt("Output directory (Enter for downloads), or pick a recent one:");
+    out.choices(recent);
+    Some(picked(out.ask("Output directory")?, recent)).filter(|dir| !dir.is_empty())
+}
+
+/// Asks for the source until the path exists; an empty answer gives up
+#[cfg(feature = "cli")]
+fn ask_input_file(out: Presenter, recent: &[String]) -> Option<String> {
+    loop {
+        let path = InputPath::normalize(&picked(out.ask("Video file path")?, recent));
+        if path.is_empty() || std::path::Path::new(&path).exists() {
+            return Some(path);
+        }
+        out.warning(&format!("{} doesn't exist; check the path and try again", path));
+    }
+}
+
+/// Asks for a time until the answer parses; an empty answer takes `default`
+#[cfg(feature = "cli")]
+fn ask_time(out: Presenter, question: &str, label: &str, default: Option<&str>) -> Option<String> {
+    out.blank();
+    out.prompt(question);
+    loop {
+        let answer = out.ask(label)?;
+        let answer = match (answer.is_empty(), default) {
+            (true, Some(default)) => default.to_string(),
+            (true, None) => {
+                out.warning(&format!("{} time required", label));
+                continue;
+            }
+            (false, _) => answer,
+        };
+        match TimeParser::parse_to_seconds(&answer) {
+            Ok(_) => return Some(answer),
+            Err(e) => out.warning(&format!("{}; try e.g. 36:07, 1:02:03.5 or 2167", e)),
         }
+    }
+}
+
+/// Why `start`-`end` can't be clipped from a source of `duration` seconds, if it can't
+#[cfg(feature = "cli")]
+fn range_problem(start: &str, end: &str, duration: Option<f64>) -> Option<String> {
+    let range = match TimeRange::parse(start, end) {
+        Ok(range) => range,
+        Err(e) => return Some(e.to_string()),
     };
+    let duration = duration?;
+    let readable = TimeParser::format_time_readable(duration);
+    if range.start >= duration {
+        Some(format!("The video is only {} long; start before the end", readable))
+    } else if range.end > duration {
+        Some(format!("The video is only {} long; end at {} at the latest", readable, readable))
+    } else {
+        None
+    }
+}
+
+/// Asks for the times that weren't given, showing the source's duration first
+/// so they aren't a guess, along with the `recent` ranges of this file to pick
+/// from. A range that doesn't fit the source, or isn't confirmed, is asked for
+/// again in full. `None` if stdin closes first.
+#[cfg(feature = "cli")]
+fn ask_range(out: Presenter, input_file: &str, mut start: Option<String>, mut end: Option<String>, recent: &[(&str, &str)]) -> Option<(String, String)> {
+    let duration = video_clip_rs::probe::probe(input_file).ok().and_then(|info| info.duration);
+    if let Some(duration) = duration {
+        out.blank();
+        out.fact("⏱️", "Video duration:", TimeParser::format_time_readable(duration));
+    }
     
-    // Get end time
-    let end_time = match args.end {
-        Some(e) => e,
-        None => {
-            println!();
-            println!("{}", "End time (e.g., 37:19 or 2239):".bright_cyan());
-            get_input("End")
+    let choices: Vec<String> = recent.iter().map(|(start, end)| format!("{} - {}", start, end)).collect();
+    loop {
+        if start.is_none() && end.is_none() && !recent.is_empty() {
+            out.blank();
+            out.prompt("Pick a recent range of this file, or press Enter for a new one:");
+            out.choices(&choices);
+            if let Ok(n @ 1..) = out.ask("Range")?.parse::<usize>() {
+                if let Some((recent_start, recent_end)) = recent.get(n - 1) {
+                    start = Some(recent_start.to_string());
+                    end = Some(recent_end.to_string());
+                }
+            }
         }
-    };
+        let start_time = match start.take() {
+            Some(start) => start,
+            None => ask_time(out, "Start time (e.g., 36:07 or 2167):", "Start", Some("0"))?,
+        };
+        let end_time = match end.take() {
+            Some(end) => end,
+            None => ask_time(out, "End time (e.g., 37:19 or 2239):", "End", None)?,
+        };
+        
+        if let Some(problem) = range_problem(&start_time, &end_time, duration) {
+            out.warning(&problem);
+            continue;
+        }
+        let length = TimeParser::parse_to_seconds(&end_time).ok()? - TimeParser::parse_to_seconds(&start_time).ok()?;
+        out.blank();
+        let question = format!("Clip {} to {} ({})? [Y/n]", start_time, end_time, TimeParser::format_time_readable(length));
+        if !out.ask(&question)?.to_lowercase().starts_with('n') {
+            return Some((start_time, end_time));
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+fn run_capture(
+    out: Presenter,
+    duration: &str,
+    region: Option<&str>,
+    framerate: u32,
+    display: Option<String>,
+    no_cursor: bool,
+    output_dir: Option<String>,
+) -> Result<()> {
+    let duration = TimeParser::parse_to_seconds(duration)?;
+    let output_dir = output_dir.unwrap_or_else(|| "downloads".to_string());
     
-    if end_time.is_empty() {
-        eprintln!("{} {}", "❌".bright_red(), "End time required!".red());
-        std::process::exit(1);
+    let mut capture = ScreenCapture::for_current_platform(ScreenCapture::default_output_path(&output_dir), duration)?;
+    capture.set_region(region.map(CaptureRegion::parse).transpose()?);
+    capture.set_framerate(framerate);
+    capture.set_display(display);
+    capture.set_capture_cursor(!no_cursor);
+    capture.validate()?;
+    
+    out.heading("🔴", "Recording screen:");
+    out.field("Duration:", TimeParser::format_time_readable(duration));
+    out.field("Output:", capture.output().display());
+    out.blank();
+    out.heading("⏳", "Recording...");
+    
+    let result = capture.execute()?;
+    
+    out.blank();
+    out.success("✅", "SUCCESS!");
+    out.fact("📁", "Recording saved:", out.highlight(&result.output_file));
+    if let Some(size_mb) = result.file_size_mb {
+        out.fact("📊", "Size:", format!("{:.1} MB", size_mb));
     }
+    out.blank();
+    out.fact("✂️", "Trim it with:", format!("video-clip \"{}\" --start <START> --end <END>", result.output_file));
     
-    // Create clip request
-    let request = ClipRequest {
-        input_file: input_file.clone(),
-        start_time,
-        end_time,
-        output_dir: args.output_dir,
-    };
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_frames(out: Presenter, request: ClipRequest, options: FrameExportOptions) -> Result<()> {
+    out.heading("🖼️", "Exporting frames:");
+    out.field("Input:", &request.input_file);
+    out.field("Start:", &request.start_time);
+    out.field("End:", &request.end_time);
+    out.blank();
+    out.heading("⏳", "Processing...");
+    
+    let result = VideoClipper::new().export_frames(&request, &options)?;
+    
+    out.blank();
+    out.success("✅", "SUCCESS!");
+    out.fact("📁", "Frames saved:", out.highlight(&result.output_dir));
+    out.fact("🎞️", "Frame count:", result.frame_count);
+    
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_storyboard(out: Presenter, request: ClipRequest, options: StoryboardOptions) -> Result<()> {
+    out.heading("🗂️", "Building storyboard:");
+    out.field("Input:", &request.input_file);
+    out.field("Grid:", format!("{}x{}", options.columns, options.rows));
+    out.blank();
+    out.heading("⏳", "Processing...");
+    
+    let result = VideoClipper::new().storyboard(&request, &options)?;
     
-    // Create clipper
-    let clipper = VideoClipper::new();
-    
-    println!();
-    println!("{} {}", "✂️".bright_yellow(), "Creating clip:".bright_cyan());
-    println!("   {} {}", "Input:".bright_white(), input_file);
-    println!("   {} {}", "Start:".bright_white(), request.start_time);
-    println!("   {} {}", "End:".bright_white(), request.end_time);
-    
-    println!();
-    println!("{} {}", "⏳".bright_yellow(), "Processing...".bright_cyan());
-    
-    // Execute clipping
-    match clipper.clip_video(&request) {
-        Ok(result) => {
-            println!();
-            println!("{} {}", "✅".bright_green(), "SUCCESS!".bright_green().bold());
-            println!("{} {}", "📁 Clip saved:".bright_white(), result.output_file.bright_cyan());
-            
-            if let Some(size_mb) = result.file_size_mb {
-                println!("{} {:.1} MB", "📊 Size:".bright_white(), size_mb);
-            }
-            
-            println!("{} {:.1}s", "⏱️ Duration:".bright_white(), result.duration);
-            println!();
-            println!("{} {}", "🎉".bright_yellow(), "Done! Your clip is ready!".bright_green().bold());
-        }
-        Err(e) => {
-            eprintln!();
-            eprintln!("{} {}", "❌".bright_red(), format!("Error: {}", e).red());
+    out.blank();
+    out.success("✅", "SUCCESS!");
+    out.fact("📁", "Storyboard saved:", out.highlight(&result.output_file));
+    out.fact("🎞️", "Tiles:", format!("every {:.1}s", result.interval_seconds));
+    
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+impl HookArgs {
+    fn into_hooks(self) -> Result<Hooks> {
+        let mut hooks = Hooks::new();
+        for command in self.on_success {
+            hooks.add_on_success(Hook::Command(command));
+        }
+        for command in self.on_failure {
+            hooks.add_on_failure(Hook::Command(command));
+        }
+        for url in self.webhook {
+            let mut webhook = Webhook::new(url);
+            webhook.secret = self.webhook_secret.clone();
+            webhook.validate()?;
+            hooks.add_on_success(Hook::Webhook(webhook.clone()));
+            hooks.add_on_failure(Hook::Webhook(webhook));
+        }
+        Ok(hooks)
+    }
+}
+
+/// Clips one request with live output (its parts like a batch, when it's
+/// split) and remembers it once it's done
+#[cfg(feature = "cli")]
+fn run_clip(out: Presenter, request: ClipRequest, config: Config, hooks: Hooks, limits: ProcessLimits, remember: bool) -> Result<()> {
+    if request.split.is_some() {
+        run_batch(out, request.split_parts()?, config, hooks, limits, BatchRun::default())?;
+    } else {
+        let mut clipper = VideoClipper::from_config(config);
+        clipper.set_hooks(hooks);
+        clipper.set_process_limits(limits)?;
+        
+        clipper.add_event_sink(Arc::new(CliEvents { out, single: true }));
+        
+        if clipper.clip_video(&request).is_err() {
             std::process::exit(1);
         }
     }
     
+    if remember {
+        record_history(&request);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_ladder(out: Presenter, request: ClipRequest, ladder: &Ladder, config: Config, limits: ProcessLimits) -> Result<()> {
+    let mut clipper = VideoClipper::from_config(config);
+    clipper.set_process_limits(limits)?;
+    clipper.add_event_sink(Arc::new(CliEvents { out, single: true }));
+    
+    match clipper.clip_ladder(&request, ladder) {
+        Ok((_, manifest)) => {
+            out.fact("🪜", "Manifest:", out.highlight(&manifest.display().to_string()));
+            Ok(())
+        }
+        Err(_) => std::process::exit(1),
+    }
+}
+
+/// Prints the FFmpeg command of every clip (and split part) without running
+/// any; with `copy` they also go on the clipboard, one per line
+#[cfg(feature = "cli")]
+fn run_dry_run(out: Presenter, requests: &[ClipRequest], config: Config, json: bool, copy: bool) -> Result<()> {
+    let clipper = VideoClipper::from_config(config);
+    let report = clipper.prepare_batch(requests);
+    
+    let commands: Vec<&str> = report.items.iter().filter_map(|item| item.result.as_ref()).map(|result| result.command.as_str()).collect();
+    if json {
+        out.data(&serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        for item in &report.items {
+            match (&item.result, &item.error) {
+                (Some(result), _) => out.data(&result.command),
+                (None, Some(error)) => out.item(Status::Failed, &format!("{}: {}", item.input_file, out.paint(error, Color::Red))),
+                (None, None) => {}
+            }
+        }
+    }
+    
+    if copy && !commands.is_empty() {
+        copy_to_clipboard(&commands.join("\n"))?;
+        let copied = if commands.len() == 1 { "the command".to_string() } else { format!("{} commands", commands.len()) };
+        // Keep stdout for the commands themselves
+        out.on_stderr().note("📋", &format!("Copied {} to the clipboard", copied));
+    }
+    
+    if report.failed() > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Raw FFmpeg options given as one string; values with spaces in them
+/// need a JSON request instead
+#[cfg(feature = "cli")]
+fn split_raw_args(args: &str) -> Vec<String> {
+    args.split_whitespace().map(String::from).collect()
+}
+
+#[cfg(feature = "cli")]
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    // On X11 and Wayland the text stays available after exit only through a
+    // clipboard manager, which arboard hands it to when the clipboard drops
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|e| std::io::Error::other(format!("couldn't copy to the clipboard: {}", e)))?;
+    Ok(())
+}
+
+/// How a batch reports and whether it keeps earlier outputs
+#[cfg(feature = "cli")]
+#[derive(Debug, Default)]
+struct BatchRun {
+    json: bool,
+    /// Readable report written after the run
+    report: Option<String>,
+    overwrite: bool,
+}
+
+#[cfg(feature = "cli")]
+fn run_batch(out: Presenter, requests: Vec<ClipRequest>, config: Config, hooks: Hooks, limits: ProcessLimits, run: BatchRun) -> Result<()> {
+    let BatchRun { json, report: report_path, overwrite } = run;
+    // Fail on a bad report path before clipping anything
+    let report_format = report_path.as_deref().map(ReportFormat::from_path).transpose()?;
+    if !json {
+        out.note("📦", &format!("{} {}", out.highlight("Batch clipping"), out.dim(&format!("({} files)", requests.len()))));
+        if !config.workers.is_empty() {
+            let names: Vec<&str> = config.workers.iter().map(|worker| worker.name.as_str()).collect();
+            out.field("Workers:", names.join(", "));
+        }
+    }
+    
+    let workers = config.workers.clone();
+    let mut clipper = VideoClipper::from_config(config);
+    clipper.set_hooks(hooks);
+    clipper.set_process_limits(limits)?;
+    clipper.set_overwrite_existing(overwrite);
+    if !json {
+        clipper.add_event_sink(Arc::new(CliEvents { out, single: false }));
+    }
+    let report = clipper.dispatch_batch(&requests, &workers);
+    
+    if json {
+        out.data(&serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        for item in &report.items {
+            match (&item.result, &item.error) {
+                (Some(result), _) if item.skipped => out.item(Status::Skipped, &format!("{} {}", out.highlight(&result.output_file), out.dim("(skipped)"))),
+                (Some(result), _) => match &item.worker {
+                    Some(worker) => out.item(Status::Ok, &format!("{} {}", out.highlight(&result.output_file), out.dim(&format!("({})", worker)))),
+                    None => out.item(Status::Ok, &out.highlight(&result.output_file)),
+                },
+                (None, Some(error)) => out.item(Status::Failed, &format!("{}: {}", item.input_file, out.paint(error, Color::Red))),
+                (None, None) => {}
+            }
+        }
+        out.blank();
+        out.note("📊", &format!("{} succeeded ({} skipped), {} failed", report.succeeded(), report.skipped(), report.failed()));
+    }
+    
+    if let (Some(path), Some(format)) = (report_path, report_format) {
+        std::fs::write(&path, video_clip_rs::report::render(&report, format))?;
+        if !json {
+            out.note("📝", &format!("Report written to {}", out.highlight(&path)));
+        }
+    }
+    
+    if report.failed() > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Terminal output driven by the clipper's events. For a single clip it
+/// narrates everything; in a batch it only adds what the per-file summary
+/// lines can't show (fallbacks and chunk progress).
+#[cfg(feature = "cli")]
+struct CliEvents {
+    out: Presenter,
+    single: bool,
+}
+
+#[cfg(feature = "cli")]
+impl EventSink for CliEvents {
+    fn on_start(&self, request: &ClipRequest) {
+        if !self.single {
+            return;
+        }
+        let out = self.out;
+        out.blank();
+        out.heading("✂️", "Creating clip:");
+        out.field("Input:", &request.input_file);
+        out.field("Start:", &request.start_time);
+        out.field("End:", &request.end_time);
+    
+        out.blank();
+        out.heading("⏳", "Processing...");
+    }
+    
+    fn on_progress(&self, request: &ClipRequest, progress: &Progress) {
+        if progress.stage == ProgressStage::Chunks {
+            let out = self.out;
+            out.progress(&format!("{} chunk {}/{}", out.dim(&request.input_file), progress.completed, progress.total));
+        }
+    }
+    
+    fn on_fallback(&self, request: &ClipRequest, fallback: &Fallback) {
+        let out = self.out;
+        out.item(Status::Warning, &format!("{}: {}", request.input_file, out.paint(&fallback.to_string(), Color::Yellow)));
+    }
+    
+    fn on_complete(&self, result: &ClipResult) {
+        if !self.single {
+            return;
+        }
+        let out = self.out;
+        out.blank();
+        out.success("✅", "SUCCESS!");
+        out.fact("📁", "Clip saved:", out.highlight(&result.output_file));
+    
+        if let Some(size_mb) = result.file_size_mb {
+            out.fact("📊", "Size:", format!("{:.1} MB", size_mb));
+        }
+        for rendition in result.renditions.iter().skip(1) {
+            out.fact("📁", "Rendition:", format!("{} {}", out.highlight(&rendition.output_file), out.dim(&format!("({})", rendition.name))));
+        }
+    
+        out.fact("⏱️", "Duration:", format!("{:.1}s", result.duration));
+        if let Some(trim) = result.black_trim.filter(|t| t.is_trimmed()) {
+            let (leading, trailing) = trim.removed();
+            out.fact("🎞️", "Trimmed:", format!(
+                "{} to {} (removed {:.1}s leading, {:.1}s trailing black)",
+                TimeParser::format_time_readable(trim.start),
+                TimeParser::format_time_readable(trim.end),
+                leading,
+                trailing
+            ));
+        }
+        if let Some(report) = &result.cut_report {
+            out.fact("🎯", "Cut drift:", out.dim("requested → actual"));
+            let rows = [
+                ("Start:", report.requested_start, report.actual_start, report.start_delta),
+                ("End:", report.requested_end, report.actual_end, report.end_delta),
+            ];
+            for (label, requested, actual, delta) in rows {
+                out.field(&format!("{:<6}", label), format!("{:>10.3}s → {:>10.3}s  ({:+.3}s)", requested, actual, delta));
+            }
+        }
+        if result.encoder != "copy" {
+            out.fact("🎛️", "Encoder:", &result.encoder);
+        }
+        if let Some(scrub) = &result.scrub {
+            let removed = match scrub.removed.is_empty() {
+                true => "none found".to_string(),
+                false => scrub.removed.join(", "),
+            };
+            out.fact("🔒", "Stripped:", removed);
+        }
+        if let Some(quality) = &result.quality {
+            let verdict = if quality.passed { "passed" } else { "below" };
+            out.fact("📊", "VMAF:", format!("{:.1} ({} the minimum of {})", quality.vmaf, verdict, quality.min_vmaf));
+        }
+        for warning in &result.warnings {
+            out.warning(warning);
+        }
+        out.blank();
+        out.success("🎉", "Done! Your clip is ready!");
+    }
+    
+    fn on_error(&self, _request: &ClipRequest, error: &VideoClipError) {
+        if !self.single {
+            return;
+        }
+        self.out.error(&format!("Error: {}", error));
+    }
+}
+
+/// Probes every source, prints the pre-flight report and returns what's left
+/// to run; exits when a plain check finds problems
+#[cfg(feature = "cli")]
+fn run_preflight(out: Presenter, requests: Vec<ClipRequest>, action: PreflightAction, json: bool) -> Result<Vec<ClipRequest>> {
+    let durations = video_clip_rs::preflight::probe_durations(&requests);
+    let report = video_clip_rs::preflight::check_requests(&requests, &durations, action);
+    
+    // Keep stdout for the JSON batch report
+    let out = if json { out.on_stderr() } else { out };
+    for issue in &report.issues {
+        let status = if issue.kind.is_error() { Status::Failed } else { Status::Warning };
+        out.item(status, &issue.describe());
+    }
+    out.note("🛫", &format!(
+        "Pre-flight: {} ranges, {} problems, {} fixed, {} dropped",
+        requests.len(), report.errors(), report.fixed.len(),
+        if action == PreflightAction::Check { 0 } else { report.skipped.len() }
+    ));
+    
+    if !report.is_ok() {
+        out.error("Nothing was clipped; rerun with --preflight fix or --preflight skip to continue");
+        std::process::exit(1);
+    }
+    Ok(report.requests)
+}
+
+/// What `serve` offers beside the clip API
+#[cfg(feature = "server")]
+struct ServeExtras {
+    uploads: Option<UploadStore>,
+    ui: bool,
+    allowed_dirs: Vec<PathBuf>,
+}
+
+#[cfg(feature = "server")]
+fn run_serve(out: Presenter, listen: &str, queue_limits: QueueLimits, config: Config, hooks: Hooks, limits: ProcessLimits, extras: ServeExtras) -> Result<()> {
+    let auth = match config.auth.is_enabled() {
+        true => Some(video_clip_rs::auth::Authenticator::new(config.auth.clone())?),
+        false => None,
+    };
+    let signer = config.auth.url_secret.as_deref().map(video_clip_rs::auth::UrlSigner::new).transpose()?;
+    let mut clipper = VideoClipper::from_config(config);
+    clipper.set_hooks(hooks);
+    clipper.set_process_limits(limits)?;
+    // Callers may otherwise read or overwrite any file the server can reach
+    let mut allowed_dirs = extras.allowed_dirs;
+    if allowed_dirs.is_empty() {
+        std::fs::create_dir_all(clipper.output_dir())?;
+        allowed_dirs.push(clipper.output_dir().to_path_buf());
+        allowed_dirs.extend(extras.uploads.as_ref().map(|uploads| uploads.dir().to_path_buf()));
+    }
+    let allowed = video_clip_rs::AllowedDirs::new(&allowed_dirs)?;
+    let queue = video_clip_rs::JobQueue::with_limits(clipper, queue_limits);
+    
+    out.note("🌐", &format!("{} {}", out.highlight("Serving on"), out.paint(&format!("http://{}", listen), Color::BrightWhite)));
+    let mut endpoints = vec![
+        "POST /clips", "GET /clips", "GET /clips/{id}", "DELETE /clips/{id}", "GET /clips/{id}/file",
+        "GET /schema", "GET /metrics", "GET /openapi.json",
+    ];
+    let mut app = video_clip_rs::server::router(Arc::clone(&queue));
+    if let Some(signer) = signer {
+        endpoints.extend(["GET /clips/{id}/url", "GET /files/{id}"]);
+        app = app.merge(video_clip_rs::server::signed_url_router(queue, Arc::new(signer)));
+    }
+    if let Some(uploads) = extras.uploads {
+        endpoints.extend(["POST /sources", "HEAD|PATCH|DELETE /sources/{id}"]);
+        out.field("Uploads:", format!(
+            "up to {} MB each, removed {}h after they last changed",
+            uploads.max_bytes() / (1024 * 1024),
+            uploads.ttl().as_secs() / 3600,
+        ));
+        app = app.merge(video_clip_rs::server::upload_router(Arc::new(uploads)));
+    }
+    if extras.ui {
+        endpoints.push("GET / (web UI)");
+        app = app.merge(video_clip_rs::server::ui_router());
+    }
+    out.field("Endpoints:", endpoints.join(", "));
+    let dirs: Vec<String> = allowed.dirs().iter().map(|dir| dir.display().to_string()).collect();
+    out.field("Files in:", dirs.join(", "));
+    app = video_clip_rs::server::with_allowed_dirs(app, allowed);
+    match auth {
+        Some(auth) => {
+            out.field("Auth:", "bearer token (API key or JWT)");
+            app = video_clip_rs::server::with_auth(app, Arc::new(auth));
+        }
+        None => out.item(Status::Warning, "No API keys or JWT secret set; anyone who can reach the server can use it"),
+    }
+    
+    tokio::runtime::Runtime::new()?.block_on(video_clip_rs::server::serve(listen, app))
+}
+
+#[cfg(feature = "cli")]
+fn run_animate(out: Presenter, request: ClipRequest, options: AnimatedOptions) -> Result<()> {
+    out.heading("🎞️", "Exporting animation:");
+    out.field("Input:", &request.input_file);
+    out.field("Start:", &request.start_time);
+    out.field("End:", &request.end_time);
+    out.blank();
+    out.heading("⏳", "Processing...");
+    
+    let result = VideoClipper::new().export_animated(&request, &options)?;
+    
+    out.blank();
+    out.success("✅", "SUCCESS!");
+    out.fact("📁", "Animation saved:", out.highlight(&result.output_file));
+    if let Some(size_mb) = result.file_size_mb {
+        out.fact("📊", "Size:", format!("{:.1} MB", size_mb));
+    }
+    out.fact("🎛️", "Encoder:", &result.encoder);
+    
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_remux(out: Presenter, input: &str, container: &str, json: bool, output_dir: Option<String>) -> Result<()> {
+    if !json {
+        out.heading("📦", "Remuxing:");
+        out.field("Input:", input);
+        out.field("Container:", container);
+        out.blank();
+        out.heading("⏳", "Processing...");
+    }
+    
+    let clipper = output_dir.map_or_else(VideoClipper::new, VideoClipper::with_output_dir);
+    let report = clipper.remux(Path::new(input), container)?;
+    
+    if json {
+        out.data(&serde_json::to_string_pretty(&report).unwrap());
+        return Ok(());
+    }
+    out.blank();
+    out.success("✅", "SUCCESS!");
+    out.fact("📁", "Remux saved:", out.highlight(&report.output_file));
+    out.fact("🔍", "Verified:", format!(
+        "{} streams, {}",
+        report.output_streams,
+        report.output_duration.map_or("unknown duration".to_string(), TimeParser::format_time_readable)
+    ));
+    
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_compare(out: Presenter, a: &str, b: &str, metrics: &[QualityMetric], json: bool) -> Result<()> {
+    if !json {
+        out.heading("⚖️", "Comparing:");
+        out.field("A:", a);
+        out.field("B:", b);
+        out.blank();
+    }
+    
+    let report = VideoClipper::new().compare(Path::new(a), Path::new(b), metrics)?;
+    
+    if json {
+        out.data(&serde_json::to_string_pretty(&report).unwrap());
+        return Ok(());
+    }
+    if let Some(delta) = report.duration_delta {
+        out.fact("⏱️", "Duration:", format!("{:+.3}s", delta));
+    }
+    if report.differences.is_empty() {
+        out.fact("🔍", "Streams:", "same streams and formats");
+    }
+    for difference in &report.differences {
+        out.fact("🔍", "Differs:", difference);
+    }
+    if let Some(quality) = &report.quality {
+        if let Some(psnr) = quality.psnr {
+            out.fact("📊", "PSNR:", format!("{:.2} dB", psnr));
+        }
+        if let Some(ssim) = quality.ssim {
+            out.fact("📊", "SSIM:", format!("{:.4}", ssim));
+        }
+        if let Some(vmaf) = quality.vmaf {
+            out.fact("📊", "VMAF:", format!("{:.2}", vmaf));
+        }
+    }
+    
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_segment(out: Presenter, input: &str, seconds: f64, json: bool, output_dir: Option<String>) -> Result<()> {
+    if !json {
+        out.heading("🔪", "Splitting:");
+        out.field("Input:", input);
+        out.field("Every:", TimeParser::format_time_readable(seconds));
+        out.blank();
+        out.heading("⏳", "Processing...");
+    }
+    
+    let clipper = output_dir.map_or_else(VideoClipper::new, VideoClipper::with_output_dir);
+    let parts = clipper.split_by_duration(Path::new(input), seconds)?;
+    
+    if json {
+        out.data(&serde_json::to_string_pretty(&parts).unwrap());
+        return Ok(());
+    }
+    out.blank();
+    out.success("✅", &format!("{} parts:", parts.len()));
+    for part in &parts {
+        out.line(&format!(
+            "  {}  ({} to {})",
+            out.highlight(&part.path.display().to_string()),
+            TimeParser::format_time_readable(part.start),
+            TimeParser::format_time_readable(part.end)
+        ));
+    }
+    
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn print_doctor_report(out: Presenter, report: &DoctorReport) {
+    out.heading("🩺", "Environment check:");
+    for check in &report.checks {
+        let status = match check.status {
+            CheckStatus::Pass => out.paint("PASS", Color::BrightGreen),
+            CheckStatus::Warn => out.paint("WARN", Color::BrightYellow),
+            CheckStatus::Fail => out.paint("FAIL", Color::BrightRed),
+        };
+        let name = format!("{:<11}", check.name);
+        out.line(&format!("   [{}] {} {} {}", status, out.paint(&name, Color::BrightWhite), check.detail, out.dim(&format!("({} ms)", check.elapsed_ms))));
+    }
+    out.blank();
+    
+    if report.is_healthy() {
+        out.success("✅", "Everything looks good!");
+    } else {
+        out.error("Some checks failed");
+    }
+}
+
+#[cfg(feature = "cli")]
+fn run_highlights(out: Presenter, input: &str, range: Option<TimeRange>, options: HighlightOptions, json: bool) -> Result<()> {
+    if !json {
+        out.heading("📣", "Listening for highlights:");
+        out.field("Input:", input);
+        out.blank();
+        out.heading("⏳", "Processing...");
+    }
+    
+    let highlights = video_clip_rs::highlights::detect_highlights(input, range, &options)?;
+    
+    if json {
+        out.data(&serde_json::to_string_pretty(&highlights).unwrap());
+        return Ok(());
+    }
+    out.blank();
+    if highlights.is_empty() {
+        out.note("ℹ️", "No loudness spikes found; try a lower --threshold");
+        return Ok(());
+    }
+    out.success("✅", &format!("{} suggested highlights:", highlights.len()));
+    for (rank, highlight) in highlights.iter().enumerate() {
+        out.line(&format!(
+            "  {:>2}. {} to {}  (+{:.1} LU at {})",
+            rank + 1,
+            out.highlight(&TimeParser::format_time_readable(highlight.range.start)),
+            out.highlight(&TimeParser::format_time_readable(highlight.range.end)),
+            highlight.score,
+            TimeParser::format_time_readable(highlight.peak)
+        ));
+    }
+    
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_schema(out: Presenter) -> Result<()> {
+    out.data(&serde_json::to_string_pretty(&ClipRequest::json_schema()).unwrap());
     Ok(())
 }
 
+#[cfg(feature = "cli")]
+fn run_mcp(out: Presenter, config: Config, limits: ProcessLimits, allowed_dirs: &[PathBuf], list_tools: bool) -> Result<()> {
+    if list_tools {
+        out.data(&serde_json::to_string_pretty(&video_clip_rs::mcp::tools()).unwrap());
+        return Ok(());
+    }
+    let mut clipper = VideoClipper::from_config(config);
+    clipper.set_process_limits(limits)?;
+    if allowed_dirs.is_empty() {
+        log::warn!("No --allow-dir given; agents may name any file this process can read or write");
+    }
+    // Stdout carries the protocol, so nothing else may be printed there
+    let server = video_clip_rs::mcp::McpServer::new(clipper, allowed_dirs)?;
+    server.serve(std::io::stdin().lock(), std::io::stdout().lock())
+}
+
+#[cfg(feature = "cli")]
+fn run_cache(out: Presenter, clear: bool, invalidate: Vec<String>) -> Result<()> {
+    let Some(cache) = ProbeCache::default_location() else {
+        out.note("ℹ️", "No cache directory on this platform");
+        return Ok(());
+    };
+    
+    if clear {
+        let removed = cache.clear()?;
+        out.note("🧹", &format!("Removed {} cached probe results", removed));
+    } else if !invalidate.is_empty() {
+        for file in &invalidate {
+            let removed = cache.invalidate(file)?;
+            out.note("🧹", &format!("{}: removed {} cached results", file, removed));
+        }
+    } else {
+        out.fact("📂", "Cache:", cache.dir().display());
+        out.fact("📊", "Entries:", cache.len());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_analyze(out: Presenter, input: &str, stages: &[Stage], range: Option<(f64, f64)>, rerun: bool, json: bool) -> Result<()> {
+    if !json {
+        out.heading("🔬", "Analyzing:");
+        out.field("Input:", input);
+        out.blank();
+    }
+    
+    let mut pipeline = AnalysisPipeline::new(input);
+    pipeline.set_rerun(rerun);
+    pipeline.run(stages)?;
+    let range = range.map(|(start, end)| pipeline.range(start, end)).transpose()?;
+    
+    if json {
+        let value = match &range {
+            Some(range) => serde_json::to_value(range).unwrap(),
+            None => serde_json::json!({ "stages": stages, "ran": pipeline.ran() }),
+        };
+        out.data(&serde_json::to_string_pretty(&value).unwrap());
+        return Ok(());
+    }
+    for stage in stages.iter().chain(pipeline.ran()).collect::<std::collections::BTreeSet<_>>() {
+        let how = if pipeline.ran().contains(stage) { "ran" } else { "saved result" };
+        out.fact("🧩", &format!("{}:", stage.name()), how);
+    }
+    if let Some(dir) = pipeline.store().map(|store| store.dir().display().to_string()) {
+        out.fact("📂", "Stages:", dir);
+    }
+    let Some(range) = range else {
+        return Ok(());
+    };
+    out.blank();
+    out.heading("🗺️", "Range:");
+    if let Some(chapter) = &range.chapter {
+        out.field("Chapter:", chapter);
+    }
+    if let Some(keyframe) = range.estimate.keyframe_start {
+        out.field("Copy starts:", format!("{:.3}s (keyframe)", keyframe));
+    }
+    out.field("Scene changes:", range.scenes.len());
+    out.field("Silences:", range.silences.len());
+    if let Some(lufs) = range.loudness_lufs {
+        out.field("Loudness:", format!("{:.1} LUFS", lufs));
+    }
+    Ok(())
+}
+
+/// Prints the plan of a clip as JSON, for run-plan to execute
+#[cfg(feature = "cli")]
+fn run_print_plan(out: Presenter, request: &ClipRequest, config: Config, limits: ProcessLimits) -> Result<()> {
+    let mut clipper = VideoClipper::from_config(config);
+    clipper.set_process_limits(limits)?;
+    let plan = clipper.plan_clip(request)?;
+    out.data(&serde_json::to_string_pretty(&plan).unwrap());
+    Ok(())
+}
+
+/// Prints what clipping would do, and why, without doing it
+#[cfg(feature = "cli")]
+fn run_explain(out: Presenter, request: &ClipRequest, config: Config) -> Result<()> {
+    let plan = VideoClipper::from_config(config).plan_clip(request)?;
+    let explanation = plan.explain();
+    out.heading("🧭", "Plan:");
+    out.field("Video:", &explanation.video);
+    out.field("Audio:", &explanation.audio);
+    out.field("Output:", format!("{} at {}", explanation.output, plan.output_file));
+    for note in &explanation.notes {
+        out.warning(note);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_plan(out: Presenter, source: &str, json: bool, limits: ProcessLimits) -> Result<()> {
+    let text = match source {
+        "-" => std::io::read_to_string(std::io::stdin())?,
+        path => std::fs::read_to_string(path)?,
+    };
+    let plan: ClipPlan = serde_json::from_str(&text)
+        .map_err(|e| VideoClipError::InvalidOptions(format!("invalid plan {}: {}", source, e)))?;
+    
+    let mut clipper = VideoClipper::new();
+    clipper.set_process_limits(limits)?;
+    if !json {
+        out.heading("✂️", "Running plan:");
+        out.field("Input:", &plan.input_file);
+        out.field("Commands:", plan.commands.len());
+        out.field("Plan:", plan.explain().summary);
+    }
+    let result = clipper.execute_plan(&plan)?;
+    
+    if json {
+        out.data(&serde_json::to_string_pretty(&result).unwrap());
+    } else {
+        CliEvents { out, single: true }.on_complete(&result);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_index(out: Presenter, input: &str, rebuild: bool, json: bool) -> Result<()> {
+    if !json {
+        out.heading("🗂️", "Indexing:");
+        out.field("Input:", input);
+        out.blank();
+    }
+    
+    let (index, cached) = MediaIndex::load_or_build(Path::new(input), rebuild)?;
+    
+    if json {
+        out.data(&serde_json::to_string_pretty(&index).unwrap());
+        return Ok(());
+    }
+    out.fact("📦", "Index:", if cached { "current, loaded from disk" } else { "built" });
+    out.fact("🔑", "Keyframes:", index.keyframes.len());
+    out.fact("📑", "Chapters:", index.chapters.len());
+    out.fact("🔇", "Silences:", index.silences.len());
+    out.fact("🎬", "Scene changes:", index.scenes.len());
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn run_doctor(out: Presenter, json: bool, output_dir: Option<String>) -> Result<()> {
+    let output_dir = output_dir.unwrap_or_else(|| "downloads".to_string());
+    let report = video_clip_rs::doctor::run_doctor(std::path::Path::new(&output_dir));
+    
+    if json {
+        out.data(&serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        print_doctor_report(out, &report);
+    }
+    
+    if !report.is_healthy() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+fn main() -> Result<()> {
+    env_logger::init();
+    
+    let args = Args::parse();
+    let out = Presenter::from_flags(args.quiet, args.plain, args.no_color);
+    
+    video_clip_rs::probe_cache::set_enabled(!args.no_cache);
+    // Flags beat the config file, which beats VIDEO_CLIP_* variables
+    let loaded = match &args.config {
+        Some(path) => Config::from_file(path),
+        None => Config::load(),
+    }.map(|file| file.or(Config::from_env()))
+        .and_then(|config| config.validate().map(|_| config));
+    let (mut config, config_error) = match loaded {
+        Ok(config) => (config, None),
+        Err(e) => (Config::from_env(), Some(e)),
+    };
+    if let Some(image) = &args.container_image {
+        config.container = Some(ContainerExecutor::new(args.container_runtime.parse()?, image));
+        config.ssh = None;
+    }
+    if let Some(host) = &args.ssh {
+        config.ssh = Some(SshExecutor { fetch: args.ssh_fetch, ..SshExecutor::parse(host)? });
+        config.container = None;
+    }
+    if let Some(path) = &config.ffmpeg_path {
+        video_clip_rs::tools::set_ffmpeg_path(Some(path.clone()));
+    }
+    video_clip_rs::tools::set_executor(config.executor());
+    if let Some(model) = args.whisper_model.clone() {
+        config.whisper_model = Some(model);
+    }
+    
+    // Keep JSON output and dry-run commands machine-readable
+    let machine_readable = args.dry_run.dry_run || args.plan || matches!(
+        args.command,
+        Some(Commands::Doctor { json: true, .. }) | Some(Commands::Batch { json: true, .. }) | Some(Commands::Transcript { json: true, .. }) | Some(Commands::Highlights { json: true, .. }) | Some(Commands::Remux { json: true, .. }) | Some(Commands::Compare { json: true, .. }) | Some(Commands::Segment { json: true, .. }) | Some(Commands::Index { json: true, .. }) | Some(Commands::Analyze { json: true, .. }) | Some(Commands::RunPlan { json: true, .. }) | Some(Commands::Batch { dry_run: DryRunArgs { dry_run: true, .. }, .. }) | Some(Commands::Schema) | Some(Commands::Mcp { .. })
+    );
+    if !machine_readable {
+        out.banner();
+    }
+    
+    // A broken config file only stops the commands that read it (presets,
+    // auth, workers, the whisper model); the rest run without it
+    if let Some(e) = config_error {
+        let reads_config = !matches!(
+            args.command,
+            Some(Commands::Capture { .. } | Commands::Frames { .. } | Commands::Storyboard { .. } | Commands::Highlights { .. } | Commands::Animate { .. } | Commands::Remux { .. } | Commands::Compare { .. } | Commands::Segment { .. } | Commands::Doctor { .. } | Commands::Cache { .. } | Commands::Analyze { .. } | Commands::Index { .. } | Commands::RunPlan { .. } | Commands::Schema)
+        );
+        if reads_config {
+            return Err(e);
+        }
+        if !machine_readable {
+            out.warning(&format!("Ignoring the config file: {}", e));
+        }
+    }
+    
+    if let Some(command) = args.command {
+        let outcome = match command {
+            Commands::Capture { duration, region, framerate, display, no_cursor, output_dir } => {
+                run_capture(out, &duration, region.as_deref(), framerate, display, no_cursor, output_dir)
+            }
+            Commands::Frames { input, start, end, format, fps, width, height, quality, output_dir } => {
+                let request = ClipRequest {
+                    input_file: InputPath::normalize(&input),
+                    start_time: start,
+                    end_time: end,
+                    output_dir,
+                    ..Default::default()
+                };
+                let options = FrameExportOptions {
+                    format: if format == "png" { ImageFormat::Png } else { ImageFormat::Jpeg },
+                    fps,
+                    width,
+                    height,
+                    jpeg_quality: quality,
+                };
+                run_frames(out, request, options)
+            }
+            Commands::Storyboard { input, start, end, columns, rows, tile_width, no_timestamps, output_dir } => {
+                let request = ClipRequest {
+                    input_file: InputPath::normalize(&input),
+                    start_time: start,
+                    end_time: end,
+                    output_dir,
+                    ..Default::default()
+                };
+                let options = StoryboardOptions {
+                    columns,
+                    rows,
+                    tile_width,
+                    show_timestamps: !no_timestamps,
+                    ..Default::default()
+                };
+                run_storyboard(out, request, options)
+            }
+            Commands::Batch { patterns, start, end, manifest, input_dir, align, extensions, recursive, columns, mirror, priority, preset, json, workers, report, overwrite, preflight, pre_roll, post_roll, split, dry_run, hooks, limits, output_dir } => {
+                let template = ClipRequest {
+                    start_time: start.unwrap_or_default(),
+                    end_time: end.unwrap_or_default(),
+                    output_dir,
+                    priority,
+                    preset_name: preset,
+                    split: split.into_split()?,
+                    pre_roll: pre_roll.unwrap_or_default(),
+                    post_roll: post_roll.unwrap_or_default(),
+                    set_fields: given_fields(&[("pre_roll", pre_roll.is_some()), ("post_roll", post_roll.is_some())]),
+                    ..Default::default()
+                };
+                let requests = match (manifest, input_dir) {
+                    (Some(path), _) => columns.as_deref().map(str::parse::<CsvColumns>).transpose()
+                        .and_then(|columns| BatchManifest::from_path(&path, columns.as_ref()))
+                        .and_then(|mut m| {
+                            m.mirror_dirs |= mirror;
+                            m.requests(&template)
+                        }),
+                    (None, Some(dir)) => {
+                        // Explicit extensions are taken as the whole list
+                        let options = match extensions.is_empty() {
+                            true => DirectoryOptions { recursive, ..Default::default() },
+                            false => DirectoryOptions { extensions, recursive, sniff: false },
+                        };
+                        template.time_range().and_then(|range| {
+                            video_clip_rs::batch::requests_from_directory(InputPath::normalize(&dir), &template, &options, mirror, |_| Some(range))
+                        })
+                    }
+                    (None, None) if !align.is_empty() => align.iter()
+                        .map(|spec| {
+                            let input: AlignedInput = spec.parse()?;
+                            Ok(AlignedInput { input_file: InputPath::normalize(&input.input_file), ..input })
+                        })
+                        .collect::<Result<Vec<_>>>()
+                        .and_then(|inputs| video_clip_rs::multicam::aligned_requests(&inputs, &template)),
+                    (None, None) => video_clip_rs::batch::requests_from_patterns(&patterns, &template, mirror),
+                };
+                requests
+                    .and_then(expand_splits)
+                    .and_then(|requests| match preflight {
+                        Some(action) => run_preflight(out, requests, action, json),
+                        None => Ok(requests),
+                    })
+                    .and_then(|requests| match dry_run.dry_run {
+                        true => run_dry_run(out, &requests, config, json, dry_run.copy_command),
+                        false => {
+                            let workers = workers.iter().map(|spec| spec.parse()).collect::<Result<Vec<Worker>>>()?;
+                            let config = match workers.is_empty() {
+                                true => config,
+                                false => Config { workers, ..config },
+                            };
+                            run_batch(out, requests, config, hooks.into_hooks()?, limits.into_limits(), BatchRun { json, report, overwrite })
+                        }
+                    })
+            }
+            Commands::Highlights { input, start, end, threshold, max, pre_roll, post_roll, exclude_static, min_motion, json } => {
+                let range = match (start, end) {
+                    (Some(start), Some(end)) => Some(TimeRange::parse(&start, &end)?),
+                    _ => None,
+                };
+                let min_motion = exclude_static.then(|| min_motion.unwrap_or(video_clip_rs::motion::DEFAULT_MIN_MOTION));
+                let options = HighlightOptions { threshold, max_suggestions: max, pre_roll, post_roll, min_motion, ..Default::default() };
+                run_highlights(out, &InputPath::normalize(&input), range, options, json)
+            }
+            Commands::Transcript { input, transcript, say, cues, retime, pre_roll, post_roll, json, hooks, limits, output_dir } => {
+                let transcript_path = transcript.as_deref().map(InputPath::normalize);
+                let template = ClipRequest {
+                    input_file: InputPath::normalize(&input),
+                    output_dir,
+                    pre_roll,
+                    post_roll,
+                    subtitles: transcript_path.clone().filter(|_| retime),
+                    captions: retime && transcript_path.is_none(),
+                    ..Default::default()
+                };
+                let query = match (say, cues) {
+                    (Some(text), _) => Ok(TranscriptQuery::Text(text)),
+                    (None, cues) => TranscriptQuery::parse_cues(&cues.unwrap_or_default()),
+                };
+                let provider: Result<Arc<dyn TranscriptProvider>> = match transcript_path {
+                    Some(path) => Ok(Arc::new(SubtitleFile { path: path.into() })),
+                    None => config.transcript_provider().and_then(|provider| provider.ok_or_else(|| VideoClipError::InvalidOptions(
+                        "give a --transcript file, or a --whisper-model to transcribe the video".to_string()
+                    ))),
+                };
+                query
+                    .and_then(|query| {
+                        let transcript = provider?.transcribe(Path::new(&template.input_file), None)?;
+                        video_clip_rs::transcript::requests_for(&transcript, &query, &template)
+                    })
+                    .and_then(|requests| run_batch(out, requests, config, hooks.into_hooks()?, limits.into_limits(), BatchRun { json, ..Default::default() }))
+            }
+            #[cfg(feature = "server")]
+            Commands::Serve { listen, workers, max_per_source, api_keys, jwt_secret, rate_limit, url_secret, upload_dir, no_ui, max_upload_mb, upload_ttl_hours, allowed_dirs, hooks, limits, output_dir } => {
+                let queue_limits = QueueLimits { max_concurrent: workers, max_per_source, ..Default::default() };
+                let mut config = config;
+                config.auth.keys.extend(api_keys.into_iter().enumerate().map(|(i, key)| ApiKey {
+                    name: format!("cli-{}", i + 1),
+                    key,
+                    rate_limit: None,
+                    high_priority: false,
+                }));
+                config.auth = AuthConfig { jwt_secret, rate_limit, url_secret, ..Default::default() }.or(config.auth);
+                config.output_dir = output_dir.map(Into::into).or(config.output_dir);
+                let uploads = upload_dir.map(|dir| UploadStore::new(dir, max_upload_mb * 1024 * 1024)).transpose()?.map(|mut uploads| {
+                    uploads.set_ttl(std::time::Duration::from_secs(upload_ttl_hours * 3600));
+                    uploads
+                });
+                let extras = ServeExtras { uploads, ui: !no_ui, allowed_dirs };
+                run_serve(out, &listen, queue_limits, config, hooks.into_hooks()?, limits.into_limits(), extras)
+            }
+            Commands::Animate { input, start, end, format, fps, width, quality, loop_count, output_dir } => {
+                let request = ClipRequest {
+                    input_file: InputPath::normalize(&input),
+                    start_time: start,
+                    end_time: end,
+                    output_dir,
+                    ..Default::default()
+                };
+                let options = AnimatedOptions {
+                    format: format.parse()?,
+                    fps,
+                    width,
+                    quality,
+                    loop_count,
+                };
+                run_animate(out, request, options)
+            }
+            Commands::Remux { input, container, json, output_dir } => {
+                run_remux(out, &InputPath::normalize(&input), &container, json, output_dir)
+            }
+            Commands::Compare { a, b, metrics, json } => {
+                let metrics = metrics.iter().map(|m| m.parse()).collect::<Result<Vec<QualityMetric>>>()?;
+                run_compare(out, &InputPath::normalize(&a), &InputPath::normalize(&b), &metrics, json)
+            }
+            Commands::Segment { input, every, json, output_dir } => {
+                run_segment(out, &InputPath::normalize(&input), TimeParser::parse_to_seconds(&every)?, json, output_dir)
+            }
+            Commands::Doctor { json, output_dir } => run_doctor(out, json, output_dir),
+            Commands::Cache { clear, invalidate } => run_cache(out, clear, invalidate),
+            Commands::Analyze { input, stages, start, end, rerun, json } => {
+                let stages = match stages.is_empty() {
+                    true => Stage::ALL.to_vec(),
+                    false => stages.iter().map(|s| s.parse()).collect::<Result<Vec<Stage>>>()?,
+                };
+                let range = match (start, end) {
+                    (Some(start), Some(end)) => Some((TimeParser::parse_to_seconds(&start)?, TimeParser::parse_to_seconds(&end)?)),
+                    _ => None,
+                };
+                run_analyze(out, &InputPath::normalize(&input), &stages, range, rerun, json)
+            }
+            Commands::Index { input, rebuild, json } => run_index(out, &InputPath::normalize(&input), rebuild, json),
+            Commands::RunPlan { plan, json, limits } => run_plan(out, &plan, json, limits.into_limits()),
+            Commands::Schema => run_schema(out),
+            Commands::Mcp { allowed_dirs, list_tools, limits, output_dir } => {
+                let mut config = config;
+                config.output_dir = output_dir.map(Into::into).or(config.output_dir);
+                run_mcp(out, config, limits.into_limits(), &allowed_dirs, list_tools)
+            }
+            Commands::Redo { last: _, entry, list, tweaks, hooks, limits } => {
+                let history = load_history(args.no_history);
+                if list {
+                    print_history(out, &history);
+                    Ok(())
+                } else {
+                    let entry = entry.unwrap_or(1);
+                    history.nth(entry).cloned()
+                        .ok_or_else(|| VideoClipError::InvalidOptions(format!("no clip #{} in the history ({} remembered)", entry, history.entries.len())))
+                        .and_then(|request| tweaks.apply(request))
+                        .and_then(|request| run_clip(out, request, config, hooks.into_hooks()?, limits.into_limits(), !args.no_history))
+                }
+            }
+        };
+        
+        if let Err(e) = outcome {
+            out.error(&format!("Error: {}", e));
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    
+    let sampling = args.sample_every.is_some();
+    let interactive = args.input.is_none() || (!sampling && args.range.is_none() && (args.start.is_none() || args.end.is_none()));
+    let history = if interactive { load_history(args.no_history) } else { History::default() };
+    
+    // Get input file
+    let input_file = match args.input {
+        Some(f) => InputPath::normalize(&f),
+        None => {
+            let recent = owned(history.recent_inputs(QUICK_PICKS));
+            out.prompt("Enter path to your video file:");
+            out.prompt("Note: The file must be accessible from this environment");
+            if !recent.is_empty() {
+                out.prompt("Or pick a recent one:");
+                out.choices(&recent);
+            }
+            out.blank();
+            ask_input_file(out, &recent).unwrap_or_default()
+        }
+    };
+    
+    if input_file.is_empty() {
+        out.error("No file path provided!");
+        std::process::exit(1);
+    }
+    
+    // A range in words is read against the source's duration
+    let (start, end) = match &args.range {
+        Some(phrase) => {
+            let request = video_clip_rs::phrase::request_from_phrase(&input_file, phrase)?;
+            (Some(request.start_time), Some(request.end_time))
+        }
+        None => (args.start, args.end),
+    };
+    
+    // Ask for whichever times weren't given
+    let (start_time, end_time) = match (start, end) {
+        (Some(start), Some(end)) => (start, end),
+        // Samples span the whole file
+        (None, None) if sampling => (String::new(), String::new()),
+        (start, end) => match ask_range(out, &input_file, start, end, &history.recent_ranges(&input_file, QUICK_PICKS)) {
+            Some(range) => range,
+            None => {
+                out.error("No time range provided!");
+                std::process::exit(1);
+            }
+        },
+    };
+    
+    let output_dir = match args.output_dir {
+        Some(dir) => Some(dir),
+        None if interactive => ask_output_dir(out, &owned(history.recent_output_dirs(QUICK_PICKS))),
+        None => None,
+    };
+    
+    let set_fields = given_fields(&[
+        ("video_codec", args.codec.is_some()),
+        ("playback", args.playback.is_some()),
+        ("pre_roll", args.pre_roll.is_some()),
+        ("post_roll", args.post_roll.is_some()),
+    ]);
+    
+    // Create clip request
+    let request = ClipRequest {
+        input_file: input_file.clone(),
+        start_time,
+        end_time,
+        wall_clock: args.wall_clock,
+        recording_start: args.recording_start,
+        output_dir,
+        video_codec: args.codec.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+        encoder: args.encoder,
+        container: args.container,
+        force_keyframes: args.force_keyframes.as_deref().map(str::parse).transpose()?,
+        deinterlace: args.deinterlace,
+        target_fps: args.fps,
+        constant_frame_rate: args.cfr,
+        tonemap: args.tonemap,
+        fit: match &args.size {
+            Some(size) => {
+                let (width, height) = FitOptions::parse_size(size)?;
+                Some(FitOptions {
+                    width,
+                    height,
+                    mode: args.fit.parse()?,
+                    background: args.background.parse()?,
+                })
+            }
+            None => None,
+        },
+        volume_db: args.volume,
+        overlay_audio: args.overlay_audio.map(|path| OverlayAudio {
+            path,
+            volume_db: args.overlay_volume,
+            duck: args.duck,
+            loop_to_fit: args.loop_overlay,
+            fade_out: args.overlay_fade_out,
+        }),
+        intro: args.intro,
+        outro: args.outro,
+        end_card: args.end_card.map(|image| EndCard {
+            image,
+            seconds: args.end_card_seconds,
+            music: args.end_card_music,
+        }),
+        redact: args.redact.iter()
+            .map(|region| {
+                let mut region: RedactRegion = region.parse()?;
+                region.style = args.redact_style.parse()?;
+                Ok(region)
+            })
+            .collect::<Result<_>>()?,
+        redact_audio: args.mute.iter().map(|i| (i, AudioRedactStyle::Mute))
+            .chain(args.bleep.iter().map(|i| (i, AudioRedactStyle::Bleep)))
+            .map(|(interval, style)| Ok(AudioRedaction { style, ..interval.parse()? }))
+            .collect::<Result<_>>()?,
+        attribution: match args.attribution {
+            Some(text) => Some(Attribution {
+                text,
+                corner: args.attribution_corner.parse()?,
+                size: args.attribution_size,
+                start: args.attribution_from.as_deref().map(TimeParser::parse_to_seconds).transpose()?,
+                end: args.attribution_until.as_deref().map(TimeParser::parse_to_seconds).transpose()?,
+            }),
+            None => None,
+        },
+        streams: args.streams.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+        tracks: args.tracks.iter().map(|track| track.parse()).collect::<Result<_>>()?,
+        playback: args.playback.as_deref().map(str::parse).transpose()?.unwrap_or_default(),
+        loop_count: args.loop_count,
+        auto_trim_black: args.trim_black,
+        verify_cut: args.verify_cut,
+        quality_gate: args.min_vmaf.map(|min_vmaf| QualityGate {
+            min_vmaf,
+            action: if args.vmaf_warn_only { GateAction::Warn } else { GateAction::Fail },
+            retries: args.vmaf_retries,
+        }),
+        smart_cut: args.smart_cut,
+        recover: args.recover,
+        mirror_root: None,
+        priority: Priority::default(),
+        chunking: args.chunk_minutes.map(|minutes| ChunkOptions { minutes, parallel: args.chunk_parallel }),
+        preset_name: args.preset,
+        renditions: args.renditions.iter().map(|spec| spec.parse()).collect::<Result<_>>()?,
+        proxy: args.proxy.then_some(ProxyOptions { height: args.proxy_height }),
+        output_name: None,
+        split: args.split.into_split()?,
+        pre_roll: args.pre_roll.unwrap_or_default(),
+        post_roll: args.post_roll.unwrap_or_default(),
+        subtitles: args.subtitles.as_deref().map(InputPath::normalize),
+        captions: args.captions,
+        metadata: Some(ClipMetadata {
+            title: args.title,
+            description: args.description,
+            tags: args.tags,
+        }).filter(|m| !m.is_empty()),
+        strip_metadata: args.strip_metadata,
+        extra_input_args: args.ffmpeg_input_args.as_deref().map(split_raw_args).unwrap_or_default(),
+        extra_output_args: args.ffmpeg_output_args.as_deref().map(split_raw_args).unwrap_or_default(),
+
+        set_fields,
+        schema_version: None,
+    };
+    
+    if let (Some(every), Some(length)) = (&args.sample_every, &args.sample_length) {
+        let requests = SampleOptions::parse(every, length)
+            .and_then(|options| video_clip_rs::sampling::sample_requests(&request, &options))?;
+        return match args.dry_run.dry_run {
+            true => run_dry_run(out, &requests, config, false, args.dry_run.copy_command),
+            false => run_batch(out, requests, config, args.hooks.into_hooks()?, args.limits.into_limits(), BatchRun::default()),
+        };
+    }
+    
+    if args.explain {
+        if let Err(e) = run_explain(out, &request, config) {
+            out.error(&format!("Error: {}", e));
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.plan {
+        if let Err(e) = run_print_plan(out, &request, config, args.limits.into_limits()) {
+            out.error(&format!("Error: {}", e));
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.dry_run.dry_run {
+        if let Err(e) = run_dry_run(out, &[request], config, false, args.dry_run.copy_command) {
+            out.error(&format!("Error: {}", e));
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(rungs) = &args.ladder {
+        let ladder = match rungs.is_empty() {
+            true => Ladder::default(),
+            false => rungs.parse()?,
+        };
+        return run_ladder(out, request, &ladder, config, args.limits.into_limits());
+    }
+    run_clip(out, request, config, args.hooks.into_hooks()?, args.limits.into_limits(), !args.no_history)
+}
+
 #[cfg(not(feature = "cli"))]
 fn main() {
     eprintln!("This binary requires the 'cli' feature to be enabled.");
@@ -0,0 +1,235 @@
+use crate::allowed_dirs::AllowedDirs;
+use crate::error::{ErrorInfo, VideoClipError, Result};
+use crate::estimate::ClipEstimate;
+use crate::video_clipper::{ClipRequest, ClipResult, VideoClipper};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+/// Tool interface for AI agents
+/// An MCP (Model Context Protocol) server over stdio: newline-delimited
+/// JSON-RPC 2.0 in, one response per request out. It offers the clipper as
+/// tools whose input schema is the clip request's own, and whose results are
+/// returned as structured content matching the output schema. Arguments are
+/// parsed strictly, so a misspelt option is an error the agent can correct
+/// rather than something silently dropped, and every problem a request has
+/// is reported at once.
+///
+/// Agents shouldn't reach every file the process can, so requests can be
+/// held to [`AllowedDirs`]. [`tools`] gives the same definitions for agent
+/// frameworks that call functions without MCP.
+
+#[derive(Debug)]
+pub struct McpServer {
+    clipper: VideoClipper,
+    allowed: AllowedDirs,
+}
+
+/// The MCP revision spoken, the first with tool output schemas
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// A tool, as `tools/list` describes it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tool {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+    pub output_schema: Value,
+}
+
+/// The tools the server offers
+pub fn tools() -> Vec<Tool> {
+    vec![
+        Tool {
+            name: "clip_video".to_string(),
+            description: "Cut the range from start_time to end_time out of a video file into a new file, optionally re-encoded, \
+                resized, captioned and so on. Times are seconds or [HH:]MM:SS[.mmm]. Returns where the clip was written."
+                .to_string(),
+            input_schema: ClipRequest::json_schema(),
+            output_schema: schemars::schema_for!(ClipResult).to_value(),
+        },
+        Tool {
+            name: "estimate_clip".to_string(),
+            description: "Estimate a clip without making it: its size, whether the streams can be copied as they are, \
+                the keyframe a copied clip starts from and how long it would take. Takes the same arguments as clip_video."
+                .to_string(),
+            input_schema: ClipRequest::json_schema(),
+            output_schema: schemars::schema_for!(ClipEstimate).to_value(),
+        },
+    ]
+}
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+impl McpServer {
+    /// Serves `clipper`, limiting requests to files under `roots` unless
+    /// that's empty
+    pub fn new(clipper: VideoClipper, roots: &[PathBuf]) -> Result<Self> {
+        Ok(Self { clipper, allowed: AllowedDirs::new(roots)? })
+    }
+
+    /// Answers messages from `input` on `output` until `input` ends
+    pub fn serve(&self, input: impl BufRead, mut output: impl Write) -> Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle(&message),
+                Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+            };
+            if let Some(response) = response {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The response to one JSON-RPC message; `None` for notifications
+    pub fn handle(&self, message: &Value) -> Option<Value> {
+        let method = message["method"].as_str();
+        let Some(id) = message.get("id").cloned() else {
+            // Notifications (`notifications/initialized`, cancellations) need no answer
+            return None;
+        };
+        let params = &message["params"];
+        let result = match method {
+            Some("initialize") => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": { "listChanged": false } },
+                "serverInfo": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+            })),
+            Some("ping") => Ok(json!({})),
+            Some("tools/list") => Ok(json!({ "tools": tools() })),
+            Some("tools/call") => self.call_tool(params),
+            Some(method) => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+            None => Err((INVALID_REQUEST, "a request needs a method".to_string())),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    /// A tool's outcome; failures the agent can act on are results flagged
+    /// `isError`, not protocol errors
+    fn call_tool(&self, params: &Value) -> std::result::Result<Value, (i64, String)> {
+        let name = params["name"].as_str().ok_or((INVALID_PARAMS, "tools/call needs a tool name".to_string()))?;
+        if !tools().iter().any(|tool| tool.name == name) {
+            return Err((INVALID_PARAMS, format!("unknown tool '{}'", name)));
+        }
+        let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        let outcome = self.request(arguments).and_then(|request| match name {
+            "clip_video" => self.clipper.clip_video(&request).map(|result| serde_json::to_value(result).unwrap_or_default()),
+            _ => self.clipper.estimate_clip(&request).map(|estimate| serde_json::to_value(estimate).unwrap_or_default()),
+        });
+        Ok(match outcome {
+            Ok(structured) => tool_result(structured, false),
+            Err(e) => tool_result(serde_json::to_value(ErrorInfo::from(&e)).unwrap_or_default(), true),
+        })
+    }
+
+    /// The arguments as a request, once they're known to be valid and safe
+    fn request(&self, arguments: Value) -> Result<ClipRequest> {
+        let request = ClipRequest::from_value_strict(arguments)?.with_normalized_paths();
+        if let Err(problems) = request.validate() {
+            let problems: Vec<String> = problems.iter().map(|problem| format!("{}: {}", problem.field, problem.message)).collect();
+            return Err(VideoClipError::InvalidOptions(problems.join("; ")));
+        }
+        self.allowed.check(&request)?;
+        Ok(request)
+    }
+}
+
+fn tool_result(structured: Value, is_error: bool) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": structured.to_string() }],
+        "structuredContent": structured,
+        "isError": is_error,
+    })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn call(server: &McpServer, name: &str, arguments: Value) -> Value {
+        let message = json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": name, "arguments": arguments } });
+        server.handle(&message).unwrap()["result"].clone()
+    }
+
+    #[test]
+    fn test_session() {
+        let server = McpServer::new(VideoClipper::new(), &[]).unwrap();
+        let input = [
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": "2025-06-18"}}"#,
+            r#"{"jsonrpc": "2.0", "method": "notifications/initialized"}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "tools/list"}"#,
+            "not json",
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "resources/list"}"#,
+        ].join("\n");
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).unwrap();
+
+        let responses: Vec<Value> = output.split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["result"]["protocolVersion"], PROTOCOL_VERSION);
+        let tools = responses[1]["result"]["tools"].as_array().unwrap();
+        assert_eq!(tools[0]["name"], "clip_video");
+        assert_eq!(tools[0]["inputSchema"]["type"], "object");
+        assert!(tools[0]["outputSchema"]["properties"]["output_file"].is_object());
+        assert!(tools[1]["outputSchema"].get("description").is_none());
+        assert_eq!(responses[2]["error"]["code"], PARSE_ERROR);
+        assert_eq!(responses[3]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_arguments_are_validated() {
+        let server = McpServer::new(VideoClipper::new(), &[]).unwrap();
+        let result = call(&server, "clip_video", json!({ "input_file": "a.mp4", "start_time": "0:30", "end_time": "0:10", "volum_db": 3 }));
+        assert_eq!(result["isError"], true);
+        assert_eq!(result["structuredContent"]["code"], "invalid_options");
+        assert!(result["structuredContent"]["message"].as_str().unwrap().contains("volum_db"));
+
+        let result = call(&server, "clip_video", json!({ "input_file": "a.mp4", "start_time": "0:30", "end_time": "0:10" }));
+        assert!(result["structuredContent"]["message"].as_str().unwrap().contains("end_time"));
+        let unknown = json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": "rm_rf" } });
+        assert_eq!(server.handle(&unknown).unwrap()["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_files_must_be_in_allowed_directories() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("talk.mp4");
+        std::fs::write(&source, b"").unwrap();
+        let server = McpServer::new(VideoClipper::new(), &[dir.path().to_path_buf()]).unwrap();
+        let request = |extra: Value| {
+            let mut request = json!({ "input_file": source.display().to_string(), "start_time": "0", "end_time": "5" });
+            request.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            server.request(request)
+        };
+
+        assert!(request(json!({ "output_dir": dir.path().join("clips/new").display().to_string() })).is_ok());
+        assert!(matches!(request(json!({ "output_dir": "/tmp" })), Err(VideoClipError::InvalidPath(_))));
+        let escape = dir.path().join("../elsewhere").display().to_string();
+        assert!(matches!(request(json!({ "output_dir": escape })), Err(VideoClipError::InvalidPath(_))));
+        assert!(matches!(request(json!({ "intro": "/etc/hostname" })), Err(VideoClipError::InvalidPath(_))));
+        assert!(request(json!({ "extra_output_args": ["-f", "null"] })).is_err());
+    }
+}
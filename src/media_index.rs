@@ -0,0 +1,453 @@
+#[cfg(not(feature = "wasm"))]
+use crate::analysis::AnalysisPipeline;
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use crate::probe::Chapter;
+use crate::ranges::TimeRange;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+/// Media index sidecars
+/// Clipping the same multi-hour recording again and again used to re-run
+/// the same analysis every time: a keyframe scan before each smart cut, a
+/// full decode to find silences or shot changes. The index holds all of it
+/// for one source (keyframes, chapters, silences and scene changes, as the
+/// [`crate::analysis`] stages found them) in a small little-endian binary
+/// file, written next to the source as `.<name>.vcindex`, or in the user
+/// cache directory when the source's folder is read-only. Loading one is a
+/// single read with no parsing beyond copying numbers out. The index
+/// records the source's size and modification time, and one that doesn't
+/// match any more is ignored.
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MediaIndex {
+    pub duration: Option<f64>,
+    /// Video keyframe timestamps, ascending
+    pub keyframes: Vec<f64>,
+    pub chapters: Vec<Chapter>,
+    pub silences: Vec<TimeRange>,
+    /// Shot changes, ascending
+    pub scenes: Vec<f64>,
+}
+
+const MAGIC: &[u8; 4] = b"VCIX";
+
+/// Bumped whenever the layout or the analysis settings below change, so
+/// older indexes are rebuilt
+const FORMAT_VERSION: u16 = 1;
+
+/// Audio quieter than this counts as silence
+const SILENCE_NOISE_DB: f64 = -50.0;
+
+/// Shortest silence worth indexing, in seconds
+const MIN_SILENCE_SECONDS: f64 = 1.0;
+
+/// `scdet` score (0-100) from which a frame starts a new shot
+const SCENE_THRESHOLD: f64 = 10.0;
+
+/// Frame width scene detection runs at; shot changes survive downscaling
+const SCENE_WIDTH: u32 = 320;
+
+/// The size and modification time of the source an index was built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceStamp {
+    pub size: u64,
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+}
+
+impl SourceStamp {
+    pub fn of(source: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(source).ok()?;
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self { size: metadata.len(), mtime_secs: mtime.as_secs(), mtime_nanos: mtime.subsec_nanos() })
+    }
+}
+
+impl MediaIndex {
+    /// Keyframes within `[from, to]`
+    pub fn keyframes_in(&self, from: f64, to: f64) -> Vec<f64> {
+        let first = self.keyframes.partition_point(|&t| t < from);
+        let last = self.keyframes.partition_point(|&t| t <= to);
+        self.keyframes[first..last.max(first)].to_vec()
+    }
+
+    /// The chapter `time` falls in
+    pub fn chapter_at(&self, time: f64) -> Option<&Chapter> {
+        self.chapters.iter().find(|c| c.start <= time && time < c.end)
+    }
+
+    pub fn to_bytes(&self, stamp: &SourceStamp) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(64 + 8 * (self.keyframes.len() + self.scenes.len() + 2 * self.silences.len()));
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&stamp.size.to_le_bytes());
+        bytes.extend_from_slice(&stamp.mtime_secs.to_le_bytes());
+        bytes.extend_from_slice(&stamp.mtime_nanos.to_le_bytes());
+        bytes.extend_from_slice(&self.duration.unwrap_or(f64::NAN).to_le_bytes());
+
+        let put_times = |bytes: &mut Vec<u8>, times: &[f64]| {
+            bytes.extend_from_slice(&(times.len() as u32).to_le_bytes());
+            for time in times {
+                bytes.extend_from_slice(&time.to_le_bytes());
+            }
+        };
+        put_times(&mut bytes, &self.keyframes);
+        put_times(&mut bytes, &self.scenes);
+        let silences: Vec<f64> = self.silences.iter().flat_map(|s| [s.start, s.end]).collect();
+        put_times(&mut bytes, &silences);
+
+        bytes.extend_from_slice(&(self.chapters.len() as u32).to_le_bytes());
+        for chapter in &self.chapters {
+            bytes.extend_from_slice(&chapter.start.to_le_bytes());
+            bytes.extend_from_slice(&chapter.end.to_le_bytes());
+            let title = chapter.title.as_deref().unwrap_or_default().as_bytes();
+            let title = &title[..title.len().min(u16::MAX as usize)];
+            bytes.extend_from_slice(&(title.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(title);
+        }
+        bytes
+    }
+
+    /// The index in `bytes` and the source it was built from; `None` for
+    /// anything truncated, foreign or from another format version
+    pub fn from_bytes(bytes: &[u8]) -> Option<(Self, SourceStamp)> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(4)? != MAGIC || reader.u16()? != FORMAT_VERSION {
+            return None;
+        }
+        let stamp = SourceStamp { size: reader.u64()?, mtime_secs: reader.u64()?, mtime_nanos: reader.u32()? };
+        let duration = Some(reader.f64()?).filter(|d| !d.is_nan());
+        let keyframes = reader.times()?;
+        let scenes = reader.times()?;
+        let silences = reader.times()?.chunks_exact(2).map(|pair| TimeRange { start: pair[0], end: pair[1] }).collect();
+
+        let count = reader.u32()? as usize;
+        let mut chapters = Vec::with_capacity(count.min(reader.remaining() / 18));
+        for _ in 0..count {
+            let start = reader.f64()?;
+            let end = reader.f64()?;
+            let length = reader.u16()? as usize;
+            let title = String::from_utf8(reader.take(length)?.to_vec()).ok()?;
+            chapters.push(Chapter { start, end, title: (!title.is_empty()).then_some(title) });
+        }
+        Some((Self { duration, keyframes, chapters, silences, scenes }, stamp))
+    }
+
+    /// Where the index of `source` is looked for: next to it, then in the
+    /// user cache directory
+    pub fn locations(source: &Path) -> Vec<PathBuf> {
+        let name = source.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut locations = vec![source.with_file_name(format!(".{}.vcindex", name))];
+        if let Some(cache) = dirs::cache_dir() {
+            let path = std::fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf());
+            let key = crate::probe_cache::cache_key(path.as_os_str().as_encoded_bytes());
+            locations.push(cache.join("video-clip-rs").join("index").join(format!("{}.vcindex", key)));
+        }
+        locations
+    }
+
+    /// The index of `source`, if one was built since it last changed
+    pub fn load(source: &Path) -> Option<Self> {
+        let stamp = SourceStamp::of(source)?;
+        Self::locations(source).iter().find_map(|path| {
+            let (index, built_from) = Self::from_bytes(&std::fs::read(path).ok()?)?;
+            (built_from == stamp).then_some(index)
+        })
+    }
+
+    /// Writes the index for `source`, next to it when its folder takes
+    /// writes, and returns where it went
+    pub fn save(&self, source: &Path) -> Result<PathBuf> {
+        let stamp = SourceStamp::of(source)
+            .ok_or_else(|| VideoClipError::FileNotFound(source.display().to_string()))?;
+        let bytes = self.to_bytes(&stamp);
+        let mut last_error = None;
+        for path in Self::locations(source) {
+            let written = path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|_| std::fs::write(&path, &bytes));
+            match written {
+                Ok(()) => return Ok(path),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.map(Into::into).unwrap_or_else(|| VideoClipError::InvalidPath(source.display().to_string())))
+    }
+
+    /// Collects the index from `pipeline`, running the stages it's missing
+    #[cfg(not(feature = "wasm"))]
+    pub fn build(pipeline: &mut AnalysisPipeline) -> Result<Self> {
+        Ok(Self {
+            duration: pipeline.probe()?.duration,
+            keyframes: pipeline.keyframes()?,
+            chapters: pipeline.chapters()?,
+            silences: pipeline.silences()?,
+            scenes: pipeline.scenes()?,
+        })
+    }
+
+    /// The saved index of `source`, or a new one built and saved; the flag
+    /// is whether it came from disk. Rebuilding analyzes the source again.
+    #[cfg(not(feature = "wasm"))]
+    pub fn load_or_build(source: &Path, rebuild: bool) -> Result<(Self, bool)> {
+        if !rebuild {
+            if let Some(index) = Self::load(source) {
+                return Ok((index, true));
+            }
+        }
+        let mut pipeline = AnalysisPipeline::new(source);
+        pipeline.set_rerun(rebuild);
+        let index = Self::build(&mut pipeline)?;
+        if let Err(e) = index.save(source) {
+            log::warn!("Could not save the index of {}: {}", source.display(), e);
+        }
+        Ok((index, false))
+    }
+}
+
+/// Keyframes of `source` within `[from, from + window]`, from its index when
+/// it has a current one, otherwise probed
+#[cfg(not(feature = "wasm"))]
+pub fn keyframes(source: &Path, from: f64, window: f64) -> Result<Vec<f64>> {
+    match MediaIndex::load(source) {
+        Some(index) => Ok(index.keyframes_in(from, from + window)),
+        None => crate::probe::probe_keyframes(source, from, window),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        self.array().map(f64::from_le_bytes)
+    }
+
+    fn times(&mut self) -> Option<Vec<f64>> {
+        let count = self.u32()? as usize;
+        let bytes = self.take(count.checked_mul(8)?)?;
+        Some(bytes.chunks_exact(8).map(|b| f64::from_le_bytes(b.try_into().unwrap())).collect())
+    }
+}
+
+/// Parses `silencedetect` output into silent ranges; a silence still open
+/// when the log ends runs to `media_end` when it's known
+pub fn parse_silences(stderr: &str, media_end: Option<f64>) -> Vec<TimeRange> {
+    let mut silences = Vec::new();
+    let mut open = None;
+    for line in stderr.lines() {
+        if let Some(start) = field(line, "silence_start:") {
+            open = Some(start);
+        } else if let Some(end) = field(line, "silence_end:") {
+            if let Some(start) = open.take() {
+                silences.push(TimeRange { start: f64::max(start, 0.0), end });
+            }
+        }
+    }
+    if let (Some(start), Some(end)) = (open, media_end) {
+        if end > start {
+            silences.push(TimeRange { start, end });
+        }
+    }
+    silences
+}
+
+/// Parses `scdet` output (`lavfi.scd.score: 45.1, lavfi.scd.time: 12.5`)
+/// into shot change times
+pub fn parse_scenes(stderr: &str) -> Vec<f64> {
+    let mut scenes: Vec<f64> = stderr.lines().filter_map(|line| field(line, "lavfi.scd.time:")).collect();
+    scenes.sort_by(|a, b| a.total_cmp(b));
+    scenes.dedup();
+    scenes
+}
+
+/// The number after `key` on `line`
+fn field(line: &str, key: &str) -> Option<f64> {
+    let rest = &line[line.find(key)? + key.len()..];
+    rest.split(['|', ',']).next()?.trim().parse().ok()
+}
+
+/// One decode of the whole source through `silencedetect` and `scdet`
+#[derive(Debug, Clone)]
+pub struct IndexScanCommand {
+    input: PathBuf,
+    scenes: bool,
+    silences: bool,
+}
+
+impl IndexScanCommand {
+    /// Looks for both shot changes and silences
+    pub fn new(input: impl AsRef<Path>) -> Self {
+        Self { input: input.as_ref().to_path_buf(), scenes: true, silences: true }
+    }
+
+    /// Which of shot changes and silences to look for
+    pub fn set_detect(&mut self, scenes: bool, silences: bool) {
+        self.scenes = scenes;
+        self.silences = silences;
+    }
+
+    pub fn detects_anything(&self) -> bool {
+        self.scenes || self.silences
+    }
+
+    pub fn video_filters(&self) -> FilterGraph {
+        let mut graph = FilterGraph::new();
+        graph.push(Filter::new("scale").arg(SCENE_WIDTH).arg(-2));
+        graph.push(Filter::new("scdet").option("threshold", SCENE_THRESHOLD));
+        graph
+    }
+
+    pub fn audio_filters(&self) -> FilterGraph {
+        FilterGraph::from(
+            Filter::new("silencedetect")
+                .option("noise", format!("{}dB", SILENCE_NOISE_DB))
+                .option("duration", MIN_SILENCE_SECONDS),
+        )
+    }
+
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-hide_banner".into(),
+            "-nostats".into(),
+            "-i".into(), self.input.display().to_string(),
+        ];
+        if self.scenes {
+            args.extend(["-map".into(), "0:v:0?".into()]);
+        }
+        if self.silences {
+            args.extend(["-map".into(), "0:a:0?".into()]);
+        }
+        if self.scenes {
+            args.extend(self.video_filters().to_args());
+        }
+        if self.silences {
+            args.extend(self.audio_filters().to_audio_args());
+        }
+        args.extend(["-f", "null", "-"].iter().map(|s| s.to_string()));
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    /// Silences and shot changes; silence running to the end closes at `media_end`
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self, media_end: Option<f64>) -> Result<(Vec<TimeRange>, Vec<f64>)> {
+        let output = crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Indexing failed")?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Ok((parse_silences(&stderr, media_end), parse_scenes(&stderr)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sample() -> MediaIndex {
+        MediaIndex {
+            duration: Some(7200.0),
+            keyframes: (0..3600).map(|i| i as f64 * 2.0).collect(),
+            chapters: vec![
+                Chapter { start: 0.0, end: 600.0, title: Some("Intro".to_string()) },
+                Chapter { start: 600.0, end: 7200.0, title: None },
+            ],
+            silences: vec![TimeRange { start: 598.0, end: 603.5 }],
+            scenes: vec![12.5, 600.2],
+        }
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let stamp = SourceStamp { size: 4_000_000_000, mtime_secs: 1_700_000_000, mtime_nanos: 5 };
+        let index = sample();
+        let bytes = index.to_bytes(&stamp);
+        assert_eq!(MediaIndex::from_bytes(&bytes), Some((index.clone(), stamp)));
+
+        // Truncated or foreign files are misses, not errors
+        assert_eq!(MediaIndex::from_bytes(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(MediaIndex::from_bytes(b"{\"keyframes\": []}"), None);
+        let empty = MediaIndex::default();
+        assert_eq!(MediaIndex::from_bytes(&empty.to_bytes(&stamp)), Some((empty, stamp)));
+    }
+
+    #[test]
+    fn test_lookups() {
+        let index = sample();
+        assert_eq!(index.keyframes_in(59.0, 64.0), vec![60.0, 62.0, 64.0]);
+        assert!(index.keyframes_in(8000.0, 8010.0).is_empty());
+        assert_eq!(index.chapter_at(601.0).unwrap().start, 600.0);
+        assert!(index.chapter_at(9000.0).is_none());
+    }
+
+    #[test]
+    fn test_changed_source_is_not_loaded() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("talk.mp4");
+        std::fs::write(&source, b"video").unwrap();
+        let saved = sample().save(&source).unwrap();
+        assert_eq!(saved, dir.path().join(".talk.mp4.vcindex"));
+        assert_eq!(MediaIndex::load(&source), Some(sample()));
+
+        std::fs::write(&source, b"a re-exported video").unwrap();
+        assert_eq!(MediaIndex::load(&source), None);
+    }
+
+    #[test]
+    fn test_parse_scan_output() {
+        let stderr = "[silencedetect @ 0x1] silence_start: -0.01\n\
+            [silencedetect @ 0x1] silence_end: 2.5 | silence_duration: 2.51\n\
+            [scdet @ 0x2] lavfi.scd.score: 45.120, lavfi.scd.time: 30.03\n\
+            [scdet @ 0x2] lavfi.scd.score: 12.000, lavfi.scd.time: 12.5\n\
+            [silencedetect @ 0x1] silence_start: 118\n";
+        assert_eq!(
+            parse_silences(stderr, Some(120.0)),
+            vec![TimeRange { start: 0.0, end: 2.5 }, TimeRange { start: 118.0, end: 120.0 }]
+        );
+        assert_eq!(parse_silences(stderr, None).len(), 1);
+        assert_eq!(parse_scenes(stderr), vec![12.5, 30.03]);
+    }
+
+    #[test]
+    fn test_scan_command() {
+        assert_eq!(
+            IndexScanCommand::new("talk.mp4").get_command_string(),
+            "ffmpeg -hide_banner -nostats -i talk.mp4 -map 0:v:0? -map 0:a:0? -vf scale=320:-2,scdet=threshold=10 -af silencedetect=noise=-50dB:duration=1 -f null -"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Clip metadata
+//! A title, description and tags that travel with a clip: they're written into
+//! the output container's metadata and into a `<clip>.json` sidecar next to
+//! it, so the context of a range survives the clip being copied elsewhere.
+
+use crate::error::{VideoClipError, Result};
+use crate::video_clipper::ClipResult;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ClipMetadata {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl ClipMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none() && self.tags.is_empty()
+    }
+
+    /// `-metadata` output options; the description doubles as the comment,
+    /// which more players show, and tags are joined into `keywords`
+    pub fn ffmpeg_args(&self) -> Vec<String> {
+        let mut tags: Vec<(&str, String)> = Vec::new();
+        if let Some(title) = &self.title {
+            tags.push(("title", title.clone()));
+        }
+        if let Some(description) = &self.description {
+            tags.push(("description", description.clone()));
+            tags.push(("comment", description.clone()));
+        }
+        if !self.tags.is_empty() {
+            tags.push(("keywords", self.tags.join(",")));
+        }
+        tags.into_iter()
+            .flat_map(|(key, value)| ["-metadata".to_string(), format!("{}={}", key, value)])
+            .collect()
+    }
+}
+
+/// What a sidecar records: the metadata plus where the clip came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sidecar {
+    pub input_file: String,
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    #[serde(flatten)]
+    pub metadata: ClipMetadata,
+    /// Fingerprint of the batch request that made the clip, so a later batch
+    /// can tell the clip is already done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<String>,
+}
+
+/// `clip.mp4` → `clip.json`
+pub fn sidecar_path(output: impl AsRef<Path>) -> PathBuf {
+    output.as_ref().with_extension("json")
+}
+
+/// Writes a sidecar next to every file `result` produced (each rendition gets
+/// its own) and returns their paths
+pub fn write_sidecars(result: &ClipResult, metadata: &ClipMetadata) -> Result<Vec<PathBuf>> {
+    let sidecar = Sidecar {
+        input_file: result.input_file.clone(),
+        start_seconds: result.start_seconds,
+        end_seconds: result.end_seconds,
+        metadata: metadata.clone(),
+        request: None,
+    };
+    let json = serde_json::to_string_pretty(&sidecar)
+        .map_err(|e| VideoClipError::InvalidOptions(format!("couldn't serialize sidecar: {}", e)))?;
+
+    let outputs: Vec<&str> = if result.renditions.is_empty() {
+        vec![result.output_file.as_str()]
+    } else {
+        result.renditions.iter().map(|r| r.output_file.as_str()).collect()
+    };
+    let mut paths = Vec::new();
+    for output in outputs {
+        let path = sidecar_path(output);
+        std::fs::write(&path, &json)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// The sidecar next to `output`, if it has a readable one
+pub fn read_sidecar(output: impl AsRef<Path>) -> Option<Sidecar> {
+    let json = std::fs::read_to_string(sidecar_path(output)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Records `fingerprint` in the sidecar of `result`'s output, writing one
+/// (without metadata) if the clip has none yet
+pub fn record_request(result: &ClipResult, fingerprint: &str) -> Result<()> {
+    let mut sidecar = read_sidecar(&result.output_file).unwrap_or_else(|| Sidecar {
+        input_file: result.input_file.clone(),
+        start_seconds: result.start_seconds,
+        end_seconds: result.end_seconds,
+        metadata: ClipMetadata::default(),
+        request: None,
+    });
+    sidecar.request = Some(fingerprint.to_string());
+    let json = serde_json::to_string_pretty(&sidecar)
+        .map_err(|e| VideoClipError::InvalidOptions(format!("couldn't serialize sidecar: {}", e)))?;
+    std::fs::write(sidecar_path(&result.output_file), json)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> ClipMetadata {
+        ClipMetadata {
+            title: Some("Keynote: opening".to_string()),
+            description: Some("First five minutes".to_string()),
+            tags: vec!["keynote".to_string(), "2024".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_ffmpeg_args() {
+        assert_eq!(metadata().ffmpeg_args(), vec![
+            "-metadata", "title=Keynote: opening",
+            "-metadata", "description=First five minutes",
+            "-metadata", "comment=First five minutes",
+            "-metadata", "keywords=keynote,2024",
+        ]);
+        assert!(ClipMetadata::default().ffmpeg_args().is_empty());
+        assert!(ClipMetadata::default().is_empty());
+    }
+
+    #[test]
+    fn test_sidecar_written_next_to_the_clip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let output = dir.path().join("talk_clip.mp4");
+        let result = ClipResult {
+            input_file: "talk.mp4".to_string(),
+            output_file: output.display().to_string(),
+            start_seconds: 10.0,
+            end_seconds: 20.0,
+            duration: 10.0,
+            file_size_mb: None,
+            command: String::new(),
+            encoder: "copy".to_string(),
+            warnings: vec![],
+            black_trim: None,
+            cut_report: None,
+            renditions: vec![],
+            recovery: None,
+            scrub: None,
+            quality: None,
+        };
+
+        let paths = write_sidecars(&result, &metadata()).unwrap();
+        assert_eq!(paths, vec![dir.path().join("talk_clip.json")]);
+        let sidecar: Sidecar = serde_json::from_str(&std::fs::read_to_string(&paths[0]).unwrap()).unwrap();
+        assert_eq!(sidecar.metadata, metadata());
+        assert_eq!(sidecar.end_seconds, 20.0);
+
+        record_request(&result, "fingerprint").unwrap();
+        let sidecar = read_sidecar(&output).unwrap();
+        assert_eq!(sidecar.metadata, metadata());
+        assert_eq!(sidecar.request.as_deref(), Some("fingerprint"));
+    }
+}
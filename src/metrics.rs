@@ -0,0 +1,163 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Job metrics
+/// Counters for the job queue, rendered in the Prometheus text exposition
+/// format for the server's `/metrics` endpoint
+
+#[derive(Debug)]
+pub struct Metrics {
+    jobs_submitted: AtomicU64,
+    jobs_started: AtomicU64,
+    jobs_succeeded: AtomicU64,
+    jobs_failed: AtomicU64,
+    bytes_written: AtomicU64,
+    ffmpeg_seconds: Mutex<Histogram>,
+}
+
+/// Upper bounds (seconds) of the ffmpeg runtime histogram buckets
+pub const RUNTIME_BUCKETS: [f64; 10] = [0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Non-cumulative counts per bucket; the extra slot is `+Inf`
+    counts: [u64; RUNTIME_BUCKETS.len() + 1],
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        let bucket = RUNTIME_BUCKETS.iter().position(|&le| value <= le).unwrap_or(RUNTIME_BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+
+    fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            jobs_submitted: AtomicU64::new(0),
+            jobs_started: AtomicU64::new(0),
+            jobs_succeeded: AtomicU64::new(0),
+            jobs_failed: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            ffmpeg_seconds: Mutex::new(Histogram::default()),
+        }
+    }
+
+    pub fn job_submitted(&self) {
+        self.jobs_submitted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn job_started(&self) {
+        self.jobs_started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a finished job: its runtime and, on success, the output size
+    pub fn job_finished(&self, seconds: f64, bytes_written: Option<u64>) {
+        match bytes_written {
+            Some(bytes) => {
+                self.jobs_succeeded.fetch_add(1, Ordering::Relaxed);
+                self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+            }
+            None => {
+                self.jobs_failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.ffmpeg_seconds.lock().unwrap().observe(seconds);
+    }
+
+    pub fn jobs_succeeded(&self) -> u64 {
+        self.jobs_succeeded.load(Ordering::Relaxed)
+    }
+
+    pub fn jobs_failed(&self) -> u64 {
+        self.jobs_failed.load(Ordering::Relaxed)
+    }
+
+    /// Prometheus text format; gauges are passed in since they're owned by the queue
+    pub fn render(&self, queue_depth: usize, jobs_running: usize) -> String {
+        let mut out = String::new();
+        let counter = |out: &mut String, name: &str, help: &str, value: &AtomicU64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        };
+        let gauge = |out: &mut String, name: &str, help: &str, value: usize| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+
+        counter(&mut out, "video_clip_jobs_submitted_total", "Clip jobs accepted into the queue.", &self.jobs_submitted);
+        counter(&mut out, "video_clip_jobs_started_total", "Clip jobs picked up by a worker.", &self.jobs_started);
+        counter(&mut out, "video_clip_jobs_succeeded_total", "Clip jobs that produced a clip.", &self.jobs_succeeded);
+        counter(&mut out, "video_clip_jobs_failed_total", "Clip jobs that failed.", &self.jobs_failed);
+        counter(&mut out, "video_clip_bytes_written_total", "Bytes of clip output written.", &self.bytes_written);
+        gauge(&mut out, "video_clip_queue_depth", "Clip jobs waiting for a worker.", queue_depth);
+        gauge(&mut out, "video_clip_jobs_running", "Clip jobs currently running.", jobs_running);
+
+        let histogram = self.ffmpeg_seconds.lock().unwrap().clone();
+        let name = "video_clip_ffmpeg_duration_seconds";
+        let _ = writeln!(out, "# HELP {} Wall-clock time spent running ffmpeg per job.", name);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (le, count) in RUNTIME_BUCKETS.iter().zip(histogram.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, histogram.count());
+        let _ = writeln!(out, "{}_sum {}", name, histogram.sum);
+        let _ = writeln!(out, "{}_count {}", name, histogram.count());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_and_gauges() {
+        let metrics = Metrics::new();
+        metrics.job_submitted();
+        metrics.job_submitted();
+        metrics.job_started();
+        metrics.job_finished(1.5, Some(2048));
+
+        let text = metrics.render(1, 0);
+        assert!(text.contains("# TYPE video_clip_jobs_submitted_total counter\nvideo_clip_jobs_submitted_total 2\n"));
+        assert!(text.contains("video_clip_jobs_succeeded_total 1\n"));
+        assert!(text.contains("video_clip_jobs_failed_total 0\n"));
+        assert!(text.contains("video_clip_bytes_written_total 2048\n"));
+        assert!(text.contains("video_clip_queue_depth 1\n"));
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let metrics = Metrics::new();
+        metrics.job_finished(0.2, None);
+        metrics.job_finished(7.0, Some(1));
+        metrics.job_finished(7200.0, Some(1));
+
+        let text = metrics.render(0, 0);
+        assert!(text.contains("video_clip_ffmpeg_duration_seconds_bucket{le=\"0.5\"} 1\n"));
+        assert!(text.contains("video_clip_ffmpeg_duration_seconds_bucket{le=\"5\"} 1\n"));
+        assert!(text.contains("video_clip_ffmpeg_duration_seconds_bucket{le=\"10\"} 2\n"));
+        assert!(text.contains("video_clip_ffmpeg_duration_seconds_bucket{le=\"3600\"} 2\n"));
+        assert!(text.contains("video_clip_ffmpeg_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("video_clip_ffmpeg_duration_seconds_sum 7207.2\n"));
+        assert_eq!(metrics.jobs_failed(), 1);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use crate::ranges::TimeRange;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Motion activity
+/// Measures how much the picture changes with FFmpeg's `signalstats` YDIF
+/// (mean absolute luma difference from the previous frame, 0-255) on a small,
+/// subsampled copy of the video, and finds static lulls (menus, pause screens,
+/// a camera pointed at an empty pitch) so they can be left out of suggested
+/// clip ranges.
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MotionSample {
+    /// Seconds into the input
+    pub time: f64,
+    /// Mean luma change from the previous sampled frame (0-255)
+    pub activity: f64,
+}
+
+/// Frames per second measured; enough to tell motion from stillness
+pub const SAMPLE_FPS: u32 = 5;
+
+/// Width the frames are scaled to before measuring
+const SAMPLE_WIDTH: u32 = 320;
+
+/// Mean activity below which a stretch counts as static
+pub const DEFAULT_MIN_MOTION: f64 = 1.0;
+
+/// Shortest still stretch worth calling a lull, in seconds
+pub const DEFAULT_MIN_LULL_SECONDS: f64 = 3.0;
+
+const YDIF_KEY: &str = "lavfi.signalstats.YDIF=";
+
+/// Parses the `frame:N pts:P pts_time:T` / `lavfi.signalstats.YDIF=V` line
+/// pairs FFmpeg's `metadata=print` filter writes to stderr
+pub fn parse_motion(stderr: &str) -> Vec<MotionSample> {
+    let mut time = None;
+    let mut samples = Vec::new();
+    for line in stderr.lines() {
+        if let Some(index) = line.find("pts_time:") {
+            time = line[index + "pts_time:".len()..].split_whitespace().next().and_then(|t| t.parse().ok());
+        } else if let (Some(index), Some(t)) = (line.find(YDIF_KEY), time) {
+            if let Ok(activity) = line[index + YDIF_KEY.len()..].trim().parse() {
+                samples.push(MotionSample { time: t, activity });
+            }
+        }
+    }
+    samples
+}
+
+/// Stretches of at least `min_seconds` where every `window` of samples
+/// averages under `min_motion`
+pub fn static_lulls(samples: &[MotionSample], window: f64, min_motion: f64, min_seconds: f64) -> Vec<TimeRange> {
+    let mut windows: Vec<(f64, f64, usize)> = Vec::new();
+    for sample in samples {
+        let start = (sample.time / window).floor() * window;
+        match windows.last_mut() {
+            Some((last, sum, count)) if *last == start => {
+                *sum += sample.activity;
+                *count += 1;
+            }
+            _ => windows.push((start, sample.activity, 1)),
+        }
+    }
+
+    let mut lulls: Vec<TimeRange> = Vec::new();
+    let mut current: Option<TimeRange> = None;
+    for (start, sum, count) in windows {
+        let still = sum / (count as f64) < min_motion;
+        current = match (current, still) {
+            (Some(lull), true) if lull.end >= start => Some(TimeRange { end: start + window, ..lull }),
+            (previous, still) => {
+                lulls.extend(previous);
+                still.then_some(TimeRange { start, end: start + window })
+            }
+        };
+    }
+    lulls.extend(current);
+    lulls.retain(|lull| lull.duration() >= min_seconds);
+    lulls
+}
+
+#[derive(Debug, Clone)]
+pub struct MotionCommand {
+    input: PathBuf,
+    start_time: f64,
+    duration: Option<f64>,
+}
+
+impl MotionCommand {
+    /// Measures `input` from `start_time`, for `duration` seconds or to the end
+    pub fn new(input: impl AsRef<Path>, start_time: f64, duration: Option<f64>) -> Self {
+        Self { input: input.as_ref().to_path_buf(), start_time, duration }
+    }
+
+    pub fn filter_graph(&self) -> FilterGraph {
+        let mut graph = FilterGraph::new();
+        graph.push(Filter::new("fps").arg(SAMPLE_FPS));
+        graph.push(Filter::new("scale").arg(SAMPLE_WIDTH).arg(-2));
+        graph.push(Filter::new("signalstats"));
+        graph.push(Filter::new("metadata").option("mode", "print").option("key", YDIF_KEY.trim_end_matches('=')));
+        graph
+    }
+
+    /// Decodes the first video stream through `signalstats`, discarding output
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-hide_banner".into(),
+            "-nostats".into(),
+            "-ss".into(), self.start_time.to_string(),
+            "-i".into(), self.input.display().to_string(),
+        ];
+        if let Some(duration) = self.duration {
+            args.extend(["-t".into(), duration.to_string()]);
+        }
+        args.extend(["-map".into(), "0:v:0".into()]);
+        args.extend(self.filter_graph().to_args());
+        args.extend(["-an", "-f", "null", "-"].iter().map(|s| s.to_string()));
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    /// Motion samples in source time
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> crate::error::Result<Vec<MotionSample>> {
+        let output = crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Motion analysis failed")?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        Ok(parse_motion(&stderr)
+            .into_iter()
+            .map(|s| MotionSample { time: s.time + self.start_time, ..s })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_metadata_output() {
+        let stderr = "[Parsed_metadata_3 @ 0x55d0] frame:0    pts:0       pts_time:0\n\
+            [Parsed_metadata_3 @ 0x55d0] lavfi.signalstats.YDIF=0.000000\n\
+            [Parsed_metadata_3 @ 0x55d0] frame:1    pts:1       pts_time:0.2\n\
+            [Parsed_metadata_3 @ 0x55d0] lavfi.signalstats.YDIF=12.5\n\
+            frame=   2 fps=0.0 q=-0.0 size=N/A time=00:00:00.40\n";
+        assert_eq!(
+            parse_motion(stderr),
+            vec![MotionSample { time: 0.0, activity: 0.0 }, MotionSample { time: 0.2, activity: 12.5 }]
+        );
+    }
+
+    #[test]
+    fn test_static_lulls() {
+        // Five samples a second: busy, still from 10s to 20s, a one-second blip of stillness at 25s, busy
+        let samples: Vec<MotionSample> = (0..30 * 5)
+            .map(|i| {
+                let time = i as f64 / 5.0;
+                let still = (10.0..20.0).contains(&time) || (25.0..26.0).contains(&time);
+                MotionSample { time, activity: if still { 0.2 } else { 8.0 } }
+            })
+            .collect();
+        assert_eq!(
+            static_lulls(&samples, 1.0, DEFAULT_MIN_MOTION, DEFAULT_MIN_LULL_SECONDS),
+            vec![TimeRange { start: 10.0, end: 20.0 }]
+        );
+        assert_eq!(static_lulls(&samples, 1.0, DEFAULT_MIN_MOTION, 1.0).len(), 2);
+    }
+
+    #[test]
+    fn test_motion_command() {
+        assert_eq!(
+            MotionCommand::new("game.mp4", 0.0, None).get_command_string(),
+            "ffmpeg -hide_banner -nostats -ss 0 -i game.mp4 -map 0:v:0 -vf fps=5,scale=320:-2,signalstats,metadata=mode=print:key=lavfi.signalstats.YDIF -an -f null -"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use crate::batch::{self, BatchReport};
+use crate::error::{VideoClipError, Result};
+use crate::time_parser::TimeParser;
+use crate::video_clipper::{ClipRequest, VideoClipper};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Multi-camera clipping
+/// Cuts the same moment out of several recordings of one event. The range is
+/// given on a shared timeline and each input carries its offset on it, the
+/// time the recording started relative to the reference; every input gets its
+/// own `-ss` so the clips line up. Stream copies still snap to keyframes, so
+/// use `smart_cut` or a re-encode when the angles must match to the frame.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlignedInput {
+    pub input_file: String,
+    /// Seconds into the shared timeline at which this recording starts:
+    /// positive when it was started after the reference, negative before
+    #[serde(default)]
+    pub offset: f64,
+}
+
+impl AlignedInput {
+    pub fn new(input_file: impl Into<String>, offset: f64) -> Self {
+        Self { input_file: input_file.into(), offset }
+    }
+}
+
+impl FromStr for AlignedInput {
+    type Err = VideoClipError;
+
+    /// `FILE@OFFSET`, the offset in any time format with an optional sign
+    /// (`cam2.mp4@1.5`, `cam3.mp4@-0:02`); a plain path has offset zero
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((input_file, offset)) = s.rsplit_once('@') else {
+            return Ok(Self::new(s, 0.0));
+        };
+        let (sign, offset) = match offset.strip_prefix('-') {
+            Some(rest) => (-1.0, rest),
+            None => (1.0, offset.strip_prefix('+').unwrap_or(offset)),
+        };
+        if input_file.is_empty() || offset.is_empty() {
+            return Err(VideoClipError::InvalidOptions(format!("expected FILE@OFFSET, got '{}'", s)));
+        }
+        Ok(Self::new(input_file, sign * TimeParser::parse_to_seconds(offset)?))
+    }
+}
+
+/// One request per input, each cloned from `template` with the template's
+/// range (on the shared timeline) moved onto that input's own clock. Clips
+/// are named after the input and the shared range, so the angles of one
+/// moment sort together; a `template` output name gets the input's name added.
+/// Fails if an input started after the range did, since its clip couldn't line up.
+pub fn aligned_requests(inputs: &[AlignedInput], template: &ClipRequest) -> Result<Vec<ClipRequest>> {
+    if inputs.is_empty() {
+        return Err(VideoClipError::InvalidOptions("aligned clipping needs at least one input".to_string()));
+    }
+    let range = template.time_range()?;
+    let requests = inputs
+        .iter()
+        .map(|input| {
+            let start = range.start - input.offset;
+            if start < 0.0 {
+                return Err(VideoClipError::InvalidOptions(format!(
+                    "{} starts {:.2}s after the range does, so its clip can't line up with the others",
+                    input.input_file, -start
+                )));
+            }
+            let stem = Path::new(&input.input_file).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            let output_name = match &template.output_name {
+                Some(name) => format!("{}_{}", name, stem),
+                None => format!("{}_sync_{}_to_{}", stem, TimeParser::format_time(range.start), TimeParser::format_time(range.end)),
+            };
+            Ok(ClipRequest {
+                input_file: input.input_file.clone(),
+                start_time: start.to_string(),
+                end_time: (range.end - input.offset).to_string(),
+                output_name: Some(output_name),
+                ..template.clone()
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    batch::validate_requests(&requests)?;
+    Ok(requests)
+}
+
+impl VideoClipper {
+    /// Clips `template`'s range, read on the shared timeline, out of every
+    /// input, as a batch
+    pub fn clip_aligned(&self, inputs: &[AlignedInput], template: &ClipRequest) -> Result<BatchReport> {
+        let requests = aligned_requests(inputs, template)?;
+        Ok(self.clip_batch(&requests))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_aligned_input() {
+        assert_eq!("cam1.mp4".parse::<AlignedInput>().unwrap(), AlignedInput::new("cam1.mp4", 0.0));
+        assert_eq!("cam2.mp4@1.5".parse::<AlignedInput>().unwrap(), AlignedInput::new("cam2.mp4", 1.5));
+        assert_eq!("me@home/cam3.mp4@-0:02".parse::<AlignedInput>().unwrap(), AlignedInput::new("me@home/cam3.mp4", -2.0));
+        assert!("cam2.mp4@".parse::<AlignedInput>().is_err());
+        assert!("cam2.mp4@soon".parse::<AlignedInput>().is_err());
+    }
+
+    #[test]
+    fn test_ranges_move_onto_each_clock() {
+        let template = ClipRequest { start_time: "1:00".to_string(), end_time: "1:30".to_string(), ..Default::default() };
+        let inputs = [
+            AlignedInput::new("wide.mp4", 0.0),
+            AlignedInput::new("close.mp4", 2.5),
+            AlignedInput::new("audience.mp4", -10.0),
+        ];
+        let requests = aligned_requests(&inputs, &template).unwrap();
+        let ranges: Vec<(&str, &str)> = requests.iter().map(|r| (r.start_time.as_str(), r.end_time.as_str())).collect();
+        assert_eq!(ranges, vec![("60", "90"), ("57.5", "87.5"), ("70", "100")]);
+        assert_eq!(requests[1].output_name.as_deref(), Some("close_sync_01-00_to_01-30"));
+
+        let named = ClipRequest { output_name: Some("goal".to_string()), ..template.clone() };
+        assert_eq!(aligned_requests(&inputs, &named).unwrap()[2].output_name.as_deref(), Some("goal_audience"));
+
+        let late = [AlignedInput::new("late.mp4", 75.0)];
+        assert!(aligned_requests(&late, &template).unwrap_err().to_string().contains("late.mp4 starts 15.00s after"));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::auth::SignedUrl;
+use crate::jobs::Job;
+use crate::server::ErrorBody;
+use crate::uploads::Upload;
+use crate::video_clipper::ClipRequest;
+use schemars::generate::SchemaSettings;
+use serde_json::{json, Value};
+
+/// OpenAPI document
+/// Describes the REST server (see [`crate::server`]) as OpenAPI 3.0, for
+/// generating client SDKs. The schemas are derived from the same types the
+/// routes take and return, so the document follows them as they change.
+/// Routes that are only there when the server enables them (uploads,
+/// signed links) are described all the same. The document is served at
+/// `GET /openapi.json`.
+pub fn document() -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    let request = generator.subschema_for::<ClipRequest>().to_value();
+    let job = generator.subschema_for::<Job>().to_value();
+    let upload = generator.subschema_for::<Upload>().to_value();
+    let signed_url = generator.subschema_for::<SignedUrl>().to_value();
+    let error = generator.subschema_for::<ErrorBody>().to_value();
+    let schemas = generator.take_definitions(true);
+
+    let ok = |description: &str, schema: &Value| json!({ "description": description, "content": { "application/json": { "schema": schema } } });
+    let failed = |description: &str| ok(description, &error);
+    let file = |description: &str| json!({
+        "description": description,
+        "headers": {
+            "Accept-Ranges": { "schema": { "type": "string" } },
+            "Content-Range": { "description": "On 206", "schema": { "type": "string" } },
+        },
+        "content": { "application/octet-stream": { "schema": { "type": "string", "format": "binary" } } },
+    });
+    let header = |name: &str, description: &str, required: bool| json!({
+        "name": name, "in": "header", "required": required, "description": description, "schema": { "type": "string" },
+    });
+    let job_id = json!({ "name": "id", "in": "path", "required": true, "schema": { "type": "integer", "format": "uint64", "minimum": 0 } });
+    let upload_id = json!({ "name": "id", "in": "path", "required": true, "schema": { "type": "string" } });
+    let range = header("Range", "A single `bytes=` range", false);
+    let offset = json!({ "Upload-Offset": { "schema": { "type": "integer" } } });
+    let open = json!([]);
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "video-clip-rs",
+            "description": "Queue clips of video files and fetch the results",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "security": [{ "bearer": [] }],
+        "paths": {
+            "/clips": {
+                "get": {
+                    "operationId": "listClips",
+                    "responses": { "200": ok("Every job", &json!({ "type": "array", "items": job })) },
+                },
+                "post": {
+                    "operationId": "submitClip",
+                    "parameters": [{
+                        "name": "strict", "in": "query", "required": false,
+                        "description": "Reject fields the request doesn't define",
+                        "schema": { "type": "boolean", "default": false },
+                    }],
+                    "requestBody": { "required": true, "content": { "application/json": { "schema": request } } },
+                    "responses": {
+                        "202": ok("Queued", &job),
+                        "422": failed("The request is invalid"),
+                    },
+                },
+            },
+            "/clips/{id}": {
+                "parameters": [job_id],
+                "get": {
+                    "operationId": "getClip",
+                    "responses": { "200": ok("The job", &job), "404": failed("No such job") },
+                },
+                "delete": {
+                    "operationId": "cancelClip",
+                    "responses": {
+                        "200": ok("Cancelled before it started", &job),
+                        "202": ok("Running, and being stopped", &job),
+                        "404": failed("No such job"),
+                        "409": failed("The job has already finished"),
+                    },
+                },
+            },
+            "/clips/{id}/file": {
+                "parameters": [job_id],
+                "get": {
+                    "operationId": "downloadClip",
+                    "parameters": [range],
+                    "responses": {
+                        "200": file("The clip"),
+                        "206": file("The requested part of the clip"),
+                        "404": failed("No such job"),
+                        "409": failed("The job hasn't made a clip"),
+                        "416": { "description": "The range starts past the end of the clip" },
+                    },
+                },
+            },
+            "/clips/{id}/url": {
+                "parameters": [job_id],
+                "get": {
+                    "operationId": "signClipUrl",
+                    "description": "A link to the clip that works without a token until it expires. Only served when the server has a URL secret.",
+                    "parameters": [{
+                        "name": "expires_in", "in": "query", "required": false,
+                        "description": "Seconds the link works for, up to a week",
+                        "schema": { "type": "integer", "default": 3600, "minimum": 1 },
+                    }],
+                    "responses": {
+                        "200": ok("The link", &signed_url),
+                        "404": failed("No such job"),
+                        "409": failed("The job hasn't made a clip"),
+                    },
+                },
+            },
+            "/files/{id}": {
+                "parameters": [job_id],
+                "get": {
+                    "operationId": "downloadSignedClip",
+                    "security": open,
+                    "parameters": [
+                        { "name": "expires", "in": "query", "required": true, "schema": { "type": "integer" } },
+                        { "name": "signature", "in": "query", "required": true, "schema": { "type": "string" } },
+                        range,
+                    ],
+                    "responses": {
+                        "200": file("The clip"),
+                        "206": file("The requested part of the clip"),
+                        "403": failed("The link is invalid or has expired"),
+                        "409": failed("The job hasn't made a clip"),
+                        "416": { "description": "The range starts past the end of the clip" },
+                    },
+                },
+            },
+            "/sources": {
+                "post": {
+                    "operationId": "uploadSource",
+                    "description": "A whole file as a multipart form, or, without a body, the start of a tus-style upload that `PATCH /sources/{id}` continues. Only served when the server has an upload directory.",
+                    "parameters": [
+                        header("Upload-Length", "Size of a tus-style upload", false),
+                        header("Upload-Metadata", "`filename <base64>`", false),
+                        header("Upload-Checksum", "`sha256 <base64>` of the whole file", false),
+                    ],
+                    "requestBody": {
+                        "required": false,
+                        "content": {
+                            "multipart/form-data": {
+                                "schema": {
+                                    "type": "object",
+                                    "required": ["file"],
+                                    "properties": {
+                                        "file": { "type": "string", "format": "binary" },
+                                        "sha256": { "type": "string", "description": "Hex SHA-256 of the file" },
+                                    },
+                                },
+                            },
+                        },
+                    },
+                    "responses": {
+                        "201": {
+                            "description": "Uploaded, or ready for the first piece",
+                            "headers": { "Location": { "schema": { "type": "string" } } },
+                            "content": { "application/json": { "schema": upload } },
+                        },
+                        "413": failed("Larger than the server accepts"),
+                        "422": failed("The request is invalid"),
+                        "460": failed("The file doesn't match its checksum"),
+                    },
+                },
+            },
+            "/sources/{id}": {
+                "parameters": [upload_id],
+                "get": {
+                    "operationId": "getSource",
+                    "description": "How much of the upload has arrived; `HEAD` for just the `Upload-Offset` header",
+                    "responses": {
+                        "200": { "description": "The upload", "headers": offset, "content": { "application/json": { "schema": upload } } },
+                        "404": failed("No such upload"),
+                    },
+                },
+                "patch": {
+                    "operationId": "appendSource",
+                    "parameters": [header("Upload-Offset", "Where this piece starts", true)],
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/offset+octet-stream": { "schema": { "type": "string", "format": "binary" } } },
+                    },
+                    "responses": {
+                        "204": { "description": "Appended", "headers": offset },
+                        "404": failed("No such upload"),
+                        "409": failed("The upload continues at another offset"),
+                        "413": failed("Larger than the server accepts"),
+                        "460": failed("The file doesn't match its checksum"),
+                    },
+                },
+                "delete": {
+                    "operationId": "discardSource",
+                    "responses": { "204": { "description": "Discarded" }, "404": failed("No such upload") },
+                },
+            },
+            "/schema": {
+                "get": {
+                    "operationId": "getClipRequestSchema",
+                    "responses": { "200": ok("JSON Schema of a clip request", &json!({ "type": "object" })) },
+                },
+            },
+            "/metrics": {
+                "get": {
+                    "operationId": "getMetrics",
+                    "responses": { "200": { "description": "Prometheus metrics", "content": { "text/plain": { "schema": { "type": "string" } } } } },
+                },
+            },
+            "/health": {
+                "get": {
+                    "operationId": "getHealth",
+                    "security": open,
+                    "responses": { "200": { "description": "The server is up", "content": { "text/plain": { "schema": { "type": "string" } } } } },
+                },
+            },
+            "/openapi.json": {
+                "get": {
+                    "operationId": "getOpenApi",
+                    "security": open,
+                    "responses": { "200": ok("This document", &json!({ "type": "object" })) },
+                },
+            },
+        },
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "bearer": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "An API key or an HS256 JWT, when the server has either",
+                },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(target)) = map.get("$ref") {
+                    found.push(target);
+                }
+                map.values().for_each(|value| refs(value, found));
+            }
+            Value::Array(items) => items.iter().for_each(|value| refs(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_every_ref_resolves() {
+        let document = document();
+        let mut found = Vec::new();
+        refs(&document, &mut found);
+        assert!(found.contains(&"#/components/schemas/Job"));
+        for target in found {
+            let name = target.strip_prefix("#/components/schemas/").unwrap_or(target);
+            assert!(document["components"]["schemas"].get(name).is_some(), "{} doesn't resolve", target);
+        }
+    }
+
+    #[test]
+    fn test_documents_the_types_routes_use() {
+        let document = document();
+        let schemas = &document["components"]["schemas"];
+        assert_eq!(schemas["JobStatus"]["enum"], json!(["queued", "running", "succeeded", "failed", "cancelled"]));
+        assert!(schemas["ClipResult"]["properties"]["output_file"].is_object());
+        assert!(schemas["ErrorBody"]["properties"]["code"].is_object());
+        assert_eq!(document["paths"]["/clips"]["post"]["responses"]["202"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/Job");
+        assert_eq!(document["paths"]["/health"]["get"]["security"], json!([]));
+    }
+
+    #[test]
+    fn test_schema_descriptions_are_the_types_own() {
+        let document = document();
+        let schemas = &document["components"]["schemas"];
+        assert_eq!(schemas["ErrorBody"]["description"], "The body of every error response");
+        // Module overviews document their modules, not the types that open them
+        for name in ["JobStatus", "Upload", "VideoCodec", "FitMode"] {
+            assert!(schemas[name].get("description").is_none(), "{} has {}", name, schemas[name]["description"]);
+        }
+    }
+}
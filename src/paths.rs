@@ -0,0 +1,148 @@
+use std::path::Path;
+
+/// Input path cleanup
+/// Paths reach the clipper in whatever shape the user's tools produce them:
+/// `file://` URLs copied from a browser or file manager, Windows paths pasted
+/// into a WSL shell, and drag-and-drop strings with shell escapes and quotes.
+/// `InputPath::normalize` turns them into a plain local path before the file
+/// is looked for.
+
+#[derive(Debug, Clone)]
+pub struct InputPath;
+
+impl InputPath {
+    /// `raw` as a path this process can open; a file that exists as written
+    /// is left alone, however odd its name
+    pub fn normalize(raw: &str) -> String {
+        if Path::new(raw).exists() {
+            return raw.to_string();
+        }
+        normalize_for(raw, is_wsl())
+    }
+}
+
+const WSL_MOUNT_ROOT: &str = "/mnt";
+
+fn is_wsl() -> bool {
+    if !cfg!(target_os = "linux") {
+        return false;
+    }
+    std::env::var_os("WSL_DISTRO_NAME").is_some()
+        || std::fs::read_to_string("/proc/sys/kernel/osrelease").is_ok_and(|release| release.to_lowercase().contains("microsoft"))
+}
+
+fn normalize_for(raw: &str, wsl: bool) -> String {
+    let path = strip_quotes(raw.trim());
+    if let Some(url) = path.strip_prefix("file://") {
+        return from_file_url(url, wsl);
+    }
+    if is_windows_path(path) {
+        return if wsl { to_wsl(path) } else { path.to_string() };
+    }
+    if cfg!(windows) {
+        path.to_string()
+    } else {
+        unescape(path)
+    }
+}
+
+/// Terminals wrap dropped paths with spaces in one kind of quote
+fn strip_quotes(path: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = path.strip_prefix(quote).and_then(|p| p.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    path
+}
+
+/// `C:\...`, `C:/...` or `\\server\share`
+fn is_windows_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let drive = bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && matches!(bytes[2], b'\\' | b'/');
+    drive || path.starts_with("\\\\")
+}
+
+/// `C:\Users\me\a.mp4` → `/mnt/c/Users/me/a.mp4`; UNC paths have no mount and are kept
+fn to_wsl(path: &str) -> String {
+    if path.starts_with("\\\\") {
+        return path.to_string();
+    }
+    let drive = path[..1].to_lowercase();
+    format!("{}/{}/{}", WSL_MOUNT_ROOT, drive, path[3..].replace('\\', "/"))
+}
+
+/// `file:///home/me/My%20Video.mp4` → `/home/me/My Video.mp4`
+fn from_file_url(url: &str, wsl: bool) -> String {
+    // The host is empty or `localhost` for local files
+    let path = url.strip_prefix("localhost").unwrap_or(url);
+    let path = percent_decode(path);
+    // `file:///C:/Users/...` is a Windows path behind a slash
+    match path.strip_prefix('/') {
+        Some(windows) if is_windows_path(windows) => if wsl { to_wsl(windows) } else { windows.to_string() },
+        _ => path,
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok()).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// `My\ Video\ \(1\).mp4` → `My Video (1).mp4`
+fn unescape(path: &str) -> String {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_urls() {
+        assert_eq!(normalize_for("file:///home/me/My%20Video%20%231.mp4", false), "/home/me/My Video #1.mp4");
+        assert_eq!(normalize_for("file://localhost/srv/clips/a.mp4", false), "/srv/clips/a.mp4");
+        assert_eq!(normalize_for("file:///C:/Users/me/Videos/a%C3%A9.mp4", false), "C:/Users/me/Videos/aé.mp4");
+        assert_eq!(normalize_for("file:///C:/Users/me/a.mp4", true), "/mnt/c/Users/me/a.mp4");
+        assert_eq!(normalize_for("file:///tmp/100%.mp4", false), "/tmp/100%.mp4");
+    }
+
+    #[test]
+    fn test_windows_paths_under_wsl() {
+        assert_eq!(normalize_for("C:\\Users\\me\\Videos\\talk.mp4", true), "/mnt/c/Users/me/Videos/talk.mp4");
+        assert_eq!(normalize_for("\"D:\\Recordings\\game day.mkv\"", true), "/mnt/d/Recordings/game day.mkv");
+        assert_eq!(normalize_for("C:\\Users\\me\\talk.mp4", false), "C:\\Users\\me\\talk.mp4");
+        assert_eq!(normalize_for("\\\\nas\\share\\talk.mp4", true), "\\\\nas\\share\\talk.mp4");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_drag_and_drop_strings() {
+        assert_eq!(normalize_for("/home/me/My\\ Video\\ \\(1\\).mp4 ", false), "/home/me/My Video (1).mp4");
+        assert_eq!(normalize_for("  '/home/me/My Video.mp4'", false), "/home/me/My Video.mp4");
+        assert_eq!(normalize_for("videos/talk.mp4", false), "videos/talk.mp4");
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::{VideoClipError, Result};
+use crate::ranges::TimeRange;
+use crate::time_parser::TimeParser;
+use crate::video_clipper::ClipRequest;
+use std::str::FromStr;
+
+/// Natural-language ranges
+/// Chat-driven frontends get ranges the way people say them: "from 36
+/// minutes 7 seconds to 37:19", "the last 90 seconds", "30 seconds starting
+/// at 1:02:00", "from 5 minutes to the end". Times and lengths are read by
+/// [`TimeParser::parse_spoken`]. Ranges counted from the end of the source
+/// need its duration, which [`request_from_phrase`] probes; with the
+/// duration known, ranges are also checked against it.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangePhrase {
+    /// "from 36:07 to 37:19", "between 1 minute and 2 minutes"
+    Between { start: f64, end: f64 },
+    /// "the first 90 seconds"
+    First(f64),
+    /// "the last 90 seconds"
+    Last(f64),
+    /// "from 5 minutes to the end", "10:00 onwards"
+    ToEnd(f64),
+    /// "30 seconds starting at 1:00", "from 1:00 for 30 seconds"
+    Starting { start: f64, length: f64 },
+    /// "30 seconds before 2:00", "30 seconds leading up to 2:00"
+    Before { end: f64, length: f64 },
+}
+
+/// Leading words that say what to do rather than which range
+const FILLER: [&str; 11] = ["clip", "cut", "grab", "take", "keep", "give", "get", "me", "just", "please", "out"];
+/// Ways of saying the words ranges are split at
+const CONNECTIVES: [(&str, &str); 5] = [
+    ("starting at", "at"),
+    ("starting from", "at"),
+    ("beginning at", "at"),
+    ("leading up to", "before"),
+    ("up to", "to"),
+];
+/// What a range is of, after it
+const SUBJECTS: [&str; 5] = ["video", "recording", "file", "clip", "it"];
+
+impl FromStr for RangePhrase {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lower = s.trim().trim_end_matches(['.', '!', '?']).to_lowercase().replace(['–', '—'], " - ");
+        for (phrase, word) in CONNECTIVES {
+            lower = lower.replace(phrase, word);
+        }
+        let mut words: Vec<&str> = lower.split_whitespace().filter(|&w| w != "the").collect();
+        let first_meaningful = words.iter().position(|w| !FILLER.contains(w)).unwrap_or(words.len());
+        words.drain(..first_meaningful);
+        if let [rest @ .., "of", subject] = words.as_slice() {
+            if SUBJECTS.contains(subject) {
+                words.truncate(rest.len());
+            }
+        }
+        parse_words(&words).ok_or_else(|| VideoClipError::InvalidOptions(format!(
+            "couldn't read a range from '{}' (try e.g. 'from 36:07 to 37:19' or 'the last 90 seconds')",
+            s.trim()
+        )))
+    }
+}
+
+impl RangePhrase {
+    /// Whether the range is counted from the end of the source
+    pub fn needs_duration(&self) -> bool {
+        matches!(self, RangePhrase::Last(_) | RangePhrase::ToEnd(_))
+    }
+
+    /// The range in seconds, in a source lasting `duration` seconds when
+    /// that's known; a range from the end clamps to the start of a shorter
+    /// source, but one starting past the end is an error
+    pub fn resolve(&self, duration: Option<f64>) -> Result<TimeRange> {
+        let duration_needed = || VideoClipError::InvalidOptions("the source's duration is needed for a range counted from its end".to_string());
+        let range = match *self {
+            RangePhrase::Between { start, end } => TimeRange::new(start, end)?,
+            RangePhrase::First(length) => TimeRange::new(0.0, duration.map_or(length, |d| length.min(d)))?,
+            RangePhrase::Last(length) => {
+                let duration = duration.ok_or_else(duration_needed)?;
+                TimeRange::new((duration - length).max(0.0), duration)?
+            }
+            RangePhrase::ToEnd(start) => TimeRange::new(start, duration.ok_or_else(duration_needed)?)?,
+            RangePhrase::Starting { start, length } => TimeRange::new(start, start + length)?,
+            RangePhrase::Before { end, length } => TimeRange::new((end - length).max(0.0), end)?,
+        };
+        match duration {
+            Some(duration) if range.start >= duration => Err(VideoClipError::InvalidOptions(format!(
+                "{} is past the end of the source, which is {} long",
+                TimeParser::format_time_readable(range.start),
+                TimeParser::format_time_readable(duration)
+            ))),
+            Some(duration) => Ok(TimeRange { end: range.end.min(duration), ..range }),
+            None => Ok(range),
+        }
+    }
+}
+
+/// A request to clip `input_file` over the range `phrase` describes,
+/// validated, with the source probed for its duration
+#[cfg(not(feature = "wasm"))]
+pub fn request_from_phrase(input_file: &str, phrase: &str) -> Result<ClipRequest> {
+    let phrase: RangePhrase = phrase.parse()?;
+    let duration = crate::probe::probe(input_file).ok().and_then(|info| info.duration);
+    request_for(input_file, &phrase, duration)
+}
+
+/// A validated request to clip `input_file` over `phrase`, in a source
+/// lasting `duration` seconds when that's known
+pub fn request_for(input_file: &str, phrase: &RangePhrase, duration: Option<f64>) -> Result<ClipRequest> {
+    let range = phrase.resolve(duration)?;
+    let request = ClipRequest {
+        input_file: input_file.to_string(),
+        start_time: range.start.to_string(),
+        end_time: range.end.to_string(),
+        ..Default::default()
+    };
+    match request.validate() {
+        Ok(()) => Ok(request),
+        Err(problems) => Err(VideoClipError::InvalidOptions(
+            problems.iter().map(|p| p.message.as_str()).collect::<Vec<_>>().join("; "),
+        )),
+    }
+}
+
+fn parse_words(words: &[&str]) -> Option<RangePhrase> {
+    let spoken = |words: &[&str]| (!words.is_empty()).then(|| TimeParser::parse_spoken(&words.join(" ")).ok()).flatten();
+    match words {
+        ["first", length @ ..] => return spoken(length).map(RangePhrase::First),
+        ["last" | "final", length @ ..] => return spoken(length).map(RangePhrase::Last),
+        ["between", rest @ ..] => return split_at(rest, &["and"], spoken).map(|(start, end)| RangePhrase::Between { start, end }),
+        _ => {}
+    }
+    let words = words.strip_prefix(&["from"]).or_else(|| words.strip_prefix(&["at"])).unwrap_or(words);
+    match words {
+        [start @ .., "to" | "until" | "till" | "through" | "-", "end"] | [start @ .., "onwards" | "onward" | "on"] => {
+            return spoken(start).map(RangePhrase::ToEnd);
+        }
+        [length @ .., "from" | "before", "end"] => return spoken(length).map(RangePhrase::Last),
+        _ => {}
+    }
+    if let Some((start, end)) = split_at(words, &["to", "until", "till", "through", "-"], spoken) {
+        return Some(RangePhrase::Between { start, end });
+    }
+    if let Some((start, length)) = split_at(words, &["for"], spoken) {
+        return Some(RangePhrase::Starting { start, length });
+    }
+    if let Some((length, start)) = split_at(words, &["from", "after", "at"], spoken) {
+        return Some(RangePhrase::Starting { start, length });
+    }
+    split_at(words, &["before"], spoken).map(|(length, end)| RangePhrase::Before { end, length })
+}
+
+/// Both sides of the first `separators` word that leaves two readable times
+fn split_at(words: &[&str], separators: &[&str], spoken: impl Fn(&[&str]) -> Option<f64>) -> Option<(f64, f64)> {
+    (0..words.len())
+        .filter(|&i| separators.contains(&words[i]))
+        .find_map(|i| Some((spoken(&words[..i])?, spoken(&words[i + 1..])?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(phrase: &str, duration: Option<f64>) -> (f64, f64) {
+        let range = phrase.parse::<RangePhrase>().unwrap().resolve(duration).unwrap();
+        (range.start, range.end)
+    }
+
+    #[test]
+    fn test_read_phrases() {
+        assert_eq!(range("from 36 minutes 7 seconds to 37:19", None), (2167.0, 2239.0));
+        assert_eq!(range("Clip 36:07 - 37:19.", None), (2167.0, 2239.0));
+        assert_eq!(range("between 1 minute and 30 seconds and 2 minutes", None), (90.0, 120.0));
+        assert_eq!(range("the last 90 seconds", Some(600.0)), (510.0, 600.0));
+        assert_eq!(range("give me the first half a minute of the video", None), (0.0, 30.0));
+        assert_eq!(range("from 5 minutes to the end", Some(600.0)), (300.0, 600.0));
+        assert_eq!(range("9:00 onwards", Some(600.0)), (540.0, 600.0));
+        assert_eq!(range("30 seconds starting at 1:02:00", None), (3720.0, 3750.0));
+        assert_eq!(range("from 1:00 for a minute and a half", None), (60.0, 150.0));
+        assert_eq!(range("ten seconds after 2:00", None), (120.0, 130.0));
+        assert_eq!(range("20 seconds leading up to 1:00", None), (40.0, 60.0));
+        assert_eq!(range("a minute starting from 2 minutes", None), (120.0, 180.0));
+        assert_eq!(range("2 minutes from the end", Some(600.0)), (480.0, 600.0));
+        assert_eq!(range("from 1:00 up to 1:30", None), (60.0, 90.0));
+        assert!("a nice bit in the middle".parse::<RangePhrase>().is_err());
+        assert!("from 2:00 to".parse::<RangePhrase>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_against_the_duration() {
+        let last: RangePhrase = "last 2 minutes".parse().unwrap();
+        assert!(last.needs_duration());
+        assert!(last.resolve(None).is_err());
+        assert_eq!(last.resolve(Some(90.0)).unwrap(), TimeRange { start: 0.0, end: 90.0 });
+
+        let late: RangePhrase = "from 12:00 to 13:00".parse().unwrap();
+        assert!(late.resolve(Some(600.0)).unwrap_err().to_string().contains("past the end"));
+        assert_eq!(range("from 9:00 to 11:00", Some(600.0)), (540.0, 600.0));
+        assert!("from 2:00 to 1:00".parse::<RangePhrase>().unwrap().resolve(None).is_err());
+
+        let request = request_for("talk.mp4", &last, Some(600.0)).unwrap();
+        assert_eq!((request.start_time.as_str(), request.end_time.as_str()), ("480", "600"));
+        assert!(request_for("talk.mp4", &"from 2:00 to 1:00".parse().unwrap(), None).is_err());
+    }
+}
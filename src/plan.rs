@@ -0,0 +1,423 @@
+#[cfg(not(feature = "wasm"))]
+use crate::analysis::Analysis;
+use crate::estimate::{mp4_carries, ClipEstimate};
+use crate::probe::MediaInfo;
+use crate::ranges::TimeRange;
+use crate::time_parser::TimeParser;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Clip plans
+/// Clipping happens in two phases. Planning resolves everything about a
+/// clip (the final times, the output file, the codecs, the filter chains
+/// and the exact FFmpeg commands) without running anything; execution runs
+/// a plan's commands and reports the result. A plan serializes to JSON, so
+/// it can be inspected, edited (an extra encoder option, a different output
+/// name) and handed back for execution. Plans cover clips made by a single
+/// FFmpeg run; features needing in-process steps between commands
+/// (renditions, smart cut, chunking, bumpers, attribution images, quality
+/// gates and output checks) are refused at planning. A plan also carries
+/// what the saved [`crate::analysis`] stages say about its range, and
+/// [`ClipPlan::explain`] tells a user what running it will do, and why.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipPlan {
+    pub input_file: String,
+    pub output_file: String,
+    /// The range cut, after black trimming and handles
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    /// Video encoder, or `copy`
+    pub video_codec: String,
+    /// Audio codec, or `copy`
+    pub audio_codec: String,
+    /// The video filter chain, when the video is encoded
+    pub video_filters: Option<String>,
+    /// The audio filter chain, when there is one
+    pub audio_filters: Option<String>,
+    /// Run in order; the last one writes `output_file`
+    pub commands: Vec<PlannedCommand>,
+    /// Found while planning
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    #[serde(default)]
+    pub analysis: Option<RangeAnalysis>,
+    /// The source's codecs and bitrate, when it could be probed
+    #[serde(default)]
+    pub source: Option<PlanSource>,
+}
+
+/// What planning found out about the source
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlanSource {
+    /// Codec of the first video stream
+    pub video_codec: Option<String>,
+    /// Codec of the first audio stream
+    pub audio_codec: Option<String>,
+    /// Overall bitrate, in bits per second
+    pub bit_rate: Option<u64>,
+}
+
+impl PlanSource {
+    pub fn from_media_info(info: &MediaInfo) -> Self {
+        Self {
+            video_codec: info.video_stream().and_then(|s| s.codec_name.clone()),
+            audio_codec: info.audio_stream().and_then(|s| s.codec_name.clone()),
+            bit_rate: info.effective_bit_rate(),
+        }
+    }
+
+    /// Whether the source's audio has to be re-encoded to go into `output_file`
+    pub fn audio_needs_encoding(&self, output_file: &str) -> bool {
+        output_file.to_lowercase().ends_with(".mp4") && self.audio_codec.as_deref().is_some_and(|codec| !mp4_carries("audio", codec))
+    }
+}
+
+/// A plan told the way a user would want to hear it before it runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PlanExplanation {
+    /// The whole plan in a sentence
+    pub summary: String,
+    /// What happens to the video, and why
+    pub video: String,
+    /// What happens to the audio, and why
+    pub audio: String,
+    /// The clip written: its length and, for a stream copy, rough size
+    pub output: String,
+    pub estimated_size_mb: Option<f64>,
+    /// Planning warnings and what the saved analysis says about the range
+    pub notes: Vec<String>,
+}
+
+impl fmt::Display for PlanExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.summary)?;
+        for note in &self.notes {
+            write!(f, "\n- {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// One program run of a plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl PlannedCommand {
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self { program: program.into(), args }
+    }
+
+    /// The file the command writes: its last argument
+    pub fn output(&self) -> Option<&str> {
+        self.args.last().map(String::as_str)
+    }
+
+    /// Adds options for the output, just before the output file
+    pub fn add_output_args(&mut self, args: impl IntoIterator<Item = String>) {
+        let at = self.args.len().saturating_sub(1);
+        self.args.splice(at..at, args);
+    }
+
+    /// Adds options for the first input, just before its `-i`
+    pub fn add_input_args(&mut self, args: impl IntoIterator<Item = String>) {
+        let at = self.args.iter().position(|arg| arg == "-i").unwrap_or(0);
+        self.args.splice(at..at, args);
+    }
+
+    pub fn command_string(&self) -> String {
+        let mut argv = vec![self.program.clone()];
+        argv.extend(self.args.iter().cloned());
+        argv.join(" ")
+    }
+}
+
+impl ClipPlan {
+    /// The commands, joined as a shell would run them
+    pub fn command_string(&self) -> String {
+        self.commands.iter().map(PlannedCommand::command_string).collect::<Vec<_>>().join(" && ")
+    }
+
+    /// What running the plan will do and why, e.g. "Will stream-copy video
+    /// (h264), re-encode audio to AAC because the source's is PCM, which MP4
+    /// can't carry, and write a 01:30 clip of ~12 MB"
+    pub fn explain(&self) -> PlanExplanation {
+        let source = self.source.clone().unwrap_or_default();
+        let in_brackets = |codec: Option<&str>| codec.map(|codec| format!(" ({})", codec_label(codec))).unwrap_or_default();
+
+        let video = match self.video_codec.as_str() {
+            "copy" => format!("stream-copy video{}", in_brackets(source.video_codec.as_deref())),
+            encoder => format!(
+                "re-encode video{} with {}{}",
+                source.video_codec.as_deref().map(|codec| format!(" from {}", codec_label(codec))).unwrap_or_default(),
+                encoder,
+                self.video_filters.as_deref().map(|graph| format!(", applying {}", filter_names(graph))).unwrap_or_default()
+            ),
+        };
+        let audio = match (self.audio_codec.as_str(), &source.audio_codec) {
+            (_, None) if self.source.is_some() => "carry no audio (the source has none)".to_string(),
+            ("copy", codec) => format!("stream-copy audio{}", in_brackets(codec.as_deref())),
+            (encoder, codec) => {
+                let reason = match (&self.audio_filters, codec) {
+                    (Some(graph), _) => format!(" to apply {}", filter_names(graph)),
+                    (None, Some(codec)) if source.audio_needs_encoding(&self.output_file) => {
+                        format!(" because the source's is {}, which MP4 can't carry", codec_label(codec))
+                    }
+                    _ => String::new(),
+                };
+                format!("re-encode audio to {}{}", codec_label(encoder), reason)
+            }
+        };
+
+        let duration = self.end_seconds - self.start_seconds;
+        // A re-encode's size depends on the encoder's settings, not the source's
+        let estimated_size_mb = source.bit_rate
+            .filter(|_| self.video_codec == "copy")
+            .map(|bps| bps as f64 * duration / 8.0 / (1024.0 * 1024.0));
+        let output = format!(
+            "a {} clip{}",
+            TimeParser::format_time_readable(duration),
+            estimated_size_mb.map(|mb| format!(" of ~{:.0} MB", mb.max(1.0))).unwrap_or_default()
+        );
+
+        let mut notes = self.warnings.clone();
+        if let Some(analysis) = &self.analysis {
+            if let Some(keyframe) = analysis.estimate.keyframe_start.filter(|&k| self.video_codec == "copy" && k < self.start_seconds) {
+                notes.push(format!(
+                    "The stream copy starts at the keyframe at {:.3}s, {:.3}s before the requested start",
+                    keyframe,
+                    self.start_seconds - keyframe
+                ));
+            }
+            if let Some(chapter) = &analysis.chapter {
+                notes.push(format!("The range starts in the chapter \"{}\"", chapter));
+            }
+        }
+
+        PlanExplanation {
+            summary: format!("Will {}, {}, and write {}", video, audio, output),
+            video,
+            audio,
+            output,
+            estimated_size_mb,
+            notes,
+        }
+    }
+
+    /// Adds options to the command writing the clip, before its output file
+    pub fn add_output_args(&mut self, args: impl IntoIterator<Item = String>) {
+        if let Some(last) = self.commands.last_mut() {
+            last.add_output_args(args);
+        }
+    }
+}
+
+/// How a codec is usually called: `AAC` for `aac`, `PCM` for any `pcm_*`
+fn codec_label(codec: &str) -> String {
+    match codec {
+        _ if codec.starts_with("pcm_") => "PCM".to_string(),
+        "aac" | "mp3" | "ac3" | "eac3" | "flac" | "alac" => codec.to_uppercase(),
+        "opus" => "Opus".to_string(),
+        _ => codec.to_string(),
+    }
+}
+
+/// The filters of a chain or graph by name, e.g. `scale and pad`
+fn filter_names(graph: &str) -> String {
+    let mut names: Vec<&str> = Vec::new();
+    for filter in split_filters(graph) {
+        // Past the input labels of a graph's filters, to the name
+        let mut filter = filter.trim();
+        while let Some(rest) = filter.strip_prefix('[').and_then(|f| f.split_once(']')).map(|(_, rest)| rest) {
+            filter = rest;
+        }
+        let name = filter.split(['=', '[']).next().unwrap_or_default().trim();
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    match names.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => "filters".to_string(),
+    }
+}
+
+/// The filters of a graph, split at the commas and semicolons between them
+/// but not at escaped or quoted ones inside their options (`between(t\,1\,2)`)
+fn split_filters(graph: &str) -> Vec<&str> {
+    let mut filters = Vec::new();
+    let (mut start, mut escaped, mut quoted) = (0, false, false);
+    for (i, c) in graph.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\'' => quoted = !quoted,
+            ',' | ';' if !quoted => {
+                filters.push(&graph[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    filters.push(&graph[start..]);
+    filters
+}
+
+/// What the analysis of a source says about clipping one range of it: what
+/// a stream copy would really do, the chapter the range starts in, and the
+/// shot changes, silences and loudness inside it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeAnalysis {
+    pub estimate: ClipEstimate,
+    /// Title of the chapter the range starts in
+    pub chapter: Option<String>,
+    /// Shot changes inside the range
+    pub scenes: Vec<f64>,
+    /// Silences overlapping the range, cut to it
+    pub silences: Vec<TimeRange>,
+    /// Integrated loudness of the range's audible parts, in LUFS
+    pub loudness_lufs: Option<f64>,
+}
+
+/// Momentary loudness below this is silence and left out of the average,
+/// as EBU R128's absolute gate does
+#[cfg(not(feature = "wasm"))]
+const LOUDNESS_GATE_LUFS: f64 = -70.0;
+
+#[cfg(not(feature = "wasm"))]
+impl RangeAnalysis {
+    pub fn from_analysis(start: f64, end: f64, analysis: &Analysis) -> Self {
+        let inside = |t: f64| t >= start && t <= end;
+        let chapter = analysis.chapters.iter()
+            .find(|c| c.start <= start && start < c.end)
+            .and_then(|c| c.title.clone());
+        let silences = analysis.silences.iter()
+            .filter(|s| s.end > start && s.start < end)
+            .map(|s| TimeRange { start: s.start.max(start), end: s.end.min(end) })
+            .collect();
+
+        // Averaged as energy, not in LU, so quiet stretches don't drag it down
+        let audible: Vec<f64> = analysis.loudness.iter()
+            .filter(|s| inside(s.time) && s.loudness > LOUDNESS_GATE_LUFS)
+            .map(|s| 10f64.powf(s.loudness / 10.0))
+            .collect();
+        let loudness_lufs = (!audible.is_empty())
+            .then(|| 10.0 * (audible.iter().sum::<f64>() / audible.len() as f64).log10());
+
+        Self {
+            estimate: ClipEstimate::from_media_info(&analysis.info, start, end, &analysis.keyframes),
+            chapter,
+            scenes: analysis.scenes.iter().copied().filter(|&t| inside(t)).collect(),
+            silences,
+            loudness_lufs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "wasm"))]
+    #[test]
+    fn test_range_analysis() {
+        use crate::analysis::Analysis;
+        use crate::highlights::LoudnessSample;
+        use crate::probe::{Chapter, MediaInfo};
+
+        let analysis = Analysis {
+            info: MediaInfo { format_name: "mov,mp4".to_string(), duration: Some(3600.0), ..Default::default() },
+            keyframes: (0..1800).map(|i| i as f64 * 2.0).collect(),
+            chapters: vec![Chapter { start: 0.0, end: 600.0, title: Some("Intro".to_string()) }],
+            scenes: vec![50.0, 65.5, 200.0],
+            silences: vec![TimeRange { start: 58.0, end: 61.0 }, TimeRange { start: 300.0, end: 310.0 }],
+            loudness: vec![
+                LoudnessSample { time: 61.0, loudness: -20.0 },
+                LoudnessSample { time: 62.0, loudness: -20.0 },
+                LoudnessSample { time: 63.0, loudness: -120.7 },
+                LoudnessSample { time: 500.0, loudness: -5.0 },
+            ],
+        };
+        let range = RangeAnalysis::from_analysis(60.5, 90.0, &analysis);
+        assert_eq!(range.estimate.keyframe_start, Some(60.0));
+        assert_eq!(range.chapter.as_deref(), Some("Intro"));
+        assert_eq!(range.scenes, vec![65.5]);
+        assert_eq!(range.silences, vec![TimeRange { start: 60.5, end: 61.0 }]);
+        assert!((range.loudness_lufs.unwrap() + 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_injected_args() {
+        let mut plan = ClipPlan {
+            input_file: "talk.mp4".to_string(),
+            output_file: "out/talk_clip.mp4".to_string(),
+            start_seconds: 60.0,
+            end_seconds: 90.0,
+            video_codec: "copy".to_string(),
+            audio_codec: "copy".to_string(),
+            video_filters: None,
+            audio_filters: None,
+            commands: vec![PlannedCommand::new("ffmpeg", ["-i", "talk.mp4", "-c", "copy", "-y", "out/talk_clip.mp4"].map(String::from).to_vec())],
+            warnings: Vec::new(),
+            analysis: None,
+            source: None,
+        };
+        plan.add_output_args(["-movflags".to_string(), "+faststart".to_string()]);
+        plan.commands[0].add_input_args(["-readrate".to_string(), "2".to_string()]);
+        assert_eq!(
+            plan.command_string(),
+            "ffmpeg -readrate 2 -i talk.mp4 -c copy -y -movflags +faststart out/talk_clip.mp4"
+        );
+        assert_eq!(plan.commands[0].output(), Some("out/talk_clip.mp4"));
+
+        let json = serde_json::to_string(&plan).unwrap();
+        assert_eq!(serde_json::from_str::<ClipPlan>(&json).unwrap(), plan);
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut plan = ClipPlan {
+            input_file: "talk.mov".to_string(),
+            output_file: "out/talk_clip.mp4".to_string(),
+            start_seconds: 60.0,
+            end_seconds: 150.0,
+            video_codec: "copy".to_string(),
+            audio_codec: "aac".to_string(),
+            video_filters: None,
+            audio_filters: None,
+            commands: Vec::new(),
+            warnings: vec!["Source has a variable frame rate".to_string()],
+            analysis: None,
+            source: Some(PlanSource { video_codec: Some("h264".to_string()), audio_codec: Some("pcm_s16le".to_string()), bit_rate: Some(8 * 1024 * 1024 * 2 / 15) }),
+        };
+        let explanation = plan.explain();
+        assert_eq!(
+            explanation.summary,
+            "Will stream-copy video (h264), re-encode audio to AAC because the source's is PCM, which MP4 can't carry, and write a 01:30 clip of ~12 MB"
+        );
+        assert_eq!(explanation.to_string().lines().nth(1), Some("- Source has a variable frame rate"));
+
+        plan.video_codec = "libx264".to_string();
+        plan.video_filters = Some("scale=1080:-2,pad=1080:1920:(ow-iw)/2:(oh-ih)/2,fps=30".to_string());
+        plan.audio_filters = Some("[0:a]volume=3dB[a0];[a0][1:a]amix=inputs=2[mix]".to_string());
+        let explanation = plan.explain();
+        assert_eq!(explanation.video, "re-encode video from h264 with libx264, applying scale, pad and fps");
+        assert_eq!(explanation.audio, "re-encode audio to AAC to apply volume and amix");
+
+        plan.video_filters = Some("drawbox=x=10:y=10:w=60:h=64:t=fill:enable='between(t\\,11\\,12)+between(t\\,15\\,16.5)',scale=1080:-2".to_string());
+        assert_eq!(plan.explain().video, "re-encode video from h264 with libx264, applying drawbox and scale");
+        let window = crate::ffmpeg::filter_graph::time_window(Some(11.0), Some(12.0), 0.0).unwrap();
+        plan.video_filters = Some(format!("drawbox=w=60:h=64:enable={},boxblur=10", window));
+        assert_eq!(plan.explain().video, "re-encode video from h264 with libx264, applying drawbox and boxblur");
+        assert_eq!((explanation.output.as_str(), explanation.estimated_size_mb), ("a 01:30 clip", None));
+
+        plan.source = None;
+        plan.audio_codec = "copy".to_string();
+        assert_eq!(plan.explain().audio, "stream-copy audio");
+    }
+}
@@ -0,0 +1,303 @@
+use crate::error::{VideoClipError, Result};
+use crate::ranges::TimeRange;
+use crate::time_parser::TimeParser;
+use crate::video_clipper::ClipRequest;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Batch pre-flight
+/// Checks every range of a batch against its source's duration before any
+/// FFmpeg work starts, so a manifest with hundreds of entries fails (or is
+/// repaired) in seconds rather than halfway through the run. Each source is
+/// probed once however many ranges it has.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IssueKind {
+    /// A start or end time that doesn't parse
+    InvalidTime,
+    /// End at or before start
+    EmptyRange,
+    /// Starts at or after the end of the source
+    StartOutOfBounds { source_duration: f64 },
+    /// Ends after the end of the source; fixable by clamping
+    EndOutOfBounds { source_duration: f64 },
+    /// Shares time with another range of the same source; only a warning
+    Overlap { other: usize },
+    /// The source's duration couldn't be probed
+    Unprobed,
+}
+
+impl IssueKind {
+    /// Warnings don't stop a batch
+    pub fn is_error(&self) -> bool {
+        !matches!(self, IssueKind::Overlap { .. } | IssueKind::Unprobed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightIssue {
+    /// Position of the request in the batch
+    pub index: usize,
+    pub input_file: String,
+    pub start_time: String,
+    pub end_time: String,
+    #[serde(flatten)]
+    pub kind: IssueKind,
+}
+
+impl PreflightIssue {
+    pub fn describe(&self) -> String {
+        let range = format!("#{} {} [{} - {}]", self.index + 1, self.input_file, self.start_time, self.end_time);
+        match &self.kind {
+            IssueKind::InvalidTime => format!("{}: invalid time", range),
+            IssueKind::EmptyRange => format!("{}: zero-length or reversed range", range),
+            IssueKind::StartOutOfBounds { source_duration } => format!(
+                "{}: starts after the source ends ({})", range, TimeParser::format_time_readable(*source_duration)
+            ),
+            IssueKind::EndOutOfBounds { source_duration } => format!(
+                "{}: ends after the source ends ({})", range, TimeParser::format_time_readable(*source_duration)
+            ),
+            IssueKind::Overlap { other } => format!("{}: overlaps #{}", range, other + 1),
+            IssueKind::Unprobed => format!("{}: couldn't read the source's duration", range),
+        }
+    }
+}
+
+/// What to do with ranges that fail pre-flight
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum PreflightAction {
+    /// Report and stop the batch if any range is invalid
+    #[default]
+    Check,
+    /// Clamp ends to the source duration; drop what can't be repaired
+    Fix,
+    /// Drop every invalid range
+    Skip,
+}
+
+impl FromStr for PreflightAction {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "check" => Ok(PreflightAction::Check),
+            "fix" => Ok(PreflightAction::Fix),
+            "skip" => Ok(PreflightAction::Skip),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown pre-flight action '{}'", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreflightReport {
+    pub action: PreflightAction,
+    pub issues: Vec<PreflightIssue>,
+    /// Requests whose end was clamped to the source duration
+    pub fixed: Vec<usize>,
+    /// Requests dropped from the batch
+    pub skipped: Vec<usize>,
+    /// The batch to run: everything that passed or was fixed, in order
+    #[serde(skip)]
+    pub requests: Vec<ClipRequest>,
+}
+
+impl PreflightReport {
+    pub fn errors(&self) -> usize {
+        self.issues.iter().filter(|i| i.kind.is_error()).count()
+    }
+
+    /// Whether the batch may run: always after fixing or skipping, and
+    /// only without errors for a plain check
+    pub fn is_ok(&self) -> bool {
+        self.action != PreflightAction::Check || self.errors() == 0
+    }
+}
+
+/// Checks `requests` against `durations` (seconds by input file; missing
+/// sources are reported but not held against their ranges) and applies `action`.
+/// Overlaps count each range's pre- and post-roll handles, trimmed to the
+/// source as clipping trims them.
+pub fn check_requests(requests: &[ClipRequest], durations: &HashMap<String, f64>, action: PreflightAction) -> PreflightReport {
+    let mut report = PreflightReport { action, ..Default::default() };
+    let mut ranges: Vec<Option<TimeRange>> = Vec::with_capacity(requests.len());
+
+    for (index, request) in requests.iter().enumerate() {
+        let issue = |kind| PreflightIssue {
+            index,
+            input_file: request.input_file.clone(),
+            start_time: request.start_time.clone(),
+            end_time: request.end_time.clone(),
+            kind,
+        };
+
+        let times = TimeParser::parse_to_seconds(&request.start_time)
+            .and_then(|start| Ok((start, TimeParser::parse_to_seconds(&request.end_time)?)));
+        let Ok((start, end)) = times else {
+            report.issues.push(issue(IssueKind::InvalidTime));
+            ranges.push(None);
+            continue;
+        };
+        if end <= start {
+            report.issues.push(issue(IssueKind::EmptyRange));
+            ranges.push(None);
+            continue;
+        }
+
+        let mut request = request.clone();
+        let mut range = TimeRange { start, end };
+        match durations.get(&request.input_file).copied() {
+            None => report.issues.push(issue(IssueKind::Unprobed)),
+            Some(source_duration) if start >= source_duration => {
+                report.issues.push(issue(IssueKind::StartOutOfBounds { source_duration }));
+                ranges.push(None);
+                continue;
+            }
+            Some(source_duration) if end > source_duration => {
+                report.issues.push(issue(IssueKind::EndOutOfBounds { source_duration }));
+                if action != PreflightAction::Fix {
+                    ranges.push(None);
+                    continue;
+                }
+                range.end = source_duration;
+                request.end_time = source_duration.to_string();
+                report.fixed.push(index);
+            }
+            Some(_) => {}
+        }
+        let source_duration = durations.get(&request.input_file).copied();
+        ranges.push(Some(range.pad(request.pre_roll, request.post_roll, source_duration)));
+        report.requests.push(request);
+    }
+
+    // Overlaps only matter between ranges of the same source that will run
+    for (i, a) in ranges.iter().enumerate() {
+        for (j, b) in ranges.iter().enumerate().skip(i + 1) {
+            if let (Some(a), Some(b)) = (a, b) {
+                if requests[i].input_file == requests[j].input_file && a.overlaps(b) {
+                    let other = &requests[j];
+                    report.issues.push(PreflightIssue {
+                        index: j,
+                        input_file: other.input_file.clone(),
+                        start_time: other.start_time.clone(),
+                        end_time: other.end_time.clone(),
+                        kind: IssueKind::Overlap { other: i },
+                    });
+                }
+            }
+        }
+    }
+    report.issues.sort_by_key(|issue| issue.index);
+
+    report.skipped = ranges.iter().enumerate().filter(|(_, r)| r.is_none()).map(|(i, _)| i).collect();
+    if action == PreflightAction::Check && !report.skipped.is_empty() {
+        // Nothing runs when the check fails
+        report.requests.clear();
+    }
+    report
+}
+
+/// Probes each distinct source of `requests` once
+#[cfg(not(feature = "wasm"))]
+pub fn probe_durations(requests: &[ClipRequest]) -> HashMap<String, f64> {
+    let mut durations = HashMap::new();
+    for request in requests {
+        if durations.contains_key(&request.input_file) {
+            continue;
+        }
+        match crate::probe::probe(&request.input_file).map(|info| info.duration) {
+            Ok(Some(duration)) => {
+                durations.insert(request.input_file.clone(), duration);
+            }
+            Ok(None) => log::warn!("No duration for {}", request.input_file),
+            Err(e) => log::warn!("Could not probe {}: {}", request.input_file, e),
+        }
+    }
+    durations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(input: &str, start: &str, end: &str) -> ClipRequest {
+        ClipRequest {
+            input_file: input.to_string(),
+            start_time: start.to_string(),
+            end_time: end.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn batch() -> (Vec<ClipRequest>, HashMap<String, f64>) {
+        let requests = vec![
+            request("a.mp4", "0:10", "0:20"),
+            request("a.mp4", "0:15", "0:25"),
+            request("a.mp4", "0:50", "1:10"),
+            request("a.mp4", "2:00", "2:10"),
+            request("b.mp4", "0:30", "0:30"),
+            request("b.mp4", "soon", "0:30"),
+            request("c.mp4", "0:00", "0:05"),
+        ];
+        let durations = HashMap::from([("a.mp4".to_string(), 60.0), ("b.mp4".to_string(), 100.0)]);
+        (requests, durations)
+    }
+
+    #[test]
+    fn test_check_reports_every_problem_and_runs_nothing() {
+        let (requests, durations) = batch();
+        let report = check_requests(&requests, &durations, PreflightAction::Check);
+
+        let kinds: Vec<_> = report.issues.iter().map(|i| (i.index, i.kind.clone())).collect();
+        assert_eq!(kinds, vec![
+            (1, IssueKind::Overlap { other: 0 }),
+            (2, IssueKind::EndOutOfBounds { source_duration: 60.0 }),
+            (3, IssueKind::StartOutOfBounds { source_duration: 60.0 }),
+            (4, IssueKind::EmptyRange),
+            (5, IssueKind::InvalidTime),
+            (6, IssueKind::Unprobed),
+        ]);
+        assert_eq!(report.errors(), 4);
+        assert_eq!(report.skipped, vec![2, 3, 4, 5]);
+        assert!(!report.is_ok());
+        assert!(report.requests.is_empty());
+    }
+
+    #[test]
+    fn test_fix_clamps_ends_and_drops_the_rest() {
+        let (requests, durations) = batch();
+        let report = check_requests(&requests, &durations, PreflightAction::Fix);
+        assert_eq!(report.fixed, vec![2]);
+        assert_eq!(report.skipped, vec![3, 4, 5]);
+        assert!(report.is_ok());
+
+        let kept: Vec<_> = report.requests.iter().map(|r| (r.start_time.as_str(), r.end_time.as_str())).collect();
+        assert_eq!(kept, vec![("0:10", "0:20"), ("0:15", "0:25"), ("0:50", "60"), ("0:00", "0:05")]);
+    }
+
+    #[test]
+    fn test_skip_drops_everything_invalid() {
+        let (requests, durations) = batch();
+        let report = check_requests(&requests, &durations, PreflightAction::Skip);
+        assert!(report.fixed.is_empty());
+        assert_eq!(report.skipped, vec![2, 3, 4, 5]);
+        assert_eq!(report.requests.len(), 3);
+        assert!(report.issues[1].describe().contains("ends after the source ends (01:00)"));
+    }
+
+    #[test]
+    fn test_overlaps_include_handles() {
+        let requests = vec![
+            ClipRequest { post_roll: 3.0, ..request("a.mp4", "0:10", "0:20") },
+            ClipRequest { pre_roll: 3.0, ..request("a.mp4", "0:25", "0:30") },
+            ClipRequest { pre_roll: 21.0, ..request("a.mp4", "0:50", "0:55") },
+        ];
+        let durations = HashMap::from([("a.mp4".to_string(), 60.0)]);
+        let report = check_requests(&requests, &durations, PreflightAction::Check);
+        let kinds: Vec<_> = report.issues.iter().map(|i| (i.index, i.kind.clone())).collect();
+        assert_eq!(kinds, vec![(1, IssueKind::Overlap { other: 0 }), (2, IssueKind::Overlap { other: 1 })]);
+    }
+}
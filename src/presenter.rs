@@ -0,0 +1,282 @@
+use colored::{Color, Colorize};
+use std::fmt::Display;
+use std::io::{self, Write};
+
+/// Terminal output
+/// Everything the CLI prints goes through a `Presenter`, which decides how it
+/// looks: emoji and colors for someone at a terminal, or plain text that stays
+/// readable in cron mail and CI logs. Quiet output keeps only warnings, errors
+/// and machine-readable data such as JSON reports.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Skipped,
+    Failed,
+    Warning,
+}
+
+impl Status {
+    fn emoji(self) -> &'static str {
+        match self {
+            Status::Ok => "✅",
+            Status::Skipped => "⏭️",
+            Status::Failed => "❌",
+            Status::Warning => "⚠️",
+        }
+    }
+
+    /// Stands in for the emoji in plain output, so the status isn't lost
+    fn label(self) -> &'static str {
+        match self {
+            Status::Ok => "[ok]",
+            Status::Skipped => "[skipped]",
+            Status::Failed => "[failed]",
+            Status::Warning => "[warning]",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Status::Ok => Color::BrightGreen,
+            Status::Skipped => Color::BrightBlue,
+            Status::Failed => Color::BrightRed,
+            Status::Warning => Color::BrightYellow,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Presenter {
+    /// Only warnings, errors, prompts and data
+    pub quiet: bool,
+    /// No banner or emoji; implies no colors
+    pub plain: bool,
+    /// ANSI colors
+    pub color: bool,
+    stderr: bool,
+}
+
+impl Default for Presenter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Presenter {
+    pub fn new() -> Self {
+        Self { quiet: false, plain: false, color: true, stderr: false }
+    }
+
+    /// From the CLI flags; a non-empty `NO_COLOR` also turns colors off
+    /// (<https://no-color.org>)
+    pub fn from_flags(quiet: bool, plain: bool, no_color: bool) -> Self {
+        let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Self { quiet, plain, color: !(plain || no_color || no_color_env), stderr: false }
+    }
+
+    /// The same presenter writing to stderr, to keep stdout for data
+    pub fn on_stderr(self) -> Self {
+        Self { stderr: true, ..self }
+    }
+
+    /// `text` in `color`, or as it is when colors are off
+    pub fn paint(&self, text: &str, color: Color) -> String {
+        if self.color {
+            text.color(color).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Paths and other values worth spotting
+    pub fn highlight(&self, text: &str) -> String {
+        self.paint(text, Color::BrightCyan)
+    }
+
+    pub fn dim(&self, text: &str) -> String {
+        if self.color {
+            text.dimmed().to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn bold(&self, text: &str, color: Color) -> String {
+        if self.color {
+            text.color(color).bold().to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// `icon text`, or just `text` in plain output
+    fn decorate(&self, icon: &str, text: &str) -> String {
+        if self.plain {
+            text.to_string()
+        } else {
+            format!("{} {}", icon, text)
+        }
+    }
+
+    fn marker(&self, status: Status) -> String {
+        if self.plain {
+            status.label().to_string()
+        } else {
+            status.emoji().to_string()
+        }
+    }
+
+    fn emit(&self, line: String) {
+        if self.stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    fn say(&self, line: String) {
+        if !self.quiet {
+            self.emit(line);
+        }
+    }
+
+    pub fn banner(&self) {
+        if self.quiet || self.plain {
+            return;
+        }
+        let rule = "=".repeat(60);
+        self.emit(self.paint(&rule, Color::BrightBlue));
+        self.emit(format!("🎬 {}", self.bold("VIDEO CLIPPER - Rust Edition", Color::BrightCyan)));
+        self.emit(format!("   {}", self.paint("High-performance video clipping with WebAssembly support", Color::BrightWhite)));
+        self.emit(self.paint(&rule, Color::BrightBlue));
+        self.emit(String::new());
+    }
+
+    pub fn blank(&self) {
+        self.say(String::new());
+    }
+
+    /// A line as it is
+    pub fn line(&self, text: &str) {
+        self.say(text.to_string());
+    }
+
+    /// What's about to happen, e.g. `✂️ Creating clip:`
+    pub fn heading(&self, icon: &str, text: &str) {
+        self.say(self.decorate(icon, &self.highlight(text)));
+    }
+
+    /// An indented `label value` under a heading
+    pub fn field(&self, label: &str, value: impl Display) {
+        self.say(format!("   {} {}", self.paint(label, Color::BrightWhite), value));
+    }
+
+    /// A labelled outcome, e.g. `📁 Clip saved: out/clip.mp4`
+    pub fn fact(&self, icon: &str, label: &str, value: impl Display) {
+        let label = self.paint(label, Color::BrightWhite);
+        self.say(format!("{} {}", self.decorate(icon, &label), value));
+    }
+
+    pub fn success(&self, icon: &str, text: &str) {
+        self.say(self.decorate(icon, &self.bold(text, Color::BrightGreen)));
+    }
+
+    /// A one-line update, e.g. `🧹 Removed 3 cached probe results`
+    pub fn note(&self, icon: &str, text: &str) {
+        self.say(self.decorate(icon, text));
+    }
+
+    /// An indented `⏳ text` for work still going
+    pub fn progress(&self, text: &str) {
+        self.say(format!("   {}", self.decorate("⏳", text)));
+    }
+
+    /// An indented per-file line; quiet output keeps failures and warnings
+    pub fn item(&self, status: Status, text: &str) {
+        let line = format!("   {} {}", self.paint(&self.marker(status), status.color()), text);
+        match status {
+            Status::Failed | Status::Warning => self.emit(line),
+            Status::Ok | Status::Skipped => self.say(line),
+        }
+    }
+
+    pub fn warning(&self, text: &str) {
+        let marker = self.paint(&self.marker(Status::Warning), Status::Warning.color());
+        self.emit(format!("{} {}", marker, self.paint(text, Color::Yellow)));
+    }
+
+    /// Always shown, on stderr
+    pub fn error(&self, text: &str) {
+        let marker = self.paint(&self.marker(Status::Failed), Status::Failed.color());
+        eprintln!("{} {}", marker, self.paint(text, Color::Red));
+    }
+
+    /// Machine-readable output (JSON reports, schemas); never styled or quieted
+    pub fn data(&self, text: &str) {
+        println!("{}", text);
+    }
+
+    /// Guidance shown before a question; kept in quiet output
+    pub fn prompt(&self, text: &str) {
+        self.interact(&format!("{}\n", self.highlight(text)));
+    }
+
+    /// Numbered quick picks for the next question; kept in quiet output
+    pub fn choices(&self, choices: &[String]) {
+        for (i, choice) in choices.iter().enumerate() {
+            self.interact(&format!("   {} {}\n", self.paint(&format!("{})", i + 1), Color::BrightYellow), choice));
+        }
+    }
+
+    /// Asks for a line on stdin, without surrounding quotes (as pasted paths
+    /// often have); `None` once stdin is closed, so loops asking again end
+    pub fn ask(&self, prompt: &str) -> Option<String> {
+        self.interact(&format!("{}: ", self.paint(prompt, Color::BrightYellow)));
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+            self.interact("\n");
+            return None;
+        }
+        Some(input.trim().trim_matches('"').trim_matches('\'').to_string())
+    }
+
+    /// Writes part of a question: to stderr in quiet output (or when
+    /// writing to stderr anyway), so stdout carries only data
+    fn interact(&self, text: &str) {
+        if self.quiet || self.stderr {
+            eprint!("{}", text);
+            let _ = io::stderr().flush();
+        } else {
+            print!("{}", text);
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_output_has_no_emoji_or_ansi() {
+        let plain = Presenter::from_flags(false, true, false);
+        assert!(!plain.color);
+        assert_eq!(plain.decorate("📁", &plain.paint("Clip saved:", Color::BrightWhite)), "Clip saved:");
+        assert_eq!(plain.marker(Status::Failed), "[failed]");
+        assert_eq!(plain.bold("SUCCESS!", Color::BrightGreen), "SUCCESS!");
+        assert_eq!(plain.dim("(3 files)"), "(3 files)");
+
+        let fancy = Presenter { color: false, ..Presenter::new() };
+        assert_eq!(fancy.decorate("📁", "Clip saved:"), "📁 Clip saved:");
+        assert_eq!(fancy.marker(Status::Skipped), "⏭️");
+    }
+
+    #[test]
+    fn test_flags() {
+        let quiet = Presenter::from_flags(true, false, true);
+        assert!(quiet.quiet && !quiet.plain && !quiet.color);
+        assert!(quiet.on_stderr().stderr);
+        assert!(!Presenter::new().on_stderr().quiet);
+    }
+}
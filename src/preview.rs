@@ -0,0 +1,286 @@
+use crate::frames::{FrameExportOptions, FrameSequenceCommand, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Preview data for scrubber UIs
+/// Extracts downsampled audio peaks (from raw PCM decoded by FFmpeg) and a
+/// sparse set of thumbnails for a range, serializable as JSON for web frontends
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewOptions {
+    /// Number of peak values to produce across the range
+    #[serde(default = "default_peak_count")]
+    pub peak_count: usize,
+    /// Sample rate the audio is decoded at before peak reduction
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    /// Number of thumbnails to extract; 0 disables thumbnails
+    #[serde(default = "default_thumbnail_count")]
+    pub thumbnail_count: u32,
+    #[serde(default = "default_thumbnail_width")]
+    pub thumbnail_width: u32,
+}
+
+fn default_peak_count() -> usize {
+    1000
+}
+
+fn default_sample_rate() -> u32 {
+    8000
+}
+
+fn default_thumbnail_count() -> u32 {
+    10
+}
+
+fn default_thumbnail_width() -> u32 {
+    160
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {
+            peak_count: default_peak_count(),
+            sample_rate: default_sample_rate(),
+            thumbnail_count: default_thumbnail_count(),
+            thumbnail_width: default_thumbnail_width(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WaveformData {
+    pub start_seconds: f64,
+    pub duration: f64,
+    /// Seconds of audio each peak value covers
+    pub seconds_per_peak: f64,
+    /// Normalized absolute peak per bucket, 0.0 (silence) to 1.0 (full scale)
+    pub peaks: Vec<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Thumbnail {
+    pub time: f64,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewData {
+    pub input_file: String,
+    pub waveform: Option<WaveformData>,
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+/// Reduces mono samples to exactly `buckets` normalized absolute peaks.
+/// With fewer samples than buckets, samples are repeated across the
+/// buckets they fall in.
+pub fn compute_peaks(samples: &[i16], buckets: usize) -> Vec<f32> {
+    if samples.is_empty() || buckets == 0 {
+        return Vec::new();
+    }
+
+    (0..buckets)
+        .map(|bucket| {
+            let start = bucket * samples.len() / buckets;
+            let end = ((bucket + 1) * samples.len() / buckets).max(start + 1);
+            let peak = samples[start..end].iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+            peak as f32 / 32768.0
+        })
+        .collect()
+}
+
+/// Same as [`compute_peaks`] for raw signed 16-bit little-endian PCM bytes,
+/// the format FFmpeg emits with `-f s16le`
+pub fn peaks_from_pcm_bytes(pcm: &[u8], buckets: usize) -> Vec<f32> {
+    let samples: Vec<i16> = pcm
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    compute_peaks(&samples, buckets)
+}
+
+#[derive(Debug, Clone)]
+pub struct WaveformCommand {
+    input: PathBuf,
+    start_time: f64,
+    duration: f64,
+    sample_rate: u32,
+}
+
+impl WaveformCommand {
+    pub fn new(input: impl AsRef<Path>, start_time: f64, duration: f64, sample_rate: u32) -> Self {
+        Self {
+            input: input.as_ref().to_path_buf(),
+            start_time,
+            duration,
+            sample_rate,
+        }
+    }
+
+    /// Decodes the first audio stream to mono s16le PCM written to `output`
+    /// (`-` for stdout, or a file path for ffmpeg.wasm's virtual FS)
+    pub fn build_args(&self, output: &str) -> Vec<String> {
+        vec![
+            "-ss".into(), self.start_time.to_string(),
+            "-i".into(), self.input.display().to_string(),
+            "-t".into(), self.duration.to_string(),
+            "-map".into(), "0:a:0".into(),
+            "-ac".into(), "1".into(),
+            "-ar".into(), self.sample_rate.to_string(),
+            "-c:a".into(), "pcm_s16le".into(),
+            "-f".into(), "s16le".into(),
+            "-y".into(),
+            output.into(),
+        ]
+    }
+
+    pub fn build_command(&self) -> Command {
+        let mut args = vec!["-v".to_string(), "error".to_string()];
+        args.extend(self.build_args("-"));
+        crate::tools::ffmpeg_command(args)
+    }
+
+    pub fn get_command_string(&self, output: &str) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args(output).join(" "))
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self, peak_count: usize) -> crate::error::Result<WaveformData> {
+        let output = crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Waveform extraction failed")?;
+        let peaks = peaks_from_pcm_bytes(&output.stdout, peak_count);
+
+        Ok(WaveformData {
+            start_seconds: self.start_time,
+            duration: self.duration,
+            seconds_per_peak: if peaks.is_empty() { 0.0 } else { self.duration / peaks.len() as f64 },
+            peaks,
+        })
+    }
+}
+
+/// Frame export command producing `count` evenly spaced thumbnails across the range
+pub fn thumbnail_command(input: impl AsRef<Path>, output_dir: impl AsRef<Path>, start_time: f64, duration: f64, count: u32, width: u32) -> FrameSequenceCommand {
+    let options = FrameExportOptions {
+        format: ImageFormat::Jpeg,
+        fps: Some(count as f64 / duration),
+        width: Some(width),
+        height: None,
+        jpeg_quality: Some(5),
+    };
+    FrameSequenceCommand::new(input, output_dir, start_time, duration, options)
+}
+
+/// Pairs thumbnail files written by [`thumbnail_command`] with their source timestamps
+pub fn thumbnails_in(output_dir: impl AsRef<Path>, start_time: f64, duration: f64, count: u32) -> Vec<Thumbnail> {
+    let interval = duration / count.max(1) as f64;
+    (0..count)
+        .map(|i| (i, output_dir.as_ref().join(format!("{}{:06}.jpg", crate::frames::FRAME_PREFIX, i + 1))))
+        .filter(|(_, path)| path.exists())
+        .map(|(i, path)| Thumbnail {
+            time: start_time + i as f64 * interval,
+            path: path.display().to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    mod peak_tests {
+        use super::*;
+
+        #[test]
+        fn test_compute_peaks_buckets() {
+            let samples = [0i16, 100, -200, 50, 16384, -32768, 0, 0];
+            let peaks = compute_peaks(&samples, 4);
+
+            assert_eq!(peaks.len(), 4);
+            assert_eq!(peaks[0], 100.0 / 32768.0);
+            assert_eq!(peaks[1], 200.0 / 32768.0);
+            assert_eq!(peaks[2], 1.0);
+            assert_eq!(peaks[3], 0.0);
+        }
+
+        #[test]
+        fn test_compute_peaks_more_buckets_than_samples() {
+            let peaks = compute_peaks(&[1000, -2000], 4);
+            assert_eq!(peaks, vec![1000.0 / 32768.0, 1000.0 / 32768.0, 2000.0 / 32768.0, 2000.0 / 32768.0]);
+        }
+
+        #[test]
+        fn test_compute_peaks_uneven_buckets() {
+            let samples: Vec<i16> = (1..=10).collect();
+            let peaks = compute_peaks(&samples, 6);
+            assert_eq!(peaks.len(), 6);
+            assert_eq!(peaks[5], 10.0 / 32768.0);
+        }
+
+        #[test]
+        fn test_compute_peaks_empty_input() {
+            assert!(compute_peaks(&[], 10).is_empty());
+            assert!(compute_peaks(&[1, 2, 3], 0).is_empty());
+        }
+
+        #[test]
+        fn test_peaks_from_pcm_bytes() {
+            let mut pcm = Vec::new();
+            for sample in [0i16, 16384, -8192, 0] {
+                pcm.extend_from_slice(&sample.to_le_bytes());
+            }
+            // Trailing odd byte is ignored
+            pcm.push(0xff);
+
+            let peaks = peaks_from_pcm_bytes(&pcm, 2);
+            assert_eq!(peaks, vec![0.5, 0.25]);
+        }
+    }
+
+    mod command_building_tests {
+        use super::*;
+
+        #[test]
+        fn test_waveform_command() {
+            let cmd = WaveformCommand::new("input.mp4", 30.0, 60.0, 8000);
+
+            let cmd_string = cmd.get_command_string("audio.pcm");
+            assert!(cmd_string.starts_with("ffmpeg -ss 30 -i input.mp4 -t 60"));
+            assert!(cmd_string.contains("-map 0:a:0 -ac 1 -ar 8000"));
+            assert!(cmd_string.contains("-f s16le"));
+            assert!(cmd_string.ends_with("audio.pcm"));
+
+            let args: Vec<String> = cmd.build_command().get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect();
+            assert_eq!(args.last().unwrap(), "-");
+        }
+
+        #[test]
+        fn test_thumbnail_command_spacing() {
+            let cmd = thumbnail_command("input.mp4", "thumbs", 0.0, 100.0, 10, 160);
+            let cmd_string = cmd.get_command_string();
+            assert!(cmd_string.contains("-vf fps=0.1,scale=160:-2"));
+            assert!(cmd_string.ends_with("frame_%06d.jpg"));
+        }
+    }
+
+    mod thumbnail_tests {
+        use super::*;
+        use std::fs::File;
+
+        #[test]
+        fn test_thumbnails_in_pairs_timestamps() {
+            let temp_dir = tempdir().unwrap();
+            File::create(temp_dir.path().join("frame_000001.jpg")).unwrap();
+            File::create(temp_dir.path().join("frame_000002.jpg")).unwrap();
+
+            let thumbnails = thumbnails_in(temp_dir.path(), 20.0, 40.0, 4);
+            assert_eq!(thumbnails.len(), 2);
+            assert_eq!(thumbnails[0].time, 20.0);
+            assert_eq!(thumbnails[1].time, 30.0);
+            assert!(thumbnails[1].path.ends_with("frame_000002.jpg"));
+        }
+    }
+}
@@ -0,0 +1,672 @@
+use crate::error::{VideoClipError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// FFprobe wrapper
+/// Reads container and stream metadata so operations can make decisions
+/// (stream copy compatibility, durations, keyframe positions) before running FFmpeg
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MediaInfo {
+    pub format_name: String,
+    /// Earliest timestamp across all streams
+    pub start_time: Option<f64>,
+    pub duration: Option<f64>,
+    pub size_bytes: Option<u64>,
+    pub bit_rate: Option<u64>,
+    pub streams: Vec<StreamInfo>,
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StreamInfo {
+    pub index: u32,
+    /// `video`, `audio`, `subtitle`, `data` or `attachment`
+    pub codec_type: String,
+    pub codec_name: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub pix_fmt: Option<String>,
+    /// Codec profile as ffprobe names it, e.g. `High` or `Main 10`
+    pub profile: Option<String>,
+    /// Codec level as ffprobe reports it: 41 for H.264 4.1, 123 (30 × 4.1)
+    /// for HEVC
+    pub level: Option<i32>,
+    /// Transfer characteristic, e.g. `smpte2084` (PQ) or `arib-std-b67` (HLG)
+    pub color_transfer: Option<String>,
+    pub color_primaries: Option<String>,
+    pub frame_rate: Option<f64>,
+    pub avg_frame_rate: Option<f64>,
+    pub bit_rate: Option<u64>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub duration: Option<f64>,
+    pub tags: BTreeMap<String, String>,
+}
+
+// ffprobe reports most numbers as JSON strings, so parse through raw structs first
+#[derive(Deserialize)]
+struct RawProbe {
+    #[serde(default)]
+    format: Option<RawFormat>,
+    #[serde(default)]
+    streams: Vec<RawStream>,
+}
+
+#[derive(Deserialize)]
+struct RawFormat {
+    format_name: Option<String>,
+    start_time: Option<String>,
+    duration: Option<String>,
+    size: Option<String>,
+    bit_rate: Option<String>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct RawStream {
+    index: u32,
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    pix_fmt: Option<String>,
+    profile: Option<String>,
+    level: Option<i32>,
+    color_transfer: Option<String>,
+    color_primaries: Option<String>,
+    r_frame_rate: Option<String>,
+    avg_frame_rate: Option<String>,
+    bit_rate: Option<String>,
+    sample_rate: Option<String>,
+    channels: Option<u32>,
+    duration: Option<String>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
+
+/// Parses ffprobe rationals such as `30000/1001`; `0/0` means unknown
+fn parse_rational(value: &str) -> Option<f64> {
+    match value.split_once('/') {
+        Some((num, den)) => {
+            let num = num.parse::<f64>().ok()?;
+            let den = den.parse::<f64>().ok()?;
+            if den == 0.0 || num == 0.0 {
+                None
+            } else {
+                Some(num / den)
+            }
+        }
+        None => value.parse::<f64>().ok(),
+    }
+}
+
+/// Average and nominal frame rates this far apart (relative) mean the
+/// frame rate varies
+const VFR_TOLERANCE: f64 = 0.01;
+
+impl StreamInfo {
+    /// PQ or HLG transfer, i.e. the stream needs tonemapping to look right in SDR
+    pub fn is_hdr(&self) -> bool {
+        matches!(self.color_transfer.as_deref(), Some("smpte2084") | Some("arib-std-b67"))
+    }
+
+    /// Carries transparency: an alpha pixel format (ProRes 4444, PNG, QuickTime
+    /// Animation), or Matroska's `alpha_mode` tag, which is how VP8/VP9 alpha
+    /// shows up since the decoder reports those streams as plain `yuv420p`
+    pub fn has_alpha(&self) -> bool {
+        let alpha_format = self.pix_fmt.as_deref().is_some_and(|format| {
+            ["yuva", "rgba", "bgra", "argb", "abgr", "gbrap", "ya"].iter().any(|prefix| format.starts_with(prefix))
+        });
+        let alpha_mode = self.tags.iter().any(|(key, value)| key.eq_ignore_ascii_case("alpha_mode") && value.trim() == "1");
+        alpha_format || alpha_mode
+    }
+
+    /// Frames don't arrive at a steady rate: the average rate is off from
+    /// the nominal one, as in screen and phone recordings
+    pub fn is_variable_frame_rate(&self) -> bool {
+        match (self.frame_rate, self.avg_frame_rate) {
+            (Some(nominal), Some(average)) => (nominal - average).abs() / nominal > VFR_TOLERANCE,
+            _ => false,
+        }
+    }
+
+    /// The average rate, rounded for a constant-rate conversion
+    pub fn constant_frame_rate(&self) -> Option<f64> {
+        self.avg_frame_rate.map(|fps| (fps * 1000.0).round() / 1000.0)
+    }
+}
+
+impl MediaInfo {
+    /// Parses the output of `ffprobe -print_format json -show_format -show_streams`
+    pub fn from_ffprobe_json(json: &str) -> Result<Self> {
+        let raw: RawProbe = serde_json::from_str(json)
+            .map_err(|e| VideoClipError::ProbeError(format!("unreadable ffprobe output: {}", e)))?;
+
+        let format = raw.format.unwrap_or(RawFormat {
+            format_name: None,
+            start_time: None,
+            duration: None,
+            size: None,
+            bit_rate: None,
+            tags: BTreeMap::new(),
+        });
+
+        let streams = raw.streams.into_iter().map(|s| StreamInfo {
+            index: s.index,
+            codec_type: s.codec_type.unwrap_or_default(),
+            codec_name: s.codec_name,
+            width: s.width,
+            height: s.height,
+            pix_fmt: s.pix_fmt,
+            profile: s.profile,
+            // ffprobe reports -99 when it doesn't know
+            level: s.level.filter(|&level| level > 0),
+            color_transfer: s.color_transfer,
+            color_primaries: s.color_primaries,
+            frame_rate: s.r_frame_rate.as_deref().and_then(parse_rational),
+            avg_frame_rate: s.avg_frame_rate.as_deref().and_then(parse_rational),
+            bit_rate: s.bit_rate.and_then(|v| v.parse().ok()),
+            sample_rate: s.sample_rate.and_then(|v| v.parse().ok()),
+            channels: s.channels,
+            duration: s.duration.and_then(|v| v.parse().ok()),
+            tags: s.tags,
+        }).collect();
+
+        Ok(Self {
+            format_name: format.format_name.unwrap_or_default(),
+            start_time: format.start_time.and_then(|v| v.parse().ok()),
+            duration: format.duration.and_then(|v| v.parse().ok()),
+            size_bytes: format.size.and_then(|v| v.parse().ok()),
+            bit_rate: format.bit_rate.and_then(|v| v.parse().ok()),
+            streams,
+            tags: format.tags,
+        })
+    }
+
+    pub fn video_stream(&self) -> Option<&StreamInfo> {
+        self.streams.iter().find(|s| s.codec_type == "video")
+    }
+
+    pub fn audio_stream(&self) -> Option<&StreamInfo> {
+        self.streams.iter().find(|s| s.codec_type == "audio")
+    }
+
+    /// Overall bitrate in bits per second, derived from the file size when
+    /// the container doesn't report one
+    pub fn effective_bit_rate(&self) -> Option<u64> {
+        self.bit_rate.or_else(|| match (self.size_bytes, self.duration) {
+            (Some(size), Some(duration)) if duration > 0.0 => Some((size as f64 * 8.0 / duration) as u64),
+            _ => None,
+        })
+    }
+}
+
+/// Splits on commas outside parentheses: `yuv420p(tv, bt709), 1920x1080`
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(s[start..].trim());
+    parts
+}
+
+/// `00:10:05.52` from FFmpeg's banner
+fn parse_banner_time(value: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in value.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+fn parse_channel_layout(layout: &str) -> Option<u32> {
+    let layout = layout.split('(').next()?;
+    match layout {
+        "mono" => Some(1),
+        "stereo" => Some(2),
+        _ => match layout.split_once('.') {
+            Some((main, lfe)) => Some(main.parse::<u32>().ok()? + lfe.parse::<u32>().ok()?),
+            None => layout.strip_suffix(" channels")?.parse().ok(),
+        },
+    }
+}
+
+/// One `Stream #0:1[0x2](eng): Audio: aac (LC), 48000 Hz, stereo, ...` line
+fn parse_banner_stream(line: &str) -> Option<StreamInfo> {
+    let rest = line.trim().strip_prefix("Stream #")?;
+    let (id, rest) = rest.split_once(": ")?;
+    // `0:1[0x2](eng)`
+    let index = id.split_once(':')?.1.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()?;
+    let (kind, rest) = rest.split_once(": ")?;
+    let parts = split_top_level(rest);
+    let mut stream = StreamInfo {
+        index,
+        codec_type: kind.to_lowercase(),
+        codec_name: parts.first().and_then(|p| p.split_whitespace().next()).map(str::to_string),
+        ..Default::default()
+    };
+
+    for part in parts.iter().skip(1) {
+        let first = part.split_whitespace().next().unwrap_or_default();
+        if let Some(rate) = part.strip_suffix(" fps") {
+            stream.frame_rate = rate.parse().ok();
+            stream.avg_frame_rate = stream.frame_rate;
+        } else if let Some((rate, _)) = part.split_once(" kb/s") {
+            // The last field may carry a trailing `(default)`
+            stream.bit_rate = rate.parse::<u64>().ok().map(|kbps| kbps * 1000);
+        } else if let Some(rate) = part.strip_suffix(" Hz") {
+            stream.sample_rate = rate.parse().ok();
+        } else if let Some((w, h)) = first.split_once('x').filter(|_| !first.starts_with("0x")) {
+            if let (Ok(w), Ok(h)) = (w.parse(), h.parse()) {
+                stream.width = Some(w);
+                stream.height = Some(h);
+            }
+        } else if stream.codec_type == "audio" && stream.channels.is_none() {
+            stream.channels = parse_channel_layout(part);
+        } else if stream.codec_type == "video" && stream.pix_fmt.is_none() {
+            let (pix_fmt, color) = part.split_once('(').unwrap_or((part, ""));
+            stream.pix_fmt = Some(pix_fmt.to_string());
+            // `space/primaries/transfer` when they differ, a single name when they don't
+            for field in color.trim_end_matches(')').split(", ") {
+                let names: Vec<&str> = field.split('/').collect();
+                let (primaries, transfer) = match names.as_slice() {
+                    [_, primaries, transfer] => (*primaries, *transfer),
+                    [name] if name.starts_with("bt") || name.starts_with("smpte") => (*name, *name),
+                    _ => continue,
+                };
+                stream.color_primaries = Some(primaries.to_string());
+                stream.color_transfer = Some(transfer.to_string());
+            }
+        }
+    }
+    Some(stream)
+}
+
+impl MediaInfo {
+    /// Probe-lite: reads what FFmpeg prints about its input (`ffmpeg -hide_banner
+    /// -i FILE`) for hosts without ffprobe, such as ffmpeg.wasm. Sizes and
+    /// tags aren't reported there, so only the container, duration, bitrate
+    /// and the streams' basics are filled in.
+    pub fn from_ffmpeg_log(log: &str) -> Result<Self> {
+        let mut info = MediaInfo::default();
+        let mut found = false;
+        for line in log.lines() {
+            let trimmed = line.trim();
+            if found && (trimmed.starts_with("Output #") || trimmed.starts_with("Stream mapping:")) {
+                // The outputs' streams follow when the log is from a real run
+                break;
+            }
+            if let Some(rest) = trimmed.strip_prefix("Input #0, ") {
+                found = true;
+                info.format_name = rest.split(", from '").next().unwrap_or_default().to_string();
+            } else if let Some(rest) = trimmed.strip_prefix("Duration: ") {
+                for field in rest.split(", ") {
+                    if let Some(value) = field.strip_prefix("start: ") {
+                        info.start_time = value.parse().ok();
+                    } else if let Some(value) = field.strip_prefix("bitrate: ") {
+                        info.bit_rate = value.trim_end_matches(" kb/s").parse::<u64>().ok().map(|kbps| kbps * 1000);
+                    } else {
+                        info.duration = parse_banner_time(field);
+                    }
+                }
+            } else if found && trimmed.starts_with("Stream #0:") {
+                info.streams.extend(parse_banner_stream(trimmed));
+            }
+        }
+        if !found {
+            return Err(VideoClipError::ProbeError("no input description in the FFmpeg log".to_string()));
+        }
+        Ok(info)
+    }
+}
+
+/// A chapter marker of the source's container
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawChapters {
+    #[serde(default)]
+    chapters: Vec<RawChapter>,
+}
+
+#[derive(Deserialize)]
+struct RawChapter {
+    start_time: Option<String>,
+    end_time: Option<String>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
+
+/// Parses the output of `ffprobe -print_format json -show_chapters`
+pub fn parse_chapters(json: &str) -> Result<Vec<Chapter>> {
+    let raw: RawChapters = serde_json::from_str(json)
+        .map_err(|e| VideoClipError::ProbeError(format!("unreadable ffprobe output: {}", e)))?;
+    Ok(raw
+        .chapters
+        .into_iter()
+        .filter_map(|c| {
+            Some(Chapter {
+                start: c.start_time?.parse().ok()?,
+                end: c.end_time?.parse().ok()?,
+                title: c.tags.get("title").cloned(),
+            })
+        })
+        .collect())
+}
+
+/// Parses `ffprobe -show_entries frame=pts_time -of csv=p=0` output into sorted timestamps
+pub fn parse_keyframe_times(output: &str) -> Vec<f64> {
+    let mut times: Vec<f64> = output
+        .lines()
+        .filter_map(|line| line.trim().trim_end_matches(',').split(',').next()?.parse::<f64>().ok())
+        .collect();
+    times.sort_by(|a, b| a.total_cmp(b));
+    times.dedup();
+    times
+}
+
+fn run_ffprobe(args: &[&str], input: &Path) -> Result<String> {
+    let cache = crate::probe_cache::active();
+    if let Some(cached) = cache.as_ref().and_then(|c| c.get(input, args)) {
+        return Ok(cached);
+    }
+
+    let mut argv: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    argv.push(input.display().to_string());
+    let output = crate::tools::ffprobe_command(argv)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => VideoClipError::FFmpegNotFound,
+            _ => VideoClipError::ProbeError(e.to_string()),
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(VideoClipError::ProbeError(format!("{}: {}", input.display(), stderr.trim())));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if let Some(cache) = cache {
+        if let Err(e) = cache.put(input, args, &stdout) {
+            log::debug!("Could not cache probe of {}: {}", input.display(), e);
+        }
+    }
+    Ok(stdout)
+}
+
+/// Probes container and stream metadata for `input`
+pub fn probe(input: impl AsRef<Path>) -> Result<MediaInfo> {
+    let json = run_ffprobe(
+        &["-v", "error", "-print_format", "json", "-show_format", "-show_streams"],
+        input.as_ref(),
+    )?;
+    MediaInfo::from_ffprobe_json(&json)
+}
+
+/// Lists video keyframe timestamps within `[from, from + window]`
+pub fn probe_keyframes(input: impl AsRef<Path>, from: f64, window: f64) -> Result<Vec<f64>> {
+    let interval = format!("{}%+{}", from.max(0.0), window);
+    let output = run_ffprobe(
+        &[
+            "-v", "error",
+            "-select_streams", "v:0",
+            "-skip_frame", "nokey",
+            "-show_entries", "frame=pts_time",
+            "-of", "csv=p=0",
+            "-read_intervals", &interval,
+        ],
+        input.as_ref(),
+    )?;
+    Ok(parse_keyframe_times(&output))
+}
+
+/// Lists every video keyframe timestamp in the file
+pub fn probe_all_keyframes(input: impl AsRef<Path>) -> Result<Vec<f64>> {
+    let output = run_ffprobe(
+        &[
+            "-v", "error",
+            "-select_streams", "v:0",
+            "-skip_frame", "nokey",
+            "-show_entries", "frame=pts_time",
+            "-of", "csv=p=0",
+        ],
+        input.as_ref(),
+    )?;
+    Ok(parse_keyframe_times(&output))
+}
+
+/// Lists the container's chapters
+pub fn probe_chapters(input: impl AsRef<Path>) -> Result<Vec<Chapter>> {
+    let json = run_ffprobe(&["-v", "error", "-print_format", "json", "-show_chapters"], input.as_ref())?;
+    parse_chapters(&json)
+}
+
+/// Lists every video frame timestamp (from packets, so nothing is decoded)
+pub fn probe_frame_times(input: impl AsRef<Path>) -> Result<Vec<f64>> {
+    let output = run_ffprobe(
+        &[
+            "-v", "error",
+            "-select_streams", "v:0",
+            "-show_entries", "packet=pts_time",
+            "-of", "csv=p=0",
+        ],
+        input.as_ref(),
+    )?;
+    Ok(parse_keyframe_times(&output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_PROBE: &str = r#"{
+        "streams": [
+            {
+                "index": 0,
+                "codec_name": "h264",
+                "codec_type": "video",
+                "width": 1920,
+                "height": 1080,
+                "pix_fmt": "yuv420p",
+                "profile": "High",
+                "level": 40,
+                "color_transfer": "bt709",
+                "color_primaries": "bt709",
+                "r_frame_rate": "30000/1001",
+                "avg_frame_rate": "30000/1001",
+                "bit_rate": "4500000",
+                "duration": "120.120000",
+                "tags": { "language": "und" }
+            },
+            {
+                "index": 1,
+                "codec_name": "aac",
+                "codec_type": "audio",
+                "profile": "LC",
+                "level": -99,
+                "sample_rate": "48000",
+                "channels": 2,
+                "r_frame_rate": "0/0",
+                "bit_rate": "128000"
+            }
+        ],
+        "format": {
+            "format_name": "mov,mp4,m4a,3gp,3g2,mj2",
+            "start_time": "0.000000",
+            "duration": "120.120000",
+            "size": "69000000",
+            "bit_rate": "4600000",
+            "tags": { "creation_time": "2024-03-01T14:00:00.000000Z" }
+        }
+    }"#;
+
+    mod parsing_tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_ffprobe_json() {
+            let info = MediaInfo::from_ffprobe_json(SAMPLE_PROBE).unwrap();
+
+            assert_eq!(info.format_name, "mov,mp4,m4a,3gp,3g2,mj2");
+            assert_eq!(info.start_time, Some(0.0));
+            assert_eq!(info.duration, Some(120.12));
+            assert_eq!(info.size_bytes, Some(69_000_000));
+            assert_eq!(info.bit_rate, Some(4_600_000));
+            assert_eq!(info.streams.len(), 2);
+            assert_eq!(info.tags.get("creation_time").unwrap(), "2024-03-01T14:00:00.000000Z");
+
+            let video = info.video_stream().unwrap();
+            assert_eq!(video.codec_name.as_deref(), Some("h264"));
+            assert_eq!(video.width, Some(1920));
+            assert_eq!((video.profile.as_deref(), video.level), (Some("High"), Some(40)));
+            assert!((video.frame_rate.unwrap() - 29.97).abs() < 0.01);
+            assert_eq!(video.color_transfer.as_deref(), Some("bt709"));
+            assert!(!video.is_hdr());
+
+            let audio = info.audio_stream().unwrap();
+            assert_eq!(audio.sample_rate, Some(48000));
+            assert_eq!(audio.channels, Some(2));
+            assert_eq!(audio.frame_rate, None);
+            assert_eq!(audio.level, None);
+        }
+
+        #[test]
+        fn test_parse_minimal_json() {
+            let info = MediaInfo::from_ffprobe_json(r#"{"streams": []}"#).unwrap();
+            assert_eq!(info.duration, None);
+            assert!(info.video_stream().is_none());
+        }
+
+        #[test]
+        fn test_parse_ffmpeg_log() {
+            let log = "\
+Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'talk.mp4':
+  Metadata:
+    major_brand     : isom
+  Duration: 00:10:05.52, start: 0.000000, bitrate: 2501 kb/s
+  Stream #0:0[0x1](und): Video: hevc (Main 10) (hvc1 / 0x31637668), yuv420p10le(tv, bt2020nc/bt2020/smpte2084), 3840x2160 [SAR 1:1 DAR 16:9], 2369 kb/s, 29.97 fps, 29.97 tbr, 30k tbn (default)
+      Metadata:
+        handler_name    : VideoHandler
+  Stream #0:1[0x2](eng): Audio: aac (LC) (mp4a / 0x6134706D), 48000 Hz, 5.1, fltp, 128 kb/s (default)
+Stream mapping:
+  Stream #0:0 -> #0:0 (copy)
+";
+            let info = MediaInfo::from_ffmpeg_log(log).unwrap();
+            assert_eq!(info.format_name, "mov,mp4,m4a,3gp,3g2,mj2");
+            assert_eq!(info.duration, Some(605.52));
+            assert_eq!(info.bit_rate, Some(2_501_000));
+
+            let video = info.video_stream().unwrap();
+            assert_eq!(video.codec_name.as_deref(), Some("hevc"));
+            assert_eq!((video.width, video.height), (Some(3840), Some(2160)));
+            assert_eq!(video.pix_fmt.as_deref(), Some("yuv420p10le"));
+            assert_eq!(video.frame_rate, Some(29.97));
+            assert!(video.is_hdr());
+
+            let audio = info.audio_stream().unwrap();
+            assert_eq!((audio.index, audio.sample_rate, audio.channels), (1, Some(48000), Some(6)));
+            assert_eq!(audio.bit_rate, Some(128_000));
+            assert_eq!(info.streams.len(), 2);
+
+            assert!(MediaInfo::from_ffmpeg_log("talk.mp4: No such file or directory").is_err());
+        }
+
+        #[test]
+        fn test_parse_invalid_json() {
+            let result = MediaInfo::from_ffprobe_json("not json");
+            assert!(matches!(result, Err(VideoClipError::ProbeError(_))));
+        }
+
+        #[test]
+        fn test_effective_bit_rate_falls_back_to_size() {
+            let info = MediaInfo {
+                size_bytes: Some(1_000_000),
+                duration: Some(8.0),
+                ..Default::default()
+            };
+            assert_eq!(info.effective_bit_rate(), Some(1_000_000));
+        }
+
+        #[test]
+        fn test_hdr_detection() {
+            let pq = StreamInfo { color_transfer: Some("smpte2084".to_string()), ..Default::default() };
+            let hlg = StreamInfo { color_transfer: Some("arib-std-b67".to_string()), ..Default::default() };
+            assert!(pq.is_hdr());
+            assert!(hlg.is_hdr());
+            assert!(!StreamInfo::default().is_hdr());
+        }
+
+        #[test]
+        fn test_alpha_detection() {
+            let prores = StreamInfo { pix_fmt: Some("yuva444p12le".to_string()), ..Default::default() };
+            let vp9 = StreamInfo {
+                pix_fmt: Some("yuv420p".to_string()),
+                tags: BTreeMap::from([("ALPHA_MODE".to_string(), "1".to_string())]),
+                ..Default::default()
+            };
+            assert!(prores.has_alpha());
+            assert!(vp9.has_alpha());
+            assert!(StreamInfo { pix_fmt: Some("rgba".to_string()), ..Default::default() }.has_alpha());
+            assert!(!StreamInfo { pix_fmt: Some("yuv420p".to_string()), ..Default::default() }.has_alpha());
+            assert!(!StreamInfo { pix_fmt: Some("yuvj420p".to_string()), ..Default::default() }.has_alpha());
+        }
+
+        #[test]
+        fn test_vfr_detection() {
+            let screen = StreamInfo { frame_rate: Some(60.0), avg_frame_rate: Some(23.61834), ..Default::default() };
+            let ntsc = StreamInfo { frame_rate: Some(30000.0 / 1001.0), avg_frame_rate: Some(29.97), ..Default::default() };
+            assert!(screen.is_variable_frame_rate());
+            assert!(!ntsc.is_variable_frame_rate());
+            assert!(!StreamInfo::default().is_variable_frame_rate());
+            assert_eq!(screen.constant_frame_rate(), Some(23.618));
+        }
+
+        #[test]
+        fn test_parse_rational() {
+            assert_eq!(parse_rational("25/1"), Some(25.0));
+            assert_eq!(parse_rational("0/0"), None);
+            assert_eq!(parse_rational("24"), Some(24.0));
+            assert_eq!(parse_rational("abc"), None);
+        }
+    }
+
+    mod keyframe_tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_keyframe_times() {
+            let output = "4.004000\n0.000000\n2.002000,\n\nN/A\n2.002000\n";
+            assert_eq!(parse_keyframe_times(output), vec![0.0, 2.002, 4.004]);
+        }
+
+        #[test]
+        fn test_parse_chapters() {
+            let json = r#"{"chapters": [
+                {"id": 0, "time_base": "1/1000", "start": 0, "start_time": "0.000000", "end": 90000, "end_time": "90.000000", "tags": {"title": "Intro"}},
+                {"id": 1, "time_base": "1/1000", "start": 90000, "start_time": "90.000000", "end": 600000, "end_time": "600.000000"}
+            ]}"#;
+            let chapters = parse_chapters(json).unwrap();
+            assert_eq!(chapters[0], Chapter { start: 0.0, end: 90.0, title: Some("Intro".to_string()) });
+            assert_eq!(chapters[1].title, None);
+            assert!(parse_chapters("{}").unwrap().is_empty());
+        }
+    }
+}
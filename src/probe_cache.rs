@@ -0,0 +1,268 @@
+//! Probe cache
+//! Keeps ffprobe output on disk keyed by the file's path, size and
+//! modification time (plus the probe arguments), so repeated batch runs over
+//! the same library skip redundant ffprobe calls. Rewriting a file changes
+//! its size or mtime, which is enough to miss the stale entry. Library users
+//! opt in with [`set_enabled`]; the CLI does unless given `--no-cache`.
+
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+pub struct ProbeCache {
+    dir: PathBuf,
+    max_entries: usize,
+}
+
+/// Entries kept before the oldest are dropped
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// File name stem for cached data about `data`: the start of its SHA-256,
+/// which unlike `DefaultHasher` stays the same across Rust releases
+pub(crate) fn cache_key(data: &[u8]) -> String {
+    Sha256::digest(data)[..8].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Turns the process-wide cache used by [`crate::probe`] on or off; off
+/// until enabled
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// The cache `probe` reads and writes, unless disabled
+pub fn active() -> Option<ProbeCache> {
+    if is_enabled() {
+        ProbeCache::default_location()
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FileKey {
+    path: String,
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+}
+
+impl FileKey {
+    fn of(input: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(input).ok()?;
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        let path = std::fs::canonicalize(input).unwrap_or_else(|_| input.to_path_buf());
+        Some(Self {
+            path: path.display().to_string(),
+            size: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    file: FileKey,
+    args: Vec<String>,
+    output: String,
+}
+
+impl ProbeCache {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    /// Entries kept before the least recently written are dropped
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries;
+    }
+
+    /// `<user cache dir>/video-clip-rs/probe`
+    pub fn default_location() -> Option<Self> {
+        dirs::cache_dir().map(|dir| Self::new(dir.join("video-clip-rs").join("probe")))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, file: &FileKey, args: &[&str]) -> PathBuf {
+        let key = serde_json::to_vec(&(file, args)).unwrap_or_default();
+        self.dir.join(format!("{}.json", cache_key(&key)))
+    }
+
+    fn read_entry(path: &Path) -> Option<CacheEntry> {
+        serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()
+    }
+
+    /// Cached output of `ffprobe args input`, if the file hasn't changed since
+    pub fn get(&self, input: &Path, args: &[&str]) -> Option<String> {
+        let file = FileKey::of(input)?;
+        let entry = Self::read_entry(&self.entry_path(&file, args))?;
+        (entry.file == file && entry.args == args).then_some(entry.output)
+    }
+
+    /// Stores probe output; files whose metadata can't be read aren't cached
+    pub fn put(&self, input: &Path, args: &[&str], output: &str) -> Result<()> {
+        let Some(file) = FileKey::of(input) else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.entry_path(&file, args);
+        let entry = CacheEntry {
+            file,
+            args: args.iter().map(|a| a.to_string()).collect(),
+            output: output.to_string(),
+        };
+        std::fs::write(path, serde_json::to_string(&entry).unwrap_or_default())?;
+        self.evict()
+    }
+
+    /// Drops the least recently written entries beyond `max_entries`
+    fn evict(&self) -> Result<()> {
+        let mut entries = self.entries();
+        if entries.len() <= self.max_entries {
+            return Ok(());
+        }
+        let written = |path: &PathBuf| path.metadata().and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+        entries.sort_by_cached_key(written);
+        for path in &entries[..entries.len() - self.max_entries] {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn entries(&self) -> Vec<PathBuf> {
+        std::fs::read_dir(&self.dir)
+            .map(|dir| {
+                dir.filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every entry for `input`, returning how many were removed
+    pub fn invalidate(&self, input: impl AsRef<Path>) -> Result<usize> {
+        let input = input.as_ref();
+        let path = std::fs::canonicalize(input).unwrap_or_else(|_| input.to_path_buf()).display().to_string();
+        let mut removed = 0;
+        for entry_path in self.entries() {
+            if Self::read_entry(&entry_path).is_some_and(|e| e.file.path == path) {
+                std::fs::remove_file(entry_path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Removes every entry, returning how many there were
+    pub fn clear(&self) -> Result<usize> {
+        let entries = self.entries();
+        for entry_path in &entries {
+            std::fs::remove_file(entry_path)?;
+        }
+        Ok(entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const ARGS: &[&str] = &["-v", "error", "-show_format"];
+
+    fn setup() -> (TempDir, ProbeCache, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let cache = ProbeCache::new(dir.path().join("cache"));
+        let input = dir.path().join("talk.mp4");
+        std::fs::write(&input, b"video").unwrap();
+        (dir, cache, input)
+    }
+
+    #[test]
+    fn test_round_trip_keyed_by_args() {
+        let (_dir, cache, input) = setup();
+        assert_eq!(cache.get(&input, ARGS), None);
+
+        cache.put(&input, ARGS, "{\"format\": {}}").unwrap();
+        assert_eq!(cache.get(&input, ARGS).as_deref(), Some("{\"format\": {}}"));
+        assert_eq!(cache.get(&input, &["-show_streams"]), None);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_modified_file_misses() {
+        let (_dir, cache, input) = setup();
+        cache.put(&input, ARGS, "old").unwrap();
+        std::fs::write(&input, b"a longer video").unwrap();
+        assert_eq!(cache.get(&input, ARGS), None);
+    }
+
+    #[test]
+    fn test_invalidate_and_clear() {
+        let (dir, cache, input) = setup();
+        let other = dir.path().join("other.mp4");
+        std::fs::write(&other, b"other").unwrap();
+        cache.put(&input, ARGS, "a").unwrap();
+        cache.put(&input, &["-show_streams"], "b").unwrap();
+        cache.put(&other, ARGS, "c").unwrap();
+
+        assert_eq!(cache.invalidate(&input).unwrap(), 2);
+        assert_eq!(cache.get(&other, ARGS).as_deref(), Some("c"));
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_oldest_entries_are_evicted() {
+        let (dir, mut cache, input) = setup();
+        cache.set_max_entries(2);
+        let other = dir.path().join("other.mp4");
+        std::fs::write(&other, b"other").unwrap();
+        cache.put(&input, ARGS, "a").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.put(&input, &["-show_streams"], "b").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.put(&other, ARGS, "c").unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&input, ARGS), None);
+        assert_eq!(cache.get(&other, ARGS).as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn test_keys_are_stable() {
+        assert_eq!(cache_key(b"talk.mp4"), cache_key(b"talk.mp4"));
+        assert_eq!(cache_key(b""), "e3b0c44298fc1c14");
+    }
+
+    #[test]
+    fn test_missing_files_are_not_cached() {
+        let (dir, cache, _input) = setup();
+        cache.put(&dir.path().join("missing.mp4"), ARGS, "x").unwrap();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,319 @@
+use crate::error::{VideoClipError, Result};
+use crate::time_parser::TimeParser;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Clip range arithmetic
+/// Operations on `[start, end)` second ranges: merging overlaps, cutting out
+/// exclusion zones (ad breaks, dead air), splitting long ranges and spotting
+/// duplicates in a batch before any FFmpeg work starts.
+/// `SplitOptions` turns one long request into platform-sized parts (e.g. 60s
+/// for Shorts), optionally overlapping so no moment falls on a cut.
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl TimeRange {
+    /// A non-empty range; `end` must be after `start`
+    pub fn new(start: f64, end: f64) -> Result<Self> {
+        TimeParser::validate_time_range(start, end)?;
+        Ok(Self { start, end })
+    }
+
+    /// Range from user-facing times such as `"1:30"` and `"2:00"`
+    pub fn parse(start: &str, end: &str) -> Result<Self> {
+        Self::new(TimeParser::parse_to_seconds(start)?, TimeParser::parse_to_seconds(end)?)
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+
+    pub fn contains(&self, seconds: f64) -> bool {
+        self.start <= seconds && seconds < self.end
+    }
+
+    /// Whether the ranges share any time; touching ranges don't overlap
+    pub fn overlaps(&self, other: &TimeRange) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    pub fn intersection(&self, other: &TimeRange) -> Option<TimeRange> {
+        self.overlaps(other).then(|| TimeRange {
+            start: self.start.max(other.start),
+            end: self.end.min(other.end),
+        })
+    }
+
+    /// Consecutive pieces of at most `max_duration` seconds; the last may be shorter
+    pub fn split(&self, max_duration: f64) -> Vec<TimeRange> {
+        if !(max_duration.is_finite() && max_duration > 0.0) {
+            return vec![*self];
+        }
+        let mut pieces = Vec::new();
+        let mut start = self.start;
+        while start < self.end {
+            let end = (start + max_duration).min(self.end);
+            pieces.push(TimeRange { start, end });
+            start = end;
+        }
+        pieces
+    }
+
+    /// Extended by `pre` seconds before and `post` after, clamped to the
+    /// start of the media and to `media_end` when it's known
+    pub fn pad(&self, pre: f64, post: f64, media_end: Option<f64>) -> TimeRange {
+        let end = self.end + post;
+        TimeRange {
+            start: (self.start - pre).max(0.0),
+            end: media_end.map_or(end, |media_end| end.min(media_end.max(self.end))),
+        }
+    }
+
+    /// What's left of this range once `exclusion` is cut out (zero, one or two pieces)
+    pub fn subtract(&self, exclusion: &TimeRange) -> Vec<TimeRange> {
+        if !self.overlaps(exclusion) {
+            return vec![*self];
+        }
+        [
+            TimeRange { start: self.start, end: exclusion.start },
+            TimeRange { start: exclusion.end, end: self.end },
+        ]
+        .into_iter()
+        .filter(|piece| piece.end > piece.start)
+        .collect()
+    }
+}
+
+/// How split parts are named
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PartNaming {
+    /// Each part is named after its own range, like any other clip
+    #[default]
+    Range,
+    /// `<clip>_part01of03`, named after the whole requested range
+    Numbered,
+}
+
+impl FromStr for PartNaming {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "range" => Ok(PartNaming::Range),
+            "numbered" => Ok(PartNaming::Numbered),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown part naming '{}'", other))),
+        }
+    }
+}
+
+/// Shortest tail split off as a part of its own
+pub const MIN_PART_SECONDS: f64 = 1.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct SplitOptions {
+    /// Longest allowed part
+    pub max_seconds: f64,
+    /// Seconds each part repeats from the end of the previous one, at most
+    /// half of `max_seconds`
+    #[serde(default)]
+    pub overlap_seconds: f64,
+    #[serde(default)]
+    pub naming: PartNaming,
+}
+
+impl SplitOptions {
+    pub fn new(max_seconds: f64) -> Self {
+        Self {
+            max_seconds,
+            overlap_seconds: 0.0,
+            naming: PartNaming::default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.max_seconds.is_finite() && self.max_seconds > 0.0) {
+            return Err(VideoClipError::InvalidOptions(format!("maximum part length must be positive, got {}s", self.max_seconds)));
+        }
+        // Longer overlaps make a part for every sliver of new footage
+        if !(0.0..=self.max_seconds / 2.0).contains(&self.overlap_seconds) {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "part overlap must be at least 0s and at most half the {}s part length, got {}s",
+                self.max_seconds, self.overlap_seconds
+            )));
+        }
+        Ok(())
+    }
+
+    /// Parts of `range`, each at most `max_seconds` long and starting
+    /// `overlap_seconds` before the previous one ended. Less than
+    /// [`MIN_PART_SECONDS`] left at the end is added to the last part
+    /// instead of becoming a part of its own.
+    pub fn parts(&self, range: &TimeRange) -> Vec<TimeRange> {
+        let step = self.max_seconds - self.overlap_seconds;
+        let mut parts = Vec::new();
+        let mut start = range.start;
+        loop {
+            let mut end = (start + self.max_seconds).min(range.end);
+            if range.end - end < MIN_PART_SECONDS {
+                end = range.end;
+            }
+            parts.push(TimeRange { start, end });
+            if end >= range.end {
+                return parts;
+            }
+            start += step;
+        }
+    }
+}
+
+fn sorted(ranges: &[TimeRange]) -> Vec<TimeRange> {
+    let mut ranges = ranges.to_vec();
+    ranges.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.end.total_cmp(&b.end)));
+    ranges
+}
+
+/// Sorted ranges with overlapping and touching ones combined
+pub fn merge(ranges: &[TimeRange]) -> Vec<TimeRange> {
+    let mut merged: Vec<TimeRange> = Vec::new();
+    for range in sorted(ranges) {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// `ranges` with every exclusion cut out, sorted and merged
+pub fn subtract(ranges: &[TimeRange], exclusions: &[TimeRange]) -> Vec<TimeRange> {
+    let exclusions = merge(exclusions);
+    merge(ranges)
+        .into_iter()
+        .flat_map(|range| {
+            exclusions.iter().fold(vec![range], |pieces, exclusion| {
+                pieces.iter().flat_map(|piece| piece.subtract(exclusion)).collect()
+            })
+        })
+        .collect()
+}
+
+/// Every range split into pieces of at most `max_duration` seconds, in order
+pub fn split(ranges: &[TimeRange], max_duration: f64) -> Vec<TimeRange> {
+    ranges.iter().flat_map(|range| range.split(max_duration)).collect()
+}
+
+/// Index pairs `(i, j)`, `i < j`, of ranges that are exactly the same
+pub fn duplicates(ranges: &[TimeRange]) -> Vec<(usize, usize)> {
+    pairs(ranges, |a, b| a == b)
+}
+
+/// Index pairs `(i, j)`, `i < j`, of ranges that share any time
+pub fn overlapping(ranges: &[TimeRange]) -> Vec<(usize, usize)> {
+    pairs(ranges, TimeRange::overlaps)
+}
+
+fn pairs(ranges: &[TimeRange], matches: impl Fn(&TimeRange, &TimeRange) -> bool) -> Vec<(usize, usize)> {
+    let mut found = Vec::new();
+    for (i, a) in ranges.iter().enumerate() {
+        for (j, b) in ranges.iter().enumerate().skip(i + 1) {
+            if matches(a, b) {
+                found.push((i, j));
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: f64, end: f64) -> TimeRange {
+        TimeRange::new(start, end).unwrap()
+    }
+
+    #[test]
+    fn test_construction() {
+        assert_eq!(TimeRange::parse("1:00", "1:30").unwrap(), range(60.0, 90.0));
+        assert!(matches!(TimeRange::new(5.0, 5.0), Err(VideoClipError::InvalidTimeRange { .. })));
+        assert_eq!(range(10.0, 25.0).duration(), 15.0);
+        assert!(range(10.0, 20.0).contains(10.0));
+        assert!(!range(10.0, 20.0).contains(20.0));
+    }
+
+    #[test]
+    fn test_overlap_and_intersection() {
+        assert!(range(0.0, 10.0).overlaps(&range(5.0, 15.0)));
+        assert!(!range(0.0, 10.0).overlaps(&range(10.0, 15.0)));
+        assert_eq!(range(0.0, 10.0).intersection(&range(5.0, 15.0)), Some(range(5.0, 10.0)));
+        assert_eq!(range(0.0, 10.0).intersection(&range(20.0, 30.0)), None);
+    }
+
+    #[test]
+    fn test_pad_is_clamped_to_the_media() {
+        assert_eq!(range(10.0, 20.0).pad(2.0, 3.0, None), range(8.0, 23.0));
+        assert_eq!(range(1.0, 20.0).pad(2.0, 3.0, Some(21.5)), range(0.0, 21.5));
+        // A range already past the probed end isn't shortened
+        assert_eq!(range(10.0, 30.0).pad(0.0, 5.0, Some(25.0)), range(10.0, 30.0));
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = merge(&[range(30.0, 40.0), range(0.0, 10.0), range(5.0, 15.0), range(15.0, 20.0)]);
+        assert_eq!(merged, vec![range(0.0, 20.0), range(30.0, 40.0)]);
+        assert!(merge(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_subtract_ad_breaks() {
+        let show = [range(0.0, 1800.0)];
+        let breaks = [range(600.0, 720.0), range(1200.0, 1320.0), range(1750.0, 1900.0)];
+        assert_eq!(
+            subtract(&show, &breaks),
+            vec![range(0.0, 600.0), range(720.0, 1200.0), range(1320.0, 1750.0)]
+        );
+        assert!(range(10.0, 20.0).subtract(&range(0.0, 30.0)).is_empty());
+        assert_eq!(range(10.0, 20.0).subtract(&range(30.0, 40.0)), vec![range(10.0, 20.0)]);
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(range(0.0, 150.0).split(60.0), vec![range(0.0, 60.0), range(60.0, 120.0), range(120.0, 150.0)]);
+        assert_eq!(range(0.0, 30.0).split(60.0), vec![range(0.0, 30.0)]);
+        assert_eq!(split(&[range(0.0, 20.0), range(100.0, 110.0)], 10.0).len(), 3);
+    }
+
+    #[test]
+    fn test_split_options_with_overlap() {
+        let options = SplitOptions { overlap_seconds: 5.0, ..SplitOptions::new(60.0) };
+        assert!(options.validate().is_ok());
+        assert_eq!(
+            options.parts(&range(0.0, 150.0)),
+            vec![range(0.0, 60.0), range(55.0, 115.0), range(110.0, 150.0)]
+        );
+        assert_eq!(SplitOptions::new(60.0).parts(&range(0.0, 120.0)), vec![range(0.0, 60.0), range(60.0, 120.0)]);
+        assert_eq!(SplitOptions::new(60.0).parts(&range(10.0, 40.0)), vec![range(10.0, 40.0)]);
+        // A sliver at the end joins the last part
+        assert_eq!(SplitOptions::new(60.0).parts(&range(0.0, 120.5)), vec![range(0.0, 60.0), range(60.0, 120.5)]);
+        assert_eq!(options.parts(&range(0.0, 115.5)), vec![range(0.0, 60.0), range(55.0, 115.5)]);
+
+        assert!(SplitOptions::new(0.0).validate().is_err());
+        assert!(SplitOptions { overlap_seconds: 30.0, ..SplitOptions::new(60.0) }.validate().is_ok());
+        assert!(SplitOptions { overlap_seconds: 31.0, ..SplitOptions::new(60.0) }.validate().is_err());
+        assert_eq!("numbered".parse::<PartNaming>().unwrap(), PartNaming::Numbered);
+    }
+
+    #[test]
+    fn test_duplicates_and_overlaps() {
+        let ranges = [range(0.0, 10.0), range(5.0, 15.0), range(0.0, 10.0), range(20.0, 30.0)];
+        assert_eq!(duplicates(&ranges), vec![(0, 2)]);
+        assert_eq!(overlapping(&ranges), vec![(0, 1), (0, 2), (1, 2)]);
+    }
+}
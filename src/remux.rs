@@ -0,0 +1,224 @@
+use crate::error::{VideoClipError, Result};
+use crate::probe::{MediaInfo, StreamInfo};
+use crate::renditions::VIDEO_FORMATS;
+#[cfg(not(feature = "wasm"))]
+use crate::video_clipper::VideoClipper;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Lossless remux
+/// Rewraps a whole file into another container (an MKV a phone won't play
+/// into an MP4, say) with every stream copied, so nothing is re-encoded and
+/// there's no generation loss. The result is probed and must have the
+/// source's streams, codecs and durations; a remux that dropped or cut
+/// anything short counts as failed.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemuxReport {
+    pub input_file: String,
+    pub output_file: String,
+    /// Streams in the source and in the remux
+    pub source_streams: usize,
+    pub output_streams: usize,
+    pub source_duration: Option<f64>,
+    pub output_duration: Option<f64>,
+    /// Every way the remux differs from its source; empty when it matches
+    pub mismatches: Vec<String>,
+}
+
+/// Muxers round durations to their own timebase and packet boundaries, so
+/// a matching remux can be off by up to about a frame
+pub const DURATION_TOLERANCE: f64 = 0.05;
+
+impl RemuxReport {
+    /// Compares a remux's probe against its source's: the same streams in
+    /// the same order, with the same codecs, and durations within
+    /// [`DURATION_TOLERANCE`]
+    pub fn compare(input_file: impl Into<String>, output_file: impl Into<String>, source: &MediaInfo, output: &MediaInfo) -> Self {
+        let mut mismatches = Vec::new();
+        if source.streams.len() != output.streams.len() {
+            mismatches.push(format!("{} streams became {}", source.streams.len(), output.streams.len()));
+        }
+        for (i, (before, after)) in source.streams.iter().zip(&output.streams).enumerate() {
+            if (&before.codec_type, &before.codec_name) != (&after.codec_type, &after.codec_name) {
+                mismatches.push(format!("stream {} was {} and is now {}", i, describe(before), describe(after)));
+            } else if let Some(drift) = drift(before.duration, after.duration) {
+                mismatches.push(format!("stream {} ({}) is {:+.3}s off", i, describe(before), drift));
+            }
+        }
+        if let Some(drift) = drift(source.duration, output.duration) {
+            mismatches.push(format!("duration is {:+.3}s off", drift));
+        }
+
+        Self {
+            input_file: input_file.into(),
+            output_file: output_file.into(),
+            source_streams: source.streams.len(),
+            output_streams: output.streams.len(),
+            source_duration: source.duration,
+            output_duration: output.duration,
+            mismatches,
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// `video h264`, `audio aac`, ...
+fn describe(stream: &StreamInfo) -> String {
+    format!("{} {}", stream.codec_type, stream.codec_name.as_deref().unwrap_or("unknown"))
+}
+
+/// How far `after` is from `before`, when both are known and it's too far
+fn drift(before: Option<f64>, after: Option<f64>) -> Option<f64> {
+    let drift = after? - before?;
+    (drift.abs() > DURATION_TOLERANCE).then_some(drift)
+}
+
+/// Checks `output` is a container a remux can write and isn't `input` itself
+pub fn validate_remux(input: &Path, output: &Path) -> Result<()> {
+    let container = output.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    if !VIDEO_FORMATS.contains(&container) {
+        return Err(VideoClipError::InvalidOptions(format!(
+            "can't remux to '{}' (expected a {} file)",
+            output.display(),
+            VIDEO_FORMATS.join(", ")
+        )));
+    }
+    if input == output {
+        return Err(VideoClipError::InvalidOptions(format!("remuxing {} would overwrite it", input.display())));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct RemuxCommand {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+impl RemuxCommand {
+    pub fn new(input: impl AsRef<Path>, output: impl AsRef<Path>) -> Self {
+        Self { input: input.as_ref().to_path_buf(), output: output.as_ref().to_path_buf() }
+    }
+
+    /// Every stream, chapter and metadata tag copied as it is. Nothing is
+    /// converted, so a stream the container can't hold fails the remux
+    /// instead of being dropped or re-encoded.
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-hide_banner".into(),
+            "-i".into(), self.input.display().to_string(),
+            "-map".into(), "0".into(),
+            "-c".into(), "copy".into(),
+            "-map_metadata".into(), "0".into(),
+            "-map_chapters".into(), "0".into(),
+        ];
+        if self.output.extension().is_some_and(|e| e != "mkv") {
+            args.extend(["-movflags".into(), "+faststart".into()]);
+        }
+        args.extend(["-y".into(), self.output.display().to_string()]);
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    /// Remuxes and compares the result with the source. A remux that doesn't
+    /// match is deleted and reported as an error.
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<RemuxReport> {
+        validate_remux(&self.input, &self.output)?;
+        let source = crate::probe::probe(&self.input)?;
+        crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Remux failed")?;
+        let output = crate::probe::probe(&self.output)?;
+
+        let report = RemuxReport::compare(self.input.display().to_string(), self.output.display().to_string(), &source, &output);
+        if !report.is_exact() {
+            let _ = std::fs::remove_file(&self.output);
+            return Err(VideoClipError::FFmpegError(format!(
+                "remux of {} doesn't match its source: {}",
+                self.input.display(),
+                report.mismatches.join("; ")
+            )));
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+impl VideoClipper {
+    /// Rewraps `input` into a `container` (`mp4`, `mov` or `mkv`) file of
+    /// the same name in the output directory, verifying nothing was lost
+    pub fn remux(&self, input: &Path, container: &str) -> Result<RemuxReport> {
+        self.validate_video_input(input)?;
+        self.ensure_output_dir()?;
+        let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("remux");
+        let output = self.output_dir().join(format!("{}.{}", stem, container));
+        RemuxCommand::new(input, output).execute()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(codec_type: &str, codec_name: &str, duration: Option<f64>) -> StreamInfo {
+        StreamInfo {
+            codec_type: codec_type.to_string(),
+            codec_name: Some(codec_name.to_string()),
+            duration,
+            ..Default::default()
+        }
+    }
+
+    fn media(duration: f64, streams: Vec<StreamInfo>) -> MediaInfo {
+        MediaInfo { duration: Some(duration), streams, ..Default::default() }
+    }
+
+    #[test]
+    fn test_matching_remux() {
+        // Matroska doesn't report stream durations, so only the container's is compared
+        let source = media(600.0, vec![stream("video", "h264", None), stream("audio", "aac", None)]);
+        let output = media(600.021, vec![stream("video", "h264", Some(600.0)), stream("audio", "aac", Some(600.021))]);
+        let report = RemuxReport::compare("talk.mkv", "out/talk.mp4", &source, &output);
+        assert!(report.is_exact(), "{:?}", report.mismatches);
+        assert_eq!((report.source_streams, report.output_streams), (2, 2));
+    }
+
+    #[test]
+    fn test_lost_streams_and_time_are_mismatches() {
+        let source = media(600.0, vec![stream("video", "h264", Some(600.0)), stream("audio", "aac", Some(600.0)), stream("subtitle", "subrip", None)]);
+        let output = media(540.0, vec![stream("video", "h264", Some(540.0)), stream("audio", "mp3", Some(600.0))]);
+        let report = RemuxReport::compare("talk.mkv", "out/talk.mp4", &source, &output);
+        assert_eq!(
+            report.mismatches,
+            vec![
+                "3 streams became 2",
+                "stream 0 (video h264) is -60.000s off",
+                "stream 1 was audio aac and is now audio mp3",
+                "duration is -60.000s off",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remux_command() {
+        assert_eq!(
+            RemuxCommand::new("talk.mkv", "out/talk.mp4").get_command_string(),
+            "ffmpeg -hide_banner -i talk.mkv -map 0 -c copy -map_metadata 0 -map_chapters 0 -movflags +faststart -y out/talk.mp4"
+        );
+        assert!(!RemuxCommand::new("talk.mp4", "out/talk.mkv").get_command_string().contains("movflags"));
+        assert!(validate_remux(Path::new("talk.mkv"), Path::new("talk.webm")).is_err());
+        assert!(validate_remux(Path::new("talk.mp4"), Path::new("talk.mp4")).is_err());
+        assert!(validate_remux(Path::new("talk.mkv"), Path::new("out/talk.mp4")).is_ok());
+    }
+}
@@ -0,0 +1,490 @@
+//! Multi-rendition output
+//! Renders one clip range to several outputs (e.g. a full-quality MP4, a
+//! 720p web MP4 and an MP3) with a single FFmpeg invocation, so the source
+//! is read and decoded once and each output only pays for its own encode.
+//! A proxy is the common special case: the exact stream copy for the archive
+//! plus a small draft encode for review tools, named after it.
+
+use crate::encoder::{EncoderChoice, EncoderSelector, VideoCodec};
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use crate::ffmpeg::limits::ProcessLimits;
+use crate::metadata::ClipMetadata;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Rendition {
+    /// Appended to the clip's file name, e.g. `talk_clip_01-00_to_01-30_web.mp4`
+    pub name: String,
+    /// `copy` (the default) keeps the source video unless the rendition is scaled
+    #[serde(default)]
+    pub video_codec: VideoCodec,
+    /// Scale to this height, keeping the aspect ratio
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Drop the video and keep only the audio
+    #[serde(default)]
+    pub audio_only: bool,
+    /// Container: mp4, mov or mkv (ProRes needs mov), or mp3, m4a or wav for
+    /// audio-only renditions
+    #[serde(default = "default_format")]
+    pub format: String,
+    /// Fast, small encode (e.g. for review proxies) instead of visually lossless
+    #[serde(default)]
+    pub draft: bool,
+    /// Target video bit rate in kb/s, capped at that rate, instead of
+    /// constant quality
+    #[serde(default)]
+    pub bitrate_kbps: Option<u32>,
+}
+
+fn default_format() -> String {
+    "mp4".to_string()
+}
+
+/// Low-res review copy written next to a stream-copied clip
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ProxyOptions {
+    /// Proxy frame height; width follows the source aspect ratio
+    #[serde(default = "default_proxy_height")]
+    pub height: u32,
+}
+
+fn default_proxy_height() -> u32 {
+    540
+}
+
+impl Default for ProxyOptions {
+    fn default() -> Self {
+        Self {
+            height: default_proxy_height(),
+        }
+    }
+}
+
+impl ProxyOptions {
+    pub fn validate(&self) -> Result<()> {
+        self.rendition().validate()
+    }
+
+    /// `<clip stem>_proxy.mp4`: an H.264 draft at `height`
+    pub fn rendition(&self) -> Rendition {
+        Rendition {
+            video_codec: VideoCodec::H264,
+            height: Some(self.height),
+            draft: true,
+            ..Rendition::new("proxy")
+        }
+    }
+}
+
+pub(crate) const VIDEO_FORMATS: [&str; 3] = ["mp4", "mov", "mkv"];
+const AUDIO_FORMATS: [&str; 3] = ["mp3", "m4a", "wav"];
+/// Names a proxy's two outputs report under
+const RESERVED_NAMES: [&str; 2] = ["master", "proxy"];
+
+impl Rendition {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            video_codec: VideoCodec::default(),
+            height: None,
+            audio_only: false,
+            format: default_format(),
+            draft: false,
+            bitrate_kbps: None,
+        }
+    }
+
+    /// Audio-only rendition in `format`
+    pub fn audio(name: impl Into<String>, format: impl Into<String>) -> Self {
+        Self {
+            audio_only: true,
+            format: format.into(),
+            ..Self::new(name)
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "rendition name '{}' must be letters, digits, '-' or '_'", self.name
+            )));
+        }
+        let formats: &[&str] = if self.audio_only { &AUDIO_FORMATS } else { &VIDEO_FORMATS };
+        if !formats.contains(&self.format.as_str()) {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "rendition '{}' can't be written as {} (expected one of {})",
+                self.name, self.format, formats.join(", ")
+            )));
+        }
+        if !self.audio_only && self.video_codec == VideoCodec::ProRes && self.format != "mov" {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "rendition '{}' is ProRes and must be written as mov, not {}", self.name, self.format
+            )));
+        }
+        if self.height == Some(0) {
+            return Err(VideoClipError::InvalidOptions(format!("rendition '{}' height must be positive", self.name)));
+        }
+        if self.bitrate_kbps == Some(0) {
+            return Err(VideoClipError::InvalidOptions(format!("rendition '{}' bit rate must be positive", self.name)));
+        }
+        Ok(())
+    }
+
+    /// The requested codec, promoted from copy to H.264 when frames must be
+    /// filtered (by the rendition's scale or the request's shared filters)
+    /// or the rendition has a bit rate
+    pub fn effective_video_codec(&self, shared_filters: bool) -> VideoCodec {
+        if self.video_codec == VideoCodec::Copy && (shared_filters || self.height.is_some() || self.bitrate_kbps.is_some()) {
+            VideoCodec::H264
+        } else {
+            self.video_codec
+        }
+    }
+
+    /// Software encoder for this rendition; `None` for stream copy and audio-only
+    /// renditions. Hardware encoders are skipped since their device setup
+    /// would apply to every output of the shared input.
+    pub fn encoder(&self, shared_filters: bool) -> Result<Option<EncoderChoice>> {
+        if self.audio_only {
+            return Ok(None);
+        }
+        let mut selector = EncoderSelector::new(self.effective_video_codec(shared_filters));
+        selector.set_allow_hardware(false);
+        selector.resolve()
+    }
+
+    /// `<clip stem>_<name>.<format>` next to `clip_output`
+    pub fn output_path(&self, clip_output: &Path) -> PathBuf {
+        let stem = clip_output.file_stem().and_then(|s| s.to_str()).unwrap_or("clip");
+        clip_output.with_file_name(format!("{}_{}.{}", stem, self.name, self.format))
+    }
+}
+
+/// `NAME[:SPEC,...]` where each spec is a height (`720p`), a bit rate
+/// (`3000k`), a container (`mkv`), an audio format (`mp3`, implying audio
+/// only), `draft` or a video codec (`hevc`), e.g. `web:720p` or `audio:mp3`.
+/// Without a container the codec's own is used (mov for ProRes, mkv for VP9).
+impl FromStr for Rendition {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, specs) = s.split_once(':').unwrap_or((s, ""));
+        let mut rendition = Rendition::new(name);
+        let mut container = None;
+        for spec in specs.split(',').map(str::trim).filter(|spec| !spec.is_empty()) {
+            if let Some(height) = spec.strip_suffix('p').and_then(|h| h.parse().ok()) {
+                rendition.height = Some(height);
+            } else if let Some(kbps) = spec.strip_suffix('k').and_then(|k| k.parse().ok()) {
+                rendition.bitrate_kbps = Some(kbps);
+            } else if VIDEO_FORMATS.contains(&spec) {
+                container = Some(spec);
+            } else if AUDIO_FORMATS.contains(&spec) {
+                rendition.audio_only = true;
+                rendition.format = spec.to_string();
+            } else if spec == "draft" {
+                rendition.draft = true;
+            } else {
+                rendition.video_codec = spec.parse().map_err(|_| {
+                    VideoClipError::InvalidOptions(format!("unknown rendition option '{}' in '{}'", spec, s))
+                })?;
+            }
+        }
+        if !rendition.audio_only {
+            rendition.format = container.unwrap_or_else(|| rendition.video_codec.container()).to_string();
+        }
+        rendition.validate()?;
+        Ok(rendition)
+    }
+}
+
+/// Checks every rendition and that their names (and so their files) are distinct
+pub fn validate_renditions(renditions: &[Rendition]) -> Result<()> {
+    for (i, rendition) in renditions.iter().enumerate() {
+        rendition.validate()?;
+        if RESERVED_NAMES.contains(&rendition.name.as_str()) {
+            return Err(VideoClipError::InvalidOptions(format!("rendition name '{}' is reserved for the proxy's outputs", rendition.name)));
+        }
+        if renditions[..i].iter().any(|r| r.name == rendition.name) {
+            return Err(VideoClipError::InvalidOptions(format!("duplicate rendition name '{}'", rendition.name)));
+        }
+    }
+    Ok(())
+}
+
+/// One rendition's output
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RenditionResult {
+    pub name: String,
+    pub output_file: String,
+    pub file_size_mb: Option<f64>,
+    /// FFmpeg video encoder used, `copy` for stream copy or `none` for audio only
+    pub encoder: String,
+}
+
+#[derive(Debug, Clone)]
+struct RenditionOutput {
+    rendition: Rendition,
+    path: PathBuf,
+    encoder: Option<EncoderChoice>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MultiRenderCommand {
+    input: PathBuf,
+    start_time: f64,
+    duration: f64,
+    video_filter_graph: FilterGraph,
+    audio_filter_graph: FilterGraph,
+    outputs: Vec<RenditionOutput>,
+    metadata: Option<ClipMetadata>,
+    process_limits: ProcessLimits,
+}
+
+impl MultiRenderCommand {
+    pub fn new(input: impl AsRef<Path>, start_time: f64, duration: f64) -> Self {
+        Self {
+            input: input.as_ref().to_path_buf(),
+            start_time,
+            duration,
+            video_filter_graph: FilterGraph::new(),
+            audio_filter_graph: FilterGraph::new(),
+            outputs: Vec::new(),
+            metadata: None,
+            process_limits: ProcessLimits::default(),
+        }
+    }
+
+    /// Video filters every video rendition gets before its own scaling
+    pub fn set_video_filter_graph(&mut self, graph: FilterGraph) {
+        self.video_filter_graph = graph;
+    }
+
+    /// Audio filters every rendition gets
+    pub fn set_audio_filter_graph(&mut self, graph: FilterGraph) {
+        self.audio_filter_graph = graph;
+    }
+
+    /// Title, description and tags written into every output
+    pub fn set_metadata(&mut self, metadata: Option<ClipMetadata>) {
+        self.metadata = metadata;
+    }
+
+    pub fn set_process_limits(&mut self, limits: ProcessLimits) {
+        self.process_limits = limits;
+    }
+
+    /// Adds `rendition`, written to `path` with `encoder` (`None` copies video)
+    pub fn add_output(&mut self, rendition: Rendition, path: impl AsRef<Path>, encoder: Option<EncoderChoice>) {
+        self.outputs.push(RenditionOutput {
+            rendition,
+            path: path.as_ref().to_path_buf(),
+            encoder,
+        });
+    }
+
+    pub fn output_paths(&self) -> Vec<&Path> {
+        self.outputs.iter().map(|o| o.path.as_path()).collect()
+    }
+
+    fn output_args(&self, output: &RenditionOutput) -> Vec<String> {
+        let mut args: Vec<String> = Vec::new();
+        let rendition = &output.rendition;
+
+        if rendition.audio_only {
+            args.extend(["-map".into(), "0:a".into(), "-vn".into()]);
+            args.extend(self.audio_filter_graph.to_audio_args());
+            let codec: &[&str] = match rendition.format.as_str() {
+                "mp3" => &["-c:a", "mp3", "-b:a", "192k"],
+                "wav" => &["-c:a", "pcm_s16le"],
+                _ => &["-c:a", "aac", "-b:a", "192k"],
+            };
+            args.extend(codec.iter().map(|s| s.to_string()));
+        } else {
+            args.extend(["-map".into(), "0:v?".into(), "-map".into(), "0:a?".into()]);
+            match &output.encoder {
+                Some(encoder) => {
+                    let mut graph = self.video_filter_graph.clone();
+                    if let Some(height) = rendition.height {
+                        graph.push(Filter::new("scale").arg(-2).arg(height));
+                    }
+                    args.extend(graph.to_args());
+                    args.extend(match rendition.bitrate_kbps {
+                        Some(kbps) => encoder.capped_args(kbps),
+                        None if rendition.draft => encoder.draft_args(),
+                        None => encoder.output_args(),
+                    });
+                }
+                None => args.extend(["-c:v".into(), "copy".into()]),
+            }
+            args.extend(self.audio_filter_graph.to_audio_args());
+            // Re-encoded renditions get AAC so they play anywhere
+            if output.encoder.is_none() && self.audio_filter_graph.is_empty() {
+                args.extend(["-c:a".into(), "copy".into()]);
+            } else {
+                args.extend(["-c:a".into(), "aac".into(), "-b:a".into(), "128k".into()]);
+            }
+            if rendition.format != "mkv" {
+                args.extend(["-movflags".into(), "+faststart".into()]);
+            }
+        }
+
+        args.extend(["-avoid_negative_ts".into(), "make_zero".into()]);
+        if let Some(metadata) = &self.metadata {
+            args.extend(metadata.ffmpeg_args());
+        }
+        args.extend(self.process_limits.thread_args());
+        args.extend(["-y".into(), output.path.display().to_string()]);
+        args
+    }
+
+    /// The range is selected on the input, so it's decoded once for every
+    /// output. A stream-copied video output starts on the keyframe before the
+    /// range, so encoded outputs then keep those frames too rather than
+    /// starting up to a GOP later.
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-ss".into(), self.start_time.to_string(),
+            "-t".into(), self.duration.to_string(),
+        ];
+        if self.outputs.iter().any(|o| o.encoder.is_none() && !o.rendition.audio_only) {
+            args.push("-noaccurate_seek".into());
+        }
+        args.extend(self.process_limits.input_args());
+        args.extend(["-i".into(), self.input.display().to_string()]);
+        for output in &self.outputs {
+            args.extend(self.output_args(output));
+        }
+        args
+    }
+
+    pub fn get_command_string(&self) -> String {
+        let mut argv = self.process_limits.priority_prefix();
+        argv.push(crate::tools::ffmpeg());
+        argv.extend(self.build_args());
+        argv.join(" ")
+    }
+
+    /// Per-rendition results; sizes are read from disk when the files exist
+    pub fn results(&self) -> Vec<RenditionResult> {
+        self.outputs.iter()
+            .map(|output| RenditionResult {
+                name: output.rendition.name.clone(),
+                output_file: output.path.display().to_string(),
+                file_size_mb: output.path.metadata().ok().map(|m| m.len() as f64 / (1024.0 * 1024.0)),
+                encoder: match (&output.encoder, output.rendition.audio_only) {
+                    (_, true) => "none".to_string(),
+                    (Some(encoder), false) => encoder.name.clone(),
+                    (None, false) => "copy".to_string(),
+                },
+            })
+            .collect()
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<()> {
+        let command = self.process_limits.command(&crate::tools::ffmpeg(), self.build_args());
+        crate::ffmpeg::FFmpegCommand::run(command, "Rendering renditions failed")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> MultiRenderCommand {
+        let mut command = MultiRenderCommand::new("talk.mp4", 60.0, 30.0);
+        let web = Rendition { height: Some(720), ..Rendition::new("web") };
+        command.add_output(Rendition::new("full"), "out/clip_full.mp4", None);
+        command.add_output(web, "out/clip_web.mp4", Some(EncoderChoice::new("libx264")));
+        command.add_output(Rendition::audio("audio", "mp3"), "out/clip_audio.mp3", None);
+        command
+    }
+
+    #[test]
+    fn test_one_input_many_outputs() {
+        let cmd = command().get_command_string();
+        assert!(cmd.starts_with("ffmpeg -ss 60 -t 30 -noaccurate_seek -i talk.mp4 -map 0:v? -map 0:a? -c:v copy -c:a copy"));
+        assert_eq!(cmd.matches("-i ").count(), 1);
+        assert!(cmd.contains("-vf scale=-2:720 -c:v libx264"));
+        assert!(cmd.contains("-map 0:a -vn -c:a mp3 -b:a 192k -avoid_negative_ts make_zero -y out/clip_audio.mp3"));
+    }
+
+    #[test]
+    fn test_shared_filters_apply_to_encoded_renditions() {
+        let mut command = command();
+        command.set_video_filter_graph(FilterGraph::from(Filter::new("bwdif")));
+        command.set_audio_filter_graph(FilterGraph::from(Filter::new("volume").arg("3dB")));
+        let cmd = command.get_command_string();
+        assert!(cmd.contains("-vf bwdif,scale=-2:720"));
+        assert_eq!(cmd.matches("-af volume=3dB").count(), 3);
+        assert!(!cmd.contains("-c:a copy"));
+    }
+
+    #[test]
+    fn test_validation_and_naming() {
+        assert!(validate_renditions(&[Rendition::new("full"), Rendition::audio("audio", "m4a")]).is_ok());
+        assert!(validate_renditions(&[Rendition::new("a"), Rendition::new("a")]).is_err());
+        assert!(Rendition::new("web/720").validate().is_err());
+        assert!(Rendition::audio("audio", "mp4").validate().is_err());
+        assert!(Rendition { height: Some(0), ..Rendition::new("web") }.validate().is_err());
+
+        let web = Rendition { height: Some(720), ..Rendition::new("web") };
+        assert_eq!(web.effective_video_codec(false), VideoCodec::H264);
+        assert_eq!(Rendition::new("full").effective_video_codec(false), VideoCodec::Copy);
+        assert_eq!(
+            Rendition::audio("audio", "mp3").output_path(Path::new("out/talk_clip_01-00_to_01-30.mp4")),
+            PathBuf::from("out/talk_clip_01-00_to_01-30_audio.mp3")
+        );
+    }
+
+    #[test]
+    fn test_parse_rendition_spec() {
+        assert_eq!("full".parse::<Rendition>().unwrap(), Rendition::new("full"));
+        let web: Rendition = "web:720p,hevc,mkv".parse().unwrap();
+        assert_eq!((web.height, web.video_codec, web.format.as_str()), (Some(720), VideoCodec::Hevc, "mkv"));
+        assert_eq!("audio:mp3".parse::<Rendition>().unwrap(), Rendition::audio("audio", "mp3"));
+        assert!("review:360p,draft".parse::<Rendition>().unwrap().draft);
+        assert_eq!("web:720p,3000k".parse::<Rendition>().unwrap().bitrate_kbps, Some(3000));
+        assert!("web:0k".parse::<Rendition>().is_err());
+        assert!("web:huge".parse::<Rendition>().is_err());
+        assert!(":720p".parse::<Rendition>().is_err());
+        assert_eq!("master:prores".parse::<Rendition>().unwrap().format, "mov");
+        assert_eq!("web:vp9".parse::<Rendition>().unwrap().format, "mkv");
+        assert!("master:prores,mp4".parse::<Rendition>().is_err());
+        assert!(Rendition { video_codec: VideoCodec::ProRes, ..Rendition::new("master") }.validate().is_err());
+    }
+
+    #[test]
+    fn test_proxy_is_a_small_draft() {
+        let proxy = ProxyOptions::default().rendition();
+        assert_eq!(proxy.output_path(Path::new("out/talk_clip.mp4")), PathBuf::from("out/talk_clip_proxy.mp4"));
+
+        let mut command = MultiRenderCommand::new("talk.mp4", 0.0, 10.0);
+        command.add_output(Rendition::new("master"), "out/talk_clip.mp4", None);
+        command.add_output(proxy, "out/talk_clip_proxy.mp4", Some(EncoderChoice::new("libx264")));
+        let cmd = command.get_command_string();
+        assert!(cmd.contains("-c:v copy -c:a copy -movflags +faststart -avoid_negative_ts make_zero -y out/talk_clip.mp4"));
+        assert!(cmd.contains("-vf scale=-2:540 -c:v libx264 -preset veryfast -crf 28"));
+        // Both start on the keyframe the stream copy has to start on
+        assert!(cmd.starts_with("ffmpeg -ss 0 -t 10 -noaccurate_seek -i talk.mp4"));
+        assert!(ProxyOptions { height: 0 }.validate().is_err());
+        assert!(validate_renditions(&[Rendition::new("proxy")]).is_err());
+        assert!(validate_renditions(&[Rendition::new("master")]).is_err());
+    }
+
+    #[test]
+    fn test_results_report_encoders() {
+        let results = command().results();
+        let encoders: Vec<_> = results.iter().map(|r| r.encoder.as_str()).collect();
+        assert_eq!(encoders, vec!["copy", "libx264", "none"]);
+        assert_eq!(results[1].output_file, "out/clip_web.mp4");
+    }
+}
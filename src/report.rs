@@ -0,0 +1,248 @@
+use crate::batch::{BatchItemResult, BatchReport};
+use crate::error::{VideoClipError, Result};
+use crate::time_parser::TimeParser;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Batch run reports
+/// Renders a finished batch as a Markdown or standalone HTML page: totals,
+/// then one row per clip with its range, size and any error, for sharing
+/// with people who won't read the JSON report.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "html" | "htm" => Ok(ReportFormat::Html),
+            other => Err(VideoClipError::InvalidOptions(format!("unknown report format '{}'", other))),
+        }
+    }
+}
+
+impl ReportFormat {
+    /// Format implied by a file's extension
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or_else(|| VideoClipError::InvalidOptions(format!("can't tell the report format of '{}' (use .md or .html)", path.display())))?
+            .parse()
+    }
+}
+
+/// Totals shown at the top of a report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReportSummary {
+    pub clips: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Seconds of source covered by successful clips
+    pub total_duration: f64,
+    pub total_size_mb: f64,
+}
+
+impl ReportSummary {
+    pub fn new(report: &BatchReport) -> Self {
+        let results = report.items.iter().filter_map(|item| item.result.as_ref());
+        Self {
+            clips: report.items.len(),
+            succeeded: report.succeeded(),
+            failed: report.failed(),
+            skipped: report.skipped(),
+            total_duration: results.clone().map(|r| r.duration).sum(),
+            total_size_mb: results.filter_map(|r| r.file_size_mb).sum(),
+        }
+    }
+}
+
+fn status(item: &BatchItemResult) -> &'static str {
+    match (&item.error, item.skipped) {
+        (Some(_), _) => "failed",
+        (None, true) => "skipped",
+        (None, false) => "ok",
+    }
+}
+
+fn size(mb: Option<f64>) -> String {
+    mb.map_or_else(|| "-".to_string(), |mb| format!("{:.1} MB", mb))
+}
+
+/// `(status, input, range, output, size, error)` for each item
+fn rows(report: &BatchReport) -> Vec<[String; 6]> {
+    report.items.iter().map(|item| {
+        let result = item.result.as_ref();
+        [
+            status(item).to_string(),
+            item.input_file.clone(),
+            result.map_or_else(|| "-".to_string(), |r| format!(
+                "{} - {}", TimeParser::format_time_readable(r.start_seconds), TimeParser::format_time_readable(r.end_seconds)
+            )),
+            result.map_or_else(|| "-".to_string(), |r| r.output_file.clone()),
+            size(result.and_then(|r| r.file_size_mb)),
+            item.error.clone().unwrap_or_default(),
+        ]
+    }).collect()
+}
+
+const HEADERS: [&str; 6] = ["Status", "Input", "Range", "Output", "Size", "Error"];
+
+pub fn render(report: &BatchReport, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Markdown => render_markdown(report),
+        ReportFormat::Html => render_html(report),
+    }
+}
+
+fn render_markdown(report: &BatchReport) -> String {
+    let summary = ReportSummary::new(report);
+    let cell = |value: &str| value.replace('|', "\\|").replace('\n', " ");
+
+    let mut out = String::from("# Batch report\n\n");
+    let _ = writeln!(out, "- **Clips:** {}", summary.clips);
+    let _ = writeln!(out, "- **Succeeded:** {} ({} skipped)", summary.succeeded, summary.skipped);
+    let _ = writeln!(out, "- **Failed:** {}", summary.failed);
+    let _ = writeln!(out, "- **Duration processed:** {}", TimeParser::format_time_readable(summary.total_duration));
+    let _ = writeln!(out, "- **Total size:** {:.1} MB", summary.total_size_mb);
+    let _ = writeln!(out, "\n| {} |", HEADERS.join(" | "));
+    let _ = writeln!(out, "|{}", "---|".repeat(HEADERS.len()));
+    for row in rows(report) {
+        let cells: Vec<String> = row.iter().map(|value| cell(value)).collect();
+        let _ = writeln!(out, "| {} |", cells.join(" | "));
+    }
+    out
+}
+
+fn escape_html(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn render_html(report: &BatchReport) -> String {
+    let summary = ReportSummary::new(report);
+
+    let mut out = String::from(concat!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Batch report</title>\n<style>\n",
+        "body { font-family: sans-serif; margin: 2em; }\n",
+        "table { border-collapse: collapse; }\n",
+        "th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }\n",
+        "tr.failed { background: #fdecea; }\n",
+        "tr.skipped { color: #777; }\n",
+        "</style>\n</head>\n<body>\n<h1>Batch report</h1>\n<ul>\n",
+    ));
+    let _ = writeln!(out, "<li><strong>Clips:</strong> {}</li>", summary.clips);
+    let _ = writeln!(out, "<li><strong>Succeeded:</strong> {} ({} skipped)</li>", summary.succeeded, summary.skipped);
+    let _ = writeln!(out, "<li><strong>Failed:</strong> {}</li>", summary.failed);
+    let _ = writeln!(out, "<li><strong>Duration processed:</strong> {}</li>", TimeParser::format_time_readable(summary.total_duration));
+    let _ = writeln!(out, "<li><strong>Total size:</strong> {:.1} MB</li>", summary.total_size_mb);
+    out.push_str("</ul>\n<table>\n<tr>");
+    for header in HEADERS {
+        let _ = write!(out, "<th>{}</th>", header);
+    }
+    out.push_str("</tr>\n");
+    for row in rows(report) {
+        let _ = write!(out, "<tr class=\"{}\">", row[0]);
+        for value in &row {
+            let _ = write!(out, "<td>{}</td>", escape_html(value));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video_clipper::ClipResult;
+
+    fn result(output: &str, start: f64, end: f64, size: Option<f64>) -> ClipResult {
+        ClipResult {
+            input_file: "talk.mp4".to_string(),
+            output_file: output.to_string(),
+            start_seconds: start,
+            end_seconds: end,
+            duration: end - start,
+            file_size_mb: size,
+            command: String::new(),
+            encoder: "copy".to_string(),
+            warnings: vec![],
+            black_trim: None,
+            cut_report: None,
+            renditions: vec![],
+            recovery: None,
+            scrub: None,
+            quality: None,
+        }
+    }
+
+    fn report() -> BatchReport {
+        BatchReport {
+            items: vec![
+                BatchItemResult {
+                    input_file: "talk.mp4".to_string(),
+                    result: Some(result("out/talk_a.mp4", 0.0, 90.0, Some(12.5))),
+                    error: None,
+                    skipped: false,
+                    worker: None,
+                },
+                BatchItemResult {
+                    input_file: "talk.mp4".to_string(),
+                    result: Some(result("out/talk_b.mp4", 120.0, 150.0, Some(4.0))),
+                    error: None,
+                    skipped: true,
+                    worker: None,
+                },
+                BatchItemResult {
+                    input_file: "<panel>.mp4".to_string(),
+                    result: None,
+                    error: Some("File not found: a|b".to_string()),
+                    skipped: false,
+                    worker: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_summary_totals() {
+        let summary = ReportSummary::new(&report());
+        assert_eq!((summary.clips, summary.succeeded, summary.failed, summary.skipped), (3, 2, 1, 1));
+        assert_eq!(summary.total_duration, 120.0);
+        assert_eq!(summary.total_size_mb, 16.5);
+    }
+
+    #[test]
+    fn test_markdown() {
+        let markdown = render(&report(), ReportFormat::Markdown);
+        assert!(markdown.contains("- **Failed:** 1"));
+        assert!(markdown.contains("- **Duration processed:** 02:00"));
+        assert!(markdown.contains("| ok | talk.mp4 | 00:00 - 01:30 | out/talk_a.mp4 | 12.5 MB |  |"));
+        assert!(markdown.contains("| failed | <panel>.mp4 | - | - | - | File not found: a\\|b |"));
+    }
+
+    #[test]
+    fn test_html_is_escaped() {
+        let html = render(&report(), ReportFormat::Html);
+        assert!(html.contains("<tr class=\"failed\"><td>failed</td><td>&lt;panel&gt;.mp4</td>"));
+        assert!(html.contains("<li><strong>Total size:</strong> 16.5 MB</li>"));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ReportFormat::from_path("runs/report.md").unwrap(), ReportFormat::Markdown);
+        assert_eq!(ReportFormat::from_path("report.HTML").unwrap(), ReportFormat::Html);
+        assert!(ReportFormat::from_path("report").is_err());
+        assert!(ReportFormat::from_path("report.pdf").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use crate::batch;
+use crate::error::{VideoClipError, Result};
+use crate::ranges::TimeRange;
+use crate::time_parser::TimeParser;
+use crate::video_clipper::ClipRequest;
+#[cfg(not(feature = "wasm"))]
+use crate::video_clipper::VideoClipper;
+use serde::{Deserialize, Serialize};
+
+/// Interval sampling
+/// Spot-checks a long capture by clipping a short sample at a fixed interval
+/// across it (say 10 seconds every 10 minutes), so an overnight recording can
+/// be checked for dropouts, frozen frames or lost audio without watching it.
+/// Samples cover the request's range, or the whole file when the request
+/// leaves its times empty.
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SampleOptions {
+    /// Seconds from the start of one sample to the start of the next
+    pub every_seconds: f64,
+    /// Length of each sample
+    pub length_seconds: f64,
+}
+
+impl SampleOptions {
+    /// From user-facing times such as `"10:00"` and `"10"`
+    pub fn parse(every: &str, length: &str) -> Result<Self> {
+        let options = Self {
+            every_seconds: TimeParser::parse_to_seconds(every)?,
+            length_seconds: TimeParser::parse_to_seconds(length)?,
+        };
+        options.validate()?;
+        Ok(options)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.length_seconds.is_finite() && self.length_seconds > 0.0) {
+            return Err(VideoClipError::InvalidOptions(format!("sample length must be positive, got {}s", self.length_seconds)));
+        }
+        if !(self.every_seconds.is_finite() && self.every_seconds >= self.length_seconds) {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "samples must be at least their {}s length apart, got every {}s",
+                self.length_seconds, self.every_seconds
+            )));
+        }
+        Ok(())
+    }
+
+    /// Samples of `span`, the first at its start; the last is cut short if
+    /// `span` ends inside it
+    pub fn ranges(&self, span: &TimeRange) -> Vec<TimeRange> {
+        let mut ranges = Vec::new();
+        let mut start = span.start;
+        while start < span.end {
+            ranges.push(TimeRange { start, end: (start + self.length_seconds).min(span.end) });
+            start += self.every_seconds;
+        }
+        ranges
+    }
+}
+
+/// One request per sample of `span`, cloned from `template`. Samples are
+/// named after their range like any clip; a `template` output name gets a
+/// sample number added.
+pub fn sample_requests_in(template: &ClipRequest, span: &TimeRange, options: &SampleOptions) -> Result<Vec<ClipRequest>> {
+    options.validate()?;
+    let ranges = options.ranges(span);
+    let requests: Vec<ClipRequest> = ranges
+        .iter()
+        .enumerate()
+        .map(|(i, range)| ClipRequest {
+            start_time: range.start.to_string(),
+            end_time: range.end.to_string(),
+            output_name: template.output_name.as_ref().map(|name| format!("{}_sample{:03}", name, i + 1)),
+            ..template.clone()
+        })
+        .collect();
+    batch::validate_requests(&requests)?;
+    Ok(requests)
+}
+
+/// Whether `request` leaves its range to the whole file
+pub fn is_whole_file(request: &ClipRequest) -> bool {
+    request.start_time.trim().is_empty() && request.end_time.trim().is_empty()
+}
+
+/// Sample requests across `template`'s range, or across the whole input
+/// (probed for its duration) when the range is left empty
+#[cfg(not(feature = "wasm"))]
+pub fn sample_requests(template: &ClipRequest, options: &SampleOptions) -> Result<Vec<ClipRequest>> {
+    let span = if is_whole_file(template) {
+        let duration = crate::probe::probe(&template.input_file)?
+            .duration
+            .ok_or_else(|| VideoClipError::InvalidOptions(format!("{} has no known duration to sample across", template.input_file)))?;
+        TimeRange::new(0.0, duration)?
+    } else {
+        template.time_range()?
+    };
+    sample_requests_in(template, &span, options)
+}
+
+#[cfg(not(feature = "wasm"))]
+impl VideoClipper {
+    /// Clips a sample every `options.every_seconds` across `template`'s
+    /// range (or the whole input), as a batch
+    pub fn clip_samples(&self, template: &ClipRequest, options: &SampleOptions) -> Result<batch::BatchReport> {
+        let requests = sample_requests(template, options)?;
+        Ok(self.clip_batch(&requests))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_ranges() {
+        let options = SampleOptions::parse("10:00", "10").unwrap();
+        let ranges = options.ranges(&TimeRange { start: 0.0, end: 1805.0 });
+        assert_eq!(
+            ranges,
+            vec![
+                TimeRange { start: 0.0, end: 10.0 },
+                TimeRange { start: 600.0, end: 610.0 },
+                TimeRange { start: 1200.0, end: 1210.0 },
+                TimeRange { start: 1800.0, end: 1805.0 },
+            ]
+        );
+        assert!(SampleOptions::parse("5", "10").is_err());
+        assert!(SampleOptions::parse("10:00", "0").is_err());
+    }
+
+    #[test]
+    fn test_sample_requests() {
+        let template = ClipRequest { input_file: "night.mkv".to_string(), output_name: Some("qc".to_string()), ..Default::default() };
+        assert!(is_whole_file(&template));
+        let options = SampleOptions { every_seconds: 60.0, length_seconds: 5.0 };
+        let requests = sample_requests_in(&template, &TimeRange { start: 30.0, end: 150.0 }, &options).unwrap();
+        let summary: Vec<(&str, &str, &str)> = requests
+            .iter()
+            .map(|r| (r.start_time.as_str(), r.end_time.as_str(), r.output_name.as_deref().unwrap()))
+            .collect();
+        assert_eq!(summary, vec![("30", "35", "qc_sample001"), ("90", "95", "qc_sample002")]);
+    }
+}
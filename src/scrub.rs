@@ -0,0 +1,107 @@
+//! Metadata scrubbing
+//! Phones and cameras tag recordings with where and when they were made and
+//! on what device. A clip made with `strip_metadata` is written without any
+//! of the source's container, stream or chapter metadata, then probed to
+//! prove none of those tags survived; the report lists what was dropped.
+
+use crate::probe::MediaInfo;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "wasm"))]
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ScrubReport {
+    /// Sensitive tags the source had that the clip doesn't, e.g. `location`
+    /// or `stream 0 creation_time`
+    pub removed: Vec<String>,
+    /// Sensitive tags the clip still has; empty for a clean clip
+    pub remaining: Vec<String>,
+}
+
+/// Parts of tag names (compared lowercase) that identify a place, a time or a
+/// device, e.g. `location`, `com.apple.quicktime.location.ISO6709`,
+/// `creation_time` or `com.android.manufacturer`
+const SENSITIVE_KEYS: &[&str] = &[
+    "location", "gps", "xyz", "creation_time", "date", "make", "model", "manufacturer", "serial", "device", "software",
+    "com.android.",
+];
+
+pub fn is_sensitive(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SENSITIVE_KEYS.iter().any(|part| key.contains(part))
+}
+
+/// Output options that drop every tag and chapter the source had; tags the
+/// request sets itself (title, languages, ...) are still written
+pub fn strip_args() -> Vec<String> {
+    ["-map_metadata", "-1", "-map_metadata:s", "-1", "-map_chapters", "-1"].map(String::from).to_vec()
+}
+
+/// Sensitive tags of a file, named as in [`ScrubReport`]
+fn sensitive_tags(info: &MediaInfo) -> Vec<String> {
+    let format = info.tags.keys().filter(|key| is_sensitive(key)).cloned();
+    let streams = info.streams.iter().flat_map(|stream| {
+        stream.tags.keys()
+            .filter(|key| is_sensitive(key))
+            .map(move |key| format!("stream {} {}", stream.index, key))
+    });
+    format.chain(streams).collect()
+}
+
+impl ScrubReport {
+    pub fn compare(source: &MediaInfo, output: &MediaInfo) -> Self {
+        let remaining = sensitive_tags(output);
+        let removed = sensitive_tags(source).into_iter().filter(|tag| !remaining.contains(tag)).collect();
+        Self { removed, remaining }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.remaining.is_empty()
+    }
+}
+
+/// Probes a scrubbed clip and its source
+#[cfg(not(feature = "wasm"))]
+pub fn verify(source: &Path, output: &Path) -> crate::error::Result<ScrubReport> {
+    Ok(ScrubReport::compare(&crate::probe::probe(source)?, &crate::probe::probe(output)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::StreamInfo;
+    use std::collections::BTreeMap;
+
+    fn tags(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_sensitive_keys() {
+        for key in ["location", "com.apple.quicktime.location.ISO6709", "creation_time", "com.apple.quicktime.model", "com.android.version"] {
+            assert!(is_sensitive(key), "{}", key);
+        }
+        for key in ["title", "language", "handler_name", "encoder", "major_brand"] {
+            assert!(!is_sensitive(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let phone = MediaInfo {
+            tags: tags(&[("location", "+37.7749-122.4194/"), ("creation_time", "2026-05-01T10:00:00Z"), ("major_brand", "qt  ")]),
+            streams: vec![StreamInfo { index: 0, tags: tags(&[("creation_time", "2026-05-01T10:00:00Z")]), ..Default::default() }],
+            ..Default::default()
+        };
+        let clean = MediaInfo { tags: tags(&[("encoder", "Lavf61.7.100")]), ..Default::default() };
+        let report = ScrubReport::compare(&phone, &clean);
+        assert!(report.is_clean());
+        assert_eq!(report.removed, vec!["creation_time", "location", "stream 0 creation_time"]);
+
+        let leaky = MediaInfo { tags: tags(&[("location", "+37.7749-122.4194/")]), ..Default::default() };
+        let report = ScrubReport::compare(&phone, &leaky);
+        assert_eq!(report.remaining, vec!["location"]);
+        assert!(!report.is_clean());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::error::{VideoClipError, Result};
+use crate::renditions::VIDEO_FORMATS;
+#[cfg(not(feature = "wasm"))]
+use crate::video_clipper::VideoClipper;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Splitting by duration
+/// Chops a whole file into parts of a fixed length with FFmpeg's segment
+/// muxer: one run reads the file once and stream copies every part, which is
+/// far quicker than clipping each range separately. Parts can only start on
+/// keyframes, so each is the requested length give or take a GOP.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SegmentFile {
+    pub path: PathBuf,
+    /// Where the part starts and ends in the source, in seconds
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Extension of the part list written next to the parts
+const LIST_FILE_EXTENSION: &str = "segments.csv";
+
+/// Parses the `file,start,end` lines of a `-segment_list_type csv` list;
+/// file names are relative to `dir`
+pub fn parse_segment_list(list: &str, dir: &Path) -> Vec<SegmentFile> {
+    list.lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, ',');
+            let end = fields.next()?.trim().parse().ok()?;
+            let start = fields.next()?.trim().parse().ok()?;
+            let name = fields.next()?.trim().trim_matches('"');
+            Some(SegmentFile { path: dir.join(name), start, end })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct SegmentCommand {
+    input: PathBuf,
+    output_dir: PathBuf,
+    segment_seconds: f64,
+}
+
+impl SegmentCommand {
+    /// Splits `input` into `segment_seconds` parts written to `output_dir`
+    pub fn new(input: impl AsRef<Path>, output_dir: impl AsRef<Path>, segment_seconds: f64) -> Self {
+        Self {
+            input: input.as_ref().to_path_buf(),
+            output_dir: output_dir.as_ref().to_path_buf(),
+            segment_seconds,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.segment_seconds.is_finite() && self.segment_seconds > 0.0) {
+            return Err(VideoClipError::InvalidOptions(format!("segment length must be positive, got {}s", self.segment_seconds)));
+        }
+        Ok(())
+    }
+
+    fn stem(&self) -> &str {
+        self.input.file_stem().and_then(|s| s.to_str()).unwrap_or("segment")
+    }
+
+    /// The input's own container when it's one clips are written in, else MP4
+    fn container(&self) -> &str {
+        self.input
+            .extension()
+            .and_then(|e| e.to_str())
+            .filter(|e| VIDEO_FORMATS.contains(e))
+            .unwrap_or("mp4")
+    }
+
+    /// Pattern of the part files, e.g. `out/talk_part%03d.mp4`
+    pub fn output_pattern(&self) -> PathBuf {
+        self.output_dir.join(format!("{}_part%03d.{}", self.stem(), self.container()))
+    }
+
+    /// The CSV list FFmpeg writes the parts and their times to
+    pub fn list_path(&self) -> PathBuf {
+        self.output_dir.join(format!("{}.{}", self.stem(), LIST_FILE_EXTENSION))
+    }
+
+    pub fn build_args(&self) -> Vec<String> {
+        vec![
+            "-hide_banner".into(),
+            "-i".into(), self.input.display().to_string(),
+            "-map".into(), "0:v?".into(),
+            "-map".into(), "0:a?".into(),
+            "-c".into(), "copy".into(),
+            "-f".into(), "segment".into(),
+            "-segment_time".into(), self.segment_seconds.to_string(),
+            "-reset_timestamps".into(), "1".into(),
+            "-segment_list".into(), self.list_path().display().to_string(),
+            "-segment_list_type".into(), "csv".into(),
+            "-y".into(), self.output_pattern().display().to_string(),
+        ]
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    /// The parts written, in order
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<Vec<SegmentFile>> {
+        self.validate()?;
+        std::fs::create_dir_all(&self.output_dir)?;
+        crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Splitting failed")?;
+        let list = std::fs::read_to_string(self.list_path())?;
+        let _ = std::fs::remove_file(self.list_path());
+        Ok(parse_segment_list(&list, &self.output_dir))
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+impl VideoClipper {
+    /// Splits all of `input` into parts of about `chunk_seconds` each, in
+    /// the output directory, with a single FFmpeg run
+    pub fn split_by_duration(&self, input: &Path, chunk_seconds: f64) -> Result<Vec<SegmentFile>> {
+        self.validate_video_input(input)?;
+        SegmentCommand::new(input, self.output_dir(), chunk_seconds).execute()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_command() {
+        let cmd = SegmentCommand::new("talks/keynote.mkv", "out", 600.0);
+        assert_eq!(
+            cmd.get_command_string(),
+            "ffmpeg -hide_banner -i talks/keynote.mkv -map 0:v? -map 0:a? -c copy -f segment -segment_time 600 -reset_timestamps 1 \
+             -segment_list out/keynote.segments.csv -segment_list_type csv -y out/keynote_part%03d.mkv"
+        );
+        assert_eq!(SegmentCommand::new("capture.ts", "out", 60.0).output_pattern(), Path::new("out/capture_part%03d.mp4"));
+        assert!(SegmentCommand::new("a.mp4", "out", 0.0).validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_segment_list() {
+        let list = "keynote_part000.mkv,0.000000,600.040000\n\
+                    keynote_part001.mkv,600.040000,1200.000000\n\
+                    \"keynote, day 2_part002.mkv\",1200.000000,1475.520000\n";
+        let parts = parse_segment_list(list, Path::new("out"));
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0], SegmentFile { path: PathBuf::from("out/keynote_part000.mkv"), start: 0.0, end: 600.04 });
+        assert_eq!(parts[2].path, PathBuf::from("out/keynote, day 2_part002.mkv"));
+    }
+}
@@ -0,0 +1,920 @@
+//! REST server
+//! `POST /clips` queues a clip request (`"priority": "high"` to jump the
+//! queue; `?strict=true` rejects fields it doesn't know), `GET /clips/{id}`
+//! reports its status and result, `DELETE /clips/{id}` cancels it,
+//! `GET /clips/{id}/file` serves the finished clip (with `Range` requests,
+//! for seeking in a preview), `GET /schema` serves the request's JSON
+//! Schema, `GET /openapi.json` the whole API's (see [`crate::openapi`]) and
+//! `GET /metrics` exposes queue metrics for Prometheus. With an
+//! [`Authenticator`], every route but `GET /health`, `GET /openapi.json`
+//! and the web UI needs a bearer token (see [`crate::auth`]), and only keys
+//! marked `high_priority` may queue high-priority jobs. Each job belongs to
+//! the key or JWT subject that queued it: other callers don't see it in
+//! `GET /clips` and get 404 for its routes. With [`with_allowed_dirs`],
+//! requests may only name files in those directories (see
+//! [`crate::allowed_dirs`]).
+//!
+//! Sources can be uploaded to `POST /sources` (see [`crate::uploads`]),
+//! either as a multipart form with a `file` field and an optional hex
+//! `sha256` field, or tus-style: `POST` with `Upload-Length`,
+//! `Upload-Metadata: filename <base64>` and an optional whole-file
+//! `Upload-Checksum: sha256 <base64>`, then `PATCH /sources/{id}` with
+//! `Upload-Offset` for each piece (up to 64 MiB) and `HEAD /sources/{id}`
+//! to find where to resume. `DELETE /sources/{id}` abandons an upload.
+//!
+//! [`ui_router`] serves a page at `/` for submitting clips, following
+//! their status and previewing or downloading the results through the
+//! routes above.
+//!
+//! With a [`UrlSigner`], `GET /clips/{id}/url?expires_in=<seconds>` hands
+//! out a `/files/{id}` link to the clip that needs no bearer token until it
+//! expires (an hour by default, a week at most).
+
+use crate::allowed_dirs::AllowedDirs;
+use crate::auth::{AuthError, Authenticator, Caller, SignedUrl, UrlSigner};
+use crate::error::{ErrorInfo, VideoClipError, Result};
+use crate::jobs::{Job, JobQueue, JobStatus};
+use crate::uploads::{checksum_header_sha256, Upload, UploadError, UploadStore};
+use crate::video_clipper::{ClipRequest, Priority};
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, FromRequest, Multipart, Path, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// The body of every error response
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub(crate) struct ErrorBody {
+    /// The message
+    error: String,
+    /// `VideoClipError::code`, for clients to branch on
+    code: String,
+    /// Details that depend on the code, e.g. the offending times
+    context: serde_json::Map<String, serde_json::Value>,
+}
+
+struct ApiError(StatusCode, ErrorInfo);
+
+impl From<VideoClipError> for ApiError {
+    fn from(e: VideoClipError) -> Self {
+        let status = match e {
+            VideoClipError::FileNotFound(_) => StatusCode::NOT_FOUND,
+            VideoClipError::InvalidTimeFormat(_)
+            | VideoClipError::InvalidTimeRange { .. }
+            | VideoClipError::InvalidPath(_)
+            | VideoClipError::NotAVideoFile { .. }
+            | VideoClipError::CorruptSource { .. }
+            | VideoClipError::InvalidOptions(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError(status, ErrorInfo::from(&e))
+    }
+}
+
+impl From<UploadError> for ApiError {
+    fn from(e: UploadError) -> Self {
+        let message = e.to_string();
+        let (status, code, context) = match e {
+            UploadError::Invalid(e) => return ApiError::from(e),
+            UploadError::NotFound(id) => (StatusCode::NOT_FOUND, "upload_not_found", serde_json::json!({ "id": id })),
+            UploadError::TooLarge { max_bytes } => (StatusCode::PAYLOAD_TOO_LARGE, "upload_too_large", serde_json::json!({ "max_bytes": max_bytes })),
+            UploadError::OffsetMismatch { expected } => (StatusCode::CONFLICT, "upload_offset_mismatch", serde_json::json!({ "expected": expected })),
+            // tus's status for a checksum that doesn't match
+            UploadError::ChecksumMismatch { expected, actual } => (
+                StatusCode::from_u16(460).expect("460 is a valid status"),
+                "checksum_mismatch",
+                serde_json::json!({ "expected": expected, "actual": actual }),
+            ),
+        };
+        let context = match context {
+            serde_json::Value::Object(context) => context,
+            _ => serde_json::Map::new(),
+        };
+        ApiError(status, ErrorInfo { code: code.to_string(), message, context })
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let ErrorInfo { code, message, context } = self.1;
+        (self.0, Json(ErrorBody { error: message, code, context })).into_response()
+    }
+}
+
+/// Routes backed by `queue`
+pub fn router(queue: Arc<JobQueue>) -> Router {
+    Router::new()
+        .route("/clips", get(list_clips).post(submit_clip))
+        .route("/clips/{id}", get(get_clip).delete(cancel_clip))
+        .route("/clips/{id}/file", get(download_clip))
+        .route("/schema", get(|| async { Json(ClipRequest::json_schema()) }))
+        .route("/openapi.json", get(|| async { Json(crate::openapi::document()) }))
+        .route("/metrics", get(metrics))
+        .route("/health", get(|| async { "ok" }))
+        .with_state(queue)
+}
+
+#[derive(Clone)]
+struct SignedFiles {
+    queue: Arc<JobQueue>,
+    signer: Arc<UrlSigner>,
+}
+
+/// Signed link routes for `queue`'s clips, to merge into [`router`]'s
+pub fn signed_url_router(queue: Arc<JobQueue>, signer: Arc<UrlSigner>) -> Router {
+    Router::new()
+        .route("/clips/{id}/url", get(sign_clip_url))
+        .route("/files/{id}", get(download_signed))
+        .with_state(SignedFiles { queue, signer })
+}
+
+/// The web page, in the binary so the server needs no files beside it
+const UI: &str = include_str!("ui.html");
+
+/// The web UI at `/`, to merge into [`router`]'s
+pub fn ui_router() -> Router {
+    Router::new().route("/", get(|| async { ([(header::CACHE_CONTROL, "no-cache")], axum::response::Html(UI)) }))
+}
+
+/// Largest piece a tus-style upload takes per `PATCH`
+const MAX_PIECE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Upload routes backed by `store`, to merge into [`router`]'s
+pub fn upload_router(store: Arc<UploadStore>) -> Router {
+    Router::new()
+        // Multipart bodies are streamed to disk and checked against the store's limit as they come
+        .route("/sources", post(create_source).layer(DefaultBodyLimit::disable()))
+        .route(
+            "/sources/{id}",
+            get(get_source).patch(append_source).delete(discard_source).layer(DefaultBodyLimit::max(MAX_PIECE_BYTES)),
+        )
+        .with_state(store)
+}
+
+/// `app`, refusing requests `auth` doesn't let through
+pub fn with_auth(app: Router, auth: Arc<Authenticator>) -> Router {
+    app.layer(axum::middleware::from_fn_with_state(auth, require_auth))
+}
+
+/// `app`, refusing clip requests that name files outside `dirs`
+pub fn with_allowed_dirs(app: Router, dirs: AllowedDirs) -> Router {
+    app.layer(Extension(Arc::new(dirs)))
+}
+
+async fn require_auth(State(auth): State<Arc<Authenticator>>, request: Request, next: Next) -> Response {
+    // The page asks for a token itself, and signed links carry their own proof (see `download_signed`)
+    if matches!(request.uri().path(), "/" | "/health" | "/openapi.json") || request.uri().path().starts_with("/files/") {
+        return next.run(request).await;
+    }
+    let authorization = request.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+    match auth.authenticate(authorization) {
+        Ok(caller) => {
+            log::debug!("{} {} by {}", request.method(), request.uri().path(), caller.name);
+            let mut request = request;
+            request.extensions_mut().insert(caller);
+            next.run(request).await
+        }
+        Err(AuthError::Unauthorized(reason)) => {
+            let error = ApiError(StatusCode::UNAUTHORIZED, ErrorInfo {
+                code: "unauthorized".to_string(),
+                message: reason,
+                context: serde_json::Map::new(),
+            });
+            ([(header::WWW_AUTHENTICATE, "Bearer")], error).into_response()
+        }
+        Err(AuthError::RateLimited { retry_after }) => {
+            let seconds = retry_after.as_secs().max(1);
+            let error = ApiError(StatusCode::TOO_MANY_REQUESTS, ErrorInfo {
+                code: "rate_limited".to_string(),
+                message: format!("rate limit reached; retry in {}s", seconds),
+                context: serde_json::Map::from_iter([("retry_after".to_string(), seconds.into())]),
+            });
+            ([(header::RETRY_AFTER, seconds.to_string())], error).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SubmitParams {
+    #[serde(default)]
+    strict: bool,
+}
+
+/// Without [`with_auth`] there's no caller, and every request is trusted
+async fn submit_clip(
+    State(queue): State<Arc<JobQueue>>,
+    caller: Option<Extension<Caller>>,
+    allowed: Option<Extension<Arc<AllowedDirs>>>,
+    Query(params): Query<SubmitParams>,
+    Json(body): Json<serde_json::Value>,
+) -> std::result::Result<(StatusCode, Json<Job>), ApiError> {
+    let request: ClipRequest = if params.strict {
+        ClipRequest::from_value_strict(body)?
+    } else {
+        serde_json::from_value(body)
+            .map_err(|e| VideoClipError::InvalidOptions(format!("invalid clip request: {}", e)))?
+    };
+    if let Some(Extension(allowed)) = &allowed {
+        allowed.check(&request)?;
+    }
+    if let Some(Extension(caller)) = caller.as_ref().filter(|_| request.priority == Priority::High) {
+        if !caller.high_priority {
+            return Err(ApiError(StatusCode::FORBIDDEN, ErrorInfo {
+                code: "priority_not_allowed".to_string(),
+                message: format!("{} may not queue high-priority jobs", caller.name),
+                context: serde_json::Map::from_iter([("priority".to_string(), "high".into())]),
+            }));
+        }
+    }
+    let id = queue.submit_for(caller.map(|Extension(caller)| caller.name), request)?;
+    let job = queue.job(id).expect("job was just submitted");
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+async fn list_clips(State(queue): State<Arc<JobQueue>>, caller: Option<Extension<Caller>>) -> Json<Vec<Job>> {
+    Json(queue.jobs().into_iter().filter(|job| owns(caller.as_ref(), job)).collect())
+}
+
+/// Whether `caller` may see `job`; everyone may without [`with_auth`]
+fn owns(caller: Option<&Extension<Caller>>, job: &Job) -> bool {
+    caller.is_none_or(|Extension(caller)| job.owner.as_ref() == Some(&caller.name))
+}
+
+/// Job `id`, if `caller` may see it
+fn owned_job(queue: &JobQueue, id: u64, caller: Option<&Extension<Caller>>) -> std::result::Result<Job, ApiError> {
+    queue.job(id).filter(|job| owns(caller, job)).ok_or_else(|| job_not_found(id))
+}
+
+async fn get_clip(
+    State(queue): State<Arc<JobQueue>>,
+    caller: Option<Extension<Caller>>,
+    Path(id): Path<u64>,
+) -> std::result::Result<Json<Job>, ApiError> {
+    owned_job(&queue, id, caller.as_ref()).map(Json)
+}
+
+/// 200 with the cancelled job, 202 while a running job is being stopped,
+/// 409 for a job that finished first
+async fn cancel_clip(
+    State(queue): State<Arc<JobQueue>>,
+    caller: Option<Extension<Caller>>,
+    Path(id): Path<u64>,
+) -> std::result::Result<(StatusCode, Json<Job>), ApiError> {
+    owned_job(&queue, id, caller.as_ref())?;
+    let job = queue.cancel(id).ok_or_else(|| job_not_found(id))?;
+    match job.status {
+        JobStatus::Cancelled => Ok((StatusCode::OK, Json(job))),
+        JobStatus::Running => Ok((StatusCode::ACCEPTED, Json(job))),
+        status => Err(ApiError(StatusCode::CONFLICT, ErrorInfo {
+            code: "job_finished".to_string(),
+            message: format!("job {} already finished", id),
+            context: serde_json::Map::from_iter([
+                ("id".to_string(), id.into()),
+                ("status".to_string(), serde_json::to_value(status).unwrap_or_default()),
+            ]),
+        })),
+    }
+}
+
+async fn download_clip(
+    State(queue): State<Arc<JobQueue>>,
+    caller: Option<Extension<Caller>>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+) -> std::result::Result<Response, ApiError> {
+    owned_job(&queue, id, caller.as_ref())?;
+    let output = finished_output(&queue, id)?;
+    file_response(std::path::Path::new(&output), &headers).await
+}
+
+const DEFAULT_URL_TTL: u64 = 60 * 60;
+const MAX_URL_TTL: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Debug, Default, Deserialize)]
+struct UrlParams {
+    /// Seconds the link works for
+    expires_in: Option<u64>,
+}
+
+async fn sign_clip_url(
+    State(files): State<SignedFiles>,
+    caller: Option<Extension<Caller>>,
+    Path(id): Path<u64>,
+    Query(params): Query<UrlParams>,
+) -> std::result::Result<Json<SignedUrl>, ApiError> {
+    owned_job(&files.queue, id, caller.as_ref())?;
+    finished_output(&files.queue, id)?;
+    let ttl = params.expires_in.unwrap_or(DEFAULT_URL_TTL).clamp(1, MAX_URL_TTL);
+    Ok(Json(files.signer.sign(&format!("/files/{}", id), Duration::from_secs(ttl))))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SignatureParams {
+    expires: Option<u64>,
+    signature: Option<String>,
+}
+
+async fn download_signed(
+    State(files): State<SignedFiles>,
+    Path(id): Path<u64>,
+    Query(params): Query<SignatureParams>,
+    headers: HeaderMap,
+) -> std::result::Result<Response, ApiError> {
+    let valid = match (params.expires, params.signature) {
+        (Some(expires), Some(signature)) => files.signer.verify(&format!("/files/{}", id), expires, &signature),
+        _ => false,
+    };
+    if !valid {
+        return Err(ApiError(StatusCode::FORBIDDEN, ErrorInfo {
+            code: "invalid_signature".to_string(),
+            message: "the link is invalid or has expired".to_string(),
+            context: serde_json::Map::new(),
+        }));
+    }
+    let output = finished_output(&files.queue, id)?;
+    file_response(std::path::Path::new(&output), &headers).await
+}
+
+/// The clip job `id` made, once it has
+fn finished_output(queue: &JobQueue, id: u64) -> std::result::Result<String, ApiError> {
+    let job = queue.job(id).ok_or_else(|| job_not_found(id))?;
+    match (job.status, job.result) {
+        (JobStatus::Succeeded, Some(result)) => Ok(result.output_file),
+        (status, _) => Err(ApiError(StatusCode::CONFLICT, ErrorInfo {
+            code: "clip_not_ready".to_string(),
+            message: format!("job {} has no clip to serve", id),
+            context: serde_json::Map::from_iter([
+                ("id".to_string(), id.into()),
+                ("status".to_string(), serde_json::to_value(status).unwrap_or_default()),
+            ]),
+        })),
+    }
+}
+
+/// The file at `path`, or the part of it a `Range` header asks for
+async fn file_response(path: &std::path::Path, headers: &HeaderMap) -> std::result::Result<Response, ApiError> {
+    let mut file = tokio::fs::File::open(path).await
+        .map_err(|_| VideoClipError::FileNotFound(path.display().to_string()))?;
+    let len = file.metadata().await.map_err(VideoClipError::from)?.len();
+    let file_name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().replace('"', ""));
+    let common = [
+        (header::CONTENT_TYPE, content_type(path).to_string()),
+        (header::ACCEPT_RANGES, "bytes".to_string()),
+        (header::CONTENT_DISPOSITION, format!("inline; filename=\"{}\"", file_name)),
+    ];
+
+    let (status, start, end) = match byte_range(header_value(headers, "range"), len) {
+        Ok(Some((start, end))) => (StatusCode::PARTIAL_CONTENT, start, end),
+        Ok(None) => (StatusCode::OK, 0, len.saturating_sub(1)),
+        Err(()) => {
+            let range = [(header::CONTENT_RANGE, format!("bytes */{}", len))];
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, common, range).into_response());
+        }
+    };
+    let count = match len {
+        0 => 0,
+        _ => end - start + 1,
+    };
+    file.seek(std::io::SeekFrom::Start(start)).await.map_err(VideoClipError::from)?;
+    let body = axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file.take(count)));
+    let mut response = (status, common, [(header::CONTENT_LENGTH, count.to_string())], body).into_response();
+    if status == StatusCode::PARTIAL_CONTENT {
+        let range = format!("bytes {}-{}/{}", start, end, len).parse().expect("a byte range is a valid header value");
+        response.headers_mut().insert(header::CONTENT_RANGE, range);
+    }
+    Ok(response)
+}
+
+/// The first and last byte of the single `bytes=` range in a `Range` header,
+/// for a `len`-byte file. `None` serves the whole file: no header, or one
+/// this doesn't handle (several ranges, other units), which HTTP allows to
+/// be ignored. `Err` is a range that starts past the end.
+fn byte_range(range: Option<&str>, len: u64) -> std::result::Result<Option<(u64, u64)>, ()> {
+    let Some((first, last)) = range.and_then(|range| range.strip_prefix("bytes=")).and_then(|range| range.trim().split_once('-')) else {
+        return Ok(None);
+    };
+    if first.contains(',') || last.contains(',') {
+        return Ok(None);
+    }
+    let (start, end) = match (first.trim(), last.trim()) {
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return Err(()),
+            Ok(suffix) => (len.saturating_sub(suffix), len.saturating_sub(1)),
+            Err(_) => return Ok(None),
+        },
+        (first, "") => match first.parse::<u64>() {
+            Ok(first) => (first, len.saturating_sub(1)),
+            Err(_) => return Ok(None),
+        },
+        (first, last) => match (first.parse::<u64>(), last.parse::<u64>()) {
+            (Ok(first), Ok(last)) if first <= last => (first, last.min(len.saturating_sub(1))),
+            _ => return Ok(None),
+        },
+    };
+    match start < len {
+        true => Ok(Some((start, end))),
+        false => Err(()),
+    }
+}
+
+/// What browsers need to play or show a clip inline
+fn content_type(path: &std::path::Path) -> &'static str {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("mp4" | "m4v") => "video/mp4",
+        Some("mov") => "video/quicktime",
+        Some("mkv") => "video/x-matroska",
+        Some("webm") => "video/webm",
+        Some("ts" | "m2ts") => "video/mp2t",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("mp3") => "audio/mpeg",
+        Some("m4a") => "audio/mp4",
+        Some("wav") => "audio/wav",
+        Some("vtt") => "text/vtt",
+        Some("srt") => "application/x-subrip",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+const TUS_RESUMABLE: (&str, &str) = ("tus-resumable", "1.0.0");
+
+/// A multipart upload, or the start of a tus-style one
+async fn create_source(State(store): State<Arc<UploadStore>>, request: Request) -> std::result::Result<Response, ApiError> {
+    let is_multipart = request.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+    if is_multipart {
+        let multipart = Multipart::from_request(request, &()).await
+            .map_err(|e| VideoClipError::InvalidOptions(format!("invalid multipart body: {}", e)))?;
+        let upload = receive_multipart(&store, multipart).await?;
+        return Ok((StatusCode::CREATED, Json(upload)).into_response());
+    }
+
+    let headers = request.headers();
+    let length = header_value(headers, "upload-length")
+        .ok_or_else(|| VideoClipError::InvalidOptions("a tus upload needs Upload-Length (or send a multipart form)".to_string()))?
+        .parse::<u64>()
+        .map_err(|_| VideoClipError::InvalidOptions("Upload-Length must be a byte count".to_string()))?;
+    let file_name = header_value(headers, "upload-metadata").and_then(metadata_file_name).unwrap_or_else(|| "upload".to_string());
+    let sha256 = header_value(headers, "upload-checksum").map(checksum_header_sha256).transpose()?;
+    let upload = store.create(&file_name, Some(length), sha256.as_deref())?;
+    let location = format!("/sources/{}", upload.id);
+    Ok((
+        StatusCode::CREATED,
+        [(header::LOCATION.as_str(), location.as_str()), ("upload-offset", &upload.offset.to_string()), TUS_RESUMABLE],
+        Json(upload),
+    ).into_response())
+}
+
+async fn receive_multipart(store: &UploadStore, mut multipart: Multipart) -> std::result::Result<Upload, ApiError> {
+    let invalid = |e: axum::extract::multipart::MultipartError| VideoClipError::InvalidOptions(format!("invalid multipart body: {}", e));
+    let mut received = Unfinished { store, id: None };
+    let mut sha256 = None;
+    while let Some(mut field) = multipart.next_field().await.map_err(invalid)? {
+        match field.name() {
+            Some("sha256") => sha256 = Some(field.text().await.map_err(invalid)?),
+            Some("file") if received.id.is_none() => {
+                let upload = store.create(field.file_name().unwrap_or("upload"), None, None)?;
+                received.id = Some(upload.id.clone());
+                let mut offset = 0;
+                while let Some(chunk) = field.chunk().await.map_err(invalid)? {
+                    offset = store.append(&upload.id, offset, &chunk)?.offset;
+                }
+            }
+            _ => {}
+        }
+    }
+    let id = received.id.clone().ok_or_else(|| VideoClipError::InvalidOptions("a multipart upload needs a `file` field".to_string()))?;
+    let upload = store.finish(&id, sha256.as_deref())?;
+    received.id = None;
+    Ok(upload)
+}
+
+/// A multipart upload on its way in. A multipart body can't be resumed, so
+/// if it fails or the client goes away first, what arrived is removed.
+struct Unfinished<'a> {
+    store: &'a UploadStore,
+    id: Option<String>,
+}
+
+impl Drop for Unfinished<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            let _ = self.store.discard(&id);
+        }
+    }
+}
+
+/// `Upload-Offset` tells a tus client where to resume (`HEAD` gets just the headers)
+async fn get_source(State(store): State<Arc<UploadStore>>, Path(id): Path<String>) -> std::result::Result<Response, ApiError> {
+    let upload = store.get(&id).ok_or(UploadError::NotFound(id))?;
+    let mut headers = vec![("upload-offset", upload.offset.to_string()), (header::CACHE_CONTROL.as_str(), "no-store".to_string())];
+    if let Some(length) = upload.length {
+        headers.push(("upload-length", length.to_string()));
+    }
+    let headers: Vec<(&str, String)> = headers.into_iter().chain([(TUS_RESUMABLE.0, TUS_RESUMABLE.1.to_string())]).collect();
+    Ok((axum::response::AppendHeaders(headers), Json(upload)).into_response())
+}
+
+async fn append_source(
+    State(store): State<Arc<UploadStore>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> std::result::Result<Response, ApiError> {
+    let offset = header_value(&headers, "upload-offset")
+        .and_then(|offset| offset.parse::<u64>().ok())
+        .ok_or_else(|| VideoClipError::InvalidOptions("PATCH needs Upload-Offset".to_string()))?;
+    let upload = store.append(&id, offset, &body)?;
+    Ok((StatusCode::NO_CONTENT, [("upload-offset", upload.offset.to_string().as_str()), TUS_RESUMABLE]).into_response())
+}
+
+async fn discard_source(State(store): State<Arc<UploadStore>>, Path(id): Path<String>) -> std::result::Result<StatusCode, ApiError> {
+    store.discard(&id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim)
+}
+
+/// The file name in tus `Upload-Metadata` (`filename <base64>,...`)
+fn metadata_file_name(metadata: &str) -> Option<String> {
+    metadata.split(',')
+        .filter_map(|pair| pair.trim().split_once(' '))
+        .find(|(key, _)| matches!(*key, "filename" | "name"))
+        .and_then(|(_, value)| STANDARD.decode(value.trim()).ok())
+        .and_then(|name| String::from_utf8(name).ok())
+}
+
+fn job_not_found(id: u64) -> ApiError {
+    ApiError(StatusCode::NOT_FOUND, ErrorInfo {
+        code: "job_not_found".to_string(),
+        message: format!("no job {}", id),
+        context: serde_json::Map::from_iter([("id".to_string(), id.into())]),
+    })
+}
+
+async fn metrics(State(queue): State<Arc<JobQueue>>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], queue.render_metrics())
+}
+
+/// Serves `app` (see [`router`]) on `addr` until the process is stopped
+pub async fn serve(addr: &str, app: Router) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    log::info!("Listening on {}", listener.local_addr()?);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video_clipper::VideoClipper;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn call(queue: &Arc<JobQueue>, request: Request<Body>) -> (StatusCode, String) {
+        let response = router(Arc::clone(queue)).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn post_clip(json: &str) -> Request<Body> {
+        Request::post("/clips")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_submit_and_poll() {
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        let (status, body) = call(&queue, post_clip(r#"{"input_file": "missing.mp4", "start_time": "0", "end_time": "5", "priority": "high"}"#)).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let job: Job = serde_json::from_str(&body).unwrap();
+        assert_eq!(job.request.priority, crate::video_clipper::Priority::High);
+
+        queue.wait(job.id);
+        let (status, body) = call(&queue, Request::get(format!("/clips/{}", job.id)).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let job: Job = serde_json::from_str(&body).unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+
+        let (status, _) = call(&queue, Request::get("/clips/999").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Too late to cancel
+        let (status, body) = call(&queue, Request::delete(format!("/clips/{}", job.id)).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.contains("\"status\":\"failed\""));
+        let (status, _) = call(&queue, Request::delete("/clips/999").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        queue.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_invalid_request_is_rejected() {
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        let (status, body) = call(&queue, post_clip(r#"{"input_file": "a.mp4", "start_time": "0:30", "end_time": "0:10"}"#)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["code"], "invalid_time_range");
+        assert_eq!(body["context"]["start"], 30.0);
+        assert!(body["error"].as_str().unwrap().contains("must be after start time"));
+        queue.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_names_unknown_fields() {
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        let json = r#"{"input_file": "a.mp4", "start_time": "0", "end_time": "5", "fit": {"width": 1080, "height": 1920, "mdoe": "crop"}, "volume": 3}"#;
+        let (status, _) = call(&queue, post_clip(json)).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let strict = Request::post("/clips?strict=true")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json))
+            .unwrap();
+        let (status, body) = call(&queue, strict).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body.contains("unknown clip request fields: fit.mdoe, volume"));
+
+        let (status, body) = call(&queue, Request::get("/schema").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("\"additionalProperties\":false"));
+        queue.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_auth_guards_everything_but_health() {
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        let auth = Arc::new(Authenticator::new(crate::auth::AuthConfig {
+            keys: vec![crate::auth::ApiKey { name: "ci".to_string(), key: "k-123".to_string(), rate_limit: Some(1), high_priority: false }],
+            ..Default::default()
+        }).unwrap());
+        let app = || with_auth(router(Arc::clone(&queue)), Arc::clone(&auth));
+        let get = |path: &str, key: Option<&str>| {
+            let request = Request::get(path);
+            let request = match key {
+                Some(key) => request.header(header::AUTHORIZATION, format!("Bearer {}", key)),
+                None => request,
+            };
+            request.body(Body::empty()).unwrap()
+        };
+
+        let response = app().oneshot(get("/clips", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+        assert_eq!(app().oneshot(get("/clips", Some("k-999"))).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(app().oneshot(get("/health", None)).await.unwrap().status(), StatusCode::OK);
+        let page = with_auth(ui_router(), Arc::clone(&auth)).oneshot(get("/", None)).await.unwrap();
+        assert_eq!(page.status(), StatusCode::OK);
+        assert!(page.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/html"));
+
+        assert_eq!(app().oneshot(get("/clips", Some("k-123"))).await.unwrap().status(), StatusCode::OK);
+        let response = app().oneshot(get("/clips", Some("k-123"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+        queue.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_needs_a_privileged_key() {
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        let key = |name: &str, high_priority| crate::auth::ApiKey {
+            name: name.to_string(),
+            key: format!("k-{}", name),
+            rate_limit: None,
+            high_priority,
+        };
+        let auth = Arc::new(Authenticator::new(crate::auth::AuthConfig {
+            keys: vec![key("ci", false), key("ops", true)],
+            ..Default::default()
+        }).unwrap());
+        let submit = |key: &str, priority: &str| {
+            let json = format!(r#"{{"input_file": "missing.mp4", "start_time": "0", "end_time": "5", "priority": "{}"}}"#, priority);
+            let request = Request::post("/clips")
+                .header(header::AUTHORIZATION, format!("Bearer {}", key))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json))
+                .unwrap();
+            with_auth(router(Arc::clone(&queue)), Arc::clone(&auth)).oneshot(request)
+        };
+
+        let response = submit("k-ci", "high").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("priority_not_allowed"));
+        assert_eq!(submit("k-ci", "low").await.unwrap().status(), StatusCode::ACCEPTED);
+        assert_eq!(submit("k-ops", "high").await.unwrap().status(), StatusCode::ACCEPTED);
+        queue.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_jobs_belong_to_their_caller() {
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        let key = |name: &str| crate::auth::ApiKey {
+            name: name.to_string(),
+            key: format!("k-{}", name),
+            rate_limit: None,
+            high_priority: false,
+        };
+        let auth = Arc::new(Authenticator::new(crate::auth::AuthConfig { keys: vec![key("ci"), key("dashboard")], ..Default::default() }).unwrap());
+        let send = |key: &str, request: axum::http::request::Builder, body: Body| {
+            let request = request.header(header::AUTHORIZATION, format!("Bearer {}", key)).body(body).unwrap();
+            with_auth(router(Arc::clone(&queue)), Arc::clone(&auth)).oneshot(request)
+        };
+
+        let json = r#"{"input_file": "missing.mp4", "start_time": "0", "end_time": "5"}"#;
+        let response = send("k-ci", Request::post("/clips").header(header::CONTENT_TYPE, "application/json"), Body::from(json)).await.unwrap();
+        let job: Job = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(job.owner.as_deref(), Some("key:ci"));
+        let path = format!("/clips/{}", job.id);
+
+        let listed = |response: Response| async {
+            serde_json::from_slice::<Vec<Job>>(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap().len()
+        };
+        assert_eq!(listed(send("k-ci", Request::get("/clips"), Body::empty()).await.unwrap()).await, 1);
+        assert_eq!(listed(send("k-dashboard", Request::get("/clips"), Body::empty()).await.unwrap()).await, 0);
+        for request in [Request::get(&path), Request::delete(&path), Request::get(format!("{}/file", path))] {
+            assert_eq!(send("k-dashboard", request, Body::empty()).await.unwrap().status(), StatusCode::NOT_FOUND);
+        }
+        assert_eq!(send("k-ci", Request::get(&path), Body::empty()).await.unwrap().status(), StatusCode::OK);
+        queue.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_held_to_the_allowed_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        let allowed = AllowedDirs::new(&[dir.path().to_path_buf()]).unwrap();
+        let submit = |json: serde_json::Value| {
+            let request = Request::post("/clips")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json.to_string()))
+                .unwrap();
+            with_allowed_dirs(router(Arc::clone(&queue)), allowed.clone()).oneshot(request)
+        };
+        let source = dir.path().join("talk.mp4").display().to_string();
+
+        let inside = serde_json::json!({ "input_file": source, "start_time": "0", "end_time": "5", "output_dir": dir.path().join("clips") });
+        assert_eq!(submit(inside).await.unwrap().status(), StatusCode::ACCEPTED);
+        for outside in [
+            serde_json::json!({ "input_file": "/etc/hostname", "start_time": "0", "end_time": "5" }),
+            serde_json::json!({ "input_file": source, "start_time": "0", "end_time": "5", "output_dir": "/tmp" }),
+            serde_json::json!({ "input_file": source, "start_time": "0", "end_time": "5", "intro": "/etc/hostname" }),
+        ] {
+            assert_eq!(submit(outside).await.unwrap().status(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        queue.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_uploads() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(UploadStore::new(dir.path(), 1024).unwrap());
+        let send = |request: Request<Body>| upload_router(Arc::clone(&store)).oneshot(request);
+
+        // tus-style, in two pieces
+        let create = Request::post("/sources")
+            .header("upload-length", "11")
+            .header("upload-metadata", format!("filename {}", STANDARD.encode("talk.mp4")))
+            .body(Body::empty())
+            .unwrap();
+        let response = send(create).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[header::LOCATION].to_str().unwrap().to_string();
+        let patch = |offset: &str, bytes: &'static str| Request::patch(location.as_str())
+            .header("upload-offset", offset)
+            .header(header::CONTENT_TYPE, "application/offset+octet-stream")
+            .body(Body::from(bytes))
+            .unwrap();
+        assert_eq!(send(patch("0", "hello ")).await.unwrap().status(), StatusCode::NO_CONTENT);
+        assert_eq!(send(patch("0", "hello ")).await.unwrap().status(), StatusCode::CONFLICT);
+        let head = send(Request::head(location.as_str()).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(head.headers()["upload-offset"], "6");
+        assert_eq!(send(patch("6", "world")).await.unwrap().status(), StatusCode::NO_CONTENT);
+        let response = send(Request::get(location.as_str()).body(Body::empty()).unwrap()).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let upload: Upload = serde_json::from_slice(&body).unwrap();
+        assert!(upload.complete && upload.path.ends_with("talk.mp4"));
+        assert_eq!(std::fs::read(&upload.path).unwrap(), b"hello world");
+
+        // Multipart, with a checksum that doesn't match
+        let form = "--x\r\nContent-Disposition: form-data; name=\"sha256\"\r\n\r\n"
+            .to_string() + &"0".repeat(64)
+            + "\r\n--x\r\nContent-Disposition: form-data; name=\"file\"; filename=\"b.mp4\"\r\n\r\nbytes\r\n--x--\r\n";
+        let multipart = |body: String| Request::post("/sources")
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=x")
+            .body(Body::from(body))
+            .unwrap();
+        assert_eq!(send(multipart(form.clone())).await.unwrap().status().as_u16(), 460);
+        let form = form.replace(&"0".repeat(64), &hex::encode(<sha2::Sha256 as sha2::Digest>::digest(b"bytes")));
+        assert_eq!(send(multipart(form)).await.unwrap().status(), StatusCode::CREATED);
+
+        // Multipart, cut off partway through the file
+        let truncated = "--x\r\nContent-Disposition: form-data; name=\"file\"; filename=\"c.mp4\"\r\n\r\nthe first half".to_string();
+        assert_eq!(send(multipart(truncated)).await.unwrap().status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let leftovers: Vec<_> = std::fs::read_dir(dir.path()).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "part"))
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+
+        let too_big = Request::post("/sources").header("upload-length", "2048").body(Body::empty()).unwrap();
+        assert_eq!(send(too_big).await.unwrap().status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_byte_ranges() {
+        assert_eq!(byte_range(None, 100), Ok(None));
+        assert_eq!(byte_range(Some("bytes=0-9"), 100), Ok(Some((0, 9))));
+        assert_eq!(byte_range(Some("bytes=90-"), 100), Ok(Some((90, 99))));
+        assert_eq!(byte_range(Some("bytes=-10"), 100), Ok(Some((90, 99))));
+        assert_eq!(byte_range(Some("bytes=50-500"), 100), Ok(Some((50, 99))));
+        assert_eq!(byte_range(Some("bytes=-500"), 100), Ok(Some((0, 99))));
+        assert_eq!(byte_range(Some("bytes=100-"), 100), Err(()));
+        assert_eq!(byte_range(Some("bytes=0-1,5-9"), 100), Ok(None));
+        assert_eq!(byte_range(Some("seconds=1-2"), 100), Ok(None));
+        assert_eq!(content_type(std::path::Path::new("a/talk.MP4")), "video/mp4");
+    }
+
+    #[tokio::test]
+    async fn test_file_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.webm");
+        std::fs::write(&path, b"0123456789").unwrap();
+        let get = |range: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(range) = range {
+                headers.insert(header::RANGE, range.parse().unwrap());
+            }
+            let path = path.clone();
+            async move { file_response(&path, &headers).await.ok().unwrap() }
+        };
+
+        let whole = get(None).await;
+        assert_eq!(whole.status(), StatusCode::OK);
+        assert_eq!(whole.headers()[header::CONTENT_TYPE], "video/webm");
+        assert_eq!(whole.headers()[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(to_bytes(whole.into_body(), usize::MAX).await.unwrap(), "0123456789");
+
+        let part = get(Some("bytes=2-4")).await;
+        assert_eq!(part.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(part.headers()[header::CONTENT_RANGE], "bytes 2-4/10");
+        assert_eq!(part.headers()[header::CONTENT_LENGTH], "3");
+        assert_eq!(to_bytes(part.into_body(), usize::MAX).await.unwrap(), "234");
+
+        let past_the_end = get(Some("bytes=10-")).await;
+        assert_eq!(past_the_end.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(past_the_end.headers()[header::CONTENT_RANGE], "bytes */10");
+    }
+
+    #[tokio::test]
+    async fn test_clip_files_need_a_finished_job_and_links_a_signature() {
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        let (_, body) = call(&queue, post_clip(r#"{"input_file": "missing.mp4", "start_time": "0", "end_time": "5"}"#)).await;
+        let job: Job = serde_json::from_str(&body).unwrap();
+        queue.wait(job.id);
+        let (status, body) = call(&queue, Request::get(format!("/clips/{}/file", job.id)).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.contains("clip_not_ready"));
+
+        let signer = Arc::new(UrlSigner::new("links").unwrap());
+        let app = || signed_url_router(Arc::clone(&queue), Arc::clone(&signer));
+        let url = Request::get(format!("/clips/{}/url", job.id)).body(Body::empty()).unwrap();
+        assert_eq!(app().oneshot(url).await.unwrap().status(), StatusCode::CONFLICT);
+        let link = signer.sign(&format!("/files/{}", job.id), Duration::from_secs(60));
+        let forged = link.url.replace("signature=", "signature=00");
+        assert_eq!(app().oneshot(Request::get(forged).body(Body::empty()).unwrap()).await.unwrap().status(), StatusCode::FORBIDDEN);
+        // A good signature still needs a clip behind it
+        assert_eq!(app().oneshot(Request::get(link.url).body(Body::empty()).unwrap()).await.unwrap().status(), StatusCode::CONFLICT);
+        queue.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let queue = JobQueue::start(VideoClipper::new(), 1);
+        let (status, body) = call(&queue, Request::get("/metrics").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("video_clip_queue_depth 0"));
+        assert!(body.contains("video_clip_ffmpeg_duration_seconds_count 0"));
+        queue.shutdown();
+    }
+}
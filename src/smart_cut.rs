@@ -0,0 +1,449 @@
+use crate::encoder::{EncoderChoice, VideoCodec};
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::FilterGraph;
+use crate::ffmpeg::limits::ProcessLimits;
+use crate::metadata::ClipMetadata;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Smart cutting
+/// Frame-accurate clips at close to stream-copy speed: only the partial GOPs
+/// at either edge of the range are re-encoded, the keyframe-aligned middle is
+/// copied untouched, and the pieces are joined with the concat demuxer.
+/// Audio is cut in one piece from the source so there are no seams. The
+/// edges are encoded at the source's profile and level, since some decoders
+/// refuse a stream whose parameters change at the seams; sources whose
+/// profile the encoder can't produce are refused instead.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentMode {
+    Encode,
+    Copy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CutSegment {
+    pub start: f64,
+    pub end: f64,
+    pub mode: SegmentMode,
+}
+
+impl CutSegment {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+/// Keyframes this close to a boundary count as on it
+const KEYFRAME_TOLERANCE: f64 = 0.001;
+
+/// Splits `[start, end]` at the first and last keyframes inside it. Ranges
+/// with fewer than two keyframes become a single encoded segment.
+pub fn plan_segments(start: f64, end: f64, keyframes: &[f64]) -> Vec<CutSegment> {
+    let inside = |t: &f64| *t >= start - KEYFRAME_TOLERANCE && *t <= end + KEYFRAME_TOLERANCE;
+    let first = keyframes.iter().copied().filter(inside).reduce(f64::min);
+    let last = keyframes.iter().copied().filter(inside).reduce(f64::max);
+
+    let (copy_start, copy_end) = match (first, last) {
+        (Some(first), Some(last)) if last - first > KEYFRAME_TOLERANCE => (first.max(start), last.min(end)),
+        _ => return vec![CutSegment { start, end, mode: SegmentMode::Encode }],
+    };
+
+    let mut segments = Vec::new();
+    if copy_start - start > KEYFRAME_TOLERANCE {
+        segments.push(CutSegment { start, end: copy_start, mode: SegmentMode::Encode });
+    }
+    segments.push(CutSegment { start: copy_start, end: copy_end, mode: SegmentMode::Copy });
+    if end - copy_end > KEYFRAME_TOLERANCE {
+        segments.push(CutSegment { start: copy_end, end, mode: SegmentMode::Encode });
+    }
+    segments
+}
+
+/// Codec matching the source stream, so re-encoded edges can be concatenated
+/// with the copied middle. AV1 is left out: FFmpeg's AV1 encoders can't be
+/// held to a level.
+pub fn codec_for_source(codec_name: &str) -> Result<VideoCodec> {
+    match codec_name {
+        "h264" => Ok(VideoCodec::H264),
+        "hevc" => Ok(VideoCodec::Hevc),
+        other => Err(VideoClipError::InvalidOptions(format!(
+            "smart cut supports H.264 and HEVC sources, not {}",
+            other
+        ))),
+    }
+}
+
+/// Encoder options giving the edges the source's `profile` and `level`, as
+/// ffprobe reports them; whichever isn't known is left to the encoder
+pub fn profile_args(codec: VideoCodec, profile: Option<&str>, level: Option<i32>) -> Result<Vec<String>> {
+    let unsupported = |profile: &str| VideoClipError::InvalidOptions(format!(
+        "smart cut can't re-encode the edges at the source's {} profile; clip without smart cut",
+        profile
+    ));
+    let mut args = Vec::new();
+    match codec {
+        VideoCodec::H264 => {
+            if let Some(profile) = profile {
+                let name = match profile {
+                    "Baseline" | "Constrained Baseline" => "baseline",
+                    "Main" => "main",
+                    "High" => "high",
+                    "High 10" => "high10",
+                    "High 4:2:2" => "high422",
+                    "High 4:4:4 Predictive" => "high444",
+                    other => return Err(unsupported(other)),
+                };
+                args.extend(["-profile:v".to_string(), name.to_string()]);
+            }
+            if let Some(level) = level {
+                args.extend(["-level:v".to_string(), format!("{}.{}", level / 10, level % 10)]);
+            }
+        }
+        VideoCodec::Hevc => {
+            if let Some(profile) = profile {
+                let name = match profile {
+                    "Main" => "main",
+                    "Main 10" => "main10",
+                    "Main Still Picture" => "mainstillpicture",
+                    other => return Err(unsupported(other)),
+                };
+                args.extend(["-profile:v".to_string(), name.to_string()]);
+            }
+            // HEVC levels are 30 times the level number
+            if let Some(level) = level {
+                args.extend(["-x265-params".to_string(), format!("level-idc={:.1}", level as f64 / 30.0)]);
+            }
+        }
+        _ => {}
+    }
+    Ok(args)
+}
+
+#[derive(Debug, Clone)]
+pub struct SmartCutCommand {
+    input: PathBuf,
+    output: PathBuf,
+    start_time: f64,
+    duration: f64,
+    segments: Vec<CutSegment>,
+    codec: VideoCodec,
+    encoder: EncoderChoice,
+    pix_fmt: Option<String>,
+    /// `-profile:v`/`-level:v` and the like for the edges
+    profile_args: Vec<String>,
+    audio_filter_graph: FilterGraph,
+    metadata: Option<ClipMetadata>,
+    process_limits: ProcessLimits,
+}
+
+impl SmartCutCommand {
+    /// Edges are encoded in software: hardware encoders emit different
+    /// parameter sets, which breaks playback across the concat seams
+    pub fn new(input: impl AsRef<Path>, output: impl AsRef<Path>, start_time: f64, duration: f64, keyframes: &[f64], codec: VideoCodec) -> Result<Self> {
+        let encoder = codec.software_encoder()
+            .map(EncoderChoice::new)
+            .ok_or_else(|| VideoClipError::InvalidOptions("smart cut needs a source codec to re-encode".to_string()))?;
+
+        Ok(Self {
+            input: input.as_ref().to_path_buf(),
+            output: output.as_ref().to_path_buf(),
+            start_time,
+            duration,
+            segments: plan_segments(start_time, start_time + duration, keyframes),
+            codec,
+            encoder,
+            pix_fmt: None,
+            profile_args: Vec::new(),
+            audio_filter_graph: FilterGraph::new(),
+            metadata: None,
+            process_limits: ProcessLimits::default(),
+        })
+    }
+
+    /// Matches the encoded edges' pixel format to the source (e.g. 10-bit)
+    pub fn set_pix_fmt(&mut self, pix_fmt: Option<String>) {
+        self.pix_fmt = pix_fmt;
+    }
+
+    /// Matches the encoded edges' profile and level to the source's, refusing
+    /// profiles the encoder can't produce
+    pub fn set_source_profile(&mut self, profile: Option<&str>, level: Option<i32>) -> Result<()> {
+        self.profile_args = profile_args(self.codec, profile, level)?;
+        Ok(())
+    }
+
+    /// Audio filters for the clip's own audio (gain, ...)
+    pub fn set_audio_filter_graph(&mut self, graph: FilterGraph) {
+        self.audio_filter_graph = graph;
+    }
+
+    /// Title, description and tags written into the joined output
+    pub fn set_metadata(&mut self, metadata: Option<ClipMetadata>) {
+        self.metadata = metadata;
+    }
+
+    /// Thread cap and CPU/IO priority for every step
+    pub fn set_process_limits(&mut self, limits: ProcessLimits) {
+        self.process_limits = limits;
+    }
+
+    pub fn segments(&self) -> &[CutSegment] {
+        &self.segments
+    }
+
+    pub fn encoder(&self) -> &EncoderChoice {
+        &self.encoder
+    }
+
+    /// Scratch directory for segment files, next to the output
+    pub fn work_dir(&self) -> PathBuf {
+        let mut name = self.output.file_name().unwrap_or_default().to_os_string();
+        name.push(".smartcut");
+        self.output.with_file_name(name)
+    }
+
+    pub fn segment_path(&self, index: usize) -> PathBuf {
+        self.work_dir().join(format!("segment_{:03}.mp4", index))
+    }
+
+    /// Video-only command for one segment; input seeking is frame-accurate
+    /// when decoding and lands exactly on the keyframe when copying
+    pub fn segment_args(&self, index: usize) -> Vec<String> {
+        let segment = &self.segments[index];
+        let mut args: Vec<String> = vec!["-ss".into(), segment.start.to_string()];
+        args.extend(self.process_limits.input_args());
+        args.extend([
+            "-i".into(), self.input.display().to_string(),
+            "-t".into(), segment.duration().to_string(),
+            "-map".into(), "0:v:0".into(),
+            "-an".into(),
+        ]);
+
+        match segment.mode {
+            SegmentMode::Encode => {
+                let mut encoder_args = self.encoder.output_args();
+                if let Some(pix_fmt) = &self.pix_fmt {
+                    if let Some(i) = encoder_args.iter().position(|a| a == "-pix_fmt") {
+                        encoder_args[i + 1] = pix_fmt.clone();
+                    } else {
+                        encoder_args.extend(["-pix_fmt".into(), pix_fmt.clone()]);
+                    }
+                }
+                args.extend(encoder_args);
+                args.extend(self.profile_args.iter().cloned());
+            }
+            SegmentMode::Copy => args.extend(["-c:v".into(), "copy".into()]),
+        }
+
+        // A shared timescale keeps segment timestamps compatible for concat
+        args.extend([
+            "-video_track_timescale".into(), "90000".into(),
+            "-avoid_negative_ts".into(), "make_zero".into(),
+        ]);
+        args.extend(self.process_limits.thread_args());
+        args.extend(["-y".into(), self.segment_path(index).display().to_string()]);
+        args
+    }
+
+    /// Concat demuxer script listing the segment files in order; the demuxer
+    /// resolves relative entries against the script's own directory
+    pub fn concat_list(&self) -> String {
+        (0..self.segments.len())
+            .map(|i| format!("file 'segment_{:03}.mp4'\n", i))
+            .collect()
+    }
+
+    pub fn concat_list_path(&self) -> PathBuf {
+        self.work_dir().join("segments.txt")
+    }
+
+    /// Joins the segments' video with the range's audio, cut from the source
+    pub fn concat_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-f".into(), "concat".into(),
+            "-safe".into(), "0".into(),
+            "-i".into(), self.concat_list_path().display().to_string(),
+            "-ss".into(), self.start_time.to_string(),
+            "-t".into(), self.duration.to_string(),
+        ];
+        // Only the source is throttled; the segments are scratch files
+        args.extend(self.process_limits.input_args());
+        args.extend([
+            "-i".into(), self.input.display().to_string(),
+            "-map".into(), "0:v:0".into(),
+            "-map".into(), "1:a?".into(),
+            "-c:v".into(), "copy".into(),
+        ]);
+        args.extend(self.audio_filter_graph.to_audio_args());
+        args.extend([
+            "-c:a".into(), "aac".into(),
+            "-b:a".into(), "128k".into(),
+            "-movflags".into(), "+faststart".into(),
+        ]);
+        if let Some(metadata) = &self.metadata {
+            args.extend(metadata.ffmpeg_args());
+        }
+        args.extend(self.process_limits.thread_args());
+        args.extend(["-y".into(), self.output.display().to_string()]);
+        args
+    }
+
+    /// Every step as it would be typed, segments first
+    pub fn get_command_string(&self) -> String {
+        (0..self.segments.len())
+            .map(|i| self.segment_args(i))
+            .chain(std::iter::once(self.concat_args()))
+            .map(|args| {
+                let mut argv = self.process_limits.priority_prefix();
+                argv.push(crate::tools::ffmpeg());
+                argv.extend(args);
+                argv.join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join(" && ")
+    }
+
+    /// `copy` when nothing needed re-encoding, otherwise the edge encoder
+    pub fn encoder_summary(&self) -> String {
+        if self.segments.iter().all(|s| s.mode == SegmentMode::Copy) {
+            "copy".to_string()
+        } else {
+            format!("smart ({})", self.encoder.name)
+        }
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<()> {
+        use crate::ffmpeg::FFmpegCommand;
+
+        let work_dir = self.work_dir();
+        std::fs::create_dir_all(&work_dir)?;
+
+        let result = (|| {
+            for index in 0..self.segments.len() {
+                let cmd = self.process_limits.command(&crate::tools::ffmpeg(), self.segment_args(index));
+                FFmpegCommand::run(cmd, &format!("Smart cut segment {} failed", index))?;
+            }
+
+            std::fs::write(self.concat_list_path(), self.concat_list())?;
+            let cmd = self.process_limits.command(&crate::tools::ffmpeg(), self.concat_args());
+            FFmpegCommand::run(cmd, "Smart cut concat failed").map(|_| ())
+        })();
+
+        if let Err(e) = std::fs::remove_dir_all(&work_dir) {
+            log::warn!("Could not remove {}: {}", work_dir.display(), e);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modes(segments: &[CutSegment]) -> Vec<(f64, f64, SegmentMode)> {
+        segments.iter().map(|s| (s.start, s.end, s.mode)).collect()
+    }
+
+    mod planning_tests {
+        use super::*;
+
+        #[test]
+        fn test_edges_are_encoded_and_middle_copied() {
+            let segments = plan_segments(61.5, 78.25, &[58.0, 60.0, 62.0, 64.0, 76.0, 78.0, 80.0]);
+            assert_eq!(
+                modes(&segments),
+                vec![
+                    (61.5, 62.0, SegmentMode::Encode),
+                    (62.0, 78.0, SegmentMode::Copy),
+                    (78.0, 78.25, SegmentMode::Encode),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_keyframe_aligned_range_is_pure_copy() {
+            let segments = plan_segments(10.0, 20.0, &[0.0, 10.0, 15.0, 20.0]);
+            assert_eq!(modes(&segments), vec![(10.0, 20.0, SegmentMode::Copy)]);
+        }
+
+        #[test]
+        fn test_range_within_one_gop_is_fully_encoded() {
+            assert_eq!(modes(&plan_segments(10.5, 11.5, &[10.0, 12.0])), vec![(10.5, 11.5, SegmentMode::Encode)]);
+            assert_eq!(modes(&plan_segments(10.5, 13.5, &[12.0])), vec![(10.5, 13.5, SegmentMode::Encode)]);
+            assert_eq!(modes(&plan_segments(1.0, 2.0, &[])), vec![(1.0, 2.0, SegmentMode::Encode)]);
+        }
+
+        #[test]
+        fn test_source_codec_mapping() {
+            assert_eq!(codec_for_source("hevc").unwrap(), VideoCodec::Hevc);
+            assert!(matches!(codec_for_source("mpeg2video"), Err(VideoClipError::InvalidOptions(_))));
+            assert!(codec_for_source("av1").is_err());
+        }
+
+        #[test]
+        fn test_profile_and_level_follow_the_source() {
+            assert_eq!(profile_args(VideoCodec::H264, Some("High"), Some(41)).unwrap().join(" "), "-profile:v high -level:v 4.1");
+            assert_eq!(profile_args(VideoCodec::H264, Some("Constrained Baseline"), None).unwrap().join(" "), "-profile:v baseline");
+            assert_eq!(
+                profile_args(VideoCodec::Hevc, Some("Main 10"), Some(153)).unwrap().join(" "),
+                "-profile:v main10 -x265-params level-idc=5.1"
+            );
+            assert!(profile_args(VideoCodec::H264, None, None).unwrap().is_empty());
+            assert!(profile_args(VideoCodec::H264, Some("High 4:4:4 Intra"), Some(40)).is_err());
+            assert!(profile_args(VideoCodec::Hevc, Some("Rext"), None).is_err());
+        }
+    }
+
+    mod command_tests {
+        use super::*;
+
+        fn command() -> SmartCutCommand {
+            SmartCutCommand::new("talk.mp4", "out/clip.mp4", 61.5, 16.75, &[60.0, 62.0, 78.0], VideoCodec::H264).unwrap()
+        }
+
+        #[test]
+        fn test_segment_commands() {
+            let cmd = command();
+            assert_eq!(cmd.work_dir(), PathBuf::from("out/clip.mp4.smartcut"));
+
+            assert_eq!(
+                cmd.segment_args(0).join(" "),
+                "-ss 61.5 -i talk.mp4 -t 0.5 -map 0:v:0 -an -c:v libx264 -preset medium -crf 23 -pix_fmt yuv420p \
+                 -video_track_timescale 90000 -avoid_negative_ts make_zero -y out/clip.mp4.smartcut/segment_000.mp4"
+            );
+            assert!(cmd.segment_args(1).join(" ").starts_with("-ss 62 -i talk.mp4 -t 16 -map 0:v:0 -an -c:v copy"));
+            assert_eq!(cmd.encoder_summary(), "smart (libx264)");
+        }
+
+        #[test]
+        fn test_source_pixel_format_is_kept() {
+            let mut cmd = command();
+            cmd.set_pix_fmt(Some("yuv420p10le".to_string()));
+            cmd.set_source_profile(Some("High 10"), Some(51)).unwrap();
+            let args = cmd.segment_args(2).join(" ");
+            assert!(args.contains("-pix_fmt yuv420p10le -profile:v high10 -level:v 5.1"));
+            assert!(!args.contains("-pix_fmt yuv420p "));
+            assert!(!cmd.segment_args(1).join(" ").contains("-profile:v"));
+        }
+
+        #[test]
+        fn test_concat_step() {
+            let mut cmd = command();
+            cmd.set_audio_filter_graph(FilterGraph::from(crate::ffmpeg::audio_mix::volume_filter(-3.0)));
+
+            assert_eq!(
+                cmd.concat_list(),
+                "file 'segment_000.mp4'\nfile 'segment_001.mp4'\nfile 'segment_002.mp4'\n"
+            );
+            assert_eq!(
+                cmd.concat_args().join(" "),
+                "-f concat -safe 0 -i out/clip.mp4.smartcut/segments.txt -ss 61.5 -t 16.75 -i talk.mp4 \
+                 -map 0:v:0 -map 1:a? -c:v copy -af volume=-3dB -c:a aac -b:a 128k -movflags +faststart -y out/clip.mp4"
+            );
+            assert_eq!(cmd.get_command_string().matches("ffmpeg ").count(), 4);
+        }
+    }
+}
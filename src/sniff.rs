@@ -0,0 +1,245 @@
+use crate::error::{VideoClipError, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// File type detection
+/// Looks at a file's first bytes rather than its extension, so a recording
+/// saved without one (or renamed to the wrong one) is still clipped, and a
+/// photo, PDF or text file is turned away with `NotAVideoFile` before FFmpeg
+/// gets to fail on it in its own words. Files that match no known signature
+/// are left for FFmpeg to judge.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Container {
+    /// MP4, MOV, M4V and 3GP (ISO base media)
+    Mp4,
+    /// MKV and WebM
+    Matroska,
+    Avi,
+    MpegTs,
+    MpegPs,
+    Flv,
+    /// WMV and ASF
+    Asf,
+    Ogg,
+}
+
+impl Container {
+    pub fn name(self) -> &'static str {
+        match self {
+            Container::Mp4 => "MP4/QuickTime",
+            Container::Matroska => "Matroska/WebM",
+            Container::Avi => "AVI",
+            Container::MpegTs => "MPEG transport stream",
+            Container::MpegPs => "MPEG program stream",
+            Container::Flv => "FLV",
+            Container::Asf => "ASF/WMV",
+            Container::Ogg => "Ogg",
+        }
+    }
+}
+
+/// What a file's first bytes say it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Video(Container),
+    /// Recognized, and not a video; holds a readable name like `PNG image`
+    NotVideo(&'static str),
+    /// No known signature; FFmpeg may still read it
+    Unknown,
+}
+
+impl FileKind {
+    /// Bytes read from the start of a file; enough for every signature,
+    /// including a transport stream's second sync byte
+    pub const SNIFF_LEN: usize = 512;
+
+    pub fn of_path(path: impl AsRef<Path>) -> Result<Self> {
+        let mut header = Vec::with_capacity(Self::SNIFF_LEN);
+        File::open(path)?.take(Self::SNIFF_LEN as u64).read_to_end(&mut header)?;
+        Ok(Self::of_bytes(&header))
+    }
+
+    pub fn of_bytes(bytes: &[u8]) -> Self {
+        if bytes.is_empty() {
+            return FileKind::NotVideo("empty file");
+        }
+        if let Some(kind) = iso_media(bytes) {
+            return kind;
+        }
+        if let Some(container) = video_signature(bytes) {
+            return FileKind::Video(container);
+        }
+        if let Some(name) = other_signature(bytes) {
+            return FileKind::NotVideo(name);
+        }
+        if looks_like_text(bytes) {
+            return FileKind::NotVideo("text file");
+        }
+        FileKind::Unknown
+    }
+
+    pub fn is_video(self) -> bool {
+        matches!(self, FileKind::Video(_))
+    }
+}
+
+/// `NotAVideoFile` when `path`'s content is recognized as something else;
+/// videos and unrecognized files pass
+pub fn ensure_video(path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    match FileKind::of_path(path)? {
+        FileKind::NotVideo(detected) => Err(VideoClipError::NotAVideoFile {
+            path: path.display().to_string(),
+            detected: detected.to_string(),
+        }),
+        FileKind::Video(_) | FileKind::Unknown => Ok(()),
+    }
+}
+
+/// ISO base media files start with a `ftyp` box whose brand tells MP4 video
+/// from M4A audio and HEIF photos
+fn iso_media(bytes: &[u8]) -> Option<FileKind> {
+    if bytes.get(4..8)? != b"ftyp" {
+        // QuickTime files from older cameras can open with other atoms
+        return matches!(bytes.get(4..8)?, b"moov" | b"mdat" | b"wide" | b"free" | b"skip" | b"pnot")
+            .then_some(FileKind::Video(Container::Mp4));
+    }
+    let kind = match bytes.get(8..12)? {
+        b"M4A " | b"M4B " | b"M4P " => FileKind::NotVideo("M4A audio"),
+        b"heic" | b"heix" | b"mif1" | b"msf1" | b"avif" => FileKind::NotVideo("HEIF/AVIF image"),
+        _ => FileKind::Video(Container::Mp4),
+    };
+    Some(kind)
+}
+
+fn video_signature(bytes: &[u8]) -> Option<Container> {
+    let container = if bytes.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        Container::Matroska
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"AVI ") {
+        Container::Avi
+    } else if bytes[0] == 0x47 && bytes.get(188) == Some(&0x47) {
+        Container::MpegTs
+    } else if bytes.starts_with(&[0x00, 0x00, 0x01, 0xBA]) || bytes.starts_with(&[0x00, 0x00, 0x01, 0xB3]) {
+        Container::MpegPs
+    } else if bytes.starts_with(b"FLV\x01") {
+        Container::Flv
+    } else if bytes.starts_with(&[0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11]) {
+        Container::Asf
+    } else if bytes.starts_with(b"OggS") {
+        Container::Ogg
+    } else {
+        return None;
+    };
+    Some(container)
+}
+
+fn other_signature(bytes: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "PNG image"),
+        (&[0xFF, 0xD8, 0xFF], "JPEG image"),
+        (b"GIF87a", "GIF image"),
+        (b"GIF89a", "GIF image"),
+        (b"%PDF", "PDF document"),
+        (b"PK\x03\x04", "ZIP archive"),
+        (&[0x1F, 0x8B], "gzip archive"),
+        (b"7z\xBC\xAF\x27\x1C", "7z archive"),
+        (b"Rar!", "RAR archive"),
+        (b"ID3", "MP3 audio"),
+        (b"fLaC", "FLAC audio"),
+        (b"\x7fELF", "executable"),
+    ];
+    if let Some((_, name)) = SIGNATURES.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+        return Some(name);
+    }
+    if bytes.starts_with(b"RIFF") {
+        return match bytes.get(8..12)? {
+            b"WAVE" => Some("WAV audio"),
+            b"WEBP" => Some("WebP image"),
+            _ => None,
+        };
+    }
+    // Bare MPEG audio frames
+    (bytes.len() > 1 && bytes[0] == 0xFF && bytes[1] & 0xE0 == 0xE0).then_some("MPEG audio")
+}
+
+/// Valid UTF-8 without control characters other than whitespace; a cut-off
+/// multi-byte character at the end of the sample doesn't count against it
+fn looks_like_text(bytes: &[u8]) -> bool {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+        Err(_) => return false,
+    };
+    !text.is_empty() && text.chars().all(|c| !c.is_control() || c.is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_header(header: &[u8]) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(FileKind::SNIFF_LEN, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_video_signatures() {
+        let cases: &[(&[u8], Container)] = &[
+            (b"\0\0\0\x20ftypisom", Container::Mp4),
+            (b"\0\0\0\x14ftypqt  ", Container::Mp4),
+            (b"\0\0\0\x08wide", Container::Mp4),
+            (&[0x1A, 0x45, 0xDF, 0xA3, 0x9F], Container::Matroska),
+            (b"RIFF\x10\0\0\0AVI LIST", Container::Avi),
+            (&[0x00, 0x00, 0x01, 0xBA, 0x44], Container::MpegPs),
+            (b"FLV\x01\x05", Container::Flv),
+            (&[0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11, 0xA6], Container::Asf),
+        ];
+        for (header, container) in cases {
+            assert_eq!(FileKind::of_bytes(&with_header(header)), FileKind::Video(*container), "{:?}", header);
+        }
+
+        let mut ts = vec![0xAB; 400];
+        ts[0] = 0x47;
+        ts[188] = 0x47;
+        assert_eq!(FileKind::of_bytes(&ts), FileKind::Video(Container::MpegTs));
+    }
+
+    #[test]
+    fn test_files_that_arent_video() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", "empty file"),
+            (b"\x89PNG\r\n\x1a\n\0\0", "PNG image"),
+            (&[0xFF, 0xD8, 0xFF, 0xE0], "JPEG image"),
+            (b"%PDF-1.7", "PDF document"),
+            (b"\0\0\0\x20ftypM4A ", "M4A audio"),
+            (b"\0\0\0\x1cftypheic", "HEIF/AVIF image"),
+            (b"RIFF\x10\0\0\0WAVEfmt ", "WAV audio"),
+            (b"ID3\x04", "MP3 audio"),
+            ("notes about the talk — café\n".as_bytes(), "text file"),
+        ];
+        for (bytes, name) in cases {
+            assert_eq!(FileKind::of_bytes(bytes), FileKind::NotVideo(name), "{:?}", bytes);
+        }
+        assert_eq!(FileKind::of_bytes(&[0x00, 0x01, 0x02, 0x03, 0x9C]), FileKind::Unknown);
+    }
+
+    #[test]
+    fn test_ensure_video_ignores_the_extension() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let renamed = dir.path().join("recording.txt");
+        std::fs::write(&renamed, with_header(b"\0\0\0\x20ftypisom")).unwrap();
+        assert!(ensure_video(&renamed).is_ok());
+
+        let photo = dir.path().join("holiday.mp4");
+        std::fs::write(&photo, with_header(&[0xFF, 0xD8, 0xFF, 0xE1])).unwrap();
+        match ensure_video(&photo) {
+            Err(VideoClipError::NotAVideoFile { detected, .. }) => assert_eq!(detected, "JPEG image"),
+            other => panic!("expected NotAVideoFile, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::error::{VideoClipError, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+
+/// Source access on network shares
+/// Sources on SMB/NFS mounts can drop a read mid-clip (a stale handle, a
+/// reconnecting server) or be replaced by a sync tool while FFmpeg reads
+/// them. A clipper can hold a shared advisory lock on each source while it's
+/// clipped, so cooperating writers that take an exclusive lock wait, and can
+/// retry a clip that failed with a transient read error after a growing
+/// pause. Clips are staged (see [`crate::staging`]), so a failed attempt
+/// leaves nothing behind.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceAccess {
+    /// Hold a shared lock on the source while it's read
+    #[serde(default)]
+    pub lock: bool,
+    /// Times a clip that failed reading its source is tried again
+    #[serde(default)]
+    pub retries: u32,
+    /// Pause before the first retry; doubled for each one after
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+}
+
+fn default_backoff_ms() -> u64 {
+    1000
+}
+
+impl Default for SourceAccess {
+    fn default() -> Self {
+        Self { lock: false, retries: 0, backoff_ms: default_backoff_ms() }
+    }
+}
+
+const MAX_RETRIES: u32 = 10;
+
+/// Longest single pause, however many retries came before
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+impl SourceAccess {
+    pub fn validate(&self) -> Result<()> {
+        if self.retries > MAX_RETRIES {
+            return Err(VideoClipError::InvalidOptions(format!("at most {} read retries, got {}", MAX_RETRIES, self.retries)));
+        }
+        if self.retries > 0 && self.backoff_ms == 0 {
+            return Err(VideoClipError::InvalidOptions("the retry backoff must be at least 1ms".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Pause before retry number `retry` (from 0)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u64.saturating_pow(retry);
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor)).min(MAX_BACKOFF)
+    }
+
+    /// A shared lock on `source`, held until the file is dropped; `None`
+    /// when locking is off, the source isn't a local path (a URL, a pipe)
+    /// or the share doesn't support locks
+    pub fn lock_source(&self, source: &Path) -> Result<Option<File>> {
+        if !self.lock || !source.is_file() {
+            return Ok(None);
+        }
+        let file = File::open(source)?;
+        match file.lock_shared() {
+            Ok(()) => Ok(Some(file)),
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Runs `attempt` until it succeeds, fails for good, or runs out of
+    /// retries; returns its result and how many retries it took
+    pub fn retry<T>(&self, mut attempt: impl FnMut() -> Result<T>) -> Result<(T, u32)> {
+        let mut retries = 0;
+        loop {
+            match attempt() {
+                Ok(value) => return Ok((value, retries)),
+                Err(e) if retries < self.retries && e.is_transient_io() => {
+                    std::thread::sleep(self.backoff(retries));
+                    retries += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flaky() -> VideoClipError {
+        VideoClipError::FFmpegError("Clip failed: /mnt/nas/talk.mp4: Input/output error".to_string())
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_a_cap() {
+        let access = SourceAccess { retries: 3, backoff_ms: 500, ..Default::default() };
+        assert_eq!(access.backoff(0), Duration::from_millis(500));
+        assert_eq!(access.backoff(2), Duration::from_secs(2));
+        assert_eq!(access.backoff(40), MAX_BACKOFF);
+        assert!(SourceAccess { retries: 11, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_retries_only_transient_errors() {
+        let access = SourceAccess { retries: 2, backoff_ms: 1, ..Default::default() };
+        let mut calls = 0;
+        let outcome = access.retry(|| {
+            calls += 1;
+            if calls < 3 { Err(flaky()) } else { Ok("clip") }
+        });
+        assert_eq!(outcome.unwrap(), ("clip", 2));
+
+        let mut calls = 0;
+        let outcome: Result<((), u32)> = access.retry(|| {
+            calls += 1;
+            Err(VideoClipError::InvalidOptions("bad".to_string()))
+        });
+        assert!(outcome.is_err());
+        assert_eq!(calls, 1);
+
+        let outcome: Result<((), u32)> = access.retry(|| Err(flaky()));
+        assert!(outcome.unwrap_err().is_transient_io());
+    }
+
+    #[test]
+    fn test_lock_source() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let source = dir.path().join("talk.mp4");
+        std::fs::write(&source, b"video").unwrap();
+        let access = SourceAccess { lock: true, ..Default::default() };
+        let held = access.lock_source(&source).unwrap();
+        assert!(held.is_some());
+        // Other readers share the lock
+        assert!(access.lock_source(&source).unwrap().is_some());
+        assert!(access.lock_source(&dir.path().join("missing.mp4")).unwrap().is_none());
+        assert!(SourceAccess::default().lock_source(&source).unwrap().is_none());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::error::{VideoClipError, Result};
+use crate::ranges::TimeRange;
+use crate::transcript::{Cue, SubtitleFormat, Transcript, TranscriptQuery};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Speech transcripts
+/// `TranscriptProvider` is where a transcript comes from when a range is
+/// searched by what's said, or when a clip gets a caption sidecar: a subtitle
+/// file that goes with the video, or speech recognition. Building with the
+/// `whisper` feature adds `WhisperProvider`, which runs a whisper.cpp (ggml)
+/// model over the audio locally.
+
+#[derive(Debug, Clone)]
+pub struct SubtitleFile {
+    /// An existing SRT/WebVTT file for the input
+    pub path: PathBuf,
+}
+
+/// Where cues come from
+pub trait TranscriptProvider: fmt::Debug + Send + Sync {
+    /// Cues for `range` of `input` (all of it when `None`), timed from the
+    /// start of the file
+    fn transcribe(&self, input: &Path, range: Option<&TimeRange>) -> Result<Transcript>;
+}
+
+impl TranscriptProvider for SubtitleFile {
+    fn transcribe(&self, _input: &Path, range: Option<&TimeRange>) -> Result<Transcript> {
+        let transcript = Transcript::from_path(&self.path)?;
+        Ok(match range {
+            Some(range) => transcript.within(range),
+            None => transcript,
+        })
+    }
+}
+
+/// Ranges of `input` where `query` is said
+pub fn find_speech(provider: &dyn TranscriptProvider, input: &Path, query: &TranscriptQuery) -> Result<Vec<TimeRange>> {
+    provider.transcribe(input, None)?.find(query)
+}
+
+/// Speech models take mono audio at 16 kHz
+pub const SAMPLE_RATE: u32 = 16_000;
+
+/// FFmpeg arguments that decode `range` of `input`'s first audio stream to
+/// raw little-endian float samples on stdout
+pub fn pcm_args(input: &Path, range: Option<&TimeRange>) -> Vec<String> {
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-nostdin".into()];
+    if let Some(range) = range {
+        args.extend(["-ss".into(), range.start.to_string(), "-t".into(), range.duration().to_string()]);
+    }
+    args.extend(["-i".into(), input.display().to_string()]);
+    args.extend(["-map", "0:a:0", "-vn", "-ac", "1"].map(String::from));
+    args.extend(["-ar".into(), SAMPLE_RATE.to_string(), "-f".into(), "f32le".into(), "-".into()]);
+    args
+}
+
+/// `range` of `input` as samples for a speech model
+#[cfg(not(feature = "wasm"))]
+pub fn decode_pcm(input: &Path, range: Option<&TimeRange>) -> Result<Vec<f32>> {
+    let cmd = crate::tools::ffmpeg_command(pcm_args(input, range));
+    let output = crate::ffmpeg::FFmpegCommand::run(cmd, "Audio decoding for transcription failed")?;
+    Ok(output.stdout.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+}
+
+/// A transcript from recognized segments `(start, end, text)` timed from
+/// the start of the decoded audio, which began `offset` seconds into the file
+pub fn transcript_from_segments(segments: impl IntoIterator<Item = (f64, f64, String)>, offset: f64) -> Transcript {
+    let cues = segments
+        .into_iter()
+        .filter(|(_, _, text)| !text.trim().is_empty())
+        .enumerate()
+        .map(|(i, (start, end, text))| Cue {
+            index: i + 1,
+            start: start + offset,
+            end: end + offset,
+            text: text.trim().to_string(),
+        })
+        .collect();
+    Transcript { format: SubtitleFormat::Srt, cues }
+}
+
+/// A provider running the whisper model at `model`, in `language` (or the
+/// one it detects); an error in builds without the `whisper` feature
+pub fn whisper_provider(model: &str, language: Option<String>) -> Result<Arc<dyn TranscriptProvider>> {
+    #[cfg(feature = "whisper")]
+    return Ok(Arc::new(WhisperProvider::new(model, language)?));
+    #[cfg(not(feature = "whisper"))]
+    {
+        let _ = (model, language);
+        Err(VideoClipError::InvalidOptions("speech recognition needs a build with the `whisper` feature".to_string()))
+    }
+}
+
+#[cfg(feature = "whisper")]
+pub use whisper::WhisperProvider;
+
+#[cfg(feature = "whisper")]
+mod whisper {
+    use super::*;
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+    /// whisper.cpp segment times are in hundredths of a second
+    const TICKS_PER_SECOND: f64 = 100.0;
+
+    pub struct WhisperProvider {
+        context: WhisperContext,
+        language: Option<String>,
+    }
+
+    impl fmt::Debug for WhisperProvider {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("WhisperProvider").field("language", &self.language).finish_non_exhaustive()
+        }
+    }
+
+    impl WhisperProvider {
+        /// Loads the ggml model at `model` (e.g. `ggml-base.en.bin`)
+        pub fn new(model: &str, language: Option<String>) -> Result<Self> {
+            if !Path::new(model).is_file() {
+                return Err(VideoClipError::FileNotFound(model.to_string()));
+            }
+            let context = WhisperContext::new_with_params(model, WhisperContextParameters::default())
+                .map_err(|e| VideoClipError::TranscriptionFailed(format!("couldn't load {}: {}", model, e)))?;
+            Ok(Self { context, language })
+        }
+    }
+
+    impl TranscriptProvider for WhisperProvider {
+        fn transcribe(&self, input: &Path, range: Option<&TimeRange>) -> Result<Transcript> {
+            let samples = decode_pcm(input, range)?;
+            let failed = |e: whisper_rs::WhisperError| VideoClipError::TranscriptionFailed(e.to_string());
+
+            let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+            params.set_language(Some(self.language.as_deref().unwrap_or("auto")));
+            params.set_print_progress(false);
+            params.set_print_realtime(false);
+            params.set_print_special(false);
+            params.set_print_timestamps(false);
+
+            let mut state = self.context.create_state().map_err(failed)?;
+            state.full(params, &samples).map_err(failed)?;
+            let mut segments = Vec::new();
+            for i in 0..state.full_n_segments().map_err(failed)? {
+                let start = state.full_get_segment_t0(i).map_err(failed)? as f64 / TICKS_PER_SECOND;
+                let end = state.full_get_segment_t1(i).map_err(failed)? as f64 / TICKS_PER_SECOND;
+                segments.push((start, end, state.full_get_segment_text_lossy(i).map_err(failed)?));
+            }
+            Ok(transcript_from_segments(segments, range.map_or(0.0, |r| r.start)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcm_args() {
+        let range = TimeRange { start: 60.0, end: 90.0 };
+        assert_eq!(
+            pcm_args(Path::new("talk.mp4"), Some(&range)).join(" "),
+            "-hide_banner -nostdin -ss 60 -t 30 -i talk.mp4 -map 0:a:0 -vn -ac 1 -ar 16000 -f f32le -"
+        );
+    }
+
+    #[test]
+    fn test_segments_become_cues_in_file_time() {
+        let transcript = transcript_from_segments(
+            vec![(0.0, 2.5, " Welcome back.".to_string()), (2.5, 3.0, " ".to_string()), (3.0, 6.0, " Let's start.".to_string())],
+            60.0,
+        );
+        assert_eq!(transcript.cues.len(), 2);
+        assert_eq!((transcript.cues[1].index, transcript.cues[1].start, transcript.cues[1].text.as_str()), (2, 63.0, "Let's start."));
+
+        let found = transcript.find(&TranscriptQuery::Text("let's start".to_string())).unwrap();
+        assert_eq!(found, vec![TimeRange { start: 63.0, end: 66.0 }]);
+    }
+
+    #[test]
+    fn test_subtitle_file_provider() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("talk.srt");
+        std::fs::write(&path, "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n00:00:10,000 --> 00:00:12,000\nAgain\n").unwrap();
+        let provider = SubtitleFile { path };
+        let transcript = provider.transcribe(Path::new("talk.mp4"), Some(&TimeRange { start: 9.0, end: 20.0 })).unwrap();
+        assert_eq!(transcript.cues.iter().map(|c| c.text.as_str()).collect::<Vec<_>>(), vec!["Again"]);
+        assert!(whisper_provider("missing.bin", None).is_err());
+    }
+}
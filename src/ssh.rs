@@ -0,0 +1,191 @@
+use crate::error::{VideoClipError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Running FFmpeg on another host over SSH
+/// A thin client can have every ffmpeg and ffprobe run happen on an encode
+/// server (see [`crate::tools::set_executor`]). Paths in requests are then
+/// the server's: sources are read and clips written there, and relative
+/// paths start from the login directory. Clips are planned locally and each
+/// planned command runs remotely (see [`crate::VideoClipper::execute_plan`]),
+/// so only clips made by a single FFmpeg run can be cut this way. With
+/// `fetch` set, each finished clip is also copied back into the
+/// clipper's output directory.
+///
+/// ssh runs non-interactively (`BatchMode`), so the host needs key-based login.
+/// Without a terminal, killing ssh leaves the remote FFmpeg running, so each
+/// run keeps its pid in a file for a cancelled clip to kill it by (see
+/// [`SshExecutor::stop_args`]). The file goes in the remote user's
+/// `$XDG_RUNTIME_DIR`, or their home directory without one, rather than a
+/// shared directory where another user could plant it first.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SshExecutor {
+    /// `host` or `user@host`
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Private key, when the agent or `~/.ssh/config` doesn't pick one
+    #[serde(default)]
+    pub identity_file: Option<PathBuf>,
+    /// More options for ssh, e.g. `-o ProxyJump=bastion`
+    #[serde(default)]
+    pub ssh_args: Vec<String>,
+    /// Copy each clip back after it's made
+    #[serde(default)]
+    pub fetch: bool,
+}
+
+/// How long, in seconds, stopping a cancelled run waits to reach the host
+pub const STOP_CONNECT_TIMEOUT: u32 = 10;
+
+impl SshExecutor {
+    pub fn new(host: impl Into<String>) -> Self {
+        Self { host: host.into(), port: None, identity_file: None, ssh_args: Vec::new(), fetch: false }
+    }
+
+    /// `user@host` with an optional `:port`
+    pub fn parse(target: &str) -> Result<Self> {
+        let invalid = || VideoClipError::InvalidOptions(format!("invalid SSH host '{}' (expected [USER@]HOST[:PORT])", target));
+        let (host, port) = match target.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port.parse().map_err(|_| invalid())?)),
+            None => (target, None),
+        };
+        if host.is_empty() || host.starts_with('-') || host.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+        Ok(Self { port, ..Self::new(host) })
+    }
+
+    /// ssh arguments that run `program args...` on the host, quoted for its shell
+    pub fn run_args(&self, program: &str, args: Vec<String>) -> Vec<String> {
+        self.ssh_args(remote_command(program, args))
+    }
+
+    /// Like [`SshExecutor::run_args`], with the remote process's pid kept in
+    /// a file named after `name` while it runs
+    pub fn run_named_args(&self, name: &str, program: &str, args: Vec<String>) -> Vec<String> {
+        self.ssh_args(format!(
+            "{} & echo $! > {pid}; wait $!; status=$?; rm -f {pid}; exit $status",
+            remote_command(program, args),
+            pid = pid_file(name)
+        ))
+    }
+
+    /// ssh arguments that kill the run `name` on the host, giving up if the
+    /// host can't be reached within [`STOP_CONNECT_TIMEOUT`] seconds
+    pub fn stop_args(&self, name: &str) -> Vec<String> {
+        let mut ssh = vec!["-o".to_string(), format!("ConnectTimeout={}", STOP_CONNECT_TIMEOUT)];
+        ssh.extend(self.ssh_args(format!("kill $(cat {pid}) 2>/dev/null; rm -f {pid}", pid = pid_file(name))));
+        ssh
+    }
+
+    fn ssh_args(&self, remote: String) -> Vec<String> {
+        let mut ssh = vec!["-o".to_string(), "BatchMode=yes".to_string()];
+        if let Some(port) = self.port {
+            ssh.extend(["-p".to_string(), port.to_string()]);
+        }
+        if let Some(key) = &self.identity_file {
+            ssh.extend(["-i".to_string(), key.display().to_string()]);
+        }
+        ssh.extend(self.ssh_args.iter().cloned());
+        ssh.extend(["--".to_string(), self.host.clone(), remote]);
+        ssh
+    }
+
+    /// Creates `dir` on the host
+    pub fn create_dir_all(&self, dir: &str) -> Result<()> {
+        let mut cmd = Command::new("ssh");
+        cmd.args(self.run_args("mkdir", vec!["-p".to_string(), dir.to_string()]));
+        run(cmd, "Creating the remote output directory failed")
+    }
+
+    /// Copies `remote` from the host to `local`. It's streamed through ssh
+    /// rather than scp, whose remote paths are quoted differently depending
+    /// on its protocol version.
+    pub fn fetch(&self, remote: &str, local: &Path) -> Result<()> {
+        let mut cmd = Command::new("ssh");
+        cmd.args(self.run_args("cat", vec![remote.to_string()]));
+        cmd.stdout(std::fs::File::create(local)?);
+        let fetched = run(cmd, "Copying the clip back failed");
+        if fetched.is_err() {
+            let _ = std::fs::remove_file(local);
+        }
+        fetched
+    }
+}
+
+/// `program args...` quoted for the host's shell
+fn remote_command(program: &str, args: Vec<String>) -> String {
+    let words: Vec<String> = [program.to_string()].into_iter().chain(args).map(|arg| shell_quote(&arg)).collect();
+    words.join(" ")
+}
+
+/// The pid file of the run `name`, as a word for the host's shell
+fn pid_file(name: &str) -> String {
+    format!("\"${{XDG_RUNTIME_DIR:-$HOME}}\"/{}", shell_quote(&format!(".{}.pid", name)))
+}
+
+fn run(mut cmd: Command, context: &str) -> Result<()> {
+    let output = cmd.output().map_err(|e| VideoClipError::FFmpegError(format!("{}: {}", context, e)))?;
+    if !output.status.success() {
+        return Err(VideoClipError::FFmpegError(format!("{}: {}", context, String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(())
+}
+
+/// `arg` as one word for a POSIX shell
+pub fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=+,@%".contains(c);
+    match !arg.is_empty() && arg.chars().all(plain) {
+        true => arg.to_string(),
+        false => format!("'{}'", arg.replace('\'', r"'\''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_args_quote_for_the_remote_shell() {
+        let ssh = SshExecutor { identity_file: Some(PathBuf::from("/keys/encode")), ..SshExecutor::parse("clipper@encode-01:2222").unwrap() };
+        let args = ["-i", "/media/Bob's talk.mp4", "-vf", "scale=1280:-2", "-y", "out/clip.mp4"].map(String::from).to_vec();
+        assert_eq!(
+            ssh.run_args("ffmpeg", args),
+            vec![
+                "-o", "BatchMode=yes", "-p", "2222", "-i", "/keys/encode", "--", "clipper@encode-01",
+                r"ffmpeg -i '/media/Bob'\''s talk.mp4' -vf scale=1280:-2 -y out/clip.mp4",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_named_runs_keep_their_status_and_clean_up() {
+        let runtime = tempfile::tempdir().unwrap();
+        let ssh = SshExecutor::new("encode-01");
+        // The pid is written just after the run starts, so wait (briefly) for it
+        let wait = r#"for _ in $(seq 100); do [ -e "$XDG_RUNTIME_DIR"/.video-clip-test.pid ] && break; sleep 0.05; done"#;
+        let args = vec!["-c".to_string(), format!(r#"{}; ls -A "$XDG_RUNTIME_DIR"; exit 3"#, wait)];
+        let script = ssh.run_named_args("video-clip-test", "sh", args).pop().unwrap();
+        let output = Command::new("sh").args(["-c", &script]).env("XDG_RUNTIME_DIR", runtime.path()).output().unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b".video-clip-test.pid\n");
+        assert_eq!(std::fs::read_dir(runtime.path()).unwrap().count(), 0);
+
+        let stop = ssh.stop_args("video-clip-test");
+        assert_eq!(stop[..4], ["-o", "ConnectTimeout=10", "-o", "BatchMode=yes"]);
+        assert_eq!(stop.last().unwrap(), r#"kill $(cat "${XDG_RUNTIME_DIR:-$HOME}"/.video-clip-test.pid) 2>/dev/null; rm -f "${XDG_RUNTIME_DIR:-$HOME}"/.video-clip-test.pid"#);
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        assert_eq!(SshExecutor::parse("encode").unwrap().port, None);
+        assert!(SshExecutor::parse("encode:ssh").is_err());
+        assert!(SshExecutor::parse("-oProxyCommand=x").is_err());
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a;b"), "'a;b'");
+    }
+}
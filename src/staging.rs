@@ -0,0 +1,118 @@
+//! Staged output files
+//! Parallel workers (the threads of a batch, or separate processes sharing
+//! an output directory) can be asked for the same clip. Each one writes to a
+//! temporary file no other writer uses, named after the output plus its
+//! process id and a counter, and renames it into place once the clip has
+//! been checked. Renames within a directory are atomic, so readers see the
+//! old file or the whole new one, never a mix. Writers publish holding an
+//! exclusive lock on the directory's lock file, so two publishing the same
+//! name take turns.
+
+use crate::error::Result;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Exclusive lock on a directory's [`LOCK_FILE`], released when dropped
+#[derive(Debug)]
+pub struct DirLock {
+    _file: File,
+}
+
+impl DirLock {
+    /// Blocks until no other writer holds the lock
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(dir.join(LOCK_FILE))?;
+        file.lock()?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// Held by every writer publishing into a directory; left in place, since
+/// deleting a lock file others may be waiting on would split the lock
+pub const LOCK_FILE: &str = ".video-clip.lock";
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// `out/.clip.mp4.4242-7.partial.mp4`: hidden so folder watchers skip it,
+/// and ending in the output's extension, which FFmpeg picks the muxer by
+pub fn temp_path(output: &Path) -> PathBuf {
+    let name = output.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = output.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    output.with_file_name(format!(".{}.{}-{}.partial{}", name, std::process::id(), sequence, extension))
+}
+
+/// An output being written under a temporary name. Dropping it before
+/// [`StagedOutput::publish`] deletes whatever was written.
+#[derive(Debug)]
+pub struct StagedOutput {
+    temp: PathBuf,
+    output: PathBuf,
+}
+
+impl StagedOutput {
+    pub fn new(output: impl AsRef<Path>) -> Self {
+        let output = output.as_ref().to_path_buf();
+        Self { temp: temp_path(&output), output }
+    }
+
+    /// Where to write
+    pub fn path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// Renames the finished file into place, replacing any earlier clip of
+    /// the same name, and returns the final path
+    pub fn publish(self) -> Result<PathBuf> {
+        let dir = self.output.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let _lock = DirLock::acquire(dir)?;
+        fs::rename(&self.temp, &self.output)?;
+        Ok(self.output.clone())
+    }
+}
+
+impl Drop for StagedOutput {
+    fn drop(&mut self) {
+        // Gone already once published
+        let _ = fs::remove_file(&self.temp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_temp_names_are_unique() {
+        let output = Path::new("out/talk_clip_01-00_to_01-30.mp4");
+        let (a, b) = (temp_path(output), temp_path(output));
+        assert_ne!(a, b);
+        assert_eq!(a.parent(), Some(Path::new("out")));
+        assert_eq!(a.extension().unwrap(), "mp4");
+        let name = a.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with(".talk_clip_01-00_to_01-30.mp4.") && name.ends_with(".partial.mp4"), "{}", name);
+    }
+
+    #[test]
+    fn test_publish_replaces_and_abandoned_stages_are_removed() {
+        let dir = TempDir::new().unwrap();
+        let output = dir.path().join("clip.mp4");
+        std::fs::write(&output, b"old").unwrap();
+
+        let first = StagedOutput::new(&output);
+        let second = StagedOutput::new(&output);
+        std::fs::write(first.path(), b"first").unwrap();
+        std::fs::write(second.path(), b"second").unwrap();
+        let abandoned = second.path().to_path_buf();
+        drop(second);
+        assert!(!abandoned.exists());
+
+        assert_eq!(first.publish().unwrap(), output);
+        assert_eq!(std::fs::read(&output).unwrap(), b"first");
+        let left: Vec<_> = std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(left.len(), 2, "{:?}", left);
+        assert!(dir.path().join(LOCK_FILE).exists());
+    }
+}
@@ -0,0 +1,250 @@
+use crate::error::{VideoClipError, Result};
+use crate::ffmpeg::filter_graph::{Filter, FilterGraph};
+use crate::frames::ImageFormat;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Contact sheet / storyboard generation
+/// Samples evenly spaced frames across a range and tiles them into a single
+/// image (optionally stamped with timestamps) for quick visual review
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoryboardOptions {
+    #[serde(default = "default_grid")]
+    pub columns: u32,
+    #[serde(default = "default_grid")]
+    pub rows: u32,
+    /// Width of each tile in pixels; height follows the aspect ratio
+    #[serde(default = "default_tile_width")]
+    pub tile_width: u32,
+    /// Burn the source timestamp of each frame into its tile
+    #[serde(default = "default_show_timestamps")]
+    pub show_timestamps: bool,
+    #[serde(default = "default_format")]
+    pub format: ImageFormat,
+}
+
+fn default_grid() -> u32 {
+    4
+}
+
+fn default_tile_width() -> u32 {
+    320
+}
+
+fn default_show_timestamps() -> bool {
+    true
+}
+
+fn default_format() -> ImageFormat {
+    ImageFormat::Jpeg
+}
+
+impl Default for StoryboardOptions {
+    fn default() -> Self {
+        Self {
+            columns: default_grid(),
+            rows: default_grid(),
+            tile_width: default_tile_width(),
+            show_timestamps: default_show_timestamps(),
+            format: default_format(),
+        }
+    }
+}
+
+impl StoryboardOptions {
+    pub fn frame_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoryboardResult {
+    pub input_file: String,
+    pub output_file: String,
+    pub columns: u32,
+    pub rows: u32,
+    /// Source timestamp (in seconds) of each tile, in reading order
+    pub timestamps: Vec<f64>,
+    pub interval_seconds: f64,
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    pub command: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct StoryboardCommand {
+    input: PathBuf,
+    output: PathBuf,
+    start_time: f64,
+    duration: f64,
+    options: StoryboardOptions,
+}
+
+impl StoryboardCommand {
+    pub fn new(input: impl AsRef<Path>, output: impl AsRef<Path>, start_time: f64, duration: f64, options: StoryboardOptions) -> Self {
+        Self {
+            input: input.as_ref().to_path_buf(),
+            output: output.as_ref().to_path_buf(),
+            start_time,
+            duration,
+            options,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.options.columns == 0 || self.options.rows == 0 {
+            return Err(VideoClipError::InvalidOptions(
+                "storyboard needs at least one column and one row".to_string()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn interval(&self) -> f64 {
+        self.duration / self.options.frame_count() as f64
+    }
+
+    pub fn timestamps(&self) -> Vec<f64> {
+        let interval = self.interval();
+        (0..self.options.frame_count())
+            .map(|i| self.start_time + i as f64 * interval)
+            .collect()
+    }
+
+    fn filter_graph(&self) -> FilterGraph {
+        let mut graph = FilterGraph::new();
+        // One frame per interval yields exactly columns*rows evenly spaced frames
+        graph.push(Filter::new("fps").arg(format!("{}/{}", self.options.frame_count(), self.duration)));
+        graph.push(Filter::new("scale").arg(self.options.tile_width).arg(-2));
+
+        if self.options.show_timestamps {
+            // Input seeking resets pts to zero, so offset the label by the range start
+            graph.push(
+                Filter::new("drawtext")
+                    .option("text", format!("'%{{pts\\:hms\\:{}}}'", self.start_time))
+                    .option("x", 5)
+                    .option("y", "h-th-5")
+                    .option("fontsize", 16)
+                    .option("fontcolor", "white")
+                    .option("box", 1)
+                    .option("boxcolor", "black@0.6")
+                    .option("boxborderw", 3),
+            );
+        }
+
+        graph.push(
+            Filter::new("tile")
+                .arg(format!("{}x{}", self.options.columns, self.options.rows))
+                .option("padding", 4)
+                .option("margin", 4),
+        );
+        graph
+    }
+
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-ss".into(), self.start_time.to_string(),
+            "-i".into(), self.input.display().to_string(),
+            "-t".into(), self.duration.to_string(),
+            "-vf".into(), self.filter_graph().to_string(),
+            "-frames:v".into(), "1".into(),
+        ];
+
+        if self.options.format == ImageFormat::Jpeg {
+            args.extend(["-q:v".into(), "3".into()]);
+        }
+
+        args.extend(["-y".into(), self.output.display().to_string()]);
+        args
+    }
+
+    pub fn build_command(&self) -> Command {
+        crate::tools::ffmpeg_command(self.build_args())
+    }
+
+    pub fn get_command_string(&self) -> String {
+        format!("{} {}", crate::tools::ffmpeg(), self.build_args().join(" "))
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn execute(&self) -> Result<()> {
+        self.validate()?;
+        crate::ffmpeg::FFmpegCommand::run(self.build_command(), "Storyboard generation failed")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod command_building_tests {
+        use super::*;
+
+        #[test]
+        fn test_default_storyboard_filter_chain() {
+            let cmd = StoryboardCommand::new("input.mp4", "board.jpg", 60.0, 120.0, StoryboardOptions::default());
+
+            let cmd_string = cmd.get_command_string();
+            assert!(cmd_string.starts_with("ffmpeg -ss 60 -i input.mp4 -t 120"));
+            assert!(cmd_string.contains("fps=16/120,scale=320:-2,drawtext="));
+            assert!(cmd_string.contains("%{pts\\:hms\\:60}"));
+            assert!(cmd_string.contains("tile=4x4:padding=4:margin=4"));
+            assert!(cmd_string.contains("-frames:v 1"));
+            assert!(cmd_string.contains("-q:v 3"));
+            assert!(cmd_string.ends_with("-y board.jpg"));
+        }
+
+        #[test]
+        fn test_storyboard_without_timestamps() {
+            let options = StoryboardOptions {
+                columns: 3,
+                rows: 2,
+                show_timestamps: false,
+                format: ImageFormat::Png,
+                ..Default::default()
+            };
+            let cmd = StoryboardCommand::new("input.mp4", "board.png", 0.0, 60.0, options);
+
+            let cmd_string = cmd.get_command_string();
+            assert!(cmd_string.contains("-vf fps=6/60,scale=320:-2,tile=3x2"));
+            assert!(!cmd_string.contains("drawtext"));
+            assert!(!cmd_string.contains("-q:v"));
+        }
+    }
+
+    mod timestamp_tests {
+        use super::*;
+
+        #[test]
+        fn test_evenly_spaced_timestamps() {
+            let options = StoryboardOptions { columns: 2, rows: 2, ..Default::default() };
+            let cmd = StoryboardCommand::new("input.mp4", "board.jpg", 10.0, 40.0, options);
+
+            assert_eq!(cmd.interval(), 10.0);
+            assert_eq!(cmd.timestamps(), vec![10.0, 20.0, 30.0, 40.0]);
+        }
+
+        #[test]
+        fn test_empty_grid_is_rejected() {
+            let options = StoryboardOptions { columns: 0, ..Default::default() };
+            let cmd = StoryboardCommand::new("input.mp4", "board.jpg", 0.0, 10.0, options);
+            assert!(matches!(cmd.validate(), Err(VideoClipError::InvalidOptions(_))));
+        }
+    }
+
+    mod serialization_tests {
+        use super::*;
+
+        #[test]
+        fn test_options_deserialize_with_defaults() {
+            let options: StoryboardOptions = serde_json::from_str(r#"{"columns": 5}"#).unwrap();
+            assert_eq!(options.columns, 5);
+            assert_eq!(options.rows, 4);
+            assert!(options.show_timestamps);
+            assert_eq!(options.format, ImageFormat::Jpeg);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::encoder::VideoCodec;
+use crate::error::{VideoClipError, Result};
+use crate::video_clipper::ClipRequest;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Streaming plans
+/// Files too big for ffmpeg.wasm's in-memory FS (4GB+ recordings) can still be
+/// clipped in the browser if only the bytes around the range are ever loaded.
+/// A plan lists the steps for the host: write a byte-range slice of the file
+/// into the FS, run FFmpeg on it, delete what's done with, and finally read the
+/// output. Slices overlap by a margin and each one only keeps its share of the
+/// range by timestamp (`-copyts`), so the pieces join back into one continuous
+/// stream. Byte offsets are estimated from the average bitrate; only MPEG
+/// transport and program streams can be read from an arbitrary offset.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamSource {
+    /// File name, used for its extension and in the slices' names
+    pub name: String,
+    pub size_bytes: u64,
+    /// Seconds, e.g. from probing the first slice or a `<video>` element
+    pub duration: f64,
+    /// First timestamp of the file; transport streams rarely start at 0
+    #[serde(default)]
+    pub start_time: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamingOptions {
+    /// Largest slice written to the FS at once
+    #[serde(default = "default_max_slice_mb")]
+    pub max_slice_mb: u64,
+    /// Extra seconds read on each side of a slice's share of the range, to
+    /// absorb bitrate swings; raise it for very variable sources
+    #[serde(default = "default_margin_seconds")]
+    pub margin_seconds: f64,
+}
+
+fn default_max_slice_mb() -> u64 {
+    256
+}
+
+fn default_margin_seconds() -> f64 {
+    10.0
+}
+
+impl Default for StreamingOptions {
+    fn default() -> Self {
+        Self {
+            max_slice_mb: default_max_slice_mb(),
+            margin_seconds: default_margin_seconds(),
+        }
+    }
+}
+
+impl StreamingOptions {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_slice_mb == 0 {
+            return Err(VideoClipError::InvalidOptions("slices must be at least 1 MB".to_string()));
+        }
+        if !(self.margin_seconds.is_finite() && self.margin_seconds >= 0.0) {
+            return Err(VideoClipError::InvalidOptions(format!("slice margin can't be negative, got {}", self.margin_seconds)));
+        }
+        Ok(())
+    }
+}
+
+/// One thing for the host to do, in order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StreamStep {
+    /// Write `file.slice(offset, offset + length)` of the source to `file`
+    WriteSlice { file: String, offset: u64, length: u64 },
+    /// Run FFmpeg with `args` (ffmpeg.wasm's `exec` takes them as they are)
+    Run { args: Vec<String> },
+    /// Remove `file` from the FS to free its memory
+    Delete { file: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamPlan {
+    pub output_file: String,
+    pub steps: Vec<StreamStep>,
+    /// Number of slices read
+    pub slices: usize,
+    /// Most source bytes in the FS at any one time
+    pub peak_slice_bytes: u64,
+}
+
+/// Packet size slices are aligned to, for containers that can be read from
+/// any offset
+fn packet_size(name: &str) -> Option<u64> {
+    let ext = Path::new(name).extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "ts" | "m2t" => Some(188),
+        // BDAV adds a 4-byte timecode to each packet
+        "m2ts" | "mts" => Some(192),
+        "mpg" | "mpeg" | "vob" => Some(2048),
+        _ => None,
+    }
+}
+
+fn seconds_arg(seconds: f64) -> String {
+    format!("{:.3}", seconds)
+}
+
+impl StreamPlan {
+    /// Plans `request` (a stream copy) over `source`, writing `output_file`
+    pub fn new(source: &StreamSource, request: &ClipRequest, output_file: &str, options: &StreamingOptions) -> Result<Self> {
+        options.validate()?;
+        if request.effective_video_codec() != VideoCodec::Copy || request.needs_filtering() {
+            return Err(VideoClipError::InvalidOptions("streaming plans only stream copy; re-encode the clip afterwards".to_string()));
+        }
+        let Some(packet) = packet_size(&source.name) else {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "{} can't be read in slices; only MPEG-TS and MPEG-PS files (.ts, .m2ts, .mpg, ...) can",
+                source.name
+            )));
+        };
+        if source.size_bytes == 0 || !(source.duration.is_finite() && source.duration > 0.0) {
+            return Err(VideoClipError::InvalidOptions(format!("{} needs a size and duration to be sliced", source.name)));
+        }
+
+        let range = request.time_range()?.pad(request.pre_roll, request.post_roll, Some(source.duration));
+        if range.start >= source.duration {
+            return Err(VideoClipError::InvalidTimeRange { start: range.start, end: range.end });
+        }
+        let end = range.end.min(source.duration);
+
+        let bytes_per_second = source.size_bytes as f64 / source.duration;
+        let max_slice = options.max_slice_mb as f64 * 1024.0 * 1024.0;
+        let capacity = max_slice - 2.0 * options.margin_seconds * bytes_per_second - 2.0 * packet as f64;
+        if capacity <= 0.0 {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "{} MB slices can't hold {}s of margin at this bitrate",
+                options.max_slice_mb, options.margin_seconds
+            )));
+        }
+        let count = (((end - range.start) * bytes_per_second) / capacity).ceil().max(1.0) as usize;
+        let share = (end - range.start) / count as f64;
+
+        let byte_at = |seconds: f64| (seconds.clamp(0.0, source.duration) * bytes_per_second) as u64;
+        let mut steps = Vec::new();
+        let mut parts = Vec::new();
+        let mut peak_slice_bytes = 0;
+        for i in 0..count {
+            let from = range.start + share * i as f64;
+            let to = if i + 1 == count { end } else { from + share };
+            let offset = byte_at(from - options.margin_seconds) / packet * packet;
+            let slice_end = byte_at(to + options.margin_seconds).div_ceil(packet) * packet;
+            let length = slice_end.min(source.size_bytes) - offset;
+            peak_slice_bytes = peak_slice_bytes.max(length);
+
+            let slice = format!("{}.slice_{:03}{}", output_file, i, extension(&source.name));
+            let (target, format) = if count == 1 {
+                (output_file.to_string(), None)
+            } else {
+                (format!("{}.part_{:03}.ts", output_file, i), Some("mpegts"))
+            };
+            // Timestamps are kept, so these are positions in the source itself
+            let mut args: Vec<String> = vec![
+                "-hide_banner".into(), "-copyts".into(), "-i".into(), slice.clone(),
+                "-ss".into(), seconds_arg(source.start_time + from),
+                "-to".into(), seconds_arg(source.start_time + to),
+                "-c".into(), "copy".into(),
+            ];
+            match format {
+                Some(format) => args.extend(["-f".into(), format.into()]),
+                None => args.extend(["-avoid_negative_ts".into(), "make_zero".into()]),
+            }
+            args.extend(["-y".into(), target.clone()]);
+
+            steps.push(StreamStep::WriteSlice { file: slice.clone(), offset, length });
+            steps.push(StreamStep::Run { args });
+            steps.push(StreamStep::Delete { file: slice });
+            if count > 1 {
+                parts.push(target);
+            }
+        }
+
+        if !parts.is_empty() {
+            // The parts are byte-for-byte pieces of one transport stream, so
+            // the concat protocol joins them without re-cutting at keyframes
+            steps.push(StreamStep::Run {
+                args: vec![
+                    "-hide_banner".into(), "-i".into(), format!("concat:{}", parts.join("|")),
+                    "-c".into(), "copy".into(), "-avoid_negative_ts".into(), "make_zero".into(),
+                    "-y".into(), output_file.to_string(),
+                ],
+            });
+            steps.extend(parts.into_iter().map(|file| StreamStep::Delete { file }));
+        }
+
+        Ok(Self {
+            output_file: output_file.to_string(),
+            steps,
+            slices: count,
+            peak_slice_bytes,
+        })
+    }
+}
+
+/// `.ts` from `recording.ts`, so FFmpeg probes slices as the right format
+fn extension(name: &str) -> String {
+    Path::new(name).extension().map_or_else(String::new, |ext| format!(".{}", ext.to_string_lossy()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn source() -> StreamSource {
+        // 10 MB per second for an hour
+        StreamSource { name: "game.ts".to_string(), size_bytes: 36_000 * MB, duration: 3600.0, start_time: 1.4 }
+    }
+
+    fn request(start: &str, end: &str) -> ClipRequest {
+        ClipRequest {
+            input_file: "game.ts".to_string(),
+            start_time: start.to_string(),
+            end_time: end.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_short_clip_is_one_slice() {
+        let plan = StreamPlan::new(&source(), &request("30:00", "30:05"), "clip.mp4", &StreamingOptions::default()).unwrap();
+        assert_eq!(plan.slices, 1);
+        let StreamStep::WriteSlice { file, offset, length } = &plan.steps[0] else { panic!("{:?}", plan.steps[0]) };
+        assert_eq!(file, "clip.mp4.slice_000.ts");
+        assert_eq!(offset % 188, 0);
+        assert_eq!(*offset, 1790 * 10 * MB / 188 * 188);
+        assert!(*length >= 25 * 10 * MB && *length < 26 * 10 * MB);
+
+        let StreamStep::Run { args } = &plan.steps[1] else { panic!("{:?}", plan.steps[1]) };
+        assert_eq!(args.join(" "), "-hide_banner -copyts -i clip.mp4.slice_000.ts -ss 1801.400 -to 1806.400 -c copy -avoid_negative_ts make_zero -y clip.mp4");
+        assert_eq!(plan.steps[2], StreamStep::Delete { file: "clip.mp4.slice_000.ts".to_string() });
+        assert_eq!(plan.steps.len(), 3);
+    }
+
+    #[test]
+    fn test_long_clip_is_sliced_and_joined() {
+        let options = StreamingOptions { max_slice_mb: 512, margin_seconds: 5.0 };
+        let plan = StreamPlan::new(&source(), &request("0:00", "2:00"), "clip.mp4", &options).unwrap();
+        // 1200 MB of range in slices holding at most ~412 MB of it
+        assert_eq!(plan.slices, 3);
+        assert!(plan.peak_slice_bytes <= 512 * MB);
+
+        let runs: Vec<_> = plan.steps.iter().filter_map(|step| match step {
+            StreamStep::Run { args } => Some(args.join(" ")),
+            _ => None,
+        }).collect();
+        assert!(runs[1].contains("-ss 41.400 -to 81.400 -c copy -f mpegts -y clip.mp4.part_001.ts"));
+        assert_eq!(
+            runs[3],
+            "-hide_banner -i concat:clip.mp4.part_000.ts|clip.mp4.part_001.ts|clip.mp4.part_002.ts -c copy -avoid_negative_ts make_zero -y clip.mp4"
+        );
+        assert!(matches!(plan.steps.last(), Some(StreamStep::Delete { file }) if file == "clip.mp4.part_002.ts"));
+    }
+
+    #[test]
+    fn test_unsliceable_requests() {
+        let options = StreamingOptions::default();
+        let mp4 = StreamSource { name: "game.mp4".to_string(), ..source() };
+        assert!(StreamPlan::new(&mp4, &request("0:00", "0:10"), "clip.mp4", &options).is_err());
+
+        let encode = ClipRequest { video_codec: VideoCodec::H264, ..request("0:00", "0:10") };
+        assert!(StreamPlan::new(&source(), &encode, "clip.mp4", &options).is_err());
+        assert!(StreamPlan::new(&source(), &request("2:00:00", "2:00:10"), "clip.mp4", &options).is_err());
+
+        let tiny = StreamingOptions { max_slice_mb: 64, margin_seconds: 10.0 };
+        assert!(StreamPlan::new(&source(), &request("0:00", "0:10"), "clip.mp4", &tiny).is_err());
+    }
+}
@@ -101,35 +101,33 @@ impl TimeParser {
                 }
                 
                 // Single unit: check for simple vs complex
-                if time_str.ends_with('s') {
-                    let num_str = &time_str[..time_str.len()-1];
-                    if num_str.chars().all(|c| c.is_ascii_digit() || c == '.') {
-                        return num_str.parse::<f64>()
-                            .map_err(|_| VideoClipError::InvalidTimeFormat(time_str.to_string()));
+                let is_plain_number = |num_str: &str| num_str.chars().all(|c| c.is_ascii_digit() || c == '.');
+                if let Some(num_str) = time_str.strip_suffix('s') {
+                    if is_plain_number(num_str) {
+                        num_str.parse::<f64>()
+                            .map_err(|_| VideoClipError::InvalidTimeFormat(time_str.to_string()))
                     } else {
-                        return Self::parse_complex_format(time_str);
+                        Self::parse_complex_format(time_str)
                     }
-                } else if time_str.ends_with('m') {
-                    let num_str = &time_str[..time_str.len()-1];
-                    if num_str.chars().all(|c| c.is_ascii_digit() || c == '.') {
+                } else if let Some(num_str) = time_str.strip_suffix('m') {
+                    if is_plain_number(num_str) {
                         let minutes = num_str.parse::<f64>()
                             .map_err(|_| VideoClipError::InvalidTimeFormat(time_str.to_string()))?;
-                        return Ok(minutes * 60.0);
+                        Ok(minutes * 60.0)
                     } else {
-                        return Self::parse_complex_format(time_str);
+                        Self::parse_complex_format(time_str)
                     }
-                } else if time_str.ends_with('h') {
-                    let num_str = &time_str[..time_str.len()-1];
-                    if num_str.chars().all(|c| c.is_ascii_digit() || c == '.') {
+                } else if let Some(num_str) = time_str.strip_suffix('h') {
+                    if is_plain_number(num_str) {
                         let hours = num_str.parse::<f64>()
                             .map_err(|_| VideoClipError::InvalidTimeFormat(time_str.to_string()))?;
-                        return Ok(hours * 3600.0);
+                        Ok(hours * 3600.0)
                     } else {
-                        return Self::parse_complex_format(time_str);
+                        Self::parse_complex_format(time_str)
                     }
                 } else {
                     // Contains units but doesn't end with one, must be complex
-                    return Self::parse_complex_format(time_str);
+                    Self::parse_complex_format(time_str)
                 }
             } else {
                 // Try to parse as seconds
@@ -139,6 +137,60 @@ impl TimeParser {
         }
     }
     
+    /// Spoken durations and times: `"36 minutes 7 seconds"`, `"an hour and
+    /// a half"`, `"ninety seconds"`, or `"1 hour 30"` (a trailing bare number
+    /// counts in the next smaller unit). Anything else goes to
+    /// [`TimeParser::parse_to_seconds`], so `"37:19"` and `"2m30s"` work too.
+    pub fn parse_spoken(time_str: &str) -> Result<f64> {
+        let lower = time_str.trim().to_lowercase();
+        let words: Vec<&str> = lower.split(|c: char| c.is_whitespace() || c == ',' || c == '-').filter(|w| !w.is_empty()).collect();
+        if !words.iter().any(|w| spoken_unit(w).is_some()) {
+            return Self::parse_to_seconds(time_str);
+        }
+        let invalid = || VideoClipError::InvalidTimeFormat(time_str.to_string());
+
+        let mut total = 0.0;
+        let mut last_unit: Option<f64> = None;
+        // The number waiting for its unit, and whether it was just "a"/"an"
+        let mut pending: Option<(f64, bool)> = None;
+        for word in words {
+            if let Some(unit) = spoken_unit(word) {
+                let (count, _) = pending.take().unwrap_or((1.0, true));
+                if last_unit.is_some_and(|last| unit >= last) {
+                    return Err(invalid());
+                }
+                total += count * unit;
+                last_unit = Some(unit);
+            } else if word == "and" {
+                continue;
+            } else if word == "a" || word == "an" {
+                pending = pending.or(Some((1.0, true)));
+            } else if word == "half" {
+                pending = Some(match pending {
+                    Some((count, false)) => (count + 0.5, false),
+                    _ => (0.5, false),
+                });
+            } else if let Some(number) = spoken_number(word) {
+                pending = Some(match pending {
+                    // "forty five"
+                    Some((tens, false)) if tens >= 20.0 && tens % 10.0 == 0.0 && number < 10.0 => (tens + number, false),
+                    None | Some((_, true)) => (number, false),
+                    Some(_) => return Err(invalid()),
+                });
+            } else {
+                return Self::parse_to_seconds(time_str);
+            }
+        }
+
+        match (pending, last_unit) {
+            (None, _) => Ok(total),
+            // "a minute and a half"
+            (Some((0.5, _)), Some(unit)) => Ok(total + 0.5 * unit),
+            (Some((count, false)), Some(unit)) if unit > 1.0 => Ok(total + count * unit / 60.0),
+            _ => Err(invalid()),
+        }
+    }
+
     pub fn format_time(seconds: f64) -> String {
         let mins = (seconds / 60.0) as u32;
         let secs = (seconds % 60.0) as u32;
@@ -168,6 +220,29 @@ impl TimeParser {
     }
 }
 
+/// Seconds in a spoken unit
+fn spoken_unit(word: &str) -> Option<f64> {
+    match word {
+        "hour" | "hours" | "hr" | "hrs" => Some(3600.0),
+        "minute" | "minutes" | "min" | "mins" => Some(60.0),
+        "second" | "seconds" | "sec" | "secs" => Some(1.0),
+        _ => None,
+    }
+}
+
+fn spoken_number(word: &str) -> Option<f64> {
+    if word.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return word.parse().ok();
+    }
+    const ONES: [&str; 20] = [
+        "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+        "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+    ];
+    const TENS: [&str; 8] = ["twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+    ONES.iter().position(|&n| n == word).map(|n| n as f64)
+        .or_else(|| TENS.iter().position(|&n| n == word).map(|n| (n as f64 + 2.0) * 10.0))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -252,6 +327,23 @@ mod tests {
             assert_eq!(TimeParser::parse_to_seconds("30m45").unwrap(), 1845.0);
             assert!(TimeParser::parse_to_seconds("h30m").is_err());
         }
+
+        #[test]
+        fn test_parse_spoken() {
+            assert_eq!(TimeParser::parse_spoken("36 minutes 7 seconds").unwrap(), 2167.0);
+            assert_eq!(TimeParser::parse_spoken("36 minutes and 7").unwrap(), 2167.0);
+            assert_eq!(TimeParser::parse_spoken("ninety seconds").unwrap(), 90.0);
+            assert_eq!(TimeParser::parse_spoken("forty-five secs").unwrap(), 45.0);
+            assert_eq!(TimeParser::parse_spoken("a minute and a half").unwrap(), 90.0);
+            assert_eq!(TimeParser::parse_spoken("one and a half minutes").unwrap(), 90.0);
+            assert_eq!(TimeParser::parse_spoken("half an hour").unwrap(), 1800.0);
+            assert_eq!(TimeParser::parse_spoken("1 hour 30").unwrap(), 5400.0);
+            assert_eq!(TimeParser::parse_spoken("37:19").unwrap(), 2239.0);
+            assert_eq!(TimeParser::parse_spoken("2m30s").unwrap(), 150.0);
+            assert!(TimeParser::parse_spoken("7 seconds 36 minutes").is_err());
+            assert!(TimeParser::parse_spoken("a few minutes").is_err());
+            assert!(TimeParser::parse_spoken("5 minutes a").is_err());
+        }
     }
     
     mod format_time_tests {
@@ -0,0 +1,219 @@
+use crate::container::ContainerExecutor;
+use crate::ssh::SshExecutor;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// FFmpeg binaries
+/// Every command runs `ffmpeg` and `ffprobe` from the PATH unless a path to
+/// the ffmpeg binary is set, e.g. from `VIDEO_CLIP_FFMPEG_PATH` or the config
+/// file's `ffmpeg_path` (see [`crate::config::Config`]). ffprobe is taken from
+/// the same directory. With an [`Executor`] set they run in a container or
+/// on another host instead. Both settings are process-wide, like the probe
+/// cache's, though a batch worker's thread can run with its own executor.
+static FFMPEG_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+static EXECUTOR: RwLock<Option<Executor>> = RwLock::new(None);
+
+/// Set on commands that run in a container or on another host to the run's
+/// name, so cancelling one can stop the far side too (see [`stop_command`])
+const RUN_NAME_VAR: &str = "VIDEO_CLIP_RUN";
+
+static RUNS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Overrides `EXECUTOR` on this thread when set; `Some(None)` runs here
+    static THREAD_EXECUTOR: RefCell<Option<Option<Executor>>> = const { RefCell::new(None) };
+}
+
+/// Where ffmpeg and ffprobe run, when not on this host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Executor {
+    Container(ContainerExecutor),
+    Ssh(SshExecutor),
+}
+
+/// Runs FFmpeg from `path` instead of the PATH; `None` goes back to the PATH
+pub fn set_ffmpeg_path(path: Option<PathBuf>) {
+    *FFMPEG_PATH.write().unwrap_or_else(|e| e.into_inner()) = path;
+}
+
+pub fn ffmpeg_path() -> Option<PathBuf> {
+    FFMPEG_PATH.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Runs ffmpeg and ffprobe with `executor`; `None` runs them on this host
+pub fn set_executor(executor: Option<Executor>) {
+    *EXECUTOR.write().unwrap_or_else(|e| e.into_inner()) = executor;
+}
+
+/// Runs `f` with this thread's ffmpeg and ffprobe going through `executor`
+/// instead of the process-wide one (see [`crate::dispatch`])
+pub fn with_executor<T>(executor: Option<Executor>, f: impl FnOnce() -> T) -> T {
+    let previous = THREAD_EXECUTOR.with(|cell| cell.replace(Some(executor)));
+    let outcome = f();
+    THREAD_EXECUTOR.with(|cell| *cell.borrow_mut() = previous);
+    outcome
+}
+
+pub fn executor() -> Option<Executor> {
+    THREAD_EXECUTOR.with(|cell| cell.borrow().clone())
+        .unwrap_or_else(|| EXECUTOR.read().unwrap_or_else(|e| e.into_inner()).clone())
+}
+
+/// The SSH executor, when commands run on another host and the paths they
+/// name are that host's
+pub fn remote_host() -> Option<SshExecutor> {
+    match executor() {
+        Some(Executor::Ssh(ssh)) => Some(ssh),
+        _ => None,
+    }
+}
+
+/// The ffmpeg program to run (the executor's own, when there is one)
+pub fn ffmpeg() -> String {
+    match (executor(), ffmpeg_path()) {
+        (None, Some(path)) => path.display().to_string(),
+        _ => "ffmpeg".to_string(),
+    }
+}
+
+/// The ffprobe program to run: next to the configured ffmpeg, keeping any
+/// prefix, suffix or extension of its name (`ffmpeg-6.exe` → `ffprobe-6.exe`)
+pub fn ffprobe() -> String {
+    match (executor(), ffmpeg_path()) {
+        (None, Some(path)) => sibling_ffprobe(&path).display().to_string(),
+        _ => "ffprobe".to_string(),
+    }
+}
+
+/// The program and arguments that run `program args...`: as they are, or
+/// through the container runtime or ssh as the run called `name`
+pub fn invocation(name: &str, program: &str, args: Vec<String>) -> (String, Vec<String>) {
+    match executor() {
+        Some(Executor::Container(container)) => (container.runtime.program().to_string(), container.run_args(name, program, args)),
+        Some(Executor::Ssh(ssh)) => ("ssh".to_string(), ssh.run_named_args(name, program, args)),
+        None => (program.to_string(), args),
+    }
+}
+
+pub fn command(program: &str, args: Vec<String>) -> Command {
+    command_with_prefix(Vec::new(), program, args)
+}
+
+/// `program args...` as [`command`] runs it, behind `prefix` (e.g. `nice`)
+pub fn command_with_prefix(prefix: Vec<String>, program: &str, args: Vec<String>) -> Command {
+    let name = run_name();
+    let (program, args) = invocation(&name, program, args);
+    let mut argv = prefix;
+    argv.push(program);
+    argv.extend(args);
+
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..]);
+    if executor().is_some() {
+        cmd.env(RUN_NAME_VAR, name);
+    }
+    cmd
+}
+
+/// The command that stops the run `command` started in a container or on
+/// another host; killing the runtime's client or ssh alone leaves it going
+#[cfg(not(feature = "wasm"))]
+pub(crate) fn stop_command(command: &Command) -> Option<Command> {
+    let (_, name) = command.get_envs().find(|(key, _)| *key == RUN_NAME_VAR)?;
+    let name = name?.to_str()?;
+    let (program, args) = match executor()? {
+        Executor::Container(container) => (container.runtime.program().to_string(), container.stop_args(name)),
+        Executor::Ssh(ssh) => ("ssh".to_string(), ssh.stop_args(name)),
+    };
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    Some(cmd)
+}
+
+/// A name for a remote run that other clients of the same host or Docker
+/// daemon won't pick
+fn run_name() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.subsec_nanos());
+    format!("video-clip-{}-{}-{:08x}", std::process::id(), RUNS.fetch_add(1, Ordering::Relaxed), nanos)
+}
+
+pub fn ffmpeg_command(args: Vec<String>) -> Command {
+    command(&ffmpeg(), args)
+}
+
+pub fn ffprobe_command(args: Vec<String>) -> Command {
+    command(&ffprobe(), args)
+}
+
+fn sibling_ffprobe(ffmpeg: &Path) -> PathBuf {
+    let name = ffmpeg.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    match name.contains("ffmpeg") {
+        true => ffmpeg.with_file_name(name.replacen("ffmpeg", "ffprobe", 1)),
+        false => ffmpeg.with_file_name("ffprobe"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_executor_overrides_the_process_one() {
+        let ssh = Executor::Ssh(SshExecutor::new("encode-01"));
+        with_executor(Some(ssh.clone()), || {
+            assert_eq!(executor(), Some(ssh));
+            assert_eq!(invocation("run", "ffmpeg", vec!["-version".to_string()]).0, "ssh");
+            with_executor(None, || assert_eq!(invocation("run", "ffmpeg", Vec::new()).0, "ffmpeg"));
+            assert!(remote_host().is_some());
+        });
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    #[test]
+    fn test_cancelling_a_container_run_kills_the_container() {
+        use crate::container::ContainerRuntime;
+
+        let container = Executor::Container(ContainerExecutor::new(ContainerRuntime::Podman, "ffmpeg:7"));
+        with_executor(Some(container), || {
+            let cmd = command("ffmpeg", vec!["-version".to_string()]);
+            let args: Vec<String> = cmd.get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+            let name = &args[args.iter().position(|arg| arg == "--name").unwrap() + 1];
+            assert!(args.contains(&"--init".to_string()));
+
+            let stop = stop_command(&cmd).unwrap();
+            assert_eq!(stop.get_program(), "podman");
+            assert_eq!(stop.get_args().collect::<Vec<_>>(), ["kill", name.as_str()]);
+            assert_ne!(name, &run_name());
+        });
+        assert!(stop_command(&command("ffmpeg", Vec::new())).is_none());
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    #[test]
+    fn test_cancelling_an_ssh_run_kills_the_remote_process() {
+        with_executor(Some(Executor::Ssh(SshExecutor::new("encode-01"))), || {
+            let cmd = command("ffmpeg", vec!["-version".to_string()]);
+            let (_, name) = cmd.get_envs().find(|(key, _)| *key == RUN_NAME_VAR).unwrap();
+            let pid_file = format!("\"${{XDG_RUNTIME_DIR:-$HOME}}\"/.{}.pid", name.unwrap().to_string_lossy());
+            let script = cmd.get_args().last().unwrap().to_string_lossy().to_string();
+            assert!(script.starts_with("ffmpeg -version & echo $! > "), "{}", script);
+            assert!(script.contains(&pid_file));
+
+            let stop = stop_command(&cmd).unwrap();
+            assert_eq!(stop.get_program(), "ssh");
+            assert!(stop.get_args().last().unwrap().to_string_lossy().contains(&format!("kill $(cat {})", pid_file)));
+        });
+    }
+
+    #[test]
+    fn test_ffprobe_next_to_ffmpeg() {
+        assert_eq!(sibling_ffprobe(Path::new("/opt/ffmpeg/bin/ffmpeg")), Path::new("/opt/ffmpeg/bin/ffprobe"));
+        assert_eq!(sibling_ffprobe(Path::new("C:/tools/ffmpeg-6.exe")), Path::new("C:/tools/ffprobe-6.exe"));
+        assert_eq!(sibling_ffprobe(Path::new("/usr/local/bin/avconv")), Path::new("/usr/local/bin/ffprobe"));
+    }
+}
@@ -0,0 +1,322 @@
+use crate::error::{VideoClipError, Result};
+use crate::ranges::{self, TimeRange};
+use crate::video_clipper::{ClipRequest, ClipResult};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Transcript-driven clipping
+/// Reads SRT and WebVTT subtitle files so a range can be picked by what's
+/// said ("clip where the speaker says X") or by cue numbers instead of by
+/// timestamps. A clip can also take the cues that fall inside it, re-timed to
+/// start at zero, as a subtitle file next to it.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cue {
+    /// 1-based position in the file
+    pub index: usize,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub format: SubtitleFormat,
+    pub cues: Vec<Cue>,
+}
+
+/// What to clip from a transcript
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranscriptQuery {
+    /// Every place the words are said, ignoring case, punctuation and line breaks
+    Text(String),
+    /// Cues `first` to `last`, 1-based and inclusive
+    Cues { first: usize, last: usize },
+}
+
+impl TranscriptQuery {
+    /// `3-7` or `3` for a cue range
+    pub fn parse_cues(s: &str) -> Result<Self> {
+        let invalid = || VideoClipError::InvalidOptions(format!("cue range '{}' should be FIRST-LAST, e.g. 3-7", s));
+        let (first, last) = s.split_once('-').unwrap_or((s, s));
+        let first: usize = first.trim().parse().map_err(|_| invalid())?;
+        let last: usize = last.trim().parse().map_err(|_| invalid())?;
+        if first == 0 || last < first {
+            return Err(invalid());
+        }
+        Ok(TranscriptQuery::Cues { first, last })
+    }
+}
+
+impl FromStr for Transcript {
+    type Err = VideoClipError;
+
+    /// SRT or WebVTT; cues without a readable timing line are skipped
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim_start_matches('\u{feff}');
+        let format = if s.trim_start().starts_with("WEBVTT") { SubtitleFormat::Vtt } else { SubtitleFormat::Srt };
+        let mut cues = Vec::new();
+        for block in s.replace("\r\n", "\n").split("\n\n") {
+            let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+            let Some(timing) = lines.next() else { continue };
+            let Some((start, end)) = timing.split_once("-->") else { continue };
+            // WebVTT puts cue settings after the end time
+            let end = end.split_whitespace().next().unwrap_or_default();
+            let (Some(start), Some(end)) = (parse_timestamp(start.trim()), parse_timestamp(end)) else { continue };
+            let text: Vec<&str> = lines.collect();
+            cues.push(Cue { index: cues.len() + 1, start, end, text: text.join("\n") });
+        }
+        if cues.is_empty() {
+            return Err(VideoClipError::InvalidOptions("no subtitle cues found".to_string()));
+        }
+        Ok(Self { format, cues })
+    }
+}
+
+impl Transcript {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(VideoClipError::FileNotFound(path.display().to_string()));
+        }
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// The ranges `query` picks, merged where they touch; empty when nothing matches
+    pub fn find(&self, query: &TranscriptQuery) -> Result<Vec<TimeRange>> {
+        let ranges = match query {
+            TranscriptQuery::Text(text) => self.search(text),
+            TranscriptQuery::Cues { first, last } => {
+                let cues = self.cues.get(first.saturating_sub(1)..*last).filter(|cues| !cues.is_empty()).ok_or_else(|| VideoClipError::InvalidOptions(format!(
+                    "cues {}-{} are out of range; the transcript has {}",
+                    first, last, self.cues.len()
+                )))?;
+                vec![TimeRange { start: cues[0].start, end: cues[cues.len() - 1].end }]
+            }
+        };
+        Ok(ranges::merge(&ranges))
+    }
+
+    /// Cues containing `text`, or pairs of consecutive cues it's split across
+    fn search(&self, text: &str) -> Vec<TimeRange> {
+        let needle = normalize(text);
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        let texts: Vec<String> = self.cues.iter().map(|cue| normalize(&cue.text)).collect();
+        let mut ranges = Vec::new();
+        for (i, cue) in self.cues.iter().enumerate() {
+            if texts[i].contains(&needle) {
+                ranges.push(TimeRange { start: cue.start, end: cue.end });
+            } else if let Some(next) = self.cues.get(i + 1) {
+                if !texts[i + 1].contains(&needle) && format!("{} {}", texts[i], texts[i + 1]).contains(&needle) {
+                    ranges.push(TimeRange { start: cue.start, end: next.end });
+                }
+            }
+        }
+        ranges
+    }
+
+    /// The cues overlapping `range`, as they are
+    pub fn within(&self, range: &TimeRange) -> Self {
+        let cues = self.cues.iter()
+            .filter(|cue| TimeRange { start: cue.start, end: cue.end }.overlaps(range))
+            .cloned()
+            .collect();
+        Self { format: self.format, cues }
+    }
+
+    /// The cues overlapping `range`, cut to it and shifted to start at zero
+    pub fn retimed(&self, range: &TimeRange) -> Self {
+        let cues = self.cues.iter()
+            .filter_map(|cue| TimeRange { start: cue.start, end: cue.end }.intersection(range).map(|kept| (cue, kept)))
+            .enumerate()
+            .map(|(i, (cue, kept))| Cue {
+                index: i + 1,
+                start: kept.start - range.start,
+                end: kept.end - range.start,
+                text: cue.text.clone(),
+            })
+            .collect();
+        Self { format: self.format, cues }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if self.format == SubtitleFormat::Vtt {
+            out.push_str("WEBVTT\n\n");
+        }
+        for cue in &self.cues {
+            if self.format == SubtitleFormat::Srt {
+                out.push_str(&format!("{}\n", cue.index));
+            }
+            out.push_str(&format!(
+                "{} --> {}\n{}\n\n",
+                format_timestamp(cue.start, self.format),
+                format_timestamp(cue.end, self.format),
+                cue.text
+            ));
+        }
+        out
+    }
+}
+
+/// One request per range `query` finds in `transcript`, cloned from
+/// `template`; fails when nothing matches
+pub fn requests_for(transcript: &Transcript, query: &TranscriptQuery, template: &ClipRequest) -> Result<Vec<ClipRequest>> {
+    let found = transcript.find(query)?;
+    if found.is_empty() {
+        let message = match query {
+            TranscriptQuery::Text(text) => format!("no cue says '{}'", text),
+            TranscriptQuery::Cues { .. } => "no cues selected".to_string(),
+        };
+        return Err(VideoClipError::InvalidOptions(message));
+    }
+    Ok(found.iter().map(|range| ClipRequest {
+        start_time: range.start.to_string(),
+        end_time: range.end.to_string(),
+        ..template.clone()
+    }).collect())
+}
+
+/// `clip.mp4` → `clip.srt` (or `.vtt`)
+pub fn subtitle_path(output: impl AsRef<Path>, format: SubtitleFormat) -> PathBuf {
+    output.as_ref().with_extension(format.extension())
+}
+
+/// Writes `transcript`'s cues, re-timed to the clip, next to every file
+/// `result` produced and returns their paths
+pub fn write_retimed(result: &ClipResult, transcript: &Transcript) -> Result<Vec<PathBuf>> {
+    let range = TimeRange { start: result.start_seconds, end: result.end_seconds };
+    let subtitles = transcript.retimed(&range).render();
+    let outputs: Vec<&str> = if result.renditions.is_empty() {
+        vec![result.output_file.as_str()]
+    } else {
+        result.renditions.iter().map(|r| r.output_file.as_str()).collect()
+    };
+    let mut paths = Vec::new();
+    for output in outputs {
+        let path = subtitle_path(output, transcript.format);
+        std::fs::write(&path, &subtitles)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// `00:01:02,500` (SRT), `00:01:02.500` or `01:02.500` (WebVTT)
+fn parse_timestamp(s: &str) -> Option<f64> {
+    let s = s.replace(',', ".");
+    let parts: Vec<&str> = s.split(':').collect();
+    let (hours, minutes, seconds) = match parts[..] {
+        [h, m, s] => (h.parse::<f64>().ok()?, m.parse::<f64>().ok()?, s.parse::<f64>().ok()?),
+        [m, s] => (0.0, m.parse::<f64>().ok()?, s.parse::<f64>().ok()?),
+        _ => return None,
+    };
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+fn format_timestamp(seconds: f64, format: SubtitleFormat) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    let separator = if format == SubtitleFormat::Srt { ',' } else { '.' };
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+/// Lowercase words without markup or punctuation, single-spaced
+fn normalize(text: &str) -> String {
+    let mut plain = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' | '{' => in_tag = true,
+            '>' | '}' => in_tag = false,
+            _ if in_tag => {}
+            c if c.is_alphanumeric() || c == '\'' => plain.extend(c.to_lowercase()),
+            _ => plain.push(' '),
+        }
+    }
+    plain.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRT: &str = "1\r\n00:00:01,000 --> 00:00:04,000\r\nWelcome back, everyone.\r\n\r\n\
+        2\r\n00:00:04,500 --> 00:00:07,250\r\nToday we're talking about\r\n<i>the new release</i>\r\n\r\n\
+        3\r\n00:00:08,000 --> 00:00:10,000\r\nand what it means\r\n\r\n\
+        4\r\n00:00:10,000 --> 00:00:12,000\r\nfor the team.\r\n";
+
+    const VTT: &str = "WEBVTT\n\nNOTE recorded live\n\nintro\n00:01.000 --> 00:04.000 align:start\nWelcome back\n\n00:00:05.000 --> 00:00:06.000\nThanks\n";
+
+    #[test]
+    fn test_parse_srt_and_vtt() {
+        let srt: Transcript = SRT.parse().unwrap();
+        assert_eq!(srt.format, SubtitleFormat::Srt);
+        assert_eq!(srt.cues.len(), 4);
+        assert_eq!((srt.cues[1].start, srt.cues[1].end), (4.5, 7.25));
+        assert_eq!(srt.cues[1].text, "Today we're talking about\n<i>the new release</i>");
+
+        let vtt: Transcript = VTT.parse().unwrap();
+        assert_eq!(vtt.format, SubtitleFormat::Vtt);
+        assert_eq!(vtt.cues.iter().map(|c| (c.start, c.end)).collect::<Vec<_>>(), vec![(1.0, 4.0), (5.0, 6.0)]);
+        assert!("just some notes".parse::<Transcript>().is_err());
+    }
+
+    #[test]
+    fn test_find_by_text_and_cues() {
+        let transcript: Transcript = SRT.parse().unwrap();
+        let find = |text: &str| transcript.find(&TranscriptQuery::Text(text.to_string())).unwrap();
+        assert_eq!(find("THE NEW RELEASE"), vec![TimeRange { start: 4.5, end: 7.25 }]);
+        // Split across cues 3 and 4, which touch and are merged
+        assert_eq!(find("what it means for the team"), vec![TimeRange { start: 8.0, end: 12.0 }]);
+        assert!(find("roadmap").is_empty());
+
+        let cues = TranscriptQuery::parse_cues("2-3").unwrap();
+        assert_eq!(transcript.find(&cues).unwrap(), vec![TimeRange { start: 4.5, end: 10.0 }]);
+        assert!(transcript.find(&TranscriptQuery::parse_cues("3-9").unwrap()).is_err());
+        assert!(TranscriptQuery::parse_cues("4-2").is_err());
+
+        let template = ClipRequest { input_file: "talk.mp4".to_string(), ..Default::default() };
+        let requests = requests_for(&transcript, &TranscriptQuery::Text("welcome".to_string()), &template).unwrap();
+        assert_eq!((requests[0].start_time.as_str(), requests[0].end_time.as_str()), ("1", "4"));
+        assert!(requests_for(&transcript, &TranscriptQuery::Text("roadmap".to_string()), &template).is_err());
+    }
+
+    #[test]
+    fn test_retimed_subtitles() {
+        let transcript: Transcript = SRT.parse().unwrap();
+        let clip = transcript.retimed(&TimeRange { start: 3.0, end: 9.0 });
+        assert_eq!(
+            clip.render(),
+            "1\n00:00:00,000 --> 00:00:01,000\nWelcome back, everyone.\n\n\
+             2\n00:00:01,500 --> 00:00:04,250\nToday we're talking about\n<i>the new release</i>\n\n\
+             3\n00:00:05,000 --> 00:00:06,000\nand what it means\n\n"
+        );
+        let vtt: Transcript = VTT.parse().unwrap();
+        assert!(vtt.retimed(&TimeRange { start: 0.5, end: 2.0 }).render().starts_with("WEBVTT\n\n00:00:00.500 --> 00:00:01.500\n"));
+    }
+}
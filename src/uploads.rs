@@ -0,0 +1,344 @@
+//! Source uploads
+//! Clients that don't share a filesystem with the server upload the source
+//! first and clip it by the `path` the upload reports. A file comes whole
+//! as the `file` field of a multipart form, or in pieces the way tus does
+//! it: one request announces the length, each later one appends at an
+//! offset, and after a dropped connection the client asks how much arrived
+//! and carries on from there. Files over the store's size limit are
+//! refused, and a SHA-256 given up front is checked once the file is
+//! complete; a file that doesn't match is discarded. Uploads, finished or
+//! not, are removed once they've gone unchanged for the store's time to
+//! live (a day by default), including ones an earlier run left behind.
+
+use crate::error::{VideoClipError, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Upload {
+    pub id: String,
+    pub file_name: String,
+    /// Total size, when announced up front
+    pub length: Option<u64>,
+    /// Bytes received so far
+    pub offset: u64,
+    /// Expected SHA-256, hex
+    pub sha256: Option<String>,
+    pub complete: bool,
+    /// Where the finished file is, for clip requests' `input_file`
+    pub path: String,
+}
+
+#[derive(Error, Debug)]
+pub enum UploadError {
+    #[error("no upload {0}")]
+    NotFound(String),
+    /// The file is, or would grow, larger than the limit
+    #[error("uploads are limited to {max_bytes} bytes")]
+    TooLarge { max_bytes: u64 },
+    /// A piece didn't start where the last one ended
+    #[error("the upload continues at offset {expected}")]
+    OffsetMismatch { expected: u64 },
+    #[error("SHA-256 {actual} doesn't match the expected {expected}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error(transparent)]
+    Invalid(#[from] VideoClipError),
+}
+
+impl From<std::io::Error> for UploadError {
+    fn from(e: std::io::Error) -> Self {
+        UploadError::Invalid(e.into())
+    }
+}
+
+pub type UploadResult<T> = std::result::Result<T, UploadError>;
+
+/// How long uploads are kept unless the store is told otherwise
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug)]
+pub struct UploadStore {
+    dir: PathBuf,
+    max_bytes: u64,
+    ttl: Duration,
+    uploads: Mutex<HashMap<String, Arc<Mutex<Upload>>>>,
+    next_id: AtomicU64,
+}
+
+impl UploadStore {
+    /// Keeps uploads under `dir`, refusing files over `max_bytes`
+    pub fn new(dir: impl AsRef<Path>, max_bytes: u64) -> Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            max_bytes,
+            ttl: DEFAULT_TTL,
+            uploads: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Where uploads are kept
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// How long an upload is kept after it last changed
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// Starts an upload of `file_name`; `length` is required for uploads
+    /// sent in pieces and `sha256` (hex) is checked when it completes
+    pub fn create(&self, file_name: &str, length: Option<u64>, sha256: Option<&str>) -> UploadResult<Upload> {
+        self.sweep();
+        let file_name = safe_file_name(file_name)?;
+        if length.is_some_and(|length| length > self.max_bytes) {
+            return Err(UploadError::TooLarge { max_bytes: self.max_bytes });
+        }
+        let sha256 = sha256.map(parse_sha256).transpose()?;
+
+        let id = self.new_id();
+        let upload = Upload {
+            path: self.dir.join(&id).join(&file_name).display().to_string(),
+            id,
+            file_name,
+            length,
+            offset: 0,
+            sha256,
+            complete: false,
+        };
+        File::create(self.part_path(&upload.id))?;
+        self.uploads.lock().unwrap().insert(upload.id.clone(), Arc::new(Mutex::new(upload.clone())));
+        if length == Some(0) {
+            return self.finish(&upload.id, None);
+        }
+        Ok(upload)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Upload> {
+        self.entry(id).ok().map(|upload| upload.lock().unwrap().clone())
+    }
+
+    /// Appends `bytes` at `offset`, finishing the upload once its announced
+    /// length has arrived
+    pub fn append(&self, id: &str, offset: u64, bytes: &[u8]) -> UploadResult<Upload> {
+        let entry = self.entry(id)?;
+        // Held while writing, so pieces sent at once can't both land at the same offset
+        let mut upload = entry.lock().unwrap();
+        if upload.complete || offset != upload.offset {
+            return Err(UploadError::OffsetMismatch { expected: upload.offset });
+        }
+        let end = offset + bytes.len() as u64;
+        let limit = upload.length.unwrap_or(self.max_bytes).min(self.max_bytes);
+        if end > limit {
+            self.discard(id)?;
+            return Err(UploadError::TooLarge { max_bytes: limit });
+        }
+
+        OpenOptions::new().append(true).open(self.part_path(id))?.write_all(bytes)?;
+        upload.offset = end;
+        match upload.length == Some(end) {
+            true => self.complete(&mut upload),
+            false => Ok(upload.clone()),
+        }
+    }
+
+    /// Finishes an upload sent without a length, checking it against
+    /// `sha256` (hex) when that's only known at the end
+    pub fn finish(&self, id: &str, sha256: Option<&str>) -> UploadResult<Upload> {
+        let entry = self.entry(id)?;
+        let mut upload = entry.lock().unwrap();
+        if let Some(sha256) = sha256 {
+            upload.sha256 = Some(parse_sha256(sha256)?);
+        }
+        self.complete(&mut upload)
+    }
+
+    /// Forgets an unfinished upload and removes what arrived of it
+    pub fn discard(&self, id: &str) -> UploadResult<()> {
+        self.uploads.lock().unwrap().remove(id).ok_or_else(|| UploadError::NotFound(id.to_string()))?;
+        let _ = fs::remove_file(self.part_path(id));
+        Ok(())
+    }
+
+    /// Removes the uploads that haven't changed for the time to live, and
+    /// forgets them
+    pub fn sweep(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut uploads = self.uploads.lock().unwrap();
+        for entry in entries.flatten() {
+            let age = entry.metadata().and_then(|metadata| metadata.modified()).ok().and_then(|modified| modified.elapsed().ok());
+            if age.is_none_or(|age| age <= self.ttl) {
+                continue;
+            }
+            // A finished upload is a directory named after its id, an unfinished one `<id>.part`
+            let name = entry.file_name().to_string_lossy().to_string();
+            uploads.remove(name.strip_suffix(".part").unwrap_or(&name));
+            let path = entry.path();
+            let removed = match path.is_dir() {
+                true => fs::remove_dir_all(&path),
+                false => fs::remove_file(&path),
+            };
+            if let Err(e) = removed {
+                log::warn!("Couldn't remove expired upload {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    fn entry(&self, id: &str) -> UploadResult<Arc<Mutex<Upload>>> {
+        self.uploads.lock().unwrap().get(id).cloned().ok_or_else(|| UploadError::NotFound(id.to_string()))
+    }
+
+    /// Checks the file against its checksum and moves it into place
+    fn complete(&self, upload: &mut Upload) -> UploadResult<Upload> {
+        if upload.complete {
+            return Ok(upload.clone());
+        }
+        if let Some(length) = upload.length.filter(|&length| length != upload.offset) {
+            return Err(VideoClipError::InvalidOptions(format!("{} of {} bytes have arrived", upload.offset, length)).into());
+        }
+        let part = self.part_path(&upload.id);
+        if let Some(expected) = &upload.sha256 {
+            let actual = sha256_file(&part)?;
+            if &actual != expected {
+                self.discard(&upload.id)?;
+                return Err(UploadError::ChecksumMismatch { expected: expected.clone(), actual });
+            }
+        }
+        let path = PathBuf::from(&upload.path);
+        fs::create_dir_all(path.parent().expect("uploads live in a directory of their own"))?;
+        fs::rename(&part, &path)?;
+        upload.complete = true;
+        upload.length = Some(upload.offset);
+        Ok(upload.clone())
+    }
+
+    fn part_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.part", id))
+    }
+
+    /// Hard to guess, so one client can't append to another's upload
+    fn new_id(&self) -> String {
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+        let seed = format!("{}:{}:{}", nanos, std::process::id(), self.next_id.fetch_add(1, Ordering::Relaxed));
+        hex::encode(&Sha256::digest(seed.as_bytes())[..12])
+    }
+}
+
+/// The hex SHA-256 from a tus `Upload-Checksum: sha256 <base64>` header
+pub fn checksum_header_sha256(value: &str) -> Result<String> {
+    let invalid = || VideoClipError::InvalidOptions(format!("unsupported checksum '{}' (expected sha256 <base64>)", value));
+    let (algorithm, digest) = value.trim().split_once(' ').ok_or_else(invalid)?;
+    if algorithm != "sha256" {
+        return Err(invalid());
+    }
+    STANDARD.decode(digest.trim()).map(hex::encode).map_err(|_| invalid())
+}
+
+fn parse_sha256(hex: &str) -> Result<String> {
+    let hex = hex.trim().to_ascii_lowercase();
+    match hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => Ok(hex),
+        false => Err(VideoClipError::InvalidOptions("sha256 must be 64 hex digits".to_string())),
+    }
+}
+
+/// Just the name, so an upload can't be written outside its directory
+fn safe_file_name(name: &str) -> Result<String> {
+    let name = Path::new(name.trim()).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    match name.is_empty() || name == ".." {
+        true => Err(VideoClipError::InvalidPath("an upload needs a file name".to_string())),
+        false => Ok(name),
+    }
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resumable_upload() {
+        let dir = tempdir().unwrap();
+        let store = UploadStore::new(dir.path(), 1024).unwrap();
+        let sha256 = hex::encode(Sha256::digest(b"hello world"));
+        let upload = store.create("../talk.mp4", Some(11), Some(&sha256)).unwrap();
+        assert_eq!(upload.file_name, "talk.mp4");
+
+        assert_eq!(store.append(&upload.id, 0, b"hello ").unwrap().offset, 6);
+        assert!(matches!(store.append(&upload.id, 0, b"hello "), Err(UploadError::OffsetMismatch { expected: 6 })));
+        let done = store.append(&upload.id, 6, b"world").unwrap();
+        assert!(done.complete);
+        assert_eq!(fs::read(&done.path).unwrap(), b"hello world");
+        assert!(done.path.starts_with(&dir.path().display().to_string()));
+    }
+
+    #[test]
+    fn test_limits_and_checksums() {
+        let dir = tempdir().unwrap();
+        let store = UploadStore::new(dir.path(), 8).unwrap();
+        assert!(matches!(store.create("big.mp4", Some(9), None), Err(UploadError::TooLarge { max_bytes: 8 })));
+
+        let open_ended = store.create("a.mp4", None, None).unwrap();
+        assert!(matches!(store.append(&open_ended.id, 0, b"123456789"), Err(UploadError::TooLarge { .. })));
+        assert!(store.get(&open_ended.id).is_none());
+
+        let wrong = store.create("b.mp4", None, Some(&"0".repeat(64))).unwrap();
+        store.append(&wrong.id, 0, b"1234").unwrap();
+        assert!(matches!(store.finish(&wrong.id, None), Err(UploadError::ChecksumMismatch { .. })));
+        assert!(!Path::new(&wrong.path).exists());
+
+        assert_eq!(
+            checksum_header_sha256("sha256 uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=").unwrap(),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert!(checksum_header_sha256("md5 abc").is_err());
+    }
+
+    #[test]
+    fn test_expired_uploads_are_removed() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("0123abcd.part"), b"left by an earlier run").unwrap();
+        let mut store = UploadStore::new(dir.path(), 1024).unwrap();
+        let kept = store.create("a.mp4", Some(3), None).unwrap();
+        store.append(&kept.id, 0, b"abc").unwrap();
+        store.sweep();
+        assert!(store.get(&kept.id).is_some());
+        assert!(dir.path().join("0123abcd.part").exists());
+
+        store.set_ttl(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(10));
+        store.sweep();
+        assert!(store.get(&kept.id).is_none());
+        assert!(!Path::new(&kept.path).exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::error::{VideoClipError, Result};
+use crate::probe::MediaInfo;
+use crate::ranges::TimeRange;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use std::str::FromStr;
+
+/// Wall-clock ranges
+/// CCTV and screen-recording users know when something happened, not how far
+/// into the file it is. A wall-clock range (`14:03:00` to `14:05:30`) is
+/// resolved against when the recording began: a given start, or the file's
+/// `creation_time` metadata, which is stored in UTC and converted to the
+/// local time zone. Times of day are placed on the recording's date, rolling
+/// over midnight when the recording does.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockTime {
+    /// `14:03:00`, `14:03` or `14:03:00.500`, on the recording's date
+    TimeOfDay(NaiveTime),
+    /// `2024-03-01 14:03:00` or `2024-03-01T14:03:00`
+    At(NaiveDateTime),
+}
+
+const TIME_FORMATS: [&str; 2] = ["%H:%M:%S%.f", "%H:%M"];
+const DATE_TIME_FORMATS: [&str; 4] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"];
+
+impl FromStr for ClockTime {
+    type Err = VideoClipError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(time) = TIME_FORMATS.iter().find_map(|f| NaiveTime::parse_from_str(s, f).ok()) {
+            return Ok(ClockTime::TimeOfDay(time));
+        }
+        DATE_TIME_FORMATS.iter()
+            .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+            .map(ClockTime::At)
+            .ok_or_else(|| VideoClipError::InvalidTimeFormat(s.to_string()))
+    }
+}
+
+impl ClockTime {
+    /// This time on or after `after`; a time of day takes `after`'s date, or
+    /// the next day's if it's earlier in the day than `after`
+    fn on_or_after(self, after: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            ClockTime::At(at) => at,
+            ClockTime::TimeOfDay(time) => {
+                let same_day = after.date().and_time(time);
+                if same_day < after { same_day + chrono::Duration::days(1) } else { same_day }
+            }
+        }
+    }
+}
+
+/// When a recording began: an RFC 3339 timestamp (`2024-03-01T14:00:00Z`,
+/// converted to local time) or a local date and time
+pub fn parse_recording_start(s: &str) -> Result<NaiveDateTime> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(s.trim()) {
+        return Ok(at.with_timezone(&Local).naive_local());
+    }
+    match s.parse()? {
+        ClockTime::At(at) => Ok(at),
+        // Today's recording, as far as a bare time of day can say
+        ClockTime::TimeOfDay(time) => Ok(Local::now().date_naive().and_time(time)),
+    }
+}
+
+/// The recording start from `info`'s `creation_time` tag (the container's,
+/// or else the first stream's that has one), in local time
+pub fn creation_time(info: &MediaInfo) -> Option<NaiveDateTime> {
+    let tag = info.tags.get("creation_time")
+        .or_else(|| info.streams.iter().find_map(|s| s.tags.get("creation_time")))?;
+    let at = DateTime::parse_from_rfc3339(tag).ok()?;
+    // Cameras without a clock write the epoch or their firmware date
+    (at.date_naive() > NaiveDate::from_ymd_opt(1970, 1, 2)?).then(|| at.with_timezone(&Local).naive_local())
+}
+
+/// Seconds into a recording that began at `recording_start` and, when known,
+/// lasts `duration` seconds; the end is placed after the start, so
+/// `23:58`–`00:02` spans midnight
+pub fn resolve_range(start: ClockTime, end: ClockTime, recording_start: NaiveDateTime, duration: Option<f64>) -> Result<TimeRange> {
+    let start_at = start.on_or_after(recording_start);
+    let end_at = end.on_or_after(start_at);
+    let seconds = |at: NaiveDateTime| (at - recording_start).num_milliseconds() as f64 / 1000.0;
+    if start_at < recording_start || duration.is_some_and(|d| seconds(start_at) >= d) {
+        let recording_end = duration.map(|d| recording_start + chrono::Duration::milliseconds((d * 1000.0) as i64));
+        return Err(VideoClipError::InvalidOptions(format!(
+            "{} isn't in the recording, which runs from {}{}",
+            start_at,
+            recording_start,
+            recording_end.map(|end| format!(" to {}", end)).unwrap_or_default()
+        )));
+    }
+    TimeRange::new(seconds(start_at), seconds(end_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_parse_clock_times() {
+        assert_eq!("14:03".parse::<ClockTime>().unwrap(), ClockTime::TimeOfDay(NaiveTime::from_hms_opt(14, 3, 0).unwrap()));
+        assert_eq!("14:03:00.5".parse::<ClockTime>().unwrap(), ClockTime::TimeOfDay(NaiveTime::from_hms_milli_opt(14, 3, 0, 500).unwrap()));
+        assert_eq!("2024-03-01T14:03:00".parse::<ClockTime>().unwrap(), ClockTime::At(at("2024-03-01 14:03:00")));
+        assert!("25:00".parse::<ClockTime>().is_err());
+        assert!("2239".parse::<ClockTime>().is_err());
+        assert_eq!(parse_recording_start("2024-03-01 13:58:20").unwrap(), at("2024-03-01 13:58:20"));
+    }
+
+    #[test]
+    fn test_resolve_against_recording_start() {
+        let start = at("2024-03-01 13:58:20");
+        let range = resolve_range("14:03:00".parse().unwrap(), "14:05:30".parse().unwrap(), start, Some(3600.0)).unwrap();
+        assert_eq!((range.start, range.end), (280.0, 430.0));
+
+        // Overnight recording; the range after midnight is on the next day
+        let night = at("2024-03-01 22:00:00");
+        let range = resolve_range("23:59:00".parse().unwrap(), "00:01:00".parse().unwrap(), night, None).unwrap();
+        assert_eq!((range.start, range.end), (7140.0, 7260.0));
+        let range = resolve_range("01:00".parse().unwrap(), "01:10".parse().unwrap(), night, Some(8.0 * 3600.0)).unwrap();
+        assert_eq!(range.start, 10800.0);
+
+        // Earlier that day, not the next morning, once the length is known
+        let before = resolve_range("13:50".parse().unwrap(), "13:55".parse().unwrap(), start, Some(3600.0)).unwrap_err();
+        assert!(before.to_string().contains("runs from 2024-03-01 13:58:20 to 2024-03-01 14:58:20"));
+        assert!(resolve_range("2024-03-01T13:00:00".parse().unwrap(), "14:00".parse().unwrap(), start, None).is_err());
+    }
+
+    #[test]
+    fn test_creation_time_from_metadata() {
+        let mut info = MediaInfo::default();
+        assert_eq!(creation_time(&info), None);
+        info.tags.insert("creation_time".to_string(), "1970-01-01T00:00:00.000000Z".to_string());
+        assert_eq!(creation_time(&info), None);
+
+        info.tags.insert("creation_time".to_string(), "2024-03-01T14:00:00.000000Z".to_string());
+        let expected = DateTime::parse_from_rfc3339("2024-03-01T14:00:00Z").unwrap().with_timezone(&Local).naive_local();
+        assert_eq!(creation_time(&info), Some(expected));
+    }
+}
@@ -1,7 +1,102 @@
 use wasm_bindgen::prelude::*;
-use serde_wasm_bindgen::{to_value, from_value};
-use crate::{VideoClipper, TimeParser};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use serde_wasm_bindgen::{from_value, Serializer};
+use crate::animated::{AnimatedCommand, AnimatedOptions};
+use crate::{Config, MediaInfo, VideoClipError, VideoClipper, TimeParser};
+use crate::ranges::TimeRange;
+use crate::streaming::{StreamPlan, StreamSource, StreamingOptions};
 use crate::video_clipper::ClipRequest;
+use crate::preview::{peaks_from_pcm_bytes, WaveformCommand};
+
+/// Structs cross into JS as plain objects (maps included, so `tags` is a
+/// `Record` rather than a `Map`) with camelCase field names, matching the
+/// interfaces at the end of this file. The library's types are snake_case
+/// like everywhere else (config files included); names are only converted
+/// here, at the boundary.
+fn to_value<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    let value = serde_json::to_value(value)
+        .map_err(|e| js_error(VideoClipError::WasmError(e.to_string())))?;
+    camel_case_keys(value).serialize(&Serializer::new().serialize_maps_as_objects(true))
+        .map_err(|e| js_error(VideoClipError::WasmError(e.to_string())))
+}
+
+fn from_js<T: DeserializeOwned>(value: JsValue) -> Result<T, JsValue> {
+    let value: Value = from_value(value).map_err(|e| js_error(VideoClipError::InvalidOptions(e.to_string())))?;
+    serde_json::from_value(snake_case_keys(value)).map_err(|e| js_error(VideoClipError::InvalidOptions(e.to_string())))
+}
+
+/// Keys of free-form maps, which are data rather than field names
+const DATA_KEYS: [&str; 1] = ["tags"];
+
+/// Keys of lists of field names (a JSON Schema's `required`, a request's
+/// `set_fields`), which are renamed along with the fields
+const NAME_LISTS: [&str; 2] = ["required", "set_fields"];
+
+fn rename_all(names: Vec<Value>, rename: fn(&str) -> String) -> Value {
+    Value::Array(names.into_iter().map(|name| match name {
+        Value::String(name) => Value::String(rename(&name)),
+        other => other,
+    }).collect())
+}
+
+/// `value` with its field names camelCase
+fn camel_case_keys(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(fields.into_iter().map(|(key, value)| {
+            let value = match (key.as_str(), value) {
+                (key, value) if DATA_KEYS.contains(&key) => value,
+                (key, Value::Array(names)) if NAME_LISTS.contains(&key) => rename_all(names, camel_case),
+                (_, value) => camel_case_keys(value),
+            };
+            (camel_case(&key), value)
+        }).collect()),
+        Value::Array(items) => Value::Array(items.into_iter().map(camel_case_keys).collect()),
+        other => other,
+    }
+}
+
+/// `value` with its field names snake_case; snake_case names pass through
+fn snake_case_keys(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(fields.into_iter().map(|(key, value)| {
+            let key = snake_case(&key);
+            let value = match (key.as_str(), value) {
+                (key, value) if DATA_KEYS.contains(&key) => value,
+                (key, Value::Array(names)) if NAME_LISTS.contains(&key) => rename_all(names, snake_case),
+                (_, value) => snake_case_keys(value),
+            };
+            (key, value)
+        }).collect()),
+        Value::Array(items) => Value::Array(items.into_iter().map(snake_case_keys).collect()),
+        other => other,
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    name.split('_').enumerate().map(|(i, word)| match (i, word.get(..1)) {
+        (0, _) | (_, None) => word.to_string(),
+        (_, Some(first)) => first.to_uppercase() + &word[1..],
+    }).collect()
+}
+
+fn snake_case(name: &str) -> String {
+    name.chars().fold(String::new(), |mut snake, c| {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+        snake
+    })
+}
+
+/// Errors are thrown as `{ code, message, context }` objects (`VideoClipError`
+/// below) so callers can branch on `code` and localize from `context`
+fn js_error(e: VideoClipError) -> JsValue {
+    e.serialize(&Serializer::new().serialize_maps_as_objects(true))
+        .unwrap_or_else(|_| JsValue::from_str(&e.to_string()))
+}
 
 #[wasm_bindgen]
 pub fn init_wasm() {
@@ -21,7 +116,7 @@ pub fn init_wasm() {
 #[wasm_bindgen]
 pub fn parse_time_to_seconds(time_str: &str) -> Result<f64, JsValue> {
     TimeParser::parse_to_seconds(time_str)
-        .map_err(|e| JsValue::from_str(&e.to_string()))
+        .map_err(js_error)
 }
 
 #[wasm_bindgen]
@@ -37,7 +132,32 @@ pub fn format_time_readable(seconds: f64) -> String {
 #[wasm_bindgen]
 pub fn validate_time_range(start_seconds: f64, end_seconds: f64) -> Result<f64, JsValue> {
     TimeParser::validate_time_range(start_seconds, end_seconds)
-        .map_err(|e| JsValue::from_str(&e.to_string()))
+        .map_err(js_error)
+}
+
+/// Parses and checks a range in one go
+#[wasm_bindgen(unchecked_return_type = "TimeRange")]
+pub fn parse_time_range(start_time: &str, end_time: &str) -> Result<JsValue, JsValue> {
+    let range = TimeRange::parse(start_time, end_time)
+        .map_err(js_error)?;
+    
+    to_value(&range)
+}
+
+/// Command whose log `parse_probe_log` reads; ffmpeg.wasm ships without
+/// ffprobe, and FFmpeg exits with an error here after describing the input
+#[wasm_bindgen]
+pub fn generate_probe_command(input_file: &str) -> String {
+    format!("ffmpeg -hide_banner -i {}", input_file)
+}
+
+/// Probe-lite: `MediaInfo` from the log of `generate_probe_command`
+#[wasm_bindgen(unchecked_return_type = "MediaInfo")]
+pub fn parse_probe_log(log: &str) -> Result<JsValue, JsValue> {
+    let info = MediaInfo::from_ffmpeg_log(log)
+        .map_err(js_error)?;
+    
+    to_value(&info)
 }
 
 #[wasm_bindgen]
@@ -48,11 +168,11 @@ pub fn generate_ffmpeg_command(
     end_time: &str,
 ) -> Result<String, JsValue> {
     let start_sec = TimeParser::parse_to_seconds(start_time)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        .map_err(js_error)?;
     let end_sec = TimeParser::parse_to_seconds(end_time)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        .map_err(js_error)?;
     let duration = TimeParser::validate_time_range(start_sec, end_sec)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        .map_err(js_error)?;
     
     let command = format!(
         "ffmpeg -i {} -ss {} -t {} -c copy -avoid_negative_ts make_zero -y {}",
@@ -62,22 +182,82 @@ pub fn generate_ffmpeg_command(
     Ok(command)
 }
 
+/// Command that decodes a range to mono s16le PCM in ffmpeg.wasm's FS;
+/// feed the resulting file to `compute_waveform_peaks`
 #[wasm_bindgen]
-pub fn prepare_clip(request_js: JsValue) -> Result<JsValue, JsValue> {
-    let request: ClipRequest = from_value(request_js)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+pub fn generate_waveform_command(
+    input_file: &str,
+    output_file: &str,
+    start_time: &str,
+    end_time: &str,
+    sample_rate: u32,
+) -> Result<String, JsValue> {
+    let start_sec = TimeParser::parse_to_seconds(start_time)
+        .map_err(js_error)?;
+    let end_sec = TimeParser::parse_to_seconds(end_time)
+        .map_err(js_error)?;
+    let duration = TimeParser::validate_time_range(start_sec, end_sec)
+        .map_err(js_error)?;
+    
+    Ok(WaveformCommand::new(input_file, start_sec, duration, sample_rate).get_command_string(output_file))
+}
+
+/// Command rendering a range as an animated GIF/WebP/AVIF (`AnimatedOptions`)
+/// in ffmpeg.wasm's FS; the build must include the format's encoder
+#[wasm_bindgen]
+pub fn generate_animated_command(
+    input_file: &str,
+    output_file: &str,
+    start_time: &str,
+    end_time: &str,
+    #[wasm_bindgen(unchecked_param_type = "AnimatedOptions")] options_js: JsValue,
+) -> Result<String, JsValue> {
+    let start_sec = TimeParser::parse_to_seconds(start_time)
+        .map_err(js_error)?;
+    let end_sec = TimeParser::parse_to_seconds(end_time)
+        .map_err(js_error)?;
+    let duration = TimeParser::validate_time_range(start_sec, end_sec)
+        .map_err(js_error)?;
+    let options: AnimatedOptions = from_js(options_js)?;
+    options.validate()
+        .map_err(js_error)?;
     
-    let clipper = VideoClipper::new();
-    let result = clipper.prepare_clip_command(&request)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(AnimatedCommand::new(input_file, output_file, start_sec, duration, options).get_command_string())
+}
+
+/// Reduces s16le PCM bytes to normalized peaks (returned as a Float32Array)
+#[wasm_bindgen]
+pub fn compute_waveform_peaks(pcm: &[u8], peak_count: usize) -> Vec<f32> {
+    peaks_from_pcm_bytes(pcm, peak_count)
+}
+
+#[wasm_bindgen(unchecked_return_type = "ClipResult")]
+pub fn prepare_clip(#[wasm_bindgen(unchecked_param_type = "ClipRequest")] request_js: JsValue) -> Result<JsValue, JsValue> {
+    WasmVideoClipper::new().prepare_clip_command(request_js)
+}
+
+/// Everything wrong with a request, each tied to its field; empty when it's valid
+#[wasm_bindgen(unchecked_return_type = "RequestProblem[]")]
+pub fn validate_request(#[wasm_bindgen(unchecked_param_type = "ClipRequest")] request_js: JsValue) -> Result<JsValue, JsValue> {
+    let request: ClipRequest = from_js(request_js)?;
+    let mut problems = request.validate().err().unwrap_or_default();
+    for problem in &mut problems {
+        problem.field = camel_case(&problem.field);
+    }
     
-    to_value(&result)
-        .map_err(|e| JsValue::from_str(&e.to_string()))
+    to_value(&problems)
+}
+
+/// JSON Schema of `ClipRequest`, for validating forms before they're sent
+#[wasm_bindgen]
+pub fn clip_request_schema() -> Result<JsValue, JsValue> {
+    to_value(&ClipRequest::json_schema())
 }
 
 #[wasm_bindgen]
 pub struct WasmVideoClipper {
     clipper: VideoClipper,
+    strict: bool,
 }
 
 #[wasm_bindgen]
@@ -87,6 +267,7 @@ impl WasmVideoClipper {
         init_wasm();
         Self {
             clipper: VideoClipper::new(),
+            strict: false,
         }
     }
     
@@ -95,52 +276,536 @@ impl WasmVideoClipper {
         init_wasm();
         Self {
             clipper: VideoClipper::with_output_dir(output_dir),
+            strict: false,
         }
     }
     
+    /// Strict mode: requests with fields the library doesn't know are
+    /// rejected with their names instead of the fields being ignored
     #[wasm_bindgen]
-    pub fn prepare_clip_command(&self, request_js: JsValue) -> Result<JsValue, JsValue> {
-        let request: ClipRequest = from_value(request_js)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+    
+    /// Presets from a `config.toml`; requests name them in `presetName`
+    #[wasm_bindgen]
+    pub fn set_config_toml(&mut self, toml: &str) -> Result<(), JsValue> {
+        let config = Config::from_toml(toml)
+            .map_err(js_error)?;
+        self.clipper.set_config(config);
+        Ok(())
+    }
+    
+    #[wasm_bindgen]
+    pub fn preset_names(&self) -> Vec<String> {
+        self.clipper.config().preset_names().into_iter().map(str::to_string).collect()
+    }
+    
+    /// The request with its preset's options filled in
+    #[wasm_bindgen(unchecked_return_type = "ClipRequest")]
+    pub fn apply_preset(&self, #[wasm_bindgen(unchecked_param_type = "ClipRequest")] request_js: JsValue) -> Result<JsValue, JsValue> {
+        let request = self.resolve(request_js)?;
+        to_value(&request)
+    }
+    
+    #[wasm_bindgen(unchecked_return_type = "ClipResult")]
+    pub fn prepare_clip_command(&self, #[wasm_bindgen(unchecked_param_type = "ClipRequest")] request_js: JsValue) -> Result<JsValue, JsValue> {
+        let request = self.resolve(request_js)?;
         
         let result = self.clipper.prepare_clip_command(&request)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            .map_err(js_error)?;
         
         to_value(&result)
-            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    
+    /// Commands for every clip of a batch, splits expanded; requests that
+    /// can't be planned carry an `error` instead of a `result`
+    #[wasm_bindgen(unchecked_return_type = "BatchReport")]
+    pub fn prepare_batch(&self, #[wasm_bindgen(unchecked_param_type = "ClipRequest[]")] requests_js: JsValue) -> Result<JsValue, JsValue> {
+        let requests: Vec<ClipRequest> = if self.strict {
+            let values: Vec<serde_json::Value> = from_js(requests_js)?;
+            values.into_iter()
+                .map(ClipRequest::from_value_strict)
+                .collect::<crate::Result<_>>()
+                .map_err(js_error)?
+        } else {
+            from_js(requests_js)?
+        };
+        
+        to_value(&self.clipper.prepare_batch(&requests))
+    }
+    
+    /// File name the request's clip gets: its `outputName`, or one made from the range
+    #[wasm_bindgen]
+    pub fn clip_file_name(&self, #[wasm_bindgen(unchecked_param_type = "ClipRequest")] request_js: JsValue) -> Result<String, JsValue> {
+        let request = self.resolve(request_js)?;
+        let range = request.time_range()
+            .map_err(js_error)?;
+        
+        let path = std::path::Path::new(&request.input_file);
+        Ok(self.clipper.clip_file_name(&request, path, range.start, range.end))
     }
     
     #[wasm_bindgen]
     pub fn generate_output_filename(&self, input_file: &str, start_time: &str, end_time: &str) -> Result<String, JsValue> {
         let start_sec = TimeParser::parse_to_seconds(start_time)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            .map_err(js_error)?;
         let end_sec = TimeParser::parse_to_seconds(end_time)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            .map_err(js_error)?;
         
         let path = std::path::Path::new(input_file);
         let output = self.clipper.generate_output_filename(path, start_sec, end_sec);
         
         Ok(output.display().to_string())
     }
+    
+    /// Steps for clipping a file too big for the FS from byte-range slices:
+    /// for each step in order, write the slice (`file.slice(offset, offset +
+    /// length)`), run FFmpeg with the args, or delete the file, then read
+    /// `outputFile`. Stream copy only, from MPEG-TS/PS sources.
+    #[wasm_bindgen(unchecked_return_type = "StreamPlan")]
+    pub fn plan_streaming_clip(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "StreamSource")] source_js: JsValue,
+        #[wasm_bindgen(unchecked_param_type = "ClipRequest")] request_js: JsValue,
+        #[wasm_bindgen(unchecked_param_type = "StreamingOptions | undefined")] options_js: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let source: StreamSource = from_js(source_js)?;
+        let options: StreamingOptions = if options_js.is_undefined() || options_js.is_null() {
+            StreamingOptions::default()
+        } else {
+            from_js(options_js)?
+        };
+        let request = self.resolve(request_js)?;
+        let range = request.time_range()
+            .map_err(js_error)?;
+        
+        let output_file = self.clipper.clip_file_name(&request, std::path::Path::new(&source.name), range.start, range.end);
+        let plan = StreamPlan::new(&source, &request, &output_file, &options)
+            .map_err(js_error)?;
+        
+        to_value(&plan)
+    }
+    
+    fn resolve(&self, request_js: JsValue) -> Result<ClipRequest, JsValue> {
+        let request: ClipRequest = if self.strict {
+            let value: serde_json::Value = from_js(request_js)?;
+            ClipRequest::from_value_strict(value)
+                .map_err(js_error)?
+        } else {
+            from_js(request_js)?
+        };
+        
+        self.clipper.config().apply_preset(&request)
+            .and_then(ClipRequest::resolve_wall_clock)
+            .map_err(js_error)
+    }
 }
 
 // Re-export for JavaScript
 #[wasm_bindgen(typescript_custom_section)]
 const TS_APPEND_CONTENT: &'static str = r#"
+export type ErrorCode =
+    | "invalid_time_format"
+    | "invalid_time_range"
+    | "file_not_found"
+    | "ffmpeg_not_found"
+    | "ffmpeg_failed"
+    | "probe_failed"
+    | "io_error"
+    | "not_a_video_file"
+    | "corrupt_source"
+    | "transcription_failed"
+    | "invalid_path"
+    | "invalid_options"
+    | "unsupported_platform"
+    | "unsupported_by_ffmpeg_build"
+    | "invalid_capture_region"
+    | "cancelled"
+    | "wasm_error";
+
+/** What every function throws */
+export interface VideoClipError {
+    code: ErrorCode;
+    message: string;
+    context: Record<string, unknown>;
+}
+
+export type VideoCodec = "copy" | "h264" | "hevc" | "av1" | "prores" | "vp9";
+
+/** Reverse and boomerang re-encode, and take ranges of up to 15s */
+export type Playback = "forward" | "reverse" | "boomerang";
+
+/** At the start and content edges, or every N seconds; needs a videoCodec */
+export type ForcedKeyframes = "start" | { every: number };
+
 export interface ClipRequest {
-    input_file: string;
-    start_time: string;
-    end_time: string;
-    output_dir?: string;
+    inputFile: string;
+    startTime: string;
+    endTime: string;
+    /** startTime/endTime are times of day on the recording's clock */
+    wallClock?: boolean;
+    /** Required with wallClock, as the file can't be probed for its creation time */
+    recordingStart?: string;
+    outputDir?: string;
+    videoCodec?: VideoCodec;
+    encoder?: string;
+    /** "mp4", "mov" or "mkv"; follows videoCodec when unset */
+    container?: string;
+    forceKeyframes?: ForcedKeyframes;
+    deinterlace?: boolean;
+    targetFps?: number;
+    constantFrameRate?: boolean;
+    tonemap?: boolean;
+    fit?: FitOptions;
+    volumeDb?: number;
+    overlayAudio?: OverlayAudio;
+    /** Not joined in the browser */
+    intro?: string;
+    outro?: string;
+    endCard?: EndCard;
+    attribution?: Attribution;
+    redact?: RedactRegion[];
+    redactAudio?: AudioRedaction[];
+    /** e.g. ["all"], ["1", "audio:eng"]; video and audio when unset */
+    streams?: string[];
+    tracks?: TrackSettings[];
+    playback?: Playback;
+    loopCount?: number;
+    autoTrimBlack?: boolean;
+    verifyCut?: boolean;
+    qualityGate?: QualityGate;
+    smartCut?: boolean;
+    recover?: boolean;
+    mirrorRoot?: string;
+    priority?: "low" | "normal" | "high";
+    chunking?: ChunkOptions;
+    presetName?: string;
+    renditions?: Rendition[];
+    proxy?: ProxyOptions;
+    outputName?: string;
+    split?: SplitOptions;
+    preRoll?: number;
+    postRoll?: number;
+    subtitles?: string;
+    captions?: boolean;
+    metadata?: ClipMetadata;
+    /** Not verified in the browser */
+    stripMetadata?: boolean;
+    setFields?: string[];
+    schemaVersion?: number;
+}
+
+export interface RequestProblem {
+    field: string;
+    message: string;
+}
+
+export interface ClipMetadata {
+    title?: string;
+    description?: string;
+    tags?: string[];
+}
+
+export interface Rendition {
+    name: string;
+    videoCodec?: VideoCodec;
+    height?: number;
+    audioOnly?: boolean;
+    format?: "mp4" | "mov" | "mkv" | "mp3" | "m4a" | "wav";
+    draft?: boolean;
+    /** Capped video bit rate instead of constant quality */
+    bitrateKbps?: number;
+}
+
+export interface SplitOptions {
+    maxSeconds: number;
+    overlapSeconds?: number;
+    naming?: "range" | "numbered";
+}
+
+export interface ProxyOptions {
+    height?: number;
+}
+
+export interface ChunkOptions {
+    minutes: number;
+    parallel?: number;
+}
+
+export interface OverlayAudio {
+    path: string;
+    volumeDb?: number;
+    duck?: boolean;
+    loopToFit?: boolean;
+    /** Seconds */
+    fadeOut?: number;
+}
+
+export interface EndCard {
+    image: string;
+    /** Default 5 */
+    seconds?: number;
+    music?: string;
+}
+
+export type Corner = "top-left" | "top-right" | "bottom-left" | "bottom-right";
+
+export interface Attribution {
+    /** URL or text encoded in the QR code */
+    text: string;
+    corner?: Corner;
+    /** Pixels; default 120 */
+    size?: number;
+    /** Seconds into the clip */
+    start?: number;
+    end?: number;
+}
+
+export type RedactStyle = "blur" | "pixelate" | "box";
+
+/** A rectangle of the source frame, in pixels */
+export interface RedactRegion {
+    x: number;
+    y: number;
+    width: number;
+    height: number;
+    style?: RedactStyle;
+    /** Seconds into the clip */
+    start?: number;
+    end?: number;
+}
+
+/** Seconds into the clip */
+export interface AudioRedaction {
+    start: number;
+    end: number;
+    style?: "mute" | "bleep";
+}
+
+export interface TrackSettings {
+    kind: "video" | "audio" | "subtitle";
+    /** Which track of that kind in the clip, from 0 */
+    index: number;
+    default?: boolean;
+    forced?: boolean;
+    title?: string;
+    /** ISO 639-2, e.g. "eng" */
+    language?: string;
+}
+
+export interface QualityGate {
+    /** Minimum VMAF score, 0-100 (default 90) */
+    minVmaf?: number;
+    action?: "fail" | "warn";
+    /** Higher-quality re-encodes tried before the action is taken (default 2) */
+    retries?: number;
+}
+
+export interface FitOptions {
+    width: number;
+    height: number;
+    mode?: "pad" | "crop" | "stretch";
+    background?: { color: string } | "blur";
+}
+
+export interface TimeRange {
+    start: number;
+    end: number;
+}
+
+export interface AnimatedOptions {
+    format?: "gif" | "webp" | "avif";
+    fps?: number;
+    width?: number;
+    quality?: number;
+    loopCount?: number;
+}
+
+export interface AnimatedResult {
+    inputFile: string;
+    outputFile: string;
+    format: "gif" | "webp" | "avif";
+    encoder: string;
+    startSeconds: number;
+    endSeconds: number;
+    fileSizeMb?: number;
+    command: string;
+}
+
+export interface PreviewOptions {
+    peakCount?: number;
+    sampleRate?: number;
+    thumbnailCount?: number;
+    thumbnailWidth?: number;
+}
+
+export interface WaveformData {
+    startSeconds: number;
+    duration: number;
+    secondsPerPeak: number;
+    peaks: number[];
+}
+
+export interface Thumbnail {
+    time: number;
+    path: string;
+}
+
+export interface PreviewData {
+    inputFile: string;
+    waveform?: WaveformData;
+    thumbnails: Thumbnail[];
+}
+
+export interface MediaInfo {
+    formatName: string;
+    startTime?: number;
+    duration?: number;
+    sizeBytes?: number;
+    bitRate?: number;
+    streams: StreamInfo[];
+    tags: Record<string, string>;
+}
+
+export interface StreamInfo {
+    index: number;
+    codecType: "video" | "audio" | "subtitle" | "data" | "attachment";
+    codecName?: string;
+    width?: number;
+    height?: number;
+    pixFmt?: string;
+    colorTransfer?: string;
+    colorPrimaries?: string;
+    frameRate?: number;
+    avgFrameRate?: number;
+    bitRate?: number;
+    sampleRate?: number;
+    channels?: number;
+    duration?: number;
+    tags: Record<string, string>;
 }
 
 export interface ClipResult {
-    input_file: string;
-    output_file: string;
-    start_seconds: number;
-    end_seconds: number;
+    inputFile: string;
+    outputFile: string;
+    startSeconds: number;
+    endSeconds: number;
     duration: number;
-    file_size_mb?: number;
+    fileSizeMb?: number;
     command: string;
+    encoder: string;
+    warnings: string[];
+    blackTrim?: BlackTrim;
+    cutReport?: CutReport;
+    renditions?: RenditionResult[];
+    recovery?: RecoveryReport;
+    scrub?: ScrubReport;
+    quality?: QualityCheck;
+}
+
+export interface ScrubReport {
+    removed: string[];
+    remaining: string[];
+}
+
+export interface QualityCheck {
+    vmaf: number;
+    minVmaf: number;
+    attempts: number;
+    passed: boolean;
+}
+
+export type SourceIssue =
+    | { kind: "missing_moov" }
+    | { kind: "truncated"; expectedBytes: number; actualBytes: number }
+    | { kind: "unreadable"; details: string };
+
+export interface RecoveryReport {
+    issues: SourceIssue[];
+    requestedSeconds: number;
+    recoveredSeconds?: number;
+}
+
+export interface StreamSource {
+    name: string;
+    sizeBytes: number;
+    duration: number;
+    startTime?: number;
+}
+
+export interface StreamingOptions {
+    maxSliceMb?: number;
+    marginSeconds?: number;
+}
+
+export type StreamStep =
+    | { kind: "write_slice"; file: string; offset: number; length: number }
+    | { kind: "run"; args: string[] }
+    | { kind: "delete"; file: string };
+
+export interface StreamPlan {
+    outputFile: string;
+    steps: StreamStep[];
+    slices: number;
+    peakSliceBytes: number;
+}
+
+export interface BatchItemResult {
+    inputFile: string;
+    result?: ClipResult;
+    error?: string;
+    skipped: boolean;
+    worker?: string;
+}
+
+export interface BatchReport {
+    items: BatchItemResult[];
+}
+
+export interface RenditionResult {
+    name: string;
+    outputFile: string;
+    fileSizeMb?: number;
+    encoder: string;
+}
+
+export interface CutReport {
+    requestedStart: number;
+    requestedEnd: number;
+    actualStart: number;
+    actualEnd: number;
+    startDelta: number;
+    endDelta: number;
+}
+
+export interface BlackTrim {
+    requestedStart: number;
+    requestedEnd: number;
+    start: number;
+    end: number;
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_field_names_convert_at_the_boundary() {
+        let result = json!({ "input_file": "a.mp4", "cut_report": { "start_delta": 0.1 }, "tags": { "creation_time": "x" } });
+        assert_eq!(
+            camel_case_keys(result),
+            json!({ "inputFile": "a.mp4", "cutReport": { "startDelta": 0.1 }, "tags": { "creation_time": "x" } })
+        );
+        let schema = json!({ "required": ["input_file", "start_time"], "properties": { "pre_roll": { "type": "number" } } });
+        assert_eq!(camel_case_keys(schema), json!({ "required": ["inputFile", "startTime"], "properties": { "preRoll": { "type": "number" } } }));
+
+        let request = json!({ "inputFile": "a.mp4", "end_time": "5", "overlayAudio": { "loopToFit": true } });
+        assert_eq!(snake_case_keys(request), json!({ "input_file": "a.mp4", "end_time": "5", "overlay_audio": { "loop_to_fit": true } }));
+        let request = json!({ "videoCodec": "copy", "setFields": ["videoCodec", "pre_roll"] });
+        assert_eq!(snake_case_keys(request), json!({ "video_codec": "copy", "set_fields": ["video_codec", "pre_roll"] }));
+        assert_eq!(snake_case(&camel_case("file_size_mb")), "file_size_mb");
+    }
 }
-"#;
\ No newline at end of file
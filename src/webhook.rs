@@ -0,0 +1,150 @@
+use crate::error::{VideoClipError, Result};
+use crate::hooks::HookEvent;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Webhook notifications
+/// POSTs each hook event as JSON so other services can react to finished or
+/// failed clips without polling. Each delivery carries its send time as
+/// `X-Video-Clip-Timestamp` (Unix seconds). With a secret, `<timestamp>.<body>`
+/// is signed with HMAC-SHA256 and sent as `X-Video-Clip-Signature:
+/// sha256=<hex>`, so receivers can refuse stale deliveries replayed later.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    /// Shared secret for the signature header; unsigned when unset
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+pub const SIGNATURE_HEADER: &str = "X-Video-Clip-Signature";
+pub const EVENT_HEADER: &str = "X-Video-Clip-Event";
+pub const TIMESTAMP_HEADER: &str = "X-Video-Clip-Timestamp";
+
+fn default_timeout_seconds() -> u64 {
+    10
+}
+
+/// `sha256=<hex>` HMAC of `<timestamp>.<body>`, as sent in
+/// [`SIGNATURE_HEADER`] alongside `timestamp` in [`TIMESTAMP_HEADER`]
+pub fn signature(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+impl Webhook {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            secret: None,
+            timeout_seconds: default_timeout_seconds(),
+        }
+    }
+
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.url.starts_with("http://") || self.url.starts_with("https://")) {
+            return Err(VideoClipError::InvalidOptions(format!(
+                "webhook URL must start with http:// or https://: {}",
+                self.url
+            )));
+        }
+        if self.timeout_seconds == 0 {
+            return Err(VideoClipError::InvalidOptions("webhook timeout must be positive".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn send(&self, event: &HookEvent) -> std::result::Result<(), String> {
+        let body = serde_json::to_vec(event).map_err(|e| e.to_string())?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let mut request = ureq::post(&self.url)
+            .timeout(Duration::from_secs(self.timeout_seconds))
+            .set("Content-Type", "application/json")
+            .set(EVENT_HEADER, event.name())
+            .set(TIMESTAMP_HEADER, &timestamp.to_string());
+        if let Some(secret) = &self.secret {
+            request = request.set(SIGNATURE_HEADER, &signature(secret, timestamp, &body));
+        }
+
+        request.send_bytes(&body)
+            .map(|_| ())
+            .map_err(|e| format!("webhook {} failed: {}", self.url, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video_clipper::ClipRequest;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_signature_covers_the_timestamp() {
+        // RFC 4231 test case 2's key and data, prefixed with the timestamp
+        assert_eq!(
+            signature("Jefe", 1700000000, b"what do ya want for nothing?"),
+            "sha256=1cdd0650c8be1cb0974b1788d458b1e781206cfef59b85faafc582d2e182c57e"
+        );
+        assert_ne!(signature("Jefe", 1700000001, b"what do ya want for nothing?"), signature("Jefe", 1700000000, b"what do ya want for nothing?"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Webhook::new("https://hooks.example.com/clips").validate().is_ok());
+        assert!(Webhook::new("hooks.example.com/clips").validate().is_err());
+        let webhook = Webhook { timeout_seconds: 0, ..Webhook::new("http://localhost") };
+        assert!(webhook.validate().is_err());
+    }
+
+    #[test]
+    fn test_send_posts_signed_event() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                headers.push(line.trim().to_lowercase());
+            }
+            let length: usize = headers.iter()
+                .find_map(|h| h.strip_prefix("content-length: ").map(|v| v.parse().unwrap()))
+                .unwrap();
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            stream.write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").unwrap();
+            (headers, body)
+        });
+
+        let event = HookEvent::Failure { request: ClipRequest::default(), error: "boom".to_string() };
+        Webhook::new(url).with_secret("s3cret").send(&event).unwrap();
+
+        let (headers, body) = server.join().unwrap();
+        let timestamp: u64 = headers.iter()
+            .find_map(|h| h.strip_prefix("x-video-clip-timestamp: ").map(|v| v.parse().unwrap()))
+            .unwrap();
+        let expected = format!("{}: {}", SIGNATURE_HEADER.to_lowercase(), signature("s3cret", timestamp, &body));
+        assert!(headers.contains(&expected));
+        assert!(headers.contains(&"x-video-clip-event: failure".to_string()));
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["error"], "boom");
+    }
+}
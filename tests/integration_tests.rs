@@ -75,7 +75,6 @@ mod time_parsing_tests {
 mod video_clipper_tests {
     use super::*;
     use tempfile::tempdir;
-    use std::fs::File;
 
     #[test]
     fn test_clipper_creation() {
@@ -131,6 +130,7 @@ mod video_clipper_tests {
             start_time: "30s".to_string(),
             end_time: "2m".to_string(),
             output_dir: None,
+            ..Default::default()
         };
 
         let result = clipper.prepare_clip_command(&request);
@@ -152,6 +152,7 @@ mod video_clipper_tests {
             start_time: "2m".to_string(),
             end_time: "1m".to_string(), // End before start
             output_dir: None,
+            ..Default::default()
         };
 
         let result = clipper.prepare_clip_command(&request);
@@ -174,6 +175,7 @@ mod serialization_tests {
             start_time: "1m30s".to_string(),
             end_time: "3m45s".to_string(),
             output_dir: Some("/tmp/clips".to_string()),
+            ..Default::default()
         };
 
         // Test JSON serialization
@@ -196,6 +198,14 @@ mod serialization_tests {
             duration: 135.0,
             file_size_mb: Some(12.5),
             command: "ffmpeg -i input.mp4 -ss 90 -t 135 -c copy output.mp4".to_string(),
+            encoder: "copy".to_string(),
+            warnings: Vec::new(),
+            black_trim: None,
+            cut_report: None,
+            renditions: Vec::new(),
+            recovery: None,
+            scrub: None,
+            quality: None,
         };
 
         let json = serde_json::to_string(&result).unwrap();
@@ -311,7 +321,7 @@ mod edge_case_tests {
         
         // Test very small durations
         assert_eq!(TimeParser::validate_time_range(0.0, 0.1).unwrap(), 0.1);
-        assert_eq!(TimeParser::validate_time_range(100.0, 100.001).unwrap(), 0.001);
+        assert!((TimeParser::validate_time_range(100.0, 100.001).unwrap() - 0.001).abs() < 1e-9);
     }
 
     #[test]